@@ -1,4 +1,4 @@
-use ck_chunk::{ChunkConfig, chunk_text, chunk_text_with_config};
+use ck_chunk::{ChunkConfig, ChunkStrategy, chunk_text, chunk_text_with_config};
 use ck_core::Language;
 
 fn estimate_tokens(text: &str) -> usize {
@@ -68,6 +68,8 @@ fn test_striding() {
         max_tokens: 200,    // Very small limit to trigger striding
         stride_overlap: 50, // 25% overlap
         enable_striding: true,
+        chunk_overlap_lines: 0,
+        strategy: ChunkStrategy::SyntaxAware,
     };
 
     let code = std::fs::read_to_string("examples/code/large_function.py")
@@ -218,6 +220,8 @@ def very_large_function():
         max_tokens: 8192,     // Nomic model's actual limit
         stride_overlap: 1024, // 12.5% overlap
         enable_striding: true,
+        chunk_overlap_lines: 0,
+        strategy: ChunkStrategy::SyntaxAware,
     };
 
     let strided_chunks = chunk_text_with_config(large_code, Some(Language::Python), &config)
@@ -0,0 +1,221 @@
+//! Chunkers for file types ck has no parser for, registered by extension.
+//!
+//! A library user with an in-house language registers a [`Chunker`] for its
+//! extensions once at startup; [`chunk_file_content`](crate::chunk_file_content)
+//! then uses it for those files in place of the generic line windows. Chunks
+//! it returns are strided and overlapped like a built-in parser's.
+//!
+//! ```
+//! use ck_chunk::{Chunker, CustomChunk, register_chunker};
+//! use ck_core::Span;
+//!
+//! /// One chunk per top-level `rule NAME { ... }` block.
+//! struct RuleChunker;
+//!
+//! impl Chunker for RuleChunker {
+//!     fn chunk(&self, text: &str) -> anyhow::Result<Vec<CustomChunk>> {
+//!         let mut chunks = Vec::new();
+//!         let mut start: Option<(usize, usize, String)> = None;
+//!         let mut offset = 0;
+//!         for (index, line) in text.split_inclusive('\n').enumerate() {
+//!             if let Some(name) = line.strip_prefix("rule ") {
+//!                 let name = name.split_whitespace().next().unwrap_or_default();
+//!                 start = Some((offset, index + 1, name.to_string()));
+//!             }
+//!             offset += line.len();
+//!             if line.trim_end() == "}"
+//!                 && let Some((byte_start, line_start, name)) = start.take()
+//!             {
+//!                 chunks.push(CustomChunk {
+//!                     span: Span::new(byte_start, offset, line_start, index + 1)?,
+//!                     symbol: Some(name),
+//!                 });
+//!             }
+//!         }
+//!         Ok(chunks)
+//!     }
+//! }
+//!
+//! register_chunker(&["rules"], RuleChunker);
+//!
+//! let text = "rule allow_admins {\n  role == admin\n}\nrule deny_all {\n  false\n}\n";
+//! let chunks = ck_chunk::chunk_file_content(
+//!     std::path::Path::new("policy.rules"),
+//!     text,
+//!     None,
+//!     None,
+//!     0,
+//!     ck_chunk::ChunkStrategy::SyntaxAware,
+//! )?;
+//! assert_eq!(chunks.len(), 2);
+//! assert_eq!(chunks[1].metadata.breadcrumb.as_deref(), Some("deny_all"));
+//! # Ok::<(), anyhow::Error>(())
+//! ```
+
+use anyhow::{Result, bail};
+use ck_core::Span;
+use std::collections::HashMap;
+use std::path::Path;
+use std::sync::{Arc, OnceLock, RwLock};
+
+use crate::{Chunk, ChunkMetadata, ChunkType};
+
+/// Splits the content of a file type into chunks.
+pub trait Chunker: Send + Sync {
+    /// Chunk `text`, the whole content of one file. Spans are byte offsets
+    /// into `text` with 1-based line numbers; text between chunks is not
+    /// indexed.
+    fn chunk(&self, text: &str) -> Result<Vec<CustomChunk>>;
+}
+
+/// A chunk produced by a [`Chunker`].
+#[derive(Debug, Clone)]
+pub struct CustomChunk {
+    pub span: Span,
+    /// Name of what the chunk defines, shown as the result's breadcrumb.
+    /// Chunks with a symbol are indexed as functions, the rest as text.
+    pub symbol: Option<String>,
+}
+
+type Registry = RwLock<HashMap<String, Arc<dyn Chunker>>>;
+
+fn registry() -> &'static Registry {
+    static CHUNKERS: OnceLock<Registry> = OnceLock::new();
+    CHUNKERS.get_or_init(Default::default)
+}
+
+/// Use `chunker` for files with any of `extensions` (without the dot,
+/// matched case-insensitively), replacing whatever was registered for them.
+pub fn register_chunker(extensions: &[&str], chunker: impl Chunker + 'static) {
+    let chunker: Arc<dyn Chunker> = Arc::new(chunker);
+    let mut chunkers = registry().write().unwrap_or_else(|e| e.into_inner());
+    for extension in extensions {
+        chunkers.insert(
+            extension.trim_start_matches('.').to_ascii_lowercase(),
+            Arc::clone(&chunker),
+        );
+    }
+}
+
+/// Remove the chunker registered for `extension`, returning whether there
+/// was one.
+pub fn unregister_chunker(extension: &str) -> bool {
+    registry()
+        .write()
+        .unwrap_or_else(|e| e.into_inner())
+        .remove(&extension.trim_start_matches('.').to_ascii_lowercase())
+        .is_some()
+}
+
+/// The chunker registered for `path`'s extension, if any.
+pub(crate) fn chunker_for(path: &Path) -> Option<Arc<dyn Chunker>> {
+    let extension = path.extension()?.to_str()?.to_ascii_lowercase();
+    registry()
+        .read()
+        .unwrap_or_else(|e| e.into_inner())
+        .get(&extension)
+        .cloned()
+}
+
+/// Run `chunker` on `text` and turn its spans into [`Chunk`]s, rejecting
+/// spans that fall outside `text` or split a character.
+pub(crate) fn run_chunker(chunker: &dyn Chunker, text: &str) -> Result<Vec<Chunk>> {
+    chunker
+        .chunk(text)?
+        .into_iter()
+        .map(|custom| {
+            let span = custom.span;
+            let Some(chunk_text) = text.get(span.byte_start..span.byte_end) else {
+                bail!(
+                    "custom chunker returned bytes {}..{} outside the {}-byte text",
+                    span.byte_start,
+                    span.byte_end,
+                    text.len()
+                );
+            };
+            span.validate()?;
+            let chunk_type = if custom.symbol.is_some() {
+                ChunkType::Function
+            } else {
+                ChunkType::Text
+            };
+            Ok(Chunk {
+                span,
+                text: chunk_text.to_string(),
+                chunk_type,
+                stride_info: None,
+                metadata: ChunkMetadata::from_context(
+                    chunk_text,
+                    custom.symbol.into_iter().collect(),
+                    Vec::new(),
+                    Vec::new(),
+                ),
+            })
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{ChunkStrategy, chunk_file_content};
+
+    struct HalvesChunker;
+
+    impl Chunker for HalvesChunker {
+        fn chunk(&self, text: &str) -> Result<Vec<CustomChunk>> {
+            let middle = text.find("\n\n").unwrap() + 1;
+            Ok(vec![
+                CustomChunk {
+                    span: Span::new(0, middle, 1, 2)?,
+                    symbol: Some("first".to_string()),
+                },
+                CustomChunk {
+                    span: Span::new(middle + 1, text.len(), 4, 5)?,
+                    symbol: None,
+                },
+            ])
+        }
+    }
+
+    #[test]
+    fn test_registered_chunker_is_used_for_its_extensions() {
+        register_chunker(&[".halves", "HALF"], HalvesChunker);
+        let text = "one\ntwo\n\nthree\nfour\n";
+        let chunk = |name: &str, strategy| {
+            chunk_file_content(Path::new(name), text, None, None, 0, strategy).unwrap()
+        };
+
+        let chunks = chunk("a.halves", ChunkStrategy::SyntaxAware);
+        assert_eq!(chunks.len(), 2);
+        assert_eq!(chunks[0].text, "one\ntwo\n");
+        assert_eq!(chunks[0].chunk_type, ChunkType::Function);
+        assert_eq!(chunks[0].metadata.breadcrumb.as_deref(), Some("first"));
+        assert_eq!(chunks[1].text, "three\nfour\n");
+        assert_eq!(chunks[1].span.line_start, 4);
+        assert_eq!(chunks[1].chunk_type, ChunkType::Text);
+        assert_eq!(chunk("B.Half", ChunkStrategy::SyntaxAware).len(), 2);
+
+        // An explicit strategy wins, as it does over tree-sitter
+        assert_eq!(chunk("a.halves", ChunkStrategy::FixedTokens).len(), 1);
+
+        assert!(unregister_chunker("halves"));
+        assert!(!unregister_chunker("halves"));
+        assert_eq!(chunk("a.halves", ChunkStrategy::SyntaxAware).len(), 1);
+    }
+
+    #[test]
+    fn test_out_of_range_span_is_an_error() {
+        struct Overreaching;
+        impl Chunker for Overreaching {
+            fn chunk(&self, text: &str) -> Result<Vec<CustomChunk>> {
+                Ok(vec![CustomChunk {
+                    span: Span::new(0, text.len() + 10, 1, 1)?,
+                    symbol: None,
+                }])
+            }
+        }
+        let err = run_chunker(&Overreaching, "short").unwrap_err();
+        assert!(err.to_string().contains("outside the 5-byte text"));
+    }
+}
@@ -1,9 +1,17 @@
 use anyhow::Result;
 use ck_core::Span;
 use serde::{Deserialize, Serialize};
+use std::path::Path;
 
+mod custom;
+mod markdown;
+mod notebook;
+mod pdf;
 mod query_chunker;
 
+pub use custom::{Chunker, CustomChunk, register_chunker, unregister_chunker};
+pub use markdown::{HEADING_PATH_SEPARATOR, heading_path_at_line};
+
 /// Import token estimation from ck-embed
 pub use ck_embed::TokenEstimator;
 
@@ -62,6 +70,13 @@ pub struct ChunkMetadata {
     pub trailing_trivia: Vec<String>,
     pub byte_length: usize,
     pub estimated_tokens: usize,
+    /// Leading lines of the chunk text repeated from before its span
+    /// (see [`ChunkConfig::chunk_overlap_lines`])
+    #[serde(default)]
+    pub overlap_lines: usize,
+    /// Language named on the fence of a Markdown code block chunk
+    #[serde(default)]
+    pub code_language: Option<String>,
 }
 
 impl ChunkMetadata {
@@ -84,6 +99,8 @@ impl ChunkMetadata {
             trailing_trivia,
             byte_length: text.len(),
             estimated_tokens: estimate_tokens(text),
+            overlap_lines: 0,
+            code_language: None,
         }
     }
 
@@ -95,6 +112,8 @@ impl ChunkMetadata {
             trailing_trivia: Vec::new(),
             byte_length: text.len(),
             estimated_tokens: estimate_tokens(text),
+            overlap_lines: 0,
+            code_language: None,
         }
     }
 
@@ -201,6 +220,30 @@ impl TryFrom<ck_core::Language> for ParseableLanguage {
     }
 }
 
+/// How text is split into chunks (`--chunk-strategy`).
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum ChunkStrategy {
+    /// Symbol-aware chunks from tree-sitter for supported languages, with
+    /// fixed windows for everything else
+    #[default]
+    SyntaxAware,
+    /// Uniform windows of the model's target size, whatever the language
+    FixedTokens,
+    /// One chunk per run of non-blank lines, for prose and notes
+    Paragraph,
+}
+
+impl std::fmt::Display for ChunkStrategy {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            ChunkStrategy::SyntaxAware => "syntax-aware",
+            ChunkStrategy::FixedTokens => "fixed-tokens",
+            ChunkStrategy::Paragraph => "paragraph",
+        })
+    }
+}
+
 pub fn chunk_text(text: &str, language: Option<ck_core::Language>) -> Result<Vec<Chunk>> {
     chunk_text_with_config(text, language, &ChunkConfig::default())
 }
@@ -214,6 +257,11 @@ pub struct ChunkConfig {
     pub stride_overlap: usize,
     /// Enable striding for chunks that exceed max_tokens
     pub enable_striding: bool,
+    /// Lines of preceding context repeated at the start of each chunk's text,
+    /// so code near a boundary is embedded with its neighbour. Spans are left
+    /// at the chunk's own lines; 0 disables.
+    pub chunk_overlap_lines: usize,
+    pub strategy: ChunkStrategy,
 }
 
 impl Default for ChunkConfig {
@@ -222,6 +270,8 @@ impl Default for ChunkConfig {
             max_tokens: 8192,     // Default to Nomic model limit
             stride_overlap: 1024, // 12.5% overlap
             enable_striding: true,
+            chunk_overlap_lines: 0,
+            strategy: ChunkStrategy::SyntaxAware,
         }
     }
 }
@@ -231,6 +281,34 @@ pub fn chunk_text_with_model(
     text: &str,
     language: Option<ck_core::Language>,
     model_name: Option<&str>,
+) -> Result<Vec<Chunk>> {
+    chunk_text_with_model_and_overlap(text, language, model_name, 0)
+}
+
+/// Model-specific chunking with `chunk_overlap_lines` of preceding context
+/// carried into each chunk (see [`ChunkConfig::chunk_overlap_lines`]).
+pub fn chunk_text_with_model_and_overlap(
+    text: &str,
+    language: Option<ck_core::Language>,
+    model_name: Option<&str>,
+    chunk_overlap_lines: usize,
+) -> Result<Vec<Chunk>> {
+    chunk_text_with_strategy(
+        text,
+        language,
+        model_name,
+        chunk_overlap_lines,
+        ChunkStrategy::SyntaxAware,
+    )
+}
+
+/// Model-specific chunking with an explicit [`ChunkStrategy`].
+pub fn chunk_text_with_strategy(
+    text: &str,
+    language: Option<ck_core::Language>,
+    model_name: Option<&str>,
+    chunk_overlap_lines: usize,
+    strategy: ChunkStrategy,
 ) -> Result<Vec<Chunk>> {
     let (target_tokens, overlap_tokens) = get_model_chunk_config(model_name);
 
@@ -239,11 +317,61 @@ pub fn chunk_text_with_model(
         max_tokens: target_tokens,
         stride_overlap: overlap_tokens,
         enable_striding: true,
+        chunk_overlap_lines,
+        strategy,
     };
 
     chunk_text_with_config_and_model(text, language, &config, model_name)
 }
 
+/// Chunk the content of the file at `path` the way indexing does: with the
+/// [`Chunker`] registered for its extension when there is one and the
+/// strategy is syntax-aware, otherwise as [`chunk_text_with_strategy`] would
+/// for `language`.
+pub fn chunk_file_content(
+    path: &Path,
+    text: &str,
+    language: Option<ck_core::Language>,
+    model_name: Option<&str>,
+    chunk_overlap_lines: usize,
+    strategy: ChunkStrategy,
+) -> Result<Vec<Chunk>> {
+    let chunker = match strategy {
+        ChunkStrategy::SyntaxAware => custom::chunker_for(path)
+            .or_else(|| notebook::chunker_for(path))
+            .or_else(|| pdf::chunker_for(path)),
+        ChunkStrategy::FixedTokens | ChunkStrategy::Paragraph => None,
+    };
+    let Some(chunker) = chunker else {
+        // Plain-text documents have no syntax; their paragraphs are the units
+        let strategy = if strategy == ChunkStrategy::SyntaxAware && is_plain_text_file(path) {
+            ChunkStrategy::Paragraph
+        } else {
+            strategy
+        };
+        return chunk_text_with_strategy(text, language, model_name, chunk_overlap_lines, strategy);
+    };
+
+    tracing::debug!("Using registered chunker for {}", path.display());
+    let (target_tokens, overlap_tokens) = get_model_chunk_config(model_name);
+    let config = ChunkConfig {
+        max_tokens: target_tokens,
+        stride_overlap: overlap_tokens,
+        enable_striding: true,
+        chunk_overlap_lines,
+        strategy,
+    };
+    let chunks = custom::run_chunker(chunker.as_ref(), text)?;
+    finish_chunks(text, chunks, &config, model_name)
+}
+
+/// Whether `path` is a plain-text document (`.txt`).
+fn is_plain_text_file(path: &Path) -> bool {
+    path.extension()
+        .and_then(|ext| ext.to_str())
+        .is_some_and(|ext| ext.eq_ignore_ascii_case("txt"))
+}
+
 pub fn chunk_text_with_config(
     text: &str,
     language: Option<ck_core::Language>,
@@ -265,26 +393,46 @@ fn chunk_text_with_config_and_model(
         config
     );
 
-    let result = match language.map(ParseableLanguage::try_from) {
-        Some(Ok(lang)) => {
+    let result = match (config.strategy, language.map(ParseableLanguage::try_from)) {
+        (ChunkStrategy::FixedTokens, _) => {
+            tracing::debug!("Using fixed-size chunking strategy");
+            chunk_generic_with_token_config(text, model_name, config.chunk_overlap_lines)
+        }
+        (ChunkStrategy::Paragraph, _) => {
+            tracing::debug!("Using paragraph chunking strategy");
+            Ok(chunk_paragraphs(text))
+        }
+        (ChunkStrategy::SyntaxAware, Some(Ok(lang))) => {
             tracing::debug!("Using {} tree-sitter parser", lang);
             chunk_language_with_model(text, lang, model_name)
         }
-        Some(Err(_)) => {
+        (ChunkStrategy::SyntaxAware, Some(Err(_))) => {
             tracing::debug!("Language not supported for parsing, using generic chunking strategy");
-            chunk_generic_with_token_config(text, model_name)
+            chunk_generic_with_token_config(text, model_name, config.chunk_overlap_lines)
         }
-        None => {
+        (ChunkStrategy::SyntaxAware, None) => {
             tracing::debug!("Using generic chunking strategy");
-            chunk_generic_with_token_config(text, model_name)
+            chunk_generic_with_token_config(text, model_name, config.chunk_overlap_lines)
         }
     };
 
-    let mut chunks = result?;
+    finish_chunks(text, result?, config, model_name)
+}
 
+/// Stride oversized chunks and add the configured line overlap.
+fn finish_chunks(
+    text: &str,
+    mut chunks: Vec<Chunk>,
+    config: &ChunkConfig,
+    model_name: Option<&str>,
+) -> Result<Vec<Chunk>> {
     // Apply striding if enabled and necessary
     if config.enable_striding {
-        chunks = apply_striding(chunks, config)?;
+        chunks = apply_striding(chunks, config, model_name)?;
+    }
+
+    if config.chunk_overlap_lines > 0 {
+        chunks = apply_chunk_overlap(text, chunks, config.chunk_overlap_lines);
     }
 
     tracing::debug!("Successfully created {} final chunks", chunks.len());
@@ -292,10 +440,14 @@ fn chunk_text_with_config_and_model(
 }
 
 fn chunk_generic(text: &str) -> Result<Vec<Chunk>> {
-    chunk_generic_with_token_config(text, None)
+    chunk_generic_with_token_config(text, None, 0)
 }
 
-fn chunk_generic_with_token_config(text: &str, model_name: Option<&str>) -> Result<Vec<Chunk>> {
+fn chunk_generic_with_token_config(
+    text: &str,
+    model_name: Option<&str>,
+    chunk_overlap_lines: usize,
+) -> Result<Vec<Chunk>> {
     let mut chunks = Vec::new();
     let lines: Vec<&str> = text.lines().collect();
 
@@ -309,7 +461,13 @@ fn chunk_generic_with_token_config(text: &str, model_name: Option<&str>) -> Resu
     let overlap_lines = ((overlap_tokens as f32) / avg_tokens_per_line) as usize;
 
     let chunk_size = target_lines.max(5); // Minimum 5 lines
-    let overlap = overlap_lines.max(1); // Minimum 1 line overlap
+    // A configured overlap is added afterwards as context outside the span,
+    // so the windows themselves must not overlap.
+    let overlap = if chunk_overlap_lines > 0 {
+        0
+    } else {
+        overlap_lines.max(1) // Minimum 1 line overlap
+    };
 
     // Pre-compute cumulative byte offsets for O(1) lookup, accounting for different line endings
     let mut line_byte_offsets = Vec::with_capacity(lines.len() + 1);
@@ -370,6 +528,49 @@ fn chunk_generic_with_token_config(text: &str, model_name: Option<&str>) -> Resu
     Ok(chunks)
 }
 
+/// One chunk per run of non-blank lines. Spans end after the paragraph's
+/// last newline, like the fixed-size windows; oversized paragraphs are left
+/// to striding.
+fn chunk_paragraphs(text: &str) -> Vec<Chunk> {
+    let mut chunks = Vec::new();
+    // (first line, byte start) of the paragraph being read
+    let mut current: Option<(usize, usize)> = None;
+    let mut byte_pos = 0;
+    let mut push = |line_start: usize, line_end: usize, byte_start: usize, byte_end: usize| {
+        let chunk_text = text[byte_start..byte_end].trim_end().to_string();
+        let metadata = ChunkMetadata::from_text(&chunk_text);
+        chunks.push(Chunk {
+            span: Span {
+                byte_start,
+                byte_end,
+                line_start,
+                line_end,
+            },
+            text: chunk_text,
+            chunk_type: ChunkType::Text,
+            stride_info: None,
+            metadata,
+        });
+    };
+
+    let mut line_count = 0;
+    for (index, line) in text.split_inclusive('\n').enumerate() {
+        line_count = index + 1;
+        if line.trim().is_empty() {
+            if let Some((line_start, byte_start)) = current.take() {
+                push(line_start, index, byte_start, byte_pos);
+            }
+        } else if current.is_none() {
+            current = Some((index + 1, byte_pos));
+        }
+        byte_pos += line.len();
+    }
+    if let Some((line_start, byte_start)) = current {
+        push(line_start, line_count, byte_start, byte_pos);
+    }
+    chunks
+}
+
 pub(crate) fn tree_sitter_language(language: ParseableLanguage) -> Result<tree_sitter::Language> {
     if language == ParseableLanguage::Markdown {
         return Ok(tree_sitter_md::LANGUAGE.into());
@@ -400,6 +601,10 @@ pub(crate) fn tree_sitter_language(language: ParseableLanguage) -> Result<tree_s
 }
 
 fn chunk_language(text: &str, language: ParseableLanguage) -> Result<Vec<Chunk>> {
+    if language == ParseableLanguage::Markdown {
+        return Ok(markdown::chunk_markdown(text));
+    }
+
     let mut parser = tree_sitter::Parser::new();
     let ts_language = tree_sitter_language(language)?;
     parser.set_language(&ts_language)?;
@@ -444,12 +649,6 @@ fn chunk_language(text: &str, language: ParseableLanguage) -> Result<Vec<Chunk>>
         chunks = suppress_contained_text_chunks(chunks);
     }
 
-    // Merge small chunks if Markdown
-    if language == ParseableLanguage::Markdown {
-        let (target_tokens, _) = get_model_chunk_config(None);
-        chunks = merge_small_chunks(chunks, text, target_tokens);
-    }
-
     Ok(chunks)
 }
 
@@ -1042,10 +1241,10 @@ fn chunk_type_for_node(
         {
             return None;
         }
-        ParseableLanguage::CSharp if node.kind() == "variable_declaration" => {
-            if !is_csharp_field_like(*node) {
-                return None;
-            }
+        ParseableLanguage::CSharp
+            if node.kind() == "variable_declaration" && !is_csharp_field_like(*node) =>
+        {
+            return None;
         }
         _ => {}
     }
@@ -1284,7 +1483,7 @@ fn strip_method_bodies_in_class_text(
             .to_string();
     }
 
-    replacements.sort_by(|a, b| b.0.cmp(&a.0));
+    replacements.sort_by_key(|b| std::cmp::Reverse(b.0));
     let mut text = source
         .get(byte_start..byte_end)
         .unwrap_or_default()
@@ -1940,12 +2139,46 @@ fn is_csharp_field_like(node: tree_sitter::Node<'_>) -> bool {
     false
 }
 
-/// Apply striding to chunks that exceed the token limit
-fn apply_striding(chunks: Vec<Chunk>, config: &ChunkConfig) -> Result<Vec<Chunk>> {
+/// Prefix each chunk's text with up to `overlap_lines` lines that precede its
+/// span, reaching back no further than the start of the previous chunk.
+/// Chunks that already overlap their predecessor (strides) are left alone, and
+/// the spans are unchanged so results never count a line twice.
+fn apply_chunk_overlap(text: &str, mut chunks: Vec<Chunk>, overlap_lines: usize) -> Vec<Chunk> {
+    let lines: Vec<&str> = text.lines().collect();
+
+    for i in 1..chunks.len() {
+        let prev = &chunks[i - 1].span;
+        let start = chunks[i].span.line_start;
+        if start <= prev.line_end || start > lines.len() + 1 {
+            continue;
+        }
+
+        let from = start.saturating_sub(overlap_lines).max(prev.line_start);
+        if from >= start {
+            continue;
+        }
+
+        let chunk = &mut chunks[i];
+        let overlap_text = lines[from - 1..start - 1].join("\n");
+        chunk.text = format!("{overlap_text}\n{}", chunk.text);
+        chunk.metadata = chunk.metadata.with_updated_text(&chunk.text);
+        chunk.metadata.overlap_lines = start - from;
+    }
+
+    chunks
+}
+
+/// Apply striding to chunks that exceed the token limit, counted with the
+/// model's own tokenizer when it is cached
+fn apply_striding(
+    chunks: Vec<Chunk>,
+    config: &ChunkConfig,
+    model_name: Option<&str>,
+) -> Result<Vec<Chunk>> {
     let mut result = Vec::new();
 
     for chunk in chunks {
-        let estimated_tokens = estimate_tokens(&chunk.text);
+        let estimated_tokens = TokenEstimator::count_tokens(&chunk.text, model_name);
 
         if estimated_tokens <= config.max_tokens {
             // Chunk fits within limit, no striding needed
@@ -1958,7 +2191,7 @@ fn apply_striding(chunks: Vec<Chunk>, config: &ChunkConfig) -> Result<Vec<Chunk>
                 config.max_tokens
             );
 
-            let strided_chunks = stride_large_chunk(chunk, config)?;
+            let strided_chunks = stride_large_chunk(chunk, config, model_name)?;
             result.extend(strided_chunks);
         }
     }
@@ -1967,7 +2200,11 @@ fn apply_striding(chunks: Vec<Chunk>, config: &ChunkConfig) -> Result<Vec<Chunk>
 }
 
 /// Create strided chunks from a large chunk that exceeds token limits
-fn stride_large_chunk(chunk: Chunk, config: &ChunkConfig) -> Result<Vec<Chunk>> {
+fn stride_large_chunk(
+    chunk: Chunk,
+    config: &ChunkConfig,
+    model_name: Option<&str>,
+) -> Result<Vec<Chunk>> {
     let text = &chunk.text;
 
     // Early return for empty chunks to avoid divide-by-zero
@@ -1978,7 +2215,7 @@ fn stride_large_chunk(chunk: Chunk, config: &ChunkConfig) -> Result<Vec<Chunk>>
     // Calculate stride parameters in characters (not bytes!)
     // Use a conservative estimate to ensure we stay under token limits
     let char_count = text.chars().count();
-    let estimated_tokens = estimate_tokens(text);
+    let estimated_tokens = TokenEstimator::count_tokens(text, model_name);
     // Guard against zero token estimate to prevent divide-by-zero panic
     let chars_per_token = if estimated_tokens == 0 {
         4.5 // Use default average if estimation fails
@@ -2076,124 +2313,73 @@ fn stride_large_chunk(chunk: Chunk, config: &ChunkConfig) -> Result<Vec<Chunk>>
     tracing::debug!(
         "Created {} strides from chunk of {} tokens",
         strided_chunks.len(),
-        estimate_tokens(text)
+        estimated_tokens
     );
 
     Ok(strided_chunks)
 }
 
-/// Merge adjacent Markdown chunks that are individually too small to be useful.
-///
-/// Markdown is often structured as many tiny logical units (a heading, a short
-/// paragraph, a single list item).  Emitting each one as its own chunk would
-/// pollute the embedding index with near-empty vectors that carry little
-/// semantic signal.  This pass greedily accumulates adjacent chunks until the
-/// running token count approaches `target_tokens`, then emits one merged chunk.
-///
-/// Side-effect: merged chunks lose their individual `ChunkType` labels (heading
-/// becomes `ChunkType::Text`) because the merged span covers mixed content.
-/// That is intentional — the important thing is that the *text* survives so
-/// search can still match it.
-fn merge_small_chunks(chunks: Vec<Chunk>, text: &str, target_tokens: usize) -> Vec<Chunk> {
-    if chunks.is_empty() {
-        return chunks;
-    }
+// Removed duplicate estimate_tokens function - using the one from ck-embed via TokenEstimator
 
-    let mut result = Vec::new();
-    let mut current_group: Vec<Chunk> = Vec::new();
-    let mut current_tokens = 0;
+#[cfg(test)]
+mod tests {
+    use super::*;
 
-    for chunk in chunks {
-        let chunk_tokens = chunk.metadata.estimated_tokens;
-
-        if current_tokens + chunk_tokens > target_tokens {
-            // Flush
-            if !current_group.is_empty() {
-                result.push(merge_group(&current_group, text));
-                current_group.clear();
-                current_tokens = 0;
-            }
-        }
+    #[test]
+    fn test_chunk_strategies_dispatch() {
+        let code = "fn alpha() {\n    1\n}\n\nfn beta() {\n    2\n}\n";
+        let chunk = |strategy| {
+            chunk_text_with_strategy(code, Some(ck_core::Language::Rust), None, 0, strategy)
+                .unwrap()
+        };
 
-        // If single chunk is huge
-        if chunk_tokens > target_tokens {
-            if !current_group.is_empty() {
-                result.push(merge_group(&current_group, text));
-                current_group.clear();
-                current_tokens = 0;
-            }
-            result.push(chunk);
-            continue;
-        }
+        let syntax = chunk(ChunkStrategy::SyntaxAware);
+        assert!(syntax.iter().any(|c| c.chunk_type == ChunkType::Function));
 
-        current_group.push(chunk);
-        current_tokens += chunk_tokens;
-    }
+        // Small input fits in a single fixed window
+        let fixed = chunk(ChunkStrategy::FixedTokens);
+        assert_eq!(fixed.len(), 1);
+        assert_eq!(fixed[0].chunk_type, ChunkType::Text);
 
-    // Flush remaining
-    if !current_group.is_empty() {
-        result.push(merge_group(&current_group, text));
+        let paragraphs = chunk(ChunkStrategy::Paragraph);
+        assert_eq!(paragraphs.len(), 2);
+        assert_eq!(paragraphs[1].text, "fn beta() {\n    2\n}");
+        assert_eq!(
+            (paragraphs[1].span.line_start, paragraphs[1].span.line_end),
+            (5, 7)
+        );
+        assert_eq!(
+            &code[paragraphs[0].span.byte_start..paragraphs[0].span.byte_end],
+            "fn alpha() {\n    1\n}\n"
+        );
     }
 
-    result
-}
-
-fn merge_group(group: &[Chunk], text: &str) -> Chunk {
-    if group.len() == 1 {
-        return group[0].clone();
+    #[test]
+    fn test_paragraph_chunks_skip_blank_runs() {
+        let text = "\n\nFirst para\nstill first\n\n\n   \nSecond para";
+        let chunks = chunk_paragraphs(text);
+        assert_eq!(chunks.len(), 2);
+        assert_eq!((chunks[0].span.line_start, chunks[0].span.line_end), (3, 4));
+        assert_eq!(chunks[1].text, "Second para");
+        assert_eq!((chunks[1].span.line_start, chunks[1].span.line_end), (8, 8));
+        assert_eq!(chunks[1].span.byte_end, text.len());
     }
 
-    let first = &group[0];
-    let last = &group[group.len() - 1];
-
-    // Calculate new span
-    // Assuming sorted, which fill_gaps ensures
-    let byte_start = first.span.byte_start;
-    let byte_end = last.span.byte_end;
-    let line_start = first.span.line_start;
-    let line_end = last.span.line_end;
-
-    // Safely slice text
-    let chunk_text = if byte_end <= text.len() {
-        text[byte_start..byte_end].to_string()
-    } else {
-        // Fallback for safety, though existing chunks should be valid
-        text.get(byte_start..).unwrap_or("").to_string()
-    };
-
-    let metadata = ChunkMetadata::from_text(&chunk_text);
-
-    // If all chunks in the group share the same semantic type, preserve it.
-    // Otherwise (the common case for Markdown, where headings, paragraphs, and
-    // code blocks are merged together), fall back to ChunkType::Text.  This is
-    // intentional: the merged chunk is a mixed-content blob and no single type
-    // describes it accurately.  Callers should rely on chunk *text* content
-    // rather than chunk type when working with merged Markdown output.
-    let chunk_type = if group.iter().all(|c| c.chunk_type == first.chunk_type) {
-        first.chunk_type.clone()
-    } else {
-        ChunkType::Text
-    };
-
-    Chunk {
-        span: Span {
-            byte_start,
-            byte_end,
-            line_start,
-            line_end,
-        },
-        text: chunk_text,
-        chunk_type,
-        stride_info: None,
-        metadata,
+    #[test]
+    fn test_txt_files_chunk_by_paragraph() {
+        let text = "Spec\n\nThe cache is rebuilt on demand.\n";
+        let chunks = chunk_file_content(
+            Path::new("notes/SPEC.TXT"),
+            text,
+            None,
+            None,
+            0,
+            ChunkStrategy::SyntaxAware,
+        )
+        .unwrap();
+        let texts: Vec<&str> = chunks.iter().map(|c| c.text.as_str()).collect();
+        assert_eq!(texts, ["Spec", "The cache is rebuilt on demand."]);
     }
-}
-
-// Removed duplicate estimate_tokens function - using the one from ck-embed via TokenEstimator
-
-#[cfg(test)]
-mod tests {
-    use super::*;
 
     fn canonicalize_spans(
         mut spans: Vec<(usize, usize, ChunkType)>,
@@ -2992,13 +3178,14 @@ shapeDescription (Square s) = "square of side " ++ show s
         let chunks =
             chunk_text(&source, Some(ck_core::Language::Markdown)).expect("chunk markdown");
 
-        // The fixture is a small document (well under the token target), so
-        // merge_small_chunks collapses all individual heading / paragraph chunks
-        // into a single merged chunk.  After merging the chunk_type is
-        // ChunkType::Text (mixed content) — that is intentional; see
-        // merge_small_chunks for the rationale.  What we verify here is that
-        // the actual heading text survives in the merged output so that
-        // full-text search can still find it.
+        let breadcrumbs: Vec<_> = chunks
+            .iter()
+            .filter_map(|c| c.metadata.breadcrumb.as_deref())
+            .collect();
+        assert!(breadcrumbs.contains(&"Project Overview > Usage > Installation"));
+        // An `===` underline makes a top-level heading
+        assert!(breadcrumbs.contains(&"Setext Section"));
+
         let all_text: String = chunks
             .iter()
             .map(|c| c.text.as_str())
@@ -3007,15 +3194,15 @@ shapeDescription (Square s) = "square of side " ++ show s
 
         assert!(
             all_text.contains("Project Overview"),
-            "expected top-level heading text to be present in merged chunk"
+            "expected top-level heading text to be present"
         );
         assert!(
             all_text.contains("## Usage"),
-            "expected second-level heading text to be present in merged chunk"
+            "expected second-level heading text to be present"
         );
         assert!(
             all_text.contains("Setext Section"),
-            "expected setext heading text to be present in merged chunk"
+            "expected setext heading text to be present"
         );
     }
 
@@ -3047,13 +3234,14 @@ Trailing paragraph.
 
         let chunks = chunk_text(source, Some(ck_core::Language::Markdown)).expect("chunk markdown");
 
-        // This source is a small synthetic document.  merge_small_chunks will
-        // collapse all individual heading / paragraph / code-block chunks into
-        // one (or a few) merged chunks whose chunk_type is ChunkType::Text.
-        // That is intentional — tiny Markdown chunks produce weak embeddings;
-        // see merge_small_chunks for the full rationale.  What matters is that
-        // all block content is preserved in the output text so that full-text
-        // and semantic search can still find it.
+        // Every block survives in some chunk, and the fenced code is a
+        // chunk of its own
+        assert!(
+            chunks
+                .iter()
+                .any(|c| c.metadata.code_language.as_deref() == Some("rust")
+                    && c.text.starts_with("```rust"))
+        );
         let all_text: String = chunks
             .iter()
             .map(|c| c.text.as_str())
@@ -3062,7 +3250,7 @@ Trailing paragraph.
 
         assert!(
             all_text.contains("# Title") || all_text.contains("## Usage"),
-            "expected heading text to be present after merging"
+            "expected heading text to be present"
         );
         assert!(
             all_text.contains("```rust"),
@@ -3450,7 +3638,7 @@ public class Calculator
         };
 
         let config = ChunkConfig::default();
-        let result = stride_large_chunk(empty_chunk.clone(), &config);
+        let result = stride_large_chunk(empty_chunk.clone(), &config, None);
 
         // Should not panic and return the original chunk
         assert!(result.is_ok());
@@ -3476,7 +3664,7 @@ public class Calculator
         };
 
         let config = ChunkConfig::default();
-        let result = stride_large_chunk(chunk, &config);
+        let result = stride_large_chunk(chunk, &config, None);
 
         // Should not panic and handle gracefully
         assert!(result.is_ok());
@@ -3508,7 +3696,7 @@ public class Calculator
             ..Default::default()
         };
 
-        let result = stride_large_chunk(chunk, &config);
+        let result = stride_large_chunk(chunk, &config, None);
         if let Err(e) = &result {
             eprintln!("Stride error: {e}");
         }
@@ -4088,4 +4276,62 @@ end
             behaviour_chunks.len()
         );
     }
+
+    #[test]
+    fn test_chunk_overlap_repeats_boundary_lines() {
+        let code = r#"def first():
+    a = 1
+    return a
+
+
+def second():
+    b = 2
+    return b
+"#;
+        let config = ChunkConfig {
+            chunk_overlap_lines: 3,
+            ..Default::default()
+        };
+        let chunks =
+            chunk_text_with_config(code, Some(ck_core::Language::Python), &config).unwrap();
+        let first = chunks
+            .iter()
+            .find(|c| c.text.contains("def first"))
+            .unwrap();
+        let second = chunks
+            .iter()
+            .find(|c| c.text.contains("def second"))
+            .unwrap();
+
+        // The tail of `first` is repeated at the start of `second`...
+        assert!(first.text.ends_with("    return a"));
+        assert!(second.text.starts_with("    return a\n\n\ndef second():"));
+        assert_eq!(second.metadata.overlap_lines, 3);
+        // ...but the spans stay disjoint
+        assert!(first.span.line_end < second.span.line_start);
+        assert_eq!(second.span.line_start, 6);
+        assert_eq!(first.metadata.overlap_lines, 0);
+    }
+
+    #[test]
+    fn test_chunk_overlap_generic_windows() {
+        let text = (1..=200)
+            .map(|i| format!("line {i} of plain text"))
+            .collect::<Vec<_>>()
+            .join("\n");
+        let chunks = chunk_text_with_model_and_overlap(&text, None, None, 3).unwrap();
+        assert!(chunks.len() > 1);
+
+        for pair in chunks.windows(2) {
+            let (prev, next) = (&pair[0], &pair[1]);
+            assert_eq!(prev.span.line_end + 1, next.span.line_start);
+            assert_eq!(next.metadata.overlap_lines, 3);
+            let shared = format!("line {} of plain text", prev.span.line_end);
+            assert!(prev.text.contains(&shared));
+            assert!(
+                next.text
+                    .starts_with(&format!("line {} of plain text", prev.span.line_end - 2))
+            );
+        }
+    }
 }
@@ -0,0 +1,373 @@
+//! Markdown chunking that follows the document's structure: one chunk per
+//! section, from a heading to the next, and one per fenced code block, so a
+//! hit in a code sample is told apart from prose. Each chunk's breadcrumb is
+//! the path of headings it sits under (`Install > macOS`), and a code
+//! block's metadata records the language of its fence.
+//!
+//! The scan is line based, like the notebook chunker: headings and fences
+//! are recognised per line, and nothing inside a fence is taken for a
+//! heading.
+
+use ck_core::Span;
+
+use crate::{Chunk, ChunkMetadata, ChunkType, parse_atx_heading_line, parse_setext_level};
+
+/// Joins the headings of a path, outermost first.
+pub const HEADING_PATH_SEPARATOR: &str = " > ";
+
+/// What a line of a Markdown document is, as far as chunking cares.
+#[derive(Debug, Clone, PartialEq)]
+enum LineKind {
+    Blank,
+    Text,
+    /// An ATX heading, or the text line of a setext heading
+    Heading(usize, String),
+    /// The `===`/`---` line under a setext heading
+    Underline,
+    /// Opening fence, with the first word of its info string
+    FenceOpen(Option<String>),
+    Code,
+    FenceClose,
+}
+
+/// The kind of every line of `text`, in order. A fence left open runs to the
+/// end of the document, and YAML front matter counts as text.
+fn classify_lines(lines: &[&str]) -> Vec<LineKind> {
+    let mut kinds = Vec::with_capacity(lines.len());
+    let mut fence: Option<(char, usize)> = None;
+    let mut index = 0;
+
+    if lines.first().is_some_and(|line| line.trim_end() == "---") {
+        let close = lines
+            .iter()
+            .skip(1)
+            .position(|line| matches!(line.trim_end(), "---" | "..."));
+        if let Some(close) = close {
+            kinds.extend(std::iter::repeat_n(LineKind::Text, close + 2));
+            index = close + 2;
+        }
+    }
+
+    while index < lines.len() {
+        let line = lines[index];
+        if let Some((marker, length)) = fence {
+            if is_fence_close(line, marker, length) {
+                fence = None;
+                kinds.push(LineKind::FenceClose);
+            } else {
+                kinds.push(LineKind::Code);
+            }
+            index += 1;
+            continue;
+        }
+        if let Some((marker, length, language)) = fence_open(line) {
+            fence = Some((marker, length));
+            kinds.push(LineKind::FenceOpen(language));
+            index += 1;
+            continue;
+        }
+        if line.trim().is_empty() {
+            kinds.push(LineKind::Blank);
+        } else if let Some((level, heading)) = (indent(line) < 4)
+            .then(|| parse_atx_heading_line(line))
+            .flatten()
+        {
+            kinds.push(LineKind::Heading(level, heading));
+        } else if let Some(level) = lines
+            .get(index + 1)
+            .filter(|_| can_be_setext_text(line))
+            .and_then(|next| parse_setext_level(next))
+        {
+            kinds.push(LineKind::Heading(level, line.trim().to_string()));
+            kinds.push(LineKind::Underline);
+            index += 2;
+            continue;
+        } else {
+            kinds.push(LineKind::Text);
+        }
+        index += 1;
+    }
+    kinds
+}
+
+fn indent(line: &str) -> usize {
+    line.len() - line.trim_start_matches(' ').len()
+}
+
+/// A paragraph line a setext underline would turn into a heading; list
+/// items, quotes and table rows above a `---` are followed by a rule.
+fn can_be_setext_text(line: &str) -> bool {
+    let trimmed = line.trim_start();
+    indent(line) < 4
+        && !trimmed.starts_with(['-', '*', '+', '>', '|'])
+        && !trimmed.split_once(['.', ')']).is_some_and(|(number, _)| {
+            !number.is_empty() && number.chars().all(|c| c.is_ascii_digit())
+        })
+}
+
+/// An opening code fence: its marker, length and language.
+fn fence_open(line: &str) -> Option<(char, usize, Option<String>)> {
+    if indent(line) > 3 {
+        return None;
+    }
+    let trimmed = line.trim();
+    let marker = trimmed.chars().next().filter(|c| matches!(c, '`' | '~'))?;
+    let length = trimmed.chars().take_while(|&c| c == marker).count();
+    if length < 3 {
+        return None;
+    }
+    let info = &trimmed[length..];
+    if marker == '`' && info.contains('`') {
+        return None;
+    }
+    let language = info
+        .split(|c: char| c.is_whitespace() || c == ',')
+        .next()
+        .map(|word| word.trim_matches(|c| c == '{' || c == '}' || c == '.'))
+        .filter(|word| !word.is_empty())
+        .map(str::to_string);
+    Some((marker, length, language))
+}
+
+fn is_fence_close(line: &str, marker: char, length: usize) -> bool {
+    let trimmed = line.trim();
+    indent(line) <= 3 && trimmed.chars().count() >= length && trimmed.chars().all(|c| c == marker)
+}
+
+fn update_heading_path(path: &mut Vec<(usize, String)>, level: usize, heading: &str) {
+    while path.last().is_some_and(|(existing, _)| *existing >= level) {
+        path.pop();
+    }
+    path.push((level, heading.to_string()));
+}
+
+/// The headings 1-based `line` of `text` sits under, outermost first; a
+/// heading line is under itself.
+pub fn heading_path_at_line(text: &str, line: usize) -> Vec<String> {
+    let lines: Vec<&str> = text.lines().collect();
+    let mut path = Vec::new();
+    for kind in classify_lines(&lines).into_iter().take(line) {
+        if let LineKind::Heading(level, heading) = kind {
+            update_heading_path(&mut path, level, &heading);
+        }
+    }
+    path.into_iter().map(|(_, heading)| heading).collect()
+}
+
+/// A chunk being collected: where it starts and what it is.
+struct OpenChunk {
+    byte_start: usize,
+    line_start: usize,
+    path: Vec<String>,
+    /// `None` for prose, the fence's language (if any) for code
+    code: Option<Option<String>>,
+    starts_with_heading: bool,
+    /// Nothing but its heading so far
+    heading_only: bool,
+}
+
+/// One chunk per section and per fenced code block of `text`.
+pub(crate) fn chunk_markdown(text: &str) -> Vec<Chunk> {
+    let lines: Vec<&str> = text.split_inclusive('\n').collect();
+    let kinds = classify_lines(&lines);
+    let mut chunks = Vec::new();
+    let mut path: Vec<(usize, String)> = Vec::new();
+    let mut open: Option<OpenChunk> = None;
+    let mut offset = 0;
+
+    let finish = |chunks: &mut Vec<Chunk>, open: OpenChunk, byte_end: usize, line_end: usize| {
+        let chunk_text = &text[open.byte_start..byte_end];
+        if chunk_text.trim().is_empty() {
+            return;
+        }
+        let chunk_type = if open.starts_with_heading {
+            ChunkType::Module
+        } else {
+            ChunkType::Text
+        };
+        let breadcrumb = (!open.path.is_empty()).then(|| open.path.join(HEADING_PATH_SEPARATOR));
+        let mut metadata =
+            ChunkMetadata::from_context(chunk_text, open.path, Vec::new(), Vec::new());
+        metadata.breadcrumb = breadcrumb;
+        metadata.code_language = open.code.flatten();
+        chunks.push(Chunk {
+            span: Span {
+                byte_start: open.byte_start,
+                byte_end,
+                line_start: open.line_start,
+                line_end: line_end.max(open.line_start),
+            },
+            text: chunk_text.to_string(),
+            chunk_type,
+            stride_info: None,
+            metadata,
+        });
+    };
+    let headings = |path: &[(usize, String)]| -> Vec<String> {
+        path.iter().map(|(_, heading)| heading.clone()).collect()
+    };
+
+    for (index, (line, kind)) in lines.iter().zip(&kinds).enumerate() {
+        match kind {
+            LineKind::Heading(level, heading) => {
+                update_heading_path(&mut path, *level, heading);
+                // A heading with nothing under it but subsections joins the
+                // first of them rather than being a chunk on its own
+                match open.take() {
+                    Some(section) if section.heading_only => {
+                        open = Some(OpenChunk {
+                            path: headings(&path),
+                            heading_only: true,
+                            ..section
+                        });
+                    }
+                    previous => {
+                        if let Some(previous) = previous {
+                            finish(&mut chunks, previous, offset, index);
+                        }
+                        open = Some(OpenChunk {
+                            byte_start: offset,
+                            line_start: index + 1,
+                            path: headings(&path),
+                            code: None,
+                            starts_with_heading: true,
+                            heading_only: true,
+                        });
+                    }
+                }
+            }
+            LineKind::FenceOpen(language) => {
+                if let Some(previous) = open.take() {
+                    finish(&mut chunks, previous, offset, index);
+                }
+                open = Some(OpenChunk {
+                    byte_start: offset,
+                    line_start: index + 1,
+                    path: headings(&path),
+                    code: Some(language.clone()),
+                    starts_with_heading: false,
+                    heading_only: false,
+                });
+            }
+            LineKind::FenceClose => {
+                if let Some(fenced) = open.take() {
+                    finish(&mut chunks, fenced, offset + line.len(), index + 1);
+                }
+            }
+            LineKind::Text => match open.as_mut() {
+                Some(current) => current.heading_only = false,
+                None => {
+                    open = Some(OpenChunk {
+                        byte_start: offset,
+                        line_start: index + 1,
+                        path: headings(&path),
+                        code: None,
+                        starts_with_heading: false,
+                        heading_only: false,
+                    });
+                }
+            },
+            LineKind::Blank | LineKind::Underline | LineKind::Code => {}
+        }
+        offset += line.len();
+    }
+    if let Some(last) = open {
+        finish(&mut chunks, last, text.len(), lines.len());
+    }
+    chunks
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const GUIDE: &str = "\
+# Install
+
+Pick your platform.
+
+## macOS
+
+Use Homebrew:
+
+```bash
+brew install ck
+```
+
+Then check the version.
+
+## Linux
+### From source
+
+~~~rust,ignore
+# not a heading
+fn main() {}
+~~~
+
+Setext Usage
+------------
+
+Run `ck --help`.
+";
+
+    #[test]
+    fn sections_and_fences_become_their_own_chunks() {
+        let chunks = chunk_markdown(GUIDE);
+        let summary: Vec<(Option<&str>, Option<&str>, usize)> = chunks
+            .iter()
+            .map(|chunk| {
+                (
+                    chunk.metadata.breadcrumb.as_deref(),
+                    chunk.metadata.code_language.as_deref(),
+                    chunk.span.line_start,
+                )
+            })
+            .collect();
+        assert_eq!(
+            summary,
+            [
+                (Some("Install"), None, 1),
+                (Some("Install > macOS"), None, 5),
+                (Some("Install > macOS"), Some("bash"), 9),
+                (Some("Install > macOS"), None, 13),
+                // `## Linux` has no body, so it opens its first subsection
+                (Some("Install > Linux > From source"), None, 15),
+                (Some("Install > Linux > From source"), Some("rust"), 18),
+                (Some("Install > Setext Usage"), None, 23),
+            ]
+        );
+
+        for chunk in &chunks {
+            assert_eq!(
+                &GUIDE[chunk.span.byte_start..chunk.span.byte_end],
+                chunk.text
+            );
+        }
+        assert_eq!(chunks[0].chunk_type, ChunkType::Module);
+        assert_eq!(chunks[2].text, "```bash\nbrew install ck\n```\n");
+        assert_eq!(chunks[2].chunk_type, ChunkType::Text);
+        assert!(chunks[4].text.starts_with("## Linux\n### From source\n"));
+    }
+
+    #[test]
+    fn heading_path_follows_levels_and_skips_fenced_lines() {
+        assert_eq!(heading_path_at_line(GUIDE, 3), ["Install"]);
+        assert_eq!(heading_path_at_line(GUIDE, 10), ["Install", "macOS"]);
+        // `# not a heading` is inside a fence
+        assert_eq!(
+            heading_path_at_line(GUIDE, 20),
+            ["Install", "Linux", "From source"]
+        );
+        assert_eq!(heading_path_at_line(GUIDE, 26), ["Install", "Setext Usage"]);
+        assert!(heading_path_at_line("no headings\n", 1).is_empty());
+    }
+
+    #[test]
+    fn front_matter_and_rules_are_not_headings() {
+        let text = "---\ntitle: Guide\n---\nIntro\n\n- item\n---\n\n# Real\n";
+        let chunks = chunk_markdown(text);
+        assert_eq!(chunks.len(), 2);
+        assert_eq!(chunks[0].metadata.breadcrumb, None);
+        assert_eq!(chunks[1].metadata.breadcrumb.as_deref(), Some("Real"));
+    }
+}
@@ -0,0 +1,91 @@
+//! Built-in chunker for the extracted cells of a Jupyter notebook (see
+//! [`ck_core::notebook`]): one chunk per cell, named after it, so results
+//! point at `cell N` of the notebook. Large cells are strided like any other
+//! chunk. A chunker registered for `ipynb` takes precedence.
+
+use anyhow::Result;
+use ck_core::Span;
+use ck_core::notebook::{cell_header, is_notebook_file};
+use std::path::Path;
+use std::sync::Arc;
+
+use crate::custom::{Chunker, CustomChunk};
+
+struct NotebookChunker;
+
+impl Chunker for NotebookChunker {
+    fn chunk(&self, text: &str) -> Result<Vec<CustomChunk>> {
+        // (byte_start, line_start, cell) of the chunk being collected; text
+        // before the first header (a notebook that wasn't extracted) is
+        // chunked on its own without a name
+        let mut current: Option<(usize, usize, Option<usize>)> = None;
+        let mut chunks = Vec::new();
+        let mut offset = 0;
+        let mut lines = 0;
+        let mut finish = |start: (usize, usize, Option<usize>), byte_end, line_end| -> Result<()> {
+            let (byte_start, line_start, cell) = start;
+            chunks.push(CustomChunk {
+                span: Span::new(byte_start, byte_end, line_start, line_end)?,
+                symbol: cell.map(|cell| format!("cell {cell}")),
+            });
+            Ok(())
+        };
+        for (index, line) in text.split_inclusive('\n').enumerate() {
+            if let Some(cell) = cell_header(line.trim_end_matches(['\n', '\r'])) {
+                if let Some(start) = current.take() {
+                    finish(start, offset, index)?;
+                }
+                current = Some((offset, index + 1, Some(cell)));
+            } else if current.is_none() {
+                current = Some((offset, index + 1, None));
+            }
+            offset += line.len();
+            lines = index + 1;
+        }
+        if let Some(start) = current {
+            finish(start, offset, lines)?;
+        }
+        Ok(chunks)
+    }
+}
+
+/// The notebook chunker, for `.ipynb` files.
+pub(crate) fn chunker_for(path: &Path) -> Option<Arc<dyn Chunker>> {
+    is_notebook_file(path).then(|| Arc::new(NotebookChunker) as Arc<dyn Chunker>)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{ChunkStrategy, chunk_file_content};
+    use ck_core::notebook::{cell_at_line, extract_cells};
+    use std::path::Path;
+
+    #[test]
+    fn test_notebook_fixture_is_chunked_per_cell() {
+        let fixture = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures/analysis.ipynb");
+        let text = extract_cells(&std::fs::read_to_string(&fixture).unwrap()).unwrap();
+        let chunks =
+            chunk_file_content(&fixture, &text, None, None, 0, ChunkStrategy::SyntaxAware).unwrap();
+
+        // The raw cell (3) is left out, and outputs aren't source
+        let cells: Vec<_> = chunks
+            .iter()
+            .map(|c| c.metadata.breadcrumb.as_deref().unwrap())
+            .collect();
+        assert_eq!(cells, ["cell 1", "cell 2", "cell 4", "cell 5"]);
+        assert!(!text.contains("nbconvert") && !text.contains("1204.5"));
+
+        // Every span maps back to the cell it was cut from
+        for (chunk, cell) in chunks.iter().zip([1, 2, 4, 5]) {
+            assert_eq!(
+                &text[chunk.span.byte_start..chunk.span.byte_end],
+                chunk.text
+            );
+            assert_eq!(cell_at_line(&text, chunk.span.line_start), Some(cell));
+            assert_eq!(cell_at_line(&text, chunk.span.line_end), Some(cell));
+        }
+        let function = &chunks[2];
+        assert!(function.text.contains("def revenue_by_region(frame):"));
+        assert_eq!((function.span.line_start, function.span.line_end), (9, 15));
+    }
+}
@@ -0,0 +1,115 @@
+//! Built-in chunker for the extracted text of a PDF (see [`ck_core::pdf`]):
+//! each page is split into paragraphs at blank lines, and every chunk is
+//! named after its page, so results point at `page N` of the document. A
+//! chunk never spans a page break, and page header lines aren't indexed.
+//! A chunker registered for `pdf` takes precedence.
+
+use anyhow::Result;
+use ck_core::Span;
+use ck_core::pdf::{is_pdf_file, page_header};
+use std::path::Path;
+use std::sync::Arc;
+
+use crate::custom::{Chunker, CustomChunk};
+
+struct PdfChunker;
+
+impl Chunker for PdfChunker {
+    fn chunk(&self, text: &str) -> Result<Vec<CustomChunk>> {
+        // (byte_start, line_start, byte_end, line_end) of the paragraph being
+        // collected; text before the first header (a PDF cached without
+        // pages) is chunked without a name
+        let mut current: Option<(usize, usize, usize, usize)> = None;
+        let mut page = None;
+        let mut chunks = Vec::new();
+        let mut offset = 0;
+        let mut finish =
+            |paragraph: (usize, usize, usize, usize), page: Option<usize>| -> Result<()> {
+                let (byte_start, line_start, byte_end, line_end) = paragraph;
+                chunks.push(CustomChunk {
+                    span: Span::new(byte_start, byte_end, line_start, line_end)?,
+                    symbol: page.map(|page| format!("page {page}")),
+                });
+                Ok(())
+            };
+        for (index, line) in text.split_inclusive('\n').enumerate() {
+            let content = line.trim_end_matches(['\n', '\r']);
+            let header = page_header(content);
+            if header.is_some() || content.trim().is_empty() {
+                if let Some(paragraph) = current.take() {
+                    finish(paragraph, page)?;
+                }
+                page = header.or(page);
+            } else {
+                let (byte_start, line_start, _, _) =
+                    current.unwrap_or((offset, index + 1, offset, index + 1));
+                current = Some((byte_start, line_start, offset + line.len(), index + 1));
+            }
+            offset += line.len();
+        }
+        if let Some(paragraph) = current {
+            finish(paragraph, page)?;
+        }
+        Ok(chunks)
+    }
+}
+
+/// The PDF chunker, for `.pdf` files.
+pub(crate) fn chunker_for(path: &Path) -> Option<Arc<dyn Chunker>> {
+    is_pdf_file(path).then(|| Arc::new(PdfChunker) as Arc<dyn Chunker>)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{ChunkStrategy, chunk_file_content};
+    use ck_core::pdf::{join_pages, page_at_line};
+    use std::path::Path;
+
+    #[test]
+    fn test_pdf_text_is_chunked_per_page_paragraph() {
+        let text = join_pages(&[
+            "Design overview\n\nRetries back off exponentially.\nAt most five attempts."
+                .to_string(),
+            "Cache layout: one sidecar per file.".to_string(),
+        ]);
+        let chunks = chunk_file_content(
+            Path::new("docs/design.pdf"),
+            &text,
+            Some(ck_core::Language::Pdf),
+            None,
+            0,
+            ChunkStrategy::SyntaxAware,
+        )
+        .unwrap();
+
+        let summary: Vec<_> = chunks
+            .iter()
+            .map(|c| {
+                (
+                    c.metadata.breadcrumb.as_deref().unwrap(),
+                    c.text.as_str(),
+                    c.span.line_start,
+                    c.span.line_end,
+                )
+            })
+            .collect();
+        assert_eq!(
+            summary,
+            [
+                ("page 1", "Design overview\n", 2, 2),
+                (
+                    "page 1",
+                    "Retries back off exponentially.\nAt most five attempts.\n",
+                    4,
+                    5
+                ),
+                ("page 2", "Cache layout: one sidecar per file.\n", 7, 7),
+            ]
+        );
+        // Every span maps back to the page it was cut from
+        for (chunk, page) in chunks.iter().zip([1, 1, 2]) {
+            assert_eq!(page_at_line(&text, chunk.span.line_start), Some(page));
+            assert_eq!(page_at_line(&text, chunk.span.line_end), Some(page));
+        }
+    }
+}
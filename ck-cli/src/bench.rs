@@ -0,0 +1,161 @@
+//! `--bench`: repeatable search latency measurements.
+//!
+//! Each query from the queries file is run `--runs` times and the per-query
+//! p50/p95 latency is reported. Semantic searches reuse one
+//! [`ck_engine::LoadedIndex`], so loading the model and the sidecars is paid
+//! once up front instead of being counted in every query. Other modes go
+//! through the normal search path, whose index check is cheap once the index
+//! is current. The query embedding cache is turned off so that every run
+//! pays for embedding its query.
+
+use anyhow::{Result, bail};
+use ck_core::{SearchMode, SearchOptions};
+use serde::Serialize;
+use std::path::Path;
+use std::time::{Duration, Instant};
+
+use crate::progress::StatusReporter;
+
+#[derive(Debug, Serialize)]
+pub struct QueryBench {
+    pub query: String,
+    pub runs: usize,
+    pub p50_ms: f64,
+    pub p95_ms: f64,
+    /// Chunks each query was scored against (semantic mode only)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub candidates: Option<usize>,
+    pub results: usize,
+}
+
+/// Non-empty, non-comment (`#`) lines of the queries file.
+pub fn read_queries(path: &Path) -> Result<Vec<String>> {
+    let text = std::fs::read_to_string(path)
+        .map_err(|e| anyhow::anyhow!("Cannot read queries file {}: {e}", path.display()))?;
+    let queries: Vec<String> = text
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .map(str::to_string)
+        .collect();
+    if queries.is_empty() {
+        bail!("No queries found in {}", path.display());
+    }
+    Ok(queries)
+}
+
+pub async fn run_bench(
+    options: SearchOptions,
+    queries: &[String],
+    runs: usize,
+    status: &StatusReporter,
+) -> Result<Vec<QueryBench>> {
+    let runs = runs.max(1);
+    ck_embed::query_cache::set_query_cache_enabled(false);
+
+    // One untimed search brings the index up to date before anything is measured
+    let warmup_spinner = status.create_spinner("Preparing index...");
+    let mut warmup = options.clone();
+    warmup.query = queries[0].clone();
+    ck_engine::search_enhanced_with_outcome(&warmup, None, None, None).await?;
+    status.finish_progress(warmup_spinner, "Index ready");
+
+    let mut loaded = if options.mode == SearchMode::Semantic {
+        let load_spinner = status.create_spinner("Loading model and embeddings...");
+        let loaded = ck_engine::LoadedIndex::load(&options)?;
+        status.finish_progress(load_spinner, "Model and embeddings loaded");
+        Some(loaded)
+    } else {
+        None
+    };
+
+    let mut report = Vec::with_capacity(queries.len());
+    for query in queries {
+        let mut query_options = options.clone();
+        query_options.query = query.clone();
+        query_options.reindex = false;
+
+        let mut timings = Vec::with_capacity(runs);
+        let mut results = 0;
+        for _ in 0..runs {
+            let started = Instant::now();
+            let found = match loaded.as_mut() {
+                Some(index) => index.search(&query_options).await?,
+                None => {
+                    ck_engine::search_enhanced_with_outcome(&query_options, None, None, None)
+                        .await?
+                        .results
+                }
+            };
+            timings.push(started.elapsed());
+            results = found.matches.len();
+        }
+
+        report.push(QueryBench {
+            query: query.clone(),
+            runs,
+            p50_ms: millis(percentile(&mut timings, 50)),
+            p95_ms: millis(percentile(&mut timings, 95)),
+            candidates: loaded.as_ref().map(ck_engine::LoadedIndex::candidates),
+            results,
+        });
+    }
+    Ok(report)
+}
+
+/// Nearest-rank percentile of `timings`, which must not be empty.
+fn percentile(timings: &mut [Duration], pct: usize) -> Duration {
+    timings.sort();
+    let rank = (pct * timings.len()).div_ceil(100).max(1);
+    timings[rank - 1]
+}
+
+fn millis(duration: Duration) -> f64 {
+    duration.as_secs_f64() * 1000.0
+}
+
+pub fn print_table(report: &[QueryBench]) {
+    let width = report
+        .iter()
+        .map(|row| row.query.chars().count())
+        .max()
+        .unwrap_or(0)
+        .clamp(5, 48);
+    println!(
+        "{:<width$}  {:>4}  {:>9}  {:>9}  {:>10}  {:>7}",
+        "query", "runs", "p50 ms", "p95 ms", "candidates", "results"
+    );
+    for row in report {
+        let query: String = if row.query.chars().count() > width {
+            let mut cut: String = row.query.chars().take(width - 1).collect();
+            cut.push('…');
+            cut
+        } else {
+            row.query.clone()
+        };
+        let candidates = row
+            .candidates
+            .map_or_else(|| "-".to_string(), |n| n.to_string());
+        println!(
+            "{:<width$}  {:>4}  {:>9.2}  {:>9.2}  {:>10}  {:>7}",
+            query, row.runs, row.p50_ms, row.p95_ms, candidates, row.results
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_percentile_nearest_rank() {
+        let mut timings: Vec<Duration> = [5, 1, 4, 2, 3]
+            .into_iter()
+            .map(Duration::from_millis)
+            .collect();
+        assert_eq!(percentile(&mut timings, 50), Duration::from_millis(3));
+        assert_eq!(percentile(&mut timings, 95), Duration::from_millis(5));
+        let mut single = vec![Duration::from_millis(7)];
+        assert_eq!(percentile(&mut single, 50), Duration::from_millis(7));
+    }
+}
@@ -0,0 +1,71 @@
+//! Global coloring policy for `--color` and the `NO_COLOR` convention.
+//!
+//! All styled output goes through `console` (directly, or via indicatif),
+//! whose global switches are set here once at startup. The heatmap colors
+//! from `owo_colors` don't consult those switches, so callers check
+//! [`enabled`] before applying them.
+
+use std::ffi::OsStr;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, clap::ValueEnum)]
+pub enum ColorChoice {
+    /// Color when writing to a terminal, unless `NO_COLOR` is set
+    #[default]
+    Auto,
+    Always,
+    Never,
+}
+
+/// `NO_COLOR` disables color when set to any non-empty value (no-color.org).
+fn no_color_requested(value: Option<&OsStr>) -> bool {
+    value.is_some_and(|v| !v.is_empty())
+}
+
+/// The forced color state for `choice`, or `None` to keep console's own
+/// terminal detection.
+fn resolve(choice: ColorChoice, no_color: bool) -> Option<bool> {
+    match choice {
+        ColorChoice::Always => Some(true),
+        ColorChoice::Never => Some(false),
+        ColorChoice::Auto if no_color => Some(false),
+        ColorChoice::Auto => None,
+    }
+}
+
+/// Apply the policy to stdout and stderr styling for the rest of the process.
+/// Results written to a file (`--output`) are styled like stdout, and are
+/// only colored when asked for with `--color always`.
+pub fn apply(choice: ColorChoice, results_to_file: bool) {
+    let no_color = no_color_requested(std::env::var_os("NO_COLOR").as_deref());
+    if let Some(on) = resolve(choice, no_color || results_to_file) {
+        console::set_colors_enabled(on);
+    }
+    if let Some(on) = resolve(choice, no_color) {
+        console::set_colors_enabled_stderr(on);
+    }
+}
+
+/// Whether stdout output should be colored.
+pub fn enabled() -> bool {
+    console::colors_enabled()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_resolve_color_policy() {
+        assert_eq!(resolve(ColorChoice::Always, true), Some(true));
+        assert_eq!(resolve(ColorChoice::Never, false), Some(false));
+        assert_eq!(resolve(ColorChoice::Auto, true), Some(false));
+        assert_eq!(resolve(ColorChoice::Auto, false), None);
+    }
+
+    #[test]
+    fn test_no_color_requires_non_empty_value() {
+        assert!(!no_color_requested(None));
+        assert!(!no_color_requested(Some(OsStr::new(""))));
+        assert!(no_color_requested(Some(OsStr::new("1"))));
+    }
+}
@@ -4,20 +4,151 @@ use ck_core::{
     heatmap::{self, HeatmapBucket},
 };
 use clap::Parser;
+use color::ColorChoice;
 use console::style;
 use owo_colors::{OwoColorize, Rgb};
 use regex::RegexBuilder;
+use std::io::{IsTerminal, Write};
 use std::path::{Path, PathBuf};
 
+mod bench;
+mod color;
 mod mcp;
 mod mcp_server;
+mod models;
+mod output;
 mod path_utils;
+mod preview;
 mod progress;
+mod repl;
+mod symbols;
 // TUI is now in its own crate: ck-tui
 
+use output::ResultOutput;
 use path_utils::{build_include_patterns, expand_glob_patterns};
+use preview::PreviewWidth;
 use progress::StatusReporter;
 
+/// `--by` values for `--similar`.
+#[derive(Debug, Clone, Copy, Default, clap::ValueEnum)]
+enum SimilarGranularity {
+    /// Compare each file's mean chunk vector with the target's
+    #[default]
+    File,
+    /// Score each file by its closest chunk to any chunk of the target
+    Chunk,
+}
+
+impl From<SimilarGranularity> for ck_core::SimilarBy {
+    fn from(granularity: SimilarGranularity) -> Self {
+        match granularity {
+            SimilarGranularity::File => Self::File,
+            SimilarGranularity::Chunk => Self::Chunk,
+        }
+    }
+}
+
+/// `--on` values.
+#[derive(Debug, Clone, Copy, Default, clap::ValueEnum)]
+enum MatchUnit {
+    /// Each line (regex) or whole file (--lex)
+    #[default]
+    Lines,
+    /// Each chunk the index recorded
+    Chunks,
+}
+
+impl From<MatchUnit> for ck_core::MatchOn {
+    fn from(unit: MatchUnit) -> Self {
+        match unit {
+            MatchUnit::Lines => Self::Lines,
+            MatchUnit::Chunks => Self::Chunks,
+        }
+    }
+}
+
+/// `--chunk-strategy` values.
+#[derive(Debug, Clone, Copy, clap::ValueEnum)]
+enum ChunkStrategyArg {
+    /// Symbol-aware chunks for supported languages (the default)
+    Syntax,
+    /// Uniform windows sized for the embedding model
+    Fixed,
+    /// One chunk per blank-line-separated paragraph
+    Paragraph,
+}
+
+impl From<ChunkStrategyArg> for ck_chunk::ChunkStrategy {
+    fn from(strategy: ChunkStrategyArg) -> Self {
+        match strategy {
+            ChunkStrategyArg::Syntax => Self::SyntaxAware,
+            ChunkStrategyArg::Fixed => Self::FixedTokens,
+            ChunkStrategyArg::Paragraph => Self::Paragraph,
+        }
+    }
+}
+
+/// `--quantize` values.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+enum QuantizeArg {
+    /// Full-precision float32 vectors (the default)
+    None,
+    /// One byte per component, about a quarter of the size
+    Int8,
+    /// One bit per component, about a thirty-second of the size
+    Binary,
+}
+
+impl From<QuantizeArg> for ck_index::Quantization {
+    fn from(quantize: QuantizeArg) -> Self {
+        match quantize {
+            QuantizeArg::None => Self::None,
+            QuantizeArg::Int8 => Self::Int8,
+            QuantizeArg::Binary => Self::Binary,
+        }
+    }
+}
+
+/// `--path-style` values.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+enum PathStyle {
+    /// Relative to the current directory, with `..` for files outside it
+    Relative,
+    /// Absolute, with symlinks resolved
+    Absolute,
+}
+
+/// `--format` values: one line per result for editors' quickfix lists.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+enum OutputFormat {
+    /// `file:line:column:text`, like `rg --vimgrep`
+    Vimgrep,
+    /// `file:line:text`, like `grep -Hn`
+    Grep,
+}
+
+/// `--sort` values.
+#[derive(Debug, Clone, Copy, Default, clap::ValueEnum)]
+enum SortOrder {
+    /// Best score first (regex results come in path and line order)
+    #[default]
+    Score,
+    /// By file path, then line
+    Path,
+    /// By line number
+    Line,
+}
+
+impl From<SortOrder> for ck_core::ResultSort {
+    fn from(order: SortOrder) -> Self {
+        match order {
+            SortOrder::Score => Self::Score,
+            SortOrder::Path => Self::Path,
+            SortOrder::Line => Self::Line,
+        }
+    }
+}
+
 #[derive(Parser)]
 #[command(name = "ck")]
 #[command(about = "Semantic grep by embedding - seek code, semantically")]
@@ -31,22 +162,36 @@ QUICK START EXAMPLES:
     ck -i "TODO" .                     # Case-insensitive search  
     ck -r "fn main" .                  # Recursive search
     ck -n "import" lib.py              # Show line numbers
+    cat app.log | ck "timeout" -       # Search standard input
 
   Semantic search (finds conceptually similar code):
     ck --sem "error handling" src/     # Builds/updates the index automatically (top 10, threshold ≥0.6)
     ck --sem "database connection"     # Find DB-related code  
     ck --sem --limit 5 "authentication"    # Limit to top 5 results
     ck --sem --threshold 0.8 "auth"   # Higher precision filtering
+    ck --similar src/foo.rs --topk 10 # Files most similar to src/foo.rs
+    ck --similar src/foo.rs --by chunk src/  # Rank by best-matching chunk instead of whole file
+    ck --bench queries.txt --sem --runs 5    # p50/p95 latency per query (add --json for plotting)
+    ck --repl --sem src/                     # Interactive queries; :lex / :topk 5 / :help
+    ck --sem "locking bug" --at v1.2.0       # Search a tag's tree without checking it out
+    ck --sem "null deref" --modified-since 7d # Only files modified in the last week
+    ck --sem "retry logic" --ephemeral ci/   # Embed in memory, write no index
 
   Lexical search (BM25 full-text search):
     ck --lex "user authentication"    # Full-text search with ranking
     ck --lex "http client request"    # Better than regex for phrases
+    ck --lex "retry" --bm25-b 0.3     # Penalize long files less
+    ck --lex "retry backoff" --proximity 1  # Favor files with the words side by side
+    ck --lex "retry" --on chunks      # Rank functions and other indexed chunks, not files
 
   Hybrid search (combines regex + semantic):  
     ck --hybrid "async function"      # Best of both worlds
     ck --hybrid "error" --limit 10    # Top 10 most relevant results (--limit is alias for --topk)
+    ck --sem "retry" --sort path      # Best 10 results, listed by file then line
+    ck --sem "retry" --offset 10      # Results 11-20 (the second page)
     ck --hybrid "bug" --threshold 0.02 # Only results with RRF score >= 0.02
     ck --sem "auth" --scores           # Show similarity scores in output
+    ck --sem "auth" --explain          # Show how each score came about
 
   Index management:
     ck --status .                     # Check index status
@@ -54,8 +199,18 @@ QUICK START EXAMPLES:
     ck --clean-orphans .               # Clean up orphaned files
     ck --clean .                       # Remove entire index
     ck --switch-model nomic-v1.5       # Clean + rebuild with a different embedding model
-    ck --add file.rs                   # Add single file to index
+    ck --add new.rs src/feature/       # Index just these files and directories
     ck --index .                       # Optional: pre-build before CI runs
+    ck --index --dry-run --verbose .   # Preview what indexing would pick up
+    ck --count-tokens .                # Tokens indexing would embed, per language
+    ck --index --compress .            # Smaller index: zstd-compressed embedding sidecars
+    ck --index --quantize int8 .       # About 4x smaller vectors, nearly the same ranking
+    ck --index --reduce-dim 256 .      # Shorter vectors for faster scoring on large indexes
+    ck --index --prune .               # Also drop files .ckignore now excludes
+    ck --index --ann .                 # Approximate nearest-neighbour index for large trees
+    ck --show-config                   # Show settings from .ck/config.toml, defaults and flags
+    ck --export . > index.jsonl        # Dump indexed chunks as JSONL (add --with-vectors for embeddings)
+    ck --import index.jsonl .          # Rebuild sidecars from a dump
 
   JSON output for tools/scripts:
     ck --json --sem "bug fix" src/    # Traditional JSON (single array)
@@ -65,6 +220,7 @@ QUICK START EXAMPLES:
     ck --jsonl "auth" --no-snippet    # Streaming, memory-efficient format
     ck --jsonl --sem "error" src/     # Perfect for LLM/agent consumption
     ck --jsonl --topk 5 --threshold 0.8 "func"  # High-confidence agent results
+    ck --jsonl -o results.jsonl "auth" src/    # Results to a file, progress on stderr
     # Why JSONL? Streaming, error-resilient, standard in AI pipelines
 
   Advanced grep features:
@@ -72,12 +228,20 @@ QUICK START EXAMPLES:
     ck -A 3 -B 1 "TODO"              # 3 lines after, 1 before
     ck -w "test" .                    # Match whole words only
     ck -F "log.Error()" .             # Fixed string (no regex)
+    ck --no-unicode -i "timeout" app.log  # ASCII-only regex for big logs
+    ck --timeout 2s "TODO" ~/src      # Whatever turns up within 2 seconds
+    ck --max-depth 2 "TODO" .         # Root and its immediate subdirectories only
+    ck --no-generated "Handler" .     # Skip generated files (*.pb.go, DO NOT EDIT headers)
+    ck --group-by-symbol "retry" src/ # One entry per function with all its matches
+    ck -j 4 "TODO" ~/src              # Scan with 4 threads (default: one per CPU)
 
   Model and embedding options:
+    ck --models                        # Available models, their limits, and which are downloaded
     ck --index --model nomic-v1.5      # Index with higher-quality model (8k context)
     ck --index --model jina-code       # Index with code-specialized model
     ck --sem "auth" --rerank           # Enable reranking for better relevance
     ck --sem "login" --rerank-model bge # Use specific reranking model
+    ck --sem "auth" --rerank --rerank-k 50 --scores  # Rescore top 50, show original similarity
 
   AI agent integration (MCP):
     ck --serve                         # Start MCP server for Claude/Cursor integration
@@ -115,6 +279,42 @@ struct Cli {
     #[arg(short = 'H', help = "Always print filenames")]
     with_filenames: bool,
 
+    #[arg(
+        long = "heading",
+        overrides_with = "no_heading",
+        help = "Group results by file: the filename once as a header, its results beneath, and a blank line between files. The default when output is a terminal"
+    )]
+    heading: bool,
+
+    #[arg(
+        long = "no-heading",
+        overrides_with = "heading",
+        help = "Print the filename on every result instead of grouping them under a header (the default when output is piped)"
+    )]
+    no_heading: bool,
+
+    #[arg(
+        long = "group-by-symbol",
+        help = "Collapse results inside the same function, method or class (or Markdown section) into one entry for it: the symbol's name and line span, at its best result's score and preview"
+    )]
+    group_by_symbol: bool,
+
+    #[arg(
+        long = "path-style",
+        value_name = "STYLE",
+        value_enum,
+        help = "Print result paths relative to the current directory or absolute, the same way in every output format (default: as found, which is absolute)"
+    )]
+    path_style: Option<PathStyle>,
+
+    #[arg(
+        short = 'o',
+        long = "output",
+        value_name = "FILE",
+        help = "Write results to FILE instead of stdout, replacing it once the search finishes; progress and summaries stay on stderr"
+    )]
+    output: Option<PathBuf>,
+
     #[arg(
         short = 'l',
         long = "files-with-matches",
@@ -125,16 +325,35 @@ struct Cli {
     #[arg(
         short = 'L',
         long = "files-without-matches",
-        help = "Print only names of files without matches"
+        help = "Print only names of files without matches (semantic: files with no chunk scoring at or above --threshold)"
     )]
     files_without_matches: bool,
 
     #[arg(short = 'i', long = "ignore-case", help = "Case insensitive search")]
     ignore_case: bool,
 
-    #[arg(short = 'w', long = "word-regexp", help = "Match whole words only")]
+    #[arg(
+        short = 'w',
+        long = "word-regexp",
+        help = "Match whole words only (regex and --lex; with --sem/--hybrid, limits highlighting and -L)"
+    )]
     word_regexp: bool,
 
+    #[arg(
+        short = 'v',
+        long = "invert-match",
+        help = "Select what doesn't match: non-matching lines (regex) or files with no hit (--lex); not supported with --sem or --hybrid"
+    )]
+    invert_match: bool,
+
+    #[arg(
+        long = "replace",
+        value_name = "TEMPLATE",
+        conflicts_with_all = ["context", "after_context", "before_context", "full_section"],
+        help = "Print each regex match as TEMPLATE expanded against its capture groups ($1, ${name}; $$ for a literal $) instead of the matching line. Files are never modified; rejected with --sem, --lex, --hybrid and -v"
+    )]
+    replace: Option<String>,
+
     #[arg(
         short = 'F',
         long = "fixed-strings",
@@ -142,6 +361,12 @@ struct Cli {
     )]
     fixed_strings: bool,
 
+    #[arg(
+        long = "no-unicode",
+        help = "Compile the regex without Unicode support: faster on large ASCII files such as logs. `.`, classes and -i cover ASCII only; other bytes match only themselves"
+    )]
+    no_unicode: bool,
+
     #[arg(
         short = 'R',
         short_alias = 'r',
@@ -195,6 +420,34 @@ struct Cli {
     #[arg(long = "regex", help = "Regex search mode (default, grep-compatible)")]
     regex: bool,
 
+    #[arg(
+        long = "on",
+        value_name = "UNIT",
+        value_enum,
+        default_value_t = MatchUnit::Lines,
+        conflicts_with_all = ["semantic", "hybrid", "similar"],
+        help = "What regex and --lex search match: lines (the default; whole files for --lex) or chunks, the index's chunks, reported with their span and symbol like --sem results. chunks needs an index"
+    )]
+    match_on: MatchUnit,
+
+    #[arg(
+        long = "similar",
+        value_name = "FILE",
+        conflicts_with_all = ["lexical", "hybrid", "regex", "rerank"],
+        help = "Find indexed code similar to FILE (semantic; positional arguments are search paths). The file itself is excluded"
+    )]
+    similar: Option<PathBuf>,
+
+    #[arg(
+        long = "by",
+        value_name = "GRANULARITY",
+        value_enum,
+        default_value_t = SimilarGranularity::File,
+        requires = "similar",
+        help = "How --similar compares files: file (mean of each file's chunk vectors) or chunk (best-matching chunk)"
+    )]
+    similar_by: SimilarGranularity,
+
     #[arg(
         long = "topk",
         alias = "limit",
@@ -203,6 +456,59 @@ struct Cli {
     )]
     top_k: Option<usize>,
 
+    #[arg(
+        short = 'm',
+        long = "max-count",
+        value_name = "N",
+        help = "Stop after N results across all files. With --topk as well, the smaller of the two applies"
+    )]
+    max_count: Option<usize>,
+
+    #[arg(
+        long = "max-matches-per-file",
+        value_name = "N",
+        help = "Show at most N results from any one file (its best N in ranked modes, its first N for regex), so one file can't crowd out the rest. Applied before --topk and --max-count"
+    )]
+    max_matches_per_file: Option<usize>,
+
+    #[arg(
+        long = "timeout",
+        value_name = "DURATION",
+        value_parser = parse_timeout,
+        help = "Give the search at most DURATION (2s, 500ms, 1m), including any automatic indexing, and print the results found by then, noting they're truncated"
+    )]
+    timeout: Option<std::time::Duration>,
+
+    #[arg(
+        long = "offset",
+        value_name = "N",
+        default_value_t = 0,
+        help = "Skip the N best results and show the next --topk, to page through a ranking (e.g. --topk 10 --offset 20 for the third page). The skipped results are still ranked, so deep pages cost as much as the first"
+    )]
+    offset: usize,
+
+    #[arg(
+        long = "exact",
+        help = "Score every chunk even when the index has an approximate (--ann) index"
+    )]
+    exact: bool,
+
+    #[arg(
+        long = "ef-search",
+        value_name = "N",
+        help = "Candidates the approximate (--ann) index explores per query (default 64, never fewer than --topk). Higher finds more of the true nearest chunks, at the cost of speed"
+    )]
+    ef_search: Option<usize>,
+
+    #[arg(
+        long = "sort",
+        value_name = "ORDER",
+        value_enum,
+        default_value_t = SortOrder::Score,
+        help = "Order results by score, path (then line) or line. --topk still keeps the best-scoring results; this only reorders them for display"
+    )]
+    sort: SortOrder,
+
     #[arg(
         long = "threshold",
         value_name = "SCORE",
@@ -210,9 +516,29 @@ struct Cli {
     )]
     threshold: Option<f32>,
 
+    #[arg(
+        long = "threshold-percentile",
+        value_name = "N",
+        conflicts_with = "threshold",
+        help = "Keep results scoring at or above the Nth percentile (0-100) of this query's candidate scores, instead of a fixed --threshold"
+    )]
+    threshold_percentile: Option<f32>,
+
     #[arg(long = "scores", help = "Show similarity scores in output")]
     show_scores: bool,
 
+    #[arg(
+        long = "explain",
+        help = "Under each result, show how its score came about: the raw similarity or BM25 score, any rerank change, hybrid sub-ranks and how they were fused, the threshold it was compared with, and the matched chunk"
+    )]
+    explain: bool,
+
+    #[arg(
+        long = "stats",
+        help = "After the results, print to stderr how long each stage of the search took: index update, model load, query embedding, candidate load, scoring, rerank and output formatting"
+    )]
+    stats: bool,
+
     #[arg(long = "json", help = "Output results as JSON for tools/scripts")]
     json: bool,
 
@@ -222,19 +548,90 @@ struct Cli {
     #[arg(long = "jsonl", help = "Output results as JSONL for agent workflows")]
     jsonl: bool,
 
+    #[arg(
+        long = "format",
+        value_name = "FORMAT",
+        value_enum,
+        conflicts_with_all = ["json", "json_v1", "jsonl", "files_with_matches", "files_without_matches", "context", "after_context", "before_context", "full_section"],
+        help = "Print one line per result for an editor's quickfix list: vimgrep (file:line:column:text, usable as vim's grepprg) or grep (file:line:text). Ranked results use their first line and column 1"
+    )]
+    format: Option<OutputFormat>,
+
     #[arg(long = "no-snippet", help = "Exclude code snippets from JSONL output")]
     no_snippet: bool,
 
     #[arg(long = "reindex", help = "Force index update before searching")]
     reindex: bool,
 
+    #[arg(
+        long = "dedup",
+        value_name = "SIMILARITY",
+        num_args = 0..=1,
+        require_equals = true,
+        help = "Semantic: hide results whose chunk is a near copy (embedding cosine ≥ SIMILARITY, default 0.95; set with --dedup=0.9) of a higher-ranked one, so --topk counts distinct places; each kept result notes how many it stands for"
+    )]
+    dedup: Option<Option<f32>>,
+
+    #[arg(
+        long = "at",
+        value_name = "REF",
+        conflicts_with_all = ["files_without_matches", "similar"],
+        help = "Search the tree of a git commit, tag or branch without checking it out. Its files are extracted and indexed under .ck/refs/<commit>, so repeat searches are fast; results are shown as REF:path"
+    )]
+    at: Option<String>,
+
+    #[arg(
+        long = "no-stale",
+        help = "Drop results from files that changed since they were indexed"
+    )]
+    no_stale: bool,
+
+    #[arg(
+        long = "no-auto-index",
+        conflicts_with = "reindex",
+        help = "Search the existing index without updating it first (semantic, lexical and hybrid modes normally index new and changed files before searching). Fails when there is no index and warns when files changed since it was built"
+    )]
+    no_auto_index: bool,
+
+    #[arg(
+        long = "ephemeral",
+        conflicts_with_all = ["reindex", "no_auto_index", "index", "add", "lexical", "hybrid", "regex", "similar"],
+        help = "Semantic search without an index: chunk and embed the searched files in memory, search, and discard everything. Nothing is written to disk, so it suits CI, sandboxes and read-only trees, but every run re-embeds (slow for large trees). PDFs and notebooks are skipped"
+    )]
+    ephemeral: bool,
+
     #[arg(
         long = "exclude",
         value_name = "PATTERN",
-        help = "Exclude directories matching pattern (can be used multiple times)"
+        help = "Exclude files and directories matching a gitignore-style pattern (can be used multiple times). Applied in order, last match winning: '!PATTERN' re-includes what an earlier --exclude excluded"
     )]
     exclude: Vec<String>,
 
+    #[arg(
+        long = "include",
+        value_name = "GLOB",
+        help = "Only index/search files matching glob, relative to the target path (e.g. \"src/**/*.rs\"; can be used multiple times). --exclude wins when both match"
+    )]
+    include: Vec<String>,
+
+    #[arg(
+        long = "files-from",
+        value_name = "LIST",
+        conflicts_with = "files",
+        help = "Search exactly the files listed in LIST (- for stdin), one per line or NUL-separated, instead of walking a directory; excludes and ignore files don't apply (e.g. git diff --name-only | ck --files-from - --sem \"query\"). An entry PATH:START-END keeps only results overlapping those lines of the file"
+    )]
+    files_from: Option<PathBuf>,
+
+    #[arg(
+        long = "modified-since",
+        visible_alias = "after",
+        value_name = "WHEN",
+        value_parser = parse_modified_since,
+        conflicts_with = "at",
+        help = "Only search files modified since WHEN: a duration back from now (30m, 12h, 7d, 2w) or a date (2024-05-01, local midnight) or RFC 3339 timestamp. Files are filtered before searching, in every mode"
+    )]
+    modified_since: Option<std::time::SystemTime>,
+
     #[arg(
         long = "no-default-excludes",
         help = "Disable default directory exclusions (like .git, node_modules, etc.)"
@@ -249,26 +646,128 @@ struct Cli {
 
     #[arg(
         long = "hidden",
-        help = "Include hidden (dot-prefixed) files and directories"
+        visible_alias = "include-hidden",
+        help = "Include hidden (dot-prefixed) files and directories when indexing and searching. They are skipped by default, like ripgrep. .gitignore and .ckignore rules still apply to them, and .git/ stays excluded unless named by --include (e.g. --include '.git/**' on a regex search)"
     )]
     hidden: bool,
 
+    #[arg(
+        long = "follow",
+        help = "Follow symbolic links when indexing and searching. Links are not followed by default; with --follow, symlink cycles are reported and skipped, and a file reached through several links is searched once"
+    )]
+    follow: bool,
+
+    #[arg(
+        long = "index-submodules",
+        help = "Index and search Git submodules too. They're skipped by default, since each has its own history; their files are listed under the top repository's paths"
+    )]
+    index_submodules: bool,
+
+    #[arg(
+        long = "max-depth",
+        value_name = "N",
+        help = "Descend at most N directory levels below each search or index root (1 = only files directly in it). Excluded and ignored directories are skipped before depth applies"
+    )]
+    max_depth: Option<usize>,
+
+    #[arg(
+        long = "no-generated",
+        help = "Leave out generated files: protobuf output, *_generated.rs and the like, or files whose first lines say DO NOT EDIT or @generated. Add names and markers with generated_patterns and generated_markers in .ck/config.toml"
+    )]
+    no_generated: bool,
+
+    #[arg(
+        long = "print-json-schema",
+        help = "Print the JSON Schema of --json result records (of --jsonl records with --jsonl) and exit"
+    )]
+    print_json_schema: bool,
+
     #[arg(
         long = "print-default-ckignore",
         help = "Print the default .ckignore content that ck generates and exit"
     )]
     print_default_ckignore: bool,
 
+    #[arg(
+        long = "verify-models",
+        help = "Check downloaded models for partial or corrupted files, remove them and download them again, then exit"
+    )]
+    verify_models: bool,
+
+    #[arg(
+        long = "models",
+        help = "List the embedding models --model accepts, with their dimensions, token window and chunk size, and whether each is downloaded (and where), then exit. --json for machine output"
+    )]
+    models: bool,
+
+    #[arg(
+        long = "warmup",
+        help = "Download (if needed) and load the embedding model from --model, run one embedding, report the time taken, then exit"
+    )]
+    warmup: bool,
+
+    #[arg(
+        long = "show-config",
+        help = "Print the effective settings (.ck/config.toml merged with defaults and flags) and exit"
+    )]
+    show_config: bool,
+
+    #[arg(
+        long = "bench",
+        value_name = "QUERIES",
+        help = "Time each query in QUERIES (one per line) and report p50/p95 latency; honours the search mode flags and --json"
+    )]
+    bench: Option<PathBuf>,
+
+    #[arg(
+        long = "runs",
+        value_name = "N",
+        default_value_t = 5,
+        requires = "bench",
+        help = "Timed runs per query for --bench"
+    )]
+    runs: usize,
+
+    #[arg(
+        long = "repl",
+        help = "Interactive search: read queries from stdin against one warmed-up index (:help lists commands)"
+    )]
+    repl: bool,
+
     #[arg(
         long = "full-section",
         help = "Return complete code sections (functions/classes) instead of just matching lines. Uses tree-sitter to identify semantic boundaries. Supported: Python, JavaScript, TypeScript, Rust, Go, C, C++, Ruby, Haskell, C#, Zig, Dart, Elixir"
     )]
     full_section: bool,
 
+    #[arg(
+        long = "snippet-lines",
+        value_name = "N",
+        help = "Show N lines of each semantic result's chunk, centered on the line that best matches the query, instead of its first lines",
+        conflicts_with = "full_section"
+    )]
+    snippet_lines: Option<usize>,
+
+    #[arg(
+        long = "preview-width",
+        value_name = "N",
+        help = "Cut preview lines to N display columns, keeping the match in view (0: never cut). Defaults to fitting the terminal when output is one, and to not cutting when piped"
+    )]
+    preview_width: Option<usize>,
+
+    #[arg(
+        long = "color",
+        value_name = "WHEN",
+        value_enum,
+        default_value_t = ColorChoice::Auto,
+        help = "When to color output: auto (only on a terminal, and not when NO_COLOR is set), always, never"
+    )]
+    color: ColorChoice,
+
     #[arg(
         short = 'q',
         long = "quiet",
-        help = "Suppress status messages and progress indicators"
+        help = "Suppress status messages and progress indicators; searches print no results either, only setting the exit status (0 matches, 1 none, 2 error)"
     )]
     quiet: bool,
 
@@ -285,6 +784,18 @@ struct Cli {
     #[arg(long = "clean-orphans", help = "Clean only orphaned index files")]
     clean_orphans: bool,
 
+    #[arg(
+        long = "gc",
+        help = "Compact the index: drop orphaned entries, rewrite every sidecar in the current format (with the index's --compress/--quantize settings) and remove leftovers of interrupted writes, then report the space reclaimed. Safe to interrupt; --json for machine output"
+    )]
+    gc: bool,
+
+    #[arg(
+        long = "migrate",
+        help = "Upgrade an index built by an older ck to the current index format, rewriting its sidecars without re-embedding anything, then exit. Sidecars no migration covers are listed; rebuild those with --reindex"
+    )]
+    migrate: bool,
+
     #[arg(
         long = "switch-model",
         value_name = "NAME",
@@ -309,7 +820,119 @@ struct Cli {
     )]
     force: bool,
 
-    #[arg(long = "add", help = "Add a single file to the index")]
+    #[arg(
+        long = "resume",
+        help = "Continue an interrupted --index or --switch-model run from its checkpoint, skipping files it already finished (unless they changed since)"
+    )]
+    resume: bool,
+
+    #[arg(
+        long = "dry-run",
+        help = "With --index, report which files would be indexed, skipped as up to date, or excluded, without indexing anything",
+        requires = "index"
+    )]
+    dry_run: bool,
+
+    #[arg(
+        long = "verbose",
+        help = "Warn about --exclude, --include and .ckignore patterns that match nothing (likely typos), and list the entries the walk skipped (unreadable, or a symlink loop under --follow). With --dry-run, also list the decision for every file and the rule that excluded it"
+    )]
+    verbose: bool,
+
+    #[arg(
+        long = "chunk-overlap",
+        value_name = "LINES",
+        help = "Lines of preceding context repeated at the start of each chunk when indexing (rebuilds the index if it changes; also applies to --inspect)"
+    )]
+    chunk_overlap: Option<usize>,
+
+    #[arg(
+        long = "chunk-strategy",
+        value_name = "STRATEGY",
+        value_enum,
+        help = "How files are split into chunks when indexing: syntax, fixed or paragraph (rebuilds the index if it changes; also applies to --inspect)"
+    )]
+    chunk_strategy: Option<ChunkStrategyArg>,
+
+    #[arg(
+        long = "file-timeout",
+        value_name = "SECS",
+        default_value_t = ck_index::DEFAULT_FILE_TIMEOUT.as_secs(),
+        help = "When indexing, skip a file that takes longer than SECS to chunk and embed (such as a minified bundle) and list it at the end; 0 means no limit"
+    )]
+    file_timeout: u64,
+
+    #[arg(
+        long = "embed-batch-size",
+        value_name = "N",
+        help = "When indexing, embed N chunks per model call (default depends on the model; capped at the model's maximum). Larger batches are faster on big machines, smaller ones use less memory"
+    )]
+    embed_batch_size: Option<std::num::NonZeroUsize>,
+
+    #[arg(
+        short = 'j',
+        long = "jobs",
+        value_name = "N",
+        help = "Search and index N files at a time (default: one per CPU). Results come out in the same order whatever N is"
+    )]
+    jobs: Option<std::num::NonZeroUsize>,
+
+    #[arg(
+        long = "force-hash",
+        help = "When updating the index, hash every file's content instead of trusting an unchanged mtime and size. Catches edits that kept the old timestamp, e.g. after a checkout or a build tool reset mtimes"
+    )]
+    force_hash: bool,
+
+    #[arg(
+        long = "log-file",
+        value_name = "PATH",
+        requires = "index",
+        help = "With --index, write one JSON line per file to PATH: its path, decision (indexed, skipped, excluded or timeout), reason, chunk count and duration. Overwritten on every run; keep it outside the indexed tree or exclude it"
+    )]
+    log_file: Option<PathBuf>,
+
+    #[arg(
+        long = "compress",
+        requires = "index",
+        help = "Store embedding sidecars zstd-compressed; recorded in the index, and existing sidecars are converted in place"
+    )]
+    compress: bool,
+
+    #[arg(
+        long = "quantize",
+        value_name = "PRECISION",
+        value_enum,
+        requires = "index",
+        help = "Store embedding vectors at reduced precision: int8 (about 4x smaller) or binary (about 32x smaller, scored by Hamming distance), or none. Recorded in the index; existing sidecars are converted in place, or re-embedded when going back to a finer precision"
+    )]
+    quantize: Option<QuantizeArg>,
+
+    #[arg(
+        long = "reduce-dim",
+        value_name = "DIMS",
+        requires = "index",
+        help = "Project the embedding vectors onto their DIMS principal components (e.g. 256), fitted from the index's own vectors; search scores shorter vectors for a small loss in recall. Recorded in the index with the projection, so queries are projected the same way. 0 keeps the model's dimensions; changing a reduced index rebuilds it"
+    )]
+    reduce_dim: Option<usize>,
+
+    #[arg(
+        long = "prune",
+        requires = "index",
+        help = "Also remove index entries for files the current ignore rules (.gitignore, .ckignore, --exclude) now exclude, or that no longer exist"
+    )]
+    prune: bool,
+
+    #[arg(
+        long = "ann",
+        requires = "index",
+        help = "Also keep an approximate nearest-neighbour (HNSW) index, so semantic searches with --topk score a few hundred chunks instead of all of them. Recorded in the index and rebuilt whenever the index changes"
+    )]
+    ann: bool,
+
+    #[arg(
+        long = "add",
+        help = "Add or update the given files and directories in the index without rescanning the rest of the tree. Directories are walked with the usual exclude, .gitignore and .ckignore rules"
+    )]
     add: bool,
 
     #[arg(long = "status", help = "Show index status and statistics")]
@@ -318,7 +941,10 @@ struct Cli {
     #[arg(long = "status-verbose", help = "Show detailed index statistics")]
     status_verbose: bool,
 
-    #[arg(long = "status-json", help = "Output index status as JSON")]
+    #[arg(
+        long = "status-json",
+        help = "Output index status as JSON (same as --status --json); exits 1 when there is no index"
+    )]
     status_json: bool,
 
     #[arg(
@@ -327,12 +953,45 @@ struct Cli {
     )]
     inspect: bool,
 
+    #[arg(
+        long = "count-tokens",
+        help = "Count the tokens indexing PATH would embed, per language and in total, without indexing anything. Uses the same file rules and chunking as --index; --model picks the tokenizer, --json prints machine-readable totals"
+    )]
+    count_tokens: bool,
+
+    #[arg(
+        long = "diff-index",
+        help = "Show how PATH has drifted from its index, without changing it: files new, modified (content hash changed) or deleted since the last update, with counts. Uses the same file rules as --index; --json for machine output"
+    )]
+    diff_index: bool,
+
     #[arg(
         long = "dump-chunks",
         help = "Visualize chunk boundaries for a file using the same rendering as TUI chunk mode"
     )]
     dump_chunks: bool,
 
+    #[arg(
+        long = "export",
+        help = "Write every indexed chunk for the path to stdout as JSONL (reads existing sidecars, nothing is re-embedded)"
+    )]
+    export: bool,
+
+    #[arg(
+        long = "with-vectors",
+        help = "Include embedding vectors in --export output",
+        requires = "export"
+    )]
+    with_vectors: bool,
+
+    #[arg(
+        long = "import",
+        value_name = "FILE",
+        help = "Rebuild index sidecars for the path from a JSONL dump produced by --export ('-' reads stdin)",
+        conflicts_with = "export"
+    )]
+    import: Option<PathBuf>,
+
     // Model selection (index-time only)
     #[arg(
         long = "model",
@@ -341,6 +1000,11 @@ struct Cli {
     )]
     model: Option<String>,
 
+    /// Embedding model per language, from `[language_models]` in
+    /// `.ck/config.toml`; there is no flag for it
+    #[arg(skip)]
+    language_models: std::collections::BTreeMap<String, String>,
+
     // Search-time enhancement options
     #[arg(
         long = "rerank",
@@ -355,18 +1019,68 @@ struct Cli {
     )]
     rerank_model: Option<String>,
 
+    #[arg(
+        long = "rerank-k",
+        value_name = "N",
+        help = "Number of top semantic candidates to rescore when reranking [default: 20]"
+    )]
+    rerank_k: Option<usize>,
+
+    #[arg(
+        long = "ignore-model-mismatch",
+        help = "Skip indexed files embedded with a different model or dimension instead of failing semantic search"
+    )]
+    ignore_model_mismatch: bool,
+
+    #[arg(
+        long = "fuzzy",
+        help = "Typo-tolerant lexical search: also match indexed terms within edit distance 2 of each query term, ranked below exact matches (slower)",
+        requires = "lexical"
+    )]
+    fuzzy: bool,
+
+    #[arg(
+        long = "bm25-k1",
+        value_name = "K1",
+        requires = "lexical",
+        help = "BM25 term-frequency saturation for --lex (default 1.2): how much repeated occurrences of a term raise a file's score. 0 ignores repeats"
+    )]
+    bm25_k1: Option<f32>,
+
+    #[arg(
+        long = "bm25-b",
+        value_name = "B",
+        requires = "lexical",
+        help = "BM25 length normalization for --lex, from 0 to 1 (default 0.75): how much a term in a long file counts for less than in a short one"
+    )]
+    bm25_b: Option<f32>,
+
+    #[arg(
+        long = "proximity",
+        value_name = "WEIGHT",
+        requires = "lexical",
+        help = "Rank --lex results higher when query terms appear near each other: a file's score is multiplied by up to 1 + WEIGHT, the full amount when the terms are adjacent (e.g. 1). 0, the default, turns it off"
+    )]
+    proximity: Option<f32>,
+
+    #[arg(
+        long = "split-identifiers",
+        help = "Split camelCase, snake_case and kebab-case identifiers in the query into words (getUserById -> get user by id) before embedding; with --lex, match either form"
+    )]
+    split_identifiers: bool,
+
     // MCP Server mode
     #[arg(
         long = "serve",
         help = "Start MCP server mode for AI agent integration",
         conflicts_with_all = [
-            "pattern", "files", "line_numbers", "no_filenames", "with_filenames",
+            "pattern", "files", "line_numbers", "no_filenames", "with_filenames", "heading", "no_heading",
             "files_with_matches", "files_without_matches", "ignore_case", "word_regexp",
             "fixed_strings", "recursive", "context", "after_context", "before_context",
-            "semantic", "lexical", "hybrid", "regex", "top_k", "threshold", "show_scores",
+            "semantic", "lexical", "hybrid", "regex", "top_k", "threshold", "threshold_percentile", "show_scores",
             "json", "json_v1", "jsonl", "no_snippet", "reindex", "exclude", "no_default_excludes",
-            "no_ignore", "full_section", "index", "clean", "clean_orphans", "switch_model",
-            "force", "add", "status", "status_verbose", "inspect", "dump_chunks", "model", "rerank", "rerank_model", "tui"
+            "no_ignore", "full_section", "index", "clean", "clean_orphans", "gc", "migrate", "switch_model",
+            "force", "add", "status", "status_verbose", "inspect", "dump_chunks", "model", "rerank", "rerank_model", "rerank_k", "ignore_model_mismatch", "show_config", "export", "with_vectors", "import", "fuzzy", "split_identifiers", "include", "resume", "chunk_overlap", "similar", "color", "no_stale", "bench", "runs", "dry_run", "verbose", "snippet_lines", "repl", "compress", "quantize", "ann", "chunk_strategy", "verify_models", "at", "dedup", "path_style", "format", "no_auto_index", "file_timeout", "sort", "explain", "count_tokens", "modified_since", "offset", "exact", "ef_search", "max_count", "max_matches_per_file", "log_file", "invert_match", "warmup", "preview_width", "files_from", "embed_batch_size", "diff_index", "stats", "force_hash", "replace", "follow", "reduce_dim", "no_unicode", "prune", "timeout", "index_submodules", "max_depth", "ephemeral", "bm25_k1", "bm25_b", "proximity", "output", "no_generated", "group_by_symbol", "jobs", "models", "match_on", "tui"
         ]
     )]
     serve: bool,
@@ -376,13 +1090,13 @@ struct Cli {
         long = "tui",
         help = "Interactive TUI mode - like fzf but semantic. Live search with arrow keys, Tab to switch modes, Enter to open in $EDITOR",
         conflicts_with_all = [
-            "line_numbers", "no_filenames", "with_filenames",
+            "line_numbers", "no_filenames", "with_filenames", "heading", "no_heading",
             "files_with_matches", "files_without_matches", "ignore_case", "word_regexp",
             "fixed_strings", "recursive", "context", "after_context", "before_context",
-            "semantic", "lexical", "hybrid", "regex", "top_k", "threshold", "show_scores",
+            "semantic", "lexical", "hybrid", "regex", "top_k", "threshold", "threshold_percentile", "show_scores",
             "json", "json_v1", "jsonl", "no_snippet", "reindex", "exclude", "no_default_excludes",
-            "no_ignore", "full_section", "index", "clean", "clean_orphans", "switch_model",
-            "force", "add", "status", "status_verbose", "inspect", "dump_chunks", "model", "rerank", "rerank_model", "serve"
+            "no_ignore", "full_section", "index", "clean", "clean_orphans", "gc", "migrate", "switch_model",
+            "force", "add", "status", "status_verbose", "inspect", "dump_chunks", "model", "rerank", "rerank_model", "rerank_k", "ignore_model_mismatch", "show_config", "export", "with_vectors", "import", "fuzzy", "split_identifiers", "include", "resume", "chunk_overlap", "similar", "color", "no_stale", "bench", "runs", "dry_run", "verbose", "snippet_lines", "repl", "compress", "quantize", "ann", "chunk_strategy", "verify_models", "at", "dedup", "path_style", "format", "no_auto_index", "file_timeout", "sort", "explain", "count_tokens", "modified_since", "offset", "exact", "ef_search", "max_count", "max_matches_per_file", "log_file", "invert_match", "warmup", "preview_width", "files_from", "embed_batch_size", "diff_index", "stats", "force_hash", "replace", "follow", "reduce_dim", "no_unicode", "prune", "timeout", "index_submodules", "max_depth", "ephemeral", "bm25_k1", "bm25_b", "proximity", "output", "no_generated", "group_by_symbol", "jobs", "models", "match_on", "serve"
         ]
     )]
     tui: bool,
@@ -401,78 +1115,657 @@ impl Cli {
             .or_else(|| self.pattern.as_ref().map(PathBuf::from))
             .unwrap_or_else(|| PathBuf::from("."))
     }
+
+    /// Where to start looking for `.ck/config.toml`: the command target for
+    /// command-mode flags, otherwise the first search path (or the cwd).
+    fn config_search_start(&self) -> PathBuf {
+        let command_mode = self.index
+            || self.switch_model.is_some()
+            || self.clean
+            || self.clean_orphans
+            || self.gc
+            || self.migrate
+            || self.add
+            || self.status
+            || self.status_verbose
+            || self.status_json
+            || self.inspect
+            || self.count_tokens
+            || self.diff_index
+            || self.dump_chunks
+            || self.export
+            || self.import.is_some()
+            || self.show_config
+            || self.bench.is_some()
+            || self.repl;
+        if command_mode {
+            self.command_target_path()
+        } else {
+            self.files
+                .first()
+                .cloned()
+                .unwrap_or_else(|| PathBuf::from("."))
+        }
+    }
+
+    /// Whether the selected mode ranks results (and so honours `--topk`).
+    fn is_ranked_mode(&self) -> bool {
+        self.semantic || self.lexical || self.hybrid
+    }
+
+    /// BM25 parameters for `--lex`, the built-in defaults filling in any
+    /// not given.
+    fn bm25_params(&self) -> ck_core::Bm25Params {
+        let defaults = ck_core::Bm25Params::default();
+        ck_core::Bm25Params {
+            k1: self.bm25_k1.unwrap_or(defaults.k1),
+            b: self.bm25_b.unwrap_or(defaults.b),
+        }
+    }
+
+    /// Fill in any flags the user didn't pass from `.ck/config.toml`.
+    /// Flags always win; config excludes are added alongside `--exclude`.
+    /// Without `--model`, the model the project's index is pinned to comes
+    /// before the config default.
+    fn apply_config(&mut self, config: &ck_core::config::CkConfig, pinned_model: Option<&str>) {
+        if self.model.is_none() {
+            self.model = pinned_model
+                .map(str::to_string)
+                .or_else(|| config.default_model.clone());
+        }
+        self.language_models = config.language_models.clone();
+        if self.threshold.is_none() && self.threshold_percentile.is_none() && self.semantic {
+            self.threshold = config.semantic_threshold;
+        }
+        if self.top_k.is_none() && self.is_ranked_mode() {
+            self.top_k = config.topk;
+        }
+        if self.lexical {
+            self.bm25_k1 = self.bm25_k1.or(config.bm25_k1);
+            self.bm25_b = self.bm25_b.or(config.bm25_b);
+            self.proximity = self.proximity.or(config.proximity);
+        }
+        let mut exclude = config.exclude.clone();
+        exclude.append(&mut self.exclude);
+        self.exclude = exclude;
+    }
+}
+
+/// Warn when the model a run would use differs from the one the project's
+/// index is pinned to: an explicit `--model` still applies, a config default
+/// gives way to the pin. `--switch-model` is exempt, as it repins.
+fn warn_pinned_model_conflict(
+    cli: &Cli,
+    config: &ck_core::config::CkConfig,
+    pinned_model: Option<&str>,
+    status: &StatusReporter,
+) {
+    let Some(pinned) = pinned_model else {
+        return;
+    };
+    if cli.switch_model.is_some() {
+        return;
+    }
+    let registry = ck_models::ModelRegistry::default();
+    let differs = |requested: &str| {
+        registry
+            .resolve(Some(requested))
+            .map_or(requested != pinned, |(_, config)| config.name != pinned)
+    };
+    if let Some(requested) = cli.model.as_deref() {
+        if differs(requested) {
+            status.warn(&format!(
+                "'--model {requested}' differs from '{pinned}', the model this project's index is pinned to; use '--switch-model {requested}' to change it"
+            ));
+        }
+    } else if let Some(default) = config.default_model.as_deref()
+        && differs(default)
+    {
+        status.warn(&format!(
+            "Using '{pinned}', the model this project's index is pinned to, rather than config default_model '{default}'"
+        ));
+    }
+}
+
+/// Print the settings a search/index run would use and where each came from.
+fn print_effective_config(
+    cli: &Cli,
+    loaded: &ck_core::config::LoadedConfig,
+    pinned_model: Option<&str>,
+) {
+    use ck_core::config::resolve_setting;
+
+    let config = &loaded.config;
+    match &loaded.path {
+        Some(path) => println!("# Config file: {}", path.display()),
+        None => println!(
+            "# Config file: none (searched upward from {})",
+            cli.config_search_start().display()
+        ),
+    }
+
+    let registry = ck_models::ModelRegistry::default();
+    if let (None, Some(model)) = (&cli.model, pinned_model) {
+        println!("default_model = {model:?}  # pinned by index");
+    } else if let Some((model, source)) = resolve_setting(
+        cli.model.clone(),
+        config.default_model.clone(),
+        Some(registry.default_model.clone()),
+    ) {
+        println!("default_model = {model:?}  # {source}");
+    }
+    for (language, model) in &config.language_models {
+        println!("language_models.{language} = {model:?}  # config");
+    }
+    if let Some(percentile) = cli.threshold_percentile {
+        println!("threshold_percentile = {percentile}  # flag, replaces semantic_threshold");
+    } else if let Some((threshold, source)) =
+        resolve_setting(cli.threshold, config.semantic_threshold, Some(0.6))
+    {
+        println!("semantic_threshold = {threshold}  # {source}, --sem only");
+    }
+    if let Some((top_k, source)) = resolve_setting(cli.top_k, config.topk, Some(10)) {
+        println!("topk = {top_k}  # {source}, default applies to --sem only");
+    }
+    let defaults = ck_core::Bm25Params::default();
+    if let Some((k1, source)) = resolve_setting(cli.bm25_k1, config.bm25_k1, Some(defaults.k1)) {
+        println!("bm25_k1 = {k1}  # {source}, --lex only");
+    }
+    if let Some((b, source)) = resolve_setting(cli.bm25_b, config.bm25_b, Some(defaults.b)) {
+        println!("bm25_b = {b}  # {source}, --lex only");
+    }
+    if let Some((weight, source)) = resolve_setting(cli.proximity, config.proximity, Some(0.0)) {
+        println!("proximity = {weight}  # {source}, --lex only");
+    }
+
+    let mut exclude = config.exclude.clone();
+    exclude.extend(cli.exclude.iter().cloned());
+    let exclude_source = match (config.exclude.is_empty(), cli.exclude.is_empty()) {
+        (true, true) => "none",
+        (false, true) => "config",
+        (true, false) => "cli",
+        (false, false) => "config + cli",
+    };
+    println!("exclude = {exclude:?}  # {exclude_source}");
+    if cli.no_default_excludes {
+        println!("# built-in default excludes disabled by --no-default-excludes");
+    } else {
+        println!(
+            "# plus {} built-in default excludes",
+            ck_core::get_default_exclude_patterns().len()
+        );
+    }
+    for (key, values) in [
+        ("generated_patterns", &config.generated_patterns),
+        ("generated_markers", &config.generated_markers),
+    ] {
+        if !values.is_empty() {
+            println!("{key} = {values:?}  # config, added to the built-in ones");
+        }
+    }
+}
+
+/// Rewrite `path` for `--path-style`; without one it is left as found.
+fn restyle_path(path: &mut PathBuf, style: Option<PathStyle>) {
+    *path = match style {
+        None => return,
+        Some(PathStyle::Absolute) => path_utils::absolute_path(path),
+        Some(PathStyle::Relative) => match std::env::current_dir() {
+            Ok(cwd) => path_utils::relative_path(path, &cwd),
+            Err(_) => return,
+        },
+    };
+}
+
+/// `--files-from`: the paths listed in `list` (`-` for stdin), one per line,
+/// or NUL-separated as `git ls-files -z` and `find -print0` write them. An
+/// entry `path:start-end` (or `path:line`) restricts results in that file to
+/// the lines given; a file also listed without a range is searched whole.
+/// Listed paths that aren't files, such as deletions in `git diff
+/// --name-only`, are skipped with a warning.
+fn read_files_from(
+    list: &Path,
+    status: &StatusReporter,
+) -> Result<(Vec<PathBuf>, ck_core::LineRanges)> {
+    let content = if list == Path::new("-") {
+        std::io::read_to_string(std::io::stdin())?
+    } else {
+        std::fs::read_to_string(list)
+            .map_err(|e| anyhow::anyhow!("Failed to read {}: {e}", list.display()))?
+    };
+    let separator = if content.contains('\0') { '\0' } else { '\n' };
+    let mut files = Vec::new();
+    let mut skipped = Vec::new();
+    let mut ranges = ck_core::LineRanges::default();
+    let mut whole = std::collections::HashSet::new();
+    for entry in content
+        .split(separator)
+        .map(|entry| entry.trim_end_matches('\r'))
+        .filter(|entry| !entry.is_empty())
+    {
+        let path = PathBuf::from(entry);
+        if path.is_file() {
+            whole.insert(path.clone());
+            files.push(path);
+            continue;
+        }
+        if let Some((file, spec)) = entry.rsplit_once(':')
+            && Path::new(file).is_file()
+        {
+            let range = ck_core::LineRanges::parse_spec(spec).map_err(|_| {
+                anyhow::anyhow!(
+                    "Malformed line range in --files-from entry `{entry}`: expected PATH:START-END with 1 <= START <= END"
+                )
+            })?;
+            ranges.insert(Path::new(file), range);
+            files.push(PathBuf::from(file));
+            continue;
+        }
+        skipped.push(path);
+    }
+    for path in &whole {
+        ranges.remove(path);
+    }
+    let mut seen = std::collections::HashSet::new();
+    files.retain(|path| seen.insert(path.clone()));
+    if !skipped.is_empty() {
+        status.warn(&format!(
+            "Skipping {} listed path(s) that are not files: {}",
+            skipped.len(),
+            skipped
+                .iter()
+                .map(|path| path.display().to_string())
+                .collect::<Vec<_>>()
+                .join(", ")
+        ));
+    }
+    if files.is_empty() {
+        anyhow::bail!("--files-from {} lists no files to search", list.display());
+    }
+    Ok((files, ranges))
+}
+
+fn find_search_root(include_patterns: &[IncludePattern]) -> PathBuf {
+    if include_patterns.is_empty() {
+        return PathBuf::from(".");
+    }
+
+    let mut root = if include_patterns[0].is_dir {
+        include_patterns[0].path.clone()
+    } else {
+        include_patterns[0]
+            .path
+            .parent()
+            .unwrap_or(&include_patterns[0].path)
+            .to_path_buf()
+    };
+
+    for pattern in include_patterns.iter().skip(1) {
+        let mut candidate = if pattern.is_dir {
+            pattern.path.clone()
+        } else {
+            pattern.path.parent().unwrap_or(&pattern.path).to_path_buf()
+        };
+
+        if candidate.starts_with(&root) {
+            continue;
+        }
+
+        while !root.starts_with(&candidate) && !candidate.starts_with(&root) {
+            if let Some(parent) = root.parent() {
+                root = parent.to_path_buf();
+            } else {
+                break;
+            }
+        }
+
+        if !candidate.starts_with(&root) {
+            while let Some(parent) = candidate.parent() {
+                if parent.starts_with(&root) {
+                    candidate = parent.to_path_buf();
+                    break;
+                }
+                candidate = parent.to_path_buf();
+            }
+        }
+
+        if root.starts_with(&candidate) {
+            root = candidate;
+        }
+    }
+
+    if root.as_os_str().is_empty() {
+        PathBuf::from(".")
+    } else {
+        root
+    }
+}
+
+fn build_exclude_patterns(cli: &Cli) -> Vec<String> {
+    // Use the centralized pattern builder from ck-core
+    // Note: .ckignore handling is now done by WalkBuilder via the use_ckignore parameter
+    let mut patterns = ck_core::build_exclude_patterns(&cli.exclude, !cli.no_default_excludes);
+    // `--include '.git/**'` lifts the built-in exclude of `.git`, though not
+    // an `--exclude .git` of the user's own
+    if ck_core::include_globs_reach_git(&cli.include) && !cli.exclude.iter().any(|p| p == ".git") {
+        patterns.retain(|pattern| pattern != ".git");
+    }
+    patterns
+}
+
+/// `--verify-models`: check every cached model, remove broken files and
+/// download the affected registry models again.
+/// Parse `--modified-since`: a duration back from now (`45s`, `30m`, `12h`,
+/// `7d`, `2w`), a date (midnight local time) or an RFC 3339 timestamp.
+fn parse_modified_since(value: &str) -> Result<std::time::SystemTime, String> {
+    let value = value.trim();
+    if let Some(unit) = value.chars().last().filter(char::is_ascii_alphabetic)
+        && let Ok(amount) = value[..value.len() - 1].parse::<u64>()
+    {
+        let seconds = match unit {
+            's' => 1,
+            'm' => 60,
+            'h' => 60 * 60,
+            'd' => 24 * 60 * 60,
+            'w' => 7 * 24 * 60 * 60,
+            _ => {
+                return Err(format!(
+                    "unknown duration unit '{unit}' (use s, m, h, d or w)"
+                ));
+            }
+        };
+        return std::time::SystemTime::now()
+            .checked_sub(std::time::Duration::from_secs(
+                amount.saturating_mul(seconds),
+            ))
+            .ok_or_else(|| format!("duration '{value}' reaches too far back"));
+    }
+    if let Ok(date) = chrono::NaiveDate::parse_from_str(value, "%Y-%m-%d") {
+        use chrono::TimeZone;
+        return chrono::Local
+            .from_local_datetime(&date.and_time(chrono::NaiveTime::MIN))
+            .earliest()
+            .map(std::time::SystemTime::from)
+            .ok_or_else(|| format!("'{value}' has no local midnight"));
+    }
+    chrono::DateTime::parse_from_rfc3339(value)
+        .map(std::time::SystemTime::from)
+        .map_err(|_| {
+            format!("expected a duration like 7d or 12h, a date like 2024-05-01 or an RFC 3339 timestamp, got '{value}'")
+        })
+}
+
+/// Parse `--timeout`: a number with a unit of `ms`, `s` or `m`; a bare
+/// number is seconds.
+fn parse_timeout(value: &str) -> Result<std::time::Duration, String> {
+    let value = value.trim();
+    let split = value
+        .find(|c: char| !c.is_ascii_digit() && c != '.')
+        .unwrap_or(value.len());
+    let (amount, unit) = value.split_at(split);
+    let amount: f64 = amount
+        .parse()
+        .map_err(|_| format!("expected a duration like 2s or 500ms, got '{value}'"))?;
+    let seconds = match unit {
+        "ms" => amount / 1000.0,
+        "" | "s" => amount,
+        "m" => amount * 60.0,
+        _ => return Err(format!("unknown duration unit '{unit}' (use ms, s or m)")),
+    };
+    std::time::Duration::try_from_secs_f64(seconds)
+        .ok()
+        .filter(|duration| !duration.is_zero())
+        .ok_or_else(|| format!("timeout must be a positive duration, got '{value}'"))
+}
+
+/// `--verbose`: warn about the user's patterns that match nothing under
+/// `path`, since a typo in one otherwise fails silently.
+fn warn_unmatched_patterns(cli: &Cli, path: &Path, status: &StatusReporter) -> Result<()> {
+    let file_options = ck_core::FileCollectionOptions {
+        respect_gitignore: !cli.no_ignore,
+        use_ckignore: !cli.no_ckignore,
+        exclude_patterns: build_exclude_patterns(cli),
+        include_globs: cli.include.clone(),
+        show_hidden: cli.hidden,
+        follow_links: cli.follow,
+        include_submodules: cli.index_submodules,
+        max_depth: cli.max_depth,
+        skip_generated: cli.no_generated,
+    };
+    for unmatched in ck_index::unmatched_patterns(path, &cli.exclude, &file_options)? {
+        status.warn(&format!(
+            "{} pattern '{}' matches nothing under {}",
+            unmatched.source,
+            unmatched.pattern,
+            path.display()
+        ));
+    }
+    Ok(())
+}
+
+fn verify_models(status: &StatusReporter) -> Result<()> {
+    use ck_embed::model_cache;
+
+    status.section_header("Verifying Cached Models");
+    let mut checked = 0;
+    let mut repaired = Vec::new();
+    for root in model_cache::model_cache_roots()? {
+        for check in model_cache::check_model_cache(&root)? {
+            checked += 1;
+            if check.is_ok() {
+                status.success(&format!(
+                    "{}: {} file(s) OK",
+                    check.repo, check.files_checked
+                ));
+                continue;
+            }
+            for file in &check.broken {
+                status.warn(&format!(
+                    "{}: {} ({})",
+                    check.repo,
+                    file.path.display(),
+                    file.reason
+                ));
+            }
+            let removed = model_cache::remove_broken_files(&check)?;
+            status.info(&format!("{}: removed {removed} broken file(s)", check.repo));
+            repaired.push((root.clone(), check));
+        }
+    }
+    if checked == 0 {
+        status.info("No downloaded models found");
+        return Ok(());
+    }
+
+    let embed_registry = ck_models::ModelRegistry::default();
+    let rerank_registry = ck_models::RerankModelRegistry::default();
+    for (root, check) in &repaired {
+        let owns = |name: &str| model_cache::cached_model_dirs(root, name).contains(&check.dir);
+        let embed = embed_registry.models.values().find(|c| owns(&c.name));
+        let rerank = rerank_registry.models.values().find(|c| owns(&c.name));
+        let spinner = status.create_spinner(&format!("Downloading {} again...", check.repo));
+        let result = match (embed, rerank) {
+            (Some(config), _) => ck_embed::create_embedder_for_config(config, None).map(drop),
+            (None, Some(config)) => ck_embed::create_reranker_for_config(config, None).map(drop),
+            (None, None) => {
+                status.finish_progress(spinner, "Skipped");
+                status.info(&format!(
+                    "{} is not a registry model; it will be downloaded again the next time it is used",
+                    check.repo
+                ));
+                continue;
+            }
+        };
+        match result {
+            Ok(()) => status.finish_progress(spinner, &format!("{} restored", check.repo)),
+            Err(e) => {
+                status.finish_progress(spinner, "Download failed");
+                return Err(e.context(format!("Failed to download {} again", check.repo)));
+            }
+        }
+    }
+    if repaired.is_empty() {
+        status.success(&format!("All {checked} cached model(s) are intact"));
+    }
+    Ok(())
 }
 
-fn canonicalize_for_comparison(path: &Path) -> PathBuf {
-    if let Ok(canonical) = path.canonicalize() {
-        return canonical;
-    }
-
-    std::env::current_dir()
-        .map(|cwd| cwd.join(path))
-        .unwrap_or_else(|_| path.to_path_buf())
+/// `--warmup`: load the model the way indexing does and embed one short
+/// text. A search run afterwards finds the model downloaded and its files in
+/// the OS page cache, so it skips most of the cold start.
+fn warmup_model(
+    status: &StatusReporter,
+    model_alias: &str,
+    model_config: &ck_models::ModelConfig,
+) -> Result<()> {
+    let spinner = status.create_spinner(&format!("Loading {}...", model_config.name));
+    let started = std::time::Instant::now();
+    let mut embedder = ck_embed::create_embedder_for_config(model_config, None)?;
+    let loaded = started.elapsed();
+    embedder.preload()?;
+    let total = started.elapsed();
+    status.finish_progress(spinner, &format!("{} loaded", model_config.name));
+
+    status.success(&format!(
+        "Warmed up {model_alias} ({}) in {:.2}s: load {:.2}s, first embedding {}ms",
+        model_config.name,
+        total.as_secs_f64(),
+        loaded.as_secs_f64(),
+        (total - loaded).as_millis()
+    ));
+    Ok(())
 }
 
-fn find_search_root(include_patterns: &[IncludePattern]) -> PathBuf {
-    if include_patterns.is_empty() {
-        return PathBuf::from(".");
+/// Print the `--index --dry-run` report: a summary line, then with
+/// `--verbose` one line per file or excluded directory.
+fn print_token_count(count: &ck_index::TokenCount) {
+    println!(
+        "{:<14} {:>8} {:>8} {:>12}",
+        style("Language").bold(),
+        style("Files").bold(),
+        style("Chunks").bold(),
+        style("Tokens").bold()
+    );
+    for language in &count.languages {
+        println!(
+            "{:<14} {:>8} {:>8} {:>12}",
+            language.language, language.files, language.chunks, language.tokens
+        );
     }
+    println!(
+        "{:<14} {:>8} {:>8} {:>12}",
+        style("Total").bold(),
+        count.files,
+        count.chunks,
+        style(count.tokens).yellow().bold()
+    );
 
-    let mut root = if include_patterns[0].is_dir {
-        include_patterns[0].path.clone()
+    let method = if count.exact {
+        format!("Counted with the {} tokenizer", count.model)
     } else {
-        include_patterns[0]
-            .path
-            .parent()
-            .unwrap_or(&include_patterns[0].path)
-            .to_path_buf()
+        format!(
+            "Estimated; the {} tokenizer isn't cached, so counts are approximate",
+            count.model
+        )
     };
+    println!("{}", style(method).dim());
+    if !count.skipped.is_empty() {
+        println!(
+            "{}",
+            style(format!(
+                "{} file(s) not counted (PDF text is only extracted when indexing)",
+                count.skipped.len()
+            ))
+            .dim()
+        );
+    }
+}
 
-    for pattern in include_patterns.iter().skip(1) {
-        let mut candidate = if pattern.is_dir {
-            pattern.path.clone()
-        } else {
-            pattern.path.parent().unwrap_or(&pattern.path).to_path_buf()
-        };
+fn print_index_plan(root: &Path, plan: &ck_index::IndexPlan, verbose: bool) {
+    use ck_index::PlannedAction;
 
-        if candidate.starts_with(&root) {
-            continue;
+    let display = |path: &Path, is_dir: bool| {
+        let relative = path
+            .strip_prefix(root)
+            .unwrap_or(path)
+            .display()
+            .to_string();
+        if is_dir {
+            format!("{relative}/")
+        } else {
+            relative
         }
+    };
 
-        while !root.starts_with(&candidate) && !candidate.starts_with(&root) {
-            if let Some(parent) = root.parent() {
-                root = parent.to_path_buf();
-            } else {
-                break;
-            }
+    if verbose {
+        for file in &plan.files {
+            let label = match file.action {
+                PlannedAction::Add => "add",
+                PlannedAction::Update => "update",
+                PlannedAction::UpToDate => "up-to-date",
+                PlannedAction::Unreadable => "unreadable",
+            };
+            println!("{label:<10}  {}", display(&file.path, false));
         }
-
-        if !candidate.starts_with(&root) {
-            while let Some(parent) = candidate.parent() {
-                if parent.starts_with(&root) {
-                    candidate = parent.to_path_buf();
-                    break;
-                }
-                candidate = parent.to_path_buf();
-            }
+        for excluded in &plan.excluded {
+            println!(
+                "{:<10}  {} ({})",
+                "excluded",
+                display(&excluded.path, excluded.is_dir),
+                excluded.reason
+            );
         }
+    }
 
-        if root.starts_with(&candidate) {
-            root = candidate;
+    let unreadable = plan.count(PlannedAction::Unreadable);
+    println!(
+        "Dry run: {} to add, {} to update, {} up to date, {} excluded{}",
+        plan.count(PlannedAction::Add),
+        plan.count(PlannedAction::Update),
+        plan.count(PlannedAction::UpToDate),
+        plan.excluded.len(),
+        if unreadable > 0 {
+            format!(", {unreadable} unreadable")
+        } else {
+            String::new()
         }
-    }
+    );
+}
 
-    if root.as_os_str().is_empty() {
-        PathBuf::from(".")
+/// `bytes` in KB below a megabyte and in MB above, for `--gc`.
+fn format_bytes(bytes: u64) -> String {
+    let kb = bytes as f64 / 1024.0;
+    if kb < 1024.0 {
+        format!("{kb:.1} KB")
     } else {
-        root
+        format!("{:.1} MB", kb / 1024.0)
     }
 }
 
-fn build_exclude_patterns(cli: &Cli) -> Vec<String> {
-    // Use the centralized pattern builder from ck-core
-    // Note: .ckignore handling is now done by WalkBuilder via the use_ckignore parameter
-    ck_core::build_exclude_patterns(&cli.exclude, !cli.no_default_excludes)
+fn print_index_diff(diff: &ck_index::IndexDiff) {
+    if diff.is_empty() {
+        println!("Index is up to date");
+        return;
+    }
+    for (label, files) in [
+        ("New", &diff.new),
+        ("Modified", &diff.modified),
+        ("Deleted", &diff.deleted),
+    ] {
+        if files.is_empty() {
+            continue;
+        }
+        println!("{label} ({}):", files.len());
+        for file in files {
+            println!("  {}", file.display());
+        }
+    }
+    println!(
+        "{} new, {} modified, {} deleted",
+        diff.new.len(),
+        diff.modified.len(),
+        diff.deleted.len()
+    );
 }
 
 async fn run_index_workflow(
@@ -508,6 +1801,99 @@ async fn run_index_workflow(
         "📄 Chunk Config: {chunk_tokens} tokens target, {overlap_tokens} token overlap (~20%)"
     ));
 
+    if cli.verbose {
+        let requested = cli.embed_batch_size.map(std::num::NonZeroUsize::get);
+        let batch_size = model_config.effective_batch_size(requested);
+        match requested {
+            Some(requested) if requested > batch_size => status.info(&format!(
+                "📦 Embed Batch: {batch_size} chunks per call ({requested} requested; {} takes at most {batch_size})",
+                model_config.name
+            )),
+            _ => status.info(&format!("📦 Embed Batch: {batch_size} chunks per call")),
+        }
+    }
+
+    let stored_overlap = ck_index::index_chunk_overlap(path);
+    let chunk_overlap = cli.chunk_overlap.unwrap_or(stored_overlap);
+    if chunk_overlap > 0 {
+        status.info(&format!(
+            "🔗 Chunk Overlap: {chunk_overlap} lines of context per chunk"
+        ));
+    }
+    let overlap_changed = chunk_overlap != stored_overlap && ck_core::index_dir(path).exists();
+    if overlap_changed && !clean_first {
+        status.info("Chunk overlap changed; rebuilding the index");
+    }
+    let stored_strategy = ck_index::index_chunk_strategy(path);
+    let chunk_strategy = cli
+        .chunk_strategy
+        .map(ck_chunk::ChunkStrategy::from)
+        .unwrap_or(stored_strategy);
+    if chunk_strategy != ck_chunk::ChunkStrategy::default() {
+        status.info(&format!("✂ Chunk Strategy: {chunk_strategy}"));
+    }
+    let strategy_changed = chunk_strategy != stored_strategy && ck_core::index_dir(path).exists();
+    if strategy_changed && !clean_first && !overlap_changed {
+        status.info("Chunk strategy changed; rebuilding the index");
+    }
+    let stored_quantization = ck_index::index_quantization(path);
+    let quantization = cli
+        .quantize
+        .map(ck_index::Quantization::from)
+        .unwrap_or(stored_quantization);
+    // Vectors stored more coarsely can't be converted back, only re-embedded
+    let quantization_finer =
+        quantization.is_finer_than(stored_quantization) && ck_core::index_dir(path).exists();
+    if quantization_finer && !clean_first && !overlap_changed && !strategy_changed {
+        status.info("Quantization is finer than the index's; rebuilding the index");
+    }
+    let stored_reduction = ck_index::index_reduced_dimensions(path);
+    let reduce_dim = match cli.reduce_dim {
+        Some(0) => None,
+        Some(dimensions) => Some(dimensions),
+        None => stored_reduction,
+    };
+    // Projected vectors can't be restored or projected again, only re-embedded
+    let reduction_changed = stored_reduction.is_some() && reduce_dim != stored_reduction;
+    if reduction_changed
+        && !clean_first
+        && !overlap_changed
+        && !strategy_changed
+        && !quantization_finer
+    {
+        status.info("Dimension reduction changed; rebuilding the index");
+    }
+    let clean_first = clean_first
+        || overlap_changed
+        || strategy_changed
+        || quantization_finer
+        || reduction_changed;
+    let compress = cli.compress || ck_index::index_sidecar_compression(path);
+    if compress {
+        status.info("🗜 Sidecar Compression: zstd");
+    }
+    if quantization != ck_index::Quantization::None {
+        status.info(&format!("📉 Vector Quantization: {quantization}"));
+    }
+    if let Some(dimensions) = reduce_dim {
+        status.info(&format!("📐 Dimension Reduction: {dimensions} dims"));
+    }
+    if cli.ann || ck_index::index_has_ann(path) {
+        status.info("🧭 Approximate Index: HNSW");
+    }
+    let language_models =
+        ck_models::ModelRegistry::default().resolve_language_models(&cli.language_models)?;
+    if cli.verbose && !language_models.is_empty() {
+        status.info("🤖 Language Models:");
+        for (language, (alias, config)) in &language_models {
+            status.info(&format!(
+                "   {language}: {} (alias '{alias}', {} dims)",
+                config.name, config.dimensions
+            ));
+        }
+        status.info(&format!("   other languages: {}", model_config.name));
+    }
+
     // Create .ckignore file if it doesn't exist
     if !cli.no_ckignore
         && let Ok(created) = ck_core::create_ckignore_if_missing(path)
@@ -518,7 +1904,14 @@ async fn run_index_workflow(
 
     let exclude_patterns = build_exclude_patterns(cli);
 
-    if clean_first {
+    let resuming = cli.resume && ck_index::has_checkpoint(path);
+    if cli.resume && !resuming {
+        status.info("No interrupted indexing run to resume; running a normal update");
+    }
+
+    if clean_first && resuming {
+        status.info("Resuming interrupted rebuild; keeping files already re-indexed");
+    } else if clean_first {
         let index_dir = ck_core::index_dir(path);
         if index_dir.exists() {
             let spinner = status.create_spinner("Removing existing index...");
@@ -529,6 +1922,53 @@ async fn run_index_workflow(
         }
     }
 
+    if compress {
+        let converted = ck_index::set_sidecar_compression(path, true)?;
+        if converted > 0 {
+            status.info(&format!("Compressed {converted} existing sidecars"));
+        }
+    }
+    if quantization != ck_index::Quantization::None || quantization != stored_quantization {
+        let converted = ck_index::set_sidecar_quantization(path, quantization)?;
+        if converted > 0 {
+            status.info(&format!(
+                "Converted {converted} existing sidecars to {quantization} vectors"
+            ));
+        }
+    }
+    if cli.ann {
+        ck_index::set_ann_index(path, true)?;
+    }
+    let language_models = language_models
+        .into_iter()
+        .map(|(language, (_, config))| (language, config.name))
+        .collect();
+    let reembedded = ck_index::set_language_models(path, &language_models)?;
+    if reembedded > 0 {
+        status.info(&format!(
+            "Language models changed; re-embedding {reembedded} files"
+        ));
+    }
+    // Entries for files the ignore rules now exclude are dropped before the
+    // update; --include and --max-depth narrow a run, they don't shrink the
+    // index
+    let pruned = if cli.prune && !clean_first {
+        let file_options = ck_core::FileCollectionOptions {
+            respect_gitignore: !cli.no_ignore,
+            use_ckignore: !cli.no_ckignore,
+            exclude_patterns: exclude_patterns.clone(),
+            include_globs: Vec::new(),
+            show_hidden: cli.hidden,
+            follow_links: cli.follow,
+            include_submodules: cli.index_submodules,
+            max_depth: None,
+            skip_generated: false,
+        };
+        ck_index::cleanup_index(path, &file_options)?.orphaned_entries_removed
+    } else {
+        0
+    };
+
     let start_time = std::time::Instant::now();
 
     let (
@@ -614,16 +2054,37 @@ async fn run_index_workflow(
         respect_gitignore: !cli.no_ignore,
         use_ckignore: !cli.no_ckignore,
         exclude_patterns: exclude_patterns.clone(),
+        include_globs: cli.include.clone(),
         show_hidden: cli.hidden,
+        follow_links: cli.follow,
+        include_submodules: cli.index_submodules,
+        max_depth: cli.max_depth,
+        skip_generated: cli.no_generated,
     };
-    let index_future = ck_index::smart_update_index_with_detailed_progress(
+    let index_log = cli
+        .log_file
+        .as_deref()
+        .map(|log_path| ck_index::IndexLog::create(log_path, path))
+        .transpose()?;
+    let index_future = ck_index::smart_update_index_with_resume(
         path,
-        false,
+        &file_options,
         progress_callback,
         detailed_progress_callback,
-        true,
-        &file_options,
-        Some(model_alias),
+        ck_index::IndexUpdateOptions {
+            compute_embeddings: true,
+            model: Some(model_alias),
+            resume: resuming,
+            chunk_overlap: cli.chunk_overlap,
+            chunk_strategy: cli.chunk_strategy.map(Into::into),
+            file_timeout: (cli.file_timeout > 0)
+                .then(|| std::time::Duration::from_secs(cli.file_timeout)),
+            embed_batch_size: cli.embed_batch_size.map(std::num::NonZeroUsize::get),
+            force_hash: cli.force_hash,
+            jobs: cli.jobs.map(std::num::NonZeroUsize::get),
+            index_log: index_log.as_ref(),
+            ..Default::default()
+        },
     );
     tokio::pin!(index_future);
 
@@ -698,6 +2159,49 @@ async fn run_index_workflow(
             stats.orphaned_files_removed
         ));
     }
+    if cli.prune {
+        status.info(&format!("  ✂ {pruned} excluded or deleted entries pruned"));
+    }
+    if let Some(vectors) = stats.ann_vectors {
+        status.info(&format!(
+            "  🧭 Approximate index rebuilt over {vectors} chunks"
+        ));
+    }
+    if let Some(dimensions) = reduce_dim {
+        let reduced = ck_index::WorkerPool::new(cli.jobs.map(std::num::NonZeroUsize::get))?
+            .install(|| ck_index::set_dimension_reduction(path, dimensions))?;
+        if reduced > 0 {
+            status.info(&format!(
+                "  📐 Vectors of {reduced} files reduced to {dimensions} dimensions"
+            ));
+        }
+    }
+    if !stats.unreadable.is_empty() {
+        status.info(&format!(
+            "  🔒 {} entries skipped (unreadable or symlink loops)",
+            stats.unreadable.len()
+        ));
+        if cli.verbose {
+            print_unreadable(&stats.unreadable);
+        }
+    }
+    if !stats.timed_out_files.is_empty() {
+        status.warn(&format!(
+            "{} file(s) took longer than {}s and were skipped (raise --file-timeout, or exclude them):",
+            stats.timed_out_files.len(),
+            cli.file_timeout
+        ));
+        for file in &stats.timed_out_files {
+            eprintln!("  {}", file.display());
+        }
+    }
+    if !stats.unwritable.is_empty() {
+        status.warn(&format!(
+            "{} file(s) were skipped because their index entry couldn't be written:",
+            stats.unwritable.len()
+        ));
+        print_unreadable(&stats.unwritable);
+    }
 
     if clean_first {
         status.info(&format!(
@@ -709,6 +2213,14 @@ async fn run_index_workflow(
     Ok(())
 }
 
+/// `--verbose`: each entry a walk skipped, with the reason (it couldn't be
+/// read, or was a symlink loop).
+fn print_unreadable(unreadable: &[(PathBuf, String)]) {
+    for (path, reason) in unreadable {
+        eprintln!("  {}: {}", path.display(), reason);
+    }
+}
+
 async fn dump_file_chunks(file_path: &PathBuf) -> Result<()> {
     use std::path::Path;
 
@@ -717,7 +2229,7 @@ async fn dump_file_chunks(file_path: &PathBuf) -> Result<()> {
     // Use the shared live chunking function
     let (lines, chunk_metas) = ck_tui::chunk_file_live(path).map_err(|err| {
         eprintln!("Error: {err}");
-        std::process::exit(1);
+        std::process::exit(EXIT_ERROR);
     })?;
 
     // Display chunks for entire file
@@ -758,7 +2270,12 @@ async fn dump_file_chunks(file_path: &PathBuf) -> Result<()> {
     Ok(())
 }
 
-async fn inspect_file_metadata(file_path: &PathBuf, status: &StatusReporter) -> Result<()> {
+async fn inspect_file_metadata(
+    file_path: &PathBuf,
+    chunk_overlap: Option<usize>,
+    chunk_strategy: Option<ck_chunk::ChunkStrategy>,
+    status: &StatusReporter,
+) -> Result<()> {
     use ck_embed::TokenEstimator;
     use console::style;
     use std::fs;
@@ -771,27 +2288,61 @@ async fn inspect_file_metadata(file_path: &PathBuf, status: &StatusReporter) ->
         return Ok(());
     }
 
+    if ck_index::is_binary_file(path) {
+        status.info(&format!("{}: binary file, skipped", path.display()));
+        return Ok(());
+    }
+
     let metadata = fs::metadata(path)?;
     let detected_lang = ck_core::Language::from_path(path);
-    let content = fs::read_to_string(path)?;
-    let total_tokens = TokenEstimator::estimate_tokens(&content);
+    let content = fs::read(path)?;
+    let content = match String::from_utf8(content) {
+        Ok(content) => content,
+        Err(err) => {
+            status.warn("File is not valid UTF-8; showing it with invalid bytes replaced");
+            String::from_utf8_lossy(err.as_bytes()).into_owned()
+        }
+    };
+
+    // Count with the model's tokenizer when it is cached, else estimate
+    let default_model = "nomic-embed-text-v1.5";
+    let exact_total = TokenEstimator::exact_tokens(&content, default_model);
+    let (total_tokens, token_label) = match exact_total {
+        Some(tokens) => (tokens, "tokens"),
+        None => (
+            TokenEstimator::estimate_tokens(&content),
+            "tokens, estimated",
+        ),
+    };
 
     // Basic file info
     println!(
-        "File: {} ({:.1} KB, {} lines, {} tokens)",
+        "File: {} ({:.1} KB, {} lines, {} {})",
         style(path.display()).cyan().bold(),
         metadata.len() as f64 / 1024.0,
         content.lines().count(),
-        style(total_tokens).yellow()
+        style(total_tokens).yellow(),
+        token_label
     );
 
     if let Some(lang) = detected_lang {
         println!("Language: {}", style(lang.to_string()).green());
     }
 
-    // Use model-aware chunking
-    let default_model = "nomic-embed-text-v1.5";
-    let chunks = ck_chunk::chunk_text_with_model(&content, detected_lang, Some(default_model))?;
+    // Use model-aware chunking, with the overlap and strategy the surrounding
+    // index uses unless they are given
+    let parent_dir = path.parent().unwrap_or(Path::new("."));
+    let chunk_overlap = chunk_overlap.unwrap_or_else(|| ck_index::index_chunk_overlap(parent_dir));
+    let chunk_strategy =
+        chunk_strategy.unwrap_or_else(|| ck_index::index_chunk_strategy(parent_dir));
+    let chunks = ck_chunk::chunk_file_content(
+        path,
+        &content,
+        detected_lang,
+        Some(default_model),
+        chunk_overlap,
+        chunk_strategy,
+    )?;
 
     if chunks.is_empty() {
         println!("No chunks generated");
@@ -801,7 +2352,7 @@ async fn inspect_file_metadata(file_path: &PathBuf, status: &StatusReporter) ->
     // Token analysis
     let token_counts: Vec<usize> = chunks
         .iter()
-        .map(|chunk| TokenEstimator::estimate_tokens(&chunk.text))
+        .map(|chunk| TokenEstimator::count_tokens(&chunk.text, Some(default_model)))
         .collect();
 
     let min_tokens = *token_counts.iter().min().unwrap();
@@ -841,10 +2392,25 @@ async fn inspect_file_metadata(file_path: &PathBuf, status: &StatusReporter) ->
             })
             .unwrap_or_default();
 
-        // Simple preview - first 80 chars
+        // Overlap lines precede the span; show them as their own range
+        let overlap_lines = chunk.metadata.overlap_lines;
+        let overlap_display = if overlap_lines > 0 {
+            style(format!(
+                " (+ overlap L{}-{})",
+                chunk.span.line_start - overlap_lines,
+                chunk.span.line_start - 1
+            ))
+            .magenta()
+            .to_string()
+        } else {
+            String::new()
+        };
+
+        // Simple preview - first 80 chars of the chunk's own lines
         let preview = chunk
             .text
             .lines()
+            .skip(overlap_lines)
             .find(|line| !line.trim().is_empty())
             .unwrap_or("")
             .chars()
@@ -854,13 +2420,14 @@ async fn inspect_file_metadata(file_path: &PathBuf, status: &StatusReporter) ->
             .to_string();
 
         println!(
-            "  {} {}{}: {} tokens | L{}-{} | {}{}",
+            "  {} {}{}: {} tokens | L{}-{}{} | {}{}",
             style(format!("{:2}.", i + 1)).dim(),
             style(type_display).blue(),
             stride_display,
             style(chunk_tokens).yellow(),
             chunk.span.line_start,
             chunk.span.line_end,
+            overlap_display,
             preview,
             if chunk.text.len() > 80 { "..." } else { "" }
         );
@@ -871,7 +2438,6 @@ async fn inspect_file_metadata(file_path: &PathBuf, status: &StatusReporter) ->
     }
 
     // Index status
-    let parent_dir = path.parent().unwrap_or(Path::new("."));
     if let Ok(stats) = ck_index::get_index_stats(parent_dir) {
         if stats.total_files > 0 {
             println!(
@@ -902,13 +2468,18 @@ fn reset_sigpipe() {
 #[cfg(not(unix))]
 fn reset_sigpipe() {}
 
+/// Exit status of a search that found nothing, like grep's. Success is 0.
+const EXIT_NO_MATCHES: i32 = 1;
+/// Exit status of a failure (bad arguments, a missing index, I/O errors),
+/// like grep's, so scripts can tell it apart from finding nothing.
+const EXIT_ERROR: i32 = 2;
+
 #[tokio::main]
 async fn main() {
     reset_sigpipe();
 
     if let Err(e) = run_main().await {
         eprintln!("DETAILED ERROR: {e:#}");
-        eprintln!("DEBUG: Error occurred in main");
 
         // Print the error chain for better debugging
         let mut source = e.source();
@@ -917,18 +2488,29 @@ async fn main() {
             source = err.source();
         }
 
-        std::process::exit(1);
+        std::process::exit(EXIT_ERROR);
     }
 }
 
 async fn run_main() -> Result<()> {
     let cli = Cli::parse();
+    color::apply(cli.color, cli.output.is_some());
 
     if cli.print_default_ckignore {
         print!("{}", get_default_ckignore_content());
         return Ok(());
     }
 
+    if cli.print_json_schema {
+        let schema = if cli.jsonl {
+            schemars::schema_for!(ck_core::JsonlSearchResult)
+        } else {
+            schemars::schema_for!(ck_core::JsonSearchResult)
+        };
+        println!("{}", serde_json::to_string_pretty(&schema)?);
+        return Ok(());
+    }
+
     // Handle MCP server mode first
     if cli.serve {
         return run_mcp_server().await;
@@ -964,9 +2546,11 @@ async fn run_mcp_server() -> Result<()> {
     server.run().await
 }
 
-async fn run_cli_mode(cli: Cli) -> Result<()> {
+async fn run_cli_mode(mut cli: Cli) -> Result<()> {
     // Regular CLI mode logging
+    // Logs stay off stdout, which carries results and --json output
     tracing_subscriber::fmt()
+        .with_writer(std::io::stderr)
         .with_env_filter(
             tracing_subscriber::EnvFilter::from_default_env()
                 .add_directive(tracing::Level::WARN.into()),
@@ -974,6 +2558,73 @@ async fn run_cli_mode(cli: Cli) -> Result<()> {
         .init();
 
     let status = StatusReporter::new(cli.quiet);
+    // The MCP server keeps stderr free of terminal output
+    if !cli.quiet && !cli.serve {
+        ck_embed::set_download_reporter(Some(progress::model_download_reporter()));
+    }
+
+    if cli.verify_models {
+        return verify_models(&status);
+    }
+    if cli.models {
+        let cache_root = ck_embed::model_cache_root()?;
+        let models = models::model_infos(&std::env::current_dir()?, &cache_root);
+        return models::print_models(&models, &cache_root, cli.json);
+    }
+
+    // `--similar FILE` is a semantic search whose query is a file, so a
+    // positional argument is a search path rather than a pattern
+    if let Some(target) = cli.similar.clone() {
+        if let Some(path) = cli.pattern.take() {
+            cli.files.insert(0, PathBuf::from(path));
+        }
+        cli.pattern = Some(target.display().to_string());
+        cli.semantic = true;
+    }
+
+    // Layer .ck/config.toml under the command-line flags
+    let loaded_config = ck_core::config::load_config(&cli.config_search_start())?;
+    let pinned_model = ck_index::pinned_model(&cli.config_search_start());
+    if cli.show_config {
+        print_effective_config(&cli, &loaded_config, pinned_model.as_deref());
+        return Ok(());
+    }
+    warn_pinned_model_conflict(
+        &cli,
+        &loaded_config.config,
+        pinned_model.as_deref(),
+        &status,
+    );
+    cli.apply_config(&loaded_config.config, pinned_model.as_deref());
+    ck_index::set_generated_rules(
+        &loaded_config.config.generated_patterns,
+        &loaded_config.config.generated_markers,
+    )?;
+
+    if let Some(queries_file) = cli.bench.as_deref() {
+        let queries = bench::read_queries(queries_file)?;
+        let mut options = build_options(&cli, cli.reindex, None);
+        options.path = cli.command_target_path();
+        let report = bench::run_bench(options, &queries, cli.runs, &status).await?;
+        if cli.json {
+            println!("{}", serde_json::to_string_pretty(&report)?);
+        } else {
+            bench::print_table(&report);
+        }
+        return Ok(());
+    }
+
+    if cli.repl {
+        let mut options = build_options(&cli, cli.reindex, None);
+        options.path = cli.command_target_path();
+        return repl::run_repl(
+            options,
+            cli.top_k.is_some(),
+            cli.threshold.is_some() || cli.threshold_percentile.is_some(),
+            &status,
+        )
+        .await;
+    }
 
     // Handle command flags first (these take precedence over search)
     if let Some(model_name) = cli.switch_model.as_deref() {
@@ -1032,8 +2683,91 @@ async fn run_cli_mode(cli: Cli) -> Result<()> {
         return Ok(());
     }
 
+    if cli.warmup {
+        let registry = ck_models::ModelRegistry::default();
+        let (model_alias, model_config) = registry
+            .resolve(cli.model.as_deref())
+            .map_err(|e| anyhow::anyhow!(e.to_string()))?;
+        return warmup_model(&status, &model_alias, &model_config);
+    }
+
+    if cli.diff_index {
+        let path = cli.command_target_path();
+        let file_options = ck_core::FileCollectionOptions {
+            respect_gitignore: !cli.no_ignore,
+            use_ckignore: !cli.no_ckignore,
+            exclude_patterns: build_exclude_patterns(&cli),
+            include_globs: cli.include.clone(),
+            show_hidden: cli.hidden,
+            follow_links: cli.follow,
+            include_submodules: cli.index_submodules,
+            max_depth: cli.max_depth,
+            skip_generated: cli.no_generated,
+        };
+        let diff = ck_index::diff_index(&path, &file_options, cli.force_hash)?;
+        if cli.json {
+            println!("{}", serde_json::to_string_pretty(&diff)?);
+        } else {
+            print_index_diff(&diff);
+        }
+        return Ok(());
+    }
+
+    if cli.count_tokens {
+        let path = cli.command_target_path();
+        let registry = ck_models::ModelRegistry::default();
+        let (_, model_config) = registry
+            .resolve(cli.model.as_deref())
+            .map_err(|e| anyhow::anyhow!(e.to_string()))?;
+        let file_options = ck_core::FileCollectionOptions {
+            respect_gitignore: !cli.no_ignore,
+            use_ckignore: !cli.no_ckignore,
+            exclude_patterns: build_exclude_patterns(&cli),
+            include_globs: cli.include.clone(),
+            show_hidden: cli.hidden,
+            follow_links: cli.follow,
+            include_submodules: cli.index_submodules,
+            max_depth: cli.max_depth,
+            skip_generated: cli.no_generated,
+        };
+        let pool = ck_index::WorkerPool::new(cli.jobs.map(std::num::NonZeroUsize::get))?;
+        let count_tokens =
+            || pool.install(|| ck_index::count_tokens(&path, &file_options, &model_config.name));
+
+        if cli.json {
+            let count = count_tokens()?;
+            println!("{}", serde_json::to_string_pretty(&count)?);
+        } else {
+            let spinner = status.create_spinner("Chunking files...");
+            let count = count_tokens()?;
+            status.finish_progress(spinner, "Files chunked");
+            print_token_count(&count);
+        }
+        return Ok(());
+    }
+
     if cli.index {
         let path = cli.command_target_path();
+        if cli.verbose {
+            warn_unmatched_patterns(&cli, &path, &status)?;
+        }
+
+        if cli.dry_run {
+            let file_options = ck_core::FileCollectionOptions {
+                respect_gitignore: !cli.no_ignore,
+                use_ckignore: !cli.no_ckignore,
+                exclude_patterns: build_exclude_patterns(&cli),
+                include_globs: cli.include.clone(),
+                show_hidden: cli.hidden,
+                follow_links: cli.follow,
+                include_submodules: cli.index_submodules,
+                max_depth: cli.max_depth,
+                skip_generated: cli.no_generated,
+            };
+            let plan = ck_index::plan_index(&path, &file_options, cli.force_hash)?;
+            print_index_plan(&path, &plan, cli.verbose);
+            return Ok(());
+        }
 
         let registry = ck_models::ModelRegistry::default();
         let (model_alias, model_config) = registry
@@ -1070,7 +2804,12 @@ async fn run_cli_mode(cli: Cli) -> Result<()> {
                 respect_gitignore: !cli.no_ignore,
                 use_ckignore: !cli.no_ckignore,
                 exclude_patterns: exclude_patterns.clone(),
+                include_globs: cli.include.clone(),
                 show_hidden: cli.hidden,
+                follow_links: cli.follow,
+                include_submodules: cli.index_submodules,
+                max_depth: cli.max_depth,
+                skip_generated: cli.no_generated,
             };
             let cleanup_stats = ck_index::cleanup_index(&clean_path, &file_options)?;
             status.finish_progress(cleanup_spinner, "Cleanup complete");
@@ -1101,22 +2840,165 @@ async fn run_cli_mode(cli: Cli) -> Result<()> {
         return Ok(());
     }
 
+    if cli.gc {
+        let gc_path = cli.command_target_path();
+        let file_options = ck_core::FileCollectionOptions {
+            respect_gitignore: !cli.no_ignore,
+            use_ckignore: !cli.no_ckignore,
+            exclude_patterns: build_exclude_patterns(&cli),
+            include_globs: cli.include.clone(),
+            show_hidden: cli.hidden,
+            follow_links: cli.follow,
+            include_submodules: cli.index_submodules,
+            max_depth: cli.max_depth,
+            skip_generated: cli.no_generated,
+        };
+        if cli.json {
+            let stats = ck_index::compact_index(&gc_path, &file_options)?;
+            println!("{}", serde_json::to_string_pretty(&stats)?);
+            return Ok(());
+        }
+
+        status.section_header("Compacting Index");
+        let gc_spinner = status.create_spinner("Rewriting sidecars...");
+        let stats = ck_index::compact_index(&gc_path, &file_options)?;
+        status.finish_progress(gc_spinner, "Compaction complete");
+        status.info(&format!(
+            "Rewrote {} sidecars; removed {} orphaned entries, {} orphaned sidecars, {} unreadable sidecars and {} temporary files",
+            stats.sidecars_rewritten,
+            stats.orphaned_entries_removed,
+            stats.orphaned_sidecars_removed,
+            stats.unreadable_sidecars_removed,
+            stats.temp_files_removed
+        ));
+        status.success(&format!(
+            "Reclaimed {} ({} → {})",
+            format_bytes(stats.bytes_reclaimed()),
+            format_bytes(stats.bytes_before),
+            format_bytes(stats.bytes_after)
+        ));
+        return Ok(());
+    }
+
+    if cli.migrate {
+        let migrate_path = cli.command_target_path();
+        let stats = ck_index::migrate_index(&migrate_path)?;
+        if stats.migrated > 0 {
+            status.success(&format!(
+                "Migrated {} sidecars to index format {}",
+                stats.migrated,
+                ck_index::INDEX_FORMAT_VERSION
+            ));
+        } else if stats.failed.is_empty() {
+            status.success(&format!(
+                "Index is already at format {}",
+                ck_index::INDEX_FORMAT_VERSION
+            ));
+        }
+        if !stats.failed.is_empty() {
+            for path in &stats.failed {
+                eprintln!("  {}", path.display());
+            }
+            return Err(anyhow::anyhow!(
+                "{} sidecars could not be migrated; run `ck --reindex` to rebuild them",
+                stats.failed.len()
+            ));
+        }
+        return Ok(());
+    }
+
+    if cli.export {
+        let export_path = cli.command_target_path();
+        let stdout = std::io::stdout();
+        let mut out = std::io::BufWriter::new(stdout.lock());
+        let stats = ck_index::export_index(&export_path, cli.with_vectors, &mut out)?;
+        std::io::Write::flush(&mut out)?;
+        status.info(&format!(
+            "Exported {} chunks from {} files",
+            stats.chunks_exported, stats.files_exported
+        ));
+        if stats.files_skipped > 0 {
+            status.warn(&format!(
+                "Skipped {} files with unreadable sidecars",
+                stats.files_skipped
+            ));
+        }
+        return Ok(());
+    }
+
+    if let Some(dump) = cli.import.as_ref() {
+        let import_path = cli.command_target_path();
+        status.section_header("Importing Index");
+        status.info(&format!(
+            "Reading {} into {}",
+            dump.display(),
+            import_path.display()
+        ));
+
+        let stats = if dump.as_os_str() == "-" {
+            ck_index::import_index(&import_path, std::io::stdin().lock())?
+        } else {
+            let file = std::fs::File::open(dump)
+                .map_err(|e| anyhow::anyhow!("Failed to open {}: {}", dump.display(), e))?;
+            ck_index::import_index(&import_path, std::io::BufReader::new(file))?
+        };
+
+        status.success(&format!(
+            "Imported {} chunks for {} files",
+            stats.chunks_imported, stats.files_imported
+        ));
+        if stats.files_missing_locally > 0 {
+            status.warn(&format!(
+                "{} imported files do not exist under {}",
+                stats.files_missing_locally,
+                import_path.display()
+            ));
+        }
+        return Ok(());
+    }
+
     if cli.add {
-        // Handle --add flag
-        let file = cli
-            .files
-            .first()
-            .cloned()
-            .or_else(|| cli.pattern.as_ref().map(PathBuf::from))
-            .ok_or_else(|| anyhow::anyhow!("No file specified. Usage: ck --add <file>"))?;
-        status.section_header("Adding File to Index");
-        status.info(&format!("Processing {}", file.display()));
+        // Handle --add flag: the pattern slot holds the first path
+        let paths: Vec<PathBuf> = cli
+            .pattern
+            .iter()
+            .map(PathBuf::from)
+            .chain(cli.files.iter().cloned())
+            .collect();
+        if paths.is_empty() {
+            anyhow::bail!("No file specified. Usage: ck --add <path>...");
+        }
+        status.section_header("Adding Files to Index");
+        for path in &paths {
+            status.info(&format!("Processing {}", path.display()));
+        }
 
+        let file_options = ck_core::FileCollectionOptions {
+            respect_gitignore: !cli.no_ignore,
+            use_ckignore: !cli.no_ckignore,
+            exclude_patterns: build_exclude_patterns(&cli),
+            include_globs: cli.include.clone(),
+            show_hidden: cli.hidden,
+            follow_links: cli.follow,
+            include_submodules: cli.index_submodules,
+            max_depth: cli.max_depth,
+            skip_generated: cli.no_generated,
+        };
         let add_spinner = status.create_spinner("Updating index...");
-        ck_index::index_file(&file, true).await?;
-        status.finish_progress(add_spinner, "File indexed");
+        let stats = ck_index::add_paths(
+            &paths,
+            true,
+            &file_options,
+            cli.embed_batch_size.map(std::num::NonZeroUsize::get),
+            cli.force_hash,
+        )
+        .await?;
+        status.finish_progress(add_spinner, "Files indexed");
 
-        status.success(&format!("Added {} to index", file.display()));
+        status.success(&format!(
+            "Added {} and updated {} file(s) in the index ({} already up to date)",
+            stats.files_added, stats.files_updated, stats.files_up_to_date
+        ));
         return Ok(());
     }
 
@@ -1124,8 +3006,10 @@ async fn run_cli_mode(cli: Cli) -> Result<()> {
         // Handle --status, --status-verbose, and --status-json flags
         let status_path = cli.command_target_path();
         let verbose = cli.status_verbose;
+        let status_json = cli.status_json || cli.json;
+        let index_exists = ck_core::index_exists(&status_path);
 
-        let stats = if cli.status_json {
+        let stats = if status_json {
             // For JSON output, skip spinner and human-readable messages
             ck_index::get_index_stats(&status_path)?
         } else {
@@ -1136,19 +3020,12 @@ async fn run_cli_mode(cli: Cli) -> Result<()> {
             stats
         };
 
-        if cli.status_json {
-            // Output JSON format
-            let mut json_output = serde_json::json!({
-                "path": status_path.to_string_lossy(),
-                "index_exists": stats.total_files > 0,
-                "total_files": stats.total_files,
-                "total_chunks": stats.total_chunks,
-                "embedded_chunks": stats.embedded_chunks,
-                "total_size_bytes": stats.total_size_bytes,
-                "index_size_bytes": stats.index_size_bytes,
-                "index_created": stats.index_created,
-                "index_updated": stats.index_updated,
-            });
+        if status_json {
+            // Output JSON format: the IndexStats fields plus where and what model
+            let mut json_output = serde_json::to_value(&stats)?;
+            json_output["path"] = serde_json::json!(status_path.to_string_lossy());
+            json_output["index_exists"] = serde_json::json!(index_exists);
+            json_output["schema_version"] = serde_json::json!(ck_core::JSON_SCHEMA_VERSION);
 
             // Add model information if available
             let manifest_path = ck_core::index_dir(&status_path).join("manifest.json");
@@ -1182,9 +3059,13 @@ async fn run_cli_mode(cli: Cli) -> Result<()> {
             }
 
             println!("{}", serde_json::to_string_pretty(&json_output)?);
-        } else if stats.total_files == 0 {
+            if !index_exists {
+                std::process::exit(1);
+            }
+        } else if !index_exists {
             status.warn(&format!("No index found at {}", status_path.display()));
             status.info("Run 'ck --index .' to create an index");
+            std::process::exit(1);
         } else {
             status.info(&format!("Index location: {}", status_path.display()));
             status.success(&format!("Files indexed: {}", stats.total_files));
@@ -1227,7 +3108,24 @@ async fn run_cli_mode(cli: Cli) -> Result<()> {
                 let size_mb = stats.total_size_bytes as f64 / (1024.0 * 1024.0);
                 let index_size_mb = stats.index_size_bytes as f64 / (1024.0 * 1024.0);
                 status.info(&format!("  Source size: {size_mb:.1} MB"));
-                status.info(&format!("  Index size: {index_size_mb:.1} MB"));
+                let mut encodings = Vec::new();
+                if stats.compressed_sidecars {
+                    encodings.push("zstd-compressed sidecars".to_string());
+                }
+                if stats.quantization != ck_index::Quantization::None {
+                    encodings.push(format!("{} vectors", stats.quantization));
+                }
+                if let Some(dimensions) = stats.reduced_dimensions {
+                    encodings.push(format!("vectors reduced to {dimensions} dims"));
+                }
+                if encodings.is_empty() {
+                    status.info(&format!("  Index size: {index_size_mb:.1} MB"));
+                } else {
+                    status.info(&format!(
+                        "  Index size: {index_size_mb:.1} MB on disk ({})",
+                        encodings.join(", ")
+                    ));
+                }
 
                 use std::time::UNIX_EPOCH;
                 if stats.index_created > 0
@@ -1261,6 +3159,32 @@ async fn run_cli_mode(cli: Cli) -> Result<()> {
                         compression_ratio * 100.0
                     ));
                 }
+
+                if !stats.by_language.is_empty() {
+                    let mut languages: Vec<_> = stats.by_language.iter().collect();
+                    languages.sort_by(|a, b| b.1.files.cmp(&a.1.files).then(a.0.cmp(b.0)));
+                    let width = languages
+                        .iter()
+                        .map(|(lang, _)| lang.len())
+                        .max()
+                        .unwrap_or(0);
+                    status.info("  By language:");
+                    for (lang, counts) in languages {
+                        status.info(&format!(
+                            "    {lang:<width$}  {:>6} files  {:>7} chunks",
+                            counts.files, counts.chunks
+                        ));
+                    }
+                }
+
+                match stats.models_used.as_slice() {
+                    [] => {}
+                    [model] => status.info(&format!("  Models used: {model}")),
+                    models => status.warn(&format!(
+                        "  Models used: {} (mixed; rebuild with --switch-model to make them consistent)",
+                        models.join(", ")
+                    )),
+                }
             }
         }
         return Ok(());
@@ -1275,13 +3199,19 @@ async fn run_cli_mode(cli: Cli) -> Result<()> {
             cli.files[0].clone()
         } else {
             eprintln!("Error: --inspect requires a file path");
-            std::process::exit(1);
+            std::process::exit(EXIT_ERROR);
         };
 
         status.section_header("File Inspection");
 
         // Inspect the file metadata
-        inspect_file_metadata(&file_path, &status).await?;
+        inspect_file_metadata(
+            &file_path,
+            cli.chunk_overlap,
+            cli.chunk_strategy.map(Into::into),
+            &status,
+        )
+        .await?;
         return Ok(());
     }
 
@@ -1293,7 +3223,7 @@ async fn run_cli_mode(cli: Cli) -> Result<()> {
             cli.files[0].clone()
         } else {
             eprintln!("Error: --dump-chunks requires a file path");
-            std::process::exit(1);
+            std::process::exit(EXIT_ERROR);
         };
 
         dump_file_chunks(&file_path).await?;
@@ -1303,9 +3233,28 @@ async fn run_cli_mode(cli: Cli) -> Result<()> {
     // Validate conflicting flags
     if cli.files_with_matches && cli.files_without_matches {
         eprintln!("Error: Cannot use -l and -L together");
-        std::process::exit(1);
+        std::process::exit(EXIT_ERROR);
     }
 
+    // `-` as the path searches standard input, as one document named (stdin)
+    let stdin_document = if cli.pattern.is_some() && cli.files.iter().any(|f| f == Path::new("-")) {
+        if cli.files.len() > 1 {
+            eprintln!("Error: - (standard input) can't be searched together with other paths");
+            std::process::exit(EXIT_ERROR);
+        }
+        if cli.files_from.is_some() || cli.files_without_matches {
+            eprintln!("Error: --files-from and -L can't be used with - (standard input)");
+            std::process::exit(EXIT_ERROR);
+        }
+        cli.files.clear();
+        // Decoded lossily, as files are: invalid UTF-8 doesn't stop a search
+        let mut content = Vec::new();
+        std::io::Read::read_to_end(&mut std::io::stdin(), &mut content)?;
+        Some(ck_core::Document::stdin(String::from_utf8_lossy(&content)))
+    } else {
+        None
+    };
+
     // Default behavior: search with pattern
     if let Some(ref pattern) = cli.pattern {
         let reindex = cli.reindex;
@@ -1331,7 +3280,7 @@ async fn run_cli_mode(cli: Cli) -> Result<()> {
         let expanded_targets = if cli.files.is_empty() {
             vec![PathBuf::from(".")]
         } else {
-            expand_glob_patterns(&cli.files, &temp_options.exclude_patterns)?
+            expand_glob_patterns(&cli.files, &temp_options.exclude_patterns, cli.follow)?
         };
 
         let include_patterns = if cli.files.is_empty() {
@@ -1359,6 +3308,24 @@ async fn run_cli_mode(cli: Cli) -> Result<()> {
             include_patterns
         };
 
+        // --files-from names the files outright: no walk, so no excludes or
+        // ignore rules
+        let (explicit_files, line_ranges) = match cli.files_from.as_deref() {
+            Some(list) => {
+                let (files, ranges) = read_files_from(list, &status)?;
+                (Some(files), ranges)
+            }
+            None => (None, ck_core::LineRanges::default()),
+        };
+        let (include_patterns, search_root) = match &explicit_files {
+            Some(files) => {
+                let include_patterns = build_include_patterns(files);
+                let search_root = find_search_root(&include_patterns);
+                (include_patterns, search_root)
+            }
+            None => (include_patterns, search_root),
+        };
+
         // Handle multiple files like grep; allow -h/-H overrides
         let mut show_filenames = if include_patterns.is_empty() {
             expanded_targets.len() > 1 || expanded_targets.iter().any(|p| p.is_dir())
@@ -1375,34 +3342,116 @@ async fn run_cli_mode(cli: Cli) -> Result<()> {
         options.show_filenames = show_filenames;
         options.include_patterns = include_patterns.clone();
         options.path = search_root.clone();
+        options.explicit_files = explicit_files.unwrap_or_default();
+        options.line_ranges = line_ranges;
+        if let Some(document) = stdin_document {
+            options.document = Some(document);
+            // A single input, listed by line; its name only with -H
+            options.line_numbers = true;
+            options.show_filenames = cli.with_filenames;
+        }
 
-        let summary = run_search(pattern.clone(), search_root, options, &status).await?;
+        // Several directories in semantic mode are searched as separate index
+        // roots and ranked together
+        if options.mode == ck_core::SearchMode::Semantic
+            && options.similar_to.is_none()
+            && expanded_targets.len() > 1
+            && expanded_targets.iter().all(|target| target.is_dir())
+        {
+            options.roots = expanded_targets.clone();
+        }
 
+        let mut out = ResultOutput::open(cli.output.as_deref())?;
         if cli.files_without_matches {
-            let matched_canon: Vec<PathBuf> = summary
-                .matched_paths
-                .iter()
-                .map(|p| canonicalize_for_comparison(p))
-                .collect();
-
-            for target in &expanded_targets {
-                let canonical_target = canonicalize_for_comparison(target);
-                let target_is_dir = target.is_dir();
-                let has_match = matched_canon.iter().any(|matched| {
-                    if target_is_dir {
-                        matched.starts_with(&canonical_target)
-                    } else {
-                        matched == &canonical_target
-                    }
-                });
+            options.query = pattern.clone();
+            let spinner = status.create_spinner("Searching...");
+            let mut report = ck_engine::files_without_matches(&options).await?;
+            for file in report.files.iter_mut().chain(&mut report.not_indexed) {
+                restyle_path(file, cli.path_style);
+            }
+            status.finish_progress(
+                spinner,
+                &format!("{} files without matches", report.files.len()),
+            );
+            for file in report.files.iter().filter(|_| !cli.quiet) {
+                writeln!(out, "{}", file.display())?;
+            }
+            out.finish()?;
+            if !report.not_indexed.is_empty() && !cli.quiet {
+                eprintln!(
+                    "{}",
+                    style(format!(
+                        "{} file(s) are not in the index and could not be compared:",
+                        report.not_indexed.len()
+                    ))
+                    .dim()
+                );
+                for file in &report.not_indexed {
+                    eprintln!("  {}", file.display());
+                }
+            }
+            // Like grep -L: success when any file is listed
+            if report.files.is_empty() && report.not_indexed.is_empty() {
+                std::process::exit(EXIT_NO_MATCHES);
+            }
+            return Ok(());
+        }
+
+        if cli.verbose {
+            warn_unmatched_patterns(&cli, &search_root, &status)?;
+        }
 
-                if !has_match {
-                    println!("{}", target.display());
+        // Results written to a file are laid out as if piped
+        let to_terminal = cli.output.is_none() && std::io::stdout().is_terminal();
+        let heading = if cli.heading || cli.no_heading {
+            cli.heading
+        } else {
+            to_terminal
+        };
+        let terminal_width = to_terminal
+            .then(|| console::Term::stdout().size_checked())
+            .flatten()
+            .map(|(_, columns)| usize::from(columns));
+        let summary = run_search(
+            pattern.clone(),
+            search_root,
+            options,
+            cli.path_style,
+            cli.format,
+            heading,
+            PreviewWidth::resolve(cli.preview_width, terminal_width),
+            &status,
+            cli.stats,
+            cli.group_by_symbol,
+            &mut out,
+        )
+        .await?;
+        out.finish()?;
+        if let Some(stats) = &summary.stats {
+            stats.print();
+        }
+        if summary.truncated && !cli.quiet {
+            eprintln!("{}", style("(results truncated by timeout)").dim());
+        }
+        if !summary.unreadable.is_empty() {
+            status.warn(&format!(
+                "{} entries skipped (unreadable or symlink loops){}",
+                summary.unreadable.len(),
+                if cli.verbose {
+                    ":"
+                } else {
+                    " (--verbose lists them)"
                 }
+            ));
+            if cli.verbose {
+                print_unreadable(&summary.unreadable);
             }
         }
 
         // grep-like exit codes: 0 if matches found, 1 if none
+        if !summary.had_matches && cli.quiet {
+            std::process::exit(EXIT_NO_MATCHES);
+        }
         if !summary.had_matches {
             eprintln!("No matches found");
 
@@ -1414,7 +3463,9 @@ async fn run_cli_mode(cli: Cli) -> Result<()> {
 
                 // Get the pattern as a string
                 let options = build_options(&cli, false, repo_root);
-                let highlighted_preview = highlight_matches(&closest.preview, pattern, &options);
+                let terms = ck_engine::highlight_terms(pattern);
+                let highlighted_preview =
+                    highlight_matches(&closest.preview, pattern, &terms, &options);
 
                 // Print in red with same format as regular results, with header
                 eprintln!();
@@ -1426,18 +3477,48 @@ async fn run_cli_mode(cli: Cli) -> Result<()> {
                     style(closest.span.line_start).red(),
                     style(highlighted_preview).red()
                 );
+                for line in explain_lines(&closest) {
+                    eprintln!("    {}", style(line).dim());
+                }
+            }
+            if let Some(threshold) = build_options(&cli, false, repo_root).threshold
+                && let Some(suggestion) = threshold_suggestion(threshold, &summary.lower_thresholds)
+            {
+                eprintln!();
+                eprintln!("{}", style(suggestion).dim());
             }
 
-            std::process::exit(1);
+            std::process::exit(EXIT_NO_MATCHES);
         }
     } else {
         eprintln!("Error: No pattern specified");
-        std::process::exit(1);
+        std::process::exit(EXIT_ERROR);
     }
 
     Ok(())
 }
 
+/// A nudge towards the highest of `steps` below `threshold` that returns
+/// anything, for a semantic search that found nothing.
+fn threshold_suggestion(threshold: f32, steps: &[ck_core::ThresholdStep]) -> Option<String> {
+    let step = steps.iter().find(|step| step.results > 0)?;
+    Some(format!(
+        "No matches at ≥{threshold:.2}; lowering --threshold to {:.2} would return {} result{}",
+        step.threshold,
+        step.results,
+        if step.results == 1 { "" } else { "s" }
+    ))
+}
+
+/// `topk` and `threshold` used when the flags are not given: intelligent
+/// defaults for semantic search, unlimited otherwise.
+fn mode_defaults(mode: &SearchMode) -> (Option<usize>, Option<f32>) {
+    match mode {
+        SearchMode::Semantic => (Some(10), Some(0.6)),
+        _ => (None, None),
+    }
+}
+
 fn build_options(cli: &Cli, reindex: bool, _repo_root: Option<&Path>) -> SearchOptions {
     let mode = if cli.semantic {
         SearchMode::Semantic
@@ -1456,22 +3537,17 @@ fn build_options(cli: &Cli, reindex: bool, _repo_root: Option<&Path>) -> SearchO
     // Use the unified pattern builder
     let exclude_patterns = build_exclude_patterns(cli);
 
-    // Set intelligent defaults for semantic search
-    let default_topk = match mode {
-        SearchMode::Semantic => Some(10),
-        _ => None,
-    };
-    let default_threshold = match mode {
-        SearchMode::Semantic => Some(0.6),
-        _ => None,
-    };
+    let (default_topk, default_threshold) = mode_defaults(&mode);
 
     SearchOptions {
         mode,
         query: String::new(),
         path: PathBuf::from("."),
         top_k: cli.top_k.or(default_topk),
-        threshold: cli.threshold.or(default_threshold),
+        threshold: match cli.threshold_percentile {
+            Some(_) => None,
+            None => cli.threshold.or(default_threshold),
+        },
         case_insensitive: cli.ignore_case,
         whole_word: cli.word_regexp,
         fixed_string: cli.fixed_strings,
@@ -1494,16 +3570,69 @@ fn build_options(cli: &Cli, reindex: bool, _repo_root: Option<&Path>) -> SearchO
         use_ckignore: !cli.no_ckignore,
         full_section: cli.full_section,
         hidden: cli.hidden,
+        follow_links: cli.follow,
+        include_submodules: cli.index_submodules,
+        max_depth: cli.max_depth,
+        no_generated: cli.no_generated,
         // Enhanced embedding options (search-time only)
         rerank: cli.rerank,
         rerank_model: cli.rerank_model.clone(),
+        rerank_top_k: cli.rerank_k,
         embedding_model: cli.model.clone(),
+        embed_batch_size: cli.embed_batch_size.map(std::num::NonZeroUsize::get),
+        force_hash: cli.force_hash,
+        jobs: cli.jobs.map(std::num::NonZeroUsize::get),
+        ignore_model_mismatch: cli.ignore_model_mismatch,
+        fuzzy: cli.fuzzy,
+        bm25: cli.bm25_params(),
+        proximity: cli.proximity.unwrap_or(0.0),
+        match_on: cli.match_on.into(),
+        document: None,
+        split_identifiers: cli.split_identifiers,
+        include_globs: cli.include.clone(),
+        similar_to: cli.similar.clone(),
+        similar_by: cli.similar_by.into(),
+        roots: Vec::new(),
+        no_stale: cli.no_stale,
+        snippet_lines: cli.snippet_lines,
+        threshold_percentile: cli.threshold_percentile,
+        at_ref: cli.at.clone(),
+        dedup: cli
+            .dedup
+            .map(|similarity| similarity.unwrap_or(ck_engine::DEFAULT_DEDUP_SIMILARITY)),
+        no_auto_index: cli.no_auto_index,
+        ephemeral: cli.ephemeral,
+        sort: cli.sort.into(),
+        explain: cli.explain,
+        modified_since: cli.modified_since,
+        offset: cli.offset,
+        exact: cli.exact,
+        ef_search: cli.ef_search,
+        max_matches_per_file: cli.max_matches_per_file,
+        max_count: cli.max_count,
+        invert_match: cli.invert_match,
+        explicit_files: Vec::new(),
+        line_ranges: Default::default(),
+        cancel: None,
+        replace: cli.replace.clone(),
+        ascii_only: cli.no_unicode,
+        deadline: cli
+            .timeout
+            .map(|timeout| std::time::Instant::now() + timeout),
     }
 }
 
-fn highlight_matches(text: &str, pattern: &str, options: &SearchOptions) -> String {
-    // Don't highlight if this is JSON/JSONL output
-    if options.json_output || options.jsonl_output {
+/// `terms` are the query's tokenized forms from [`ck_engine::highlight_terms`],
+/// used by every mode except regex.
+fn highlight_matches(
+    text: &str,
+    pattern: &str,
+    terms: &[String],
+    options: &SearchOptions,
+) -> String {
+    // Don't highlight if this is JSON/JSONL output, or a --replace expansion
+    // rather than the text the pattern matched
+    if options.json_output || options.jsonl_output || options.replace.is_some() {
         return text.to_string();
     }
 
@@ -1511,34 +3640,146 @@ fn highlight_matches(text: &str, pattern: &str, options: &SearchOptions) -> Stri
         SearchMode::Regex => highlight_regex_matches(text, pattern, options),
         SearchMode::Semantic | SearchMode::Hybrid => {
             // For semantic/hybrid search, use subchunk similarity highlighting
-            highlight_semantic_chunks(text, pattern, options)
+            highlight_semantic_chunks(text, pattern, terms, options.whole_word)
         }
-        _ => text.to_string(),
+        SearchMode::Lexical => highlight_query_terms(text, terms, options.whole_word)
+            .unwrap_or_else(|| text.to_string()),
     }
 }
 
-fn highlight_regex_matches(text: &str, pattern: &str, options: &SearchOptions) -> String {
-    // Build regex from pattern with EXACT same logic as regex_search in ck-engine
-    let regex_pattern = if options.fixed_string {
-        regex::escape(pattern)
-    } else if options.whole_word {
-        // Must escape the pattern for whole_word, matching the search engine behavior
-        format!(r"\b{}\b", regex::escape(pattern))
-    } else {
-        pattern.to_string()
-    };
+/// Highlight the words in `text` that are one of the query `terms`, either
+/// whole (`getUserById`) or as one word of a compound identifier (`User` in
+/// `loadUserProfile`). With `whole_word` (`-w`) only whole words count.
+/// `None` when no term appears literally.
+fn highlight_query_terms(text: &str, terms: &[String], whole_word: bool) -> Option<String> {
+    let ranges = query_term_ranges(text, terms, whole_word);
+    if ranges.is_empty() {
+        return None;
+    }
+    let mut out = String::with_capacity(text.len());
+    let mut last = 0;
+    for (start, end) in ranges {
+        out.push_str(&text[last..start]);
+        out.push_str(&style(&text[start..end]).red().bold().to_string());
+        last = end;
+    }
+    out.push_str(&text[last..]);
+    Some(out)
+}
+
+/// Byte ranges of the words in `text` that [`highlight_query_terms`] would
+/// highlight, in order.
+fn query_term_ranges(text: &str, terms: &[String], whole_word: bool) -> Vec<(usize, usize)> {
+    if terms.is_empty() {
+        return Vec::new();
+    }
+    let is_term = |word: &str| terms.iter().any(|t| t.eq_ignore_ascii_case(word));
+
+    let is_word_char = |c: char| c.is_alphanumeric() || c == '_';
+    let mut runs = Vec::new();
+    let mut run_start = None;
+    for (i, c) in text.char_indices() {
+        match (is_word_char(c), run_start) {
+            (true, None) => run_start = Some(i),
+            (false, Some(start)) => {
+                runs.push((start, &text[start..i]));
+                run_start = None;
+            }
+            _ => {}
+        }
+    }
+    if let Some(start) = run_start {
+        runs.push((start, &text[start..]));
+    }
+
+    let mut ranges = Vec::new();
+    for (start, run) in runs {
+        if is_term(run) {
+            ranges.push((start, start + run.len()));
+            continue;
+        }
+        if whole_word {
+            continue;
+        }
+        let mut cursor = 0;
+        for word in ck_engine::split_identifier(run) {
+            let Some(offset) = run[cursor..].find(word.as_str()) else {
+                continue;
+            };
+            let word_start = cursor + offset;
+            if is_term(&word) {
+                ranges.push((start + word_start, start + word_start + word.len()));
+            }
+            cursor = word_start + word.len();
+        }
+    }
+    ranges
+}
+
+/// Byte ranges in `text` a JSON consumer should highlight: regex matches in
+/// regex mode, otherwise the query terms that appear literally.
+fn highlight_ranges(
+    text: &str,
+    pattern: &str,
+    terms: &[String],
+    options: &SearchOptions,
+) -> Vec<(usize, usize)> {
+    match options.mode {
+        SearchMode::Regex if options.invert_match || options.replace.is_some() => Vec::new(),
+        SearchMode::Regex => ck_engine::SearchRegex::new(pattern, options)
+            .map(|re| {
+                re.find_iter(text)
+                    .filter(|m| !m.is_empty())
+                    .map(|m| (m.start, m.end))
+                    .collect()
+            })
+            .unwrap_or_default(),
+        SearchMode::Semantic | SearchMode::Hybrid | SearchMode::Lexical => {
+            query_term_ranges(text, terms, options.whole_word)
+        }
+    }
+}
+
+/// What a long preview line is windowed around: the regex match, or for
+/// the other modes the first query term.
+fn preview_focus(options: &SearchOptions, terms: &[String]) -> Option<ck_engine::SearchRegex> {
+    match options.mode {
+        SearchMode::Regex if !options.invert_match => {
+            ck_engine::SearchRegex::new(&options.query, options).ok()
+        }
+        SearchMode::Regex => None,
+        _ if terms.is_empty() => None,
+        _ => {
+            let alternatives: Vec<String> = terms.iter().map(|t| regex::escape(t)).collect();
+            let pattern = if options.whole_word {
+                format!(r"\b(?:{})\b", alternatives.join("|"))
+            } else {
+                alternatives.join("|")
+            };
+            RegexBuilder::new(&pattern)
+                .case_insensitive(true)
+                .build()
+                .ok()
+                .map(ck_engine::SearchRegex::from)
+        }
+    }
+}
 
-    let regex_result = RegexBuilder::new(&regex_pattern)
-        .case_insensitive(options.case_insensitive)
-        .build();
+fn highlight_regex_matches(text: &str, pattern: &str, options: &SearchOptions) -> String {
+    let regex_result = ck_engine::SearchRegex::new(pattern, options);
 
     match regex_result {
         Ok(re) => {
             // Replace matches with highlighted versions
-            re.replace_all(text, |caps: &regex::Captures| {
-                style(&caps[0]).red().bold().to_string()
-            })
-            .to_string()
+            let mut highlighted = String::with_capacity(text.len());
+            let mut cursor = 0;
+            for range in re.find_iter(text) {
+                highlighted.push_str(&text[cursor..range.start]);
+                highlighted.push_str(&style(&text[range.clone()]).red().bold().to_string());
+                cursor = range.end;
+            }
+            highlighted.push_str(&text[cursor..]);
+            highlighted
         }
         Err(e) => {
             // Surface regex compilation error to user
@@ -1549,14 +3790,22 @@ fn highlight_regex_matches(text: &str, pattern: &str, options: &SearchOptions) -
     }
 }
 
-fn highlight_semantic_chunks(text: &str, pattern: &str, _options: &SearchOptions) -> String {
+fn highlight_semantic_chunks(
+    text: &str,
+    pattern: &str,
+    terms: &[String],
+    whole_word: bool,
+) -> String {
     let tokens = heatmap::split_into_tokens(text);
 
+    // Query terms that appear literally win over the fuzzy heatmap
     let highlighted_tokens: Vec<String> = tokens
         .into_iter()
         .map(|token| {
-            let similarity_score = heatmap::calculate_token_similarity(&token, pattern);
-            apply_heatmap_color(&token, similarity_score)
+            highlight_query_terms(&token, terms, whole_word).unwrap_or_else(|| {
+                let similarity_score = heatmap::calculate_token_similarity(&token, pattern);
+                apply_heatmap_color(&token, similarity_score)
+            })
         })
         .collect();
 
@@ -1568,6 +3817,10 @@ fn apply_heatmap_color(token: &str, score: f32) -> String {
         return token.to_string();
     }
 
+    if !color::enabled() {
+        return token.to_string();
+    }
+
     let bucket = HeatmapBucket::from_score(score);
 
     match bucket.rgb() {
@@ -1586,25 +3839,95 @@ fn apply_heatmap_color(token: &str, score: f32) -> String {
 struct SearchSummary {
     had_matches: bool,
     closest_below_threshold: Option<ck_core::SearchResult>,
-    matched_paths: Vec<PathBuf>,
+    /// `--timeout` cut the search short
+    truncated: bool,
+    /// With no semantic match, how many results lower thresholds would give
+    lower_thresholds: Vec<ck_core::ThresholdStep>,
+    /// Entries the search couldn't read, with the reason
+    unreadable: Vec<(PathBuf, String)>,
+    /// Per-stage timings, when `--stats` asked for them.
+    stats: Option<SearchStats>,
+}
+
+/// Where the time of one search went, for `--stats`.
+struct SearchStats {
+    /// `None` when the search never touched the index (regex mode).
+    index_update: Option<std::time::Duration>,
+    search: ck_core::SearchTimings,
+    formatting: std::time::Duration,
+    total: std::time::Duration,
+}
+
+impl SearchStats {
+    fn print(&self) {
+        let stage = |name: &str, duration: Option<std::time::Duration>| {
+            let value = duration.map_or_else(
+                || "-".to_string(),
+                |d| format!("{:.1} ms", d.as_secs_f64() * 1000.0),
+            );
+            eprintln!("  {name:<16}{value:>12}");
+        };
+        eprintln!("{}", style("Search timings:").bold());
+        stage("index update", self.index_update);
+        stage("model load", self.search.model_load);
+        stage("query embed", self.search.query_embed);
+        stage("candidate load", self.search.candidate_load);
+        stage("scoring", self.search.scoring);
+        stage("rerank", self.search.rerank);
+        stage("formatting", Some(self.formatting));
+        stage("total", Some(self.total));
+    }
 }
 
+#[allow(clippy::too_many_arguments)]
 async fn run_search(
     pattern: String,
     path: PathBuf,
     mut options: SearchOptions,
+    path_style: Option<PathStyle>,
+    format: Option<OutputFormat>,
+    heading: bool,
+    preview_width: PreviewWidth,
     status: &StatusReporter,
+    stats: bool,
+    group_by_symbol: bool,
+    out: &mut ResultOutput,
 ) -> Result<SearchSummary> {
+    let started = std::time::Instant::now();
     options.query = pattern;
     options.path = path;
 
-    if options.reindex {
+    // With several roots each one is reindexed by its own search
+    let mut reindex_time = None;
+    if options.reindex && options.roots.is_empty() && options.document.is_none() {
         let reindex_spinner = status.create_spinner("Updating index...");
         let file_options = ck_core::FileCollectionOptions::from(&options);
-        ck_index::update_index(&options.path, true, &file_options).await?;
+        let reindex_started = std::time::Instant::now();
+        ck_index::update_index(
+            &options.path,
+            true,
+            &file_options,
+            options.embed_batch_size,
+            options.jobs,
+        )
+        .await?;
+        reindex_time = Some(reindex_started.elapsed());
         status.finish_progress(reindex_spinner, "Index updated");
     }
 
+    if options.no_auto_index
+        && options.document.is_none()
+        && (options.mode != ck_core::SearchMode::Regex
+            || options.match_on == ck_core::MatchOn::Chunks)
+        && let Ok(Some(freshness)) = ck_engine::index_freshness(&options)
+        && !freshness.is_fresh()
+    {
+        status.warn(&format!(
+            "The index is out of date: {} file(s) changed and {} new since it was last updated. Results may miss or misplace them; run 'ck --index' or drop --no-auto-index",
+            freshness.changed_files, freshness.new_files
+        ));
+    }
+
     // Show search parameters for semantic mode
     if matches!(
         options.mode,
@@ -1618,8 +3941,9 @@ async fn run_search(
             .map_or("none".to_string(), |t| format!("{t:.1}"));
         eprintln!("ℹ Semantic search: top {topk_info} results, threshold ≥{threshold_info}");
 
+        let model_path = options.roots.first().unwrap_or(&options.path);
         let resolved_model =
-            ck_engine::resolve_model_for_path(&options.path, options.embedding_model.as_deref())?;
+            ck_engine::resolve_model_for_path(model_path, options.embedding_model.as_deref())?;
 
         if resolved_model.alias == resolved_model.canonical_name() {
             eprintln!(
@@ -1737,44 +4061,80 @@ async fn run_search(
         (None, None)
     };
 
-    let search_results = ck_engine::search_enhanced_with_indexing_progress(
+    let outcome = ck_engine::search_enhanced_with_outcome(
         &options,
         search_progress_callback,
         indexing_progress_callback,
         detailed_indexing_progress_callback,
     )
     .await?;
+    let mut search_results = outcome.results;
+    let formatting_started = std::time::Instant::now();
+    // `--at` results are labelled REF:path, and standard input's (stdin):
+    // neither names a file on disk
+    if options.at_ref.is_none() && options.document.is_none() {
+        let closest = search_results.closest_below_threshold.as_mut();
+        for result in search_results.matches.iter_mut().chain(closest) {
+            restyle_path(&mut result.file, path_style);
+        }
+    }
+    if group_by_symbol {
+        search_results.matches = symbols::group_by_symbol(search_results.matches);
+    }
     let results = &search_results.matches;
-    let matched_paths: Vec<PathBuf> = results.iter().map(|result| result.file.clone()).collect();
 
     status.finish_progress(search_spinner, &format!("Found {} results", results.len()));
 
+    let terms = ck_engine::highlight_terms(&options.query);
     let mut has_matches = false;
-    if options.jsonl_output {
+    if status.quiet {
+        // -q: only the exit status reports whether anything matched
+        has_matches = !results.is_empty();
+    } else if let Some(format) = format {
+        let mut printed = std::collections::HashSet::new();
+        for result in results {
+            has_matches = true;
+            let line = quickfix_line(result, format);
+            // Like grep, a line with several matches is listed once; vimgrep
+            // lines differ by column and are all kept
+            if format == OutputFormat::Vimgrep || printed.insert(line.clone()) {
+                writeln!(out, "{line}")?;
+            }
+        }
+    } else if options.jsonl_output {
         for result in results {
             has_matches = true;
-            let jsonl_result =
+            let mut jsonl_result =
                 ck_core::JsonlSearchResult::from_search_result(result, !options.no_snippet);
-            println!("{}", serde_json::to_string(&jsonl_result)?);
+            if let Some(snippet) = &jsonl_result.snippet {
+                jsonl_result.highlights =
+                    highlight_ranges(snippet, &options.query, &terms, &options);
+            }
+            writeln!(out, "{}", serde_json::to_string(&jsonl_result)?)?;
         }
     } else if options.json_output {
         for result in results {
             has_matches = true;
             let json_result = ck_core::JsonSearchResult {
+                schema_version: ck_core::JSON_SCHEMA_VERSION,
                 file: result.file.display().to_string(),
                 span: result.span.clone(),
                 lang: result.lang,
                 symbol: result.symbol.clone(),
                 score: result.score,
                 signals: ck_core::SearchSignals {
-                    lex_rank: None,
-                    vec_rank: None,
+                    lex_rank: result.explanation.as_ref().and_then(|e| e.keyword_rank),
+                    vec_rank: result.explanation.as_ref().and_then(|e| e.semantic_rank),
                     rrf_score: result.score,
                 },
                 preview: result.preview.clone(),
                 model: "none".to_string(),
+                match_line: result.match_line,
+                highlights: highlight_ranges(&result.preview, &options.query, &terms, &options),
+                context_before: result.context_before.clone(),
+                context_after: result.context_after.clone(),
             };
-            println!("{}", serde_json::to_string(&json_result)?);
+            writeln!(out, "{}", serde_json::to_string(&json_result)?)?;
         }
     } else if options.files_with_matches {
         // For -l flag: print only unique filenames that have matches
@@ -1783,64 +4143,333 @@ async fn run_search(
             has_matches = true;
             let file_path = &result.file;
             if printed_files.insert(file_path.clone()) {
-                println!("{}", file_path.display());
+                writeln!(out, "{}", file_path.display())?;
             }
         }
-    } else if options.files_without_matches {
-        // For -L flag: just set has_matches, printing is done later
-        has_matches = !results.is_empty();
     } else {
         // Normal output
-        for result in results {
+        let focus = preview_focus(&options, &terms);
+        // Grouped under one header per file; without filenames there is
+        // nothing to group by
+        let heading = heading && options.show_filenames;
+        let ordered = if heading {
+            group_by_file(results)
+        } else {
+            results.iter().collect()
+        };
+        let mut current_file: Option<&Path> = None;
+        let mut previous_multiline = false;
+        for result in ordered {
             has_matches = true;
-            let score_text = if options.show_scores {
-                format!("[{:.3}] ", result.score)
+            let mut score_text = if options.show_scores {
+                match result.original_score {
+                    Some(original) => format!("[{:.3} ← {:.3}] ", result.score, original),
+                    None => format!("[{:.3}] ", result.score),
+                }
             } else {
                 String::new()
             };
+            if result.stale {
+                score_text.push_str(&format!("{} ", style("(index stale)").dim()));
+            }
+            if result.duplicates > 0 {
+                score_text.push_str(&format!(
+                    "{} ",
+                    style(format!("(+{} near-duplicates)", result.duplicates)).dim()
+                ));
+            }
+            if let Some(symbol) = result.symbol.as_ref().filter(|_| result.grouped > 0) {
+                let matches = match result.grouped {
+                    1 => "1 match".to_string(),
+                    count => format!("{count} matches"),
+                };
+                score_text.push_str(&format!(
+                    "{} {} ",
+                    style(symbol).bold(),
+                    style(format!(
+                        "(lines {}-{}, {matches})",
+                        result.span.line_start, result.span.line_end
+                    ))
+                    .dim()
+                ));
+            }
+
+            // A notebook's line numbers count lines of its extracted cells;
+            // point at the cell instead
+            let notebook_cell = result
+                .symbol
+                .as_ref()
+                .filter(|_| ck_core::notebook::is_notebook_file(&result.file));
+            let preview_line = match notebook_cell {
+                Some(cell) => cell.clone(),
+                None => result
+                    .preview_line
+                    .unwrap_or(result.span.line_start)
+                    .to_string(),
+            };
 
-            let highlighted_preview = highlight_matches(&result.preview, &options.query, &options);
+            // What shares the preview's first line, in the layouts below
+            let score_width = console::measure_text_width(&score_text);
+            let prefix_width = match (heading, options.line_numbers, options.show_filenames) {
+                (true, true, _) | (false, true, false) => score_width + preview_line.len() + 1,
+                (false, true, true) => {
+                    let file_width =
+                        console::measure_text_width(&result.file.display().to_string());
+                    score_width + file_width + preview_line.len() + 2
+                }
+                // The filename and score have a line of their own
+                (false, false, true) => 0,
+                _ => score_width,
+            };
+            let preview = match preview_width.columns(prefix_width) {
+                Some((width, first_line_width)) => preview::truncate_preview(
+                    &result.preview,
+                    width,
+                    first_line_width,
+                    focus.as_ref(),
+                ),
+                None => result.preview.clone(),
+            };
+            let highlighted_preview = highlight_matches(&preview, &options.query, &terms, &options);
+            // In a whole section, point out the line that matched
+            let highlighted_preview = match result.match_line.filter(|_| options.full_section) {
+                Some(line) => mark_match_line(
+                    &highlighted_preview,
+                    result.preview_line.unwrap_or(result.span.line_start),
+                    line,
+                ),
+                None => highlighted_preview,
+            };
 
             // Format output based on options
-            if options.line_numbers && options.show_filenames {
+            if heading {
+                let multiline = highlighted_preview.contains('\n');
+                if current_file != Some(result.file.as_path()) {
+                    if current_file.is_some() {
+                        writeln!(out)?;
+                    }
+                    writeln!(out, "{}", style(result.file.display()).cyan().bold())?;
+                    current_file = Some(&result.file);
+                } else if multiline || previous_multiline {
+                    // Keep several-line previews in one file apart
+                    writeln!(out, "{}", style("--").dim())?;
+                }
+                previous_multiline = multiline;
+                if options.line_numbers {
+                    writeln!(
+                        out,
+                        "{}{}:{}",
+                        score_text,
+                        style(preview_line).yellow(),
+                        highlighted_preview
+                    )?;
+                } else {
+                    writeln!(out, "{score_text}{highlighted_preview}")?;
+                }
+            } else if options.line_numbers && options.show_filenames {
                 // grep format: filename:line_number:content (all on one line)
-                println!(
+                writeln!(
+                    out,
                     "{}{}:{}:{}",
                     score_text,
                     style(result.file.display()).cyan().bold(),
-                    style(result.span.line_start).yellow(),
+                    style(preview_line).yellow(),
                     highlighted_preview
-                );
+                )?;
             } else if options.line_numbers {
                 // Just line number when no filename
-                println!(
+                writeln!(
+                    out,
                     "{}{}:{}",
                     score_text,
-                    style(result.span.line_start).yellow(),
+                    style(preview_line).yellow(),
                     highlighted_preview
-                );
+                )?;
             } else if options.show_filenames {
                 // Filename on separate line when no line numbers (more readable for semantic search)
-                println!(
+                let location = match notebook_cell {
+                    Some(cell) => format!("{}:{cell}", result.file.display()),
+                    None => result.file.display().to_string(),
+                };
+                writeln!(
+                    out,
                     "{}{}:\n{}",
                     score_text,
-                    style(result.file.display()).cyan().bold(),
+                    style(location).cyan().bold(),
                     highlighted_preview
-                );
+                )?;
             } else {
                 // No filename or line number
-                println!("{score_text}{highlighted_preview}");
+                writeln!(out, "{score_text}{highlighted_preview}")?;
             }
+            for line in explain_lines(result) {
+                writeln!(out, "    {}", style(line).dim())?;
+            }
+        }
+
+        let stale_files: std::collections::HashSet<&PathBuf> = results
+            .iter()
+            .filter(|result| result.stale)
+            .map(|result| &result.file)
+            .collect();
+        if !stale_files.is_empty() {
+            status.warn(&format!(
+                "{} file(s) changed since they were indexed; line numbers may be off. Re-run with --reindex to refresh them or --no-stale to hide them",
+                stale_files.len()
+            ));
         }
     }
 
+    let stats = stats.then(|| {
+        let auto_update = outcome
+            .index_update
+            .map(|update| std::time::Duration::from_millis(update.duration_ms));
+        SearchStats {
+            index_update: match (reindex_time, auto_update) {
+                (Some(a), Some(b)) => Some(a + b),
+                (a, b) => a.or(b),
+            },
+            search: search_results.timings,
+            formatting: formatting_started.elapsed(),
+            total: started.elapsed(),
+        }
+    });
+
     Ok(SearchSummary {
         had_matches: has_matches,
         closest_below_threshold: search_results.closest_below_threshold,
-        matched_paths,
+        truncated: search_results.truncated,
+        lower_thresholds: search_results.lower_thresholds,
+        unreadable: outcome.unreadable,
+        stats,
     })
 }
 
+/// `preview`, which starts on line `first_line`, with a `>` gutter on
+/// `match_line` and a blank one on every other line. Single-line previews
+/// and matches outside the preview are left as they are.
+fn mark_match_line(preview: &str, first_line: usize, match_line: usize) -> String {
+    let offset = match_line.checked_sub(first_line);
+    if !preview.contains('\n') || offset.is_none_or(|offset| offset >= preview.lines().count()) {
+        return preview.to_string();
+    }
+    preview
+        .lines()
+        .enumerate()
+        .map(|(index, line)| {
+            if Some(index) == offset {
+                format!("{} {line}", style(">").green().bold())
+            } else {
+                format!("  {line}")
+            }
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+/// `--explain` lines for one result, from its score breakdown (regex
+/// results, which have no score, get none).
+fn explain_lines(result: &ck_core::SearchResult) -> Vec<String> {
+    let Some(explanation) = &result.explanation else {
+        return Vec::new();
+    };
+    let mut lines = Vec::new();
+
+    if let Some(k) = explanation.rrf_k {
+        let weight = explanation.keyword_weight.unwrap_or(1.0);
+        let part = |rank: Option<usize>, name: &str, weight: f32| match rank {
+            Some(rank) if weight != 1.0 => format!("{weight} × 1/({k}+{rank}) {name} rank"),
+            Some(rank) => format!("1/({k}+{rank}) {name} rank"),
+            None => format!("no {name} match"),
+        };
+        lines.push(format!(
+            "hybrid score {:.4} = {} + {}",
+            result.score,
+            part(explanation.keyword_rank, "keyword", weight),
+            part(explanation.semantic_rank, "semantic", 1.0)
+        ));
+        if let Some(similarity) = explanation.similarity {
+            lines.push(format!("semantic similarity {similarity:.3}"));
+        }
+    } else if let Some(similarity) = explanation.similarity {
+        lines.push(format!("similarity {similarity:.3}"));
+    } else if let Some(bm25) = explanation.bm25 {
+        lines.push(format!(
+            "BM25 {bm25:.3}, scored {:.3} relative to the best match",
+            result.score
+        ));
+    }
+
+    // In hybrid mode a rerank only changed the semantic arm's ordering
+    if let Some(original) = result.original_score
+        && explanation.rrf_k.is_none()
+    {
+        lines.push(format!(
+            "reranked {original:.3} → {:.3} ({:+.3})",
+            result.score,
+            result.score - original
+        ));
+    }
+
+    if let Some(threshold) = explanation.threshold {
+        // Semantic thresholds apply to the similarity, before any rerank
+        let compared = match explanation.rrf_k {
+            Some(_) => result.score,
+            None => explanation
+                .similarity
+                .unwrap_or(result.original_score.unwrap_or(result.score)),
+        };
+        let verdict = if compared >= threshold {
+            "passed"
+        } else {
+            "below"
+        };
+        lines.push(format!(
+            "threshold {threshold:.3}: {verdict} ({compared:.3})"
+        ));
+    }
+
+    let mut chunk = format!(
+        "matched lines {}-{}",
+        result.span.line_start, result.span.line_end
+    );
+    if let Some(symbol) = &result.symbol {
+        chunk.push_str(&format!(" in {symbol}"));
+    }
+    lines.push(chunk);
+    lines
+}
+
+/// One `--format` line: regex results point at the match, ranked results
+/// at the first line of their preview.
+/// Results reordered so each file's are together, files in the order their
+/// first result appears and each file's results in their original order.
+fn group_by_file(results: &[ck_core::SearchResult]) -> Vec<&ck_core::SearchResult> {
+    let mut groups: Vec<Vec<&ck_core::SearchResult>> = Vec::new();
+    let mut group_of: std::collections::HashMap<&Path, usize> = std::collections::HashMap::new();
+    for result in results {
+        let index = *group_of.entry(result.file.as_path()).or_insert_with(|| {
+            groups.push(Vec::new());
+            groups.len() - 1
+        });
+        groups[index].push(result);
+    }
+    groups.into_iter().flatten().collect()
+}
+
+fn quickfix_line(result: &ck_core::SearchResult, format: OutputFormat) -> String {
+    let line = result.preview_line.unwrap_or(result.span.line_start);
+    let text = result.preview.lines().next().unwrap_or_default();
+    match format {
+        OutputFormat::Vimgrep => format!(
+            "{}:{line}:{}:{text}",
+            result.file.display(),
+            result.column.unwrap_or(1)
+        ),
+        OutputFormat::Grep => format!("{}:{line}:{text}", result.file.display()),
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -1867,7 +4496,7 @@ mod tests {
         fs::write(&nested_rust_file, "pub fn lib() {}\n").unwrap();
 
         let expanded =
-            expand_glob_patterns_with_base(base, &[PathBuf::from("*.rs;*.html;docs/")], &[])
+            expand_glob_patterns_with_base(base, &[PathBuf::from("*.rs;*.html;docs/")], &[], false)
                 .expect("pattern expansion");
 
         let has_example = expanded.iter().any(|p| p.ends_with("example.rs"));
@@ -1972,4 +4601,201 @@ mod tests {
         // Should work fine because whole_word escapes the pattern
         assert!(result.contains("[world]"));
     }
+
+    #[test]
+    fn test_explain_lines_cover_similarity_rerank_and_threshold() {
+        let mut result = ck_core::SearchResult {
+            file: PathBuf::from("src/auth.rs"),
+            span: ck_core::Span {
+                byte_start: 0,
+                byte_end: 10,
+                line_start: 10,
+                line_end: 24,
+            },
+            score: 0.91,
+            preview: String::new(),
+            lang: None,
+            symbol: Some("login".to_string()),
+            chunk_hash: None,
+            index_epoch: None,
+            original_score: Some(0.74),
+            stale: false,
+            root: None,
+            preview_line: None,
+            duplicates: 0,
+            grouped: 0,
+            column: None,
+            match_line: None,
+            explanation: None,
+            context_before: None,
+            context_after: None,
+        };
+        assert!(explain_lines(&result).is_empty());
+
+        result.explanation = Some(ck_core::ScoreBreakdown {
+            similarity: Some(0.74),
+            threshold: Some(0.6),
+            ..Default::default()
+        });
+        assert_eq!(
+            explain_lines(&result),
+            [
+                "similarity 0.740",
+                "reranked 0.740 → 0.910 (+0.170)",
+                "threshold 0.600: passed (0.740)",
+                "matched lines 10-24 in login",
+            ]
+        );
+
+        result.original_score = None;
+        result.score = 0.3 / 62.0 + 1.0 / 61.0;
+        result.explanation = Some(ck_core::ScoreBreakdown {
+            keyword_rank: Some(2),
+            semantic_rank: Some(1),
+            keyword_weight: Some(0.3),
+            rrf_k: Some(60.0),
+            ..Default::default()
+        });
+        assert_eq!(
+            explain_lines(&result)[0],
+            "hybrid score 0.0212 = 0.3 × 1/(60+2) keyword rank + 1/(60+1) semantic rank"
+        );
+    }
+
+    #[test]
+    fn test_group_by_file_keeps_first_appearance_order() {
+        let result = |file: &str, line: usize| ck_core::SearchResult {
+            file: PathBuf::from(file),
+            span: ck_core::Span {
+                byte_start: 0,
+                byte_end: 1,
+                line_start: line,
+                line_end: line,
+            },
+            score: 1.0,
+            preview: String::new(),
+            lang: None,
+            symbol: None,
+            chunk_hash: None,
+            index_epoch: None,
+            original_score: None,
+            stale: false,
+            root: None,
+            preview_line: None,
+            duplicates: 0,
+            grouped: 0,
+            column: None,
+            match_line: None,
+            explanation: None,
+            context_before: None,
+            context_after: None,
+        };
+        let results = [
+            result("b.rs", 9),
+            result("a.rs", 3),
+            result("b.rs", 1),
+            result("a.rs", 2),
+            result("c.rs", 5),
+        ];
+        let grouped: Vec<(String, usize)> = group_by_file(&results)
+            .into_iter()
+            .map(|r| (r.file.display().to_string(), r.span.line_start))
+            .collect();
+        assert_eq!(
+            grouped,
+            [
+                ("b.rs".to_string(), 9),
+                ("b.rs".to_string(), 1),
+                ("a.rs".to_string(), 3),
+                ("a.rs".to_string(), 2),
+                ("c.rs".to_string(), 5),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_threshold_suggestion_picks_first_step_with_results() {
+        let step = |threshold, results| ck_core::ThresholdStep { threshold, results };
+        assert_eq!(
+            threshold_suggestion(0.6, &[step(0.55, 0), step(0.5, 4), step(0.45, 9)]).as_deref(),
+            Some("No matches at ≥0.60; lowering --threshold to 0.50 would return 4 results")
+        );
+        assert!(threshold_suggestion(0.6, &[step(0.55, 0)]).is_none());
+        assert!(threshold_suggestion(0.6, &[]).is_none());
+    }
+
+    #[test]
+    fn test_parse_timeout_units() {
+        use std::time::Duration;
+        assert_eq!(parse_timeout("2s").unwrap(), Duration::from_secs(2));
+        assert_eq!(parse_timeout("500ms").unwrap(), Duration::from_millis(500));
+        assert_eq!(parse_timeout("1m").unwrap(), Duration::from_secs(60));
+        assert_eq!(parse_timeout("1.5").unwrap(), Duration::from_millis(1500));
+        assert!(parse_timeout("0s").is_err());
+        assert!(parse_timeout("2h").is_err());
+        assert!(parse_timeout("soon").is_err());
+    }
+
+    #[test]
+    fn test_parse_modified_since_durations_and_dates() {
+        let week_ago = parse_modified_since("7d").unwrap();
+        let now = std::time::SystemTime::now();
+        let elapsed = now.duration_since(week_ago).unwrap().as_secs();
+        assert!((7 * 24 * 3600..7 * 24 * 3600 + 60).contains(&elapsed));
+        assert!(parse_modified_since("90m").unwrap() > parse_modified_since("2h").unwrap());
+
+        let date = parse_modified_since("2024-05-01").unwrap();
+        let timestamp = parse_modified_since("2024-05-01T12:00:00Z").unwrap();
+        let hours_apart = timestamp
+            .duration_since(date)
+            .or_else(|e| Ok::<_, ()>(e.duration()))
+            .unwrap()
+            .as_secs()
+            / 3600;
+        assert!(
+            hours_apart <= 26,
+            "local midnight is within a day of noon UTC"
+        );
+
+        assert!(parse_modified_since("7y").is_err());
+        assert!(parse_modified_since("last week").is_err());
+    }
+
+    #[test]
+    fn test_highlight_query_terms_matches_whole_and_split_words() {
+        let terms = ck_engine::highlight_terms("user lookup");
+
+        let text = "fn loadUserProfile(user_id: u32) -> Lookup";
+        let result = highlight_query_terms(text, &terms, false).expect("terms appear literally");
+        assert_eq!(console::strip_ansi_codes(&result), text);
+
+        // "users" is not "user"; nothing matches, so the caller shows plain text
+        assert!(highlight_query_terms("let users = all();", &terms, false).is_none());
+        assert!(highlight_query_terms(text, &[], false).is_none());
+    }
+
+    #[test]
+    fn test_query_term_ranges_are_byte_offsets() {
+        let terms = ck_engine::highlight_terms("user lookup");
+        let text = "fn loadUserProfile(user_id: u32) -> Lookup";
+        let words: Vec<&str> = query_term_ranges(text, &terms, false)
+            .into_iter()
+            .map(|(start, end)| &text[start..end])
+            .collect();
+        assert_eq!(words, ["User", "user", "Lookup"]);
+
+        // Offsets count bytes, not characters
+        assert_eq!(query_term_ranges("// café user", &terms, false), [(9, 13)]);
+    }
+
+    #[test]
+    fn test_query_term_ranges_whole_word_skips_identifier_parts() {
+        let terms = ck_engine::highlight_terms("user lookup");
+        let text = "fn loadUserProfile(user_id: u32) -> Lookup";
+        let words: Vec<&str> = query_term_ranges(text, &terms, true)
+            .into_iter()
+            .map(|(start, end)| &text[start..end])
+            .collect();
+        assert_eq!(words, ["Lookup"]);
+    }
 }
@@ -4,9 +4,8 @@ use std::sync::Arc;
 use std::time::{Duration, SystemTime};
 use tokio::sync::RwLock;
 
-// EmbedderCache removed - embedder caching would require deeper integration with ck-engine.
-// Currently, search functions create embedders internally via ck_embed::create_embedder().
-// Future optimization: expose search APIs that accept pre-created embedders for true caching.
+// Embedders are kept warm between tool calls by ck_embed::embedder_pool,
+// which the server enables at startup; ck-engine draws query embedders from it.
 
 /// Cache for index statistics with TTL. Stores the full `ck_index::IndexStats`
 /// so cache hits report the same fields as fresh reads (chunk counts, embedded
@@ -110,10 +110,11 @@ fn resolve_include_patterns(
         }
     }
 
-    let expanded = expand_glob_patterns_with_base(base_path, &prepared_patterns, exclude_patterns)
-        .map_err(|e| {
-            ErrorData::invalid_params(format!("Failed to expand include patterns: {e}"), None)
-        })?;
+    let expanded =
+        expand_glob_patterns_with_base(base_path, &prepared_patterns, exclude_patterns, false)
+            .map_err(|e| {
+                ErrorData::invalid_params(format!("Failed to expand include patterns: {e}"), None)
+            })?;
 
     Ok(build_include_patterns(&expanded))
 }
@@ -692,7 +693,8 @@ impl CkMcpServer {
                     "version": env!("CARGO_PKG_VERSION"),
                     "protocol": "mcp",
                     "timestamp": chrono::Utc::now().to_rfc3339(),
-                    "cwd": context.service.context.cwd.to_string_lossy()
+                    "cwd": context.service.context.cwd.to_string_lossy(),
+                    "loaded_models": ck_embed::embedder_pool::pooled_models(),
                 });
 
                 let summary = format!(
@@ -939,6 +941,9 @@ impl CkMcpServer {
 
     pub async fn run(&self) -> Result<()> {
         info!("Starting ck MCP server");
+        // Keep each model loaded between tool calls instead of reloading it
+        // for every query the query cache misses
+        ck_embed::embedder_pool::set_embedder_pool_enabled(true);
 
         let stdio_transport = transport::stdio();
         let running_service = self.clone().serve(stdio_transport).await?;
@@ -1056,9 +1061,50 @@ impl CkMcpServer {
             use_ckignore: true,
             full_section: false,
             hidden: false,
+            follow_links: false,
+            include_submodules: false,
+            max_depth: None,
+            no_generated: false,
             rerank: request.rerank.unwrap_or(false),
             rerank_model: request.rerank_model.clone(),
+            rerank_top_k: None,
             embedding_model: None,
+            embed_batch_size: None,
+            force_hash: false,
+            jobs: None,
+            ignore_model_mismatch: false,
+            fuzzy: false,
+            bm25: ck_core::Bm25Params::default(),
+            proximity: 0.0,
+            match_on: ck_core::MatchOn::default(),
+            document: None,
+            split_identifiers: false,
+            include_globs: Vec::new(),
+            similar_to: None,
+            similar_by: ck_core::SimilarBy::File,
+            roots: Vec::new(),
+            no_stale: false,
+            snippet_lines: None,
+            threshold_percentile: None,
+            at_ref: None,
+            dedup: None,
+            no_auto_index: false,
+            ephemeral: false,
+            sort: ck_core::ResultSort::Score,
+            explain: false,
+            modified_since: None,
+            offset: 0,
+            exact: false,
+            ef_search: None,
+            max_matches_per_file: None,
+            max_count: None,
+            invert_match: false,
+            explicit_files: Vec::new(),
+            line_ranges: Default::default(),
+            cancel: None,
+            replace: None,
+            ascii_only: false,
+            deadline: None,
         };
 
         // Note: Embedders are created fresh for each request by ck-engine
@@ -1266,9 +1312,50 @@ impl CkMcpServer {
             use_ckignore: true,
             full_section: false,
             hidden: false,
+            follow_links: false,
+            include_submodules: false,
+            max_depth: None,
+            no_generated: false,
             rerank: false,
             rerank_model: None,
+            rerank_top_k: None,
             embedding_model: None,
+            embed_batch_size: None,
+            force_hash: false,
+            jobs: None,
+            ignore_model_mismatch: false,
+            fuzzy: false,
+            bm25: ck_core::Bm25Params::default(),
+            proximity: 0.0,
+            match_on: ck_core::MatchOn::default(),
+            document: None,
+            split_identifiers: false,
+            include_globs: Vec::new(),
+            similar_to: None,
+            similar_by: ck_core::SimilarBy::File,
+            roots: Vec::new(),
+            no_stale: false,
+            snippet_lines: None,
+            threshold_percentile: None,
+            at_ref: None,
+            dedup: None,
+            no_auto_index: false,
+            ephemeral: false,
+            sort: ck_core::ResultSort::Score,
+            explain: false,
+            modified_since: None,
+            offset: 0,
+            exact: false,
+            ef_search: None,
+            max_matches_per_file: None,
+            max_count: None,
+            invert_match: false,
+            explicit_files: Vec::new(),
+            line_ranges: Default::default(),
+            cancel: None,
+            replace: None,
+            ascii_only: false,
+            deadline: None,
         };
 
         let started = Instant::now();
@@ -1400,9 +1487,50 @@ impl CkMcpServer {
             use_ckignore: true,
             full_section: false,
             hidden: false,
+            follow_links: false,
+            include_submodules: false,
+            max_depth: None,
+            no_generated: false,
             rerank: false,
             rerank_model: None,
+            rerank_top_k: None,
             embedding_model: None,
+            embed_batch_size: None,
+            force_hash: false,
+            jobs: None,
+            ignore_model_mismatch: false,
+            fuzzy: false,
+            bm25: ck_core::Bm25Params::default(),
+            proximity: 0.0,
+            match_on: ck_core::MatchOn::default(),
+            document: None,
+            split_identifiers: false,
+            include_globs: Vec::new(),
+            similar_to: None,
+            similar_by: ck_core::SimilarBy::File,
+            roots: Vec::new(),
+            no_stale: false,
+            snippet_lines: None,
+            threshold_percentile: None,
+            at_ref: None,
+            dedup: None,
+            no_auto_index: false,
+            ephemeral: false,
+            sort: ck_core::ResultSort::Score,
+            explain: false,
+            modified_since: None,
+            offset: 0,
+            exact: false,
+            ef_search: None,
+            max_matches_per_file: None,
+            max_count: None,
+            invert_match: false,
+            explicit_files: Vec::new(),
+            line_ranges: Default::default(),
+            cancel: None,
+            replace: None,
+            ascii_only: false,
+            deadline: None,
         };
 
         // Perform the search (no indexing needed for regex)
@@ -1535,9 +1663,50 @@ impl CkMcpServer {
             use_ckignore: true,
             full_section: false,
             hidden: false,
+            follow_links: false,
+            include_submodules: false,
+            max_depth: None,
+            no_generated: false,
             rerank: request.rerank.unwrap_or(false),
             rerank_model: request.rerank_model.clone(),
+            rerank_top_k: None,
             embedding_model: None,
+            embed_batch_size: None,
+            force_hash: false,
+            jobs: None,
+            ignore_model_mismatch: false,
+            fuzzy: false,
+            bm25: ck_core::Bm25Params::default(),
+            proximity: 0.0,
+            match_on: ck_core::MatchOn::default(),
+            document: None,
+            split_identifiers: false,
+            include_globs: Vec::new(),
+            similar_to: None,
+            similar_by: ck_core::SimilarBy::File,
+            roots: Vec::new(),
+            no_stale: false,
+            snippet_lines: None,
+            threshold_percentile: None,
+            at_ref: None,
+            dedup: None,
+            no_auto_index: false,
+            ephemeral: false,
+            sort: ck_core::ResultSort::Score,
+            explain: false,
+            modified_since: None,
+            offset: 0,
+            exact: false,
+            ef_search: None,
+            max_matches_per_file: None,
+            max_count: None,
+            invert_match: false,
+            explicit_files: Vec::new(),
+            line_ranges: Default::default(),
+            cancel: None,
+            replace: None,
+            ascii_only: false,
+            deadline: None,
         };
 
         // Perform the search (suppress progress callbacks for MCP)
@@ -1820,9 +1989,50 @@ impl CkMcpServer {
             use_ckignore: true,
             full_section: false,
             hidden: false,
+            follow_links: false,
+            include_submodules: false,
+            max_depth: None,
+            no_generated: false,
             rerank: false,
             rerank_model: None,
+            rerank_top_k: None,
             embedding_model: None,
+            embed_batch_size: None,
+            force_hash: false,
+            jobs: None,
+            ignore_model_mismatch: false,
+            fuzzy: false,
+            bm25: ck_core::Bm25Params::default(),
+            proximity: 0.0,
+            match_on: ck_core::MatchOn::default(),
+            document: None,
+            split_identifiers: false,
+            include_globs: Vec::new(),
+            similar_to: None,
+            similar_by: ck_core::SimilarBy::File,
+            roots: Vec::new(),
+            no_stale: false,
+            snippet_lines: None,
+            threshold_percentile: None,
+            at_ref: None,
+            dedup: None,
+            no_auto_index: false,
+            ephemeral: false,
+            sort: ck_core::ResultSort::Score,
+            explain: false,
+            modified_since: None,
+            offset: 0,
+            exact: false,
+            ef_search: None,
+            max_matches_per_file: None,
+            max_count: None,
+            invert_match: false,
+            explicit_files: Vec::new(),
+            line_ranges: Default::default(),
+            cancel: None,
+            replace: None,
+            ascii_only: false,
+            deadline: None,
         };
 
         // Perform reindexing
@@ -0,0 +1,102 @@
+//! `--models`: every embedding model ck knows, with what it produces, how
+//! large a window it reads, and whether it is downloaded yet.
+
+use anyhow::Result;
+use console::style;
+use serde::Serialize;
+use std::path::{Path, PathBuf};
+
+#[derive(Debug, Serialize)]
+pub struct ModelInfo {
+    /// Name `--model` takes
+    pub alias: String,
+    /// Model name as published, and as indexes record it
+    pub name: String,
+    pub provider: String,
+    pub description: String,
+    pub dimensions: usize,
+    /// Tokens the model reads per input; longer chunks are split
+    pub max_tokens: usize,
+    /// Tokens chunking aims for with this model, and overlaps by
+    pub chunk_tokens: usize,
+    pub chunk_overlap_tokens: usize,
+    /// Used when neither `--model` nor the config names one
+    pub default: bool,
+    /// The index at or above the current directory is pinned to it
+    pub pinned: bool,
+    pub cached: bool,
+    /// Where it is downloaded, when it is
+    pub cache_path: Option<PathBuf>,
+}
+
+/// The registry's embedding models by alias, marking the one the index
+/// under `cwd` is pinned to and looking each up in `cache_root`.
+pub fn model_infos(cwd: &Path, cache_root: &Path) -> Vec<ModelInfo> {
+    let registry = ck_models::ModelRegistry::default();
+    let pinned = ck_index::pinned_model(cwd);
+    let mut models: Vec<ModelInfo> = registry
+        .models
+        .iter()
+        .map(|(alias, config)| {
+            let (chunk_tokens, chunk_overlap_tokens) =
+                ck_chunk::get_model_chunk_config(Some(&config.name));
+            let cache_path = ck_embed::model_cache::cached_model_dirs(cache_root, &config.name)
+                .into_iter()
+                .next();
+            ModelInfo {
+                alias: alias.clone(),
+                name: config.name.clone(),
+                provider: config.provider.clone(),
+                description: config.description.clone(),
+                dimensions: config.dimensions,
+                max_tokens: config.max_tokens,
+                chunk_tokens,
+                chunk_overlap_tokens,
+                default: *alias == registry.default_model,
+                pinned: pinned.as_deref() == Some(config.name.as_str()),
+                cached: cache_path.is_some(),
+                cache_path,
+            }
+        })
+        .collect();
+    models.sort_by(|a, b| a.alias.cmp(&b.alias));
+    models
+}
+
+/// Print `models` for people, or as a JSON array with `json`.
+pub fn print_models(models: &[ModelInfo], cache_root: &Path, json: bool) -> Result<()> {
+    if json {
+        println!("{}", serde_json::to_string_pretty(models)?);
+        return Ok(());
+    }
+    for model in models {
+        let mut tags = Vec::new();
+        if model.default {
+            tags.push("default");
+        }
+        if model.pinned {
+            tags.push("this index");
+        }
+        let tags = if tags.is_empty() {
+            String::new()
+        } else {
+            format!(" ({})", tags.join(", "))
+        };
+        println!("{}{}", style(&model.alias).bold(), tags);
+        println!("  {}: {}", model.name, model.description);
+        println!(
+            "  {} dimensions, {}-token window, ~{}-token chunks",
+            model.dimensions, model.max_tokens, model.chunk_tokens
+        );
+        match &model.cache_path {
+            Some(path) => println!("  {} {}", style("Cached at").green(), path.display()),
+            None => println!("  {}", style("Not downloaded yet").dim()),
+        }
+    }
+    println!();
+    println!(
+        "Models are downloaded to {} on first use; --warmup --model NAME fetches one ahead of time.",
+        cache_root.display()
+    );
+    Ok(())
+}
@@ -0,0 +1,82 @@
+//! Where search results are written: stdout, or the file named by `--output`.
+//!
+//! A file is written to a temporary sibling and moved over the target once
+//! the search finishes, so the target is replaced whole and never holds a
+//! half-written or stale tail. Progress and summaries stay on stderr either way.
+
+use anyhow::{Context, Result};
+use std::fs;
+use std::io::{self, BufWriter, Write};
+use std::path::{Path, PathBuf};
+use tempfile::NamedTempFile;
+
+pub enum ResultOutput {
+    Stdout(io::Stdout),
+    File {
+        file: BufWriter<NamedTempFile>,
+        path: PathBuf,
+    },
+}
+
+impl ResultOutput {
+    /// Results to `path` when given, stdout otherwise.
+    pub fn open(path: Option<&Path>) -> Result<Self> {
+        let Some(path) = path else {
+            return Ok(Self::Stdout(io::stdout()));
+        };
+        let parent = match path.parent() {
+            Some(parent) if !parent.as_os_str().is_empty() => parent,
+            _ => Path::new("."),
+        };
+        let mut builder = tempfile::Builder::new();
+        // Temporary files are owner-only; results get a new file's usual
+        // mode (after the umask), or the mode of the file they replace
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            builder.permissions(fs::Permissions::from_mode(0o666));
+        }
+        let file = builder
+            .tempfile_in(parent)
+            .with_context(|| format!("Cannot write to {}", path.display()))?;
+        if let Ok(metadata) = fs::metadata(path) {
+            fs::set_permissions(file.path(), metadata.permissions())
+                .with_context(|| format!("Cannot write to {}", path.display()))?;
+        }
+        Ok(Self::File {
+            file: BufWriter::new(file),
+            path: path.to_path_buf(),
+        })
+    }
+
+    /// Flush what was written and, for a file, move it into place.
+    pub fn finish(self) -> Result<()> {
+        match self {
+            Self::Stdout(mut stdout) => stdout.flush()?,
+            Self::File { file, path } => {
+                let file = file.into_inner().map_err(|err| err.into_error())?;
+                file.as_file().sync_all()?;
+                // Renames over the target, which is never missing meanwhile
+                file.persist(&path)
+                    .with_context(|| format!("Cannot write to {}", path.display()))?;
+            }
+        }
+        Ok(())
+    }
+}
+
+impl Write for ResultOutput {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        match self {
+            Self::Stdout(stdout) => stdout.write(buf),
+            Self::File { file, .. } => file.write(buf),
+        }
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        match self {
+            Self::Stdout(stdout) => stdout.flush(),
+            Self::File { file, .. } => file.flush(),
+        }
+    }
+}
@@ -3,23 +3,27 @@ use ck_core::IncludePattern;
 use glob::glob;
 use globset::{Glob, GlobSet, GlobSetBuilder};
 use std::path::{Component, Path, PathBuf};
+use walkdir::WalkDir;
 
 /// Expand user-provided glob patterns, mimicking shell behaviour while tolerating
 /// unmatched globs by keeping the original pattern. Bare filename globs (e.g.
-/// `*.rs`) automatically get a recursive fallback to align with the CLI UX.
+/// `*.rs`) automatically get a recursive fallback to align with the CLI UX,
+/// which descends into symlinked directories only with `follow_links`.
 pub fn expand_glob_patterns(
     paths: &[PathBuf],
     exclude_patterns: &[String],
+    follow_links: bool,
 ) -> Result<Vec<PathBuf>> {
-    expand_glob_patterns_internal(paths, exclude_patterns, None)
+    expand_glob_patterns_internal(paths, exclude_patterns, None, follow_links)
 }
 
 pub fn expand_glob_patterns_with_base(
     base_dir: &Path,
     paths: &[PathBuf],
     exclude_patterns: &[String],
+    follow_links: bool,
 ) -> Result<Vec<PathBuf>> {
-    expand_glob_patterns_internal(paths, exclude_patterns, Some(base_dir))
+    expand_glob_patterns_internal(paths, exclude_patterns, Some(base_dir), follow_links)
 }
 
 /// Build IncludePattern structures from filesystem paths, canonicalising where
@@ -71,8 +75,9 @@ fn expand_glob_patterns_internal(
     paths: &[PathBuf],
     exclude_patterns: &[String],
     base_dir: Option<&Path>,
+    follow_links: bool,
 ) -> Result<Vec<PathBuf>> {
-    let globset = build_globset(exclude_patterns);
+    let excludes = ExcludeGlobs::new(exclude_patterns);
     let mut expanded = Vec::new();
 
     for path in paths {
@@ -91,16 +96,16 @@ fn expand_glob_patterns_internal(
             };
 
             let glob_str = glob_path.to_string_lossy().to_string();
-            let mut matched = run_glob(&glob_str, &globset, base_dir, &mut expanded)?;
+            let mut matched = run_glob(&glob_str, &excludes, base_dir, &mut expanded)?;
 
             if is_simple {
-                let fallback_path = if let Some(base) = base_dir {
-                    base.join(format!("**/{pattern}"))
-                } else {
-                    PathBuf::from(format!("**/{pattern}"))
-                };
-                let fallback_str = fallback_path.to_string_lossy().to_string();
-                matched |= run_glob(&fallback_str, &globset, base_dir, &mut expanded)?;
+                matched |= run_recursive_fallback(
+                    &pattern,
+                    &excludes,
+                    base_dir,
+                    follow_links,
+                    &mut expanded,
+                );
             }
 
             if !matched {
@@ -114,7 +119,7 @@ fn expand_glob_patterns_internal(
 
 fn run_glob(
     pattern: &str,
-    globset: &GlobSet,
+    excludes: &ExcludeGlobs,
     base_dir: Option<&Path>,
     expanded: &mut Vec<PathBuf>,
 ) -> Result<bool> {
@@ -124,12 +129,16 @@ fn run_glob(
             for glob_result in glob_paths {
                 match glob_result {
                     Ok(matched_path) => {
-                        if should_exclude_path(&matched_path, globset, base_dir) {
+                        if should_exclude_path(&matched_path, excludes, base_dir) {
                             continue;
                         }
                         matched = true;
                         push_if_new(expanded, matched_path);
                     }
+                    // The search's own walk reports unreadable directories
+                    Err(e) if e.error().kind() == std::io::ErrorKind::PermissionDenied => {
+                        tracing::debug!("Glob for '{pattern}' skipped {}: {e}", e.path().display());
+                    }
                     Err(e) => {
                         eprintln!("Warning: glob error for pattern '{pattern}': {e}");
                     }
@@ -143,6 +152,86 @@ fn run_glob(
     Ok(matched)
 }
 
+/// `**/{pattern}` under `base_dir` (or the current directory): every entry
+/// below it whose name matches `pattern`. Symbolic links to directories are
+/// only descended into with `follow_links`, and then a link back to one of
+/// its ancestors is skipped, so a symlink cycle can't keep the walk going.
+fn run_recursive_fallback(
+    pattern: &str,
+    excludes: &ExcludeGlobs,
+    base_dir: Option<&Path>,
+    follow_links: bool,
+    expanded: &mut Vec<PathBuf>,
+) -> bool {
+    let Ok(glob) = Glob::new(pattern) else {
+        return false;
+    };
+    let matcher = glob.compile_matcher();
+    let root = base_dir.unwrap_or(Path::new("."));
+    let mut matched = false;
+    let walk = WalkDir::new(root)
+        .min_depth(1)
+        .follow_links(follow_links)
+        .sort_by_file_name();
+    for entry in walk {
+        let entry = match entry {
+            Ok(entry) => entry,
+            Err(e) => {
+                tracing::debug!("Recursive glob for '{pattern}' skipped an entry: {e}");
+                continue;
+            }
+        };
+        if !matcher.is_match(entry.file_name()) {
+            continue;
+        }
+        let path = match base_dir {
+            Some(_) => entry.path(),
+            None => entry.path().strip_prefix(root).unwrap_or(entry.path()),
+        };
+        if should_exclude_path(path, excludes, base_dir) {
+            continue;
+        }
+        matched = true;
+        push_if_new(expanded, path.to_path_buf());
+    }
+    matched
+}
+
+/// `path` relative to `base`, stepping up with `..` where the two diverge.
+/// Both are resolved to absolute paths first; a path that shares no root
+/// with `base` (another drive on Windows) is returned absolute.
+pub fn relative_path(path: &Path, base: &Path) -> PathBuf {
+    let path = canonicalize_lossy(path);
+    let base = canonicalize_lossy(base);
+    let mut path_components = path.components().peekable();
+    let mut base_components = base.components().peekable();
+    while let (Some(a), Some(b)) = (path_components.peek(), base_components.peek()) {
+        if a != b {
+            break;
+        }
+        path_components.next();
+        base_components.next();
+    }
+    if path_components
+        .peek()
+        .is_some_and(|c| matches!(c, Component::Prefix(_) | Component::RootDir))
+    {
+        return path;
+    }
+
+    let mut relative: PathBuf = base_components.map(|_| Component::ParentDir).collect();
+    relative.extend(path_components);
+    if relative.as_os_str().is_empty() {
+        relative.push(".");
+    }
+    relative
+}
+
+/// `path` as an absolute path, resolving symlinks where it exists.
+pub fn absolute_path(path: &Path) -> PathBuf {
+    canonicalize_lossy(path)
+}
+
 fn push_if_new(acc: &mut Vec<PathBuf>, candidate: PathBuf) {
     if !acc.iter().any(|existing| existing == &candidate) {
         acc.push(candidate);
@@ -159,63 +248,78 @@ fn canonicalize_lossy(path: &Path) -> PathBuf {
         .unwrap_or_else(|_| path.to_path_buf())
 }
 
-fn should_exclude_path(path: &Path, globset: &GlobSet, base_dir: Option<&Path>) -> bool {
-    if globset.is_match(path) {
-        return true;
-    }
-
+fn should_exclude_path(path: &Path, globs: &ExcludeGlobs, base_dir: Option<&Path>) -> bool {
+    let mut candidates = vec![path];
     if let Some(base) = base_dir
         && let Ok(relative) = path.strip_prefix(base)
+        && !relative.as_os_str().is_empty()
     {
-        if !relative.as_os_str().is_empty() && globset.is_match(relative) {
-            return true;
-        }
-
-        for component in relative.components() {
-            if let Component::Normal(name) = component
-                && globset.is_match(name)
-            {
-                return true;
-            }
-        }
+        candidates.push(relative);
+        candidates.extend(normal_components(relative));
     }
-
-    for component in path.components() {
-        if let Component::Normal(name) = component
-            && globset.is_match(name)
-        {
-            return true;
-        }
-    }
-
-    false
+    candidates.extend(normal_components(path));
+    globs.is_excluded(candidates)
 }
 
-fn build_globset(patterns: &[String]) -> GlobSet {
-    let mut builder = GlobSetBuilder::new();
+fn normal_components(path: &Path) -> impl Iterator<Item = &Path> {
+    path.components().filter_map(|component| match component {
+        Component::Normal(name) => Some(Path::new(name)),
+        _ => None,
+    })
+}
 
-    for pattern in patterns {
-        if let Ok(glob) = Glob::new(pattern) {
-            builder.add(glob);
-        }
+/// Exclude globs evaluated the way `.gitignore` lines are: the last matching
+/// pattern decides, so a `!pattern` match keeps a path an earlier pattern
+/// excluded.
+struct ExcludeGlobs {
+    set: GlobSet,
+    /// Per glob in `set`, whether its pattern was negated
+    negated: Vec<bool>,
+}
 
-        if let Some(stripped) = pattern.strip_suffix("/**") {
-            if !stripped.is_empty()
-                && let Ok(glob) = Glob::new(stripped)
-            {
+impl ExcludeGlobs {
+    fn new(patterns: &[String]) -> Self {
+        let mut builder = GlobSetBuilder::new();
+        let mut negated = Vec::new();
+        let mut add = |pattern: &str, is_negated: bool| {
+            if let Ok(glob) = Glob::new(pattern) {
                 builder.add(glob);
+                negated.push(is_negated);
             }
-        } else if let Some(stripped) = pattern.strip_suffix("\\**") {
-            // Support Windows-style globstar suffixes as well.
-            if !stripped.is_empty()
-                && let Ok(glob) = Glob::new(stripped)
-            {
-                builder.add(glob);
+        };
+
+        for pattern in patterns {
+            let (pattern, is_negated) = match pattern.strip_prefix('!') {
+                Some(rest) => (rest, true),
+                None => (pattern.as_str(), false),
+            };
+            add(pattern, is_negated);
+
+            if let Some(stripped) = pattern.strip_suffix("/**") {
+                if !stripped.is_empty() {
+                    add(stripped, is_negated);
+                }
+            } else if let Some(stripped) = pattern.strip_suffix("\\**") {
+                // Support Windows-style globstar suffixes as well.
+                if !stripped.is_empty() {
+                    add(stripped, is_negated);
+                }
             }
         }
+
+        Self {
+            set: builder.build().unwrap_or_else(|_| GlobSet::empty()),
+            negated,
+        }
     }
 
-    builder.build().unwrap_or_else(|_| GlobSet::empty())
+    fn is_excluded<'a>(&self, candidates: impl IntoIterator<Item = &'a Path>) -> bool {
+        candidates
+            .into_iter()
+            .flat_map(|candidate| self.set.matches(candidate))
+            .max()
+            .is_some_and(|index| !self.negated[index])
+    }
 }
 
 #[cfg(test)]
@@ -241,7 +345,7 @@ mod tests {
         write_file(&base.join("nested/beta.rs"), "fn helper() {}");
         write_file(&base.join("gamma.ts"), "export const X = 1;");
 
-        let expanded = expand_glob_patterns_with_base(base, &[PathBuf::from("*.rs")], &[])
+        let expanded = expand_glob_patterns_with_base(base, &[PathBuf::from("*.rs")], &[], false)
             .expect("expand *.rs");
 
         let has_alpha = expanded.iter().any(|p| p.ends_with("alpha.rs"));
@@ -255,6 +359,28 @@ mod tests {
         assert_eq!(expanded.len(), 2);
     }
 
+    #[test]
+    fn negated_exclude_patterns_reinclude_in_order() {
+        let temp_dir = tempdir().unwrap();
+        let base = temp_dir.path();
+
+        write_file(&base.join("package.json"), "{}");
+        write_file(&base.join("config/schema.json"), "{}");
+        write_file(&base.join("config/secret.json"), "{}");
+
+        let excludes = [
+            "*.json".to_string(),
+            "!config/*.json".to_string(),
+            "config/secret.json".to_string(),
+        ];
+        let expanded =
+            expand_glob_patterns_with_base(base, &[PathBuf::from("**/*.json")], &excludes, false)
+                .expect("expand **/*.json");
+
+        assert_eq!(expanded.len(), 1, "got {expanded:?}");
+        assert!(expanded[0].ends_with("config/schema.json"));
+    }
+
     #[test]
     fn expands_literal_files_and_directories() {
         let temp_dir = tempdir().unwrap();
@@ -267,6 +393,7 @@ mod tests {
             base,
             &[PathBuf::from("docs/"), PathBuf::from("src/file.ts")],
             &[],
+            false,
         )
         .expect("expand literals");
 
@@ -286,9 +413,13 @@ mod tests {
         write_file(&base.join("lib/lib.rs"), "pub fn lib() {}");
         write_file(&base.join("file.ts"), "export {}");
 
-        let expanded =
-            expand_glob_patterns_with_base(base, &[PathBuf::from("docs/;*.rs;file.ts")], &[])
-                .expect("expand semicolon list");
+        let expanded = expand_glob_patterns_with_base(
+            base,
+            &[PathBuf::from("docs/;*.rs;file.ts")],
+            &[],
+            false,
+        )
+        .expect("expand semicolon list");
 
         let has_docs = expanded.iter().any(|p| p.ends_with("docs"));
         let has_rs = expanded.iter().any(|p| p.ends_with("lib.rs"));
@@ -309,9 +440,13 @@ mod tests {
         write_file(&base.join("nested/keep.rs"), "fn helper() {}");
         write_file(&base.join("nested/ignore.json"), "{}");
 
-        let expanded =
-            expand_glob_patterns_with_base(base, &[PathBuf::from("**/*")], &["*.json".to_string()])
-                .expect("expand with excludes");
+        let expanded = expand_glob_patterns_with_base(
+            base,
+            &[PathBuf::from("**/*")],
+            &["*.json".to_string()],
+            false,
+        )
+        .expect("expand with excludes");
 
         let includes_json = expanded.iter().any(|p| p.ends_with("ignore.json"));
         let includes_rs = expanded.iter().filter(|p| p.ends_with("keep.rs")).count();
@@ -329,9 +464,13 @@ mod tests {
         write_file(&base.join("foo/other.txt"), "foo");
         write_file(&base.join("root.txt"), "root");
 
-        let expanded =
-            expand_glob_patterns_with_base(base, &[PathBuf::from("**/*")], &["foo/**".to_string()])
-                .expect("expand with directory glob");
+        let expanded = expand_glob_patterns_with_base(
+            base,
+            &[PathBuf::from("**/*")],
+            &["foo/**".to_string()],
+            false,
+        )
+        .expect("expand with directory glob");
 
         let includes_foo = expanded.iter().any(|p| p.to_string_lossy().contains("foo"));
         let includes_root = expanded.iter().any(|p| p.ends_with("root.txt"));
@@ -339,4 +478,23 @@ mod tests {
         assert!(!includes_foo, "foo/** should exclude everything under foo");
         assert!(includes_root, "root.txt should still be present");
     }
+
+    #[test]
+    fn relative_paths_step_up_from_the_base() {
+        let temp_dir = tempdir().unwrap();
+        let base = temp_dir.path();
+        write_file(&base.join("src/lib.rs"), "");
+        write_file(&base.join("docs/guide.md"), "");
+
+        assert_eq!(
+            relative_path(&base.join("src/lib.rs"), base),
+            PathBuf::from("src/lib.rs")
+        );
+        assert_eq!(
+            relative_path(&base.join("docs/guide.md"), &base.join("src")),
+            PathBuf::from("../docs/guide.md")
+        );
+        assert_eq!(relative_path(base, base), PathBuf::from("."));
+        assert!(absolute_path(Path::new("src/lib.rs")).is_absolute());
+    }
 }
@@ -0,0 +1,202 @@
+//! `--preview-width`: cut long preview lines to a number of terminal
+//! columns, counted by display width so CJK characters and emoji take the
+//! two columns they occupy on screen.
+
+use ck_engine::SearchRegex;
+use std::ops::Range;
+use unicode_width::{UnicodeWidthChar, UnicodeWidthStr};
+
+const ELLIPSIS: char = '…';
+
+/// Narrowest a preview gets when fitting lines to the terminal, however long
+/// the filename in front of it.
+const MIN_TERMINAL_PREVIEW_WIDTH: usize = 20;
+
+/// How wide result previews may be.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PreviewWidth {
+    Unlimited,
+    /// `--preview-width N`: N columns of preview text
+    Columns(usize),
+    /// A terminal this wide: the line, filename and line number included
+    Terminal(usize),
+}
+
+impl PreviewWidth {
+    /// The explicit `--preview-width` (0 turns truncation off), otherwise the
+    /// terminal's width when stdout is one.
+    pub fn resolve(explicit: Option<usize>, terminal_width: Option<usize>) -> Self {
+        match (explicit, terminal_width) {
+            (Some(0), _) | (None, None) => PreviewWidth::Unlimited,
+            (Some(columns), _) => PreviewWidth::Columns(columns),
+            (None, Some(columns)) => PreviewWidth::Terminal(columns),
+        }
+    }
+
+    /// Columns for the preview's lines and for its first line, which is
+    /// printed after `prefix_width` columns of filename and line number.
+    pub fn columns(self, prefix_width: usize) -> Option<(usize, usize)> {
+        match self {
+            PreviewWidth::Unlimited => None,
+            PreviewWidth::Columns(columns) => Some((columns, columns)),
+            PreviewWidth::Terminal(columns) => Some((
+                columns,
+                columns
+                    .saturating_sub(prefix_width)
+                    .max(MIN_TERMINAL_PREVIEW_WIDTH),
+            )),
+        }
+    }
+}
+
+/// `preview` with every line cut to `width` columns. `first_line_width`
+/// replaces `width` for the first line, which may share its line with a
+/// filename and line number. Lines are windowed around `focus`'s first
+/// match in them.
+pub fn truncate_preview(
+    preview: &str,
+    width: usize,
+    first_line_width: usize,
+    focus: Option<&SearchRegex>,
+) -> String {
+    preview
+        .split('\n')
+        .enumerate()
+        .map(|(index, line)| {
+            let width = if index == 0 { first_line_width } else { width };
+            let matched = focus.and_then(|regex| regex.find(line));
+            truncate_line(line, width, matched)
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+/// `line` cut to `width` display columns, an ellipsis marking each cut end.
+/// When the match at byte range `focus` would be cut off, the window
+/// scrolls right to show it, with a quarter of the width of context before.
+pub fn truncate_line(line: &str, width: usize, focus: Option<Range<usize>>) -> String {
+    if line.width() <= width {
+        return line.to_string();
+    }
+    // Room for the text when the end is cut
+    let budget = width.saturating_sub(1);
+    let start = match focus {
+        Some(focus) if line[..focus.end].width() > budget => {
+            let target = line[..focus.start].width().saturating_sub(width / 4).max(1);
+            let mut column = 0;
+            let scrolled = line
+                .char_indices()
+                .find(|(_, c)| {
+                    let reached = column >= target;
+                    column += c.width().unwrap_or(0);
+                    reached
+                })
+                .map_or(line.len(), |(index, _)| index);
+            // Near the end of the line, show as much before the match as fits
+            let mut remaining = line.width();
+            let tail = line
+                .char_indices()
+                .find(|(_, c)| {
+                    let fits = remaining <= budget;
+                    remaining -= c.width().unwrap_or(0);
+                    fits
+                })
+                .map_or(line.len(), |(index, _)| index);
+            scrolled.min(tail)
+        }
+        _ => 0,
+    };
+
+    let mut out = String::with_capacity(width * 4);
+    let mut used = 0;
+    if start > 0 {
+        out.push(ELLIPSIS);
+        used += 1;
+    }
+    let rest = &line[start..];
+    if used + rest.width() <= width {
+        out.push_str(rest);
+        return out;
+    }
+    for c in rest.chars() {
+        let char_width = c.width().unwrap_or(0);
+        if used + char_width > budget {
+            break;
+        }
+        out.push(c);
+        used += char_width;
+    }
+    if used < width {
+        out.push(ELLIPSIS);
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_truncate_line_counts_display_width() {
+        assert_eq!(truncate_line("short", 10, None), "short");
+        assert_eq!(truncate_line("abcdefghij", 6, None), "abcde…");
+        // Each CJK character is two columns; one that would straddle the
+        // edge is left out rather than split
+        assert_eq!(truncate_line("日本語のテキスト", 6, None), "日本…");
+        assert_eq!(truncate_line("日本語のテキスト", 7, None), "日本語…");
+        assert_eq!(truncate_line("🚀🚀🚀🚀", 5, None), "🚀🚀…");
+    }
+
+    #[test]
+    fn test_truncate_line_scrolls_to_the_match() {
+        let line = format!("{}needle{}", "x".repeat(40), "y".repeat(40));
+        let focus = 40..46;
+        let cut = truncate_line(&line, 20, Some(focus));
+        assert_eq!(cut.width(), 20);
+        assert!(cut.starts_with('…') && cut.ends_with('…'));
+        assert!(cut.contains("needle"), "{cut}");
+        // A match already in view doesn't move the window
+        assert_eq!(
+            truncate_line(&line, 20, Some(2..4)),
+            format!("{}…", "x".repeat(19))
+        );
+        // The tail of the line fits once scrolled, so only the front is cut
+        let tail = format!("{}needle", "x".repeat(40));
+        assert_eq!(truncate_line(&tail, 12, Some(40..46)), "…xxxxxneedle");
+    }
+
+    #[test]
+    fn test_preview_width_resolution() {
+        assert_eq!(PreviewWidth::resolve(None, None), PreviewWidth::Unlimited);
+        assert_eq!(
+            PreviewWidth::resolve(None, Some(80)),
+            PreviewWidth::Terminal(80)
+        );
+        assert_eq!(
+            PreviewWidth::resolve(Some(40), None),
+            PreviewWidth::Columns(40)
+        );
+        assert_eq!(
+            PreviewWidth::resolve(Some(0), Some(80)),
+            PreviewWidth::Unlimited
+        );
+
+        // Only the terminal width has the filename to make room for
+        assert_eq!(PreviewWidth::Columns(40).columns(30), Some((40, 40)));
+        assert_eq!(PreviewWidth::Terminal(80).columns(30), Some((80, 50)));
+        assert_eq!(
+            PreviewWidth::Terminal(80).columns(75),
+            Some((80, MIN_TERMINAL_PREVIEW_WIDTH))
+        );
+    }
+
+    #[test]
+    fn test_truncate_preview_applies_first_line_width() {
+        let regex = SearchRegex::from(regex::Regex::new("b+").unwrap());
+        let preview = "aaaaaaaaaa\nccccccccbbbb";
+        assert_eq!(
+            truncate_preview(preview, 8, 4, Some(&regex)),
+            "aaa…\n…cccbbbb"
+        );
+    }
+}
@@ -1,7 +1,58 @@
 use console::{Term, style};
 use indicatif::{MultiProgress, ProgressBar, ProgressStyle};
+use std::sync::Mutex;
 use std::time::{Duration, Instant};
 
+/// Display for model downloads, installed with
+/// `ck_embed::set_download_reporter`: a byte bar while a file downloads, and a
+/// spinner with the latest message for providers that only report messages.
+pub fn model_download_reporter() -> ck_embed::ModelDownloadCallback {
+    let bar: Mutex<Option<ProgressBar>> = Mutex::new(None);
+    Box::new(move |progress| {
+        let Ok(mut bar) = bar.lock() else {
+            return;
+        };
+        match progress {
+            ck_embed::DownloadProgress::Message(msg) => {
+                bar.get_or_insert_with(download_spinner).set_message(msg);
+            }
+            ck_embed::DownloadProgress::Bytes { received, total } => {
+                let pb = bar.get_or_insert_with(download_spinner);
+                if pb.length() != Some(total) {
+                    pb.set_length(total);
+                    pb.set_style(
+                        ProgressStyle::default_bar()
+                            .template(
+                                "{spinner:.green} {msg} {wide_bar:.cyan/blue} {bytes}/{total_bytes} ({eta})",
+                            )
+                            .unwrap_or_else(|_| ProgressStyle::default_bar())
+                            .progress_chars("█▉▊▋▌▍▎▏  ")
+                            .tick_strings(&["⠋", "⠙", "⠹", "⠸", "⠼", "⠴", "⠦", "⠧", "⠇", "⠏"]),
+                    );
+                }
+                pb.set_position(received);
+            }
+            ck_embed::DownloadProgress::Finished => {
+                if let Some(pb) = bar.take() {
+                    pb.finish_and_clear();
+                }
+            }
+        }
+    })
+}
+
+fn download_spinner() -> ProgressBar {
+    let pb = ProgressBar::new_spinner();
+    pb.set_style(
+        ProgressStyle::default_spinner()
+            .template("{spinner:.green} {msg}")
+            .unwrap_or_else(|_| ProgressStyle::default_spinner())
+            .tick_strings(&["⠋", "⠙", "⠹", "⠸", "⠼", "⠴", "⠦", "⠧", "⠇", "⠏"]),
+    );
+    pb.enable_steady_tick(Duration::from_millis(120));
+    pb
+}
+
 pub struct StatusReporter {
     term: Term,
     multi_progress: MultiProgress,
@@ -0,0 +1,305 @@
+//! `--repl`: interactive searching against one warmed-up index.
+//!
+//! The first semantic query brings the index up to date and runs through the
+//! normal search path; after that a [`ck_engine::LoadedIndex`] keeps the
+//! model and sidecars in memory, so each further query pays only for
+//! embedding and ranking. Lines starting with `:` are commands (see [`HELP`]),
+//! anything else is a query. On a terminal, input has line editing and
+//! history; piped input is read line by line.
+
+use anyhow::Result;
+use ck_core::{SearchMode, SearchOptions, SearchResult};
+use console::style;
+use std::io::{BufRead, IsTerminal};
+
+use crate::progress::StatusReporter;
+
+const HELP: &str = "\
+Type a query to search. Commands:
+  :sem :lex :regex :hybrid   switch search mode
+  :topk N | :topk none       limit the number of results
+  :threshold X | :threshold none
+                             minimum score for ranked modes
+  :reload                    re-check the index on the next semantic query
+  :set                       show the current settings
+  :help                      show this help
+  :quit                      leave (Ctrl-D also works)";
+
+#[derive(Debug, PartialEq)]
+enum Command {
+    Query(String),
+    Mode(SearchMode),
+    TopK(Option<usize>),
+    Threshold(Option<f32>),
+    Reload,
+    Settings,
+    Help,
+    Quit,
+}
+
+/// Parse one input line; `Ok(None)` for a blank line.
+fn parse_line(line: &str) -> Result<Option<Command>, String> {
+    let line = line.trim();
+    if line.is_empty() {
+        return Ok(None);
+    }
+    let Some(command) = line.strip_prefix(':') else {
+        return Ok(Some(Command::Query(line.to_string())));
+    };
+
+    let mut words = command.split_whitespace();
+    let name = words.next().unwrap_or_default();
+    let arg = words.next();
+    if words.next().is_some() {
+        return Err(format!("Too many arguments for :{name}"));
+    }
+    let parsed = match (name, arg) {
+        ("sem" | "semantic", None) => Command::Mode(SearchMode::Semantic),
+        ("lex" | "lexical", None) => Command::Mode(SearchMode::Lexical),
+        ("regex", None) => Command::Mode(SearchMode::Regex),
+        ("hybrid", None) => Command::Mode(SearchMode::Hybrid),
+        ("topk", Some("none")) => Command::TopK(None),
+        ("topk", Some(n)) => match n.parse::<usize>() {
+            Ok(n) if n > 0 => Command::TopK(Some(n)),
+            _ => return Err(format!("Invalid topk '{n}': expected a positive number")),
+        },
+        ("threshold", Some("none")) => Command::Threshold(None),
+        ("threshold", Some(x)) => match x.parse::<f32>() {
+            Ok(x) => Command::Threshold(Some(x)),
+            Err(_) => return Err(format!("Invalid threshold '{x}': expected a number")),
+        },
+        ("topk" | "threshold", None) => return Err(format!(":{name} needs a value or 'none'")),
+        ("reload", None) => Command::Reload,
+        ("set", None) => Command::Settings,
+        ("help" | "h" | "?", None) => Command::Help,
+        ("quit" | "q" | "exit", None) => Command::Quit,
+        _ => return Err(format!("Unknown command ':{command}' (try :help)")),
+    };
+    Ok(Some(parsed))
+}
+
+struct Session {
+    options: SearchOptions,
+    /// `topk` / `threshold` given on the command line, kept across mode
+    /// switches instead of being reset to the new mode's defaults
+    topk_explicit: bool,
+    threshold_explicit: bool,
+    loaded: Option<ck_engine::LoadedIndex>,
+}
+
+impl Session {
+    fn apply(&mut self, command: Command) -> bool {
+        match command {
+            Command::Mode(mode) => {
+                let (topk, threshold) = crate::mode_defaults(&mode);
+                self.options.mode = mode;
+                if !self.topk_explicit {
+                    self.options.top_k = topk;
+                }
+                if !self.threshold_explicit {
+                    self.options.threshold = threshold;
+                }
+            }
+            Command::TopK(top_k) => {
+                self.options.top_k = top_k;
+                self.topk_explicit = true;
+            }
+            Command::Threshold(threshold) => {
+                self.options.threshold = threshold;
+                self.options.threshold_percentile = None;
+                self.threshold_explicit = true;
+            }
+            Command::Reload => self.loaded = None,
+            Command::Settings => eprintln!("{}", self.settings()),
+            Command::Help => eprintln!("{HELP}"),
+            Command::Quit => return false,
+            Command::Query(_) => {}
+        }
+        true
+    }
+
+    fn settings(&self) -> String {
+        let top_k = self
+            .options
+            .top_k
+            .map_or_else(|| "none".to_string(), |k| k.to_string());
+        let threshold = self
+            .options
+            .threshold
+            .map_or_else(|| "none".to_string(), |t| t.to_string());
+        format!(
+            "mode={}  topk={top_k}  threshold={threshold}",
+            mode_name(&self.options.mode)
+        )
+    }
+
+    async fn search(&mut self, query: &str, status: &StatusReporter) -> Result<Vec<SearchResult>> {
+        let mut options = self.options.clone();
+        options.query = query.to_string();
+
+        if options.mode == SearchMode::Semantic
+            && let Some(index) = self.loaded.as_mut()
+        {
+            return Ok(index.search(&options).await?.matches);
+        }
+
+        let spinner = status.create_spinner("Searching...");
+        let outcome = ck_engine::search_enhanced_with_outcome(&options, None, None, None).await;
+        status.finish_progress(spinner, "Done");
+        let results = outcome?.results.matches;
+        // Only the first search honours --reindex
+        self.options.reindex = false;
+
+        if options.mode == SearchMode::Semantic {
+            let spinner = status.create_spinner("Loading model and embeddings...");
+            let loaded = ck_engine::LoadedIndex::load(&self.options);
+            status.finish_progress(spinner, "Model and embeddings loaded");
+            self.loaded = Some(loaded?);
+        }
+        Ok(results)
+    }
+}
+
+fn mode_name(mode: &SearchMode) -> &'static str {
+    match mode {
+        SearchMode::Semantic => "sem",
+        SearchMode::Lexical => "lex",
+        SearchMode::Regex => "regex",
+        SearchMode::Hybrid => "hybrid",
+    }
+}
+
+fn print_results(results: &[SearchResult], show_scores: bool) {
+    for result in results {
+        let score = if show_scores {
+            format!("[{:.3}] ", result.score)
+        } else {
+            String::new()
+        };
+        let preview = result.preview.lines().next().unwrap_or_default().trim();
+        println!(
+            "{score}{}:{}: {preview}",
+            style(result.file.display()).cyan().bold(),
+            style(result.span.line_start).yellow()
+        );
+    }
+    if results.is_empty() {
+        eprintln!("{}", style("No matches").dim());
+    }
+}
+
+/// Read queries and commands until `:quit` or end of input.
+pub async fn run_repl(
+    options: SearchOptions,
+    topk_explicit: bool,
+    threshold_explicit: bool,
+    status: &StatusReporter,
+) -> Result<()> {
+    let mut session = Session {
+        options,
+        topk_explicit,
+        threshold_explicit,
+        loaded: None,
+    };
+    let interactive = std::io::stdin().is_terminal();
+    let mut history = dialoguer::BasicHistory::new()
+        .max_entries(500)
+        .no_duplicates(true);
+    let mut piped = std::io::stdin().lock().lines();
+
+    if interactive {
+        eprintln!(
+            "{}",
+            style("ck interactive search; :help for commands").dim()
+        );
+    }
+    loop {
+        let line = if interactive {
+            let prompt = format!("ck[{}]", mode_name(&session.options.mode));
+            match dialoguer::Input::<String>::new()
+                .with_prompt(prompt)
+                .allow_empty(true)
+                .history_with(&mut history)
+                .interact_text()
+            {
+                Ok(line) => line,
+                // End of input (Ctrl-D) or a closed terminal
+                Err(_) => break,
+            }
+        } else {
+            match piped.next() {
+                Some(line) => line?,
+                None => break,
+            }
+        };
+
+        let command = match parse_line(&line) {
+            Ok(Some(command)) => command,
+            Ok(None) => continue,
+            Err(message) => {
+                status.warn(&message);
+                continue;
+            }
+        };
+        let Command::Query(query) = command else {
+            if !session.apply(command) {
+                break;
+            }
+            continue;
+        };
+        match session.search(&query, status).await {
+            Ok(results) => print_results(&results, session.options.mode != SearchMode::Regex),
+            Err(e) => status.warn(&format!("Search failed: {e}")),
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_queries_and_commands() {
+        assert_eq!(parse_line("   "), Ok(None));
+        assert_eq!(
+            parse_line(" error handling "),
+            Ok(Some(Command::Query("error handling".to_string())))
+        );
+        assert_eq!(
+            parse_line(":lex"),
+            Ok(Some(Command::Mode(SearchMode::Lexical)))
+        );
+        assert_eq!(parse_line(":topk 5"), Ok(Some(Command::TopK(Some(5)))));
+        assert_eq!(parse_line(":topk none"), Ok(Some(Command::TopK(None))));
+        assert_eq!(
+            parse_line(":threshold 0.7"),
+            Ok(Some(Command::Threshold(Some(0.7))))
+        );
+        assert_eq!(parse_line(":q"), Ok(Some(Command::Quit)));
+
+        assert!(parse_line(":topk 0").is_err());
+        assert!(parse_line(":threshold").is_err());
+        assert!(parse_line(":sem now").is_err());
+        assert!(parse_line(":frobnicate").is_err());
+    }
+
+    #[test]
+    fn test_mode_switch_keeps_explicit_settings() {
+        let mut session = Session {
+            options: SearchOptions::default(),
+            topk_explicit: false,
+            threshold_explicit: false,
+            loaded: None,
+        };
+        session.apply(Command::Mode(SearchMode::Semantic));
+        assert_eq!(session.options.top_k, Some(10));
+        assert_eq!(session.options.threshold, Some(0.6));
+
+        session.apply(Command::Threshold(Some(0.3)));
+        session.apply(Command::Mode(SearchMode::Lexical));
+        assert_eq!(session.options.top_k, None);
+        assert_eq!(session.options.threshold, Some(0.3));
+        assert!(!session.apply(Command::Quit));
+    }
+}
@@ -0,0 +1,190 @@
+//! `--group-by-symbol`: results inside the same function, method or class
+//! collapse into one entry for it, for a function-level view of where a
+//! search matched.
+//!
+//! A result's symbol is the one the search named (a Markdown heading path,
+//! a notebook cell, a PDF page), or else the innermost function, method or
+//! class around its matching line, found by chunking the file. Results
+//! outside any symbol are left as they are.
+
+use ck_core::{SearchResult, Span};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+
+/// A symbol results fall in: its name and extent.
+struct Symbol {
+    name: String,
+    span: Span,
+}
+
+/// The functions, methods and classes of `file` (name and span), or `None`
+/// when it can't be read or parsed.
+fn code_symbols(file: &Path) -> Option<Vec<Symbol>> {
+    let lang = ck_core::Language::from_path(file)?;
+    let text = ck_index::read_text(file).ok()?;
+    let chunks = ck_chunk::chunk_text(&text, Some(lang)).ok()?;
+    Some(
+        chunks
+            .into_iter()
+            .filter(|chunk| {
+                matches!(
+                    chunk.chunk_type,
+                    ck_chunk::ChunkType::Function
+                        | ck_chunk::ChunkType::Method
+                        | ck_chunk::ChunkType::Class
+                )
+            })
+            .map(|chunk| Symbol {
+                // Unnamed chunks go by their first line, usually a signature
+                name: chunk.metadata.breadcrumb.unwrap_or_else(|| {
+                    let first_line = chunk.text.lines().next().unwrap_or_default();
+                    first_line
+                        .trim()
+                        .trim_end_matches(['{', ':'])
+                        .trim_end()
+                        .to_string()
+                }),
+                span: chunk.span,
+            })
+            .collect(),
+    )
+}
+
+/// The innermost of `symbols` around `line`.
+fn enclosing(symbols: &[Symbol], line: usize) -> Option<&Symbol> {
+    symbols
+        .iter()
+        .filter(|symbol| (symbol.span.line_start..=symbol.span.line_end).contains(&line))
+        .min_by_key(|symbol| symbol.span.line_count())
+}
+
+/// Collapse `results` that share a symbol into one entry each, in the place
+/// of the first of them. The entry is their best-scoring result, widened to the
+/// symbol's span and named after it, with [`SearchResult::grouped`] counting
+/// the results it stands for; its preview stays on the line it came from.
+pub fn group_by_symbol(results: Vec<SearchResult>) -> Vec<SearchResult> {
+    let mut symbols: HashMap<PathBuf, Option<Vec<Symbol>>> = HashMap::new();
+    let mut grouped: Vec<SearchResult> = Vec::with_capacity(results.len());
+    let mut group_of: HashMap<(PathBuf, String), usize> = HashMap::new();
+    for mut result in results {
+        let line = result
+            .match_line
+            .or(result.preview_line)
+            .unwrap_or(result.span.line_start);
+        let (name, span) = match &result.symbol {
+            Some(name) => (name.clone(), result.span.clone()),
+            None => {
+                let file_symbols = symbols
+                    .entry(result.file.clone())
+                    .or_insert_with(|| code_symbols(&result.file));
+                match file_symbols
+                    .as_deref()
+                    .and_then(|file_symbols| enclosing(file_symbols, line))
+                {
+                    Some(symbol) => (symbol.name.clone(), symbol.span.clone()),
+                    None => {
+                        grouped.push(result);
+                        continue;
+                    }
+                }
+            }
+        };
+
+        match group_of.get(&(result.file.clone(), name.clone())) {
+            Some(&index) => {
+                let group = &mut grouped[index];
+                let count = group.grouped + 1;
+                widen(&mut group.span, &span);
+                if result.score > group.score {
+                    let span = group.span.clone();
+                    result.preview_line.get_or_insert(result.span.line_start);
+                    *group = SearchResult {
+                        span,
+                        symbol: Some(name),
+                        ..result
+                    };
+                }
+                group.grouped = count;
+            }
+            None => {
+                group_of.insert((result.file.clone(), name.clone()), grouped.len());
+                result.preview_line.get_or_insert(result.span.line_start);
+                widen(&mut result.span, &span);
+                result.symbol = Some(name);
+                result.grouped = 1;
+                grouped.push(result);
+            }
+        }
+    }
+    grouped
+}
+
+/// Grow `span` to cover `other` too.
+fn widen(span: &mut Span, other: &Span) {
+    if other.line_start < span.line_start {
+        span.line_start = other.line_start;
+        span.byte_start = other.byte_start;
+    }
+    if other.line_end > span.line_end {
+        span.line_end = other.line_end;
+        span.byte_end = other.byte_end;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    fn result(file: &Path, line: usize, score: f32) -> SearchResult {
+        let span = Span::new(0, 1, line, line).unwrap();
+        serde_json::from_value(serde_json::json!({
+            "file": file,
+            "span": span,
+            "score": score,
+            "preview": format!("line {line}"),
+        }))
+        .unwrap()
+    }
+
+    #[test]
+    fn test_results_in_one_function_collapse_into_it() {
+        let dir = tempdir().unwrap();
+        let file = dir.path().join("lib.rs");
+        std::fs::write(
+            &file,
+            "fn parse() {\n    let a = 1;\n    let b = 2;\n}\n\nfn render() {\n    draw();\n}\n\nconst LIMIT: u32 = 3;\n",
+        )
+        .unwrap();
+
+        let grouped = group_by_symbol(vec![
+            result(&file, 2, 0.4),
+            result(&file, 7, 0.6),
+            result(&file, 3, 0.9),
+            result(&file, 10, 0.5),
+        ]);
+        let summary: Vec<_> = grouped
+            .iter()
+            .map(|r| {
+                (
+                    r.symbol.as_deref(),
+                    r.span.line_start,
+                    r.span.line_end,
+                    r.score,
+                    r.grouped,
+                    r.preview.as_str(),
+                )
+            })
+            .collect();
+        assert_eq!(
+            summary,
+            [
+                (Some("fn parse()"), 1, 4, 0.9, 2, "line 3"),
+                (Some("fn render()"), 6, 8, 0.6, 1, "line 7"),
+                // Outside any function: kept as it was
+                (None, 10, 10, 0.5, 0, "line 10"),
+            ]
+        );
+        assert_eq!(grouped[0].preview_line, Some(3));
+    }
+}
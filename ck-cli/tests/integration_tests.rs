@@ -828,6 +828,50 @@ fn test_add_file_with_relative_path() {
     assert!(stdout.contains("Relative path content"));
 }
 
+#[test]
+#[serial]
+fn test_add_multiple_files_and_directories() {
+    let temp_dir = TempDir::new().unwrap();
+    fs::write(temp_dir.path().join("seed.txt"), "seed content").unwrap();
+
+    let output = ck_command()
+        .args(["--index", "."])
+        .current_dir(temp_dir.path())
+        .output()
+        .expect("Failed to create index");
+    assert!(output.status.success());
+
+    fs::write(temp_dir.path().join("one.txt"), "first added file").unwrap();
+    fs::create_dir(temp_dir.path().join("docs")).unwrap();
+    fs::write(temp_dir.path().join("docs/two.txt"), "second added file").unwrap();
+    fs::write(temp_dir.path().join("outside.txt"), "not added file").unwrap();
+
+    let output = ck_command()
+        .args(["--add", "one.txt", "docs", "seed.txt"])
+        .current_dir(temp_dir.path())
+        .output()
+        .expect("Failed to run ck --add with several paths");
+    assert!(
+        output.status.success(),
+        "stderr: {}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    assert!(
+        stderr.contains("Added 2 and updated 0 file(s) in the index (1 already up to date)"),
+        "stderr: {stderr}"
+    );
+
+    // Only the named paths were indexed; outside.txt waits for the next full update
+    let output = ck_command()
+        .args(["--status-json", "."])
+        .current_dir(temp_dir.path())
+        .output()
+        .expect("Failed to run ck --status-json");
+    let status: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+    assert_eq!(status["total_files"], 3, "status: {status}");
+}
+
 #[test]
 #[serial]
 fn test_no_ckignore_flag_disables_hierarchical_ignore() {
@@ -1231,6 +1275,173 @@ fn test_command_flags_honor_explicit_path() {
     );
 }
 
+#[test]
+#[serial]
+fn test_status_json_serializes_stats_and_fails_without_index() {
+    let temp_dir = TempDir::new().unwrap();
+
+    let output = ck_command()
+        .args(["--status", "--json", "."])
+        .current_dir(temp_dir.path())
+        .output()
+        .expect("Failed to run ck --status --json");
+    assert_eq!(output.status.code(), Some(1));
+    let json: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+    assert_eq!(json["index_exists"], false);
+
+    fs::write(temp_dir.path().join("a.txt"), "alpha beta\n").unwrap();
+    fs::write(temp_dir.path().join("b.txt"), "gamma delta\n").unwrap();
+    let output = ck_command()
+        .args(["--index", "."])
+        .current_dir(temp_dir.path())
+        .output()
+        .expect("Failed to run ck --index");
+    assert!(output.status.success());
+    fs::remove_file(temp_dir.path().join("b.txt")).unwrap();
+
+    let output = ck_command()
+        .args(["--status", "--json", "."])
+        .current_dir(temp_dir.path())
+        .output()
+        .expect("Failed to run ck --status --json");
+    assert!(output.status.success());
+    let json: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+    assert_eq!(json["index_exists"], true);
+    assert_eq!(json["total_files"], 2);
+    assert_eq!(json["orphaned_files"], serde_json::json!(["b.txt"]));
+    assert!(json["by_language"]["other"]["chunks"].as_u64().unwrap() >= 1);
+}
+
+#[test]
+#[serial]
+fn test_index_dry_run_reports_plan_without_indexing() {
+    let temp_dir = TempDir::new().unwrap();
+    fs::write(temp_dir.path().join("a.txt"), "alpha beta\n").unwrap();
+    fs::write(temp_dir.path().join("b.txt"), "gamma delta\n").unwrap();
+    fs::create_dir(temp_dir.path().join("generated")).unwrap();
+    fs::write(temp_dir.path().join("generated/out.txt"), "skip me\n").unwrap();
+
+    let output = ck_command()
+        .args([
+            "--index",
+            "--dry-run",
+            "--verbose",
+            "--exclude",
+            "generated",
+            ".",
+        ])
+        .current_dir(temp_dir.path())
+        .output()
+        .expect("Failed to run ck --index --dry-run");
+    assert!(output.status.success());
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    assert!(stdout.contains("Dry run: 2 to add, 0 to update, 0 up to date"));
+    assert!(stdout.contains("generated/ (exclude pattern 'generated')"));
+    assert!(!temp_dir.path().join(".ck").exists());
+
+    let output = ck_command()
+        .args(["--index", "--exclude", "generated", "."])
+        .current_dir(temp_dir.path())
+        .output()
+        .expect("Failed to run ck --index");
+    assert!(output.status.success());
+    fs::write(temp_dir.path().join("b.txt"), "gamma delta epsilon\n").unwrap();
+
+    let output = ck_command()
+        .args(["--index", "--dry-run", "--exclude", "generated", "."])
+        .current_dir(temp_dir.path())
+        .output()
+        .expect("Failed to run ck --index --dry-run");
+    assert!(output.status.success());
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    assert!(stdout.contains("Dry run: 0 to add, 1 to update, 1 up to date"));
+    assert!(!stdout.contains("generated/"));
+}
+
+#[test]
+#[serial]
+fn test_snippet_lines_centers_semantic_preview() {
+    let temp_dir = TempDir::new().unwrap();
+    fs::write(
+        temp_dir.path().join("notes.txt"),
+        "load the config\nread the file\nparse the text\ncheck validate timeout   \nreturn parsed\n",
+    )
+    .unwrap();
+    let output = ck_command()
+        .args(["--index", "."])
+        .current_dir(temp_dir.path())
+        .output()
+        .expect("Failed to run ck --index");
+    assert!(output.status.success());
+
+    let output = ck_command()
+        .args([
+            "--sem",
+            "validate timeout",
+            "--snippet-lines",
+            "1",
+            "--threshold=-1",
+            "--jsonl",
+            ".",
+        ])
+        .current_dir(temp_dir.path())
+        .output()
+        .expect("Failed to run ck --sem");
+    assert!(output.status.success());
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    let result: serde_json::Value = serde_json::from_str(stdout.lines().next().unwrap()).unwrap();
+    assert_eq!(result["snippet"], "check validate timeout");
+    assert_eq!(result["snippet_line"], 4);
+}
+
+#[test]
+#[serial]
+fn test_files_without_matches_per_mode() {
+    let temp_dir = TempDir::new().unwrap();
+    fs::write(temp_dir.path().join("auth.txt"), "login with a password\n").unwrap();
+    fs::write(temp_dir.path().join("math.txt"), "add two numbers\n").unwrap();
+
+    let run = |args: &[&str]| {
+        let output = ck_command()
+            .args(args)
+            .current_dir(temp_dir.path())
+            .output()
+            .expect("Failed to run ck -L");
+        let mut files: Vec<String> = String::from_utf8_lossy(&output.stdout)
+            .lines()
+            .map(|line| line.rsplit('/').next().unwrap().to_string())
+            .collect();
+        files.sort();
+        (output.status.code(), files)
+    };
+
+    // Files are listed individually, not the directory that was searched
+    assert_eq!(
+        run(&["-L", "password", "."]),
+        (Some(0), vec!["math.txt".to_string()])
+    );
+    assert_eq!(run(&["-L", "d", "."]), (Some(1), Vec::new()));
+
+    // Semantic: every indexed file has some similarity, so only the
+    // threshold decides
+    assert_eq!(
+        run(&["-L", "--sem", "credentials", "--threshold=-1", "."]),
+        (Some(1), Vec::new())
+    );
+    assert_eq!(
+        run(&["-L", "--sem", "credentials", "--threshold", "1.5", "."]),
+        (
+            Some(0),
+            vec!["auth.txt".to_string(), "math.txt".to_string()]
+        )
+    );
+
+    assert_eq!(
+        run(&["-L", "--lex", "numbers", "."]),
+        (Some(0), vec!["auth.txt".to_string()])
+    );
+}
+
 /// The tantivy lexical index used to be built once on first --lex and never
 /// refreshed: files added or edited afterwards were invisible to lexical
 /// search. It must now rebuild when the corpus changes.
@@ -1413,3 +1624,2968 @@ fn test_hidden_flag_lexical_index() {
         "file in hidden dir SHOULD be in the lexical index with --hidden; stdout: {stdout}"
     );
 }
+
+/// `.ck/config.toml` fills in defaults that flags can still override, and
+/// `--show-config` reports where each effective value came from.
+#[test]
+fn test_config_file_defaults_and_show_config() {
+    let temp_dir = TempDir::new().unwrap();
+    fs::create_dir_all(temp_dir.path().join(".ck")).unwrap();
+    fs::write(
+        temp_dir.path().join(".ck").join("config.toml"),
+        "topk = 3\nexclude = [\"skipped\"]\n",
+    )
+    .unwrap();
+    fs::create_dir_all(temp_dir.path().join("skipped")).unwrap();
+    fs::write(temp_dir.path().join("kept.txt"), "needle").unwrap();
+    fs::write(temp_dir.path().join("skipped").join("other.txt"), "needle").unwrap();
+
+    // Config excludes apply to searches started anywhere below the root
+    let output = ck_command()
+        .args(["-r", "needle", "."])
+        .current_dir(temp_dir.path())
+        .output()
+        .expect("Failed to run ck");
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(stdout.contains("kept.txt"), "stdout: {stdout}");
+    assert!(
+        !stdout.contains("other.txt"),
+        "config exclude should hide skipped/; stdout: {stdout}"
+    );
+
+    let output = ck_command()
+        .args(["--show-config", "--topk", "7"])
+        .current_dir(temp_dir.path().join("skipped"))
+        .output()
+        .expect("Failed to run ck --show-config");
+    assert!(output.status.success());
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(stdout.contains("config.toml"), "stdout: {stdout}");
+    assert!(stdout.contains("topk = 7  # cli"), "stdout: {stdout}");
+    assert!(
+        stdout.contains("semantic_threshold = 0.6  # default"),
+        "stdout: {stdout}"
+    );
+    assert!(
+        stdout.contains("exclude = [\"skipped\"]  # config"),
+        "stdout: {stdout}"
+    );
+}
+
+/// The model `ck --index` built with is recorded in the manifest; later runs
+/// without `--model` use it ahead of the config default.
+#[test]
+#[serial]
+fn test_index_pins_model_for_later_runs() {
+    let temp_dir = TempDir::new().unwrap();
+    fs::write(temp_dir.path().join("lib.rs"), "fn parse_config() {}\n").unwrap();
+
+    let output = ck_command()
+        .args(["--index", "--model", "bge-small", "."])
+        .current_dir(temp_dir.path())
+        .output()
+        .expect("Failed to run ck --index");
+    assert!(output.status.success());
+    let manifest: serde_json::Value = serde_json::from_slice(
+        &fs::read(temp_dir.path().join(".ck").join("manifest.json")).unwrap(),
+    )
+    .unwrap();
+    assert_eq!(manifest["embedding_model"], "BAAI/bge-small-en-v1.5");
+
+    fs::write(
+        temp_dir.path().join(".ck").join("config.toml"),
+        "default_model = \"nomic-v1.5\"\n",
+    )
+    .unwrap();
+    let output = ck_command()
+        .arg("--show-config")
+        .current_dir(temp_dir.path())
+        .output()
+        .expect("Failed to run ck --show-config");
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(
+        stdout.contains("default_model = \"BAAI/bge-small-en-v1.5\"  # pinned by index"),
+        "stdout: {stdout}"
+    );
+
+    // The rebuild stays on the pinned model, saying why it ignores the config
+    let output = ck_command()
+        .args(["--index", "."])
+        .current_dir(temp_dir.path())
+        .output()
+        .expect("Failed to run ck --index");
+    assert!(output.status.success());
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(
+        stderr.contains("rather than config default_model 'nomic-v1.5'"),
+        "stderr: {stderr}"
+    );
+    let manifest = fs::read_to_string(temp_dir.path().join(".ck").join("manifest.json")).unwrap();
+    assert!(manifest.contains("BAAI/bge-small-en-v1.5"), "{manifest}");
+
+    // An explicit --model that conflicts with the pin is called out
+    let output = ck_command()
+        .args(["--sem", "config", "--model", "nomic-v1.5", "."])
+        .current_dir(temp_dir.path())
+        .output()
+        .expect("Failed to run ck --sem");
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(
+        stderr.contains("'--model nomic-v1.5' differs from 'BAAI/bge-small-en-v1.5'"),
+        "stderr: {stderr}"
+    );
+}
+
+/// `[language_models]` embeds each mapped language with its own model, and
+/// semantic search scores those files against a query from the same model.
+#[test]
+#[serial]
+fn test_language_models_from_config() {
+    let temp_dir = TempDir::new().unwrap();
+    fs::create_dir_all(temp_dir.path().join(".ck")).unwrap();
+    fs::write(
+        temp_dir.path().join(".ck").join("config.toml"),
+        "[language_models]\nmarkdown = \"minilm\"\n",
+    )
+    .unwrap();
+    fs::write(temp_dir.path().join("lib.rs"), "fn parse_config() {}\n").unwrap();
+    fs::write(
+        temp_dir.path().join("README.md"),
+        "# Configuration\n\nHow the config is parsed.\n",
+    )
+    .unwrap();
+
+    let output = ck_command()
+        .args(["--index", "--verbose", "."])
+        .current_dir(temp_dir.path())
+        .output()
+        .expect("Failed to run ck --index");
+    assert!(output.status.success());
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(
+        stderr.contains("markdown: sentence-transformers/all-MiniLM-L6-v2"),
+        "stderr: {stderr}"
+    );
+
+    let output = ck_command()
+        .args(["--sem", "--threshold", "0", "-l", "parse config", "."])
+        .current_dir(temp_dir.path())
+        .output()
+        .expect("Failed to run ck --sem");
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(
+        output.status.success(),
+        "stderr: {}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+    assert!(stdout.contains("lib.rs"), "stdout: {stdout}");
+    assert!(stdout.contains("README.md"), "stdout: {stdout}");
+}
+
+#[test]
+#[serial]
+fn test_export_import_round_trip() {
+    let source = TempDir::new().unwrap();
+    fs::write(
+        source.path().join("lib.rs"),
+        "fn alpha() -> u32 {\n    1\n}\n\nfn beta() -> u32 {\n    2\n}\n",
+    )
+    .unwrap();
+
+    let output = ck_command()
+        .args(["--index", "."])
+        .current_dir(source.path())
+        .output()
+        .expect("Failed to run ck --index");
+    assert!(output.status.success());
+
+    let output = ck_command()
+        .args(["--export", "--with-vectors", "."])
+        .current_dir(source.path())
+        .output()
+        .expect("Failed to run ck --export");
+    assert!(output.status.success());
+    let dump = String::from_utf8_lossy(&output.stdout).to_string();
+    let records: Vec<serde_json::Value> = dump
+        .lines()
+        .map(|line| serde_json::from_str(line).expect("export line should be JSON"))
+        .collect();
+    assert!(!records.is_empty());
+    assert!(records.iter().all(|r| r["path"] == "lib.rs"));
+    assert!(records.iter().all(|r| r["embedding"].is_array()));
+
+    // Import into a copy of the tree that has never been indexed
+    let target = TempDir::new().unwrap();
+    fs::copy(source.path().join("lib.rs"), target.path().join("lib.rs")).unwrap();
+    let dump_path = target.path().join("dump.jsonl");
+    fs::write(&dump_path, &dump).unwrap();
+
+    let output = ck_command()
+        .args(["--import", dump_path.to_str().unwrap(), "."])
+        .current_dir(target.path())
+        .output()
+        .expect("Failed to run ck --import");
+    assert!(
+        output.status.success(),
+        "stderr: {}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+    assert!(target.path().join(".ck").join("lib.rs.ck").exists());
+
+    let output = ck_command()
+        .args(["--export", "."])
+        .current_dir(target.path())
+        .output()
+        .expect("Failed to re-export");
+    let reexported = String::from_utf8_lossy(&output.stdout);
+    assert_eq!(reexported.lines().count(), records.len());
+    assert!(!reexported.contains("\"embedding\""));
+}
+
+#[test]
+fn test_include_glob_restricts_search() {
+    let temp_dir = TempDir::new().unwrap();
+    fs::create_dir_all(temp_dir.path().join("src")).unwrap();
+    fs::create_dir_all(temp_dir.path().join("vendor")).unwrap();
+    fs::write(temp_dir.path().join("src").join("main.rs"), "needle").unwrap();
+    fs::write(temp_dir.path().join("src").join("notes.txt"), "needle").unwrap();
+    fs::write(temp_dir.path().join("vendor").join("dep.rs"), "needle").unwrap();
+
+    let output = ck_command()
+        .args(["-r", "--include", "src/**/*.rs", "needle", "."])
+        .current_dir(temp_dir.path())
+        .output()
+        .expect("Failed to run ck");
+    assert!(output.status.success());
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(stdout.contains("main.rs"), "stdout: {stdout}");
+    assert!(!stdout.contains("notes.txt"), "stdout: {stdout}");
+    assert!(!stdout.contains("dep.rs"), "stdout: {stdout}");
+
+    // Exclude wins when both match
+    let output = ck_command()
+        .args([
+            "-r",
+            "--include",
+            "**/*.rs",
+            "--exclude",
+            "vendor",
+            "needle",
+            ".",
+        ])
+        .current_dir(temp_dir.path())
+        .output()
+        .expect("Failed to run ck");
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(stdout.contains("main.rs"), "stdout: {stdout}");
+    assert!(!stdout.contains("dep.rs"), "stdout: {stdout}");
+}
+
+#[test]
+fn test_color_flag_controls_ansi_output() {
+    let temp_dir = TempDir::new().unwrap();
+    fs::write(temp_dir.path().join("a.txt"), "find the needle here").unwrap();
+
+    let run = |args: &[&str], no_color: Option<&str>| {
+        let mut cmd = ck_command();
+        cmd.args(args).current_dir(temp_dir.path());
+        cmd.env_remove("NO_COLOR");
+        if let Some(value) = no_color {
+            cmd.env("NO_COLOR", value);
+        }
+        let output = cmd.output().expect("Failed to run ck");
+        assert!(output.status.success());
+        String::from_utf8_lossy(&output.stdout).to_string()
+    };
+
+    // Piped output is plain by default
+    assert!(!run(&["needle", "a.txt"], None).contains('\x1b'));
+    assert!(run(&["--color", "always", "needle", "a.txt"], None).contains('\x1b'));
+    assert!(!run(&["--color", "never", "needle", "a.txt"], None).contains('\x1b'));
+    // An explicit --color=always wins over NO_COLOR
+    assert!(run(&["--color=always", "needle", "a.txt"], Some("1")).contains('\x1b'));
+}
+
+#[test]
+fn test_inspect_shows_chunk_overlap() {
+    let temp_dir = TempDir::new().unwrap();
+    fs::write(
+        temp_dir.path().join("lib.py"),
+        "def first():\n    return 1\n\n\ndef second():\n    return 2\n",
+    )
+    .unwrap();
+
+    let output = ck_command()
+        .args(["--inspect", "--chunk-overlap", "2", "lib.py"])
+        .current_dir(temp_dir.path())
+        .output()
+        .expect("Failed to run ck");
+    assert!(output.status.success());
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(stdout.contains("L5-6 (+ overlap L3-4)"), "stdout: {stdout}");
+    assert!(!stdout.contains("L1-2 (+ overlap"), "stdout: {stdout}");
+}
+
+#[test]
+#[serial]
+fn test_binary_and_non_utf8_files_do_not_fail_inspect_or_index() {
+    let temp_dir = TempDir::new().unwrap();
+    fs::write(
+        temp_dir.path().join("logo.png"),
+        b"\x89PNG\r\n\x1a\n\x00\x00\x00\rIHDR\x00\x00\x00\x10",
+    )
+    .unwrap();
+    fs::write(
+        temp_dir.path().join("legacy.py"),
+        b"# caf\xe9 menu\ndef order():\n    return 1\n",
+    )
+    .unwrap();
+
+    let run = |args: &[&str]| {
+        let output = ck_command()
+            .args(args)
+            .current_dir(temp_dir.path())
+            .output()
+            .expect("Failed to run ck");
+        assert!(
+            output.status.success(),
+            "stderr: {}",
+            String::from_utf8_lossy(&output.stderr)
+        );
+        output
+    };
+
+    let binary = run(&["--inspect", "logo.png"]);
+    assert!(String::from_utf8_lossy(&binary.stderr).contains("binary file, skipped"));
+
+    let legacy = run(&["--inspect", "legacy.py"]);
+    assert!(String::from_utf8_lossy(&legacy.stderr).contains("not valid UTF-8"));
+    assert!(String::from_utf8_lossy(&legacy.stdout).contains("legacy.py"));
+
+    run(&["--index", "."]);
+    let found = run(&["--lex", "order", "."]);
+    assert!(String::from_utf8_lossy(&found.stdout).contains("legacy.py"));
+}
+
+#[test]
+fn test_count_tokens_reports_languages_and_total() {
+    let temp_dir = TempDir::new().unwrap();
+    fs::write(
+        temp_dir.path().join("main.rs"),
+        "fn main() {\n    println!(\"hi\");\n}\n",
+    )
+    .unwrap();
+    fs::write(temp_dir.path().join("app.py"), "def run():\n    return 1\n").unwrap();
+
+    let output = ck_command()
+        .args(["--count-tokens", "--json", "."])
+        .current_dir(temp_dir.path())
+        .output()
+        .expect("Failed to run ck");
+    assert!(output.status.success());
+    let count: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+    assert_eq!(count["files"], 2);
+    let languages = count["languages"].as_array().unwrap();
+    assert_eq!(languages.len(), 2);
+    let sum: u64 = languages
+        .iter()
+        .map(|l| l["tokens"].as_u64().unwrap())
+        .sum();
+    assert!(sum > 0);
+    assert_eq!(count["tokens"].as_u64().unwrap(), sum);
+
+    let output = ck_command()
+        .args(["--count-tokens", "."])
+        .current_dir(temp_dir.path())
+        .output()
+        .expect("Failed to run ck");
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(stdout.contains("python"), "stdout: {stdout}");
+    assert!(stdout.contains("Total"), "stdout: {stdout}");
+    assert!(
+        !temp_dir.path().join(".ck").exists(),
+        "counting must not index"
+    );
+}
+
+#[test]
+#[serial]
+fn test_similar_ranks_other_files() {
+    let temp_dir = TempDir::new().unwrap();
+    fs::write(
+        temp_dir.path().join("a.rs"),
+        "fn parse_config(input: &str) -> Config {\n    toml::from_str(input).unwrap()\n}\n",
+    )
+    .unwrap();
+    fs::write(
+        temp_dir.path().join("b.rs"),
+        "fn load_settings(text: &str) -> Settings {\n    toml::from_str(text).unwrap()\n}\n",
+    )
+    .unwrap();
+    fs::write(temp_dir.path().join("c.txt"), "meeting notes\n").unwrap();
+
+    let output = ck_command()
+        .args(["--index", "."])
+        .current_dir(temp_dir.path())
+        .output()
+        .expect("Failed to run ck index");
+    assert!(output.status.success());
+
+    for by in ["file", "chunk"] {
+        let output = ck_command()
+            .args(["--similar", "a.rs", "--by", by, "--threshold=-1", "-l", "."])
+            .current_dir(temp_dir.path())
+            .output()
+            .expect("Failed to run ck");
+        assert!(
+            output.status.success(),
+            "stderr: {}",
+            String::from_utf8_lossy(&output.stderr)
+        );
+        let stdout = String::from_utf8_lossy(&output.stdout);
+        assert!(stdout.contains("b.rs"), "stdout: {stdout}");
+        assert!(stdout.contains("c.txt"), "stdout: {stdout}");
+        assert!(!stdout.contains("a.rs"), "stdout: {stdout}");
+    }
+
+    // --by only makes sense with --similar
+    let output = ck_command()
+        .args(["--by", "chunk", "needle", "."])
+        .current_dir(temp_dir.path())
+        .output()
+        .expect("Failed to run ck");
+    assert!(!output.status.success());
+}
+
+#[test]
+#[serial]
+fn test_semantic_search_ranks_across_index_roots() {
+    let workspace = TempDir::new().unwrap();
+    for (repo, body) in [
+        ("repo_a", "fn parse_config() {}\nfn read_file() {}\n"),
+        ("repo_b", "fn load_settings() {}\nfn write_log() {}\n"),
+    ] {
+        let dir = workspace.path().join(repo);
+        fs::create_dir(&dir).unwrap();
+        fs::write(dir.join("lib.rs"), body).unwrap();
+        fs::write(dir.join("notes.txt"), "meeting notes\n").unwrap();
+        let output = ck_command()
+            .args(["--index", "."])
+            .current_dir(&dir)
+            .output()
+            .expect("Failed to run ck index");
+        assert!(output.status.success());
+    }
+
+    let search = |topk: &str| {
+        let output = ck_command()
+            .args([
+                "--sem",
+                "config",
+                "--topk",
+                topk,
+                "--threshold=-1",
+                "--jsonl",
+                "repo_a",
+                "repo_b",
+            ])
+            .current_dir(workspace.path())
+            .output()
+            .expect("Failed to run ck");
+        assert!(
+            output.status.success(),
+            "stderr: {}",
+            String::from_utf8_lossy(&output.stderr)
+        );
+        String::from_utf8_lossy(&output.stdout)
+            .lines()
+            .map(|line| serde_json::from_str::<serde_json::Value>(line).unwrap())
+            .collect::<Vec<_>>()
+    };
+
+    // topk applies to the merged list, and every result names its root
+    assert_eq!(search("3").len(), 3);
+    let results = search("10");
+    assert_eq!(results.len(), 6);
+    let scores: Vec<f64> = results
+        .iter()
+        .map(|r| r["score"].as_f64().unwrap())
+        .collect();
+    assert!(scores.windows(2).all(|pair| pair[0] >= pair[1]));
+    for result in &results {
+        let root = result["root"].as_str().unwrap();
+        assert!(result["path"].as_str().unwrap().starts_with(root));
+    }
+    assert!(results.iter().any(|r| r["root"] == "repo_a"));
+    assert!(results.iter().any(|r| r["root"] == "repo_b"));
+
+    // Each root keeps its own index; nothing is indexed at the workspace level
+    assert!(!workspace.path().join(".ck").exists());
+}
+
+#[test]
+#[serial]
+fn test_bench_reports_latency_per_query() {
+    let temp_dir = TempDir::new().unwrap();
+    fs::create_dir(temp_dir.path().join("src")).unwrap();
+    fs::write(
+        temp_dir.path().join("src/lib.rs"),
+        "fn parse_config() {}\nfn read_file() {}\n",
+    )
+    .unwrap();
+    fs::write(
+        temp_dir.path().join("queries.txt"),
+        "# latency set\nconfig parsing\n\nfile reading\n",
+    )
+    .unwrap();
+
+    let output = ck_command()
+        .args([
+            "--bench",
+            "queries.txt",
+            "--sem",
+            "--runs",
+            "3",
+            "--json",
+            "src",
+        ])
+        .current_dir(temp_dir.path())
+        .output()
+        .expect("Failed to run ck --bench");
+    assert!(
+        output.status.success(),
+        "stderr: {}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+    let report: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+    let rows = report.as_array().unwrap();
+    assert_eq!(rows.len(), 2);
+    assert_eq!(rows[0]["query"], "config parsing");
+    assert_eq!(rows[0]["runs"], 3);
+    assert_eq!(rows[0]["candidates"], 2);
+    assert!(rows[0]["p95_ms"].as_f64().unwrap() >= rows[0]["p50_ms"].as_f64().unwrap());
+
+    // The table form has a header and one row per query
+    let output = ck_command()
+        .args(["--bench", "queries.txt", "--lex", "--runs", "1", "src"])
+        .current_dir(temp_dir.path())
+        .output()
+        .expect("Failed to run ck --bench");
+    assert!(output.status.success());
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(stdout.lines().next().unwrap().contains("p50 ms"));
+    assert_eq!(stdout.lines().count(), 3);
+}
+
+#[test]
+#[serial]
+fn test_repl_reads_queries_and_commands_from_stdin() {
+    use std::io::Write;
+    use std::process::Stdio;
+
+    let temp_dir = TempDir::new().unwrap();
+    fs::write(
+        temp_dir.path().join("notes.txt"),
+        "alpha release notes\nbeta rollout plan\n",
+    )
+    .unwrap();
+
+    let mut child = ck_command()
+        .args(["--repl", "--regex", "."])
+        .current_dir(temp_dir.path())
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .expect("Failed to spawn ck --repl");
+    child
+        .stdin
+        .take()
+        .unwrap()
+        .write_all(b"beta\n:topk zero\n:lex\nalpha\n:q\nnever searched\n")
+        .unwrap();
+    let output = child.wait_with_output().unwrap();
+    assert!(
+        output.status.success(),
+        "stderr: {}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    let lines: Vec<&str> = stdout.lines().collect();
+    assert_eq!(lines.len(), 2, "stdout: {stdout}");
+    // Regex results have no score, lexical ones do
+    assert!(lines[0].contains("notes.txt:2: beta rollout plan"));
+    assert!(!lines[0].starts_with('['));
+    assert!(lines[1].starts_with('['));
+    assert!(lines[1].contains("alpha release notes"));
+    assert!(String::from_utf8_lossy(&output.stderr).contains("Invalid topk 'zero'"));
+}
+
+#[test]
+#[serial]
+fn test_threshold_percentile_keeps_top_of_distribution() {
+    let temp_dir = TempDir::new().unwrap();
+    fs::write(
+        temp_dir.path().join("strong.txt"),
+        "cache cache cache cache eviction\n",
+    )
+    .unwrap();
+    fs::write(temp_dir.path().join("medium.txt"), "cache lookup table\n").unwrap();
+    fs::write(
+        temp_dir.path().join("weak.txt"),
+        "a long note that mentions the cache once among many other words here\n",
+    )
+    .unwrap();
+
+    let run = |extra: &[&str]| {
+        let output = ck_command()
+            .args(["--lex", "cache", "."])
+            .args(extra)
+            .current_dir(temp_dir.path())
+            .output()
+            .expect("Failed to run ck");
+        assert!(
+            output.status.success(),
+            "stderr: {}",
+            String::from_utf8_lossy(&output.stderr)
+        );
+        String::from_utf8_lossy(&output.stdout)
+            .lines()
+            .filter(|line| line.contains(".txt"))
+            .count()
+    };
+    assert_eq!(run(&[]), 3);
+    assert_eq!(run(&["--threshold-percentile", "90"]), 1);
+    assert_eq!(run(&["--threshold-percentile", "0"]), 3);
+
+    let output = ck_command()
+        .args(["--lex", "cache", "--threshold", "0.5"])
+        .args(["--threshold-percentile", "90", "."])
+        .current_dir(temp_dir.path())
+        .output()
+        .expect("Failed to run ck");
+    assert!(!output.status.success());
+    assert!(String::from_utf8_lossy(&output.stderr).contains("cannot be used with"));
+}
+
+#[test]
+#[serial]
+fn test_chunk_strategy_is_recorded_and_used_by_inspect() {
+    let temp_dir = TempDir::new().unwrap();
+    fs::write(
+        temp_dir.path().join("notes.md"),
+        "# Notes\n\nFirst paragraph about caching.\n\nSecond paragraph.\nStill second.\n\nThird one.\n",
+    )
+    .unwrap();
+
+    let run = |args: &[&str]| {
+        let output = ck_command()
+            .args(args)
+            .current_dir(temp_dir.path())
+            .output()
+            .expect("Failed to run ck");
+        assert!(
+            output.status.success(),
+            "stderr: {}",
+            String::from_utf8_lossy(&output.stderr)
+        );
+        format!(
+            "{}{}",
+            String::from_utf8_lossy(&output.stdout),
+            String::from_utf8_lossy(&output.stderr)
+        )
+    };
+
+    run(&["--index", "--chunk-strategy", "paragraph", "."]);
+    assert!(run(&["--inspect", "notes.md"]).contains("Chunks: 4"));
+
+    // A plain update keeps the recorded strategy; a different one rebuilds
+    assert!(!run(&["--index", "."]).contains("rebuilding"));
+    assert!(run(&["--inspect", "notes.md"]).contains("Chunks: 4"));
+    assert!(run(&["--index", "--chunk-strategy", "syntax", "."]).contains("rebuilding"));
+    assert!(!run(&["--inspect", "notes.md"]).contains("Chunks: 4"));
+}
+
+#[test]
+fn test_verify_models_removes_corrupt_downloads() {
+    use sha2::{Digest, Sha256};
+
+    let cache_home = TempDir::new().unwrap();
+    let repo = cache_home
+        .path()
+        .join("ck/models/models--example--tiny-encoder");
+    fs::create_dir_all(repo.join("blobs")).unwrap();
+    let intact = format!("{:x}", Sha256::digest(b"weights"));
+    let truncated = format!("{:x}", Sha256::digest(b"more weights"));
+    fs::write(repo.join("blobs").join(&intact), b"weights").unwrap();
+    fs::write(repo.join("blobs").join(&truncated), b"more").unwrap();
+    fs::write(repo.join("blobs").join("0123.part"), b"half").unwrap();
+
+    let output = ck_command()
+        .arg("--verify-models")
+        .env("XDG_CACHE_HOME", cache_home.path())
+        .output()
+        .expect("Failed to run ck");
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(output.status.success(), "stderr: {stderr}");
+    assert!(stderr.contains("checksum mismatch"), "stderr: {stderr}");
+    assert!(stderr.contains("partial download"), "stderr: {stderr}");
+    assert!(
+        stderr.contains("removed 2 broken file(s)"),
+        "stderr: {stderr}"
+    );
+    assert!(repo.join("blobs").join(&intact).exists());
+    assert!(!repo.join("blobs").join(&truncated).exists());
+
+    let output = ck_command()
+        .arg("--verify-models")
+        .env("XDG_CACHE_HOME", cache_home.path())
+        .output()
+        .expect("Failed to run ck");
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(
+        stderr.contains("example/tiny-encoder: 1 file(s) OK"),
+        "stderr: {stderr}"
+    );
+    assert!(stderr.contains("are intact"), "stderr: {stderr}");
+}
+
+#[test]
+fn test_include_hidden_searches_dotfiles_but_not_git_dir() {
+    let temp_dir = TempDir::new().unwrap();
+    fs::create_dir_all(temp_dir.path().join(".git")).unwrap();
+    fs::write(temp_dir.path().join(".git/config"), "token = git\n").unwrap();
+    fs::write(temp_dir.path().join(".env"), "token = env\n").unwrap();
+    fs::write(temp_dir.path().join("app.txt"), "token = app\n").unwrap();
+
+    let search = |extra: &[&str]| {
+        let output = ck_command()
+            .args(extra)
+            .args(["--no-default-excludes", "token", "."])
+            .current_dir(temp_dir.path())
+            .output()
+            .expect("Failed to run ck");
+        assert!(output.status.success());
+        String::from_utf8_lossy(&output.stdout).to_string()
+    };
+
+    let plain = search(&[]);
+    assert!(plain.contains("token = app"));
+    assert!(!plain.contains("token = env"));
+
+    let hidden = search(&["--include-hidden"]);
+    assert!(hidden.contains("token = app"));
+    assert!(hidden.contains("token = env"));
+    assert!(!hidden.contains("token = git"));
+}
+
+#[test]
+fn test_include_glob_reaches_into_git_dir() {
+    let temp_dir = TempDir::new().unwrap();
+    fs::create_dir_all(temp_dir.path().join(".git")).unwrap();
+    fs::write(temp_dir.path().join(".git/config"), "token = git\n").unwrap();
+    fs::write(temp_dir.path().join("app.txt"), "token = app\n").unwrap();
+
+    // With the default excludes in effect, which list .git too
+    let output = ck_command()
+        .args(["--hidden", "--include", ".git/**", "token", "."])
+        .current_dir(temp_dir.path())
+        .output()
+        .expect("Failed to run ck");
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(output.status.success(), "stdout: {stdout}");
+    assert!(stdout.contains("token = git"));
+    assert!(!stdout.contains("token = app"));
+
+    // An exclude of the user's own still wins
+    let output = ck_command()
+        .args([
+            "--hidden",
+            "--include",
+            ".git/**",
+            "--exclude",
+            ".git",
+            "token",
+            ".",
+        ])
+        .current_dir(temp_dir.path())
+        .output()
+        .expect("Failed to run ck");
+    assert_eq!(output.status.code(), Some(1));
+}
+
+#[test]
+fn test_json_records_carry_schema_version() {
+    let temp_dir = TempDir::new().unwrap();
+    fs::write(temp_dir.path().join("lib.rs"), "fn main() {}\n").unwrap();
+
+    for format in ["--json", "--jsonl"] {
+        let output = ck_command()
+            .args(["fn main", format, temp_dir.path().to_str().unwrap()])
+            .output()
+            .expect("Failed to run ck");
+        assert!(output.status.success());
+        let stdout = String::from_utf8(output.stdout).unwrap();
+        let record: serde_json::Value =
+            serde_json::from_str(stdout.lines().next().unwrap()).unwrap();
+        assert_eq!(record["schema_version"], 1, "{format}: {stdout}");
+
+        let output = ck_command()
+            .args(["--print-json-schema", format])
+            .output()
+            .expect("Failed to run ck");
+        assert!(output.status.success());
+        let schema: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+        assert!(schema["properties"]["schema_version"].is_object());
+        assert!(schema["properties"]["span"].is_object());
+    }
+}
+
+#[test]
+#[serial]
+fn test_at_searches_a_git_tag_without_checkout() {
+    let temp_dir = TempDir::new().unwrap();
+    let git = |args: &[&str]| {
+        let status = Command::new("git")
+            .args(["-c", "user.name=ck", "-c", "user.email=ck@example.com"])
+            .args(args)
+            .current_dir(temp_dir.path())
+            .output()
+            .expect("Failed to run git")
+            .status;
+        assert!(status.success(), "git {args:?}");
+    };
+    git(&["init", "-q"]);
+    fs::write(temp_dir.path().join("lock.rs"), "fn take_lock_twice() {}\n").unwrap();
+    git(&["add", "."]);
+    git(&["commit", "-q", "-m", "old"]);
+    git(&["tag", "v1"]);
+    fs::write(temp_dir.path().join("lock.rs"), "fn take_lock_once() {}\n").unwrap();
+    git(&["commit", "-q", "-am", "fix"]);
+
+    let output = ck_command()
+        .args([
+            "--sem",
+            "--threshold",
+            "0",
+            "locking bug",
+            "--at",
+            "v1",
+            ".",
+        ])
+        .current_dir(temp_dir.path())
+        .output()
+        .expect("Failed to run ck");
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(
+        output.status.success(),
+        "stderr: {}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+    assert!(stdout.contains("v1:lock.rs"), "stdout: {stdout}");
+    assert!(stdout.contains("take_lock_twice"), "stdout: {stdout}");
+    assert!(!stdout.contains("take_lock_once"), "stdout: {stdout}");
+    assert!(temp_dir.path().join(".ck/refs").is_dir());
+
+    let output = ck_command()
+        .args(["lock", "--at", "v9", "."])
+        .current_dir(temp_dir.path())
+        .output()
+        .expect("Failed to run ck");
+    assert!(!output.status.success());
+    assert!(String::from_utf8_lossy(&output.stderr).contains("Unknown git ref 'v9'"));
+}
+
+#[test]
+#[serial]
+fn test_clean_orphans_keeps_at_snapshots() {
+    let temp_dir = TempDir::new().unwrap();
+    let git = |args: &[&str]| {
+        let status = Command::new("git")
+            .args(["-c", "user.name=ck", "-c", "user.email=ck@example.com"])
+            .args(args)
+            .current_dir(temp_dir.path())
+            .output()
+            .expect("Failed to run git")
+            .status;
+        assert!(status.success(), "git {args:?}");
+    };
+    git(&["init", "-q"]);
+    fs::write(temp_dir.path().join("a.rs"), "fn retry() {}\n").unwrap();
+    git(&["add", "."]);
+    git(&["commit", "-q", "-m", "init"]);
+
+    for args in [
+        &["--index", "."][..],
+        &["--at", "HEAD", "--lex", "retry", "."],
+        &["--clean-orphans", "."],
+    ] {
+        let output = ck_command()
+            .args(args)
+            .current_dir(temp_dir.path())
+            .output()
+            .expect("Failed to run ck");
+        assert!(
+            output.status.success(),
+            "ck {args:?}: {}",
+            String::from_utf8_lossy(&output.stderr)
+        );
+    }
+
+    // The snapshot's sidecars belong to its own index, not the working copy's
+    let snapshot = fs::read_dir(temp_dir.path().join(".ck/refs"))
+        .unwrap()
+        .next()
+        .expect("a snapshot under .ck/refs")
+        .unwrap()
+        .path();
+    assert!(snapshot.join("tree/.ck/a.rs.ck").is_file());
+}
+
+#[test]
+#[serial]
+fn test_dedup_collapses_near_identical_results() {
+    let temp_dir = TempDir::new().unwrap();
+    for name in ["a.txt", "b.txt", "c.txt"] {
+        fs::write(temp_dir.path().join(name), "generated boilerplate\n").unwrap();
+    }
+    let search = |extra: &[&str]| {
+        let output = ck_command()
+            .args(["--sem", "--threshold", "0", "--jsonl"])
+            .args(extra)
+            .args(["boilerplate", "."])
+            .current_dir(temp_dir.path())
+            .output()
+            .expect("Failed to run ck");
+        assert!(
+            output.status.success(),
+            "stderr: {}",
+            String::from_utf8_lossy(&output.stderr)
+        );
+        String::from_utf8_lossy(&output.stdout)
+            .lines()
+            .map(|line| serde_json::from_str::<serde_json::Value>(line).unwrap())
+            .collect::<Vec<_>>()
+    };
+
+    assert_eq!(search(&[]).len(), 3);
+    // The dummy embedder gives every chunk the same vector, so at similarity
+    // 0 all three collapse into one
+    let deduped = search(&["--dedup=0"]);
+    assert_eq!(deduped.len(), 1);
+    assert_eq!(deduped[0]["duplicates"], 2);
+    // The default similarity is above what the dummy vectors reach
+    assert_eq!(search(&["--dedup"]).len(), 3);
+
+    let output = ck_command()
+        .args(["--sem", "--dedup=2", "boilerplate", "."])
+        .current_dir(temp_dir.path())
+        .output()
+        .expect("Failed to run ck");
+    assert!(!output.status.success());
+    assert!(String::from_utf8_lossy(&output.stderr).contains("between -1 and 1"));
+}
+
+#[test]
+#[serial]
+fn test_path_style_is_applied_to_every_output_format() {
+    let temp_dir = TempDir::new().unwrap();
+    let src = temp_dir.path().join("src");
+    fs::create_dir_all(&src).unwrap();
+    fs::write(src.join("lib.rs"), "fn needle() {}\n").unwrap();
+    fs::write(temp_dir.path().join("notes.txt"), "haystack\n").unwrap();
+
+    let run = |dir: &std::path::Path, args: &[&str]| {
+        let output = ck_command()
+            .args(args)
+            .current_dir(dir)
+            .output()
+            .expect("Failed to run ck");
+        String::from_utf8_lossy(&output.stdout).trim().to_string()
+    };
+    let absolute = src.canonicalize().unwrap().join("lib.rs");
+
+    let root = temp_dir.path();
+    assert_eq!(
+        run(root, &["--path-style", "relative", "-n", "needle", "."]),
+        "src/lib.rs:1:fn needle() {}"
+    );
+    assert_eq!(
+        run(root, &["--path-style", "relative", "-l", "needle", "src"]),
+        "src/lib.rs"
+    );
+    assert_eq!(
+        run(root, &["--path-style", "relative", "-L", "needle", "."]),
+        "notes.txt"
+    );
+    let jsonl = run(
+        root,
+        &["--path-style", "relative", "--jsonl", "needle", "."],
+    );
+    let record: serde_json::Value = serde_json::from_str(&jsonl).unwrap();
+    assert_eq!(record["path"], "src/lib.rs");
+
+    // From a subdirectory, files elsewhere are reached through ..
+    assert_eq!(
+        run(&src, &["--path-style", "relative", "-l", "needle", ".."]),
+        "lib.rs"
+    );
+    let docs = temp_dir.path().join("docs");
+    fs::create_dir_all(&docs).unwrap();
+    assert_eq!(
+        run(
+            &docs,
+            &["--path-style", "relative", "-l", "needle", "../src"]
+        ),
+        "../src/lib.rs"
+    );
+    assert_eq!(
+        run(
+            &docs,
+            &["--path-style", "absolute", "-l", "needle", "../src"]
+        ),
+        absolute.display().to_string()
+    );
+}
+
+#[test]
+#[serial]
+fn test_format_vimgrep_and_grep_lines() {
+    let temp_dir = TempDir::new().unwrap();
+    fs::write(
+        temp_dir.path().join("main.rs"),
+        "fn main() {\n    let total = add(1, add(2, 3));\n}\n",
+    )
+    .unwrap();
+
+    let run = |args: &[&str]| {
+        let output = ck_command()
+            .args(args)
+            .current_dir(temp_dir.path())
+            .output()
+            .expect("Failed to run ck");
+        assert!(output.status.success());
+        String::from_utf8_lossy(&output.stdout).to_string()
+    };
+
+    let vimgrep = run(&[
+        "--format",
+        "vimgrep",
+        "--path-style",
+        "relative",
+        "add",
+        ".",
+    ]);
+    assert_eq!(
+        vimgrep.lines().collect::<Vec<_>>(),
+        [
+            "main.rs:2:17:    let total = add(1, add(2, 3));",
+            "main.rs:2:24:    let total = add(1, add(2, 3));",
+        ]
+    );
+
+    let grep = run(&["--format", "grep", "--path-style", "relative", "add", "."]);
+    assert_eq!(grep, "main.rs:2:    let total = add(1, add(2, 3));\n");
+
+    let output = ck_command()
+        .args(["--format", "vimgrep", "--json", "add", "."])
+        .current_dir(temp_dir.path())
+        .output()
+        .expect("Failed to run ck");
+    assert!(!output.status.success());
+}
+
+#[test]
+#[serial]
+fn test_sort_orders_results_by_path_or_line() {
+    let temp_dir = TempDir::new().unwrap();
+    fs::write(temp_dir.path().join("a.txt"), "one\ntwo\nneedle here\n").unwrap();
+    fs::write(temp_dir.path().join("b.txt"), "needle first\n").unwrap();
+    fs::write(temp_dir.path().join("c.txt"), "more\nneedle again\n").unwrap();
+
+    let run = |args: &[&str]| {
+        let output = ck_command()
+            .args(["--format", "grep", "--path-style", "relative"])
+            .args(args)
+            .current_dir(temp_dir.path())
+            .output()
+            .expect("Failed to run ck");
+        assert!(
+            output.status.success(),
+            "stderr: {}",
+            String::from_utf8_lossy(&output.stderr)
+        );
+        String::from_utf8_lossy(&output.stdout)
+            .lines()
+            .map(|line| line.split(':').take(2).collect::<Vec<_>>().join(":"))
+            .collect::<Vec<_>>()
+    };
+
+    assert_eq!(run(&["needle", "."]), ["a.txt:3", "b.txt:1", "c.txt:2"]);
+    assert_eq!(
+        run(&["--sort", "line", "needle", "."]),
+        ["b.txt:1", "c.txt:2", "a.txt:3"]
+    );
+
+    // Semantic results are reordered after --topk picks them
+    let semantic = run(&[
+        "--sem",
+        "--threshold",
+        "0",
+        "--topk",
+        "2",
+        "--sort",
+        "path",
+        "needle",
+        ".",
+    ]);
+    assert_eq!(semantic.len(), 2);
+    let mut sorted = semantic.clone();
+    sorted.sort();
+    assert_eq!(semantic, sorted);
+}
+
+#[test]
+#[serial]
+fn test_explain_shows_score_breakdown() {
+    let temp_dir = TempDir::new().unwrap();
+    fs::write(
+        temp_dir.path().join("auth.rs"),
+        "fn login(user: &str) {\n    check(user);\n}\n",
+    )
+    .unwrap();
+
+    let run = |args: &[&str]| {
+        let output = ck_command()
+            .args(args)
+            .current_dir(temp_dir.path())
+            .output()
+            .expect("Failed to run ck");
+        assert!(
+            output.status.success(),
+            "stderr: {}",
+            String::from_utf8_lossy(&output.stderr)
+        );
+        String::from_utf8_lossy(&output.stdout).to_string()
+    };
+
+    let semantic = run(&["--sem", "--threshold", "0", "--explain", "login", "."]);
+    assert!(semantic.contains("similarity "), "stdout: {semantic}");
+    assert!(
+        semantic.contains("threshold 0.000: passed"),
+        "stdout: {semantic}"
+    );
+    assert!(semantic.contains("matched lines 1-3"), "stdout: {semantic}");
+
+    let lexical = run(&["--lex", "--explain", "login", "."]);
+    assert!(lexical.contains("BM25 "), "stdout: {lexical}");
+
+    let hybrid = run(&["--hybrid", "--explain", "login", "."]);
+    assert!(hybrid.contains("hybrid score "), "stdout: {hybrid}");
+    assert!(hybrid.contains("semantic rank"), "stdout: {hybrid}");
+
+    // --scores alone shows just the number
+    let scores = run(&["--sem", "--threshold", "0", "--scores", "login", "."]);
+    assert!(!scores.contains("matched lines"), "stdout: {scores}");
+}
+
+#[test]
+#[serial]
+fn test_no_auto_index_requires_an_index_and_warns_when_stale() {
+    let temp_dir = TempDir::new().unwrap();
+    fs::write(temp_dir.path().join("old.txt"), "indexed earlier\n").unwrap();
+
+    let search = || {
+        ck_command()
+            .args(["--sem", "--threshold", "0", "-l", "--no-auto-index"])
+            .args(["indexed", "."])
+            .current_dir(temp_dir.path())
+            .output()
+            .expect("Failed to run ck")
+    };
+
+    let output = search();
+    assert!(!output.status.success());
+    assert!(String::from_utf8_lossy(&output.stderr).contains("No index found"));
+    assert!(!temp_dir.path().join(".ck").exists());
+
+    let status = ck_command()
+        .args(["--index", "."])
+        .current_dir(temp_dir.path())
+        .status()
+        .expect("Failed to run ck --index");
+    assert!(status.success());
+    fs::write(temp_dir.path().join("new.txt"), "indexed later\n").unwrap();
+
+    let output = search();
+    assert!(output.status.success());
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(stdout.contains("old.txt"));
+    assert!(!stdout.contains("new.txt"), "the index must not be updated");
+    assert!(String::from_utf8_lossy(&output.stderr).contains("1 new since it was last updated"));
+}
+
+#[test]
+#[serial]
+fn test_modified_since_filters_files_by_mtime() {
+    let temp_dir = TempDir::new().unwrap();
+    fs::write(temp_dir.path().join("old.txt"), "needle in old code\n").unwrap();
+    fs::write(temp_dir.path().join("new.txt"), "needle in new code\n").unwrap();
+    let month_ago = std::time::SystemTime::now() - std::time::Duration::from_secs(30 * 24 * 3600);
+    fs::File::options()
+        .write(true)
+        .open(temp_dir.path().join("old.txt"))
+        .unwrap()
+        .set_modified(month_ago)
+        .unwrap();
+
+    let run = |args: &[&str]| {
+        let output = ck_command()
+            .args(["--format", "grep", "--path-style", "relative"])
+            .args(args)
+            .current_dir(temp_dir.path())
+            .output()
+            .expect("Failed to run ck");
+        assert!(
+            output.status.success(),
+            "stderr: {}",
+            String::from_utf8_lossy(&output.stderr)
+        );
+        let mut files: Vec<String> = String::from_utf8_lossy(&output.stdout)
+            .lines()
+            .map(|line| line.split(':').next().unwrap().to_string())
+            .collect();
+        files.dedup();
+        files
+    };
+
+    assert_eq!(run(&["--modified-since", "7d", "needle", "."]), ["new.txt"]);
+    assert_eq!(
+        run(&["--sem", "--threshold", "0", "--after", "7d", "needle", "."]),
+        ["new.txt"]
+    );
+    assert_eq!(
+        run(&["--lex", "--modified-since", "7d", "needle", "."]),
+        ["new.txt"]
+    );
+    let mut both = run(&["--modified-since", "2000-01-01", "needle", "."]);
+    both.sort();
+    assert_eq!(both, ["new.txt", "old.txt"]);
+
+    let output = ck_command()
+        .args(["--modified-since", "yesterday", "needle", "."])
+        .current_dir(temp_dir.path())
+        .output()
+        .expect("Failed to run ck");
+    assert!(!output.status.success());
+    assert!(String::from_utf8_lossy(&output.stderr).contains("expected a duration"));
+}
+
+#[test]
+#[serial]
+fn test_offset_pages_through_the_same_ranking() {
+    let temp_dir = TempDir::new().unwrap();
+    for i in 0..6 {
+        fs::write(
+            temp_dir.path().join(format!("file_{i}.txt")),
+            format!("needle number {i}\n"),
+        )
+        .unwrap();
+    }
+
+    let run = |args: &[&str]| {
+        let output = ck_command()
+            .args(["--format", "grep", "--path-style", "relative"])
+            .args(args)
+            .current_dir(temp_dir.path())
+            .output()
+            .expect("Failed to run ck");
+        assert!(
+            output.status.success(),
+            "stderr: {}",
+            String::from_utf8_lossy(&output.stderr)
+        );
+        String::from_utf8_lossy(&output.stdout)
+            .lines()
+            .map(|line| line.split(':').next().unwrap().to_string())
+            .collect::<Vec<_>>()
+    };
+
+    // Every chunk ties under the test embedder, so this also checks that
+    // ties are broken the same way on every page
+    let sem = ["--sem", "--threshold", "0"];
+    let all = run(&[&sem[..], &["--topk", "6", "needle", "."]].concat());
+    assert_eq!(all.len(), 6);
+    let mut pages = Vec::new();
+    for offset in ["0", "2", "4"] {
+        pages.extend(run(&[
+            &sem[..],
+            &["--topk", "2", "--offset", offset, "needle", "."],
+        ]
+        .concat()));
+    }
+    assert_eq!(pages, all);
+
+    // Past the end there is nothing left to show
+    let output = ck_command()
+        .args(sem)
+        .args(["--offset", "6", "needle", "."])
+        .current_dir(temp_dir.path())
+        .output()
+        .expect("Failed to run ck");
+    assert!(output.stdout.is_empty());
+
+    // Regex results are paged the same way
+    assert_eq!(
+        run(&["--topk", "2", "--offset", "3", "needle", "."]),
+        ["file_3.txt", "file_4.txt"]
+    );
+}
+
+#[test]
+#[serial]
+fn test_verbose_warns_about_patterns_that_match_nothing() {
+    let temp_dir = TempDir::new().unwrap();
+    fs::write(temp_dir.path().join("main.rs"), "fn needle() {}\n").unwrap();
+    fs::write(temp_dir.path().join("notes.tmp"), "needle\n").unwrap();
+    fs::write(temp_dir.path().join(".ckignore"), "*.tmp\n*.pnng\n").unwrap();
+
+    let run = |verbose: bool| {
+        let mut command = ck_command();
+        if verbose {
+            command.arg("--verbose");
+        }
+        let output = command
+            .args(["--exclude", "*.tmp", "--exclude", "*.tpm", "needle", "."])
+            .current_dir(temp_dir.path())
+            .output()
+            .expect("Failed to run ck");
+        assert!(output.status.success());
+        String::from_utf8_lossy(&output.stderr).to_string()
+    };
+
+    let stderr = run(true);
+    assert!(
+        stderr.contains("--exclude pattern '*.tpm' matches nothing"),
+        "stderr: {stderr}"
+    );
+    assert!(stderr.contains(".ckignore:2 pattern '*.pnng' matches nothing"));
+    assert!(!stderr.contains("'*.tmp'"), "stderr: {stderr}");
+
+    assert!(!run(false).contains("matches nothing"));
+}
+
+#[test]
+#[serial]
+fn test_heading_groups_results_under_one_header_per_file() {
+    let temp_dir = TempDir::new().unwrap();
+    fs::write(
+        temp_dir.path().join("a.txt"),
+        "needle one\nhay\nneedle two\n",
+    )
+    .unwrap();
+    fs::write(temp_dir.path().join("b.txt"), "needle three\n").unwrap();
+
+    let run = |flag: &str| {
+        let output = ck_command()
+            .args([flag, "-n", "--sort", "path", "needle", "."])
+            .current_dir(temp_dir.path())
+            .output()
+            .expect("Failed to run ck");
+        assert!(output.status.success());
+        String::from_utf8_lossy(&output.stdout).to_string()
+    };
+
+    let grouped = run("--heading");
+    let lines: Vec<&str> = grouped.lines().collect();
+    assert_eq!(lines.len(), 6, "stdout: {grouped}");
+    assert!(lines[0].ends_with("a.txt"));
+    assert_eq!(lines[1], "1:needle one");
+    assert_eq!(lines[2], "3:needle two");
+    assert_eq!(lines[3], "");
+    assert!(lines[4].ends_with("b.txt"));
+    assert_eq!(lines[5], "1:needle three");
+
+    // Piped output keeps one filename per line unless asked otherwise
+    let flat = run("--no-heading");
+    assert_eq!(flat.lines().count(), 3);
+    assert!(flat.lines().all(|line| line.contains(".txt:")));
+}
+
+#[test]
+#[serial]
+fn test_quantized_index_is_searchable_and_converts_in_place() {
+    let temp_dir = TempDir::new().unwrap();
+    fs::write(
+        temp_dir.path().join("auth.rs"),
+        "fn login(user: &str) -> bool {\n    user == \"admin\"\n}\n",
+    )
+    .unwrap();
+    fs::write(temp_dir.path().join("notes.txt"), "retry with backoff\n").unwrap();
+
+    let run = |args: &[&str]| {
+        let output = ck_command()
+            .args(args)
+            .current_dir(temp_dir.path())
+            .output()
+            .expect("Failed to run ck");
+        assert!(
+            output.status.success(),
+            "stderr: {}",
+            String::from_utf8_lossy(&output.stderr)
+        );
+        output
+    };
+
+    run(&["--index", "."]);
+    let output = run(&["--index", "--quantize", "binary", "."]);
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(
+        stderr.contains("to binary vectors"),
+        "existing sidecars should be converted: {stderr}"
+    );
+    let manifest = fs::read_to_string(temp_dir.path().join(".ck/manifest.json")).unwrap();
+    assert!(manifest.contains(r#""quantization": "binary""#));
+
+    let output = run(&["--sem", "login", "--topk", "1", "."]);
+    assert!(String::from_utf8_lossy(&output.stdout).contains("auth.rs"));
+
+    // Going back to a finer precision re-embeds rather than converting
+    let output = run(&["--index", "--quantize", "int8", "."]);
+    assert!(String::from_utf8_lossy(&output.stderr).contains("rebuilding the index"));
+    let manifest = fs::read_to_string(temp_dir.path().join(".ck/manifest.json")).unwrap();
+    assert!(manifest.contains(r#""quantization": "int8""#));
+}
+
+#[test]
+#[serial]
+fn test_ann_index_is_built_kept_current_and_matches_exact_search() {
+    let temp_dir = TempDir::new().unwrap();
+    fs::write(temp_dir.path().join("a.txt"), "parse the config file\n").unwrap();
+    fs::write(temp_dir.path().join("b.txt"), "retry the request\n").unwrap();
+
+    let run = |args: &[&str]| {
+        let output = ck_command()
+            .args(args)
+            .current_dir(temp_dir.path())
+            .output()
+            .expect("Failed to run ck");
+        assert!(
+            output.status.success(),
+            "stderr: {}",
+            String::from_utf8_lossy(&output.stderr)
+        );
+        (
+            String::from_utf8_lossy(&output.stdout).to_string(),
+            String::from_utf8_lossy(&output.stderr).to_string(),
+        )
+    };
+
+    let (_, stderr) = run(&["--index", "--ann", "."]);
+    assert!(
+        stderr.contains("Approximate index rebuilt over 2 chunks"),
+        "stderr: {stderr}"
+    );
+    assert!(temp_dir.path().join(".ck/ann.bin").exists());
+
+    let (approximate, _) = run(&["--sem", "config", "--topk", "5", "--threshold", "0", "."]);
+    let (exact, _) = run(&[
+        "--sem",
+        "config",
+        "--topk",
+        "5",
+        "--threshold",
+        "0",
+        "--exact",
+        ".",
+    ]);
+    assert_eq!(approximate, exact);
+
+    // A changed tree rebuilds it; an unchanged one leaves it alone
+    fs::write(temp_dir.path().join("c.txt"), "open the socket\n").unwrap();
+    let (_, stderr) = run(&["--index", "."]);
+    assert!(stderr.contains("rebuilt over 3 chunks"), "stderr: {stderr}");
+    let (stdout, _) = run(&["--sem", "socket", "--topk", "5", "--threshold", "0", "."]);
+    assert!(stdout.contains("c.txt"));
+    let (_, stderr) = run(&["--index", "."]);
+    assert!(!stderr.contains("rebuilt"), "stderr: {stderr}");
+}
+
+#[test]
+#[serial]
+fn test_max_matches_per_file_and_max_count() {
+    let temp_dir = TempDir::new().unwrap();
+    let functions: String = (0..4)
+        .map(|i| format!("fn needle_{i}() {{\n    println!(\"needle {i}\");\n}}\n\n"))
+        .collect();
+    fs::write(temp_dir.path().join("a.rs"), &functions).unwrap();
+    fs::write(temp_dir.path().join("b.rs"), "fn needle() {}\n").unwrap();
+    fs::write(temp_dir.path().join("c.rs"), "fn needle() {}\n").unwrap();
+
+    let run = |args: &[&str]| {
+        let output = ck_command()
+            .args(["--format", "grep", "--path-style", "relative"])
+            .args(args)
+            .current_dir(temp_dir.path())
+            .output()
+            .expect("Failed to run ck");
+        assert!(
+            output.status.success(),
+            "stderr: {}",
+            String::from_utf8_lossy(&output.stderr)
+        );
+        String::from_utf8_lossy(&output.stdout)
+            .lines()
+            .map(|line| line.split(':').next().unwrap().to_string())
+            .collect::<Vec<_>>()
+    };
+
+    // Semantic: every chunk ties under the test embedder and ties go by
+    // path, so a.rs fills the top 3 unless capped
+    let sem = ["--sem", "--threshold", "0", "--topk", "3"];
+    assert_eq!(
+        run(&[&sem[..], &["needle", "."]].concat()),
+        ["a.rs", "a.rs", "a.rs"]
+    );
+    assert_eq!(
+        run(&[&sem[..], &["--max-matches-per-file", "1", "needle", "."]].concat()),
+        ["a.rs", "b.rs", "c.rs"]
+    );
+    // The smaller of --topk and --max-count wins
+    assert_eq!(
+        run(&[&sem[..], &["-m", "2", "needle", "."]].concat()).len(),
+        2
+    );
+
+    // Regex: a file's first matches, then the overall cap
+    assert_eq!(run(&["needle", "."]).len(), 10);
+    assert_eq!(
+        run(&["--max-matches-per-file", "2", "needle", "."]),
+        ["a.rs", "a.rs", "b.rs", "c.rs"]
+    );
+    assert_eq!(
+        run(&["--max-matches-per-file", "2", "-m", "3", "needle", "."]),
+        ["a.rs", "a.rs", "b.rs"]
+    );
+
+    let output = ck_command()
+        .args(["--max-matches-per-file", "0", "needle", "."])
+        .current_dir(temp_dir.path())
+        .output()
+        .expect("Failed to run ck");
+    assert!(!output.status.success());
+}
+
+#[test]
+#[serial]
+fn test_notebook_results_name_the_cell() {
+    let temp_dir = TempDir::new().unwrap();
+    let fixture =
+        Path::new(env!("CARGO_MANIFEST_DIR")).join("../ck-chunk/tests/fixtures/analysis.ipynb");
+    fs::copy(&fixture, temp_dir.path().join("analysis.ipynb")).unwrap();
+
+    let run = |args: &[&str]| {
+        let output = ck_command()
+            .args(["--path-style", "relative"])
+            .args(args)
+            .current_dir(temp_dir.path())
+            .output()
+            .expect("Failed to run ck");
+        assert!(
+            output.status.success(),
+            "stderr: {}",
+            String::from_utf8_lossy(&output.stderr)
+        );
+        String::from_utf8_lossy(&output.stdout).to_string()
+    };
+
+    let sem = run(&["--sem", "--threshold", "0", "--json", "revenue", "."]);
+    let cells: Vec<String> = sem
+        .lines()
+        .map(|line| {
+            let result: serde_json::Value = serde_json::from_str(line).unwrap();
+            result["symbol"].as_str().unwrap().to_string()
+        })
+        .collect();
+    // The raw cell (3) isn't indexed
+    assert_eq!(cells, ["cell 1", "cell 2", "cell 4", "cell 5"]);
+
+    // The index searches the cells' source, not the notebook's JSON
+    let lex = run(&["--lex", "revenue_by_region", "."]);
+    assert!(lex.contains("analysis.ipynb:cell 4:"), "{lex}");
+    assert!(lex.contains("def revenue_by_region(frame):"), "{lex}");
+    assert!(!lex.contains("\"source\""), "{lex}");
+
+    let grep = run(&["-n", "revenue_by_region", "."]);
+    assert!(
+        grep.contains("analysis.ipynb:cell 4:def revenue_by_region(frame):"),
+        "{grep}"
+    );
+}
+
+#[test]
+#[serial]
+fn test_invert_match() {
+    let temp_dir = TempDir::new().unwrap();
+    fs::write(temp_dir.path().join("a.txt"), "alpha\nbeta\ngamma\n").unwrap();
+    fs::write(temp_dir.path().join("b.txt"), "alpha\n").unwrap();
+    fs::write(temp_dir.path().join("c.txt"), "delta\n").unwrap();
+
+    let run = |args: &[&str]| {
+        let output = ck_command()
+            .args(["--path-style", "relative"])
+            .args(args)
+            .current_dir(temp_dir.path())
+            .output()
+            .expect("Failed to run ck");
+        (
+            output.status.success(),
+            String::from_utf8_lossy(&output.stdout).to_string(),
+            String::from_utf8_lossy(&output.stderr).to_string(),
+        )
+    };
+
+    let (ok, stdout, _) = run(&["-v", "-n", "alpha", "."]);
+    assert!(ok);
+    assert_eq!(stdout, "a.txt:2:beta\na.txt:3:gamma\nc.txt:1:delta\n");
+
+    // -l lists files with a line that doesn't match; -L those where every line does
+    let (_, stdout, _) = run(&["-v", "-l", "alpha", "."]);
+    assert_eq!(stdout, "a.txt\nc.txt\n");
+    let (_, stdout, _) = run(&["--invert-match", "-L", "alpha", "."]);
+    assert_eq!(stdout, "b.txt\n");
+
+    // Lexical: the files without a hit
+    let (ok, stdout, _) = run(&["--lex", "-v", "--format", "grep", "alpha", "."]);
+    assert!(ok);
+    let files: Vec<&str> = stdout
+        .lines()
+        .map(|line| line.split(':').next().unwrap())
+        .collect();
+    assert_eq!(files, ["c.txt"]);
+
+    let (ok, _, stderr) = run(&["--sem", "-v", "alpha", "."]);
+    assert!(!ok);
+    assert!(
+        stderr.contains("--invert-match only applies to regex and lexical search"),
+        "{stderr}"
+    );
+}
+
+#[test]
+#[serial]
+fn test_replace_prints_expanded_matches() {
+    let temp_dir = TempDir::new().unwrap();
+    fs::write(
+        temp_dir.path().join("log.txt"),
+        "user=alice id=7\nuser=bob id=12\n",
+    )
+    .unwrap();
+
+    let output = ck_command()
+        .args([
+            "--no-filename",
+            "--replace",
+            "${id}:$1",
+            r"user=(\w+) id=(?P<id>\d+)",
+            "log.txt",
+        ])
+        .current_dir(temp_dir.path())
+        .output()
+        .expect("Failed to run ck");
+    assert!(output.status.success());
+    assert_eq!(String::from_utf8_lossy(&output.stdout), "7:alice\n12:bob\n");
+    // The file itself is left alone
+    assert_eq!(
+        fs::read_to_string(temp_dir.path().join("log.txt")).unwrap(),
+        "user=alice id=7\nuser=bob id=12\n"
+    );
+
+    let output = ck_command()
+        .args(["--sem", "--replace", "$1", "(user)", "."])
+        .current_dir(temp_dir.path())
+        .output()
+        .expect("Failed to run ck");
+    assert_eq!(output.status.code(), Some(2));
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(
+        stderr.contains("--replace only applies to regex search"),
+        "{stderr}"
+    );
+}
+
+#[test]
+#[serial]
+fn test_no_unicode_matches_ascii_and_opaque_bytes() {
+    let temp_dir = TempDir::new().unwrap();
+    fs::write(
+        temp_dir.path().join("app.log"),
+        "ERROR payment failed\nerror at café\ninfo ok\n",
+    )
+    .unwrap();
+    let run = |args: &[&str]| {
+        let output = ck_command()
+            .args(["--no-filename", "--no-unicode"])
+            .args(args)
+            .arg("app.log")
+            .current_dir(temp_dir.path())
+            .output()
+            .expect("Failed to run ck");
+        String::from_utf8_lossy(&output.stdout).to_string()
+    };
+
+    // -i still folds ASCII case
+    assert_eq!(
+        run(&["-i", "^error"]),
+        "ERROR payment failed\nerror at café\n"
+    );
+    // `.` is one byte, so `é` takes two
+    assert_eq!(run(&["caf.$"]), "");
+    assert_eq!(run(&["caf..$"]), "error at café\n");
+}
+
+#[test]
+#[serial]
+fn test_warmup_loads_model_and_reports_time() {
+    let temp_dir = TempDir::new().unwrap();
+    let output = ck_command()
+        .args(["--warmup", "--model", "bge-small"])
+        .current_dir(temp_dir.path())
+        .output()
+        .expect("Failed to run ck");
+    assert!(output.status.success());
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(
+        stderr.contains("Warmed up bge-small (BAAI/bge-small-en-v1.5) in"),
+        "{stderr}"
+    );
+    // Nothing is indexed
+    assert!(!temp_dir.path().join(".ck").exists());
+
+    let output = ck_command()
+        .args(["--warmup", "--model", "no-such-model"])
+        .current_dir(temp_dir.path())
+        .output()
+        .expect("Failed to run ck");
+    assert!(!output.status.success());
+}
+
+#[test]
+#[serial]
+fn test_index_prune_drops_newly_ignored_files() {
+    let temp_dir = TempDir::new().unwrap();
+    fs::write(temp_dir.path().join("kept.txt"), "kept").unwrap();
+    fs::create_dir_all(temp_dir.path().join("vendor")).unwrap();
+    fs::write(temp_dir.path().join("vendor").join("lib.txt"), "vendored").unwrap();
+    let index = |args: &[&str]| {
+        let output = ck_command()
+            .arg("--index")
+            .args(args)
+            .arg(".")
+            .current_dir(temp_dir.path())
+            .output()
+            .expect("Failed to run ck --index");
+        assert!(output.status.success());
+        String::from_utf8_lossy(&output.stderr).to_string()
+    };
+    let manifest =
+        || fs::read_to_string(temp_dir.path().join(".ck").join("manifest.json")).unwrap();
+
+    index(&[]);
+    assert!(manifest().contains("vendor/lib.txt"));
+
+    // A plain update leaves the now-ignored entry behind
+    let mut ckignore = fs::read_to_string(temp_dir.path().join(".ckignore")).unwrap();
+    ckignore.push_str("\nvendor/\n");
+    fs::write(temp_dir.path().join(".ckignore"), ckignore).unwrap();
+    index(&[]);
+    assert!(manifest().contains("vendor/lib.txt"));
+
+    let stderr = index(&["--prune"]);
+    assert!(
+        stderr.contains("1 excluded or deleted entries pruned"),
+        "{stderr}"
+    );
+    assert!(!manifest().contains("vendor/lib.txt"));
+    assert!(manifest().contains("kept.txt"));
+    assert!(
+        !temp_dir
+            .path()
+            .join(".ck")
+            .join("vendor")
+            .join("lib.txt.ck")
+            .exists()
+    );
+}
+
+#[test]
+#[serial]
+fn test_diff_index_reports_drift_without_indexing() {
+    let temp_dir = TempDir::new().unwrap();
+    fs::write(temp_dir.path().join("kept.txt"), "kept").unwrap();
+    fs::write(temp_dir.path().join("changed.txt"), "before").unwrap();
+    fs::write(temp_dir.path().join("gone.txt"), "gone").unwrap();
+
+    let output = ck_command()
+        .args(["--diff-index", "."])
+        .current_dir(temp_dir.path())
+        .output()
+        .expect("Failed to run ck");
+    assert_eq!(output.status.code(), Some(2));
+    assert!(String::from_utf8_lossy(&output.stderr).contains("No index found"));
+
+    let output = ck_command()
+        .args(["--index", "."])
+        .current_dir(temp_dir.path())
+        .output()
+        .expect("Failed to run ck");
+    assert!(output.status.success());
+
+    fs::write(temp_dir.path().join("changed.txt"), "after the edit").unwrap();
+    fs::remove_file(temp_dir.path().join("gone.txt")).unwrap();
+    fs::write(temp_dir.path().join("new.txt"), "new").unwrap();
+    let manifest = temp_dir.path().join(".ck").join("manifest.json");
+    let manifest_before = fs::read(&manifest).unwrap();
+
+    let output = ck_command()
+        .args(["--diff-index", "."])
+        .current_dir(temp_dir.path())
+        .output()
+        .expect("Failed to run ck");
+    assert!(output.status.success());
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(stdout.contains("New (1):\n  new.txt"), "{stdout}");
+    assert!(stdout.contains("Modified (1):\n  changed.txt"), "{stdout}");
+    assert!(stdout.contains("Deleted (1):\n  gone.txt"), "{stdout}");
+    assert!(stdout.contains("1 new, 1 modified, 1 deleted"), "{stdout}");
+
+    let output = ck_command()
+        .args(["--diff-index", "--json", "."])
+        .current_dir(temp_dir.path())
+        .output()
+        .expect("Failed to run ck");
+    let diff: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+    assert_eq!(diff["new"], serde_json::json!(["new.txt"]));
+    assert_eq!(diff["modified"], serde_json::json!(["changed.txt"]));
+    assert_eq!(diff["deleted"], serde_json::json!(["gone.txt"]));
+
+    // Read-only
+    assert_eq!(fs::read(&manifest).unwrap(), manifest_before);
+}
+
+#[test]
+#[serial]
+fn test_embed_batch_size_is_capped_and_reported() {
+    let temp_dir = TempDir::new().unwrap();
+    let body: String = (0..60).map(|i| format!("fn item_{i}() {{}}\n\n")).collect();
+    fs::write(temp_dir.path().join("lib.rs"), body).unwrap();
+
+    let output = ck_command()
+        .args(["--index", "--verbose", "--embed-batch-size", "1000", "."])
+        .current_dir(temp_dir.path())
+        .output()
+        .expect("Failed to run ck");
+    assert!(output.status.success());
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(
+        stderr.contains("Embed Batch: 256 chunks per call (1000 requested;"),
+        "{stderr}"
+    );
+
+    // Batching doesn't change what's found
+    let output = ck_command()
+        .args([
+            "--sem",
+            "--threshold",
+            "0",
+            "--topk",
+            "100",
+            "--jsonl",
+            "item",
+            ".",
+        ])
+        .current_dir(temp_dir.path())
+        .output()
+        .expect("Failed to run ck");
+    assert!(output.status.success());
+    assert!(String::from_utf8_lossy(&output.stdout).lines().count() > 1);
+
+    let output = ck_command()
+        .args(["--index", "--embed-batch-size", "0", "."])
+        .current_dir(temp_dir.path())
+        .output()
+        .expect("Failed to run ck");
+    assert!(!output.status.success());
+}
+
+#[test]
+#[serial]
+fn test_json_output_carries_highlight_offsets() {
+    let temp_dir = TempDir::new().unwrap();
+    fs::write(
+        temp_dir.path().join("lib.rs"),
+        "fn retry_with_backoff() {\n    sleep(backoff);\n}\n",
+    )
+    .unwrap();
+
+    let highlighted = |record: &serde_json::Value, text_field: &str| -> Vec<String> {
+        let text = record[text_field].as_str().unwrap();
+        record["highlights"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|range| {
+                let (start, end) = (range[0].as_u64().unwrap(), range[1].as_u64().unwrap());
+                text[start as usize..end as usize].to_string()
+            })
+            .collect()
+    };
+
+    // Semantic results highlight the query terms that appear literally
+    let output = ck_command()
+        .args(["--sem", "--threshold", "0", "--jsonl", "backoff", "."])
+        .current_dir(temp_dir.path())
+        .output()
+        .expect("Failed to run ck");
+    assert!(output.status.success());
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    let record: serde_json::Value = serde_json::from_str(stdout.lines().next().unwrap()).unwrap();
+    assert_eq!(highlighted(&record, "snippet"), ["backoff", "backoff"]);
+
+    let output = ck_command()
+        .args(["--json", "-i", "RETRY_\\w+", "lib.rs"])
+        .current_dir(temp_dir.path())
+        .output()
+        .expect("Failed to run ck");
+    assert!(output.status.success());
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    let record: serde_json::Value = serde_json::from_str(stdout.lines().next().unwrap()).unwrap();
+    assert_eq!(highlighted(&record, "preview"), ["retry_with_backoff"]);
+
+    // Without a snippet there is nothing to point into
+    let output = ck_command()
+        .args(["--jsonl", "--no-snippet", "retry", "lib.rs"])
+        .current_dir(temp_dir.path())
+        .output()
+        .expect("Failed to run ck");
+    assert!(!String::from_utf8_lossy(&output.stdout).contains("highlights"));
+}
+
+#[test]
+#[serial]
+fn test_gc_compacts_the_index_and_reports_reclaimed_space() {
+    let temp_dir = TempDir::new().unwrap();
+    fs::write(
+        temp_dir.path().join("kept.rs"),
+        "fn retry_with_backoff() {}\n",
+    )
+    .unwrap();
+    fs::write(temp_dir.path().join("gone.rs"), "fn gone() {}\n").unwrap();
+
+    let output = ck_command()
+        .args(["--index", "."])
+        .current_dir(temp_dir.path())
+        .output()
+        .expect("Failed to run ck");
+    assert!(output.status.success());
+    fs::remove_file(temp_dir.path().join("gone.rs")).unwrap();
+
+    let output = ck_command()
+        .args(["--gc", "--json", "."])
+        .current_dir(temp_dir.path())
+        .output()
+        .expect("Failed to run ck");
+    assert!(output.status.success());
+    let stats: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+    assert_eq!(stats["orphaned_entries_removed"], 1);
+    assert!(stats["bytes_after"].as_u64() < stats["bytes_before"].as_u64());
+
+    // The compacted index still answers searches
+    let output = ck_command()
+        .args(["--sem", "--threshold", "0", "-l", "retry", "."])
+        .current_dir(temp_dir.path())
+        .output()
+        .expect("Failed to run ck");
+    assert!(output.status.success());
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert_eq!(stdout.lines().count(), 1, "{stdout}");
+    assert!(stdout.trim().ends_with("kept.rs"), "{stdout}");
+
+    let output = ck_command()
+        .args(["--gc", "."])
+        .current_dir(TempDir::new().unwrap().path())
+        .output()
+        .expect("Failed to run ck");
+    assert_eq!(output.status.code(), Some(2));
+    assert!(String::from_utf8_lossy(&output.stderr).contains("No index found"));
+}
+
+#[test]
+#[serial]
+fn test_migrate_upgrades_an_index_built_by_an_older_ck() {
+    let temp_dir = TempDir::new().unwrap();
+    fs::write(
+        temp_dir.path().join("kept.rs"),
+        "fn retry_with_backoff() {}\n",
+    )
+    .unwrap();
+    let output = ck_command()
+        .args(["--index", "."])
+        .current_dir(temp_dir.path())
+        .output()
+        .expect("Failed to run ck");
+    assert!(output.status.success());
+
+    // Older releases wrote sidecars without the 5-byte format header and
+    // manifests without a format version
+    let sidecar = temp_dir.path().join(".ck").join("kept.rs.ck");
+    let data = fs::read(&sidecar).unwrap();
+    assert!(data.starts_with(b"CKSC"));
+    fs::write(&sidecar, &data[5..]).unwrap();
+    let manifest_path = temp_dir.path().join(".ck").join("manifest.json");
+    let mut manifest: serde_json::Value =
+        serde_json::from_slice(&fs::read(&manifest_path).unwrap()).unwrap();
+    manifest.as_object_mut().unwrap().remove("format_version");
+    fs::write(&manifest_path, serde_json::to_vec(&manifest).unwrap()).unwrap();
+
+    let output = ck_command()
+        .args(["--migrate", "."])
+        .current_dir(temp_dir.path())
+        .output()
+        .expect("Failed to run ck");
+    assert!(output.status.success());
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(stderr.contains("Migrated 1 sidecars"), "{stderr}");
+    assert!(fs::read(&sidecar).unwrap().starts_with(b"CKSC"));
+
+    let output = ck_command()
+        .args(["--sem", "--threshold", "0", "-l", "retry", "."])
+        .current_dir(temp_dir.path())
+        .output()
+        .expect("Failed to run ck");
+    assert!(output.status.success());
+    assert!(String::from_utf8_lossy(&output.stdout).contains("kept.rs"));
+
+    // A manifest this ck can't decode asks for a rebuild instead of a bare
+    // deserialization error
+    fs::write(&manifest_path, b"{\"files\": 3}").unwrap();
+    let output = ck_command()
+        .args(["--migrate", "."])
+        .current_dir(temp_dir.path())
+        .output()
+        .expect("Failed to run ck");
+    assert_eq!(output.status.code(), Some(2));
+    assert!(String::from_utf8_lossy(&output.stderr).contains("ck --reindex"));
+}
+
+#[test]
+#[serial]
+fn test_full_section_marks_the_matching_line() {
+    let temp_dir = TempDir::new().unwrap();
+    fs::write(
+        temp_dir.path().join("lib.rs"),
+        "fn connect() {\n    let attempts = 3;\n    retry_with_backoff(attempts);\n    done();\n}\n",
+    )
+    .unwrap();
+
+    let output = ck_command()
+        .args(["--sem", "--threshold", "0", "--jsonl", "retry backoff", "."])
+        .current_dir(temp_dir.path())
+        .output()
+        .expect("Failed to run ck");
+    assert!(output.status.success());
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    let record: serde_json::Value = serde_json::from_str(stdout.lines().next().unwrap()).unwrap();
+    assert_eq!(record["match_line"], 3, "{stdout}");
+
+    let output = ck_command()
+        .args(["--full-section", "-n", "retry_with_backoff", "lib.rs"])
+        .current_dir(temp_dir.path())
+        .output()
+        .expect("Failed to run ck");
+    assert!(output.status.success());
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    let lines: Vec<&str> = stdout.lines().collect();
+    // The line number is where the section starts; the gutter points at the match
+    assert_eq!(lines[0], "1:  fn connect() {", "{stdout}");
+    assert_eq!(lines[2], ">     retry_with_backoff(attempts);", "{stdout}");
+    assert_eq!(lines.iter().filter(|line| line.starts_with('>')).count(), 1);
+
+    // Without --full-section previews are unmarked
+    let output = ck_command()
+        .args(["-n", "retry_with_backoff", "lib.rs"])
+        .current_dir(temp_dir.path())
+        .output()
+        .expect("Failed to run ck");
+    assert_eq!(
+        String::from_utf8_lossy(&output.stdout).trim_end(),
+        "3:    retry_with_backoff(attempts);"
+    );
+}
+
+#[test]
+#[serial]
+fn test_stats_prints_stage_timings() {
+    let temp_dir = TempDir::new().unwrap();
+    fs::write(
+        temp_dir.path().join("lib.rs"),
+        "fn retry_with_backoff() {}\n",
+    )
+    .unwrap();
+
+    let stage_value = |stderr: &str, stage: &str| {
+        stderr
+            .lines()
+            .find(|line| line.trim_start().starts_with(stage))
+            .map(|line| line.trim_start()[stage.len()..].trim().to_string())
+            .unwrap_or_else(|| panic!("no {stage} row in {stderr}"))
+    };
+
+    let output = ck_command()
+        .args(["--sem", "--threshold", "0", "--stats", "retry", "."])
+        .current_dir(temp_dir.path())
+        .output()
+        .expect("Failed to run ck");
+    assert!(output.status.success());
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(stderr.contains("Search timings:"), "{stderr}");
+    for stage in ["index update", "query embed", "candidate load", "scoring"] {
+        assert!(
+            stage_value(&stderr, stage).ends_with("ms"),
+            "{stage}: {stderr}"
+        );
+    }
+    assert_eq!(stage_value(&stderr, "rerank"), "-");
+    // The table stays off stdout, which holds only results
+    assert!(!String::from_utf8_lossy(&output.stdout).contains("Search timings"));
+
+    // Regex never touches the index
+    let output = ck_command()
+        .args(["--stats", "retry", "."])
+        .current_dir(temp_dir.path())
+        .output()
+        .expect("Failed to run ck");
+    assert!(output.status.success());
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert_eq!(stage_value(&stderr, "index update"), "-");
+    assert!(stage_value(&stderr, "scoring").ends_with("ms"), "{stderr}");
+    assert!(
+        stage_value(&stderr, "formatting").ends_with("ms"),
+        "{stderr}"
+    );
+    assert!(stage_value(&stderr, "total").ends_with("ms"), "{stderr}");
+}
+
+#[test]
+#[serial]
+fn test_tied_scores_order_is_repeatable() {
+    let temp_dir = TempDir::new().unwrap();
+    // Identical files score identically in every mode
+    for name in ["d.txt", "b.txt", "a.txt", "c.txt", "e.txt"] {
+        fs::write(
+            temp_dir.path().join(name),
+            "retry the request with backoff\n",
+        )
+        .unwrap();
+    }
+
+    let run = |args: &[&str]| {
+        let output = ck_command()
+            .args(["--format", "grep", "--path-style", "relative"])
+            .args(args)
+            .current_dir(temp_dir.path())
+            .output()
+            .expect("Failed to run ck");
+        assert!(
+            output.status.success(),
+            "stderr: {}",
+            String::from_utf8_lossy(&output.stderr)
+        );
+        output.stdout
+    };
+
+    let expected = ["a.txt", "b.txt", "c.txt", "d.txt", "e.txt"];
+    for args in [
+        &["--sem", "--threshold", "0", "backoff", "."][..],
+        &["--lex", "backoff", "."][..],
+        &["--hybrid", "backoff", "."][..],
+        &["backoff", "."][..],
+    ] {
+        let first = run(args);
+        assert_eq!(first, run(args), "{args:?}");
+        let files: Vec<String> = String::from_utf8_lossy(&first)
+            .lines()
+            .map(|line| line.split(':').next().unwrap().to_string())
+            .collect();
+        assert_eq!(files, expected, "{args:?}");
+    }
+}
+
+#[test]
+#[serial]
+fn test_preview_width_truncates_around_the_match() {
+    let temp_dir = TempDir::new().unwrap();
+    let long_line = format!("{}needle{}", "x".repeat(100), "y".repeat(100));
+    fs::write(
+        temp_dir.path().join("a.txt"),
+        format!("{long_line}\n{}needle\nshort needle\n", "日本語".repeat(30)),
+    )
+    .unwrap();
+
+    let run = |args: &[&str]| {
+        let output = ck_command()
+            .args(args)
+            .current_dir(temp_dir.path())
+            .output()
+            .expect("Failed to run ck");
+        assert!(output.status.success());
+        String::from_utf8_lossy(&output.stdout).to_string()
+    };
+
+    // Piped output isn't cut unless asked
+    let full = run(&["-n", "needle", "a.txt"]);
+    assert!(full.contains(&long_line));
+
+    let cut = run(&["-n", "--preview-width", "30", "needle", "a.txt"]);
+    let lines: Vec<&str> = cut.lines().collect();
+    assert_eq!(lines.len(), 3);
+    assert!(lines[0].starts_with("1:…x"), "{cut}");
+    assert!(
+        lines[0].contains("needle") && lines[0].ends_with('…'),
+        "{cut}"
+    );
+    assert_eq!(lines[0].chars().count(), "1:".len() + 30);
+    // CJK characters count two columns each
+    assert!(lines[1].starts_with("2:…") && lines[1].ends_with("needle"));
+    assert!(lines[1].chars().count() <= "2:".len() + 1 + 12 + 6);
+    assert_eq!(lines[2], "3:short needle");
+}
+
+#[test]
+#[serial]
+fn test_files_from_searches_exactly_the_listed_files() {
+    use std::io::Write;
+    use std::process::Stdio;
+
+    let temp_dir = TempDir::new().unwrap();
+    fs::write(temp_dir.path().join(".gitignore"), "ignored.rs\n").unwrap();
+    fs::create_dir(temp_dir.path().join(".git")).unwrap();
+    fs::write(temp_dir.path().join("a.rs"), "fn retry_backoff() {}\n").unwrap();
+    fs::write(temp_dir.path().join("b.rs"), "fn retry_other() {}\n").unwrap();
+    fs::write(
+        temp_dir.path().join("ignored.rs"),
+        "fn retry_ignored() {}\n",
+    )
+    .unwrap();
+
+    let run = |args: &[&str], list: &[u8]| {
+        let mut child = ck_command()
+            .args(args)
+            .current_dir(temp_dir.path())
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .spawn()
+            .expect("Failed to spawn ck");
+        child.stdin.take().unwrap().write_all(list).unwrap();
+        let output = child.wait_with_output().unwrap();
+        (
+            output.status.success(),
+            String::from_utf8_lossy(&output.stdout).to_string(),
+            String::from_utf8_lossy(&output.stderr).to_string(),
+        )
+    };
+    let files = |stdout: &str| {
+        let mut files: Vec<String> = stdout
+            .lines()
+            .map(|line| line.split(':').next().unwrap().to_string())
+            .collect();
+        files.dedup();
+        files
+    };
+
+    // Semantic first: a lexical search would index without embeddings
+    let (ok, stdout, stderr) = run(
+        &[
+            "--files-from",
+            "-",
+            "--sem",
+            "--threshold",
+            "0",
+            "--format",
+            "grep",
+            "--path-style",
+            "relative",
+            "retry",
+        ],
+        b"a.rs\nignored.rs\n",
+    );
+    assert!(ok, "{stderr}");
+    assert_eq!(files(&stdout), ["a.rs", "ignored.rs"]);
+
+    // The gitignored file is searched because it is listed; the deleted
+    // one is skipped with a warning
+    let (ok, stdout, stderr) = run(
+        &["--files-from", "-", "-n", "retry"],
+        b"a.rs\nignored.rs\ngone.rs\n",
+    );
+    assert!(ok, "{stderr}");
+    assert_eq!(files(&stdout), ["a.rs", "ignored.rs"]);
+    assert!(stderr.contains("gone.rs"), "{stderr}");
+
+    // NUL-separated lists, as from git ls-files -z
+    let (_, stdout, _) = run(&["--files-from", "-", "-n", "retry"], b"b.rs\0a.rs\0");
+    assert_eq!(files(&stdout), ["a.rs", "b.rs"]);
+
+    let (ok, stdout, stderr) = run(
+        &[
+            "--files-from",
+            "-",
+            "--lex",
+            "--format",
+            "grep",
+            "--path-style",
+            "relative",
+            "retry_ignored",
+        ],
+        b"ignored.rs\n",
+    );
+    assert!(ok, "{stderr}");
+    assert_eq!(files(&stdout), ["ignored.rs"]);
+
+    let (ok, _, stderr) = run(&["--files-from", "-", "retry"], b"gone.rs\n");
+    assert!(!ok);
+    assert!(stderr.contains("lists no files to search"), "{stderr}");
+}
+
+#[test]
+#[serial]
+fn test_files_from_line_ranges_restrict_results() {
+    use std::io::Write;
+    use std::process::Stdio;
+
+    let temp_dir = TempDir::new().unwrap();
+    let body: String = (1..=40)
+        .map(|i| format!("fn retry_step_{i}() {{}}\n"))
+        .collect();
+    fs::write(temp_dir.path().join("a.rs"), &body).unwrap();
+    fs::write(temp_dir.path().join("b.rs"), "fn retry_other() {}\n").unwrap();
+
+    let run = |args: &[&str], list: &[u8]| {
+        let mut child = ck_command()
+            .args(args)
+            .current_dir(temp_dir.path())
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .spawn()
+            .expect("Failed to spawn ck");
+        child.stdin.take().unwrap().write_all(list).unwrap();
+        let output = child.wait_with_output().unwrap();
+        (
+            output.status.code(),
+            String::from_utf8_lossy(&output.stdout).to_string(),
+            String::from_utf8_lossy(&output.stderr).to_string(),
+        )
+    };
+    let lines = |stdout: &str| -> Vec<String> {
+        stdout
+            .lines()
+            .map(|line| line.splitn(3, ':').take(2).collect::<Vec<_>>().join(":"))
+            .collect()
+    };
+
+    let (code, stdout, stderr) = run(
+        &["--files-from", "-", "-n", "-H", "retry"],
+        b"a.rs:10-12\na.rs:30\nb.rs\n",
+    );
+    assert_eq!(code, Some(0), "{stderr}");
+    assert_eq!(
+        lines(&stdout),
+        ["a.rs:10", "a.rs:11", "a.rs:12", "a.rs:30", "b.rs:1"]
+    );
+
+    // A file also listed without a range is searched whole
+    let (_, stdout, _) = run(&["--files-from", "-", "-n", "retry"], b"a.rs:10-12\na.rs\n");
+    assert_eq!(stdout.lines().count(), 40);
+
+    // Semantic results are chunks overlapping the ranges
+    let (code, stdout, stderr) = run(
+        &[
+            "--files-from",
+            "-",
+            "--sem",
+            "--threshold",
+            "0",
+            "--json",
+            "retry",
+        ],
+        b"a.rs:38-40\n",
+    );
+    assert_eq!(code, Some(0), "{stderr}");
+    let results: Vec<serde_json::Value> = stdout
+        .lines()
+        .map(|line| serde_json::from_str(line).unwrap())
+        .collect();
+    assert!(!results.is_empty());
+    for result in &results {
+        assert!(
+            result["span"]["line_end"].as_u64().unwrap() >= 38,
+            "{result}"
+        );
+    }
+
+    let (code, _, stderr) = run(&["--files-from", "-", "retry"], b"a.rs:12-3\n");
+    assert_eq!(code, Some(2));
+    assert!(stderr.contains("Malformed line range"), "{stderr}");
+}
+
+#[test]
+#[serial]
+fn test_exit_codes_follow_grep() {
+    let temp_dir = TempDir::new().unwrap();
+    fs::write(temp_dir.path().join("a.txt"), "alpha\nbeta\n").unwrap();
+
+    let run = |args: &[&str]| {
+        let output = ck_command()
+            .args(args)
+            .current_dir(temp_dir.path())
+            .output()
+            .expect("Failed to run ck");
+        (
+            output.status.code(),
+            String::from_utf8_lossy(&output.stdout).to_string(),
+            String::from_utf8_lossy(&output.stderr).to_string(),
+        )
+    };
+
+    assert_eq!(run(&["alpha", "."]).0, Some(0));
+    assert_eq!(run(&["gamma", "."]).0, Some(1));
+    // Errors: a bad pattern, a missing path, a missing index, bad arguments
+    assert_eq!(run(&["(unclosed", "."]).0, Some(2));
+    assert_eq!(run(&["alpha", "missing-dir"]).0, Some(2));
+    assert_eq!(run(&["--lex", "--no-auto-index", "alpha", "."]).0, Some(2));
+    assert_eq!(run(&["-l", "-L", "alpha", "."]).0, Some(2));
+    assert_eq!(run(&["--topk", "many", "alpha", "."]).0, Some(2));
+
+    // -q prints nothing and only sets the status
+    assert_eq!(
+        run(&["-q", "alpha", "."]),
+        (Some(0), String::new(), String::new())
+    );
+    assert_eq!(
+        run(&["-q", "gamma", "."]),
+        (Some(1), String::new(), String::new())
+    );
+    assert_eq!(
+        run(&["-q", "-L", "alpha", "."]),
+        (Some(1), String::new(), String::new())
+    );
+    let (code, stdout, stderr) = run(&["-q", "(unclosed", "."]);
+    assert_eq!((code, stdout.as_str()), (Some(2), ""));
+    assert!(!stderr.is_empty());
+}
+
+/// A file the walk can't read is skipped and reported, not fatal to the
+/// index or search of everything else.
+#[cfg(unix)]
+#[test]
+#[serial]
+fn test_unreadable_files_are_skipped_and_reported() {
+    use std::os::unix::fs::PermissionsExt;
+
+    let temp_dir = TempDir::new().unwrap();
+    fs::write(temp_dir.path().join("readable.txt"), "needle here\n").unwrap();
+    let secret = temp_dir.path().join("secret.txt");
+    fs::write(&secret, "needle hidden\n").unwrap();
+    fs::set_permissions(&secret, fs::Permissions::from_mode(0o000)).unwrap();
+    if fs::read(&secret).is_ok() {
+        // Running as root: permissions don't stop reads
+        return;
+    }
+
+    let output = ck_command()
+        .args(["--index", "--verbose", "."])
+        .current_dir(temp_dir.path())
+        .output()
+        .expect("Failed to run ck --index");
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(output.status.success(), "index failed: {stderr}");
+    assert!(stderr.contains("1 entries skipped"), "{stderr}");
+    assert!(stderr.contains("secret.txt"), "{stderr}");
+
+    let output = ck_command()
+        .args(["needle", "."])
+        .current_dir(temp_dir.path())
+        .output()
+        .expect("Failed to run ck");
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(output.status.success(), "search failed: {stderr}");
+    assert!(stdout.contains("readable.txt"), "{stdout}");
+    assert!(stderr.contains("1 entries skipped"), "{stderr}");
+
+    fs::set_permissions(&secret, fs::Permissions::from_mode(0o644)).unwrap();
+}
+
+/// `--follow` descends into symlinked directories, but a link back to an
+/// ancestor is skipped: the search over a symlink cycle terminates, and a
+/// file reached through several links is reported once.
+#[cfg(unix)]
+#[test]
+#[serial]
+fn test_follow_terminates_on_symlink_cycles() {
+    use std::os::unix::fs::symlink;
+    use std::process::Stdio;
+    use std::time::{Duration, Instant};
+
+    let temp_dir = TempDir::new().unwrap();
+    let deep = temp_dir.path().join("real").join("deep");
+    fs::create_dir_all(&deep).unwrap();
+    fs::write(deep.join("a.txt"), "needle\n").unwrap();
+    symlink("..", deep.join("up")).unwrap();
+    symlink(temp_dir.path(), temp_dir.path().join("real").join("self")).unwrap();
+
+    let run = |args: &[&str]| {
+        let mut child = ck_command()
+            .args(args)
+            .current_dir(temp_dir.path())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .spawn()
+            .expect("Failed to spawn ck");
+        let started = Instant::now();
+        while child.try_wait().unwrap().is_none() {
+            if started.elapsed() > Duration::from_secs(60) {
+                child.kill().unwrap();
+                panic!("ck {args:?} did not terminate on a symlink cycle");
+            }
+            std::thread::sleep(Duration::from_millis(50));
+        }
+        child.wait_with_output().unwrap()
+    };
+
+    let output = run(&["--follow", "-l", "needle", "."]);
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(output.status.success(), "{output:?}");
+    assert_eq!(stdout.lines().count(), 1, "{stdout}");
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(stderr.contains("entries skipped"), "{stderr}");
+
+    // Without --follow the links aren't walked at all
+    let output = run(&["-l", "needle", "."]);
+    assert!(output.status.success(), "{output:?}");
+    assert_eq!(String::from_utf8_lossy(&output.stdout).lines().count(), 1);
+    assert!(output.stderr.is_empty(), "{output:?}");
+
+    let output = run(&["--index", "--follow", "."]);
+    assert!(output.status.success(), "{output:?}");
+}
+
+#[test]
+fn test_json_context_fields() {
+    let temp_dir = TempDir::new().unwrap();
+    fs::write(temp_dir.path().join("a.txt"), "one\ntwo\nneedle\nfour\n").unwrap();
+
+    let output = ck_command()
+        .args(["-C", "1", "--json", "needle", "."])
+        .current_dir(temp_dir.path())
+        .output()
+        .expect("Failed to run ck");
+    assert!(output.status.success());
+    let record: serde_json::Value =
+        serde_json::from_slice(output.stdout.trim_ascii()).expect("one JSON record");
+    assert_eq!(record["context_before"], serde_json::json!(["two"]));
+    assert_eq!(record["context_after"], serde_json::json!(["four"]));
+
+    let output = ck_command()
+        .args(["--jsonl", "needle", "."])
+        .current_dir(temp_dir.path())
+        .output()
+        .expect("Failed to run ck");
+    let record: serde_json::Value =
+        serde_json::from_slice(output.stdout.trim_ascii()).expect("one JSONL record");
+    assert!(record.get("context_before").is_none(), "{record}");
+    assert!(record.get("context_after").is_none(), "{record}");
+}
+
+#[test]
+#[serial]
+fn test_index_skips_submodules_unless_requested() {
+    let temp_dir = TempDir::new().unwrap();
+    let submodule = temp_dir.path().join("vendor").join("lib");
+    fs::create_dir_all(&submodule).unwrap();
+    fs::write(submodule.join(".git"), "gitdir: ../../.git/modules/lib\n").unwrap();
+    fs::write(submodule.join("lib.txt"), "vendored").unwrap();
+    fs::write(temp_dir.path().join("main.txt"), "main").unwrap();
+    let index = |args: &[&str]| {
+        let output = ck_command()
+            .arg("--index")
+            .args(args)
+            .arg(".")
+            .current_dir(temp_dir.path())
+            .output()
+            .expect("Failed to run ck --index");
+        assert!(output.status.success());
+        fs::read_to_string(temp_dir.path().join(".ck").join("manifest.json")).unwrap()
+    };
+
+    let manifest = index(&[]);
+    assert!(manifest.contains("main.txt"));
+    assert!(!manifest.contains("lib.txt"));
+
+    // Included files are keyed by their path in the top repository
+    let manifest = index(&["--index-submodules"]);
+    assert!(manifest.contains("vendor/lib/lib.txt"), "{manifest}");
+}
+
+#[test]
+fn test_output_writes_results_to_a_file() {
+    let temp_dir = TempDir::new().unwrap();
+    fs::write(temp_dir.path().join("a.txt"), "needle one\nhay\n").unwrap();
+    fs::write(temp_dir.path().join("b.txt"), "hay\nneedle two\n").unwrap();
+    // Whatever was in the file before is replaced, not appended to
+    fs::write(temp_dir.path().join("out.jsonl"), "stale\n".repeat(100)).unwrap();
+
+    let output = ck_command()
+        .args(["--jsonl", "-o", "out.jsonl", "needle", "."])
+        .current_dir(temp_dir.path())
+        .output()
+        .expect("Failed to run ck");
+    assert!(output.status.success());
+    assert!(output.stdout.is_empty());
+
+    let written = fs::read_to_string(temp_dir.path().join("out.jsonl")).unwrap();
+    let records: Vec<serde_json::Value> = written
+        .lines()
+        .map(|line| serde_json::from_str(line).expect("valid JSONL line"))
+        .collect();
+    assert_eq!(records.len(), 2, "{written}");
+
+    // No matches still leaves an empty file behind
+    let output = ck_command()
+        .args(["--output", "out.jsonl", "absent", "."])
+        .current_dir(temp_dir.path())
+        .output()
+        .expect("Failed to run ck");
+    assert_eq!(output.status.code(), Some(1));
+    assert_eq!(
+        fs::read_to_string(temp_dir.path().join("out.jsonl")).unwrap(),
+        ""
+    );
+    // and no temporary files
+    assert_eq!(fs::read_dir(temp_dir.path()).unwrap().count(), 3);
+}
+
+#[cfg(unix)]
+#[test]
+fn test_output_file_gets_regular_permissions() {
+    use std::os::unix::fs::PermissionsExt;
+
+    let temp_dir = TempDir::new().unwrap();
+    fs::write(temp_dir.path().join("a.txt"), "needle\n").unwrap();
+    let mode = |name: &str| {
+        fs::metadata(temp_dir.path().join(name))
+            .unwrap()
+            .permissions()
+            .mode()
+            & 0o777
+    };
+    let search_into = |name: &str| {
+        let output = ck_command()
+            .args(["-o", name, "needle", "a.txt"])
+            .current_dir(temp_dir.path())
+            .output()
+            .expect("Failed to run ck");
+        assert!(output.status.success());
+    };
+
+    // A new file is created the way any other would be, under the umask
+    search_into("new.txt");
+    assert_eq!(mode("new.txt"), mode("a.txt"));
+
+    // A replaced file keeps its mode
+    fs::write(temp_dir.path().join("old.txt"), "stale\n").unwrap();
+    fs::set_permissions(
+        temp_dir.path().join("old.txt"),
+        fs::Permissions::from_mode(0o640),
+    )
+    .unwrap();
+    search_into("old.txt");
+    assert_eq!(mode("old.txt"), 0o640);
+}
+
+#[test]
+#[serial]
+fn test_no_generated_leaves_out_generated_files() {
+    let temp_dir = TempDir::new().unwrap();
+    fs::write(temp_dir.path().join("handler.go"), "func needle() {}\n").unwrap();
+    fs::write(temp_dir.path().join("handler.pb.go"), "func needle() {}\n").unwrap();
+    fs::write(
+        temp_dir.path().join("mock.go"),
+        "// Code generated by mockgen. DO NOT EDIT.\nfunc needle() {}\n",
+    )
+    .unwrap();
+    fs::write(
+        temp_dir.path().join("schema.go"),
+        "// Built by schemagen\nfunc needle() {}\n",
+    )
+    .unwrap();
+    fs::create_dir(temp_dir.path().join(".ck")).unwrap();
+    fs::write(
+        temp_dir.path().join(".ck").join("config.toml"),
+        "generated_markers = [\"Built by schemagen\"]\n",
+    )
+    .unwrap();
+
+    let files = |args: &[&str]| {
+        let output = ck_command()
+            .args(["-l", "--no-generated"])
+            .args(args)
+            .args(["needle", "."])
+            .current_dir(temp_dir.path())
+            .output()
+            .expect("Failed to run ck");
+        assert!(output.status.success(), "{output:?}");
+        String::from_utf8(output.stdout).unwrap()
+    };
+    for mode in [&[][..], &["--lex"]] {
+        let listed = files(mode);
+        assert!(listed.contains("handler.go"), "{listed}");
+        for generated in ["handler.pb.go", "mock.go", "schema.go"] {
+            assert!(!listed.contains(generated), "{generated} in {listed}");
+        }
+    }
+}
+
+#[test]
+fn test_jobs_does_not_change_results() {
+    let temp_dir = TempDir::new().unwrap();
+    for dir in 0..8 {
+        let dir_path = temp_dir.path().join(format!("pkg{dir}"));
+        fs::create_dir(&dir_path).unwrap();
+        for file in 0..25 {
+            let body: String = (0..20)
+                .map(|line| {
+                    if (dir + file + line) % 7 == 0 {
+                        format!("let needle_{line} = {file};\n")
+                    } else {
+                        format!("let hay_{line} = {file};\n")
+                    }
+                })
+                .collect();
+            fs::write(dir_path.join(format!("mod{file}.rs")), body).unwrap();
+        }
+    }
+
+    let search = |jobs: &str, args: &[&str]| {
+        let output = ck_command()
+            .args(["--jobs", jobs])
+            .args(args)
+            .args(["needle", "."])
+            .current_dir(temp_dir.path())
+            .output()
+            .expect("Failed to run ck");
+        assert!(output.status.success(), "{output:?}");
+        String::from_utf8(output.stdout).unwrap()
+    };
+    for args in [
+        &["-n"][..],
+        &["-l"],
+        &["-m", "5"],
+        &["-n", "--max-matches-per-file", "1"],
+        &["--lex", "-l"],
+    ] {
+        let serial = search("1", args);
+        assert!(!serial.is_empty(), "{args:?}");
+        assert_eq!(serial, search("4", args), "{args:?}");
+    }
+}
+
+#[test]
+fn test_models_lists_registry_models_and_their_cache_state() {
+    let cache_home = TempDir::new().unwrap();
+    let cached = cache_home
+        .path()
+        .join("ck")
+        .join("models")
+        .join("models--Xenova--bge-small-en-v1.5");
+    fs::create_dir_all(&cached).unwrap();
+    let project = TempDir::new().unwrap();
+    fs::create_dir(project.path().join(".ck")).unwrap();
+    fs::write(
+        project.path().join(".ck").join("manifest.json"),
+        r#"{"version":"0.1.0","created":0,"updated":0,"files":{},"embedding_model":"nomic-embed-text-v1.5","embedding_dimensions":768}"#,
+    )
+    .unwrap();
+
+    let output = ck_command()
+        .args(["--models", "--json"])
+        .env("XDG_CACHE_HOME", cache_home.path())
+        .current_dir(project.path())
+        .output()
+        .expect("Failed to run ck");
+    assert!(output.status.success(), "{output:?}");
+    let models: Vec<serde_json::Value> = serde_json::from_slice(&output.stdout).unwrap();
+    let model = |alias: &str| {
+        models
+            .iter()
+            .find(|m| m["alias"] == alias)
+            .unwrap_or_else(|| panic!("{alias} not listed"))
+            .clone()
+    };
+
+    let small = model("bge-small");
+    assert_eq!(small["dimensions"], 384);
+    assert_eq!(small["max_tokens"], 512);
+    assert_eq!(small["chunk_tokens"], 400);
+    assert_eq!(small["default"], true);
+    assert_eq!(small["cached"], true);
+    assert_eq!(small["cache_path"], cached.to_str().unwrap());
+
+    let nomic = model("nomic-v1.5");
+    assert_eq!(nomic["dimensions"], 768);
+    assert_eq!(nomic["pinned"], true);
+    assert_eq!(nomic["cached"], false);
+    assert!(nomic["cache_path"].is_null());
+}
+
+#[test]
+fn test_on_chunks_reports_the_chunk_and_its_symbol() {
+    let temp_dir = TempDir::new().unwrap();
+    fs::write(
+        temp_dir.path().join("config.rs"),
+        "fn load_config() -> String {\n    let path = \"config.toml\";\n    std::fs::read_to_string(path).unwrap()\n}\n\nfn save_config(config: &str) {\n    std::fs::write(\"config.toml\", config).unwrap();\n}\n",
+    )
+    .unwrap();
+
+    let run = |args: &[&str]| {
+        let output = ck_command()
+            .args(args)
+            .current_dir(temp_dir.path())
+            .output()
+            .expect("Failed to run ck");
+        (
+            output.status.success(),
+            String::from_utf8_lossy(&output.stdout).to_string(),
+            String::from_utf8_lossy(&output.stderr).to_string(),
+        )
+    };
+    let results = |stdout: &str| -> Vec<serde_json::Value> {
+        stdout
+            .lines()
+            .map(|line| serde_json::from_str(line).unwrap())
+            .collect()
+    };
+
+    // Without an index there are no chunks to match
+    let (ok, _, stderr) = run(&["--on", "chunks", "--no-auto-index", "config", "."]);
+    assert!(!ok);
+    assert!(stderr.contains("No index found"), "{stderr}");
+
+    // One result per chunk, spanning it, however many lines match inside
+    let (ok, stdout, stderr) = run(&["--on", "chunks", "--json", "config\\.toml", "."]);
+    assert!(ok, "{stderr}");
+    let found = results(&stdout);
+    let chunks: Vec<(&str, u64, u64)> = found
+        .iter()
+        .map(|result| {
+            (
+                result["symbol"].as_str().unwrap(),
+                result["span"]["line_start"].as_u64().unwrap(),
+                result["span"]["line_end"].as_u64().unwrap(),
+            )
+        })
+        .collect();
+    assert_eq!(
+        chunks,
+        [
+            ("fn load_config() -> String", 1, 4),
+            ("fn save_config(config: &str)", 6, 8)
+        ]
+    );
+    assert!(found[0]["preview"].as_str().unwrap().contains("let path"));
+
+    // Lines stay the default
+    let (ok, stdout, _) = run(&["--json", "config\\.toml", "."]);
+    assert!(ok);
+    assert!(
+        results(&stdout)
+            .iter()
+            .all(|result| result["symbol"].is_null())
+    );
+
+    // Lexical search ranks the chunks rather than the file
+    let (ok, stdout, stderr) = run(&["--lex", "--on", "chunks", "--json", "write", "."]);
+    assert!(ok, "{stderr}");
+    let found = results(&stdout);
+    assert_eq!(found.len(), 1, "{stdout}");
+    assert_eq!(found[0]["symbol"], "fn save_config(config: &str)");
+    assert_eq!(found[0]["span"]["line_start"], 6);
+
+    let (ok, _, _) = run(&["--sem", "--on", "chunks", "config", "."]);
+    assert!(!ok);
+}
+
+#[test]
+fn test_dash_searches_stdin_as_one_document() {
+    use std::io::Write;
+    use std::process::Stdio;
+
+    let temp_dir = TempDir::new().unwrap();
+    // A file the search must not look at
+    fs::write(temp_dir.path().join("other.txt"), "retry on disk\n").unwrap();
+    let input = b"connect()\nretry with backoff\nclose()\nretry again\n";
+
+    let run_on = |input: &[u8], args: &[&str]| {
+        let mut child = ck_command()
+            .args(args)
+            .current_dir(temp_dir.path())
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .spawn()
+            .expect("Failed to spawn ck");
+        child.stdin.take().unwrap().write_all(input).unwrap();
+        let output = child.wait_with_output().unwrap();
+        (
+            output.status.code(),
+            String::from_utf8_lossy(&output.stdout).to_string(),
+            String::from_utf8_lossy(&output.stderr).to_string(),
+        )
+    };
+    let run = |args: &[&str]| run_on(input, args);
+
+    let (code, stdout, stderr) = run(&["retry", "-"]);
+    assert_eq!(code, Some(0), "{stderr}");
+    assert_eq!(stdout, "2:retry with backoff\n4:retry again\n");
+
+    let (code, stdout, _) = run(&["-H", "backoff", "-"]);
+    assert_eq!(code, Some(0));
+    assert_eq!(stdout, "(stdin):2:retry with backoff\n");
+
+    let (code, stdout, stderr) = run(&["--lex", "--json", "backoff", "-"]);
+    assert_eq!(code, Some(0), "{stderr}");
+    let result: serde_json::Value = serde_json::from_str(stdout.trim()).unwrap();
+    assert_eq!(result["file"], "(stdin)");
+
+    let (code, _, _) = run(&["missing", "-"]);
+    assert_eq!(code, Some(1));
+
+    // Nothing was indexed to search stdin
+    assert!(!temp_dir.path().join(".ck").exists());
+
+    let (code, _, stderr) = run(&["--sem", "retry", "-"]);
+    assert_eq!(code, Some(2));
+    assert!(stderr.contains("needs --ephemeral"), "{stderr}");
+
+    let (code, _, stderr) = run(&["retry", "-", "other.txt"]);
+    assert_eq!(code, Some(2));
+    assert!(stderr.contains("standard input"), "{stderr}");
+
+    // Invalid UTF-8 is decoded lossily, as in files
+    let (code, stdout, stderr) = run_on(b"caf\xe9 retry\nok\n", &["retry", "-"]);
+    assert_eq!(code, Some(0), "{stderr}");
+    assert_eq!(stdout, "1:caf\u{fffd} retry\n");
+}
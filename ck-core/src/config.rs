@@ -0,0 +1,208 @@
+//! Persistent per-repository defaults loaded from `.ck/config.toml`.
+//!
+//! Values here sit between ck's built-in defaults and command-line flags:
+//! a flag always wins, the config file fills in anything not passed, and the
+//! built-in default applies only when neither says otherwise.
+
+use crate::{CkError, Result};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fmt;
+use std::path::{Path, PathBuf};
+
+/// Directory (relative to a repository root) that holds the config file.
+pub const CONFIG_DIR: &str = ".ck";
+
+/// File name of the per-repository config inside [`CONFIG_DIR`].
+pub const CONFIG_FILE_NAME: &str = "config.toml";
+
+/// Options that can be set in `.ck/config.toml`. Every key is optional.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct CkConfig {
+    /// Embedding model alias or name used by `--index` when `--model` is not passed
+    pub default_model: Option<String>,
+    /// Similarity threshold for `--sem` when `--threshold` is not passed
+    pub semantic_threshold: Option<f32>,
+    /// Result limit for ranked modes (`--sem`, `--lex`, `--hybrid`) when `--topk` is not passed
+    pub topk: Option<usize>,
+    /// Extra exclude patterns, combined with the defaults and any `--exclude` flags
+    pub exclude: Vec<String>,
+    /// BM25 term-frequency saturation for `--lex` when `--bm25-k1` is not passed
+    pub bm25_k1: Option<f32>,
+    /// BM25 length normalization for `--lex` when `--bm25-b` is not passed
+    pub bm25_b: Option<f32>,
+    /// Proximity boost weight for `--lex` when `--proximity` is not passed
+    pub proximity: Option<f32>,
+    /// File name globs of generated files, besides the built-in ones
+    /// (`--no-generated` leaves these files out)
+    pub generated_patterns: Vec<String>,
+    /// Text that marks a file generated when it appears in its first lines,
+    /// besides the built-in markers such as `DO NOT EDIT`
+    pub generated_markers: Vec<String>,
+    /// Embedding model per language (`rust = "jina-code"`), applied by
+    /// `--index`; languages not listed use the index's model
+    pub language_models: BTreeMap<String, String>,
+}
+
+/// A config together with the file it was read from, if any.
+#[derive(Debug, Clone, Default)]
+pub struct LoadedConfig {
+    pub path: Option<PathBuf>,
+    pub config: CkConfig,
+}
+
+/// Where an effective setting came from, in increasing precedence.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ConfigSource {
+    Default,
+    ConfigFile,
+    Cli,
+}
+
+impl fmt::Display for ConfigSource {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ConfigSource::Default => write!(f, "default"),
+            ConfigSource::ConfigFile => write!(f, "config"),
+            ConfigSource::Cli => write!(f, "cli"),
+        }
+    }
+}
+
+/// Pick the highest-precedence value: CLI flag, then config file, then built-in default.
+pub fn resolve_setting<T>(
+    cli: Option<T>,
+    config: Option<T>,
+    default: Option<T>,
+) -> Option<(T, ConfigSource)> {
+    cli.map(|v| (v, ConfigSource::Cli))
+        .or_else(|| config.map(|v| (v, ConfigSource::ConfigFile)))
+        .or_else(|| default.map(|v| (v, ConfigSource::Default)))
+}
+
+/// Find the nearest `.ck/config.toml`, starting at `start` and walking up
+/// through parent directories.
+pub fn find_config_file(start: &Path) -> Option<PathBuf> {
+    let start = std::fs::canonicalize(start).unwrap_or_else(|_| start.to_path_buf());
+    let mut current = if start.is_file() {
+        start.parent()?.to_path_buf()
+    } else {
+        start
+    };
+    loop {
+        let candidate = current.join(CONFIG_DIR).join(CONFIG_FILE_NAME);
+        if candidate.is_file() {
+            return Some(candidate);
+        }
+        if !current.pop() {
+            return None;
+        }
+    }
+}
+
+/// Parse a config file. Unknown keys are rejected so typos don't go unnoticed.
+pub fn read_config(path: &Path) -> Result<CkConfig> {
+    let content = std::fs::read_to_string(path)?;
+    toml::from_str(&content)
+        .map_err(|e| CkError::Other(format!("Invalid config file {}: {e}", path.display())))
+}
+
+/// Load the nearest config for `start`, or an empty config when none exists.
+pub fn load_config(start: &Path) -> Result<LoadedConfig> {
+    match find_config_file(start) {
+        Some(path) => {
+            let config = read_config(&path)?;
+            Ok(LoadedConfig {
+                path: Some(path),
+                config,
+            })
+        }
+        None => Ok(LoadedConfig::default()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+    use tempfile::TempDir;
+
+    fn write_config(root: &Path, content: &str) -> PathBuf {
+        let dir = root.join(CONFIG_DIR);
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join(CONFIG_FILE_NAME);
+        fs::write(&path, content).unwrap();
+        path
+    }
+
+    #[test]
+    fn test_load_config_walks_up_parents() {
+        let temp_dir = TempDir::new().unwrap();
+        let root = temp_dir.path();
+        write_config(
+            root,
+            "default_model = \"nomic-v1.5\"\nsemantic_threshold = 0.4\ntopk = 25\nexclude = [\"vendor/\"]\n",
+        );
+        let nested = root.join("src").join("deep");
+        fs::create_dir_all(&nested).unwrap();
+
+        let loaded = load_config(&nested).unwrap();
+        assert!(loaded.path.is_some());
+        assert_eq!(loaded.config.default_model.as_deref(), Some("nomic-v1.5"));
+        assert_eq!(loaded.config.semantic_threshold, Some(0.4));
+        assert_eq!(loaded.config.topk, Some(25));
+        assert_eq!(loaded.config.exclude, vec!["vendor/".to_string()]);
+    }
+
+    #[test]
+    fn test_load_config_reads_language_models() {
+        let temp_dir = TempDir::new().unwrap();
+        write_config(
+            temp_dir.path(),
+            "default_model = \"bge-small\"\n\n[language_models]\nrust = \"jina-code\"\nmarkdown = \"nomic-v1.5\"\n",
+        );
+
+        let loaded = load_config(temp_dir.path()).unwrap();
+        let models = &loaded.config.language_models;
+        assert_eq!(models.len(), 2);
+        assert_eq!(models["rust"], "jina-code");
+        assert_eq!(models["markdown"], "nomic-v1.5");
+    }
+
+    #[test]
+    fn test_load_config_missing_is_empty() {
+        let temp_dir = TempDir::new().unwrap();
+        let loaded = load_config(temp_dir.path()).unwrap();
+        assert!(loaded.path.is_none());
+        assert_eq!(loaded.config, CkConfig::default());
+    }
+
+    #[test]
+    fn test_read_config_rejects_unknown_keys() {
+        let temp_dir = TempDir::new().unwrap();
+        let path = write_config(temp_dir.path(), "top_k = 5\n");
+        let err = read_config(&path).unwrap_err().to_string();
+        assert!(
+            err.contains("Invalid config file"),
+            "unexpected error: {err}"
+        );
+    }
+
+    #[test]
+    fn test_resolve_setting_precedence() {
+        assert_eq!(
+            resolve_setting(Some(1), Some(2), Some(3)),
+            Some((1, ConfigSource::Cli))
+        );
+        assert_eq!(
+            resolve_setting(None, Some(2), Some(3)),
+            Some((2, ConfigSource::ConfigFile))
+        );
+        assert_eq!(
+            resolve_setting(None, None, Some(3)),
+            Some((3, ConfigSource::Default))
+        );
+        assert_eq!(resolve_setting::<i32>(None, None, None), None);
+    }
+}
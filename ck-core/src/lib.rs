@@ -1,5 +1,8 @@
+pub mod config;
 pub mod heatmap;
+pub mod notebook;
 
+use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 use std::path::{Path, PathBuf};
 use thiserror::Error;
@@ -32,11 +35,23 @@ pub enum CkError {
 
     #[error("Other error: {0}")]
     Other(String),
+
+    /// The caller cancelled the operation through its [`CancellationToken`]
+    #[error("Operation cancelled")]
+    Cancelled,
+}
+
+pub use tokio_util::sync::CancellationToken;
+
+/// True when `err` is (or wraps) [`CkError::Cancelled`], as opposed to a
+/// failure.
+pub fn is_cancelled(err: &anyhow::Error) -> bool {
+    matches!(err.downcast_ref::<CkError>(), Some(CkError::Cancelled))
 }
 
 pub type Result<T> = std::result::Result<T, CkError>;
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize, JsonSchema)]
 pub enum Language {
     Rust,
     Python,
@@ -64,36 +79,92 @@ impl Language {
         // Convert to lowercase for case-insensitive matching
         match ext.to_lowercase().as_str() {
             "rs" => Some(Language::Rust),
-            "py" => Some(Language::Python),
-            "js" => Some(Language::JavaScript),
-            "ts" | "tsx" => Some(Language::TypeScript),
+            "py" | "pyi" | "pyw" => Some(Language::Python),
+            "js" | "jsx" | "mjs" | "cjs" => Some(Language::JavaScript),
+            "ts" | "tsx" | "mts" | "cts" => Some(Language::TypeScript),
             "hs" | "lhs" => Some(Language::Haskell),
             "go" => Some(Language::Go),
             "java" => Some(Language::Java),
             "c" => Some(Language::C),
             "cpp" | "cc" | "cxx" | "c++" => Some(Language::Cpp),
-            "h" | "hpp" => Some(Language::Cpp), // Assume C++ for headers
-            "cs" => Some(Language::CSharp),
-            "rb" => Some(Language::Ruby),
-            "php" => Some(Language::Php),
+            "h" | "hpp" | "hh" | "hxx" | "h++" | "ipp" | "tpp" | "inl" => Some(Language::Cpp), // Assume C++ for headers
+            "cs" | "csx" => Some(Language::CSharp),
+            "rb" | "rake" | "gemspec" | "ru" => Some(Language::Ruby),
+            "php" | "phtml" => Some(Language::Php),
             "swift" => Some(Language::Swift),
             "kt" | "kts" => Some(Language::Kotlin),
             "zig" => Some(Language::Zig),
             "dart" => Some(Language::Dart),
             "ex" | "exs" => Some(Language::Elixir),
-            "md" | "markdown" | "mdx" => Some(Language::Markdown),
+            "md" | "markdown" | "mdx" | "mkd" | "mdown" => Some(Language::Markdown),
             "pdf" => Some(Language::Pdf),
             _ => None,
         }
     }
 
+    /// Language of a file known by its whole name rather than an extension,
+    /// like `Rakefile`.
+    pub fn from_file_name(name: &str) -> Option<Self> {
+        match name {
+            "Rakefile" | "Gemfile" | "Guardfile" | "Podfile" | "Vagrantfile" | "Brewfile"
+            | "Fastfile" => Some(Language::Ruby),
+            "SConstruct" | "SConscript" => Some(Language::Python),
+            _ => None,
+        }
+    }
+
+    /// Language of a script from its `#!` line, e.g. `#!/usr/bin/env python3`
+    /// or `#!/usr/local/bin/node`. Interpreter version suffixes
+    /// (`python3.12`) and `env` options (`env -S ruby -w`) are skipped.
+    pub fn from_shebang(line: &str) -> Option<Self> {
+        let mut words = line.strip_prefix("#!")?.split_whitespace();
+        let mut program = words.next()?.rsplit('/').next()?;
+        if program == "env" {
+            program = words.find(|word| !word.starts_with('-') && !word.contains('='))?;
+        }
+        match program.trim_end_matches(|c: char| c.is_ascii_digit() || c == '.') {
+            "python" | "pypy" => Some(Language::Python),
+            "node" | "nodejs" | "bun" => Some(Language::JavaScript),
+            "ts-node" | "tsx" => Some(Language::TypeScript),
+            "ruby" | "jruby" => Some(Language::Ruby),
+            "php" => Some(Language::Php),
+            "elixir" => Some(Language::Elixir),
+            "runghc" | "runhaskell" => Some(Language::Haskell),
+            "swift" => Some(Language::Swift),
+            "kotlin" | "kscript" => Some(Language::Kotlin),
+            "dart" => Some(Language::Dart),
+            "rust-script" => Some(Language::Rust),
+            _ => None,
+        }
+    }
+
+    /// Language of the file at `path`: by extension, or for a file without
+    /// one by its name or, failing that, the shebang on its first line. Only
+    /// that line is read, and only for files without an extension.
     pub fn from_path(path: &Path) -> Option<Self> {
-        path.extension()
-            .and_then(|ext| ext.to_str())
-            .and_then(Self::from_extension)
+        if let Some(ext) = path.extension() {
+            return ext.to_str().and_then(Self::from_extension);
+        }
+        let name = path.file_name()?.to_str()?;
+        Self::from_file_name(name).or_else(|| Self::from_shebang(&first_line(path)?))
     }
 }
 
+/// Longest first line [`Language::from_path`] reads looking for a shebang.
+const SHEBANG_MAX_LEN: u64 = 256;
+
+/// The first line of the file at `path`, if it can be read.
+fn first_line(path: &Path) -> Option<String> {
+    use std::io::{BufRead, Read};
+
+    let file = std::fs::File::open(path).ok()?;
+    let mut line = Vec::new();
+    std::io::BufReader::new(file.take(SHEBANG_MAX_LEN))
+        .read_until(b'\n', &mut line)
+        .ok()?;
+    Some(String::from_utf8_lossy(&line).into_owned())
+}
+
 impl std::fmt::Display for Language {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         let name = match self {
@@ -121,7 +192,23 @@ impl std::fmt::Display for Language {
     }
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+impl std::str::FromStr for Language {
+    type Err = CkError;
+
+    /// Parse a language by the name [`Display`](std::fmt::Display) gives it
+    fn from_str(name: &str) -> Result<Self> {
+        use Language::*;
+        [
+            Rust, Python, JavaScript, TypeScript, Haskell, Go, Java, C, Cpp, CSharp, Ruby, Php,
+            Swift, Kotlin, Zig, Dart, Elixir, Markdown, Pdf,
+        ]
+        .into_iter()
+        .find(|language| language.to_string().eq_ignore_ascii_case(name))
+        .ok_or_else(|| CkError::Other(format!("Unknown language '{name}'")))
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
 pub struct Span {
     pub byte_start: usize,
     pub byte_end: usize,
@@ -238,6 +325,104 @@ pub struct SearchResult {
     pub chunk_hash: Option<String>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub index_epoch: Option<u64>,
+    /// Similarity score before reranking; set only when a reranker rescored this result
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub original_score: Option<f32>,
+    /// The file changed after it was indexed, so the span may no longer line up
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub stale: bool,
+    /// Which of [`SearchOptions::roots`] the result came from, when several
+    /// roots were searched together
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub root: Option<PathBuf>,
+    /// Line the preview starts on, when it is a window into the span rather
+    /// than its beginning (`--snippet-lines`)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub preview_line: Option<usize>,
+    /// Near-duplicate results collapsed into this one by `--dedup`
+    #[serde(default, skip_serializing_if = "is_zero")]
+    pub duplicates: usize,
+    /// Results within `symbol` this one stands for, collapsed by
+    /// `--group-by-symbol` (0 when results weren't grouped)
+    #[serde(default, skip_serializing_if = "is_zero")]
+    pub grouped: usize,
+    /// 1-based byte column of a regex match within `span.line_start`; other
+    /// modes match whole chunks and leave it unset
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub column: Option<usize>,
+    /// Line within the span (or a `--full-section` preview) that best matches
+    /// the query, for results covering several lines; unset when no line
+    /// shares a word with the query
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub match_line: Option<usize>,
+    /// How `score` came about; filled in only for [`SearchOptions::explain`]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub explanation: Option<ScoreBreakdown>,
+    /// The `-B`/`-C` lines before the match, oldest first; `None` unless
+    /// context was requested (empty at the start of the file)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub context_before: Option<Vec<String>>,
+    /// The `-A`/`-C` lines after the match; `None` unless context was
+    /// requested (empty at the end of the file)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub context_after: Option<Vec<String>>,
+}
+
+fn is_zero(n: &usize) -> bool {
+    *n == 0
+}
+
+impl SearchResult {
+    /// The order searches return results in; see [`rank_order`].
+    pub fn rank_cmp(&self, other: &Self) -> std::cmp::Ordering {
+        rank_order(
+            (self.score, &self.file, &self.span),
+            (other.score, &other.file, &other.span),
+        )
+    }
+}
+
+/// Total order of search results: score (highest first), then file path,
+/// then `line_start`, then `line_end`, with the span's byte start settling
+/// matches on the same line. Equal scores are common (every regex match
+/// scores 1, identical chunks score alike), and without the rest of the key
+/// their order would follow file enumeration and vary between runs.
+pub fn rank_order(a: (f32, &Path, &Span), b: (f32, &Path, &Span)) -> std::cmp::Ordering {
+    b.0.total_cmp(&a.0)
+        .then_with(|| a.1.cmp(b.1))
+        .then_with(|| a.2.line_start.cmp(&b.2.line_start))
+        .then_with(|| a.2.line_end.cmp(&b.2.line_end))
+        .then_with(|| a.2.byte_start.cmp(&b.2.byte_start))
+}
+
+/// The parts of a result's score, for `--explain`. Which fields are set
+/// depends on the search mode; a reranked result's pre-rerank score is
+/// [`SearchResult::original_score`].
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct ScoreBreakdown {
+    /// Cosine similarity of the matched chunk's embedding with the query's
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub similarity: Option<f32>,
+    /// BM25 score of the matched file in the lexical index
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub bm25: Option<f32>,
+    /// 1-based rank in hybrid search's keyword results
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub keyword_rank: Option<usize>,
+    /// 1-based rank in hybrid search's semantic results
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub semantic_rank: Option<usize>,
+    /// Weight of the keyword rank in hybrid fusion; below 1 when the literal
+    /// query matched nothing and separate query terms were matched instead
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub keyword_weight: Option<f32>,
+    /// Constant `k` of reciprocal rank fusion: a rank `r` contributes `1 / (k + r)`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub rrf_k: Option<f32>,
+    /// Minimum score results had to reach, from `threshold` or
+    /// `threshold_percentile`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub threshold: Option<f32>,
 }
 
 /// Enhanced search results that include near-miss information for threshold queries
@@ -246,9 +431,65 @@ pub struct SearchResults {
     pub matches: Vec<SearchResult>,
     /// The highest scoring result below the threshold (if any)
     pub closest_below_threshold: Option<SearchResult>,
+    /// How long each stage of the search took (`--stats`)
+    pub timings: SearchTimings,
+    /// The search reached its [`SearchOptions::deadline`] before scanning
+    /// every candidate, so `matches` are only those found by then
+    pub truncated: bool,
+    /// When no semantic result reached the threshold, how many a few lower
+    /// thresholds would have returned, highest threshold first
+    pub lower_thresholds: Vec<ThresholdStep>,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+/// The number of results a search would return at a lower threshold.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ThresholdStep {
+    pub threshold: f32,
+    pub results: usize,
+}
+
+/// Time spent in each stage of one search; `None` for stages it didn't run,
+/// such as loading a model for a regex search.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct SearchTimings {
+    /// Creating the embedding model (skipped when the query embedding is cached)
+    pub model_load: Option<std::time::Duration>,
+    /// Embedding the query
+    pub query_embed: Option<std::time::Duration>,
+    /// Reading embeddings from sidecars, or walking files for the lexical index
+    pub candidate_load: Option<std::time::Duration>,
+    /// Scoring candidates: similarity, BM25, or scanning files for a regex
+    pub scoring: Option<std::time::Duration>,
+    /// Rescoring the top results with `--rerank`
+    pub rerank: Option<std::time::Duration>,
+}
+
+impl SearchTimings {
+    /// `self` with each stage's time added to `other`'s, for searches that
+    /// run several searches (hybrid arms, several roots).
+    pub fn combined(self, other: SearchTimings) -> SearchTimings {
+        let sum = |a: Option<std::time::Duration>, b: Option<std::time::Duration>| match (a, b) {
+            (Some(a), Some(b)) => Some(a + b),
+            (a, b) => a.or(b),
+        };
+        SearchTimings {
+            model_load: sum(self.model_load, other.model_load),
+            query_embed: sum(self.query_embed, other.query_embed),
+            candidate_load: sum(self.candidate_load, other.candidate_load),
+            scoring: sum(self.scoring, other.scoring),
+            rerank: sum(self.rerank, other.rerank),
+        }
+    }
+}
+
+/// Version of the `--json` / `--jsonl` record format, carried in each record's
+/// `schema_version`. Bump it whenever a field is removed, renamed or changes
+/// meaning, so consumers can refuse records they don't understand; adding an
+/// optional field doesn't need a bump. `ck --print-json-schema` prints the
+/// current format.
+pub const JSON_SCHEMA_VERSION: u32 = 1;
+
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
 pub struct JsonSearchResult {
     pub file: String,
     pub span: Span,
@@ -258,13 +499,34 @@ pub struct JsonSearchResult {
     pub signals: SearchSignals,
     pub preview: String,
     pub model: String,
+    /// Best-matching line within `span`; see [`SearchResult::match_line`]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub match_line: Option<usize>,
+    /// Byte ranges (start, end) within `preview` to highlight: the regex
+    /// matches, or the query terms that appear literally in it
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub highlights: Vec<(usize, usize)>,
+    /// Lines before the match; see [`SearchResult::context_before`]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub context_before: Option<Vec<String>>,
+    /// Lines after the match; see [`SearchResult::context_after`]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub context_after: Option<Vec<String>>,
+    /// Record format version of the ck that wrote it; 0 for records from
+    /// before versioning
+    #[serde(default)]
+    pub schema_version: u32,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
 pub struct JsonlSearchResult {
     pub path: String,
     pub span: Span,
     pub language: Option<String>,
+    /// Section the result lies in: a Markdown heading path, notebook cell or
+    /// PDF page, or the symbol `--group-by-symbol` grouped it under
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub symbol: Option<String>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub snippet: Option<String>,
     #[serde(skip_serializing_if = "Option::is_none")]
@@ -273,9 +535,43 @@ pub struct JsonlSearchResult {
     pub chunk_hash: Option<String>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub index_epoch: Option<u64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub original_score: Option<f32>,
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub stale: bool,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub root: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub snippet_line: Option<usize>,
+    /// Near-duplicate results collapsed into this one by `--dedup`
+    #[serde(default, skip_serializing_if = "is_zero")]
+    pub duplicates: usize,
+    /// Results within the symbol collapsed into this one by
+    /// `--group-by-symbol`; see [`SearchResult::grouped`]
+    #[serde(default, skip_serializing_if = "is_zero")]
+    pub grouped: usize,
+    /// Best-matching line within `span`; see [`SearchResult::match_line`]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub match_line: Option<usize>,
+    /// Byte ranges (start, end) within `snippet` to highlight; see
+    /// [`JsonSearchResult::highlights`]
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub highlights: Vec<(usize, usize)>,
+    /// Lines before the match, with the snippet; see
+    /// [`SearchResult::context_before`]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub context_before: Option<Vec<String>>,
+    /// Lines after the match, with the snippet; see
+    /// [`SearchResult::context_after`]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub context_after: Option<Vec<String>>,
+    /// Record format version of the ck that wrote it; 0 for records from
+    /// before versioning
+    #[serde(default)]
+    pub schema_version: u32,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
 pub struct SearchSignals {
     pub lex_rank: Option<usize>,
     pub vec_rank: Option<usize>,
@@ -290,6 +586,54 @@ pub enum SearchMode {
     Hybrid,
 }
 
+/// How `--similar` compares the target file with the rest of the index.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum SimilarBy {
+    /// Mean of each file's chunk vectors against the target's mean
+    #[default]
+    File,
+    /// Best-matching pair of chunks between each file and the target
+    Chunk,
+}
+
+/// What regex and lexical search match against (`--on`).
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum MatchOn {
+    /// Each line for regex, each whole file for lexical
+    #[default]
+    Lines,
+    /// Each chunk the index recorded, whose span and symbol the result
+    /// carries, as semantic results do
+    Chunks,
+}
+
+/// Display order of search results (`--sort`). Sorting is stable, so results
+/// with equal keys keep the order the search found them in.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum ResultSort {
+    /// The search's own ranking: best score first, or path then line for regex
+    #[default]
+    Score,
+    /// By file path, then line
+    Path,
+    /// By line number
+    Line,
+}
+
+impl ResultSort {
+    pub fn apply(self, results: &mut [SearchResult]) {
+        match self {
+            ResultSort::Score => {}
+            ResultSort::Path => results.sort_by(|a, b| {
+                a.file
+                    .cmp(&b.file)
+                    .then(a.span.line_start.cmp(&b.span.line_start))
+            }),
+            ResultSort::Line => results.sort_by_key(|result| result.span.line_start),
+        }
+    }
+}
+
 #[derive(Debug, Clone)]
 pub struct IncludePattern {
     pub path: PathBuf,
@@ -307,8 +651,23 @@ pub struct FileCollectionOptions {
     pub use_ckignore: bool,
     /// Patterns to exclude files/directories
     pub exclude_patterns: Vec<String>,
+    /// Globs a file must match to be collected (empty = every file);
+    /// excludes still win over a matching include
+    pub include_globs: Vec<String>,
     /// Whether to include hidden (dot-prefixed) files and directories
     pub show_hidden: bool,
+    /// Whether to follow symbolic links; symlink cycles are detected and
+    /// each file is collected once however many links reach it
+    pub follow_links: bool,
+    /// Whether to descend into Git submodules, which are skipped by default
+    /// since they have their own history (and can have their own index)
+    pub include_submodules: bool,
+    /// How deep the walk descends below the root (None = no limit); depth 1
+    /// collects only the files directly in the root
+    pub max_depth: Option<usize>,
+    /// Whether to leave out files that look generated (see
+    /// `ck_index::is_generated`)
+    pub skip_generated: bool,
 }
 
 impl Default for FileCollectionOptions {
@@ -317,7 +676,12 @@ impl Default for FileCollectionOptions {
             respect_gitignore: true,
             use_ckignore: true,
             exclude_patterns: Vec::new(),
+            include_globs: Vec::new(),
             show_hidden: false,
+            follow_links: false,
+            include_submodules: false,
+            max_depth: None,
+            skip_generated: false,
         }
     }
 }
@@ -328,11 +692,127 @@ impl From<&SearchOptions> for FileCollectionOptions {
             respect_gitignore: opts.respect_gitignore,
             use_ckignore: true, // Always use .ckignore for hierarchical ignore support
             exclude_patterns: opts.exclude_patterns.clone(),
+            // Search-side include globs filter results instead: narrowing the
+            // walk here would make auto-indexing prune every file outside them.
+            include_globs: Vec::new(),
             show_hidden: opts.hidden,
+            follow_links: opts.follow_links,
+            include_submodules: opts.include_submodules,
+            // The search-side depth limit and generated-file filter apply
+            // to results too, for the same reason
+            max_depth: None,
+            skip_generated: false,
         }
     }
 }
 
+/// Text searched in place of files on disk, such as standard input for
+/// `ck PATTERN -`. Results name it as their file.
+#[derive(Debug, Clone)]
+pub struct Document {
+    pub name: PathBuf,
+    pub content: std::sync::Arc<str>,
+}
+
+impl Document {
+    /// Name standard input goes by in results, like grep's `(standard input)`
+    pub const STDIN_NAME: &str = "(stdin)";
+
+    /// `content` read from standard input.
+    pub fn stdin(content: impl Into<std::sync::Arc<str>>) -> Self {
+        Self {
+            name: PathBuf::from(Self::STDIN_NAME),
+            content: content.into(),
+        }
+    }
+
+    /// Language of the document, from a shebang on its first line.
+    pub fn language(&self) -> Option<Language> {
+        self.content.lines().next().and_then(Language::from_shebang)
+    }
+}
+
+/// Line ranges search results are restricted to, per file. Files without
+/// ranges are searched whole.
+#[derive(Debug, Clone, Default)]
+pub struct LineRanges {
+    /// Inclusive 1-based `(start, end)` ranges, keyed by canonical path
+    ranges: std::collections::HashMap<PathBuf, Vec<(usize, usize)>>,
+}
+
+impl LineRanges {
+    /// Parse a `start-end` range spec (or a single line number), 1-based
+    /// and inclusive.
+    pub fn parse_spec(spec: &str) -> Result<(usize, usize)> {
+        let malformed = || {
+            CkError::Search(format!(
+                "Malformed line range `{spec}`: expected START-END with 1 <= START <= END"
+            ))
+        };
+        let (start, end) = spec.split_once('-').unwrap_or((spec, spec));
+        let start: usize = start.trim().parse().map_err(|_| malformed())?;
+        let end: usize = end.trim().parse().map_err(|_| malformed())?;
+        if start == 0 || start > end {
+            return Err(malformed());
+        }
+        Ok((start, end))
+    }
+
+    /// Restrict `path` to lines `start..=end`, in addition to any ranges it
+    /// already has.
+    pub fn insert(&mut self, path: &Path, (start, end): (usize, usize)) {
+        let key = path.canonicalize().unwrap_or_else(|_| path.to_path_buf());
+        self.ranges.entry(key).or_default().push((start, end));
+    }
+
+    /// Search all of `path` again, dropping its ranges.
+    pub fn remove(&mut self, path: &Path) {
+        let key = path.canonicalize().unwrap_or_else(|_| path.to_path_buf());
+        self.ranges.remove(&key);
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.ranges.is_empty()
+    }
+
+    /// Whether a result in `file` covering `span` is kept: it overlaps one of
+    /// the file's ranges, or the file has none.
+    pub fn admits(&self, file: &Path, span: &Span) -> bool {
+        if self.ranges.is_empty() {
+            return true;
+        }
+        // Only canonicalize paths that could be a key
+        let name = file.file_name();
+        if !self.ranges.keys().any(|key| key.file_name() == name) {
+            return true;
+        }
+        let key = file.canonicalize().unwrap_or_else(|_| file.to_path_buf());
+        self.ranges.get(&key).is_none_or(|ranges| {
+            ranges
+                .iter()
+                .any(|&(start, end)| span.line_start <= end && span.line_end >= start)
+        })
+    }
+}
+
+/// The two knobs of BM25, the scoring function of lexical search.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Bm25Params {
+    /// Term-frequency saturation: how much each further occurrence of a term
+    /// adds to a file's score. 0 ignores repeats; higher values let them
+    /// keep counting
+    pub k1: f32,
+    /// Length normalization, from 0 (file length doesn't matter) to 1 (a
+    /// term in a long file counts for proportionally less)
+    pub b: f32,
+}
+
+impl Default for Bm25Params {
+    fn default() -> Self {
+        Self { k1: 1.2, b: 0.75 }
+    }
+}
+
 #[derive(Debug, Clone)]
 pub struct SearchOptions {
     pub mode: SearchMode,
@@ -363,18 +843,134 @@ pub struct SearchOptions {
     pub full_section: bool,
     /// Whether to include hidden (dot-prefixed) files and directories
     pub hidden: bool,
+    /// Whether to follow symbolic links while walking directories
+    pub follow_links: bool,
+    /// Whether to walk into Git submodules (`--index-submodules`)
+    pub include_submodules: bool,
+    /// Only search files at most this many directory levels below the root
+    /// (`--max-depth`; 1 = files directly in the root)
+    pub max_depth: Option<usize>,
+    /// Leave out files that look generated (`--no-generated`), judged by
+    /// the flag their sidecar records where there is one
+    pub no_generated: bool,
     // Enhanced embedding options (search-time only)
     pub rerank: bool,
     pub rerank_model: Option<String>,
+    /// How many leading semantic candidates the reranker rescores (None = engine default)
+    pub rerank_top_k: Option<usize>,
     pub embedding_model: Option<String>,
+    /// Chunks embedded per model call when indexing (`--embed-batch-size`);
+    /// `None` for the model's default
+    pub embed_batch_size: Option<usize>,
+    /// Decide which indexed files changed by content hash alone
+    /// (`--force-hash`), not by mtime and size
+    pub force_hash: bool,
+    /// Files searched and indexed at once (`--jobs`); `None` for one per CPU
+    pub jobs: Option<usize>,
+    /// Skip sidecars whose embedding model/dimension differs from the query
+    /// embedder instead of failing the semantic search
+    pub ignore_model_mismatch: bool,
+    /// Expand lexical query terms to indexed terms within a small edit distance
+    pub fuzzy: bool,
+    /// BM25 parameters lexical search scores with (`--bm25-k1`, `--bm25-b`)
+    pub bm25: Bm25Params,
+    /// Weight of lexical search's boost for query terms that appear near
+    /// each other (`--proximity`); 0 turns it off
+    pub proximity: f32,
+    /// Whether regex and lexical search match lines and files or indexed
+    /// chunks (`--on`)
+    pub match_on: MatchOn,
+    /// Search this document instead of the files under `path`, which then
+    /// only locates the config and embedding model (`-` as the search path)
+    pub document: Option<Document>,
+    /// Split camelCase/snake_case/kebab-case identifiers in the query into words
+    pub split_identifiers: bool,
+    /// Globs (relative to the search path) a file must match to be searched;
+    /// excludes still win
+    pub include_globs: Vec<String>,
+    /// Rank indexed files by similarity to this file instead of embedding
+    /// `query` (semantic mode only)
+    pub similar_to: Option<PathBuf>,
+    /// Granularity of the `similar_to` comparison
+    pub similar_by: SimilarBy,
+    /// Independently indexed directories searched together in semantic mode: each is
+    /// searched with its own index and the results are ranked as one list, with
+    /// `top_k` and `threshold` applied across all of them. Empty searches `path` alone.
+    pub roots: Vec<PathBuf>,
+    /// Drop results from files that changed since they were indexed
+    pub no_stale: bool,
+    /// Lines of each semantic result's chunk to show as its preview, centered on
+    /// the line that best matches the query (ignored with `full_section`)
+    pub snippet_lines: Option<usize>,
+    /// Keep only results scoring at or above this percentile (0-100) of the
+    /// query's candidate scores; an alternative to `threshold`, not combined with it
+    pub threshold_percentile: Option<f32>,
+    /// Search the tree of this git commit, tag or branch instead of the working
+    /// copy (`--at`)
+    pub at_ref: Option<String>,
+    /// Hide results whose chunk embedding has at least this cosine similarity
+    /// with a higher-ranked result's (`--dedup`)
+    pub dedup: Option<f32>,
+    /// Search the index as it is instead of bringing it up to date first
+    pub no_auto_index: bool,
+    /// Chunk and embed the searched files in memory instead of using an
+    /// index, writing nothing to disk (`--ephemeral`; semantic search only)
+    pub ephemeral: bool,
+    /// Order of the final results (`--sort`); applied after `top_k`, so the
+    /// best-scoring results are kept whatever the display order
+    pub sort: ResultSort,
+    /// Record each result's [`ScoreBreakdown`] in `SearchResult::explanation`
+    pub explain: bool,
+    /// Only search files modified at or after this time (`--modified-since`)
+    pub modified_since: Option<std::time::SystemTime>,
+    /// Results to skip before the `top_k` returned (`--offset`), for paging
+    /// through a ranking. The skipped results are still ranked, so a deep
+    /// page costs a full search; reuse a `ck_engine::LoadedIndex` to at least
+    /// avoid reloading the model and sidecars per page
+    pub offset: usize,
+    /// Score every chunk even when an approximate (`--ann`) index is
+    /// available
+    pub exact: bool,
+    /// Candidates the approximate index explores per query (`--ef-search`);
+    /// `None` for its default
+    pub ef_search: Option<usize>,
+    /// Keep at most this many results from any one file (`--max-matches-per-file`),
+    /// applied before `top_k` and `max_count`
+    pub max_matches_per_file: Option<usize>,
+    /// Results returned across all files (`-m`/`--max-count`); with `top_k` too,
+    /// the smaller one applies
+    pub max_count: Option<usize>,
+    /// Report what doesn't match: lines for regex, files with no hit for lexical
+    pub invert_match: bool,
+    /// Search exactly these files (`--files-from`) rather than walking
+    /// `path`, so no exclude or ignore rules apply
+    pub explicit_files: Vec<PathBuf>,
+    /// Restrict listed files to these line ranges (`path:start-end` entries
+    /// of `--files-from`): only results overlapping one are kept
+    pub line_ranges: LineRanges,
+    /// Cancelling this aborts the search, which then fails with
+    /// [`CkError::Cancelled`]; checked between files and between stages
+    pub cancel: Option<CancellationToken>,
+    /// With regex search, report each match as this template expanded against
+    /// its captures (`$1`, `${name}`) instead of the line; files are never changed
+    pub replace: Option<String>,
+    /// Compile the regex without Unicode support (`--no-unicode`): `.`,
+    /// classes and `-i` case folding cover ASCII only, and other bytes match
+    /// only themselves
+    pub ascii_only: bool,
+    /// Stop scanning candidates at this instant (`--timeout`) and return
+    /// what was found by then, marked [`SearchResults::truncated`]
+    pub deadline: Option<std::time::Instant>,
 }
 
 impl JsonlSearchResult {
     pub fn from_search_result(result: &SearchResult, include_snippet: bool) -> Self {
         Self {
+            schema_version: JSON_SCHEMA_VERSION,
             path: result.file.to_string_lossy().to_string(),
             span: result.span.clone(),
             language: result.lang.as_ref().map(std::string::ToString::to_string),
+            symbol: result.symbol.clone(),
             snippet: if include_snippet {
                 Some(result.preview.clone())
             } else {
@@ -387,6 +983,19 @@ impl JsonlSearchResult {
             },
             chunk_hash: result.chunk_hash.clone(),
             index_epoch: result.index_epoch,
+            original_score: result.original_score,
+            stale: result.stale,
+            root: result
+                .root
+                .as_ref()
+                .map(|root| root.to_string_lossy().to_string()),
+            snippet_line: result.preview_line.filter(|_| include_snippet),
+            duplicates: result.duplicates,
+            grouped: result.grouped,
+            match_line: result.match_line,
+            highlights: Vec::new(),
+            context_before: result.context_before.clone().filter(|_| include_snippet),
+            context_after: result.context_after.clone().filter(|_| include_snippet),
         }
     }
 }
@@ -421,14 +1030,87 @@ impl Default for SearchOptions {
             use_ckignore: true,
             full_section: false,
             hidden: false,
+            follow_links: false,
+            include_submodules: false,
+            max_depth: None,
+            no_generated: false,
             // Enhanced embedding options (search-time only)
             rerank: false,
             rerank_model: None,
+            rerank_top_k: None,
             embedding_model: None,
+            embed_batch_size: None,
+            force_hash: false,
+            jobs: None,
+            ignore_model_mismatch: false,
+            fuzzy: false,
+            bm25: Bm25Params::default(),
+            proximity: 0.0,
+            match_on: MatchOn::default(),
+            document: None,
+            split_identifiers: false,
+            include_globs: Vec::new(),
+            similar_to: None,
+            similar_by: SimilarBy::File,
+            roots: Vec::new(),
+            no_stale: false,
+            snippet_lines: None,
+            threshold_percentile: None,
+            at_ref: None,
+            dedup: None,
+            no_auto_index: false,
+            ephemeral: false,
+            sort: ResultSort::Score,
+            explain: false,
+            modified_since: None,
+            offset: 0,
+            exact: false,
+            ef_search: None,
+            max_matches_per_file: None,
+            max_count: None,
+            invert_match: false,
+            explicit_files: Vec::new(),
+            line_ranges: Default::default(),
+            cancel: None,
+            replace: None,
+            ascii_only: false,
+            deadline: None,
         }
     }
 }
 
+impl SearchOptions {
+    /// True once the caller has cancelled the search.
+    pub fn is_cancelled(&self) -> bool {
+        self.cancel
+            .as_ref()
+            .is_some_and(CancellationToken::is_cancelled)
+    }
+
+    /// True once the search's `deadline` has passed.
+    pub fn is_past_deadline(&self) -> bool {
+        self.deadline
+            .is_some_and(|deadline| std::time::Instant::now() >= deadline)
+    }
+
+    /// [`CkError::Cancelled`] once the caller has cancelled the search.
+    pub fn check_cancelled(&self) -> Result<()> {
+        if self.is_cancelled() {
+            Err(CkError::Cancelled)
+        } else {
+            Ok(())
+        }
+    }
+}
+
+/// Whether any of `include_globs` reaches into a `.git` directory (such as
+/// `--include '.git/**'`), which lifts the usual exclusion of `.git`.
+pub fn include_globs_reach_git(include_globs: &[String]) -> bool {
+    include_globs
+        .iter()
+        .any(|glob| glob.starts_with(".git/") || glob.contains("/.git/"))
+}
+
 /// Get default exclusion patterns for directories that should be skipped during search.
 /// These are common cache, build, and system directories that rarely contain user code.
 pub fn get_default_exclude_patterns() -> Vec<String> {
@@ -791,9 +1473,15 @@ pub fn compute_chunk_hash(
 }
 
 /// PDF-specific utilities
+/// PDFs are indexed through their text, extracted to the content cache one
+/// page at a time: each page is a `[page N]` header line followed by its
+/// text, so a line of the cache maps back to its page like a notebook line
+/// maps to its cell.
 pub mod pdf {
     use std::path::{Path, PathBuf};
 
+    const PAGE_HEADER_PREFIX: &str = "[page ";
+
     /// Check if a file is a PDF by extension (optimized to avoid allocations)
     pub fn is_pdf_file(path: &Path) -> bool {
         path.extension()
@@ -802,7 +1490,8 @@ pub mod pdf {
             .unwrap_or(false)
     }
 
-    /// Get path for cached PDF content
+    /// Get path for cached extracted content: PDF text, or the cells of a
+    /// notebook (see [`crate::notebook`])
     pub fn get_content_cache_path(repo_root: &Path, file_path: &Path) -> PathBuf {
         let relative = file_path.strip_prefix(repo_root).unwrap_or(file_path);
         let mut cache_path = crate::index_dir(repo_root).join("content");
@@ -818,10 +1507,45 @@ pub mod pdf {
         cache_path
     }
 
+    /// The text of each page, in order, as it is cached: a header line per
+    /// page, then the page.
+    pub fn join_pages(pages: &[String]) -> String {
+        let mut text = String::new();
+        for (index, page) in pages.iter().enumerate() {
+            text.push_str(&format!("{PAGE_HEADER_PREFIX}{}]\n", index + 1));
+            text.push_str(page.trim_matches('\n'));
+            text.push('\n');
+        }
+        text
+    }
+
+    /// The page number a header line of cached text starts.
+    pub fn page_header(line: &str) -> Option<usize> {
+        line.strip_prefix(PAGE_HEADER_PREFIX)?
+            .strip_suffix(']')?
+            .parse()
+            .ok()
+    }
+
+    /// The page that 1-based `line` of cached `text` is on.
+    pub fn page_at_line(text: &str, line: usize) -> Option<usize> {
+        text.lines().take(line).filter_map(page_header).last()
+    }
+
     #[cfg(test)]
     mod tests {
         use super::*;
         use serial_test::serial;
+
+        #[test]
+        fn test_pages_map_lines_back() {
+            let text = join_pages(&["\nFirst page\n\n".to_string(), "Second\npage".to_string()]);
+            assert_eq!(text, "[page 1]\nFirst page\n[page 2]\nSecond\npage\n");
+            assert_eq!(page_at_line(&text, 2), Some(1));
+            assert_eq!(page_at_line(&text, 3), Some(2));
+            assert_eq!(page_at_line(&text, 5), Some(2));
+            assert_eq!(page_at_line("no header\n", 1), None);
+        }
         use std::path::PathBuf;
 
         #[test]
@@ -1085,6 +1809,17 @@ mod tests {
             symbol: Some("main".to_string()),
             chunk_hash: Some("abc123".to_string()),
             index_epoch: Some(1699123456),
+            original_score: None,
+            stale: false,
+            root: None,
+            preview_line: None,
+            duplicates: 0,
+            grouped: 0,
+            column: None,
+            match_line: None,
+            explanation: None,
+            context_before: None,
+            context_after: None,
         };
 
         let json = serde_json::to_string(&result).unwrap();
@@ -1099,6 +1834,95 @@ mod tests {
         assert_eq!(result.index_epoch, deserialized.index_epoch);
     }
 
+    #[test]
+    fn test_result_sort_is_stable() {
+        let result = |file: &str, line: usize, score: f32| SearchResult {
+            file: PathBuf::from(file),
+            span: Span {
+                byte_start: 0,
+                byte_end: 1,
+                line_start: line,
+                line_end: line,
+            },
+            score,
+            preview: String::new(),
+            lang: None,
+            symbol: None,
+            chunk_hash: None,
+            index_epoch: None,
+            original_score: None,
+            stale: false,
+            root: None,
+            preview_line: None,
+            duplicates: 0,
+            grouped: 0,
+            column: None,
+            match_line: None,
+            explanation: None,
+            context_before: None,
+            context_after: None,
+        };
+        let ranked = vec![
+            result("b.rs", 9, 0.9),
+            result("a.rs", 5, 0.8),
+            result("b.rs", 2, 0.7),
+            result("c.rs", 5, 0.6),
+        ];
+        let order = |sort: ResultSort| {
+            let mut results = ranked.clone();
+            sort.apply(&mut results);
+            results.iter().map(|r| r.score).collect::<Vec<_>>()
+        };
+
+        assert_eq!(order(ResultSort::Score), [0.9, 0.8, 0.7, 0.6]);
+        assert_eq!(order(ResultSort::Path), [0.8, 0.7, 0.9, 0.6]);
+        // a.rs:5 and c.rs:5 tie on line and keep their ranked order
+        assert_eq!(order(ResultSort::Line), [0.7, 0.8, 0.6, 0.9]);
+    }
+
+    #[test]
+    fn test_rank_cmp_breaks_score_ties_by_location() {
+        let result = |file: &str, lines: (usize, usize), score: f32| SearchResult {
+            file: PathBuf::from(file),
+            span: Span {
+                byte_start: 0,
+                byte_end: 1,
+                line_start: lines.0,
+                line_end: lines.1,
+            },
+            score,
+            preview: format!("{file}:{}-{}", lines.0, lines.1),
+            lang: None,
+            symbol: None,
+            chunk_hash: None,
+            index_epoch: None,
+            original_score: None,
+            stale: false,
+            root: None,
+            preview_line: None,
+            duplicates: 0,
+            grouped: 0,
+            column: None,
+            match_line: None,
+            explanation: None,
+            context_before: None,
+            context_after: None,
+        };
+        let mut results = [
+            result("b.rs", (1, 4), 0.5),
+            result("a.rs", (3, 9), 0.5),
+            result("a.rs", (3, 5), 0.5),
+            result("z.rs", (1, 1), 0.9),
+            result("a.rs", (1, 2), 0.5),
+        ];
+        results.sort_by(SearchResult::rank_cmp);
+        let order: Vec<&str> = results.iter().map(|r| r.preview.as_str()).collect();
+        assert_eq!(
+            order,
+            ["z.rs:1-1", "a.rs:1-2", "a.rs:3-5", "a.rs:3-9", "b.rs:1-4"]
+        );
+    }
+
     #[test]
     fn test_jsonl_search_result_conversion() {
         let result = SearchResult {
@@ -1115,6 +1939,17 @@ mod tests {
             symbol: Some("authenticate".to_string()),
             chunk_hash: Some("abc123def456".to_string()),
             index_epoch: Some(1699123456),
+            original_score: None,
+            stale: false,
+            root: None,
+            preview_line: None,
+            duplicates: 0,
+            grouped: 0,
+            column: None,
+            match_line: None,
+            explanation: None,
+            context_before: None,
+            context_after: None,
         };
 
         // Test with snippet
@@ -1222,6 +2057,7 @@ mod tests {
         };
 
         let result = JsonSearchResult {
+            schema_version: JSON_SCHEMA_VERSION,
             file: "test.txt".to_string(),
             span: Span {
                 byte_start: 0,
@@ -1234,6 +2070,10 @@ mod tests {
             score: 0.95,
             signals,
             preview: "hello".to_string(),
+            match_line: None,
+            highlights: vec![(0, 5)],
+            context_before: None,
+            context_after: None,
             model: "bge-small".to_string(),
         };
 
@@ -1244,6 +2084,13 @@ mod tests {
         assert_eq!(result.score, deserialized.score);
         assert_eq!(result.signals.rrf_score, deserialized.signals.rrf_score);
         assert_eq!(result.model, deserialized.model);
+        assert_eq!(deserialized.schema_version, JSON_SCHEMA_VERSION);
+
+        // Records written before versioning still parse
+        let legacy = json.replace(&format!(",\"schema_version\":{JSON_SCHEMA_VERSION}"), "");
+        assert_ne!(legacy, json);
+        let deserialized: JsonSearchResult = serde_json::from_str(&legacy).unwrap();
+        assert_eq!(deserialized.schema_version, 0);
     }
 
     #[test]
@@ -1270,6 +2117,74 @@ mod tests {
         assert_eq!(Language::from_extension("unknown"), None);
     }
 
+    #[test]
+    fn test_language_from_path_by_extension_name_and_shebang() {
+        let temp_dir = TempDir::new().unwrap();
+        let cases: &[(&str, &str, Option<Language>)] = &[
+            ("stubs.pyi", "", Some(Language::Python)),
+            ("App.jsx", "", Some(Language::JavaScript)),
+            ("server.mjs", "", Some(Language::JavaScript)),
+            ("config.cts", "", Some(Language::TypeScript)),
+            ("vector.hh", "", Some(Language::Cpp)),
+            ("tasks.rake", "", Some(Language::Ruby)),
+            ("ck.gemspec", "", Some(Language::Ruby)),
+            ("script.csx", "", Some(Language::CSharp)),
+            ("NOTES.MKD", "", Some(Language::Markdown)),
+            ("Rakefile", "", Some(Language::Ruby)),
+            ("SConstruct", "", Some(Language::Python)),
+            (
+                "deploy",
+                "#!/usr/bin/env python3\nimport sys\n",
+                Some(Language::Python),
+            ),
+            (
+                "check",
+                "#!/usr/bin/python3.12 -u\n",
+                Some(Language::Python),
+            ),
+            ("serve", "#!/usr/bin/env node\n", Some(Language::JavaScript)),
+            (
+                "build",
+                "#!/usr/bin/env -S ts-node --esm\n",
+                Some(Language::TypeScript),
+            ),
+            (
+                "release",
+                "#!/usr/bin/env RUBYOPT=-W0 ruby\n",
+                Some(Language::Ruby),
+            ),
+            ("migrate", "#!/usr/local/bin/php\n", Some(Language::Php)),
+            (
+                "mix-task",
+                "#! /usr/bin/env elixir\n",
+                Some(Language::Elixir),
+            ),
+            (
+                "Setup",
+                "#!/usr/bin/env runhaskell\n",
+                Some(Language::Haskell),
+            ),
+            ("tool", "#!/usr/bin/env rust-script\n", Some(Language::Rust)),
+            ("install", "#!/bin/sh\n", None),
+            ("LICENSE", "MIT License\n", None),
+            // The extension decides, whatever the first line says
+            ("run.sh", "#!/usr/bin/env python3\n", None),
+            ("data.bin", "", None),
+        ];
+        for (name, content, expected) in cases {
+            let path = temp_dir.path().join(name);
+            fs::write(&path, content).unwrap();
+            assert_eq!(Language::from_path(&path), *expected, "{name}");
+        }
+        // Shebangs on their own, and files that aren't there
+        assert_eq!(
+            Language::from_shebang("#!/usr/bin/env pypy3"),
+            Some(Language::Python)
+        );
+        assert_eq!(Language::from_shebang("# not a shebang"), None);
+        assert_eq!(Language::from_path(&temp_dir.path().join("missing")), None);
+    }
+
     #[test]
     fn test_language_from_extension_case_insensitive() {
         // Test uppercase extensions - only for actually supported languages
@@ -1463,6 +2378,15 @@ mod tests {
         assert_eq!(Language::Java.to_string(), "java");
     }
 
+    #[test]
+    fn test_language_from_str_round_trips_display() {
+        for name in ["rust", "csharp", "markdown", "pdf"] {
+            assert_eq!(name.parse::<Language>().unwrap().to_string(), name);
+        }
+        assert_eq!("Rust".parse::<Language>().unwrap(), Language::Rust);
+        assert!("rs".parse::<Language>().is_err());
+    }
+
     #[test]
     fn test_create_ckignore_if_missing() {
         let temp_dir = TempDir::new().unwrap();
@@ -1847,4 +2771,36 @@ mod tests {
             "a marker for a different root must be rejected"
         );
     }
+
+    #[test]
+    fn test_line_ranges_keep_overlapping_spans() {
+        assert_eq!(LineRanges::parse_spec("10-20").unwrap(), (10, 20));
+        assert_eq!(LineRanges::parse_spec("7").unwrap(), (7, 7));
+        for malformed in ["", "0-3", "9-4", "a-b", "3-", "-3"] {
+            assert!(LineRanges::parse_spec(malformed).is_err(), "{malformed}");
+        }
+
+        let temp_dir = TempDir::new().unwrap();
+        let file = temp_dir.path().join("lib.rs");
+        let other = temp_dir.path().join("main.rs");
+        fs::write(&file, "").unwrap();
+        let mut ranges = LineRanges::default();
+        ranges.insert(&file, (10, 20));
+        let span = |line_start, line_end| Span {
+            byte_start: 0,
+            byte_end: 0,
+            line_start,
+            line_end,
+        };
+        assert!(ranges.admits(&file, &span(5, 10)));
+        assert!(ranges.admits(&file, &span(15, 15)));
+        assert!(!ranges.admits(&file, &span(21, 30)));
+        // The same file reached through another path form
+        let dotted = temp_dir.path().join(".").join("lib.rs");
+        assert!(!ranges.admits(&dotted, &span(1, 9)));
+        // Files without ranges are searched whole
+        assert!(ranges.admits(&other, &span(1, 9)));
+        ranges.remove(&file);
+        assert!(ranges.admits(&file, &span(1, 9)));
+    }
 }
@@ -0,0 +1,116 @@
+//! Jupyter notebooks: `.ipynb` files are JSON, and their source is buried in
+//! escaped string arrays. Indexing works on the cells instead, extracted to
+//! the content cache like PDF text (see [`crate::pdf::get_content_cache_path`])
+//! in the "percent" format editors and jupytext read: each code or markdown
+//! cell is a `# %% [code] cell N` header line followed by its source.
+//! Cells are numbered from 1 in notebook order, counting raw cells, which
+//! are left out.
+
+use std::path::Path;
+
+use serde::Deserialize;
+
+const CELL_HEADER_PREFIX: &str = "# %% [";
+
+pub fn is_notebook_file(path: &Path) -> bool {
+    path.extension()
+        .and_then(|ext| ext.to_str())
+        .is_some_and(|ext| ext.eq_ignore_ascii_case("ipynb"))
+}
+
+#[derive(Deserialize)]
+struct Notebook {
+    #[serde(default)]
+    cells: Vec<Cell>,
+}
+
+#[derive(Deserialize)]
+struct Cell {
+    cell_type: String,
+    #[serde(default)]
+    source: Source,
+}
+
+/// nbformat allows a cell's source as one string or as a list of lines
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum Source {
+    Text(String),
+    Lines(Vec<String>),
+}
+
+impl Default for Source {
+    fn default() -> Self {
+        Source::Text(String::new())
+    }
+}
+
+/// The code and markdown cells of the notebook `json`, one header line and
+/// then the cell's source per cell.
+pub fn extract_cells(json: &str) -> crate::Result<String> {
+    let notebook: Notebook = serde_json::from_str(json)?;
+    let mut text = String::new();
+    for (index, cell) in notebook.cells.iter().enumerate() {
+        if !matches!(cell.cell_type.as_str(), "code" | "markdown") {
+            continue;
+        }
+        text.push_str(&format!(
+            "{CELL_HEADER_PREFIX}{}] cell {}\n",
+            cell.cell_type,
+            index + 1
+        ));
+        match &cell.source {
+            Source::Text(source) => text.push_str(source),
+            Source::Lines(lines) => lines.iter().for_each(|line| text.push_str(line)),
+        }
+        if !text.ends_with('\n') {
+            text.push('\n');
+        }
+    }
+    Ok(text)
+}
+
+/// The cell number a header line of extracted text starts.
+pub fn cell_header(line: &str) -> Option<usize> {
+    let (_, number) = line
+        .strip_prefix(CELL_HEADER_PREFIX)?
+        .split_once("] cell ")?;
+    number.trim_end().parse().ok()
+}
+
+/// The cell that 1-based `line` of extracted `text` belongs to.
+pub fn cell_at_line(text: &str, line: usize) -> Option<usize> {
+    text.lines().take(line).filter_map(cell_header).last()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_extract_cells_and_map_lines_back() {
+        let json = r##"{
+          "cells": [
+            {"cell_type": "markdown", "metadata": {}, "source": ["# Load\n", "Read the CSV"]},
+            {"cell_type": "raw", "metadata": {}, "source": "skipped"},
+            {"cell_type": "code", "metadata": {}, "outputs": [], "source": "import pandas as pd\ndf = pd.read_csv('x.csv')"}
+          ],
+          "metadata": {}, "nbformat": 4, "nbformat_minor": 5
+        }"##;
+        let text = extract_cells(json).unwrap();
+        assert_eq!(
+            text,
+            "# %% [markdown] cell 1\n# Load\nRead the CSV\n\
+             # %% [code] cell 3\nimport pandas as pd\ndf = pd.read_csv('x.csv')\n"
+        );
+        // The markdown heading inside cell 1 isn't mistaken for a header
+        assert_eq!(cell_header("# Load"), None);
+        assert_eq!(cell_at_line(&text, 3), Some(1));
+        assert_eq!(cell_at_line(&text, 4), Some(3));
+        assert_eq!(cell_at_line(&text, 6), Some(3));
+
+        assert!(is_notebook_file(Path::new("analysis.IPYNB")));
+        assert!(!is_notebook_file(Path::new("analysis.py")));
+        assert!(extract_cells("not json").is_err());
+    }
+}
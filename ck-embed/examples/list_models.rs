@@ -1,6 +1,7 @@
 #[cfg(feature = "fastembed")]
 use fastembed::EmbeddingModel;
 
+#[allow(clippy::needless_return)]
 fn main() {
     #[cfg(not(feature = "fastembed"))]
     {
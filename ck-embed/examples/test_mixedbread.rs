@@ -5,6 +5,7 @@ use ck_embed::reranker::create_reranker;
 #[cfg(feature = "mixedbread")]
 use ck_models::{ModelRegistry, RerankModelRegistry};
 
+#[allow(clippy::needless_return)]
 fn main() {
     #[cfg(not(feature = "mixedbread"))]
     {
@@ -1,6 +1,7 @@
 #[cfg(feature = "fastembed")]
 use ck_embed::create_reranker;
 
+#[allow(clippy::needless_return)]
 fn main() {
     #[cfg(not(feature = "fastembed"))]
     {
@@ -0,0 +1,121 @@
+//! Progress reporting for model downloads.
+//!
+//! A model load reports to the callback its caller passed. Loads that get
+//! none, such as the ones indexing and search make, report to the
+//! process-wide reporter instead, and only when the model isn't cached yet:
+//! a front end installs it once to show every download without hearing
+//! about every load.
+
+use std::sync::{Arc, RwLock};
+
+/// What a model load reports while it runs.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum DownloadProgress {
+    /// Status text, such as which model or file is being downloaded
+    Message(String),
+    /// Bytes of the file being downloaded received so far, out of `total`.
+    /// Providers that don't expose byte counts send only messages.
+    Bytes { received: u64, total: u64 },
+    /// The model is downloaded and loaded
+    Finished,
+}
+
+pub type ModelDownloadCallback = Box<dyn Fn(DownloadProgress) + Send + Sync>;
+
+type SharedCallback = Arc<dyn Fn(DownloadProgress) + Send + Sync>;
+
+static REPORTER: RwLock<Option<SharedCallback>> = RwLock::new(None);
+
+/// Send the progress of downloads made without their own callback to
+/// `reporter`, or stop reporting them with `None`.
+pub fn set_download_reporter(reporter: Option<ModelDownloadCallback>) {
+    if let Ok(mut current) = REPORTER.write() {
+        *current = reporter.map(Arc::from);
+    }
+}
+
+/// The callback loading `model_name` reports to: `callback`, or else the
+/// process-wide reporter when the model has to be downloaded.
+#[cfg_attr(
+    not(any(feature = "fastembed", feature = "mixedbread")),
+    allow(dead_code)
+)]
+pub(crate) fn progress_for(
+    callback: Option<ModelDownloadCallback>,
+    model_name: &str,
+) -> Option<ModelDownloadCallback> {
+    if callback.is_some() {
+        return callback;
+    }
+    let reporter = REPORTER.read().ok()?.clone()?;
+    if crate::model_cache::is_model_cached(model_name) {
+        return None;
+    }
+    Some(Box::new(move |progress| reporter(progress)))
+}
+
+/// Forwards the byte counts of a Hugging Face Hub download to a callback.
+#[cfg(feature = "mixedbread")]
+pub(crate) struct HubProgress<'a> {
+    callback: &'a (dyn Fn(DownloadProgress) + Send + Sync),
+    received: u64,
+    total: u64,
+}
+
+#[cfg(feature = "mixedbread")]
+impl<'a> HubProgress<'a> {
+    pub(crate) fn new(callback: &'a (dyn Fn(DownloadProgress) + Send + Sync)) -> Self {
+        Self {
+            callback,
+            received: 0,
+            total: 0,
+        }
+    }
+}
+
+#[cfg(feature = "mixedbread")]
+impl hf_hub::api::Progress for HubProgress<'_> {
+    fn init(&mut self, size: usize, filename: &str) {
+        self.total = size as u64;
+        (self.callback)(DownloadProgress::Message(format!("Downloading {filename}")));
+    }
+
+    fn update(&mut self, size: usize) {
+        self.received += size as u64;
+        (self.callback)(DownloadProgress::Bytes {
+            received: self.received,
+            total: self.total,
+        });
+    }
+
+    fn finish(&mut self) {}
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Mutex;
+
+    #[test]
+    fn test_reporter_only_hears_about_uncached_models() {
+        let heard = Arc::new(Mutex::new(Vec::new()));
+        let sink = Arc::clone(&heard);
+        set_download_reporter(Some(Box::new(move |progress| {
+            sink.lock().unwrap().push(progress);
+        })));
+
+        let callback = progress_for(None, "example/model-that-is-never-cached").unwrap();
+        callback(DownloadProgress::Finished);
+        assert_eq!(*heard.lock().unwrap(), [DownloadProgress::Finished]);
+
+        // A callback of the caller's own takes precedence
+        let own: ModelDownloadCallback = Box::new(|_| {});
+        progress_for(Some(own), "example/model-that-is-never-cached").unwrap()(
+            DownloadProgress::Finished,
+        );
+        assert_eq!(heard.lock().unwrap().len(), 1);
+
+        set_download_reporter(None);
+        assert!(progress_for(None, "example/model-that-is-never-cached").is_none());
+    }
+}
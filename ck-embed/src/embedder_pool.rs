@@ -0,0 +1,97 @@
+//! Process-wide pool of loaded query embedders.
+//!
+//! A one-shot search loads its model once and exits, but a long-running
+//! process such as the MCP server would otherwise load the model again for
+//! every query the query cache misses. With the pool enabled, the embedder for
+//! each model is kept after its first use. It is off by default, since a
+//! loaded model holds on to hundreds of megabytes.
+
+use anyhow::Result;
+use ck_models::ModelConfig;
+use std::sync::Mutex;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+use crate::{Embedder, create_embedder_for_config};
+
+static ENABLED: AtomicBool = AtomicBool::new(false);
+static POOL: Mutex<Vec<Box<dyn Embedder>>> = Mutex::new(Vec::new());
+
+/// Keep loaded embedders for the rest of the process, or stop keeping them.
+/// Turning the pool off drops the embedders it holds.
+pub fn set_embedder_pool_enabled(enabled: bool) {
+    ENABLED.store(enabled, Ordering::Relaxed);
+    if !enabled && let Ok(mut pool) = POOL.lock() {
+        pool.clear();
+    }
+}
+
+pub fn embedder_pool_enabled() -> bool {
+    ENABLED.load(Ordering::Relaxed)
+}
+
+/// Names of the models the pool holds loaded, in the order they were loaded.
+pub fn pooled_models() -> Vec<String> {
+    POOL.lock()
+        .map(|pool| {
+            pool.iter()
+                .map(|embedder| embedder.model_name().to_string())
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+/// Run `f` with an embedder for `config`'s model: the pooled one when the
+/// pool is enabled and holds it, otherwise a newly loaded one, which the pool
+/// keeps when enabled. Calls for pooled models take turns.
+pub fn with_embedder<T>(
+    config: &ModelConfig,
+    f: impl FnOnce(&mut dyn Embedder) -> Result<T>,
+) -> Result<T> {
+    if !embedder_pool_enabled() {
+        let mut embedder = create_embedder_for_config(config, None)?;
+        return f(embedder.as_mut());
+    }
+    let mut pool = POOL
+        .lock()
+        .map_err(|_| anyhow::anyhow!("embedder pool lock poisoned"))?;
+    let position = match pool
+        .iter()
+        .position(|embedder| embedder.model_name() == config.name)
+    {
+        Some(position) => position,
+        None => {
+            pool.push(create_embedder_for_config(config, None)?);
+            pool.len() - 1
+        }
+    };
+    f(pool[position].as_mut())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_pool_keeps_one_embedder_per_model_while_enabled() {
+        let registry = ck_models::ModelRegistry::default();
+        let (_, bge) = registry.resolve(Some("bge-small")).unwrap();
+        let (_, minilm) = registry.resolve(Some("minilm")).unwrap();
+        let embed = |config| {
+            with_embedder(config, |embedder| {
+                embedder.embed(&["query".to_string()]).map(drop)
+            })
+        };
+
+        embed(&bge).unwrap();
+        assert!(pooled_models().is_empty());
+
+        set_embedder_pool_enabled(true);
+        embed(&bge).unwrap();
+        embed(&minilm).unwrap();
+        embed(&bge).unwrap();
+        assert_eq!(pooled_models(), [bge.name.clone(), minilm.name.clone()]);
+
+        set_embedder_pool_enabled(false);
+        assert!(pooled_models().is_empty());
+    }
+}
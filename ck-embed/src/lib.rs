@@ -2,12 +2,16 @@ use anyhow::{Result, bail};
 use ck_models::{ModelConfig, ModelRegistry};
 #[cfg(feature = "fastembed")]
 use std::path::Path;
-#[cfg(any(feature = "fastembed", feature = "mixedbread"))]
 use std::path::PathBuf;
 
+pub mod download;
+pub mod embedder_pool;
+pub mod model_cache;
+pub mod query_cache;
 pub mod reranker;
 pub mod tokenizer;
 
+pub use download::{DownloadProgress, ModelDownloadCallback, set_download_reporter};
 pub use reranker::{
     RerankResult, Reranker, create_reranker, create_reranker_for_config,
     create_reranker_with_progress,
@@ -24,12 +28,16 @@ pub trait Embedder: Send + Sync {
     fn dim(&self) -> usize;
     fn model_name(&self) -> &str;
     fn embed(&mut self, texts: &[String]) -> Result<Vec<Vec<f32>>>;
-}
 
-pub type ModelDownloadCallback = Box<dyn Fn(&str) + Send + Sync>;
+    /// Run one tiny embedding so initialization the runtime defers to the
+    /// first call happens now rather than during a search.
+    fn preload(&mut self) -> Result<()> {
+        self.embed(&["warmup".to_string()]).map(drop)
+    }
+}
 
-#[cfg(any(feature = "fastembed", feature = "mixedbread"))]
-pub(crate) fn model_cache_root() -> Result<PathBuf> {
+/// Where embedding models are downloaded to.
+pub fn model_cache_root() -> Result<PathBuf> {
     let base = if let Some(cache_home) = std::env::var_os("XDG_CACHE_HOME") {
         PathBuf::from(cache_home).join("ck")
     } else if let Some(home) = std::env::var_os("HOME") {
@@ -67,14 +75,16 @@ pub fn create_embedder_for_config(
             {
                 return Ok(Box::new(FastEmbedder::new_with_progress(
                     config.name.as_str(),
-                    progress_callback,
+                    download::progress_for(progress_callback, &config.name),
                 )?));
             }
 
             #[cfg(not(feature = "fastembed"))]
             {
                 if let Some(callback) = progress_callback.as_ref() {
-                    callback("fastembed provider unavailable; using dummy embedder");
+                    callback(DownloadProgress::Message(
+                        "fastembed provider unavailable; using dummy embedder".to_string(),
+                    ));
                 }
                 return Ok(Box::new(DummyEmbedder::new_with_model(
                     config.name.as_str(),
@@ -86,7 +96,7 @@ pub fn create_embedder_for_config(
             {
                 return Ok(Box::new(MixedbreadEmbedder::new(
                     config,
-                    progress_callback,
+                    download::progress_for(progress_callback, &config.name),
                 )?));
             }
             #[cfg(not(feature = "mixedbread"))]
@@ -101,6 +111,79 @@ pub fn create_embedder_for_config(
     }
 }
 
+/// A fastembed model ck can load, keyed by the name `ck-models` uses.
+#[cfg_attr(not(feature = "fastembed"), allow(dead_code))]
+struct FastembedModelSpec {
+    name: &'static str,
+    /// Input length fastembed is configured to accept
+    max_length: usize,
+    dimensions: usize,
+}
+
+/// Every fastembed model ck can load. Names missing here are refused rather
+/// than silently swapped for another model; a test checks that each
+/// fastembed model in the `ck-models` registry is listed.
+const FASTEMBED_MODELS: &[FastembedModelSpec] = &[
+    FastembedModelSpec {
+        name: "BAAI/bge-small-en-v1.5",
+        max_length: 512,
+        dimensions: 384,
+    },
+    FastembedModelSpec {
+        name: "sentence-transformers/all-MiniLM-L6-v2",
+        max_length: 512,
+        dimensions: 384,
+    },
+    FastembedModelSpec {
+        name: "nomic-embed-text-v1",
+        max_length: 8192,
+        dimensions: 768,
+    },
+    FastembedModelSpec {
+        name: "nomic-embed-text-v1.5",
+        max_length: 8192,
+        dimensions: 768,
+    },
+    FastembedModelSpec {
+        name: "jina-embeddings-v2-base-code",
+        max_length: 8192,
+        dimensions: 768,
+    },
+    FastembedModelSpec {
+        name: "BAAI/bge-base-en-v1.5",
+        max_length: 512,
+        dimensions: 768,
+    },
+    FastembedModelSpec {
+        name: "BAAI/bge-large-en-v1.5",
+        max_length: 512,
+        dimensions: 1024,
+    },
+];
+
+#[cfg_attr(not(feature = "fastembed"), allow(dead_code))]
+fn fastembed_model_spec(name: &str) -> Option<&'static FastembedModelSpec> {
+    FASTEMBED_MODELS.iter().find(|spec| spec.name == name)
+}
+
+#[cfg(feature = "fastembed")]
+impl FastembedModelSpec {
+    fn embedding_model(&self) -> fastembed::EmbeddingModel {
+        use fastembed::EmbeddingModel;
+
+        match self.name {
+            "BAAI/bge-small-en-v1.5" => EmbeddingModel::BGESmallENV15,
+            "sentence-transformers/all-MiniLM-L6-v2" => EmbeddingModel::AllMiniLML6V2,
+            "nomic-embed-text-v1" => EmbeddingModel::NomicEmbedTextV1,
+            "nomic-embed-text-v1.5" => EmbeddingModel::NomicEmbedTextV15,
+            "jina-embeddings-v2-base-code" => EmbeddingModel::JinaEmbeddingsV2BaseCode,
+            "BAAI/bge-base-en-v1.5" => EmbeddingModel::BGEBaseENV15,
+            "BAAI/bge-large-en-v1.5" => EmbeddingModel::BGELargeENV15,
+            other => unreachable!("fastembed model '{other}' has no EmbeddingModel mapping"),
+        }
+    }
+}
+
 pub struct DummyEmbedder {
     dim: usize,
     model_name: String,
@@ -163,93 +246,62 @@ impl FastEmbedder {
         model_name: &str,
         progress_callback: Option<ModelDownloadCallback>,
     ) -> Result<Self> {
-        use fastembed::{EmbeddingModel, InitOptions, TextEmbedding};
-
-        let model = match model_name {
-            // Current models
-            "BAAI/bge-small-en-v1.5" => EmbeddingModel::BGESmallENV15,
-            "sentence-transformers/all-MiniLM-L6-v2" => EmbeddingModel::AllMiniLML6V2,
-
-            // Enhanced models with longer context
-            "nomic-embed-text-v1" => EmbeddingModel::NomicEmbedTextV1,
-            "nomic-embed-text-v1.5" => EmbeddingModel::NomicEmbedTextV15,
-            "jina-embeddings-v2-base-code" => EmbeddingModel::JinaEmbeddingsV2BaseCode,
+        use fastembed::{InitOptions, TextEmbedding};
 
-            // BGE variants
-            "BAAI/bge-base-en-v1.5" => EmbeddingModel::BGEBaseENV15,
-            "BAAI/bge-large-en-v1.5" => EmbeddingModel::BGELargeENV15,
-
-            // Default to Nomic v1.5 for better performance
-            _ => EmbeddingModel::NomicEmbedTextV15,
-        };
+        let spec = fastembed_model_spec(model_name).ok_or_else(|| {
+            let supported: Vec<&str> = FASTEMBED_MODELS.iter().map(|m| m.name).collect();
+            anyhow::anyhow!(
+                "Unsupported fastembed model '{model_name}'. Supported: {}",
+                supported.join(", ")
+            )
+        })?;
+        let model = spec.embedding_model();
 
         // Configure permanent model cache directory
         let model_cache_dir = model_cache_root()?;
         std::fs::create_dir_all(&model_cache_dir)?;
 
         if let Some(ref callback) = progress_callback {
-            callback(&format!("Initializing model: {model_name}"));
+            callback(DownloadProgress::Message(format!(
+                "Initializing model: {model_name}"
+            )));
 
             // Check if model already exists
             let model_exists = Self::check_model_exists(&model_cache_dir, model_name);
             if !model_exists {
-                callback(&format!(
+                callback(DownloadProgress::Message(format!(
                     "Downloading model {} to {}",
                     model_name,
                     model_cache_dir.display()
-                ));
+                )));
             } else {
-                callback(&format!("Using cached model: {model_name}"));
+                callback(DownloadProgress::Message(format!(
+                    "Using cached model: {model_name}"
+                )));
             }
         }
 
-        // Configure max_length based on model capacity
-        let max_length = match model {
-            // Small models - keep at 512
-            EmbeddingModel::BGESmallENV15 | EmbeddingModel::AllMiniLML6V2 => 512,
-            EmbeddingModel::BGEBaseENV15 => 512,
-
-            // Large context models - use their full capacity!
-            EmbeddingModel::NomicEmbedTextV1 | EmbeddingModel::NomicEmbedTextV15 => 8192,
-            EmbeddingModel::JinaEmbeddingsV2BaseCode => 8192,
-
-            // BGE large can handle more
-            EmbeddingModel::BGELargeENV15 => 512, // Conservative for BGE
-
-            _ => 512, // Safe default
-        };
-
-        let init_options = InitOptions::new(model.clone())
-            .with_show_download_progress(progress_callback.is_some())
+        // fastembed reports no byte counts, only its own progress bar; the
+        // callback's messages stand in for it
+        let init_options = InitOptions::new(model)
+            .with_show_download_progress(false)
             .with_cache_dir(model_cache_dir)
-            .with_max_length(max_length);
+            .with_max_length(spec.max_length);
 
-        let embedding = TextEmbedding::try_new(init_options)?;
+        let embedding = model_cache::retry_after_repair(model_name, || {
+            TextEmbedding::try_new(init_options.clone())
+        })?;
 
         if let Some(ref callback) = progress_callback {
-            callback("Model loaded successfully");
+            callback(DownloadProgress::Message(
+                "Model loaded successfully".to_string(),
+            ));
+            callback(DownloadProgress::Finished);
         }
 
-        let dim = match model {
-            // Small models (384 dimensions)
-            EmbeddingModel::BGESmallENV15 => 384,
-            EmbeddingModel::AllMiniLML6V2 => 384,
-
-            // Large context models (768 dimensions)
-            EmbeddingModel::NomicEmbedTextV1 => 768,
-            EmbeddingModel::NomicEmbedTextV15 => 768,
-            EmbeddingModel::JinaEmbeddingsV2BaseCode => 768,
-            EmbeddingModel::BGEBaseENV15 => 768,
-
-            // Large models (1024 dimensions)
-            EmbeddingModel::BGELargeENV15 => 1024,
-
-            _ => 384, // Default to 384 for BGE default
-        };
-
         Ok(Self {
             model: embedding,
-            dim,
+            dim: spec.dimensions,
             model_name: model_name.to_string(),
         })
     }
@@ -315,6 +367,28 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_every_registry_fastembed_model_is_loadable() {
+        let registry = ModelRegistry::default();
+        for (alias, config) in &registry.models {
+            if config.provider != "fastembed" {
+                continue;
+            }
+            let spec = fastembed_model_spec(&config.name)
+                .unwrap_or_else(|| panic!("registry model '{alias}' has no fastembed mapping"));
+            assert_eq!(spec.dimensions, config.dimensions, "{alias}");
+        }
+        assert!(fastembed_model_spec("unknown/model").is_none());
+    }
+
+    #[cfg(feature = "fastembed")]
+    #[test]
+    fn test_fastembed_specs_map_to_models() {
+        for spec in FASTEMBED_MODELS {
+            let _ = spec.embedding_model();
+        }
+    }
+
     #[test]
     fn test_embedder_trait_object() {
         let mut embedder: Box<dyn Embedder> = Box::new(DummyEmbedder::new());
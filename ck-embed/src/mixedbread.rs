@@ -1,14 +1,16 @@
 use std::path::PathBuf;
 
 use anyhow::{Context, Result, anyhow};
-use hf_hub::{Repo, RepoType, api::sync::ApiBuilder};
+use hf_hub::{Cache, Repo, RepoType, api::sync::ApiBuilder};
 use ndarray::{Array2, ArrayView, ArrayViewD, Axis, Ix1, Ix2, Ix3};
 use ort::session::{Session, builder::GraphOptimizationLevel};
 use ort::value::Value;
 use tokenizers::{EncodeInput, Tokenizer};
 
 use crate::{
-    Embedder, ModelDownloadCallback, model_cache_root,
+    DownloadProgress, Embedder, ModelDownloadCallback,
+    download::HubProgress,
+    model_cache, model_cache_root,
     reranker::{RerankModelDownloadCallback, RerankResult, Reranker},
 };
 use ck_models::{ModelConfig, RerankModelConfig};
@@ -32,28 +34,26 @@ impl MixedbreadEmbedder {
         config: &ModelConfig,
         progress_callback: Option<ModelDownloadCallback>,
     ) -> Result<Self> {
-        if let Some(cb) = progress_callback.as_ref() {
-            cb(&format!(
+        let progress = progress_callback.as_deref();
+        if let Some(cb) = progress {
+            cb(DownloadProgress::Message(format!(
                 "Downloading Mixedbread embedding model ({}) if needed...",
                 config.name
-            ));
+            )));
         }
 
-        let (model_path, tokenizer_path) =
-            download_assets(&config.name, EMBED_MODEL_PATH, EMBED_TOKENIZER_PATH)?;
-
-        if let Some(cb) = progress_callback.as_ref() {
-            cb("Loading Mixedbread embedder session...");
+        let (session, tokenizer) = model_cache::retry_after_repair(&config.name, || {
+            load_session(
+                &config.name,
+                EMBED_MODEL_PATH,
+                EMBED_TOKENIZER_PATH,
+                progress,
+            )
+        })?;
+        if let Some(cb) = progress {
+            cb(DownloadProgress::Finished);
         }
 
-        let session = Session::builder()?
-            .with_optimization_level(GraphOptimizationLevel::Level3)?
-            .with_intra_threads(num_cpus::get().max(1))?
-            .commit_from_file(&model_path)?;
-
-        let tokenizer =
-            Tokenizer::from_file(tokenizer_path).map_err(|e| anyhow!("Tokenizer error: {e}"))?;
-
         let requires_token_type_ids = session
             .inputs()
             .iter()
@@ -210,28 +210,26 @@ impl MixedbreadReranker {
         config: &RerankModelConfig,
         progress_callback: Option<RerankModelDownloadCallback>,
     ) -> Result<Self> {
-        if let Some(cb) = progress_callback.as_ref() {
-            cb(&format!(
+        let progress = progress_callback.as_deref();
+        if let Some(cb) = progress {
+            cb(DownloadProgress::Message(format!(
                 "Downloading Mixedbread reranker model ({}) if needed...",
                 config.name
-            ));
+            )));
         }
 
-        let (model_path, tokenizer_path) =
-            download_assets(&config.name, RERANK_MODEL_PATH, RERANK_TOKENIZER_PATH)?;
-
-        if let Some(cb) = progress_callback.as_ref() {
-            cb("Loading Mixedbread reranker session...");
+        let (session, tokenizer) = model_cache::retry_after_repair(&config.name, || {
+            load_session(
+                &config.name,
+                RERANK_MODEL_PATH,
+                RERANK_TOKENIZER_PATH,
+                progress,
+            )
+        })?;
+        if let Some(cb) = progress {
+            cb(DownloadProgress::Finished);
         }
 
-        let session = Session::builder()?
-            .with_optimization_level(GraphOptimizationLevel::Level3)?
-            .with_intra_threads(num_cpus::get().max(1))?
-            .commit_from_file(&model_path)?;
-
-        let tokenizer =
-            Tokenizer::from_file(tokenizer_path).map_err(|e| anyhow!("Tokenizer error: {e}"))?;
-
         let requires_token_type_ids = session
             .inputs()
             .iter()
@@ -379,31 +377,84 @@ fn normalize_row(row: ArrayView<'_, f32, Ix1>, dim: usize) -> Vec<f32> {
     values
 }
 
+/// Byte-level progress sink for the files a load downloads.
+type ProgressSink<'a> = Option<&'a (dyn Fn(DownloadProgress) + Send + Sync)>;
+
+fn load_session(
+    model_id: &str,
+    model_path: &str,
+    tokenizer_path: &str,
+    progress: ProgressSink,
+) -> Result<(Session, Tokenizer)> {
+    let (model_path, tokenizer_path) =
+        download_assets(model_id, model_path, tokenizer_path, progress)?;
+
+    let session = Session::builder()?
+        .with_optimization_level(GraphOptimizationLevel::Level3)?
+        .with_intra_threads(num_cpus::get().max(1))?
+        .commit_from_file(&model_path)?;
+
+    let tokenizer =
+        Tokenizer::from_file(tokenizer_path).map_err(|e| anyhow!("Tokenizer error: {e}"))?;
+    Ok((session, tokenizer))
+}
+
+/// Fetch the model and tokenizer, downloading whatever isn't cached. Freshly
+/// downloaded files are checked against their checksums; broken ones are
+/// removed and fetched once more. Files that were already cached are checked
+/// only if loading them fails (see [`crate::model_cache::retry_after_repair`]).
 fn download_assets(
     model_id: &str,
     model_path: &str,
     tokenizer_path: &str,
+    progress: ProgressSink,
 ) -> Result<(PathBuf, PathBuf)> {
     let cache_dir = model_cache_root()?;
     std::fs::create_dir_all(&cache_dir)?;
 
+    let cached = Cache::new(cache_dir.clone()).repo(main_revision(model_id));
+    let was_cached = cached.get(model_path).is_some() && cached.get(tokenizer_path).is_some();
+
+    let assets = fetch_assets(&cache_dir, model_id, model_path, tokenizer_path, progress)?;
+    if was_cached {
+        return Ok(assets);
+    }
+    let repo_dir = cache_dir.join(format!("models--{}", model_id.replace('/', "--")));
+    let check = model_cache::check_model_repo(&repo_dir)?;
+    if check.is_ok() {
+        return Ok(assets);
+    }
+    model_cache::remove_broken_files(&check)?;
+    fetch_assets(&cache_dir, model_id, model_path, tokenizer_path, progress)
+}
+
+fn main_revision(model_id: &str) -> Repo {
+    Repo::with_revision(model_id.to_string(), RepoType::Model, "main".to_string())
+}
+
+fn fetch_assets(
+    cache_dir: &std::path::Path,
+    model_id: &str,
+    model_path: &str,
+    tokenizer_path: &str,
+    progress: ProgressSink,
+) -> Result<(PathBuf, PathBuf)> {
     let api = ApiBuilder::new()
-        .with_cache_dir(cache_dir)
+        .with_cache_dir(cache_dir.to_path_buf())
         .build()
         .context("Failed to initialize Hugging Face Hub client")?;
+    let repo = api.repo(main_revision(model_id));
+    let cached = Cache::new(cache_dir.to_path_buf()).repo(main_revision(model_id));
+    let fetch = |file: &str| match progress {
+        Some(callback) if cached.get(file).is_none() => {
+            repo.download_with_progress(file, HubProgress::new(callback))
+        }
+        _ => repo.get(file),
+    };
 
-    let repo = Repo::with_revision(model_id.to_string(), RepoType::Model, "main".to_string());
-    let tokenizer = api
-        .repo(Repo::with_revision(
-            model_id.to_string(),
-            RepoType::Model,
-            "main".to_string(),
-        ))
-        .get(tokenizer_path)
+    let tokenizer = fetch(tokenizer_path)
         .with_context(|| format!("Failed to download tokenizer for {model_id}"))?;
-    let model = api
-        .repo(repo)
-        .get(model_path)
+    let model = fetch(model_path)
         .with_context(|| format!("Failed to download ONNX model for {model_id}"))?;
 
     Ok((model, tokenizer))
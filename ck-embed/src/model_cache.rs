@@ -0,0 +1,336 @@
+//! Integrity checks for downloaded model files.
+//!
+//! Models are cached in the Hugging Face hub layout: each repository gets a
+//! `models--<org>--<name>` directory whose `blobs/` hold the file contents and
+//! whose `snapshots/<revision>/` link to them by their original paths. Large
+//! files such as ONNX weights are stored under their SHA-256, so a truncated
+//! or corrupted download shows up when the blob is hashed; anything else in
+//! `blobs/` that isn't named by a hash is a download that never finished.
+//! Broken blobs are removed together with the links to them, and the next
+//! load fetches them again.
+
+use anyhow::Result;
+use sha2::{Digest, Sha256};
+use std::fmt;
+use std::fs;
+use std::io::Read;
+use std::path::{Path, PathBuf};
+
+/// Every directory models are downloaded to: embedding models and rerankers.
+pub fn model_cache_roots() -> Result<Vec<PathBuf>> {
+    let models = crate::model_cache_root()?;
+    let rerankers = models.with_file_name("rerankers");
+    Ok(vec![models, rerankers])
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BrokenReason {
+    /// A temporary file left behind by an interrupted download
+    Partial,
+    /// The content no longer hashes to the name it was stored under
+    HashMismatch,
+    Empty,
+}
+
+impl fmt::Display for BrokenReason {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            BrokenReason::Partial => "partial download",
+            BrokenReason::HashMismatch => "checksum mismatch",
+            BrokenReason::Empty => "empty file",
+        })
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct BrokenFile {
+    pub path: PathBuf,
+    pub reason: BrokenReason,
+}
+
+/// The result of checking one cached model repository.
+#[derive(Debug, Clone)]
+pub struct ModelCacheCheck {
+    /// Repository id, e.g. `Xenova/bge-small-en-v1.5`
+    pub repo: String,
+    pub dir: PathBuf,
+    /// Complete blobs that were checked and found intact
+    pub files_checked: usize,
+    pub broken: Vec<BrokenFile>,
+}
+
+impl ModelCacheCheck {
+    pub fn is_ok(&self) -> bool {
+        self.broken.is_empty()
+    }
+}
+
+/// Check every model repository under `cache_root`, in name order. A missing
+/// root has nothing to check.
+pub fn check_model_cache(cache_root: &Path) -> Result<Vec<ModelCacheCheck>> {
+    let Ok(entries) = fs::read_dir(cache_root) else {
+        return Ok(Vec::new());
+    };
+    let mut dirs: Vec<PathBuf> = entries
+        .filter_map(|entry| entry.ok().map(|e| e.path()))
+        .filter(|dir| dir.is_dir() && repo_id(dir).is_some())
+        .collect();
+    dirs.sort();
+    dirs.iter().map(|dir| check_model_repo(dir)).collect()
+}
+
+/// Check the blobs of one `models--<org>--<name>` directory.
+pub fn check_model_repo(dir: &Path) -> Result<ModelCacheCheck> {
+    let mut check = ModelCacheCheck {
+        repo: repo_id(dir).unwrap_or_default(),
+        dir: dir.to_path_buf(),
+        files_checked: 0,
+        broken: Vec::new(),
+    };
+    let Ok(entries) = fs::read_dir(dir.join("blobs")) else {
+        return Ok(check);
+    };
+    let mut blobs: Vec<PathBuf> = entries
+        .filter_map(|entry| entry.ok().map(|e| e.path()))
+        .filter(|path| path.is_file())
+        .collect();
+    blobs.sort();
+
+    for path in blobs {
+        let name = path
+            .file_name()
+            .and_then(|n| n.to_str())
+            .unwrap_or_default();
+        let reason = if !is_hex(name, 40) && !is_hex(name, 64) {
+            Some(BrokenReason::Partial)
+        } else if fs::metadata(&path)?.len() == 0 {
+            Some(BrokenReason::Empty)
+        } else if is_hex(name, 64) && sha256_hex(&path)? != name.to_ascii_lowercase() {
+            Some(BrokenReason::HashMismatch)
+        } else {
+            None
+        };
+        match reason {
+            Some(reason) => check.broken.push(BrokenFile { path, reason }),
+            None => check.files_checked += 1,
+        }
+    }
+    Ok(check)
+}
+
+/// Delete the broken blobs found by `check` and the snapshot links that now
+/// point nowhere. Returns the number of blobs removed.
+pub fn remove_broken_files(check: &ModelCacheCheck) -> Result<usize> {
+    for file in &check.broken {
+        fs::remove_file(&file.path)?;
+    }
+    if !check.broken.is_empty() {
+        remove_dangling_links(&check.dir.join("snapshots"))?;
+    }
+    Ok(check.broken.len())
+}
+
+/// Check the cached repositories that belong to `model_name` in every cache
+/// root and remove what is broken, so a failed load can be retried with a
+/// fresh download. Returns the number of files removed.
+pub fn repair_cached_model(model_name: &str) -> Result<usize> {
+    let mut removed = 0;
+    for root in model_cache_roots()? {
+        for dir in cached_model_dirs(&root, model_name) {
+            removed += remove_broken_files(&check_model_repo(&dir)?)?;
+        }
+    }
+    Ok(removed)
+}
+
+/// Run `load`, and if it fails while `model_name` has broken files in the
+/// cache, remove them and run it once more to download them again.
+#[cfg_attr(
+    not(any(feature = "fastembed", feature = "mixedbread")),
+    allow(dead_code)
+)]
+pub(crate) fn retry_after_repair<T>(
+    model_name: &str,
+    mut load: impl FnMut() -> Result<T>,
+) -> Result<T> {
+    match load() {
+        Ok(value) => Ok(value),
+        Err(err) => match repair_cached_model(model_name) {
+            Ok(removed) if removed > 0 => {
+                eprintln!(
+                    "Removed {removed} corrupt or partial file(s) for {model_name}; downloading again"
+                );
+                load()
+            }
+            _ => Err(err),
+        },
+    }
+}
+
+/// Whether any cache root holds a repository for `model_name`.
+pub fn is_model_cached(model_name: &str) -> bool {
+    model_cache_roots().is_ok_and(|roots| {
+        roots
+            .iter()
+            .any(|root| !cached_model_dirs(root, model_name).is_empty())
+    })
+}
+
+/// Repository directories under `cache_root` that may hold `model_name`:
+/// the exact repository first, then mirrors of it under another owner (the
+/// fastembed builds of most models are published by `Xenova` or `Qdrant`).
+pub fn cached_model_dirs(cache_root: &Path, model_name: &str) -> Vec<PathBuf> {
+    let exact_dir = format!("models--{}", model_name.replace('/', "--"));
+    let base = model_name
+        .rsplit('/')
+        .next()
+        .unwrap_or(model_name)
+        .to_lowercase();
+
+    let Ok(entries) = fs::read_dir(cache_root) else {
+        return Vec::new();
+    };
+    let mut candidates: Vec<PathBuf> = entries
+        .filter_map(|entry| entry.ok().map(|e| e.path()))
+        .filter(|dir| {
+            let Some(name) = dir.file_name().and_then(|n| n.to_str()) else {
+                return false;
+            };
+            name == exact_dir
+                || name.starts_with("models--")
+                    && name
+                        .rsplit("--")
+                        .next()
+                        .is_some_and(|repo| repo.to_lowercase().starts_with(&base))
+        })
+        .collect();
+    // Exact repo first, then by name for a stable pick
+    candidates.sort_by_key(|dir| (dir.file_name() != Some(exact_dir.as_ref()), dir.clone()));
+    candidates
+}
+
+fn repo_id(dir: &Path) -> Option<String> {
+    let name = dir.file_name()?.to_str()?;
+    Some(name.strip_prefix("models--")?.replacen("--", "/", 1))
+}
+
+fn is_hex(name: &str, len: usize) -> bool {
+    name.len() == len && name.bytes().all(|b| b.is_ascii_hexdigit())
+}
+
+fn sha256_hex(path: &Path) -> Result<String> {
+    let mut file = fs::File::open(path)?;
+    let mut hasher = Sha256::new();
+    let mut buf = vec![0u8; 1 << 16];
+    loop {
+        let read = file.read(&mut buf)?;
+        if read == 0 {
+            break;
+        }
+        hasher.update(&buf[..read]);
+    }
+    Ok(hasher
+        .finalize()
+        .iter()
+        .map(|byte| format!("{byte:02x}"))
+        .collect())
+}
+
+fn remove_dangling_links(dir: &Path) -> Result<()> {
+    let Ok(entries) = fs::read_dir(dir) else {
+        return Ok(());
+    };
+    for entry in entries.flatten() {
+        let path = entry.path();
+        let file_type = entry.file_type()?;
+        if file_type.is_dir() {
+            remove_dangling_links(&path)?;
+        } else if file_type.is_symlink() && !path.exists() {
+            fs::remove_file(&path)?;
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    const WEIGHTS: &[u8] = b"not really onnx";
+
+    fn fake_repo(root: &Path) -> PathBuf {
+        let repo = root.join("models--Xenova--bge-small-en-v1.5");
+        let blobs = repo.join("blobs");
+        let snapshot = repo.join("snapshots").join("abc123").join("onnx");
+        fs::create_dir_all(&blobs).unwrap();
+        fs::create_dir_all(&snapshot).unwrap();
+
+        let digest = format!("{:x}", Sha256::digest(WEIGHTS));
+        fs::write(blobs.join(&digest), WEIGHTS).unwrap();
+        fs::write(blobs.join("a".repeat(40)), b"{\"tokenizer\": true}").unwrap();
+        #[cfg(unix)]
+        std::os::unix::fs::symlink(blobs.join(&digest), snapshot.join("model.onnx")).unwrap();
+        repo
+    }
+
+    #[test]
+    fn test_intact_cache_passes() {
+        let temp_dir = TempDir::new().unwrap();
+        fake_repo(temp_dir.path());
+        let checks = check_model_cache(temp_dir.path()).unwrap();
+        assert_eq!(checks.len(), 1);
+        assert_eq!(checks[0].repo, "Xenova/bge-small-en-v1.5");
+        assert_eq!(checks[0].files_checked, 2);
+        assert!(checks[0].is_ok());
+
+        assert!(
+            check_model_cache(&temp_dir.path().join("missing"))
+                .unwrap()
+                .is_empty()
+        );
+    }
+
+    #[test]
+    fn test_truncated_and_partial_blobs_are_found_and_removed() {
+        let temp_dir = TempDir::new().unwrap();
+        let repo = fake_repo(temp_dir.path());
+        let blobs = repo.join("blobs");
+        let digest = format!("{:x}", Sha256::digest(WEIGHTS));
+        fs::write(blobs.join(&digest), &WEIGHTS[..4]).unwrap();
+        fs::write(blobs.join("f00d.part"), b"half").unwrap();
+
+        let check = check_model_repo(&repo).unwrap();
+        let mut reasons: Vec<BrokenReason> = check.broken.iter().map(|f| f.reason).collect();
+        reasons.sort_by_key(|r| r.to_string());
+        assert_eq!(reasons, [BrokenReason::HashMismatch, BrokenReason::Partial]);
+        assert_eq!(check.files_checked, 1);
+
+        assert_eq!(remove_broken_files(&check).unwrap(), 2);
+        assert!(!blobs.join(&digest).exists());
+        let model_link = repo.join("snapshots/abc123/onnx/model.onnx");
+        assert!(fs::symlink_metadata(model_link).is_err());
+        assert!(check_model_repo(&repo).unwrap().is_ok());
+    }
+
+    #[test]
+    fn test_cached_model_dirs_prefers_exact_repo() {
+        let temp_dir = TempDir::new().unwrap();
+        fake_repo(temp_dir.path());
+        fs::create_dir_all(temp_dir.path().join("models--BAAI--bge-small-en-v1.5")).unwrap();
+        fs::create_dir_all(temp_dir.path().join("models--other--model")).unwrap();
+
+        let dirs = cached_model_dirs(temp_dir.path(), "BAAI/bge-small-en-v1.5");
+        let names: Vec<_> = dirs
+            .iter()
+            .map(|d| d.file_name().unwrap().to_string_lossy().to_string())
+            .collect();
+        assert_eq!(
+            names,
+            [
+                "models--BAAI--bge-small-en-v1.5",
+                "models--Xenova--bge-small-en-v1.5"
+            ]
+        );
+    }
+}
@@ -0,0 +1,167 @@
+//! Process-wide cache of query embeddings.
+//!
+//! Re-running a semantic query with a different threshold or `topk` would
+//! otherwise load the model and embed the same text again. Entries are keyed
+//! by model and query text, the least recently used one is evicted once
+//! [`QUERY_CACHE_CAPACITY`] is reached, and the whole cache is dropped when a
+//! query for a different model is stored. Benchmarks that mean to time the
+//! embedding step turn it off with [`set_query_cache_enabled`].
+
+use anyhow::Result;
+use std::collections::VecDeque;
+use std::sync::Mutex;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+/// Query embeddings kept before the least recently used is evicted.
+pub const QUERY_CACHE_CAPACITY: usize = 64;
+
+static ENABLED: AtomicBool = AtomicBool::new(true);
+static CACHE: Mutex<QueryCache> = Mutex::new(QueryCache::new());
+
+struct QueryCache {
+    model: Option<String>,
+    /// Most recently used last
+    entries: VecDeque<(String, Vec<f32>)>,
+}
+
+impl QueryCache {
+    const fn new() -> Self {
+        Self {
+            model: None,
+            entries: VecDeque::new(),
+        }
+    }
+
+    fn get(&mut self, model: &str, query: &str) -> Option<Vec<f32>> {
+        if self.model.as_deref() != Some(model) {
+            return None;
+        }
+        let position = self.entries.iter().position(|(text, _)| text == query)?;
+        let entry = self.entries.remove(position)?;
+        let embedding = entry.1.clone();
+        self.entries.push_back(entry);
+        Some(embedding)
+    }
+
+    fn insert(&mut self, model: &str, query: &str, embedding: Vec<f32>) {
+        if self.model.as_deref() != Some(model) {
+            self.entries.clear();
+            self.model = Some(model.to_string());
+        }
+        self.entries.retain(|(text, _)| text != query);
+        if self.entries.len() >= QUERY_CACHE_CAPACITY {
+            self.entries.pop_front();
+        }
+        self.entries.push_back((query.to_string(), embedding));
+    }
+
+    fn clear(&mut self) {
+        self.model = None;
+        self.entries.clear();
+    }
+}
+
+/// Turn the cache on or off for the rest of the process. Turning it off
+/// also empties it.
+pub fn set_query_cache_enabled(enabled: bool) {
+    ENABLED.store(enabled, Ordering::Relaxed);
+    if !enabled {
+        clear_query_cache();
+    }
+}
+
+pub fn query_cache_enabled() -> bool {
+    ENABLED.load(Ordering::Relaxed)
+}
+
+/// The embedding stored for `query` under `model`, if any.
+pub fn cached_query_embedding(model: &str, query: &str) -> Option<Vec<f32>> {
+    if !query_cache_enabled() {
+        return None;
+    }
+    CACHE.lock().ok()?.get(model, query)
+}
+
+/// Remember `embedding` as `model`'s embedding of `query`.
+pub fn cache_query_embedding(model: &str, query: &str, embedding: Vec<f32>) {
+    if !query_cache_enabled() {
+        return;
+    }
+    if let Ok(mut cache) = CACHE.lock() {
+        cache.insert(model, query, embedding);
+    }
+}
+
+/// `model`'s embedding of `query`: the cached one, or else the first vector
+/// `embed` returns, which is cached for next time. `None` when `embed`
+/// returns nothing.
+pub fn get_or_embed(
+    model: &str,
+    query: &str,
+    embed: impl FnOnce() -> Result<Vec<Vec<f32>>>,
+) -> Result<Option<Vec<f32>>> {
+    if let Some(embedding) = cached_query_embedding(model, query) {
+        return Ok(Some(embedding));
+    }
+    let Some(embedding) = embed()?.into_iter().next() else {
+        return Ok(None);
+    };
+    cache_query_embedding(model, query, embedding.clone());
+    Ok(Some(embedding))
+}
+
+pub fn clear_query_cache() {
+    if let Ok(mut cache) = CACHE.lock() {
+        cache.clear();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_lru_eviction_and_model_change() {
+        let mut cache = QueryCache::new();
+        for i in 0..QUERY_CACHE_CAPACITY {
+            cache.insert("model-a", &format!("q{i}"), vec![i as f32]);
+        }
+        // Touch the oldest entry so the next insert evicts q1 instead
+        assert_eq!(cache.get("model-a", "q0"), Some(vec![0.0]));
+        cache.insert("model-a", "new", vec![-1.0]);
+        assert_eq!(cache.entries.len(), QUERY_CACHE_CAPACITY);
+        assert!(cache.get("model-a", "q0").is_some());
+        assert!(cache.get("model-a", "q1").is_none());
+
+        // Same text under another model misses, and storing it clears the rest
+        assert!(cache.get("model-b", "q0").is_none());
+        cache.insert("model-b", "q0", vec![9.0]);
+        assert_eq!(cache.entries.len(), 1);
+        assert!(cache.get("model-a", "q2").is_none());
+        assert_eq!(cache.get("model-b", "q0"), Some(vec![9.0]));
+
+        cache.clear();
+        assert!(cache.get("model-b", "q0").is_none());
+    }
+
+    #[test]
+    fn test_get_or_embed_embeds_each_query_once() {
+        let mut calls = 0;
+        let mut embed = |query: &str| {
+            get_or_embed("test-model", query, || {
+                calls += 1;
+                Ok(vec![vec![query.len() as f32]])
+            })
+            .unwrap()
+        };
+        assert_eq!(embed("auth flow"), Some(vec![9.0]));
+        assert_eq!(embed("auth flow"), Some(vec![9.0]));
+        assert_eq!(embed("retry"), Some(vec![5.0]));
+        assert_eq!(calls, 2);
+
+        assert_eq!(
+            get_or_embed("test-model", "", || Ok(Vec::new())).unwrap(),
+            None
+        );
+    }
+}
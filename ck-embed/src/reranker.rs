@@ -19,7 +19,7 @@ pub trait Reranker: Send + Sync {
     fn rerank(&mut self, query: &str, documents: &[String]) -> Result<Vec<RerankResult>>;
 }
 
-pub type RerankModelDownloadCallback = Box<dyn Fn(&str) + Send + Sync>;
+pub type RerankModelDownloadCallback = crate::ModelDownloadCallback;
 
 pub fn create_reranker(model_name: Option<&str>) -> Result<Box<dyn Reranker>> {
     create_reranker_with_progress(model_name, None)
@@ -45,14 +45,16 @@ pub fn create_reranker_for_config(
             {
                 return Ok(Box::new(FastReranker::new_with_progress(
                     config.name.as_str(),
-                    progress_callback,
+                    crate::download::progress_for(progress_callback, &config.name),
                 )?));
             }
 
             #[cfg(not(feature = "fastembed"))]
             {
                 if let Some(callback) = progress_callback.as_ref() {
-                    callback("fastembed reranker unavailable; using dummy reranker");
+                    callback(crate::DownloadProgress::Message(
+                        "fastembed reranker unavailable; using dummy reranker".to_string(),
+                    ));
                 }
                 return Ok(Box::new(DummyReranker::new()));
             }
@@ -62,7 +64,7 @@ pub fn create_reranker_for_config(
             {
                 return Ok(Box::new(MixedbreadReranker::new(
                     config,
-                    progress_callback,
+                    crate::download::progress_for(progress_callback, &config.name),
                 )?));
             }
             #[cfg(not(feature = "mixedbread"))]
@@ -144,29 +146,38 @@ impl FastReranker {
         std::fs::create_dir_all(&model_cache_dir)?;
 
         if let Some(ref callback) = progress_callback {
-            callback(&format!("Initializing reranker model: {model_name}"));
+            callback(crate::DownloadProgress::Message(format!(
+                "Initializing reranker model: {model_name}"
+            )));
 
             // Check if model already exists
             let model_exists = Self::check_model_exists(&model_cache_dir, model_name);
             if !model_exists {
-                callback(&format!(
+                callback(crate::DownloadProgress::Message(format!(
                     "Downloading reranker model {} to {}",
                     model_name,
                     model_cache_dir.display()
-                ));
+                )));
             } else {
-                callback(&format!("Using cached reranker model: {model_name}"));
+                callback(crate::DownloadProgress::Message(format!(
+                    "Using cached reranker model: {model_name}"
+                )));
             }
         }
 
         let init_options = RerankInitOptions::new(model.clone())
-            .with_show_download_progress(progress_callback.is_some())
+            .with_show_download_progress(false)
             .with_cache_dir(model_cache_dir);
 
-        let reranker = TextRerank::try_new(init_options)?;
+        let reranker = crate::model_cache::retry_after_repair(model_name, || {
+            TextRerank::try_new(init_options.clone())
+        })?;
 
         if let Some(ref callback) = progress_callback {
-            callback("Reranker model loaded successfully");
+            callback(crate::DownloadProgress::Message(
+                "Reranker model loaded successfully".to_string(),
+            ));
+            callback(crate::DownloadProgress::Finished);
         }
 
         Ok(Self {
@@ -1,10 +1,32 @@
-//use anyhow::Result;
+use std::path::{Path, PathBuf};
 
-/// Simple token estimation for code and text
-/// This is a rough approximation since we don't have access to the actual model tokenizer
+/// Token counting for code and text.
+///
+/// When the model's Hugging Face `tokenizer.json` is already in the model
+/// cache, [`TokenEstimator::exact_tokens`] counts with it; otherwise callers
+/// fall back to a character-based estimate. [`TokenEstimator::count_tokens`]
+/// does both in that order.
 pub struct TokenEstimator;
 
 impl TokenEstimator {
+    /// Count tokens with `model_name`'s own tokenizer, or `None` when that
+    /// tokenizer isn't in the model cache (nothing is downloaded here) or
+    /// tokenizer support isn't compiled in.
+    pub fn exact_tokens(text: &str, model_name: &str) -> Option<usize> {
+        if text.is_empty() {
+            return Some(0);
+        }
+        exact::count(text, model_name)
+    }
+
+    /// Exact count for `model_name` when its tokenizer is available, else the
+    /// estimate. With no model the estimate is used.
+    pub fn count_tokens(text: &str, model_name: Option<&str>) -> usize {
+        model_name
+            .and_then(|model| Self::exact_tokens(text, model))
+            .unwrap_or_else(|| Self::estimate_tokens(text))
+    }
+
     /// Estimate token count for text
     /// Based on empirical analysis of code and text tokenization:
     /// - Code: ~4.2 characters per token
@@ -98,6 +120,68 @@ impl TokenEstimator {
     }
 }
 
+/// Locate a cached `tokenizer.json` for `model_name` under `cache_root`,
+/// which uses the Hugging Face hub layout
+/// (`models--<org>--<name>/snapshots/<rev>/tokenizer.json`). The repo may be
+/// a converted copy under another org (e.g. `Xenova/bge-small-en-v1.5` for
+/// `BAAI/bge-small-en-v1.5`), so after the exact repo this accepts any repo
+/// whose name starts with the model's.
+#[cfg_attr(
+    not(any(feature = "fastembed", feature = "mixedbread")),
+    allow(dead_code)
+)]
+fn find_cached_tokenizer(cache_root: &Path, model_name: &str) -> Option<PathBuf> {
+    let candidates = crate::model_cache::cached_model_dirs(cache_root, model_name);
+    candidates.iter().find_map(|dir| {
+        let mut snapshots: Vec<PathBuf> = std::fs::read_dir(dir.join("snapshots"))
+            .ok()?
+            .filter_map(|entry| entry.ok().map(|e| e.path().join("tokenizer.json")))
+            .filter(|path| path.is_file())
+            .collect();
+        snapshots.sort();
+        snapshots.pop()
+    })
+}
+
+#[cfg(any(feature = "fastembed", feature = "mixedbread"))]
+mod exact {
+    use std::collections::HashMap;
+    use std::sync::{Arc, Mutex, OnceLock};
+    use tokenizers::Tokenizer;
+
+    /// Tokenizers loaded so far, keyed by model name; `None` records a model
+    /// whose tokenizer isn't cached so the lookup isn't repeated per chunk.
+    static TOKENIZERS: OnceLock<Mutex<HashMap<String, Option<Arc<Tokenizer>>>>> = OnceLock::new();
+
+    fn load(model_name: &str) -> Option<Arc<Tokenizer>> {
+        let cache = TOKENIZERS.get_or_init(Default::default);
+        let mut cache = cache.lock().unwrap_or_else(|e| e.into_inner());
+        cache
+            .entry(model_name.to_string())
+            .or_insert_with(|| {
+                let cache_root = crate::model_cache_root().ok()?;
+                let path = super::find_cached_tokenizer(&cache_root, model_name)?;
+                Tokenizer::from_file(path).ok().map(Arc::new)
+            })
+            .clone()
+    }
+
+    pub(super) fn count(text: &str, model_name: &str) -> Option<usize> {
+        let tokenizer = load(model_name)?;
+        tokenizer
+            .encode(text, false)
+            .ok()
+            .map(|encoding| encoding.len())
+    }
+}
+
+#[cfg(not(any(feature = "fastembed", feature = "mixedbread")))]
+mod exact {
+    pub(super) fn count(_text: &str, _model_name: &str) -> Option<usize> {
+        None
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -137,6 +221,43 @@ fn main() {
         assert!(TokenEstimator::exceeds_limit(&long_text, 100));
     }
 
+    #[test]
+    fn test_find_cached_tokenizer_prefers_exact_repo() {
+        let cache = tempfile::TempDir::new().unwrap();
+        let write_tokenizer = |repo: &str, rev: &str| {
+            let snapshot = cache.path().join(repo).join("snapshots").join(rev);
+            std::fs::create_dir_all(&snapshot).unwrap();
+            std::fs::write(snapshot.join("tokenizer.json"), "{}").unwrap();
+            snapshot.join("tokenizer.json")
+        };
+        let converted = write_tokenizer("models--Xenova--bge-small-en-v1.5", "abc");
+        std::fs::create_dir_all(cache.path().join("models--other--model/snapshots/x")).unwrap();
+
+        assert_eq!(
+            find_cached_tokenizer(cache.path(), "BAAI/bge-small-en-v1.5"),
+            Some(converted)
+        );
+        let exact = write_tokenizer("models--BAAI--bge-small-en-v1.5", "def");
+        assert_eq!(
+            find_cached_tokenizer(cache.path(), "BAAI/bge-small-en-v1.5"),
+            Some(exact)
+        );
+        assert_eq!(
+            find_cached_tokenizer(cache.path(), "nomic-embed-text-v1.5"),
+            None
+        );
+    }
+
+    #[test]
+    fn test_count_tokens_falls_back_to_estimate() {
+        let text = "fn main() {}";
+        assert_eq!(
+            TokenEstimator::count_tokens(text, None),
+            TokenEstimator::estimate_tokens(text)
+        );
+        assert_eq!(TokenEstimator::exact_tokens("", "any-model"), Some(0));
+    }
+
     #[test]
     fn test_model_limits() {
         assert_eq!(
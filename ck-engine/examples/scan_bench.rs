@@ -0,0 +1,65 @@
+//! Time a regex search over a generated tree on one worker thread and on
+//! one per CPU, the difference `--jobs` makes to an unindexed search.
+//!
+//!     cargo run --release -p ck-engine --example scan_bench [FILES]
+
+use ck_core::SearchOptions;
+use std::time::{Duration, Instant};
+
+const RUNS: usize = 5;
+
+fn main() -> anyhow::Result<()> {
+    let files: usize = std::env::args()
+        .nth(1)
+        .map(|arg| arg.parse())
+        .transpose()?
+        .unwrap_or(20_000);
+
+    let fixture = tempfile::tempdir()?;
+    for file in 0..files {
+        let dir = fixture.path().join(format!("pkg{}", file / 500));
+        std::fs::create_dir_all(&dir)?;
+        let body: String = (0..200)
+            .map(|line| {
+                if (file + line) % 97 == 0 {
+                    format!("fn handle_{line}() {{ retry_with_backoff({file}); }}\n")
+                } else {
+                    format!("fn helper_{line}(value: u64) -> u64 {{ value * {file} }}\n")
+                }
+            })
+            .collect();
+        std::fs::write(dir.join(format!("mod{file}.rs")), body)?;
+    }
+    println!("Fixture: {files} files under {}", fixture.path().display());
+
+    let options = SearchOptions {
+        query: r"retry_\w+\(".to_string(),
+        path: fixture.path().to_path_buf(),
+        ..Default::default()
+    };
+    let cpus = std::thread::available_parallelism().map_or(1, |n| n.get());
+    let runtime = tokio::runtime::Builder::new_current_thread().build()?;
+
+    let mut job_counts = vec![1, cpus];
+    job_counts.dedup();
+
+    let mut baseline = None;
+    for jobs in job_counts {
+        let pool = rayon::ThreadPoolBuilder::new().num_threads(jobs).build()?;
+        let mut best = Duration::MAX;
+        let mut matches = 0;
+        for _ in 0..RUNS {
+            let started = Instant::now();
+            matches = pool
+                .install(|| runtime.block_on(ck_engine::search(&options)))?
+                .len();
+            best = best.min(started.elapsed());
+        }
+        let speedup = baseline.map_or(1.0, |base: Duration| {
+            base.as_secs_f64() / best.as_secs_f64()
+        });
+        baseline.get_or_insert(best);
+        println!("jobs={jobs:<3} {matches} matches, best of {RUNS}: {best:.2?} ({speedup:.1}x)");
+    }
+    Ok(())
+}
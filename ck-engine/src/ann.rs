@@ -0,0 +1,141 @@
+//! Semantic search through the approximate (`--ann`) index, when the index
+//! has a current one and the query can use it.
+//!
+//! Only the `ef_search` nearest chunks are scored, so the query must want a
+//! fixed number of results (`--topk`) and not a percentile of all scores.
+//! Path and file filters are applied to those candidates; when they leave
+//! fewer than the results wanted, the caller falls back to scoring every
+//! chunk rather than return a short page.
+
+use anyhow::Result;
+use ck_core::{SearchOptions, SearchTimings};
+use std::path::{Path, PathBuf};
+use std::time::Instant;
+
+use super::dedup::{self, Candidate, collapse_near_duplicates};
+use super::identifiers::split_query_identifiers;
+use super::rerank::candidate_limit;
+use super::semantic_v3::{PathScope, collect_ranked_results, embed_query};
+use super::{SearchProgressCallback, resolve_model_from_root};
+
+/// Ranked results from the ANN index under `index_root`, or `None` when the
+/// search should score every chunk instead.
+pub(crate) async fn ann_search(
+    options: &SearchOptions,
+    index_root: &Path,
+    progress_callback: Option<&SearchProgressCallback>,
+) -> Result<Option<ck_core::SearchResults>> {
+    let Some(limit) = candidate_limit(options) else {
+        return Ok(None);
+    };
+    if options.exact || options.threshold_percentile.is_some() {
+        return Ok(None);
+    }
+    let Some(ann) = ck_index::load_ann_index(index_root).filter(|ann| !ann.is_empty()) else {
+        return Ok(None);
+    };
+    let resolved_model = resolve_model_from_root(index_root, options.embedding_model.as_deref())?;
+    let model_name = resolved_model.canonical_name();
+    let projection = ck_index::index_projection(index_root);
+    let dimensions = projection
+        .as_ref()
+        .filter(|projection| projection.model == model_name)
+        .map_or(
+            resolved_model.dimensions(),
+            ck_index::Projection::dimensions,
+        );
+    if ann
+        .model
+        .as_deref()
+        .is_some_and(|model| model != model_name)
+        || ann.dimensions != dimensions
+    {
+        return Ok(None);
+    }
+
+    let query_text = if options.split_identifiers {
+        split_query_identifiers(&options.query)
+    } else {
+        options.query.clone()
+    };
+    let mut timings = SearchTimings::default();
+    let Some(mut query_embedding) = embed_query(
+        &resolved_model.config,
+        model_name,
+        &query_text,
+        &mut timings,
+    )?
+    else {
+        return Ok(Some(ck_core::SearchResults {
+            matches: Vec::new(),
+            closest_below_threshold: None,
+            timings,
+            truncated: false,
+            lower_thresholds: Vec::new(),
+        }));
+    };
+    if let Some(projection) = projection
+        && projection.applies_to(model_name, &query_embedding)
+    {
+        query_embedding = projection.project(&query_embedding);
+    }
+    let started = Instant::now();
+
+    let ef_search = options
+        .ef_search
+        .unwrap_or(ck_index::DEFAULT_EF_SEARCH)
+        .max(limit);
+    if let Some(callback) = progress_callback {
+        callback(&format!(
+            "Searching approximate index of {} chunks (ef_search {ef_search})...",
+            ann.len()
+        ));
+    }
+    let hits = ann.search(&query_embedding, ef_search, ef_search);
+
+    let scope = PathScope::new(&options.path);
+    let include_globs = super::search_include_globs(options)?;
+    let files: Vec<PathBuf> = hits
+        .iter()
+        .map(|hit| index_root.join(&ann.nodes[hit.node].file))
+        .collect();
+    let candidates: Vec<Candidate> = hits
+        .iter()
+        .zip(&files)
+        .filter(|(_, file)| {
+            super::path_matches_include(file, &options.include_patterns)
+                && include_globs.matches(file)
+                && super::modified_since_matches(file, options)
+                && scope.contains(file)
+                && !(options.no_generated && ck_index::is_generated_file(file))
+        })
+        .map(|(hit, file)| {
+            (
+                hit.similarity,
+                file,
+                &ann.nodes[hit.node].span,
+                ann.vector(hit.node),
+            )
+        })
+        .collect();
+    // The graph had more to offer than the filters let through
+    if candidates.len() < limit && hits.len() < ann.len() {
+        tracing::debug!(
+            "Approximate index left {} of {limit} results after filters; scoring every chunk",
+            candidates.len()
+        );
+        return Ok(None);
+    }
+    tracing::debug!(
+        "Scored {} of {} chunks through the approximate index",
+        hits.len(),
+        ann.len()
+    );
+
+    let (similarities, collapsed) = collapse_near_duplicates(options, candidates, limit);
+    let mut results = collect_ranked_results(options, similarities, limit).await?;
+    dedup::annotate(&mut results.matches, &collapsed);
+    timings.scoring = Some(started.elapsed());
+    results.timings = timings;
+    Ok(Some(results))
+}
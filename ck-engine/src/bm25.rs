@@ -0,0 +1,144 @@
+//! BM25 with configurable `k1` and `b` for lexical search.
+//!
+//! tantivy scores with BM25 at fixed parameters (k1 = 1.2, b = 0.75), which
+//! are also ck's defaults. Other values rescore tantivy's hits: each term of
+//! the query contributes
+//!
+//! ```text
+//! boost · idf · tf · (k1 + 1) / (tf + k1 · (1 − b + b · len / avg_len))
+//! ```
+//!
+//! with tantivy's own idf, term frequencies and field lengths. Every term is
+//! scored on its own, so a phrase counts as its words rather than as whole
+//! occurrences of the phrase.
+
+use ck_core::Bm25Params;
+use std::collections::HashMap;
+use std::collections::hash_map::Entry;
+use tantivy::fieldnorm::FieldNormReader;
+use tantivy::postings::{Postings, SegmentPostings};
+use tantivy::query::Query;
+use tantivy::schema::{Field, IndexRecordOption};
+use tantivy::{DocId, DocSet, Score, Searcher, SegmentReader, Term};
+
+/// Rescores documents with [`Bm25Params`]; plugs into
+/// `TopDocs::tweak_score`.
+#[derive(Clone)]
+pub(crate) struct Bm25Rescorer {
+    params: Bm25Params,
+    /// Each distinct term of the query with its weight: idf times boost
+    terms: Vec<(Term, Score)>,
+    /// Average length of each queried field across the index
+    average_lengths: HashMap<Field, Score>,
+}
+
+impl Bm25Rescorer {
+    /// A rescorer for the terms of `query`, a term weighted by `boosts` (by
+    /// its text) where given and 1 otherwise.
+    pub(crate) fn new(
+        searcher: &Searcher,
+        query: &dyn Query,
+        boosts: &HashMap<String, Score>,
+        params: Bm25Params,
+    ) -> tantivy::Result<Self> {
+        let mut query_terms = Vec::new();
+        query.query_terms(&mut |term, _| {
+            if !query_terms.contains(term) {
+                query_terms.push(term.clone());
+            }
+        });
+
+        let total_docs = searcher.num_docs() as Score;
+        let mut terms = Vec::with_capacity(query_terms.len());
+        let mut average_lengths = HashMap::new();
+        for term in query_terms {
+            let field = term.field();
+            if let Entry::Vacant(entry) = average_lengths.entry(field) {
+                let mut total_tokens = 0u64;
+                for segment_reader in searcher.segment_readers() {
+                    total_tokens += segment_reader.inverted_index(field)?.total_num_tokens();
+                }
+                entry.insert(total_tokens as Score / total_docs.max(1.0));
+            }
+            let doc_freq = searcher.doc_freq(&term)? as Score;
+            let boost = term
+                .value()
+                .as_str()
+                .and_then(|text| boosts.get(text))
+                .copied()
+                .unwrap_or(1.0);
+            terms.push((term, idf(doc_freq, total_docs) * boost));
+        }
+        Ok(Self {
+            params,
+            terms,
+            average_lengths,
+        })
+    }
+}
+
+/// tantivy's BM25 idf.
+fn idf(doc_freq: Score, total_docs: Score) -> Score {
+    (1.0 + (total_docs - doc_freq + 0.5) / (doc_freq + 0.5)).ln()
+}
+
+impl tantivy::collector::ScoreTweaker<Score> for Bm25Rescorer {
+    type Child = SegmentBm25;
+
+    fn segment_tweaker(&self, segment_reader: &SegmentReader) -> tantivy::Result<SegmentBm25> {
+        let mut terms = Vec::with_capacity(self.terms.len());
+        for (term, weight) in &self.terms {
+            let field = term.field();
+            let Some(postings) = segment_reader
+                .inverted_index(field)?
+                .read_postings(term, IndexRecordOption::WithFreqs)?
+            else {
+                continue;
+            };
+            terms.push(SegmentTerm {
+                postings,
+                field_lengths: segment_reader.get_fieldnorms_reader(field)?,
+                average_length: self.average_lengths[&field],
+                weight: *weight,
+            });
+        }
+        Ok(SegmentBm25 {
+            params: self.params,
+            terms,
+        })
+    }
+}
+
+/// A query term's postings and lengths within one segment.
+struct SegmentTerm {
+    postings: SegmentPostings,
+    field_lengths: FieldNormReader,
+    average_length: Score,
+    weight: Score,
+}
+
+/// [`Bm25Rescorer`] for one segment, whose documents come in increasing order.
+pub(crate) struct SegmentBm25 {
+    params: Bm25Params,
+    terms: Vec<SegmentTerm>,
+}
+
+impl tantivy::collector::ScoreSegmentTweaker<Score> for SegmentBm25 {
+    fn score(&mut self, doc: DocId, _tantivy_score: Score) -> Score {
+        let Bm25Params { k1, b } = self.params;
+        let mut score = 0.0;
+        for term in &mut self.terms {
+            if term.postings.doc() < doc {
+                term.postings.seek(doc);
+            }
+            if term.postings.doc() != doc {
+                continue;
+            }
+            let tf = term.postings.term_freq() as Score;
+            let length = term.field_lengths.fieldnorm(doc) as Score;
+            let normalization = 1.0 - b + b * length / term.average_length.max(1.0);
+            score += term.weight * tf * (k1 + 1.0) / (tf + k1 * normalization);
+        }
+        score
+    }
+}
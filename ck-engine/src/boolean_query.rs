@@ -0,0 +1,285 @@
+//! Boolean lexical queries: `ck --lex "retry AND backoff NOT test"`.
+//!
+//! A query with a bare `AND`, `OR` or `NOT` (upper case, so prose like
+//! "retry and backoff" stays a bag of terms) is parsed with this grammar:
+//!
+//! ```text
+//! query := and ("OR" and)*
+//! and   := unary (["AND"] unary)*      adjacent operands are ANDed
+//! unary := "NOT" unary | "(" query ")" | word | "\"" words "\""
+//! ```
+//!
+//! `NOT` binds tightest, then `AND`, then `OR`. Quoted phrases match only
+//! where their words appear next to each other. The expression compiles to a
+//! tantivy query, so matching files are still ranked by BM25 over their
+//! positive terms. Unlike a plain query, a malformed boolean query is an
+//! error rather than being parsed leniently.
+
+use ck_core::CkError;
+use tantivy::query::{AllQuery, BooleanQuery, Occur, Query};
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) enum BoolExpr {
+    Word(String),
+    Phrase(String),
+    And(Vec<BoolExpr>),
+    Or(Vec<BoolExpr>),
+    Not(Box<BoolExpr>),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Token {
+    Word(String),
+    Phrase(String),
+    And,
+    Or,
+    Not,
+    Open,
+    Close,
+}
+
+/// True when `query` uses the boolean syntax rather than being a bag of terms.
+pub(crate) fn is_boolean_query(query: &str) -> bool {
+    query
+        .split(|c: char| c.is_whitespace() || c == '(' || c == ')')
+        .any(|word| matches!(word, "AND" | "OR" | "NOT"))
+}
+
+pub(crate) fn parse(query: &str) -> Result<BoolExpr, CkError> {
+    let tokens = tokenize(query)?;
+    let mut parser = Parser { tokens, next: 0 };
+    let expr = parser.or()?;
+    match parser.peek() {
+        None => Ok(expr),
+        Some(Token::Close) => Err(malformed("unmatched ')'")),
+        Some(token) => Err(malformed(&format!("unexpected {}", describe(token)))),
+    }
+}
+
+/// The tantivy query for `expr`, with each word or phrase turned into a
+/// query by `leaf`.
+pub(crate) fn compile(
+    expr: &BoolExpr,
+    leaf: &mut impl FnMut(&str) -> Box<dyn Query>,
+) -> Box<dyn Query> {
+    match expr {
+        BoolExpr::Word(text) | BoolExpr::Phrase(text) => leaf(text),
+        BoolExpr::And(operands) => {
+            let mut clauses: Vec<(Occur, Box<dyn Query>)> = operands
+                .iter()
+                .map(|operand| match operand {
+                    BoolExpr::Not(inner) => (Occur::MustNot, compile(inner, leaf)),
+                    operand => (Occur::Must, compile(operand, leaf)),
+                })
+                .collect();
+            // Exclusions alone match nothing in tantivy; exclude from everything
+            if clauses.iter().all(|(occur, _)| *occur == Occur::MustNot) {
+                clauses.insert(0, (Occur::Must, Box::new(AllQuery)));
+            }
+            Box::new(BooleanQuery::new(clauses))
+        }
+        BoolExpr::Or(operands) => Box::new(BooleanQuery::new(
+            operands
+                .iter()
+                .map(|operand| (Occur::Should, compile(operand, leaf)))
+                .collect(),
+        )),
+        BoolExpr::Not(inner) => Box::new(BooleanQuery::new(vec![
+            (Occur::Must, Box::new(AllQuery)),
+            (Occur::MustNot, compile(inner, leaf)),
+        ])),
+    }
+}
+
+fn malformed(reason: &str) -> CkError {
+    CkError::Search(format!(
+        "Malformed lexical query: {reason}. Boolean queries combine words and \"quoted phrases\" with AND, OR, NOT and parentheses"
+    ))
+}
+
+fn describe(token: &Token) -> String {
+    match token {
+        Token::Word(word) => format!("'{word}'"),
+        Token::Phrase(phrase) => format!("\"{phrase}\""),
+        Token::And => "'AND'".to_string(),
+        Token::Or => "'OR'".to_string(),
+        Token::Not => "'NOT'".to_string(),
+        Token::Open => "'('".to_string(),
+        Token::Close => "')'".to_string(),
+    }
+}
+
+fn tokenize(query: &str) -> Result<Vec<Token>, CkError> {
+    let mut tokens = Vec::new();
+    let mut chars = query.chars().peekable();
+    while let Some(&c) = chars.peek() {
+        match c {
+            c if c.is_whitespace() => {
+                chars.next();
+            }
+            '(' | ')' => {
+                chars.next();
+                tokens.push(if c == '(' { Token::Open } else { Token::Close });
+            }
+            '"' => {
+                chars.next();
+                let mut phrase = String::new();
+                loop {
+                    match chars.next() {
+                        Some('"') => break,
+                        Some(c) => phrase.push(c),
+                        None => return Err(malformed("unterminated quote")),
+                    }
+                }
+                if phrase.trim().is_empty() {
+                    return Err(malformed("empty phrase"));
+                }
+                tokens.push(Token::Phrase(phrase));
+            }
+            _ => {
+                let mut word = String::new();
+                while let Some(&c) = chars.peek() {
+                    if c.is_whitespace() || matches!(c, '(' | ')' | '"') {
+                        break;
+                    }
+                    word.push(c);
+                    chars.next();
+                }
+                tokens.push(match word.as_str() {
+                    "AND" => Token::And,
+                    "OR" => Token::Or,
+                    "NOT" => Token::Not,
+                    _ => Token::Word(word),
+                });
+            }
+        }
+    }
+    Ok(tokens)
+}
+
+struct Parser {
+    tokens: Vec<Token>,
+    next: usize,
+}
+
+impl Parser {
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.next)
+    }
+
+    fn bump(&mut self) -> Option<Token> {
+        let token = self.tokens.get(self.next).cloned();
+        self.next += 1;
+        token
+    }
+
+    fn or(&mut self) -> Result<BoolExpr, CkError> {
+        let mut operands = vec![self.and()?];
+        while self.peek() == Some(&Token::Or) {
+            self.bump();
+            operands.push(self.and()?);
+        }
+        Ok(if operands.len() == 1 {
+            operands.remove(0)
+        } else {
+            BoolExpr::Or(operands)
+        })
+    }
+
+    fn and(&mut self) -> Result<BoolExpr, CkError> {
+        let mut operands = vec![self.unary()?];
+        loop {
+            match self.peek() {
+                Some(Token::And) => {
+                    self.bump();
+                }
+                Some(Token::Word(_) | Token::Phrase(_) | Token::Not | Token::Open) => {}
+                _ => break,
+            }
+            operands.push(self.unary()?);
+        }
+        Ok(if operands.len() == 1 {
+            operands.remove(0)
+        } else {
+            BoolExpr::And(operands)
+        })
+    }
+
+    fn unary(&mut self) -> Result<BoolExpr, CkError> {
+        match self.bump() {
+            Some(Token::Not) => Ok(BoolExpr::Not(Box::new(self.unary()?))),
+            Some(Token::Open) => {
+                let inner = self.or()?;
+                match self.bump() {
+                    Some(Token::Close) => Ok(inner),
+                    _ => Err(malformed("unmatched '('")),
+                }
+            }
+            Some(Token::Word(word)) => Ok(BoolExpr::Word(word)),
+            Some(Token::Phrase(phrase)) => Ok(BoolExpr::Phrase(phrase)),
+            Some(token) => Err(malformed(&format!(
+                "expected a word or phrase, found {}",
+                describe(&token)
+            ))),
+            None => Err(malformed("expected a word or phrase at the end")),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn word(w: &str) -> BoolExpr {
+        BoolExpr::Word(w.to_string())
+    }
+
+    #[test]
+    fn test_boolean_query_detection() {
+        assert!(is_boolean_query("retry AND backoff"));
+        assert!(is_boolean_query("NOT test"));
+        assert!(is_boolean_query("(a OR b)"));
+        assert!(!is_boolean_query("retry and backoff"));
+        assert!(!is_boolean_query("ANDROID ORM"));
+    }
+
+    #[test]
+    fn test_parse_precedence() {
+        assert_eq!(
+            parse("retry AND backoff NOT test").unwrap(),
+            BoolExpr::And(vec![
+                word("retry"),
+                word("backoff"),
+                BoolExpr::Not(Box::new(word("test"))),
+            ])
+        );
+        assert_eq!(
+            parse("a b OR c").unwrap(),
+            BoolExpr::Or(vec![BoolExpr::And(vec![word("a"), word("b")]), word("c")])
+        );
+        assert_eq!(
+            parse("a AND (b OR \"c d\")").unwrap(),
+            BoolExpr::And(vec![
+                word("a"),
+                BoolExpr::Or(vec![word("b"), BoolExpr::Phrase("c d".to_string())]),
+            ])
+        );
+    }
+
+    #[test]
+    fn test_parse_rejects_malformed_queries() {
+        for query in [
+            "retry AND",
+            "OR backoff",
+            "NOT",
+            "a AND AND b",
+            "(a OR b",
+            "a OR b)",
+            "\"open phrase AND b",
+            "\"\" AND a",
+        ] {
+            let err = parse(query).unwrap_err().to_string();
+            assert!(err.contains("Malformed lexical query"), "{query}: {err}");
+        }
+    }
+}
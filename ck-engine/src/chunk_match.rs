@@ -0,0 +1,396 @@
+//! `--on chunks`: regex and lexical search over the chunks the index
+//! recorded for each file instead of its lines or the file as a whole. A
+//! hit is the chunk it falls in, with that chunk's span and symbol, so it
+//! lines up with what semantic search reports for the same code.
+//!
+//! Sidecars store each chunk's span but not its text, which is sliced from
+//! the file as it reads now; a chunk whose span no longer fits the file is
+//! left out, and results from files changed since indexing are flagged
+//! stale like other index-backed results.
+
+use anyhow::Result;
+use ck_core::{CkError, SearchOptions, SearchResult, Span};
+use rayon::prelude::*;
+use std::collections::HashSet;
+use std::fs;
+use std::path::{Path, PathBuf};
+use tantivy::schema::{Field, STORED, Schema, SchemaBuilder, TEXT, Value};
+use tantivy::{Index, TantivyDocument, doc};
+
+use crate::identifiers::identifier_words;
+use crate::semantic_v3::{PathScope, indexed_files, reconstruct_original_path};
+use crate::{RegexScan, SearchRegex};
+
+/// Directory (inside `.ck`) of the lexical index over chunks, next to the
+/// one over whole files.
+pub(crate) const CHUNK_TANTIVY_DIR: &str = "tantivy_chunks";
+
+/// Fingerprint file for [`CHUNK_TANTIVY_DIR`], like `TANTIVY_META_FILE`.
+pub(crate) const CHUNK_TANTIVY_META_FILE: &str = "tantivy_chunks.meta";
+
+/// One chunk of an indexed file, with its text.
+struct IndexedChunk {
+    span: Span,
+    symbol: Option<String>,
+    chunk_hash: Option<String>,
+    text: String,
+}
+
+/// The chunks `sidecar` records for `file`, and whether the file is
+/// generated.
+fn file_chunks(
+    file: &Path,
+    sidecar: &Path,
+    index_root: &Path,
+) -> Result<(Vec<IndexedChunk>, bool)> {
+    let entry = ck_index::load_index_entry(sidecar)?;
+    // Sidecars from before the flag was recorded fall back to the file
+    let generated = entry
+        .generated
+        .unwrap_or_else(|| ck_index::is_generated_file(file));
+    let content = crate::read_file_content(file, index_root)?;
+    let chunks = entry
+        .chunks
+        .into_iter()
+        .filter_map(|chunk| {
+            let text = content
+                .get(chunk.span.byte_start..chunk.span.byte_end)?
+                .to_string();
+            Some(IndexedChunk {
+                symbol: chunk_symbol(&chunk, &text),
+                span: chunk.span,
+                chunk_hash: chunk.chunk_hash,
+                text,
+            })
+        })
+        .collect();
+    Ok((chunks, generated))
+}
+
+/// Name of a function, method or class chunk, as `--group-by-symbol` gives
+/// it: its breadcrumb, or for an unnamed chunk its first line, usually a
+/// signature.
+fn chunk_symbol(chunk: &ck_index::ChunkEntry, text: &str) -> Option<String> {
+    if !matches!(
+        chunk.chunk_type.as_deref(),
+        Some("function" | "method" | "class")
+    ) {
+        return None;
+    }
+    chunk.breadcrumb.clone().or_else(|| {
+        let first_line = text.lines().next().unwrap_or_default();
+        Some(
+            first_line
+                .trim()
+                .trim_end_matches(['{', ':'])
+                .trim_end()
+                .to_string(),
+        )
+    })
+}
+
+/// Root of the index `--on chunks` reads for `options.path`.
+fn chunk_index_root(options: &SearchOptions) -> Result<PathBuf> {
+    crate::find_nearest_index_root(&options.path).ok_or_else(|| {
+        CkError::Index(format!(
+            "--on chunks searches indexed chunks, and {} has no index. Run 'ck --index {}' first",
+            options.path.display(),
+            options.path.display()
+        ))
+        .into()
+    })
+}
+
+/// Indexed files under `index_root` the search covers, with their sidecars:
+/// those under `options.path` (or listed in `explicit_files`) that pass the
+/// include and modification-time filters.
+fn candidate_files(options: &SearchOptions, index_root: &Path) -> Result<Vec<(PathBuf, PathBuf)>> {
+    let scope = PathScope::new(&options.path);
+    let include_globs = crate::search_include_globs(options)?;
+    let listed: HashSet<PathBuf> = options
+        .explicit_files
+        .iter()
+        .map(|file| crate::canonicalize_for_matching(file))
+        .collect();
+    let index_dir = ck_core::index_dir(index_root);
+    Ok(indexed_files(&index_dir, index_root)?
+        .into_iter()
+        .filter(|(file, _)| {
+            let in_scope = if listed.is_empty() {
+                scope.contains(file)
+            } else {
+                listed.contains(&crate::canonicalize_for_matching(file))
+            };
+            in_scope
+                && crate::path_matches_include(file, &options.include_patterns)
+                && include_globs.matches(file)
+                && crate::modified_since_matches(file, options)
+        })
+        .collect())
+}
+
+/// Regex search over the chunks of the indexed files `options` covers: one
+/// result per chunk with a match, or per chunk without one when inverted.
+pub(crate) fn regex_chunk_scan(options: &SearchOptions) -> Result<RegexScan> {
+    let regex = SearchRegex::new(&options.query, options).map_err(CkError::Regex)?;
+    let index_root = chunk_index_root(options)?;
+    let files = candidate_files(options, &index_root)?;
+    let truncated = std::sync::atomic::AtomicBool::new(false);
+
+    let pool = ck_index::WorkerPool::new(options.jobs)?;
+    let results: Vec<Vec<SearchResult>> = pool.install(|| {
+        files
+            .par_iter()
+            .filter(|_| !options.is_cancelled())
+            .filter(|_| {
+                // Files not started by the deadline are left unscanned
+                let past = options.is_past_deadline();
+                if past {
+                    truncated.store(true, std::sync::atomic::Ordering::Relaxed);
+                }
+                !past
+            })
+            .map(
+                |(file, sidecar)| match file_chunks(file, sidecar, &index_root) {
+                    Ok((_, true)) if options.no_generated => Vec::new(),
+                    Ok((chunks, _)) => chunks
+                        .into_iter()
+                        .filter_map(|chunk| match_chunk(&regex, file, chunk, options))
+                        .filter(|result| options.line_ranges.admits(&result.file, &result.span))
+                        .collect(),
+                    Err(e) => {
+                        tracing::debug!("Error searching chunks of {:?}: {}", file, e);
+                        Vec::new()
+                    }
+                },
+            )
+            .collect()
+    });
+    options.check_cancelled()?;
+
+    let mut matches: Vec<SearchResult> = results.into_iter().flatten().collect();
+    // Every match scores 1, so this is file path, then line
+    matches.sort_by(SearchResult::rank_cmp);
+    let mut within_cap = crate::per_file_cap(options);
+    matches.retain(|result| within_cap(&result.file));
+    if let Some(top_k) = options.top_k {
+        matches.truncate(top_k);
+    }
+    Ok(RegexScan {
+        matches,
+        unreadable: Vec::new(),
+        truncated: truncated.into_inner(),
+    })
+}
+
+/// `chunk` as a result when `regex` matches it (or doesn't, inverted). The
+/// preview is the line of the first match, or the chunk's first lines for
+/// an inverted hit; with `full_section` it is the whole chunk.
+fn match_chunk(
+    regex: &SearchRegex,
+    file: &Path,
+    chunk: IndexedChunk,
+    options: &SearchOptions,
+) -> Option<SearchResult> {
+    let found = regex.find(&chunk.text);
+    if found.is_some() == options.invert_match {
+        return None;
+    }
+    let (line, column, line_text) = match found {
+        Some(mat) => {
+            let before = &chunk.text[..mat.start];
+            let line_begin = before.rfind('\n').map_or(0, |i| i + 1);
+            let line_end = chunk.text[mat.start..]
+                .find('\n')
+                .map_or(chunk.text.len(), |i| mat.start + i);
+            (
+                chunk.span.line_start + before.matches('\n').count(),
+                mat.start - line_begin + 1,
+                chunk.text[line_begin..line_end]
+                    .trim_end_matches('\r')
+                    .to_string(),
+            )
+        }
+        None => (
+            chunk.span.line_start,
+            1,
+            chunk.text.lines().take(3).collect::<Vec<_>>().join("\n"),
+        ),
+    };
+    let (preview, preview_line) = if options.full_section {
+        (chunk.text, None)
+    } else {
+        (line_text, Some(line))
+    };
+    Some(SearchResult {
+        file: file.to_path_buf(),
+        span: chunk.span,
+        score: 1.0,
+        preview,
+        lang: ck_core::Language::from_path(file),
+        symbol: chunk.symbol,
+        chunk_hash: chunk.chunk_hash,
+        index_epoch: None,
+        original_score: None,
+        stale: false,
+        root: None,
+        preview_line,
+        duplicates: 0,
+        grouped: 0,
+        column: Some(column),
+        match_line: (!options.invert_match).then_some(line),
+        explanation: None,
+        context_before: None,
+        context_after: None,
+    })
+}
+
+/// Fields the chunk lexical index stores beyond the file index's content,
+/// path and identifiers.
+pub(crate) struct ChunkFields {
+    byte_start: Field,
+    byte_end: Field,
+    line_start: Field,
+    line_end: Field,
+    symbol: Field,
+    chunk_hash: Field,
+    generated: Field,
+}
+
+/// A chunk document's location and labels, read back from the index.
+pub(crate) struct StoredChunk {
+    pub(crate) span: Span,
+    pub(crate) symbol: Option<String>,
+    pub(crate) chunk_hash: Option<String>,
+    pub(crate) generated: bool,
+}
+
+impl ChunkFields {
+    /// Add the fields to `schema_builder`, which must already hold the
+    /// content, path and identifiers fields, so those keep their ids.
+    pub(crate) fn add(schema_builder: &mut SchemaBuilder) -> Self {
+        Self {
+            byte_start: schema_builder.add_u64_field("byte_start", STORED),
+            byte_end: schema_builder.add_u64_field("byte_end", STORED),
+            line_start: schema_builder.add_u64_field("line_start", STORED),
+            line_end: schema_builder.add_u64_field("line_end", STORED),
+            symbol: schema_builder.add_text_field("symbol", STORED),
+            chunk_hash: schema_builder.add_text_field("chunk_hash", STORED),
+            generated: schema_builder.add_bool_field("generated", STORED),
+        }
+    }
+
+    /// The fields of `schema`, or `None` when it indexes whole files.
+    pub(crate) fn of(schema: &Schema) -> Option<Self> {
+        let field = |name| schema.get_field(name).ok();
+        Some(Self {
+            byte_start: field("byte_start")?,
+            byte_end: field("byte_end")?,
+            line_start: field("line_start")?,
+            line_end: field("line_end")?,
+            symbol: field("symbol")?,
+            chunk_hash: field("chunk_hash")?,
+            generated: field("generated")?,
+        })
+    }
+
+    pub(crate) fn read(&self, doc: &TantivyDocument) -> StoredChunk {
+        let number = |field| {
+            doc.get_first(field)
+                .and_then(|value| value.as_u64())
+                .unwrap_or_default() as usize
+        };
+        let text = |field| {
+            doc.get_first(field)
+                .and_then(|value| value.as_str())
+                .map(str::to_string)
+        };
+        StoredChunk {
+            span: Span {
+                byte_start: number(self.byte_start),
+                byte_end: number(self.byte_end),
+                line_start: number(self.line_start),
+                line_end: number(self.line_end),
+            },
+            symbol: text(self.symbol),
+            chunk_hash: text(self.chunk_hash),
+            generated: doc
+                .get_first(self.generated)
+                .and_then(|value| value.as_bool())
+                .unwrap_or_default(),
+        }
+    }
+}
+
+/// (Re)build the chunk lexical index at `tantivy_index_path`: one document
+/// per chunk recorded in `sidecars` (files under `index_dir`). Like
+/// `build_tantivy_index`, callers hold the index write lock and any existing
+/// index is replaced.
+pub(crate) fn build_chunk_tantivy_index(
+    tantivy_index_path: &Path,
+    index_dir: &Path,
+    index_root: &Path,
+    sidecars: &[PathBuf],
+) -> Result<()> {
+    if tantivy_index_path.exists() {
+        fs::remove_dir_all(tantivy_index_path)?;
+    }
+    fs::create_dir_all(tantivy_index_path)?;
+
+    let mut schema_builder = Schema::builder();
+    let content_field = schema_builder.add_text_field("content", TEXT | STORED);
+    let path_field = schema_builder.add_text_field("path", TEXT | STORED);
+    let identifiers_field = schema_builder.add_text_field("identifiers", TEXT);
+    let fields = ChunkFields::add(&mut schema_builder);
+    let schema = schema_builder.build();
+
+    let index = Index::create_in_dir(tantivy_index_path, schema)
+        .map_err(|e| CkError::Index(format!("Failed to create tantivy index: {e}")))?;
+    let mut index_writer = index
+        .writer(50_000_000)
+        .map_err(|e| CkError::Index(format!("Failed to create index writer: {e}")))?;
+
+    for batch in sidecars.chunks(crate::LEXICAL_READ_BATCH) {
+        let docs: Vec<Vec<TantivyDocument>> = batch
+            .par_iter()
+            .filter_map(|sidecar| {
+                let file = reconstruct_original_path(sidecar, index_dir, index_root)?;
+                let (chunks, generated) = file_chunks(&file, sidecar, index_root).ok()?;
+                let path = file.display().to_string();
+                Some(
+                    chunks
+                        .into_iter()
+                        .map(|chunk| {
+                            let mut doc = doc!(
+                                identifiers_field => identifier_words(&chunk.text).join(" "),
+                                content_field => chunk.text,
+                                path_field => path.clone(),
+                                fields.byte_start => chunk.span.byte_start as u64,
+                                fields.byte_end => chunk.span.byte_end as u64,
+                                fields.line_start => chunk.span.line_start as u64,
+                                fields.line_end => chunk.span.line_end as u64,
+                                fields.generated => generated
+                            );
+                            if let Some(symbol) = chunk.symbol {
+                                doc.add_text(fields.symbol, symbol);
+                            }
+                            if let Some(chunk_hash) = chunk.chunk_hash {
+                                doc.add_text(fields.chunk_hash, chunk_hash);
+                            }
+                            doc
+                        })
+                        .collect(),
+                )
+            })
+            .collect();
+        for doc in docs.into_iter().flatten() {
+            index_writer.add_document(doc)?;
+        }
+    }
+
+    index_writer
+        .commit()
+        .map_err(|e| CkError::Index(format!("Failed to commit index: {e}")))?;
+
+    Ok(())
+}
@@ -0,0 +1,128 @@
+//! `--dedup`: collapsing near-identical semantic results.
+//!
+//! Vendored copies and generated files produce chunks whose embeddings are
+//! almost the same, and they would otherwise take several of the `top_k`
+//! places between them. Candidates are walked best first; one whose
+//! embedding is at least `options.dedup` cosine-similar to an already kept
+//! candidate is dropped and counted against it. The walk stops once enough
+//! candidates are kept, so its cost grows with `top_k`, not with the corpus.
+
+use ck_core::{SearchOptions, SearchResult, Span};
+use std::collections::HashMap;
+use std::path::PathBuf;
+
+use crate::semantic_v3::cosine_similarity;
+
+/// Similarity used when `--dedup` is given without a value
+pub const DEFAULT_DEDUP_SIMILARITY: f32 = 0.95;
+
+/// A scored chunk together with its embedding.
+pub(crate) type Candidate<'a> = (f32, &'a PathBuf, &'a Span, &'a [f32]);
+
+/// How many candidates each kept result absorbed, keyed by file and span start.
+pub(crate) type Collapsed = HashMap<(PathBuf, usize), usize>;
+
+/// Sort `candidates` best first and, with `options.dedup`, drop those too
+/// similar to a higher-ranked one until `limit` are kept. Candidates past
+/// that point are left as they are, so score-distribution cutoffs still see
+/// them.
+pub(crate) fn collapse_near_duplicates<'a>(
+    options: &SearchOptions,
+    mut candidates: Vec<Candidate<'a>>,
+    limit: usize,
+) -> (Vec<(f32, &'a PathBuf, &'a Span)>, Collapsed) {
+    candidates.sort_by(|a, b| ck_core::rank_order((a.0, a.1, a.2), (b.0, b.1, b.2)));
+    let mut collapsed = Collapsed::new();
+    let Some(min_similarity) = options.dedup else {
+        let ranked = candidates
+            .into_iter()
+            .map(|(score, file, span, _)| (score, file, span))
+            .collect();
+        return (ranked, collapsed);
+    };
+
+    let mut kept: Vec<Candidate<'a>> = Vec::new();
+    let mut rest = Vec::new();
+    for candidate in candidates {
+        if kept.len() >= limit {
+            rest.push(candidate);
+            continue;
+        }
+        let duplicate_of = kept
+            .iter()
+            .find(|kept| cosine_similarity(kept.3, candidate.3) >= min_similarity);
+        match duplicate_of {
+            Some((_, file, span, _)) => {
+                *collapsed
+                    .entry(((*file).clone(), span.byte_start))
+                    .or_default() += 1;
+            }
+            None => kept.push(candidate),
+        }
+    }
+
+    let ranked = kept
+        .into_iter()
+        .chain(rest)
+        .map(|(score, file, span, _)| (score, file, span))
+        .collect();
+    (ranked, collapsed)
+}
+
+/// Record on each result how many near-duplicates it stands for.
+pub(crate) fn annotate(results: &mut [SearchResult], collapsed: &Collapsed) {
+    if collapsed.is_empty() {
+        return;
+    }
+    for result in results {
+        if let Some(count) = collapsed.get(&(result.file.clone(), result.span.byte_start)) {
+            result.duplicates = *count;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn span(byte_start: usize) -> Span {
+        Span {
+            byte_start,
+            byte_end: byte_start + 10,
+            line_start: 1,
+            line_end: 1,
+        }
+    }
+
+    #[test]
+    fn keeps_best_representative_and_counts_copies() {
+        let vendored = PathBuf::from("vendor/a.rs");
+        let original = PathBuf::from("src/a.rs");
+        let other = PathBuf::from("src/b.rs");
+        let spans = [span(0), span(100), span(200)];
+        let copy = [1.0, 0.0, 0.0];
+        let nearly = [0.99, 0.05, 0.0];
+        let different = [0.0, 1.0, 0.0];
+        let candidates: Vec<Candidate> = vec![
+            (0.70, &vendored, &spans[0], &nearly[..]),
+            (0.80, &original, &spans[1], &copy[..]),
+            (0.60, &other, &spans[2], &different[..]),
+        ];
+
+        let mut options = SearchOptions::default();
+        let (ranked, collapsed) = collapse_near_duplicates(&options, candidates.clone(), 10);
+        assert_eq!(ranked.len(), 3);
+        assert!(collapsed.is_empty());
+
+        options.dedup = Some(DEFAULT_DEDUP_SIMILARITY);
+        let (ranked, collapsed) = collapse_near_duplicates(&options, candidates.clone(), 10);
+        let files: Vec<_> = ranked.iter().map(|(_, file, _)| *file).collect();
+        assert_eq!(files, [&original, &other]);
+        assert_eq!(collapsed.get(&(original.clone(), 100)), Some(&1));
+
+        // Past the limit nothing is compared or dropped
+        let (ranked, collapsed) = collapse_near_duplicates(&options, candidates, 0);
+        assert_eq!(ranked.len(), 3);
+        assert!(collapsed.is_empty());
+    }
+}
@@ -0,0 +1,149 @@
+//! Searching a [`Document`] held in memory instead of files on disk, such
+//! as standard input for `ck PATTERN -`. Regex search scans its lines,
+//! lexical search ranks it in a throwaway in-memory index, and semantic
+//! search embeds its chunks the way `--ephemeral` does; nothing reads or
+//! writes an index.
+
+use anyhow::Result;
+use ck_core::{CkError, Document, MatchOn, SearchMode, SearchOptions, SearchResult};
+use tantivy::schema::{STORED, Schema, TEXT};
+use tantivy::{Index, doc};
+
+use crate::identifiers::identifier_words;
+use crate::semantic_v3::LoadedIndex;
+use crate::{SearchOutcome, SearchProgressCallback, SearchRegex};
+
+/// Search `document` as `options` describes. Only modes that need no index
+/// apply: semantic search must be `--ephemeral`.
+pub(crate) async fn search_document(
+    options: &SearchOptions,
+    document: &Document,
+    progress_callback: Option<SearchProgressCallback>,
+) -> Result<SearchOutcome> {
+    let name = document.name.display();
+    if options.mode == SearchMode::Semantic && !options.ephemeral && options.similar_to.is_none() {
+        return Err(CkError::Search(format!(
+            "Semantic search of {name} needs --ephemeral, which embeds it in memory: it has no index"
+        ))
+        .into());
+    }
+    let needs_index = match options.mode {
+        SearchMode::Hybrid => Some("--hybrid"),
+        SearchMode::Semantic if options.similar_to.is_some() => Some("--similar"),
+        _ if options.match_on == MatchOn::Chunks => Some("--on chunks"),
+        _ => None,
+    };
+    if let Some(search) = needs_index {
+        return Err(CkError::Search(format!(
+            "{search} needs an index, and {name} has none; use regex or --lex search, or --sem --ephemeral"
+        ))
+        .into());
+    }
+    if options.at_ref.is_some() {
+        return Err(CkError::Search(format!("--at can't be used with {name}")).into());
+    }
+
+    // Filters that choose among files on disk don't apply to a document
+    let options = &SearchOptions {
+        include_patterns: Vec::new(),
+        include_globs: Vec::new(),
+        max_depth: None,
+        modified_since: None,
+        no_generated: false,
+        explicit_files: Vec::new(),
+        line_ranges: Default::default(),
+        ..options.clone()
+    };
+    let mut timings = ck_core::SearchTimings::default();
+    let mut results = match options.mode {
+        SearchMode::Regex => {
+            let started = std::time::Instant::now();
+            let matches = regex_document(options, document)?;
+            timings.scoring = Some(started.elapsed());
+            ck_core::SearchResults {
+                matches,
+                closest_below_threshold: None,
+                timings,
+                truncated: false,
+                lower_thresholds: Vec::new(),
+            }
+        }
+        SearchMode::Lexical => {
+            let (matches, truncated) = lexical_document(options, document, &mut timings)?;
+            ck_core::SearchResults {
+                matches,
+                closest_below_threshold: None,
+                timings,
+                truncated,
+                lower_thresholds: Vec::new(),
+            }
+        }
+        SearchMode::Semantic | SearchMode::Hybrid => {
+            let mut index = LoadedIndex::in_memory(options, &[], progress_callback.as_ref())?;
+            index.search_page(options).await?
+        }
+    };
+    let lang = document.language();
+    for result in results
+        .matches
+        .iter_mut()
+        .chain(results.closest_below_threshold.as_mut())
+    {
+        result.lang = lang;
+    }
+    Ok(SearchOutcome {
+        results,
+        index_update: None,
+        unreadable: Vec::new(),
+    })
+}
+
+/// Regex search over the lines of `document`, as over a file's.
+fn regex_document(options: &SearchOptions, document: &Document) -> Result<Vec<SearchResult>> {
+    let regex = SearchRegex::new(&options.query, options).map_err(CkError::Regex)?;
+    let (lines, line_ending_lengths) = crate::split_lines_with_endings(&document.content);
+    let mut results = crate::search_file_in_memory(
+        &regex,
+        &document.name,
+        options,
+        &lines,
+        &None,
+        &line_ending_lengths,
+    )?;
+    if let Some(cap) = options.max_matches_per_file {
+        results.truncate(cap);
+    }
+    if let Some(top_k) = options.top_k {
+        results.truncate(top_k);
+    }
+    Ok(results)
+}
+
+/// Lexical search of `document` through a one-document index in memory.
+fn lexical_document(
+    options: &SearchOptions,
+    document: &Document,
+    timings: &mut ck_core::SearchTimings,
+) -> Result<(Vec<SearchResult>, bool)> {
+    let started = std::time::Instant::now();
+    let mut schema_builder = Schema::builder();
+    let content_field = schema_builder.add_text_field("content", TEXT | STORED);
+    let path_field = schema_builder.add_text_field("path", TEXT | STORED);
+    let identifiers_field = schema_builder.add_text_field("identifiers", TEXT);
+    let index = Index::create_in_ram(schema_builder.build());
+
+    let mut index_writer = index
+        .writer_with_num_threads(1, 15_000_000)
+        .map_err(|e| CkError::Index(format!("Failed to create index writer: {e}")))?;
+    index_writer.add_document(doc!(
+        identifiers_field => identifier_words(&document.content).join(" "),
+        content_field => document.content.to_string(),
+        path_field => document.name.display().to_string()
+    ))?;
+    index_writer
+        .commit()
+        .map_err(|e| CkError::Index(format!("Failed to commit index: {e}")))?;
+    timings.candidate_load = Some(started.elapsed());
+
+    crate::query_lexical_index(options, &index, timings)
+}
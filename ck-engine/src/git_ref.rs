@@ -0,0 +1,327 @@
+//! `--at REF`: searching a git commit, tag or branch without checking it out.
+//!
+//! The ref's tree is read with `git ls-tree` and `git cat-file --batch` and
+//! written to `.ck/refs/<commit>/tree`, which is then indexed and searched
+//! like any other directory. The snapshot and its index are kept, so repeat
+//! queries against the same commit skip the extraction and embedding.
+//! Results are reported as `REF:path`, since the files they point at are not
+//! the ones in the working copy.
+
+use anyhow::Result;
+use ck_core::{CkError, SearchMode, SearchOptions, SearchResult};
+use std::fs;
+use std::io::{BufRead, BufReader, Read, Write};
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+
+use crate::{
+    DetailedIndexingProgressCallback, IndexingProgressCallback, SearchOutcome,
+    SearchProgressCallback, search_single_root,
+};
+
+/// Git's mode for symbolic links, which are not followed in a snapshot
+const SYMLINK_MODE: &str = "120000";
+
+pub(crate) async fn search_at_ref(
+    options: &SearchOptions,
+    git_ref: &str,
+    progress_callback: Option<SearchProgressCallback>,
+    indexing_progress_callback: Option<IndexingProgressCallback>,
+    detailed_indexing_progress_callback: Option<DetailedIndexingProgressCallback>,
+) -> Result<SearchOutcome> {
+    if options.roots.len() > 1 {
+        return Err(CkError::Search("--at searches a single path".to_string()).into());
+    }
+    let repo_root = repo_root(&options.path)?;
+    let commit = resolve_commit(&repo_root, git_ref)?;
+    if let Some(cb) = progress_callback.as_ref() {
+        cb(&format!("Reading {git_ref} ({})...", &commit[..12]));
+    }
+    let snapshot = snapshot_tree(&repo_root, &commit)?;
+
+    let canonical_path = options
+        .path
+        .canonicalize()
+        .unwrap_or_else(|_| options.path.clone());
+    let relative = canonical_path
+        .strip_prefix(&repo_root)
+        .unwrap_or(Path::new(""));
+    let search_path = snapshot.join(relative);
+    if !search_path.exists() {
+        return Err(CkError::Search(format!(
+            "{} does not exist at {git_ref}",
+            relative.display()
+        ))
+        .into());
+    }
+
+    let mut snapshot_options = options.clone();
+    snapshot_options.path = search_path;
+    snapshot_options.at_ref = None;
+    snapshot_options.roots = Vec::new();
+    // Everything in a commit is tracked, and the working copy's .gitignore
+    // (which usually lists .ck/) would otherwise be applied to the snapshot
+    snapshot_options.respect_gitignore = false;
+
+    // Index the snapshot root first so the search below finds this index
+    // rather than the working copy's one further up
+    let index_backed =
+        options.mode != SearchMode::Regex || options.match_on == ck_core::MatchOn::Chunks;
+    if index_backed && !ck_core::index_exists(&snapshot) {
+        let need_embeddings = matches!(options.mode, SearchMode::Semantic | SearchMode::Hybrid);
+        ck_index::smart_update_index_with_resume(
+            &snapshot,
+            &ck_core::FileCollectionOptions::from(&snapshot_options),
+            indexing_progress_callback,
+            detailed_indexing_progress_callback,
+            ck_index::IndexUpdateOptions {
+                compute_embeddings: need_embeddings,
+                model: options.embedding_model.as_deref(),
+                embed_batch_size: options.embed_batch_size,
+                jobs: options.jobs,
+                ..Default::default()
+            },
+        )
+        .await?;
+    }
+
+    let mut outcome = search_single_root(&snapshot_options, progress_callback, None, None).await?;
+
+    let label = |result: &mut SearchResult| {
+        let file = result.file.canonicalize().unwrap_or(result.file.clone());
+        if let Ok(path) = file.strip_prefix(&snapshot) {
+            let path = path.to_string_lossy().replace('\\', "/");
+            result.file = PathBuf::from(format!("{git_ref}:{path}"));
+        }
+    };
+    outcome.results.matches.iter_mut().for_each(label);
+    if let Some(closest) = outcome.results.closest_below_threshold.as_mut() {
+        label(closest);
+    }
+    Ok(outcome)
+}
+
+fn git(repo: &Path) -> Command {
+    let mut command = Command::new("git");
+    command.arg("-C").arg(repo);
+    command
+}
+
+/// Run a git command and return its stdout, or `None` when it fails.
+fn git_output(command: &mut Command) -> Result<Option<String>> {
+    let output = command
+        .stderr(Stdio::null())
+        .output()
+        .map_err(|e| CkError::Search(format!("--at needs git, which could not be run: {e}")))?;
+    Ok(output
+        .status
+        .success()
+        .then(|| String::from_utf8_lossy(&output.stdout).trim().to_string()))
+}
+
+/// Top of the git work tree containing `path`, canonicalized.
+fn repo_root(path: &Path) -> Result<PathBuf> {
+    let dir = if path.is_file() {
+        path.parent().unwrap_or(path)
+    } else {
+        path
+    };
+    let root = git_output(git(dir).args(["rev-parse", "--show-toplevel"]))?.ok_or_else(|| {
+        CkError::Search(format!(
+            "{} is not inside a git repository, which --at needs",
+            path.display()
+        ))
+    })?;
+    Ok(PathBuf::from(&root)
+        .canonicalize()
+        .unwrap_or_else(|_| PathBuf::from(root)))
+}
+
+/// Full hash of the commit `git_ref` names.
+fn resolve_commit(repo_root: &Path, git_ref: &str) -> Result<String> {
+    let spec = format!("{git_ref}^{{commit}}");
+    git_output(git(repo_root).args(["rev-parse", "--verify", "--quiet", &spec]))?
+        .filter(|commit| !commit.is_empty())
+        .ok_or_else(|| CkError::Search(format!("Unknown git ref '{git_ref}'")).into())
+}
+
+/// The extracted tree of `commit`, written on first use.
+fn snapshot_tree(repo_root: &Path, commit: &str) -> Result<PathBuf> {
+    let commit_dir = ck_core::index_dir(repo_root)
+        .join(ck_index::REFS_DIR)
+        .join(commit);
+    let tree = commit_dir.join("tree");
+    if tree.is_dir() {
+        return Ok(tree.canonicalize()?);
+    }
+
+    // Extract next to the final location and rename, so an interrupted
+    // extraction is never mistaken for a complete one
+    let partial = commit_dir.join("tree.partial");
+    if partial.exists() {
+        fs::remove_dir_all(&partial)?;
+    }
+    fs::create_dir_all(&partial)?;
+    extract_tree(repo_root, commit, &partial)?;
+    fs::rename(&partial, &tree)?;
+    Ok(tree.canonicalize()?)
+}
+
+/// Write every file blob of `commit` under `dest`; returns how many.
+fn extract_tree(repo_root: &Path, commit: &str, dest: &Path) -> Result<usize> {
+    let listing = git(repo_root)
+        .args(["ls-tree", "-r", "-z", "--full-tree", commit])
+        .stderr(Stdio::null())
+        .output()?;
+    if !listing.status.success() {
+        return Err(CkError::Search(format!("Could not list the tree of {commit}")).into());
+    }
+
+    // Entries are "<mode> <type> <object>\t<path>\0"
+    let mut blobs = Vec::new();
+    for entry in listing.stdout.split(|&b| b == 0).filter(|e| !e.is_empty()) {
+        let entry = String::from_utf8_lossy(entry);
+        let Some((meta, path)) = entry.split_once('\t') else {
+            continue;
+        };
+        let mut fields = meta.split(' ');
+        if let (Some(mode), Some("blob"), Some(object)) =
+            (fields.next(), fields.next(), fields.next())
+            && mode != SYMLINK_MODE
+        {
+            blobs.push((object.to_string(), path.to_string()));
+        }
+    }
+
+    let mut cat_file = git(repo_root)
+        .args(["cat-file", "--batch"])
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::null())
+        .spawn()?;
+    let mut stdin = cat_file.stdin.take().expect("piped stdin");
+    let objects: Vec<String> = blobs.iter().map(|(object, _)| object.clone()).collect();
+    let writer = std::thread::spawn(move || -> std::io::Result<()> {
+        for object in objects {
+            writeln!(stdin, "{object}")?;
+        }
+        Ok(())
+    });
+
+    let mut stdout = BufReader::new(cat_file.stdout.take().expect("piped stdout"));
+    let mut header = String::new();
+    for (_, path) in &blobs {
+        header.clear();
+        stdout.read_line(&mut header)?;
+        // "<object> blob <size>"
+        let size: usize = header
+            .split_whitespace()
+            .nth(2)
+            .and_then(|size| size.parse().ok())
+            .ok_or_else(|| CkError::Search(format!("Unexpected git output: {header}")))?;
+        let mut content = vec![0u8; size + 1];
+        stdout.read_exact(&mut content)?;
+        content.pop();
+
+        let target = dest.join(path);
+        if let Some(parent) = target.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        fs::write(target, content)?;
+    }
+    writer
+        .join()
+        .map_err(|_| CkError::Search("git cat-file writer panicked".to_string()))??;
+    cat_file.wait()?;
+    Ok(blobs.len())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    fn run_git(dir: &Path, args: &[&str]) {
+        let status = Command::new("git")
+            .args(["-c", "user.name=ck", "-c", "user.email=ck@example.com"])
+            .args(args)
+            .current_dir(dir)
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .status()
+            .unwrap();
+        assert!(status.success(), "git {args:?}");
+    }
+
+    /// A repository whose `v1` tag holds the old retry logic
+    fn repo() -> TempDir {
+        let temp_dir = TempDir::new().unwrap();
+        let dir = temp_dir.path();
+        run_git(dir, &["init", "-q"]);
+        fs::create_dir_all(dir.join("src")).unwrap();
+        fs::write(dir.join("src/net.rs"), "fn retry_forever() {}\n").unwrap();
+        fs::write(dir.join("README"), "network helpers\n").unwrap();
+        run_git(dir, &["add", "."]);
+        run_git(dir, &["commit", "-q", "-m", "first"]);
+        run_git(dir, &["tag", "v1"]);
+        fs::write(dir.join("src/net.rs"), "fn retry_with_backoff() {}\n").unwrap();
+        run_git(dir, &["commit", "-q", "-am", "second"]);
+        temp_dir
+    }
+
+    fn regex_options(path: &Path, query: &str) -> SearchOptions {
+        SearchOptions {
+            mode: SearchMode::Regex,
+            query: query.to_string(),
+            path: path.to_path_buf(),
+            at_ref: Some("v1".to_string()),
+            ..Default::default()
+        }
+    }
+
+    #[tokio::test]
+    async fn searches_the_tree_of_a_ref() {
+        let temp_dir = repo();
+        let options = regex_options(temp_dir.path(), "retry_");
+        let outcome = search_at_ref(&options, "v1", None, None, None)
+            .await
+            .unwrap();
+        let files: Vec<_> = outcome.results.matches.iter().map(|r| &r.file).collect();
+        assert_eq!(files, [&PathBuf::from("v1:src/net.rs")]);
+        assert!(outcome.results.matches[0].preview.contains("retry_forever"));
+
+        // The snapshot is cached under the commit, and a subdirectory
+        // narrows the search within it
+        let commit = resolve_commit(&repo_root(temp_dir.path()).unwrap(), "v1").unwrap();
+        assert!(
+            temp_dir
+                .path()
+                .join(".ck/refs")
+                .join(&commit)
+                .join("tree/README")
+                .exists()
+        );
+        let options = regex_options(&temp_dir.path().join("src"), "network");
+        let outcome = search_at_ref(&options, "v1", None, None, None)
+            .await
+            .unwrap();
+        assert!(outcome.results.matches.is_empty());
+    }
+
+    #[tokio::test]
+    async fn unknown_refs_and_non_repositories_are_errors() {
+        let temp_dir = repo();
+        let options = regex_options(temp_dir.path(), "retry");
+        let err = search_at_ref(&options, "no-such-tag", None, None, None)
+            .await
+            .unwrap_err();
+        assert!(err.to_string().contains("Unknown git ref 'no-such-tag'"));
+
+        let plain = TempDir::new().unwrap();
+        let options = regex_options(plain.path(), "retry");
+        let err = search_at_ref(&options, "v1", None, None, None)
+            .await
+            .unwrap_err();
+        assert!(err.to_string().contains("not inside a git repository"));
+    }
+}
@@ -0,0 +1,125 @@
+//! Identifier splitting for `--split-identifiers`.
+//!
+//! Code identifiers pack several words into one token (`getUserById`,
+//! `MAX_RETRY_COUNT`, `http-server`). Embedding models and the lexical
+//! tokenizer both see those as a single opaque word, so a query for
+//! `getUserById` misses code that talks about "user" and "id". These helpers
+//! break identifiers into their words.
+
+/// Split one identifier into its words, preserving case.
+///
+/// Boundaries are `_` and `-`, lower-to-upper case changes (`getUser`), the
+/// end of an acronym (`HTTPServer` → `HTTP`, `Server`), and letter/digit
+/// changes (`Server2Client` → `Server`, `2`, `Client`).
+pub fn split_identifier(identifier: &str) -> Vec<String> {
+    let mut words = Vec::new();
+    for part in identifier.split(['_', '-']) {
+        let chars: Vec<char> = part.chars().collect();
+        let mut start = 0;
+        for i in 1..chars.len() {
+            let (prev, curr) = (chars[i - 1], chars[i]);
+            let next_is_lower = chars.get(i + 1).is_some_and(|c| c.is_lowercase());
+            let boundary = (prev.is_lowercase() && curr.is_uppercase())
+                || (prev.is_uppercase() && curr.is_uppercase() && next_is_lower)
+                || (prev.is_alphabetic() && curr.is_numeric())
+                || (prev.is_numeric() && curr.is_alphabetic());
+            if boundary {
+                words.push(chars[start..i].iter().collect());
+                start = i;
+            }
+        }
+        if start < chars.len() {
+            words.push(chars[start..].iter().collect());
+        }
+    }
+    words
+}
+
+/// Lowercased words of every compound identifier in `text`, in order of
+/// appearance. Plain words (nothing to split) are not included.
+pub fn identifier_words(text: &str) -> Vec<String> {
+    let mut words = Vec::new();
+    for token in identifier_tokens(text) {
+        let parts = split_identifier(token);
+        if parts.len() > 1 {
+            words.extend(parts.into_iter().map(|w| w.to_lowercase()));
+        }
+    }
+    words
+}
+
+/// Rewrite `query` with each compound identifier replaced by its lowercased
+/// words, for embedding: `"where is getUserById"` → `"where is get user by id"`.
+pub fn split_query_identifiers(query: &str) -> String {
+    let mut out = String::with_capacity(query.len() + 8);
+    let mut last = 0;
+    for token in identifier_tokens(query) {
+        let offset = token.as_ptr() as usize - query.as_ptr() as usize;
+        out.push_str(&query[last..offset]);
+        let parts = split_identifier(token);
+        if parts.len() > 1 {
+            let words: Vec<String> = parts.iter().map(|w| w.to_lowercase()).collect();
+            out.push_str(&words.join(" "));
+        } else {
+            out.push_str(token);
+        }
+        last = offset + token.len();
+    }
+    out.push_str(&query[last..]);
+    out
+}
+
+/// Runs of identifier characters (alphanumerics, `_`, `-`) in `text`.
+fn identifier_tokens(text: &str) -> impl Iterator<Item = &str> {
+    text.split(|c: char| !(c.is_alphanumeric() || c == '_' || c == '-'))
+        .filter(|token| token.chars().any(char::is_alphanumeric))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn splits_camel_case() {
+        assert_eq!(split_identifier("getUserById"), ["get", "User", "By", "Id"]);
+        assert_eq!(split_identifier("UserService"), ["User", "Service"]);
+        assert_eq!(split_identifier("plain"), ["plain"]);
+    }
+
+    #[test]
+    fn splits_snake_and_kebab_case() {
+        assert_eq!(
+            split_identifier("MAX_RETRY_COUNT"),
+            ["MAX", "RETRY", "COUNT"]
+        );
+        assert_eq!(
+            split_identifier("load_index_entry"),
+            ["load", "index", "entry"]
+        );
+        assert_eq!(split_identifier("http-server"), ["http", "server"]);
+        assert_eq!(split_identifier("__init__"), ["init"]);
+    }
+
+    #[test]
+    fn splits_acronyms_and_digits() {
+        assert_eq!(
+            split_identifier("HTTPServer2Client"),
+            ["HTTP", "Server", "2", "Client"]
+        );
+        assert_eq!(split_identifier("parseJSON"), ["parse", "JSON"]);
+        assert_eq!(split_identifier("utf8_decode"), ["utf", "8", "decode"]);
+    }
+
+    #[test]
+    fn rewrites_identifiers_in_query() {
+        assert_eq!(
+            split_query_identifiers("where is getUserById called?"),
+            "where is get user by id called?"
+        );
+        assert_eq!(split_query_identifiers("error handling"), "error handling");
+        assert_eq!(
+            identifier_words("fn getUserById(user_id: u32)"),
+            ["get", "user", "by", "id", "user", "id", "u", "32"]
+        );
+    }
+}
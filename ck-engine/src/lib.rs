@@ -1,20 +1,44 @@
+use crate::identifiers::identifier_words;
 use anyhow::Result;
-use ck_core::{CkError, IncludePattern, SearchMode, SearchOptions, SearchResult, Span};
+use ck_core::{CkError, IncludePattern, MatchOn, SearchMode, SearchOptions, SearchResult, Span};
 use globset::{Glob, GlobSet, GlobSetBuilder};
 use rayon::prelude::*;
-use regex::{Regex, RegexBuilder};
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 use std::fs;
 use std::path::PathBuf as StdPathBuf;
 use std::path::{Path, PathBuf};
 use tantivy::collector::TopDocs;
-use tantivy::query::{Query, QueryParser};
-use tantivy::schema::{STORED, Schema, TEXT, Value};
-use tantivy::{Index, ReloadPolicy, TantivyDocument, doc};
+use tantivy::query::{AllQuery, BooleanQuery, BoostQuery, Occur, Query, QueryParser, TermQuery};
+use tantivy::schema::{Field, IndexRecordOption, STORED, Schema, TEXT, Value};
+use tantivy::{Index, ReloadPolicy, Searcher, TantivyDocument, Term, doc};
 use walkdir::WalkDir;
 
+mod ann;
+mod bm25;
+mod boolean_query;
+mod chunk_match;
+mod dedup;
+mod document;
+mod git_ref;
+mod identifiers;
+mod proximity;
+mod rerank;
+mod roots;
+mod search_regex;
 mod semantic_v3;
-pub use semantic_v3::{semantic_search_v3, semantic_search_v3_with_progress};
+mod similar;
+mod snippet;
+mod without_matches;
+pub use dedup::DEFAULT_DEDUP_SIMILARITY;
+pub use identifiers::{split_identifier, split_query_identifiers};
+pub use rerank::DEFAULT_RERANK_TOP_K;
+pub use search_regex::SearchRegex;
+pub use semantic_v3::{
+    LoadedIndex, semantic_search_v3, semantic_search_v3_with_progress,
+    semantic_search_with_embedding,
+};
+pub use similar::similar_search;
+pub use without_matches::{FilesWithoutMatches, files_without_matches};
 
 pub type SearchProgressCallback = Box<dyn Fn(&str) + Send + Sync>;
 pub type IndexingProgressCallback = Box<dyn Fn(&str) + Send + Sync>;
@@ -22,9 +46,17 @@ pub type DetailedIndexingProgressCallback = Box<dyn Fn(ck_index::EmbeddingProgre
 
 /// Resolve the actual file path to read content from
 /// For PDFs: returns cache path and validates it exists
+/// For notebooks: returns the extracted cells once indexed, else the JSON itself
 /// For regular files: returns original path
 fn resolve_content_path(file_path: &Path, repo_root: &Path) -> Result<PathBuf> {
-    if ck_core::pdf::is_pdf_file(file_path) {
+    if ck_core::notebook::is_notebook_file(file_path) {
+        let cache_path = ck_core::pdf::get_content_cache_path(repo_root, file_path);
+        Ok(if cache_path.exists() {
+            cache_path
+        } else {
+            file_path.to_path_buf()
+        })
+    } else if ck_core::pdf::is_pdf_file(file_path) {
         // PDFs: Read from cached extracted text
         let cache_path = ck_core::pdf::get_content_cache_path(repo_root, file_path);
         if !cache_path.exists() {
@@ -39,16 +71,81 @@ fn resolve_content_path(file_path: &Path, repo_root: &Path) -> Result<PathBuf> {
     }
 }
 
+/// Name the notebook cell (`cell 3`) or PDF page (`page 2`) each result in
+/// one starts in as its symbol: its line numbers count lines of the
+/// extracted text, which mean nothing in the `.ipynb` or `.pdf` itself.
+fn label_extracted_sections(matches: &mut [SearchResult]) {
+    let mut extracted: HashMap<PathBuf, Option<String>> = HashMap::new();
+    for result in matches.iter_mut().filter(|r| {
+        r.symbol.is_none()
+            && (ck_core::notebook::is_notebook_file(&r.file) || ck_core::pdf::is_pdf_file(&r.file))
+    }) {
+        let text = extracted.entry(result.file.clone()).or_insert_with(|| {
+            let repo_root = find_nearest_index_root(&result.file)?;
+            let cache_path = ck_core::pdf::get_content_cache_path(&repo_root, &result.file);
+            fs::read_to_string(cache_path).ok()
+        });
+        let line = result.preview_line.unwrap_or(result.span.line_start);
+        let Some(text) = text else {
+            continue;
+        };
+        result.symbol = if ck_core::pdf::is_pdf_file(&result.file) {
+            ck_core::pdf::page_at_line(text, line).map(|page| format!("page {page}"))
+        } else {
+            ck_core::notebook::cell_at_line(text, line).map(|cell| format!("cell {cell}"))
+        };
+    }
+}
+
+/// Give each Markdown result the path of headings its line sits under
+/// (`Install > macOS`) as its symbol.
+fn label_markdown_sections(matches: &mut [SearchResult]) {
+    let mut contents: HashMap<PathBuf, Option<String>> = HashMap::new();
+    for result in matches.iter_mut().filter(|r| {
+        r.symbol.is_none()
+            && ck_core::Language::from_path(&r.file) == Some(ck_core::Language::Markdown)
+    }) {
+        let text = contents
+            .entry(result.file.clone())
+            .or_insert_with(|| ck_index::read_text(&result.file).ok());
+        let line = result.preview_line.unwrap_or(result.span.line_start);
+        if let Some(text) = text {
+            let path = ck_chunk::heading_path_at_line(text, line);
+            if !path.is_empty() {
+                result.symbol = Some(path.join(ck_chunk::HEADING_PATH_SEPARATOR));
+            }
+        }
+    }
+}
+
 /// Read content from file for search result extraction
 /// Regular files: read directly from source
 /// PDFs: read from preprocessed cache
 fn read_file_content(file_path: &Path, repo_root: &Path) -> Result<String> {
     let content_path = resolve_content_path(file_path, repo_root)?;
-    Ok(fs::read_to_string(content_path)?)
+    ck_index::read_text(&content_path)
 }
 
-/// Extract content from a file using a span (streaming version)
-async fn extract_content_from_span(file_path: &Path, span: &ck_core::Span) -> Result<String> {
+/// Extract content from a file using a span (streaming version), or from
+/// the searched document when `file_path` names it
+async fn extract_content_from_span(
+    options: &SearchOptions,
+    file_path: &Path,
+    span: &ck_core::Span,
+) -> Result<String> {
+    if let Some(document) = options
+        .document
+        .as_ref()
+        .filter(|document| document.name == file_path)
+    {
+        return Ok(document
+            .content
+            .lines()
+            .skip(span.line_start.saturating_sub(1))
+            .take(span.line_end + 1 - span.line_start)
+            .collect::<Vec<_>>()
+            .join("\n"));
+    }
     // Find repo root to locate cache
     let repo_root = find_nearest_index_root(file_path)
         .unwrap_or_else(|| file_path.parent().unwrap_or(file_path).to_path_buf());
@@ -76,12 +173,15 @@ fn extract_lines_from_file(file_path: &Path, line_start: usize, line_end: usize)
     let start_idx = line_start.saturating_sub(1);
     let end_idx = line_end.saturating_sub(1);
 
-    for (current_line, line_result) in reader.lines().enumerate() {
+    // Split on raw bytes: indexing decodes invalid UTF-8 lossily, so must this
+    for (current_line, line_result) in reader.split(b'\n').enumerate() {
         if current_line > end_idx {
             break; // Stop reading once we've passed the needed lines
         }
 
-        let line = line_result?;
+        let bytes = line_result?;
+        let bytes = bytes.strip_suffix(b"\r").unwrap_or(&bytes);
+        let line = String::from_utf8_lossy(bytes).into_owned();
 
         if current_line >= start_idx {
             result.push(line);
@@ -168,6 +268,26 @@ fn path_matches_include(path: &Path, include_patterns: &[IncludePattern]) -> boo
     })
 }
 
+/// Whether `path` was modified at or after `--modified-since`. With the
+/// filter set, a file whose modification time can't be read is left out.
+fn modified_since_matches(path: &Path, options: &SearchOptions) -> bool {
+    let Some(since) = options.modified_since else {
+        return true;
+    };
+    fs::metadata(path)
+        .and_then(|metadata| metadata.modified())
+        .is_ok_and(|modified| modified >= since)
+}
+
+/// `--include` globs and `--max-depth` for this search, matched relative to
+/// the search path.
+fn search_include_globs(options: &SearchOptions) -> Result<ck_index::IncludeGlobs> {
+    Ok(
+        ck_index::IncludeGlobs::new(&options.path, &options.include_globs)?
+            .with_max_depth(options.max_depth),
+    )
+}
+
 fn filter_files_by_include(
     files: Vec<PathBuf>,
     include_patterns: &[IncludePattern],
@@ -222,6 +342,8 @@ fn legacy_model_config(name: &str, dimensions: usize) -> ck_models::ModelConfig
         dimensions,
         max_tokens: 8192,
         description: "Legacy ck embedding model preserved for backwards compatibility".to_string(),
+        batch_size: ck_models::DEFAULT_BATCH_SIZE,
+        max_batch_size: ck_models::DEFAULT_MAX_BATCH_SIZE,
     }
 }
 
@@ -236,8 +358,7 @@ pub(crate) fn resolve_model_from_root(
     let manifest_path = index_dir.join("manifest.json");
 
     if manifest_path.exists() {
-        let data = std::fs::read(&manifest_path)?;
-        let manifest: ck_index::IndexManifest = serde_json::from_slice(&data)?;
+        let manifest = ck_index::read_manifest(&manifest_path)?;
 
         if let Some(existing_model) = manifest.embedding_model {
             let dims_hint = manifest.embedding_dimensions.unwrap_or(384);
@@ -347,6 +468,9 @@ pub struct SearchOutcome {
     pub results: ck_core::SearchResults,
     /// `None` for regex mode (which never touches the index).
     pub index_update: Option<IndexUpdate>,
+    /// Files and directories the search or its index update skipped because
+    /// they couldn't be read, with the reason
+    pub unreadable: Vec<(PathBuf, String)>,
 }
 
 pub async fn search_enhanced_with_indexing_progress(
@@ -370,6 +494,200 @@ pub async fn search_enhanced_with_outcome(
     progress_callback: Option<SearchProgressCallback>,
     indexing_progress_callback: Option<IndexingProgressCallback>,
     detailed_indexing_progress_callback: Option<DetailedIndexingProgressCallback>,
+) -> Result<SearchOutcome> {
+    validate_threshold_options(options)?;
+    options.check_cancelled()?;
+    let options = &paged_options(options);
+    let mut outcome = if let Some(document) = &options.document {
+        document::search_document(options, document, progress_callback).await?
+    } else if let Some(git_ref) = options.at_ref.as_deref() {
+        git_ref::search_at_ref(
+            options,
+            git_ref,
+            progress_callback,
+            indexing_progress_callback,
+            detailed_indexing_progress_callback,
+        )
+        .await?
+    } else if options.roots.len() > 1 && matches!(options.mode, SearchMode::Semantic) {
+        roots::search_roots(
+            options,
+            progress_callback,
+            indexing_progress_callback,
+            detailed_indexing_progress_callback,
+        )
+        .await?
+    } else {
+        search_single_root(
+            options,
+            progress_callback,
+            indexing_progress_callback,
+            detailed_indexing_progress_callback,
+        )
+        .await?
+    };
+    // Work that finished after the cancellation isn't returned as a result
+    options.check_cancelled()?;
+    skip_offset(&mut outcome.results.matches, options.offset);
+    label_extracted_sections(&mut outcome.results.matches);
+    label_markdown_sections(&mut outcome.results.matches);
+    // top_k has already kept the best results; this only reorders them
+    options.sort.apply(&mut outcome.results.matches);
+    Ok(outcome)
+}
+
+/// `options` with `top_k` lowered to `max_count` when that is smaller, then
+/// widened to reach past the `offset` results a page skips, so the page is
+/// cut from the same ranking as the pages before it.
+pub(crate) fn paged_options(options: &SearchOptions) -> std::borrow::Cow<'_, SearchOptions> {
+    if options.offset == 0 && options.max_count.is_none() {
+        return std::borrow::Cow::Borrowed(options);
+    }
+    let mut paged = options.clone();
+    paged.top_k = match (options.top_k, options.max_count) {
+        (Some(top_k), Some(max_count)) => Some(top_k.min(max_count)),
+        (top_k, max_count) => top_k.or(max_count),
+    }
+    .map(|k| k.saturating_add(options.offset));
+    std::borrow::Cow::Owned(paged)
+}
+
+/// A filter for results in ranked order that passes at most
+/// `max_matches_per_file` of them from each file; passes everything when
+/// that is unset.
+pub(crate) fn per_file_cap(options: &SearchOptions) -> impl FnMut(&Path) -> bool + use<> {
+    let cap = options.max_matches_per_file;
+    let mut counts: HashMap<PathBuf, usize> = HashMap::new();
+    move |file| {
+        let Some(cap) = cap else {
+            return true;
+        };
+        let count = counts.entry(file.to_path_buf()).or_default();
+        *count += 1;
+        *count <= cap
+    }
+}
+
+/// Drop the `offset` leading results, leaving the requested page.
+pub(crate) fn skip_offset(matches: &mut Vec<SearchResult>, offset: usize) {
+    matches.drain(..offset.min(matches.len()));
+}
+
+fn validate_threshold_options(options: &SearchOptions) -> Result<()> {
+    if let Some(similarity) = options.dedup
+        && !(-1.0..=1.0).contains(&similarity)
+    {
+        return Err(ck_core::CkError::Search(format!(
+            "--dedup similarity must be between -1 and 1, got {similarity}"
+        ))
+        .into());
+    }
+    if options.invert_match && matches!(options.mode, SearchMode::Semantic | SearchMode::Hybrid) {
+        return Err(ck_core::CkError::Search(
+            "--invert-match only applies to regex and lexical search".to_string(),
+        )
+        .into());
+    }
+    if options.replace.is_some() && options.mode != SearchMode::Regex {
+        return Err(ck_core::CkError::Search(
+            "--replace only applies to regex search, whose matches have capture groups".to_string(),
+        )
+        .into());
+    }
+    if options.replace.is_some() && options.invert_match {
+        return Err(ck_core::CkError::Search(
+            "--replace can't be used with --invert-match, which reports lines without a match"
+                .to_string(),
+        )
+        .into());
+    }
+    if options.match_on == MatchOn::Chunks {
+        if matches!(options.mode, SearchMode::Semantic | SearchMode::Hybrid) {
+            return Err(ck_core::CkError::Search(
+                "--on only applies to regex and lexical search; semantic search always matches chunks"
+                    .to_string(),
+            )
+            .into());
+        }
+        if options.replace.is_some() {
+            return Err(ck_core::CkError::Search(
+                "--replace rewrites matching lines and can't be used with --on chunks".to_string(),
+            )
+            .into());
+        }
+    }
+    if options.ephemeral && (options.mode != SearchMode::Semantic || options.similar_to.is_some()) {
+        return Err(ck_core::CkError::Search(
+            "--ephemeral only applies to semantic search (--sem)".to_string(),
+        )
+        .into());
+    }
+    let ck_core::Bm25Params { k1, b } = options.bm25;
+    if !(k1.is_finite() && k1 >= 0.0) {
+        return Err(
+            ck_core::CkError::Search(format!("--bm25-k1 must be 0 or more, got {k1}")).into(),
+        );
+    }
+    if !(0.0..=1.0).contains(&b) {
+        return Err(
+            ck_core::CkError::Search(format!("--bm25-b must be between 0 and 1, got {b}")).into(),
+        );
+    }
+    if !(options.proximity.is_finite() && options.proximity >= 0.0) {
+        return Err(ck_core::CkError::Search(format!(
+            "--proximity must be 0 or more, got {}",
+            options.proximity
+        ))
+        .into());
+    }
+    if options.max_matches_per_file == Some(0) {
+        return Err(ck_core::CkError::Search(
+            "--max-matches-per-file must be at least 1".to_string(),
+        )
+        .into());
+    }
+    let Some(percentile) = options.threshold_percentile else {
+        return Ok(());
+    };
+    if options.threshold.is_some() {
+        return Err(ck_core::CkError::Search(
+            "--threshold and --threshold-percentile cannot be combined".to_string(),
+        )
+        .into());
+    }
+    if !(0.0..=100.0).contains(&percentile) {
+        return Err(ck_core::CkError::Search(format!(
+            "Threshold percentile must be between 0 and 100, got {percentile}"
+        ))
+        .into());
+    }
+    Ok(())
+}
+
+/// Minimum score a result needs: `threshold` as given, or with
+/// `threshold_percentile` the score at that percentile (nearest rank) of this
+/// query's candidate `scores`, so the cutoff adapts to the model and corpus.
+pub(crate) fn score_cutoff(
+    options: &SearchOptions,
+    scores: impl IntoIterator<Item = f32>,
+) -> Option<f32> {
+    let Some(percentile) = options.threshold_percentile else {
+        return options.threshold;
+    };
+    let mut scores: Vec<f32> = scores.into_iter().filter(|s| !s.is_nan()).collect();
+    if scores.is_empty() {
+        return None;
+    }
+    scores.sort_by(f32::total_cmp);
+    let rank = (percentile / 100.0 * scores.len() as f32).ceil() as usize;
+    Some(scores[rank.clamp(1, scores.len()) - 1])
+}
+
+async fn search_single_root(
+    options: &SearchOptions,
+    progress_callback: Option<SearchProgressCallback>,
+    indexing_progress_callback: Option<IndexingProgressCallback>,
+    detailed_indexing_progress_callback: Option<DetailedIndexingProgressCallback>,
 ) -> Result<SearchOutcome> {
     // Validate that the search path exists
     if !options.path.exists() {
@@ -380,83 +698,329 @@ pub async fn search_enhanced_with_outcome(
         .into());
     }
 
+    // --ephemeral leaves any index alone and builds a throwaway one in memory
+    if options.ephemeral {
+        let (files, unreadable) = search_candidate_files(options)?;
+        let mut index = LoadedIndex::in_memory(options, &files, progress_callback.as_ref())?;
+        let results = index.search_page(options).await?;
+        return Ok(SearchOutcome {
+            results,
+            index_update: None,
+            unreadable,
+        });
+    }
+
+    // Regex search reads the files themselves unless it matches indexed chunks
+    let index_backed =
+        !matches!(options.mode, SearchMode::Regex) || options.match_on == MatchOn::Chunks;
+    if options.no_auto_index && index_backed && find_nearest_index_root(&options.path).is_none() {
+        return Err(ck_core::CkError::Index(format!(
+            "No index found for {}. Run 'ck --index {}' to build one, or search without --no-auto-index to build it automatically",
+            options.path.display(),
+            options.path.display()
+        ))
+        .into());
+    }
+
     // Auto-update index if needed (unless it's regex-only mode)
     let mut index_update = None;
-    if !matches!(options.mode, SearchMode::Regex) {
+    let mut unreadable = Vec::new();
+    if index_backed && !options.no_auto_index {
         let need_embeddings = matches!(options.mode, SearchMode::Semantic | SearchMode::Hybrid);
         let file_options = ck_core::FileCollectionOptions::from(options);
         let started = std::time::Instant::now();
-        let update_stats = ensure_index_updated_with_progress(
+        // The deadline bounds the update too: it stops between files, keeping
+        // those it finished, which leaves no time to search (the timer is a
+        // thread, as the update runs on this one without yielding)
+        let deadline_cancel = options.deadline.map(|deadline| {
+            let token = options
+                .cancel
+                .as_ref()
+                .map(ck_core::CancellationToken::child_token)
+                .unwrap_or_default();
+            let timer = token.clone();
+            let (finished, wait) = std::sync::mpsc::channel::<()>();
+            std::thread::spawn(move || {
+                let remaining = deadline.saturating_duration_since(std::time::Instant::now());
+                if wait.recv_timeout(remaining) == Err(std::sync::mpsc::RecvTimeoutError::Timeout) {
+                    timer.cancel();
+                }
+            });
+            (token, finished)
+        });
+        let update = ensure_index_updated_with_progress(
             &options.path,
-            options.reindex,
-            need_embeddings,
             indexing_progress_callback,
             detailed_indexing_progress_callback,
             &file_options,
-            options.embedding_model.as_deref(),
+            ck_index::IndexUpdateOptions {
+                force_rebuild: options.reindex,
+                compute_embeddings: need_embeddings,
+                model: options.embedding_model.as_deref(),
+                embed_batch_size: options.embed_batch_size,
+                force_hash: options.force_hash,
+                jobs: options.jobs,
+                cancel: deadline_cancel
+                    .as_ref()
+                    .map(|(token, _)| token)
+                    .or(options.cancel.as_ref()),
+                ..Default::default()
+            },
         )
-        .await?;
+        .await;
+        // Ends the timer thread
+        drop(deadline_cancel);
+        options.check_cancelled()?;
+        let update_stats = match update {
+            Err(e) if ck_core::is_cancelled(&e) && options.is_past_deadline() => {
+                return Ok(SearchOutcome {
+                    results: ck_core::SearchResults {
+                        matches: Vec::new(),
+                        closest_below_threshold: None,
+                        timings: Default::default(),
+                        truncated: true,
+                        lower_thresholds: Vec::new(),
+                    },
+                    index_update: None,
+                    unreadable,
+                });
+            }
+            update => update?,
+        };
+        // Listed files the walk passes over (ignored, excluded) are indexed
+        // as given, like `ck --add` does
+        let explicit_files_indexed = if options.explicit_files.is_empty() {
+            0
+        } else {
+            let added = ck_index::add_paths(
+                &options.explicit_files,
+                need_embeddings,
+                &file_options,
+                options.embed_batch_size,
+                options.force_hash,
+            )
+            .await?;
+            added.files_added + added.files_updated
+        };
         index_update = Some(IndexUpdate {
             files_indexed: update_stats
                 .as_ref()
                 .map(|s| s.files_indexed)
-                .unwrap_or_default(),
+                .unwrap_or_default()
+                + explicit_files_indexed,
             orphaned_files_removed: update_stats
                 .as_ref()
                 .map(|s| s.orphaned_files_removed)
                 .unwrap_or_default(),
             duration_ms: started.elapsed().as_millis() as u64,
         });
+        if let Some(stats) = update_stats {
+            unreadable = stats.unreadable;
+        }
     }
 
-    let search_results = match options.mode {
+    let mut search_results = match options.mode {
         SearchMode::Regex => {
-            let matches = regex_search(options)?;
+            let started = std::time::Instant::now();
+            let scan = match options.match_on {
+                MatchOn::Lines => regex_scan(options)?,
+                MatchOn::Chunks => chunk_match::regex_chunk_scan(options)?,
+            };
+            unreadable.extend(scan.unreadable);
             ck_core::SearchResults {
-                matches,
+                matches: scan.matches,
                 closest_below_threshold: None,
+                timings: ck_core::SearchTimings {
+                    scoring: Some(started.elapsed()),
+                    ..Default::default()
+                },
+                truncated: scan.truncated,
+                lower_thresholds: Vec::new(),
             }
         }
         SearchMode::Lexical => {
-            let matches = lexical_search(options).await?;
+            let mut timings = ck_core::SearchTimings::default();
+            let (matches, truncated) = lexical_search(options, &mut timings).await?;
             ck_core::SearchResults {
                 matches,
                 closest_below_threshold: None,
+                timings,
+                truncated,
+                lower_thresholds: Vec::new(),
             }
         }
         SearchMode::Semantic => {
-            // Use v3 semantic search (reads pre-computed embeddings from sidecars using spans)
-            semantic_search_v3_with_progress(options, progress_callback).await?
-        }
-        SearchMode::Hybrid => {
-            let matches = hybrid_search_with_progress(options, progress_callback).await?;
-            ck_core::SearchResults {
-                matches,
-                closest_below_threshold: None,
+            if let Some(target) = &options.similar_to {
+                similar_search(options, target, progress_callback).await?
+            } else {
+                // Use v3 semantic search (reads pre-computed embeddings from sidecars using spans)
+                semantic_search_v3_with_progress(options, progress_callback).await?
             }
         }
+        SearchMode::Hybrid => hybrid_search_with_progress(options, progress_callback).await?,
     };
+    if index_backed {
+        mark_stale_results(options, &mut search_results);
+    }
 
     Ok(SearchOutcome {
         results: search_results,
         index_update,
+        unreadable,
     })
 }
 
-fn regex_search(options: &SearchOptions) -> Result<Vec<SearchResult>> {
-    let pattern = if options.fixed_string {
-        regex::escape(&options.query)
-    } else if options.whole_word {
-        format!(r"\b{}\b", regex::escape(&options.query))
-    } else {
-        options.query.clone()
+/// How far the index covering `options.path` lags behind its files, for
+/// searches that don't update it first (`--no-auto-index`). `None` when
+/// there is no index.
+pub fn index_freshness(options: &SearchOptions) -> Result<Option<ck_index::IndexFreshness>> {
+    let Some(index_root) = find_nearest_index_root(&options.path) else {
+        return Ok(None);
     };
+    ck_index::check_index_freshness(&index_root, &ck_core::FileCollectionOptions::from(options))
+}
+
+/// Flag index-backed results whose file changed after it was indexed (for
+/// instance while the search ran), and drop them for `--no-stale`.
+fn mark_stale_results(options: &SearchOptions, results: &mut ck_core::SearchResults) {
+    let index_root = find_nearest_index_root(&options.path).unwrap_or_else(|| {
+        if options.path.is_file() {
+            options.path.parent().unwrap_or(&options.path).to_path_buf()
+        } else {
+            options.path.clone()
+        }
+    });
+    let files: HashSet<&Path> = results
+        .matches
+        .iter()
+        .chain(&results.closest_below_threshold)
+        .map(|result| result.file.as_path())
+        .collect();
+    let stale = ck_index::find_stale_files(&index_root, files, options.force_hash);
+    if stale.is_empty() {
+        return;
+    }
+
+    for result in results
+        .matches
+        .iter_mut()
+        .chain(results.closest_below_threshold.as_mut())
+    {
+        result.stale = stale.contains(&result.file);
+    }
+    if options.no_stale {
+        results.matches.retain(|result| !result.stale);
+        if results
+            .closest_below_threshold
+            .as_ref()
+            .is_some_and(|result| result.stale)
+        {
+            results.closest_below_threshold = None;
+        }
+    }
+}
+
+#[cfg(test)]
+fn regex_search(options: &SearchOptions) -> Result<Vec<SearchResult>> {
+    Ok(regex_scan(options)?.matches)
+}
+
+/// What a regex search found.
+struct RegexScan {
+    matches: Vec<SearchResult>,
+    /// Files it skipped because they couldn't be read, with the reason
+    unreadable: Vec<(PathBuf, String)>,
+    /// The deadline passed before every file was scanned
+    truncated: bool,
+}
+
+/// Regex search over the candidate files, keeping the files it skipped and
+/// whether it ran out of time.
+fn regex_scan(options: &SearchOptions) -> Result<RegexScan> {
+    let regex = SearchRegex::new(&options.query, options).map_err(CkError::Regex)?;
+
+    let (files, unreadable) = search_candidate_files(options)?;
+    let unreadable = std::sync::Mutex::new(unreadable);
+    let truncated = std::sync::atomic::AtomicBool::new(false);
+
+    let pool = ck_index::WorkerPool::new(options.jobs)?;
+    let results: Vec<Vec<SearchResult>> = pool.install(|| {
+        files
+            .par_iter()
+            .filter(|_| !options.is_cancelled())
+            .filter(|_| {
+                // Files not started by the deadline are left unscanned
+                let past = options.is_past_deadline();
+                if past {
+                    truncated.store(true, std::sync::atomic::Ordering::Relaxed);
+                }
+                !past
+            })
+            .filter_map(|file_path| match search_file(&regex, file_path, options) {
+                Ok(matches) => {
+                    if matches.is_empty() {
+                        None
+                    } else {
+                        Some(matches)
+                    }
+                }
+                Err(e) => {
+                    if let Some(err) = e.downcast_ref::<std::io::Error>()
+                        && matches!(
+                            err.kind(),
+                            std::io::ErrorKind::PermissionDenied | std::io::ErrorKind::NotFound
+                        )
+                    {
+                        unreadable
+                            .lock()
+                            .unwrap()
+                            .push((file_path.clone(), err.to_string()));
+                    } else {
+                        tracing::debug!("Error searching {:?}: {}", file_path, e);
+                    }
+                    None
+                }
+            })
+            .collect()
+    });
+    options.check_cancelled()?;
+
+    let mut all_results: Vec<SearchResult> = results.into_iter().flatten().collect();
+    // Every match scores 1, so this is file path, then line, then column
+    all_results.sort_by(SearchResult::rank_cmp);
+
+    if let Some(top_k) = options.top_k {
+        all_results.truncate(top_k);
+    }
 
-    let regex = RegexBuilder::new(&pattern)
-        .case_insensitive(options.case_insensitive)
-        .build()
-        .map_err(CkError::Regex)?;
+    let mut unreadable = unreadable.into_inner().unwrap();
+    unreadable.sort();
+    Ok(RegexScan {
+        matches: all_results,
+        unreadable,
+        truncated: truncated.into_inner(),
+    })
+}
 
+/// Files a search over `options.path` considers, before any matching: the
+/// walk (recursive for directories, like grep) with ignore rules, excludes and
+/// includes applied, or the `explicit_files` as listed. Also returns the
+/// entries the walk skipped because they couldn't be read.
+#[allow(clippy::type_complexity)]
+fn search_candidate_files(
+    options: &SearchOptions,
+) -> Result<(Vec<PathBuf>, Vec<(PathBuf, String)>)> {
+    if !options.explicit_files.is_empty() {
+        let include_globs = search_include_globs(options)?;
+        let files = options
+            .explicit_files
+            .iter()
+            .filter(|path| include_globs.matches(path) && modified_since_matches(path, options))
+            .cloned()
+            .collect();
+        return Ok((files, Vec::new()));
+    }
+    let mut unreadable = Vec::new();
     // Default to recursive for directories (like grep) to maintain compatibility
     let should_recurse = options.path.is_dir() || options.recursive;
     let files = if should_recurse {
@@ -465,52 +1029,36 @@ fn regex_search(options: &SearchOptions) -> Result<Vec<SearchResult>> {
             respect_gitignore: options.respect_gitignore,
             use_ckignore: options.use_ckignore,
             exclude_patterns: options.exclude_patterns.clone(),
+            include_globs: options.include_globs.clone(),
             show_hidden: options.hidden,
+            follow_links: options.follow_links,
+            include_submodules: options.include_submodules,
+            max_depth: options.max_depth,
+            skip_generated: false,
         };
-        let collected = ck_index::collect_files(&options.path, &file_options)?;
+        let (collected, walk_unreadable) =
+            ck_index::collect_files_with_unreadable(&options.path, &file_options)?;
+        unreadable = walk_unreadable;
         filter_files_by_include(collected, &options.include_patterns)
     } else {
         // For non-recursive, use the local collect_files
         let collected = collect_files(&options.path, should_recurse, &options.exclude_patterns)?;
+        let include_globs = search_include_globs(options)?;
         filter_files_by_include(collected, &options.include_patterns)
+            .into_iter()
+            .filter(|path| include_globs.matches(path))
+            .collect()
     };
-
-    let results: Vec<Vec<SearchResult>> = files
-        .par_iter()
-        .filter_map(|file_path| match search_file(&regex, file_path, options) {
-            Ok(matches) => {
-                if matches.is_empty() {
-                    None
-                } else {
-                    Some(matches)
-                }
-            }
-            Err(e) => {
-                tracing::debug!("Error searching {:?}: {}", file_path, e);
-                None
-            }
-        })
+    let files = files
+        .into_iter()
+        .filter(|path| modified_since_matches(path, options))
+        .filter(|path| !(options.no_generated && ck_index::is_generated_file(path)))
         .collect();
-
-    let mut all_results: Vec<SearchResult> = results.into_iter().flatten().collect();
-    // Deterministic ordering: file path, then line number
-    all_results.sort_by(|a, b| {
-        let path_cmp = a.file.cmp(&b.file);
-        if path_cmp != std::cmp::Ordering::Equal {
-            return path_cmp;
-        }
-        a.span.line_start.cmp(&b.span.line_start)
-    });
-
-    if let Some(top_k) = options.top_k {
-        all_results.truncate(top_k);
-    }
-
-    Ok(all_results)
+    Ok((files, unreadable))
 }
 
 fn search_file(
-    regex: &Regex,
+    regex: &SearchRegex,
     file_path: &Path,
     options: &SearchOptions,
 ) -> Result<Vec<SearchResult>> {
@@ -521,7 +1069,8 @@ fn search_file(
     // For full_section mode, we need the entire content for parsing
     // For context previews, we need all lines for surrounding context
     // So we'll load content when needed, but optimize for the common case
-    if options.full_section || options.context_lines > 0 {
+    let (context_before, context_after) = context_line_counts(options);
+    let mut results = if options.full_section || context_before > 0 || context_after > 0 {
         // Load full content when we need section parsing or context
         let content = read_file_content(file_path, &repo_root)?;
         let (lines, line_ending_lengths) = split_lines_with_endings(&content);
@@ -544,12 +1093,17 @@ fn search_file(
     } else {
         // Streaming search (simple case)
         search_file_streaming(regex, file_path, &repo_root, options)
+    }?;
+    results.retain(|result| options.line_ranges.admits(&result.file, &result.span));
+    if let Some(cap) = options.max_matches_per_file {
+        results.truncate(cap);
     }
+    Ok(results)
 }
 
 /// In-memory search for cases requiring context or code sections
 fn search_file_in_memory(
-    regex: &Regex,
+    regex: &SearchRegex,
     file_path: &Path,
     options: &SearchOptions,
     lines: &[String],
@@ -560,27 +1114,21 @@ fn search_file_in_memory(
     let mut byte_offset = 0;
 
     for (line_idx, line) in lines.iter().enumerate() {
+        if per_file_cap_reached(options, &results) {
+            break;
+        }
         let line_number = line_idx + 1;
 
         // Special handling for empty pattern - match the entire line once
         // An empty regex pattern will match at every position, so we need to handle it specially
-        if regex.as_str().is_empty() {
-            // Empty pattern matches the whole line once (grep compatibility)
-            let preview = if options.full_section {
-                // Try to find the containing code section
-                if let Some(sections) = code_sections {
-                    if let Some(section) = find_containing_section(sections, line_idx) {
-                        section.clone()
-                    } else {
-                        // Fall back to context lines if no section found
-                        get_context_preview(lines, line_idx, options)
-                    }
-                } else {
-                    get_context_preview(lines, line_idx, options)
-                }
-            } else {
-                get_context_preview(lines, line_idx, options)
-            };
+        if options.invert_match && regex.is_match(line) {
+            // Inverted: a matching line is left out
+        } else if options.invert_match || regex.as_str().is_empty() {
+            // Empty pattern matches the whole line once (grep compatibility),
+            // as does a line an inverted pattern doesn't match
+            let (preview, section_start) =
+                regex_match_preview(lines, line_idx, code_sections, options);
+            let context = context_around(lines, line_idx, options);
 
             results.push(SearchResult {
                 file: file_path.to_path_buf(),
@@ -596,31 +1144,32 @@ fn search_file_in_memory(
                 symbol: None,
                 chunk_hash: None,
                 index_epoch: None,
+                original_score: None,
+                stale: false,
+                root: None,
+                preview_line: section_start,
+                duplicates: 0,
+                grouped: 0,
+                column: Some(1),
+                match_line: section_start.map(|_| line_number),
+                explanation: None,
+                context_before: context.map(|(before, _)| before.to_vec()),
+                context_after: context.map(|(_, after)| after.to_vec()),
             });
         } else {
             // Find all matches in the line with their positions
             for mat in regex.find_iter(line) {
-                let preview = if options.full_section {
-                    // Try to find the containing code section
-                    if let Some(sections) = code_sections {
-                        if let Some(section) = find_containing_section(sections, line_idx) {
-                            section.clone()
-                        } else {
-                            // Fall back to context lines if no section found
-                            get_context_preview(lines, line_idx, options)
-                        }
-                    } else {
-                        get_context_preview(lines, line_idx, options)
-                    }
-                } else {
-                    get_context_preview(lines, line_idx, options)
+                let (preview, section_start) = match options.replace.as_deref() {
+                    Some(template) => (regex.expand_at(line, mat.start, template), None),
+                    None => regex_match_preview(lines, line_idx, code_sections, options),
                 };
+                let context = context_around(lines, line_idx, options);
 
                 results.push(SearchResult {
                     file: file_path.to_path_buf(),
                     span: Span {
-                        byte_start: byte_offset + mat.start(),
-                        byte_end: byte_offset + mat.end(),
+                        byte_start: byte_offset + mat.start,
+                        byte_end: byte_offset + mat.end,
                         line_start: line_number,
                         line_end: line_number,
                     },
@@ -630,6 +1179,17 @@ fn search_file_in_memory(
                     symbol: None,
                     chunk_hash: None,
                     index_epoch: None,
+                    original_score: None,
+                    stale: false,
+                    root: None,
+                    preview_line: section_start,
+                    duplicates: 0,
+                    grouped: 0,
+                    column: Some(mat.start + 1),
+                    match_line: section_start.map(|_| line_number),
+                    explanation: None,
+                    context_before: context.map(|(before, _)| before.to_vec()),
+                    context_after: context.map(|(_, after)| after.to_vec()),
                 });
             }
         }
@@ -644,10 +1204,10 @@ fn search_file_in_memory(
 
 /// Streaming search for simple cases without context or code sections
 fn search_file_streaming(
-    regex: &Regex,
+    regex: &SearchRegex,
     file_path: &Path,
     repo_root: &Path,
-    _options: &SearchOptions,
+    options: &SearchOptions,
 ) -> Result<Vec<SearchResult>> {
     use std::io::{BufRead, BufReader};
 
@@ -660,7 +1220,7 @@ fn search_file_streaming(
     let mut byte_offset = 0usize;
     let mut line_number = 1usize;
 
-    loop {
+    while !per_file_cap_reached(options, &results) {
         line.clear();
         let bytes_read = reader.read_line(&mut line)?;
         if bytes_read == 0 {
@@ -699,6 +1259,7 @@ fn search_file_streaming(
                             segment_str,
                             line_number,
                             byte_offset,
+                            options,
                             &mut results,
                         );
                         byte_offset += segment_bytes.len() + 1; // account for \r
@@ -714,6 +1275,7 @@ fn search_file_streaming(
                             segment_str,
                             line_number,
                             byte_offset,
+                            options,
                             &mut results,
                         );
                         byte_offset += segment_bytes.len();
@@ -731,6 +1293,7 @@ fn search_file_streaming(
                 line_str,
                 line_number,
                 byte_offset,
+                options,
                 &mut results,
             );
             byte_offset += line_str.len() + newline_len;
@@ -741,15 +1304,31 @@ fn search_file_streaming(
     Ok(results)
 }
 
+/// Whether a file's scan has found its `max_matches_per_file`, so the rest
+/// of the file needn't be read. A line can add several matches, so callers
+/// still trim to the cap. With line ranges, matches outside them don't count,
+/// so the whole file is read and trimmed afterwards.
+fn per_file_cap_reached(options: &SearchOptions, results: &[SearchResult]) -> bool {
+    options.line_ranges.is_empty()
+        && options
+            .max_matches_per_file
+            .is_some_and(|cap| results.len() >= cap)
+}
+
 fn process_streaming_line(
-    regex: &Regex,
+    regex: &SearchRegex,
     file_path: &Path,
     line: &str,
     line_number: usize,
     byte_offset: usize,
+    options: &SearchOptions,
     results: &mut Vec<SearchResult>,
 ) {
-    if regex.as_str().is_empty() {
+    if options.invert_match && regex.is_match(line) {
+        return;
+    }
+    // An empty pattern, like an inverted one, reports the whole line once
+    if options.invert_match || regex.as_str().is_empty() {
         results.push(SearchResult {
             file: file_path.to_path_buf(),
             span: Span {
@@ -764,23 +1343,48 @@ fn process_streaming_line(
             symbol: None,
             chunk_hash: None,
             index_epoch: None,
+            original_score: None,
+            stale: false,
+            root: None,
+            preview_line: None,
+            duplicates: 0,
+            grouped: 0,
+            column: Some(1),
+            match_line: None,
+            explanation: None,
+            context_before: None,
+            context_after: None,
         });
     } else {
         for mat in regex.find_iter(line) {
             results.push(SearchResult {
                 file: file_path.to_path_buf(),
                 span: Span {
-                    byte_start: byte_offset + mat.start(),
-                    byte_end: byte_offset + mat.end(),
+                    byte_start: byte_offset + mat.start,
+                    byte_end: byte_offset + mat.end,
                     line_start: line_number,
                     line_end: line_number,
                 },
                 score: 1.0,
-                preview: line.to_string(),
+                preview: match options.replace.as_deref() {
+                    Some(template) => regex.expand_at(line, mat.start, template),
+                    None => line.to_string(),
+                },
                 lang: ck_core::Language::from_path(file_path),
                 symbol: None,
                 chunk_hash: None,
                 index_epoch: None,
+                original_score: None,
+                stale: false,
+                root: None,
+                preview_line: None,
+                duplicates: 0,
+                grouped: 0,
+                column: Some(mat.start + 1),
+                match_line: None,
+                explanation: None,
+                context_before: None,
+                context_after: None,
             });
         }
     }
@@ -790,6 +1394,10 @@ fn process_streaming_line(
 /// the tantivy index was built from, so staleness is detectable.
 const TANTIVY_META_FILE: &str = "tantivy_index.meta";
 
+/// Bumped whenever the tantivy schema changes, so indexes built with an
+/// older schema fail the freshness check and are rebuilt.
+const LEXICAL_SCHEMA_VERSION: &str = "3";
+
 /// Fingerprint of the file set a tantivy index covers: path, mtime and size
 /// of every corpus file. Any added, removed, or modified file changes the
 /// fingerprint, as does a different exclude-pattern set (it changes the
@@ -815,6 +1423,8 @@ fn lexical_corpus_fingerprint(files: &[PathBuf]) -> String {
     entries.sort_unstable();
 
     let mut hasher = blake3::Hasher::new();
+    hasher.update(LEXICAL_SCHEMA_VERSION.as_bytes());
+    hasher.update(b"\n");
     for entry in &entries {
         hasher.update(entry.as_bytes());
         hasher.update(b"\n");
@@ -832,30 +1442,162 @@ fn lexical_query_terms(query: &str) -> Vec<String> {
         .collect()
 }
 
-/// Refine the span and preview reported for a lexical hit down to the chunk
-/// that best matches the query.
-///
-/// `terms` are the content-field terms of the parsed tantivy query (already
-/// tokenized and lowercased), not the raw query string — so field prefixes,
-/// quotes, and boolean operators, which tantivy strips before matching, don't
-/// leak into span selection.
-///
-/// Retrieval is document-granular, so this runs only after the top-k documents
-/// are selected and never changes which documents match or their scores. The
-/// file is chunked with the same chunker the semantic path uses; the winning
-/// chunk is the one with the most whole-token matches for `terms`, ties broken
-/// toward the earliest chunk. When `terms` is empty, no chunk contains a term,
-/// or the content cannot be chunked, the whole-file span and a first-lines
-/// preview are returned unchanged.
-fn locate_lexical_span(
-    file_path: &Path,
-    content: &str,
-    terms: &[String],
-    full_section: bool,
-) -> (Span, String) {
-    let whole_file = || {
-        let span = Span {
-            byte_start: 0,
+/// Split `text` into the words `-w` matches against: lowercased runs of
+/// alphanumerics and underscores, the same boundaries as regex `\b`. Unlike
+/// [`lexical_query_terms`], `test_case` stays one word, so `-w test` does not
+/// match it.
+fn whole_words(text: &str) -> impl Iterator<Item = String> + '_ {
+    text.split(|c: char| !c.is_alphanumeric() && c != '_')
+        .filter(|word| !word.is_empty())
+        .map(str::to_lowercase)
+}
+
+/// The words of `query` a `-w` lexical or semantic hit must contain at least
+/// one of, with boolean operators dropped.
+pub(crate) fn whole_word_terms(query: &str) -> Vec<String> {
+    let boolean = boolean_query::is_boolean_query(query);
+    let mut terms = Vec::new();
+    for word in query.split(|c: char| !c.is_alphanumeric() && c != '_') {
+        if word.is_empty() || (boolean && matches!(word, "AND" | "OR" | "NOT")) {
+            continue;
+        }
+        let word = word.to_lowercase();
+        if !terms.contains(&word) {
+            terms.push(word);
+        }
+    }
+    terms
+}
+
+/// Whether `text` contains one of `terms` (from [`whole_word_terms`]) as a
+/// whole word.
+pub(crate) fn contains_whole_word(text: &str, terms: &[String]) -> bool {
+    whole_words(text).any(|word| terms.contains(&word))
+}
+
+/// Largest edit distance `--fuzzy` will bridge between a query term and an
+/// indexed term.
+const FUZZY_MAX_DISTANCE: usize = 2;
+
+/// Cap on vocabulary expansions per query term. Without it a short term can
+/// pull in hundreds of neighbors and turn one clause into a huge disjunction.
+const FUZZY_MAX_EXPANSIONS: usize = 8;
+
+/// Edit distance allowed for a query term of this length. Very short terms
+/// get no expansion and medium ones only a single edit: "id" is two edits
+/// away from nearly every two-letter token in a codebase.
+fn fuzzy_distance_for(term: &str) -> usize {
+    match term.chars().count() {
+        0..=2 => 0,
+        3..=5 => 1,
+        _ => FUZZY_MAX_DISTANCE,
+    }
+}
+
+/// Boost applied to a fuzzy expansion, so a term `distance` edits away
+/// contributes proportionally less than an exact hit.
+fn fuzzy_penalty(distance: usize) -> f32 {
+    1.0 / (1.0 + distance as f32)
+}
+
+/// Levenshtein distance between `a` and `b`, or `None` once it is known to
+/// exceed `max`.
+fn bounded_levenshtein(a: &str, b: &str, max: usize) -> Option<usize> {
+    let a: Vec<char> = a.chars().collect();
+    let b: Vec<char> = b.chars().collect();
+    if a.len().abs_diff(b.len()) > max {
+        return None;
+    }
+
+    let mut prev: Vec<usize> = (0..=b.len()).collect();
+    let mut curr = vec![0; b.len() + 1];
+    for (i, ca) in a.iter().enumerate() {
+        curr[0] = i + 1;
+        let mut row_min = curr[0];
+        for (j, cb) in b.iter().enumerate() {
+            let cost = usize::from(ca != cb);
+            curr[j + 1] = (prev[j] + cost).min(prev[j + 1] + 1).min(curr[j] + 1);
+            row_min = row_min.min(curr[j + 1]);
+        }
+        if row_min > max {
+            return None;
+        }
+        std::mem::swap(&mut prev, &mut curr);
+    }
+
+    let distance = prev[b.len()];
+    (distance <= max).then_some(distance)
+}
+
+/// Indexed terms of `field` within the allowed edit distance of `term`,
+/// nearest first (then most frequent), capped at [`FUZZY_MAX_EXPANSIONS`].
+/// The exact term itself is never returned; the parsed query already has it.
+fn fuzzy_expansions(searcher: &Searcher, field: Field, term: &str) -> Result<Vec<(String, usize)>> {
+    let max = fuzzy_distance_for(term);
+    if max == 0 {
+        return Ok(Vec::new());
+    }
+
+    // term -> (distance, doc frequency summed across segments)
+    let mut neighbors: HashMap<String, (usize, u64)> = HashMap::new();
+    for segment_reader in searcher.segment_readers() {
+        let inverted_index = segment_reader.inverted_index(field)?;
+        let mut stream = inverted_index.terms().stream()?;
+        while stream.advance() {
+            let Ok(candidate) = std::str::from_utf8(stream.key()) else {
+                continue;
+            };
+            if candidate == term {
+                continue;
+            }
+            if let Some(distance) = bounded_levenshtein(term, candidate, max) {
+                let doc_freq = u64::from(stream.value().doc_freq);
+                neighbors
+                    .entry(candidate.to_string())
+                    .and_modify(|(_, freq)| *freq += doc_freq)
+                    .or_insert((distance, doc_freq));
+            }
+        }
+    }
+
+    let mut expansions: Vec<(String, usize, u64)> = neighbors
+        .into_iter()
+        .map(|(candidate, (distance, freq))| (candidate, distance, freq))
+        .collect();
+    expansions.sort_by(|a, b| a.1.cmp(&b.1).then(b.2.cmp(&a.2)).then(a.0.cmp(&b.0)));
+    expansions.truncate(FUZZY_MAX_EXPANSIONS);
+    Ok(expansions
+        .into_iter()
+        .map(|(candidate, distance, _)| (candidate, distance))
+        .collect())
+}
+
+/// Refine the span and preview reported for a lexical hit down to the chunk
+/// that best matches the query.
+///
+/// `terms` are the content-field terms of the parsed tantivy query (already
+/// tokenized and lowercased), not the raw query string — so field prefixes,
+/// quotes, and boolean operators, which tantivy strips before matching, don't
+/// leak into span selection.
+///
+/// Retrieval is document-granular, so this runs only after the top-k documents
+/// are selected and never changes which documents match or their scores. The
+/// file is chunked with the same chunker the semantic path uses; the winning
+/// chunk is the one with the most whole-token matches for `terms`, ties broken
+/// toward the earliest chunk. When `terms` is empty, no chunk contains a term,
+/// or the content cannot be chunked, the whole-file span and a first-lines
+/// preview are returned unchanged. With `whole_word`, chunks are split into
+/// [`whole_words`] instead, so `terms` should come from [`whole_word_terms`].
+fn locate_lexical_span(
+    file_path: &Path,
+    content: &str,
+    terms: &[String],
+    full_section: bool,
+    whole_word: bool,
+) -> (Span, String) {
+    let whole_file = || {
+        let span = Span {
+            byte_start: 0,
             byte_end: content.len(),
             line_start: 1,
             line_end: content.lines().count(),
@@ -879,7 +1621,14 @@ fn locate_lexical_span(
         ck_core::Language::from_path(file_path)
     };
 
-    let chunks = match ck_chunk::chunk_text(content, lang) {
+    let chunks = match ck_chunk::chunk_file_content(
+        file_path,
+        content,
+        lang,
+        None,
+        0,
+        ck_chunk::ChunkStrategy::SyntaxAware,
+    ) {
         Ok(chunks) => chunks,
         Err(_) => return whole_file(),
     };
@@ -891,10 +1640,16 @@ fn locate_lexical_span(
     // larger words ("string", "printing") and let a coincidental chunk win.
     let mut best: Option<(usize, &ck_chunk::Chunk)> = None;
     for chunk in &chunks {
-        let hits = lexical_query_terms(&chunk.text)
-            .into_iter()
-            .filter(|token| terms.contains(token))
-            .count();
+        let hits = if whole_word {
+            whole_words(&chunk.text)
+                .filter(|word| terms.contains(word))
+                .count()
+        } else {
+            lexical_query_terms(&chunk.text)
+                .into_iter()
+                .filter(|token| terms.contains(token))
+                .count()
+        };
         if hits == 0 {
             continue;
         }
@@ -916,7 +1671,57 @@ fn locate_lexical_span(
     }
 }
 
-async fn lexical_search(options: &SearchOptions) -> Result<Vec<SearchResult>> {
+/// Files the lexical index over `index_root` covers.
+fn lexical_corpus(options: &SearchOptions, index_root: &Path) -> Result<Vec<PathBuf>> {
+    // The tantivy index always covers the whole index root (include patterns
+    // are applied per result at search time), so corpus membership only
+    // depends on the root and the exclusion rules.
+    //
+    // Collection goes through ck_index::collect_files — the same walker the
+    // regex and semantic paths use — so gitignore/.ckignore semantics match
+    // and exclude patterns apply relative to the walk root. The engine-local
+    // collect_files matched exclude globs against every *absolute* path
+    // component, so a corpus under e.g. /tmp on Linux matched the default
+    // "tmp" exclude and silently produced an empty lexical index.
+    let file_options = ck_core::FileCollectionOptions {
+        respect_gitignore: options.respect_gitignore,
+        use_ckignore: options.use_ckignore,
+        exclude_patterns: options.exclude_patterns.clone(),
+        include_globs: Vec::new(),
+        show_hidden: options.hidden,
+        follow_links: options.follow_links,
+        include_submodules: options.include_submodules,
+        max_depth: None,
+        skip_generated: false,
+    };
+    let mut corpus = ck_index::collect_files(index_root, &file_options)?;
+    // --files-from files under the root count even when the walk skips them
+    if !options.explicit_files.is_empty() {
+        let walked: HashSet<PathBuf> = corpus
+            .iter()
+            .map(|f| canonicalize_for_matching(f))
+            .collect();
+        let canonical_root = canonicalize_for_matching(index_root);
+        for file in &options.explicit_files {
+            let canonical = canonicalize_for_matching(file);
+            if let Ok(relative) = canonical.strip_prefix(&canonical_root)
+                && !walked.contains(&canonical)
+            {
+                corpus.push(index_root.join(relative));
+            }
+        }
+    }
+    Ok(corpus)
+}
+
+/// BM25 search over the tantivy index, recording in `timings` how long
+/// bringing that index up to date (`candidate_load`) and querying it
+/// (`scoring`) took. Also returns whether the deadline cut the hits short.
+async fn lexical_search(
+    options: &SearchOptions,
+    timings: &mut ck_core::SearchTimings,
+) -> Result<(Vec<SearchResult>, bool)> {
+    let started = std::time::Instant::now();
     // Handle both files and directories and reuse nearest existing .ck index up the tree
     let index_root = find_nearest_index_root(&options.path).unwrap_or_else(|| {
         if options.path.is_file() {
@@ -934,27 +1739,24 @@ async fn lexical_search(options: &SearchOptions) -> Result<Vec<SearchResult>> {
     // via a CK_INDEX_DIR basename-hash collision. No-op in-tree.
     ck_core::check_index_root_marker(&index_root)?;
 
-    let tantivy_index_path = index_dir.join("tantivy_index");
-
-    // The tantivy index always covers the whole index root (include patterns
-    // are applied per result at search time below), so corpus membership only
-    // depends on the root and the exclusion rules.
-    //
-    // Collection goes through ck_index::collect_files — the same walker the
-    // regex and semantic paths use — so gitignore/.ckignore semantics match
-    // and exclude patterns apply relative to the walk root. The engine-local
-    // collect_files matched exclude globs against every *absolute* path
-    // component, so a corpus under e.g. /tmp on Linux matched the default
-    // "tmp" exclude and silently produced an empty lexical index.
-    let file_options = ck_core::FileCollectionOptions {
-        respect_gitignore: options.respect_gitignore,
-        use_ckignore: options.use_ckignore,
-        exclude_patterns: options.exclude_patterns.clone(),
-        show_hidden: options.hidden,
+    // With --on chunks the documents are the index's chunks, kept in an
+    // index of their own whose corpus is the sidecars recording them
+    let chunked = options.match_on == MatchOn::Chunks;
+    let (tantivy_index_path, meta_path, corpus) = if chunked {
+        let sidecars = semantic_v3::indexed_files(&index_dir, &index_root)?;
+        (
+            index_dir.join(chunk_match::CHUNK_TANTIVY_DIR),
+            index_dir.join(chunk_match::CHUNK_TANTIVY_META_FILE),
+            sidecars.into_iter().map(|(_, sidecar)| sidecar).collect(),
+        )
+    } else {
+        (
+            index_dir.join("tantivy_index"),
+            index_dir.join(TANTIVY_META_FILE),
+            lexical_corpus(options, &index_root)?,
+        )
     };
-    let corpus = ck_index::collect_files(&index_root, &file_options)?;
     let fingerprint = lexical_corpus_fingerprint(&corpus);
-    let meta_path = index_dir.join(TANTIVY_META_FILE);
     let is_fresh = tantivy_index_path.exists()
         && fs::read_to_string(&meta_path)
             .map(|stored| stored.trim() == fingerprint)
@@ -975,18 +1777,46 @@ async fn lexical_search(options: &SearchOptions) -> Result<Vec<SearchResult>> {
                 index_root.display(),
                 corpus.len()
             );
-            build_tantivy_index(&tantivy_index_path, &corpus)?;
+            if chunked {
+                ck_index::WorkerPool::new(options.jobs)?.install(|| {
+                    chunk_match::build_chunk_tantivy_index(
+                        &tantivy_index_path,
+                        &index_dir,
+                        &index_root,
+                        &corpus,
+                    )
+                })?;
+            } else {
+                ck_index::WorkerPool::new(options.jobs)?
+                    .install(|| build_tantivy_index(&tantivy_index_path, &index_root, &corpus))?;
+            }
             fs::write(&meta_path, &fingerprint)?;
         }
     }
 
-    let mut schema_builder = Schema::builder();
-    let content_field = schema_builder.add_text_field("content", TEXT | STORED);
-    let path_field = schema_builder.add_text_field("path", TEXT | STORED);
-    let _schema = schema_builder.build();
-
+    timings.candidate_load = Some(started.elapsed());
     let index = Index::open_in_dir(&tantivy_index_path)
         .map_err(|e| CkError::Index(format!("Failed to open tantivy index: {e}")))?;
+    query_lexical_index(options, &index, timings)
+}
+
+/// Run the lexical search `options` describes against `index`, whose
+/// documents are whole files or, when it has [`chunk_match::ChunkFields`],
+/// chunks. Records how long that took in `timings.scoring`. Hits not reached
+/// by the deadline are left out, and the flag returned with the results
+/// says so.
+fn query_lexical_index(
+    options: &SearchOptions,
+    index: &Index,
+    timings: &mut ck_core::SearchTimings,
+) -> Result<(Vec<SearchResult>, bool)> {
+    let started = std::time::Instant::now();
+    let schema = index.schema();
+    let content_field = schema.get_field("content")?;
+    let path_field = schema.get_field("path")?;
+    let identifiers_field = schema.get_field("identifiers")?;
+    let chunk_fields = chunk_match::ChunkFields::of(&schema);
+    let chunked = chunk_fields.is_some();
 
     let reader = index
         .reader_builder()
@@ -995,14 +1825,46 @@ async fn lexical_search(options: &SearchOptions) -> Result<Vec<SearchResult>> {
         .map_err(|e| CkError::Index(format!("Failed to create index reader: {e}")))?;
 
     let searcher = reader.searcher();
-    let query_parser = QueryParser::for_index(&index, vec![content_field]);
+    // With --split-identifiers, also search the split words of every indexed
+    // identifier and add the query's own split words, so `getUserById`
+    // matches `get_user_by_id` and `fetchUserById` as well as itself.
+    let (query_parser, query_text) = if options.split_identifiers {
+        let words = identifier_words(&options.query);
+        let query_text = if words.is_empty() {
+            options.query.clone()
+        } else {
+            format!("{} {}", options.query, words.join(" "))
+        };
+        (
+            QueryParser::for_index(index, vec![content_field, identifiers_field]),
+            query_text,
+        )
+    } else {
+        (
+            QueryParser::for_index(index, vec![content_field]),
+            options.query.clone(),
+        )
+    };
 
     // Parse leniently so any string is a valid query: syntax tantivy can't
     // interpret (unbalanced quotes, stray field colons, bare boolean operators)
     // degrades to the terms it can parse instead of erroring. A query that
     // already parses cleanly yields the same query object with no errors, so
     // its results and scores are unchanged.
-    let (query, parse_errors) = query_parser.parse_query_lenient(&options.query);
+    //
+    // Queries using AND/OR/NOT are parsed strictly by boolean_query instead;
+    // each word or phrase in them is quoted for tantivy, so its tokens must
+    // appear next to each other.
+    let (query, parse_errors) = if boolean_query::is_boolean_query(&options.query) {
+        let expr = boolean_query::parse(&options.query)?;
+        let query = boolean_query::compile(&expr, &mut |text| {
+            let quoted = format!("\"{}\"", text.replace('\\', " "));
+            query_parser.parse_query_lenient(&quoted).0
+        });
+        (query, Vec::new())
+    } else {
+        query_parser.parse_query_lenient(&query_text)
+    };
     for error in &parse_errors {
         tracing::debug!(
             "lenient parse of lexical query {:?}: {error:?}",
@@ -1015,8 +1877,13 @@ async fn lexical_search(options: &SearchOptions) -> Result<Vec<SearchResult>> {
     // from the parsed query rather than the raw string means field prefixes,
     // phrases, and operators are already resolved to their leaf terms.
     let mut span_terms: Vec<String> = Vec::new();
+    // The content terms whose nearness --proximity rewards
+    let mut proximity_terms: Vec<Term> = Vec::new();
     query.query_terms(&mut |term, _| {
-        if term.field() == content_field
+        if term.field() == content_field && !proximity_terms.contains(term) {
+            proximity_terms.push(term.clone());
+        }
+        if (term.field() == content_field || term.field() == identifiers_field)
             && let Some(text) = term.value().as_str()
         {
             let lowered = text.to_lowercase();
@@ -1026,45 +1893,217 @@ async fn lexical_search(options: &SearchOptions) -> Result<Vec<SearchResult>> {
         }
     });
 
-    let top_docs = if let Some(top_k) = options.top_k {
-        searcher.search(&query, &TopDocs::with_limit(top_k))?
+    // With --fuzzy, OR each query term's near neighbors from the index
+    // vocabulary into the parsed query, boosted down by edit distance so
+    // exact matches still rank first.
+    let mut fuzzy_neighbors: Vec<String> = Vec::new();
+    let mut fuzzy_boosts: HashMap<String, f32> = HashMap::new();
+    let query: Box<dyn Query> = if options.fuzzy {
+        let mut clauses: Vec<(Occur, Box<dyn Query>)> = Vec::new();
+        for term in span_terms.clone() {
+            for (neighbor, distance) in fuzzy_expansions(&searcher, content_field, &term)? {
+                let term_query = TermQuery::new(
+                    Term::from_field_text(content_field, &neighbor),
+                    IndexRecordOption::WithFreqs,
+                );
+                clauses.push((
+                    Occur::Should,
+                    Box::new(BoostQuery::new(
+                        Box::new(term_query),
+                        fuzzy_penalty(distance),
+                    )),
+                ));
+                fuzzy_boosts.insert(neighbor.clone(), fuzzy_penalty(distance));
+                if !span_terms.contains(&neighbor) {
+                    fuzzy_neighbors.push(neighbor.clone());
+                    span_terms.push(neighbor);
+                }
+            }
+        }
+        if clauses.is_empty() {
+            query
+        } else {
+            clauses.insert(0, (Occur::Should, query));
+            Box::new(BooleanQuery::new(clauses))
+        }
     } else {
-        searcher.search(&query, &TopDocs::with_limit(100))?
+        query
     };
 
-    // First, collect all results with raw scores
-    let mut raw_results = Vec::new();
-    for (_score, doc_address) in top_docs {
-        let retrieved_doc: TantivyDocument = searcher.doc(doc_address)?;
-        let path_text = retrieved_doc
-            .get_first(path_field)
-            .map(|field_value| field_value.as_str().unwrap_or(""))
-            .unwrap_or("");
-        let content_text = retrieved_doc
-            .get_first(content_field)
-            .map(|field_value| field_value.as_str().unwrap_or(""))
-            .unwrap_or("");
-
-        let file_path = PathBuf::from(path_text);
-        if !path_matches_include(&file_path, &options.include_patterns) {
-            continue;
+    // With -w, tantivy's hits are only candidates: its tokenizer splits on
+    // underscores, so `test` hits `test_case`, and --split-identifiers hits
+    // `testCase`. Each document must also contain one of the query's words
+    // whole, on the same boundaries as regex `\btest\b`. Fuzzy neighbors
+    // are whole indexed tokens and count as words too.
+    let word_terms: Option<Vec<String>> = options.whole_word.then(|| {
+        let mut terms = whole_word_terms(&options.query);
+        terms.extend(fuzzy_neighbors);
+        terms
+    });
+
+    // Inverted, the results are the files with no hit. There's no term in
+    // them to point at, so each is reported whole. With -w a token-level hit
+    // may still lack a whole word, so every document is a candidate and the
+    // word check below decides.
+    let query: Box<dyn Query> = if options.invert_match {
+        span_terms.clear();
+        if word_terms.is_some() {
+            Box::new(AllQuery)
+        } else {
+            Box::new(BooleanQuery::new(vec![
+                (Occur::Must, Box::new(AllQuery)),
+                (Occur::MustNot, query),
+            ]))
         }
-        let (span, preview) =
-            locate_lexical_span(&file_path, content_text, &span_terms, options.full_section);
+    } else {
+        query
+    };
+    let located_terms: &[String] = match &word_terms {
+        Some(terms) if !options.invert_match => terms,
+        _ => &span_terms,
+    };
 
-        raw_results.push((
-            _score,
-            SearchResult {
-                file: file_path,
-                span,
-                score: _score,
-                preview,
-                lang: ck_core::Language::from_path(&PathBuf::from(path_text)),
-                symbol: None,
-                chunk_hash: None,
-                index_epoch: None,
-            },
-        ));
+    // tantivy's own scoring uses the default parameters; other values rescore
+    // its hits, and --proximity boosts whichever score applies. Inverted
+    // results all score the same either way.
+    let rescorer = (options.bm25 != ck_core::Bm25Params::default() && !options.invert_match)
+        .then(|| bm25::Bm25Rescorer::new(&searcher, query.as_ref(), &fuzzy_boosts, options.bm25))
+        .transpose()?;
+    let boost = (options.proximity > 0.0 && !options.invert_match).then(|| {
+        proximity::ProximityBoost::new(proximity_terms, options.proximity, rescorer.clone())
+    });
+    let rank = |page: TopDocs| {
+        if let Some(boost) = &boost {
+            searcher.search(&query, &page.tweak_score(boost.clone()))
+        } else if let Some(rescorer) = &rescorer {
+            searcher.search(&query, &page.tweak_score(rescorer.clone()))
+        } else {
+            searcher.search(&query, &page)
+        }
+    };
+
+    // Hits the path, date, generated-file, line-range and -w checks below
+    // drop don't count towards top_k, so with any of them in effect the
+    // ranking is read a page at a time (each twice the last) until top_k
+    // hits survive or it runs out, rather than filtering only the first top_k
+    let top_k = options.top_k.unwrap_or(100);
+    let include_globs = search_include_globs(options)?;
+    let filtered = !options.include_patterns.is_empty()
+        || !include_globs.is_empty()
+        || options.modified_since.is_some()
+        || options.no_generated
+        || !options.line_ranges.is_empty()
+        || word_terms.is_some();
+    let mut raw_results = Vec::new();
+    let mut truncated = false;
+    let mut offset = 0;
+    let mut page_size = top_k;
+    'pages: loop {
+        let page = rank(TopDocs::with_limit(page_size).and_offset(offset))?;
+        let exhausted = page.len() < page_size;
+        offset += page.len();
+        page_size *= 2;
+        for (_score, doc_address) in page {
+            // Hits are read in rank order, so those kept are the best found
+            if options.is_past_deadline() {
+                truncated = true;
+                break 'pages;
+            }
+            let retrieved_doc: TantivyDocument = searcher.doc(doc_address)?;
+            let path_text = retrieved_doc
+                .get_first(path_field)
+                .map(|field_value| field_value.as_str().unwrap_or(""))
+                .unwrap_or("");
+            let content_text = retrieved_doc
+                .get_first(content_field)
+                .map(|field_value| field_value.as_str().unwrap_or(""))
+                .unwrap_or("");
+
+            let file_path = PathBuf::from(path_text);
+            // A chunk document's content is the chunk, which is its own span
+            let chunk = chunk_fields
+                .as_ref()
+                .map(|fields| fields.read(&retrieved_doc));
+            let generated = match &chunk {
+                Some(chunk) => chunk.generated,
+                None => ck_index::is_generated(&file_path, content_text),
+            };
+            if !path_matches_include(&file_path, &options.include_patterns)
+                || !include_globs.matches(&file_path)
+                || !modified_since_matches(&file_path, options)
+                || (options.no_generated && generated)
+            {
+                continue;
+            }
+            if let Some(terms) = &word_terms
+                && contains_whole_word(content_text, terms) == options.invert_match
+            {
+                continue;
+            }
+            let (span, preview) = match &chunk {
+                Some(chunk) => (chunk.span.clone(), content_text.to_string()),
+                None => locate_lexical_span(
+                    &file_path,
+                    content_text,
+                    located_terms,
+                    options.full_section,
+                    options.whole_word && !options.invert_match,
+                ),
+            };
+            if !options.line_ranges.admits(&file_path, &span) {
+                continue;
+            }
+            let match_line = (span.line_end > span.line_start)
+                .then(|| {
+                    let span_text: String = match &chunk {
+                        Some(_) => content_text.to_string(),
+                        None => content_text
+                            .lines()
+                            .skip(span.line_start - 1)
+                            .take(span.line_end + 1 - span.line_start)
+                            .collect::<Vec<_>>()
+                            .join("\n"),
+                    };
+                    snippet::match_line_offset(&span_text, &options.query)
+                })
+                .flatten()
+                .map(|offset| span.line_start + offset);
+            let (symbol, chunk_hash) = chunk
+                .map(|chunk| (chunk.symbol, chunk.chunk_hash))
+                .unwrap_or_default();
+
+            raw_results.push((
+                _score,
+                SearchResult {
+                    file: file_path,
+                    span,
+                    score: _score,
+                    preview,
+                    lang: ck_core::Language::from_path(&PathBuf::from(path_text)),
+                    symbol,
+                    chunk_hash,
+                    index_epoch: None,
+                    original_score: None,
+                    stale: false,
+                    root: None,
+                    // A --full-section preview is the whole file
+                    preview_line: (options.full_section && !chunked).then_some(1),
+                    duplicates: 0,
+                    grouped: 0,
+                    column: None,
+                    match_line,
+                    explanation: None,
+                    context_before: None,
+                    context_after: None,
+                },
+            ));
+            if raw_results.len() == top_k {
+                break 'pages;
+            }
+        }
+        if exhausted || !filtered {
+            break;
+        }
     }
 
     // Normalize scores to 0-1 range and apply threshold
@@ -1075,26 +2114,44 @@ async fn lexical_search(options: &SearchOptions) -> Result<Vec<SearchResult>> {
             .map(|(score, _)| *score)
             .fold(0.0f32, f32::max);
         if max_score > 0.0 {
+            let cutoff = score_cutoff(
+                options,
+                raw_results.iter().map(|(score, _)| score / max_score),
+            );
             for (raw_score, mut result) in raw_results {
                 let normalized_score = raw_score / max_score;
 
                 // Apply threshold filtering with normalized score
-                if let Some(threshold) = options.threshold
+                if let Some(threshold) = cutoff
                     && normalized_score < threshold
                 {
                     continue;
                 }
 
                 result.score = normalized_score;
+                if options.explain {
+                    result.explanation = Some(ck_core::ScoreBreakdown {
+                        bm25: Some(raw_score),
+                        threshold: cutoff,
+                        ..Default::default()
+                    });
+                }
                 results.push(result);
             }
         }
     }
+    // Tantivy returns equal scores in segment order
+    results.sort_by(SearchResult::rank_cmp);
+    timings.scoring = Some(started.elapsed());
 
-    Ok(results)
+    Ok((results, truncated))
 }
 
-/// (Re)build the tantivy index at `tantivy_index_path` over `files`.
+/// Files read at once while building the lexical index.
+const LEXICAL_READ_BATCH: usize = 256;
+
+/// (Re)build the tantivy index at `tantivy_index_path` over `files`, which
+/// are under `index_root`.
 /// Callers must hold the index write lock. Any existing index is replaced —
 /// tantivy has no cheap way to diff segments against a changed corpus, and a
 /// full text-only rebuild is fast relative to embedding work.
@@ -1102,7 +2159,11 @@ async fn lexical_search(options: &SearchOptions) -> Result<Vec<SearchResult>> {
 /// Searching the result happens in [`lexical_search`]; this function builds
 /// only (its previous incarnation duplicated the entire search/read path,
 /// which had already drifted — the rebuilt-path copy lost include filtering).
-fn build_tantivy_index(tantivy_index_path: &Path, files: &[PathBuf]) -> Result<()> {
+fn build_tantivy_index(
+    tantivy_index_path: &Path,
+    index_root: &Path,
+    files: &[PathBuf],
+) -> Result<()> {
     if tantivy_index_path.exists() {
         fs::remove_dir_all(tantivy_index_path)?;
     }
@@ -1111,6 +2172,7 @@ fn build_tantivy_index(tantivy_index_path: &Path, files: &[PathBuf]) -> Result<(
     let mut schema_builder = Schema::builder();
     let content_field = schema_builder.add_text_field("content", TEXT | STORED);
     let path_field = schema_builder.add_text_field("path", TEXT | STORED);
+    let identifiers_field = schema_builder.add_text_field("identifiers", TEXT);
     let schema = schema_builder.build();
 
     let index = Index::create_in_dir(tantivy_index_path, schema)
@@ -1120,12 +2182,27 @@ fn build_tantivy_index(tantivy_index_path: &Path, files: &[PathBuf]) -> Result<(
         .writer(50_000_000)
         .map_err(|e| CkError::Index(format!("Failed to create index writer: {e}")))?;
 
-    for file_path in files {
-        if let Ok(content) = fs::read_to_string(file_path) {
-            let doc = doc!(
-                content_field => content,
-                path_field => file_path.display().to_string()
-            );
+    // Files are read and split into identifiers on the worker pool, a batch
+    // at a time so memory stays bounded, and added in corpus order
+    for batch in files.chunks(LEXICAL_READ_BATCH) {
+        let docs: Vec<_> = batch
+            .par_iter()
+            .filter_map(|file_path| {
+                // Notebooks are searched by their extracted cells, like the other modes
+                let content = if ck_core::notebook::is_notebook_file(file_path) {
+                    read_file_content(file_path, index_root)
+                } else {
+                    ck_index::read_text(file_path)
+                };
+                let content = content.ok()?;
+                Some(doc!(
+                    identifiers_field => identifier_words(&content).join(" "),
+                    content_field => content,
+                    path_field => file_path.display().to_string()
+                ))
+            })
+            .collect();
+        for doc in docs {
             index_writer.add_document(doc)?;
         }
     }
@@ -1139,7 +2216,7 @@ fn build_tantivy_index(tantivy_index_path: &Path, files: &[PathBuf]) -> Result<(
 
 #[allow(dead_code)]
 async fn hybrid_search(options: &SearchOptions) -> Result<Vec<SearchResult>> {
-    hybrid_search_with_progress(options, None).await
+    Ok(hybrid_search_with_progress(options, None).await?.matches)
 }
 
 /// English filler words excluded from the keyword arm of hybrid search.
@@ -1165,6 +2242,31 @@ fn hybrid_query_terms(query: &str) -> Vec<String> {
         .collect()
 }
 
+/// Terms to highlight in lexical and semantic previews: the meaningful query
+/// words plus the words of any compound identifier among them, lowercased.
+/// `"getUserById lookup"` gives `["getuserbyid", "user", "lookup"]`.
+pub fn highlight_terms(query: &str) -> Vec<String> {
+    let mut seen = std::collections::HashSet::new();
+    let mut terms = Vec::new();
+    for token in query.split(|c: char| !c.is_alphanumeric() && c != '_') {
+        let parts = split_identifier(token);
+        let words = std::iter::once(token.to_string()).chain(if parts.len() > 1 {
+            parts
+        } else {
+            Vec::new()
+        });
+        for word in words.map(|w| w.to_lowercase()) {
+            if word.len() >= 3
+                && !HYBRID_STOPWORDS.contains(&word.as_str())
+                && seen.insert(word.clone())
+            {
+                terms.push(word);
+            }
+        }
+    }
+    terms
+}
+
 /// The keyword arm of hybrid search. Natural-language queries ("how are stale
 /// entries cleaned up") almost never match the corpus as a literal regex,
 /// which previously degraded hybrid search to semantic-only exactly when the
@@ -1173,15 +2275,23 @@ fn hybrid_query_terms(query: &str) -> Vec<String> {
 /// rank the matches by how many distinct terms each line covers (the regex
 /// engine itself has no scoring — raw traversal order would rank a line
 /// matching one common term above a line matching all of them).
-fn hybrid_keyword_search(options: &SearchOptions) -> Result<(Vec<SearchResult>, bool)> {
-    let literal = regex_search(options)?;
-    if !literal.is_empty() || options.fixed_string {
-        return Ok((literal, false));
+fn hybrid_keyword_search(options: &SearchOptions) -> Result<KeywordArm> {
+    let literal = regex_scan(options)?;
+    if !literal.matches.is_empty() || options.fixed_string {
+        return Ok(KeywordArm {
+            matches: literal.matches,
+            fallback: false,
+            truncated: literal.truncated,
+        });
     }
 
     let terms = hybrid_query_terms(&options.query);
     if terms.len() < 2 {
-        return Ok((literal, false));
+        return Ok(KeywordArm {
+            matches: literal.matches,
+            fallback: false,
+            truncated: literal.truncated,
+        });
     }
 
     let mut keyword_options = options.clone();
@@ -1195,7 +2305,8 @@ fn hybrid_keyword_search(options: &SearchOptions) -> Result<(Vec<SearchResult>,
     // than the literal pattern would; don't let regex_search's internal top_k
     // cut them in traversal order before we rank them below.
     keyword_options.top_k = None;
-    let matches = regex_search(&keyword_options)?;
+    let scan = regex_scan(&keyword_options)?;
+    let matches = scan.matches;
 
     // Rank matches by the rarity of the terms they contain (IDF over the
     // match set): a line containing a term that matched 3 lines corpus-wide
@@ -1233,7 +2344,20 @@ fn hybrid_keyword_search(options: &SearchOptions) -> Result<(Vec<SearchResult>,
     if let Some(top_k) = options.top_k {
         scored.truncate(top_k);
     }
-    Ok((scored.into_iter().map(|(_, r)| r).collect(), true))
+    Ok(KeywordArm {
+        matches: scored.into_iter().map(|(_, r)| r).collect(),
+        fallback: true,
+        truncated: literal.truncated || scan.truncated,
+    })
+}
+
+/// What the keyword arm of a hybrid search found.
+struct KeywordArm {
+    matches: Vec<SearchResult>,
+    /// The matches come from the term fallback rather than the literal pattern
+    fallback: bool,
+    /// The deadline passed before every file was scanned
+    truncated: bool,
 }
 
 /// Fuse keyword and semantic rankings with Reciprocal Rank Fusion:
@@ -1253,6 +2377,7 @@ fn rrf_fuse(
     keyword_results: &[SearchResult],
     semantic_results: &[SearchResult],
     keyword_weight: f32,
+    explain: bool,
 ) -> Vec<SearchResult> {
     const RRF_K: f32 = 60.0;
 
@@ -1316,6 +2441,21 @@ fn rrf_fuse(
             let rank_score = |rank: Option<usize>| rank.map_or(0.0, |r| 1.0 / (RRF_K + r as f32));
             result.score =
                 keyword_weight * rank_score(fused.keyword_rank) + rank_score(fused.semantic_rank);
+            if explain {
+                // A keyword hit folded into a semantic chunk keeps the chunk's
+                // similarity; a keyword-only result has none
+                let similarity = fused
+                    .semantic_rank
+                    .and_then(|_| result.explanation.as_ref()?.similarity);
+                result.explanation = Some(ck_core::ScoreBreakdown {
+                    similarity,
+                    keyword_rank: fused.keyword_rank,
+                    semantic_rank: fused.semantic_rank,
+                    keyword_weight: Some(keyword_weight),
+                    rrf_k: Some(RRF_K),
+                    ..Default::default()
+                });
+            }
             result
         })
         .collect()
@@ -1324,73 +2464,128 @@ fn rrf_fuse(
 async fn hybrid_search_with_progress(
     options: &SearchOptions,
     progress_callback: Option<SearchProgressCallback>,
-) -> Result<Vec<SearchResult>> {
+) -> Result<ck_core::SearchResults> {
     // Fetch more candidates from each arm than the final cut: fusion can
     // only promote results it sees, and both regex_search and the semantic
     // ranking truncate to top_k internally — at the original top_k, a result
     // boosted by the other arm would never reach the fusion stage at all.
     let mut arm_options = options.clone();
     arm_options.top_k = options.top_k.map(|k| (k * 5).max(50));
+    // A percentile applies to the fused scores, not to either arm's
+    arm_options.threshold_percentile = None;
+    // So is the per-file cap: the regex arm would keep a file's first lines
+    // rather than its best
+    arm_options.max_matches_per_file = None;
 
     if let Some(ref callback) = progress_callback {
         callback("Running keyword search...");
     }
-    let (keyword_results, keyword_is_fallback) = hybrid_keyword_search(&arm_options)?;
+    let started = std::time::Instant::now();
+    let keyword = hybrid_keyword_search(&arm_options)?;
+    let keyword_timings = ck_core::SearchTimings {
+        scoring: Some(started.elapsed()),
+        ..Default::default()
+    };
 
     if let Some(ref callback) = progress_callback {
         callback("Running semantic search...");
     }
     let semantic_results =
         semantic_search_v3_with_progress(&arm_options, progress_callback).await?;
+    let timings = semantic_results.timings.combined(keyword_timings);
 
-    let keyword_weight = if keyword_is_fallback { 0.3 } else { 1.0 };
-    let mut rrf_results = rrf_fuse(&keyword_results, &semantic_results.matches, keyword_weight);
+    let keyword_weight = if keyword.fallback { 0.3 } else { 1.0 };
+    let mut rrf_results = rrf_fuse(
+        &keyword.matches,
+        &semantic_results.matches,
+        keyword_weight,
+        options.explain,
+    );
 
     // Apply threshold filtering to raw RRF scores
-    if let Some(threshold) = options.threshold {
+    if let Some(threshold) = score_cutoff(options, rrf_results.iter().map(|r| r.score)) {
         rrf_results.retain(|result| result.score >= threshold);
+        for explanation in rrf_results
+            .iter_mut()
+            .filter_map(|r| r.explanation.as_mut())
+        {
+            explanation.threshold = Some(threshold);
+        }
     }
 
-    rrf_results.retain(|result| path_matches_include(&result.file, &options.include_patterns));
-
-    // Sort by RRF score (highest first)
-    rrf_results.sort_by(|a, b| {
-        b.score
-            .partial_cmp(&a.score)
-            .unwrap_or(std::cmp::Ordering::Equal)
+    let include_globs = search_include_globs(options)?;
+    rrf_results.retain(|result| {
+        path_matches_include(&result.file, &options.include_patterns)
+            && include_globs.matches(&result.file)
+            && modified_since_matches(&result.file, options)
+            && options.line_ranges.admits(&result.file, &result.span)
     });
 
+    // Sort by RRF score (highest first), ties by path and position so pages
+    // cut from the ranking are stable
+    rrf_results.sort_by(SearchResult::rank_cmp);
+
+    let mut within_cap = per_file_cap(options);
+    rrf_results.retain(|result| within_cap(&result.file));
     if let Some(top_k) = options.top_k {
         rrf_results.truncate(top_k);
     }
 
-    Ok(rrf_results)
+    Ok(ck_core::SearchResults {
+        matches: rrf_results,
+        closest_below_threshold: None,
+        timings,
+        truncated: keyword.truncated || semantic_results.truncated,
+        lower_thresholds: Vec::new(),
+    })
 }
 
-fn build_globset(patterns: &[String]) -> GlobSet {
-    let mut builder = GlobSetBuilder::new();
-    for pat in patterns {
-        // Treat patterns as filename or directory globs
-        if let Ok(glob) = Glob::new(pat) {
-            builder.add(glob);
-        }
-    }
-    builder.build().unwrap_or_else(|_| GlobSet::empty())
+/// Exclude globs evaluated the way `.gitignore` lines are: the last matching
+/// pattern decides, so a `!pattern` match keeps a path an earlier pattern
+/// excluded.
+struct ExcludeGlobs {
+    set: GlobSet,
+    negated: Vec<bool>,
 }
 
-fn should_exclude_path(path: &Path, globset: &GlobSet) -> bool {
-    // Match against each path component and the full path
-    if globset.is_match(path) {
-        return true;
-    }
-    for component in path.components() {
-        if let std::path::Component::Normal(name) = component
-            && globset.is_match(name)
-        {
-            return true;
+impl ExcludeGlobs {
+    fn new(patterns: &[String]) -> Self {
+        let mut builder = GlobSetBuilder::new();
+        let mut negated = Vec::new();
+        for pat in patterns {
+            let (pat, is_negated) = match pat.strip_prefix('!') {
+                Some(rest) => (rest, true),
+                None => (pat.as_str(), false),
+            };
+            // Treat patterns as filename or directory globs
+            if let Ok(glob) = Glob::new(pat) {
+                builder.add(glob);
+                negated.push(is_negated);
+            }
         }
+        Self {
+            set: builder.build().unwrap_or_else(|_| GlobSet::empty()),
+            negated,
+        }
+    }
+
+    /// Whether the last pattern matching any of `candidates` excludes
+    fn is_excluded<'a>(&self, candidates: impl IntoIterator<Item = &'a Path>) -> bool {
+        candidates
+            .into_iter()
+            .flat_map(|candidate| self.set.matches(candidate))
+            .max()
+            .is_some_and(|index| !self.negated[index])
     }
-    false
+}
+
+fn should_exclude_path(path: &Path, globs: &ExcludeGlobs) -> bool {
+    // Match against the full path and each of its components
+    let components = path.components().filter_map(|component| match component {
+        std::path::Component::Normal(name) => Some(Path::new(name)),
+        _ => None,
+    });
+    globs.is_excluded(std::iter::once(path).chain(components))
 }
 
 fn collect_files(
@@ -1399,7 +2594,7 @@ fn collect_files(
     exclude_patterns: &[String],
 ) -> Result<Vec<PathBuf>> {
     let mut files = Vec::new();
-    let globset = build_globset(exclude_patterns);
+    let globs = ExcludeGlobs::new(exclude_patterns);
 
     if path.is_file() {
         // Always add single files, even if they're excluded (user explicitly requested)
@@ -1407,12 +2602,11 @@ fn collect_files(
     } else if recursive {
         for entry in WalkDir::new(path).into_iter().filter_entry(|e| {
             // Skip excluded directories entirely for efficiency
-            let name = e.file_name();
-            !globset.is_match(e.path()) && !globset.is_match(name)
+            !globs.is_excluded([e.path(), Path::new(e.file_name())])
         }) {
             match entry {
                 Ok(entry) => {
-                    if entry.file_type().is_file() && !should_exclude_path(entry.path(), &globset) {
+                    if entry.file_type().is_file() && !should_exclude_path(entry.path(), &globs) {
                         files.push(entry.path().to_path_buf());
                     }
                 }
@@ -1430,7 +2624,7 @@ fn collect_files(
                     match entry {
                         Ok(entry) => {
                             let path = entry.path();
-                            if path.is_file() && !should_exclude_path(&path, &globset) {
+                            if path.is_file() && !should_exclude_path(&path, &globs) {
                                 files.push(path);
                             }
                         }
@@ -1455,12 +2649,10 @@ fn collect_files(
 /// `None` for the single-file fast path (which reports no stats).
 async fn ensure_index_updated_with_progress(
     path: &Path,
-    force_reindex: bool,
-    need_embeddings: bool,
     progress_callback: Option<ck_index::ProgressCallback>,
     detailed_progress_callback: Option<ck_index::DetailedProgressCallback>,
     file_options: &ck_core::FileCollectionOptions,
-    model_override: Option<&str>,
+    update: ck_index::IndexUpdateOptions<'_>,
 ) -> Result<Option<ck_index::UpdateStats>> {
     // Find index root for .ck directory location
     let index_root_buf = find_nearest_index_root(path).unwrap_or_else(|| {
@@ -1474,15 +2666,13 @@ async fn ensure_index_updated_with_progress(
 
     // Pass the original path to indexing function so it can index just that file/directory
     // The indexing function will use collect_files() which now handles individual files correctly
-    if force_reindex {
-        let stats = ck_index::smart_update_index_with_detailed_progress(
+    if update.force_rebuild {
+        let stats = ck_index::smart_update_index_with_resume(
             index_root,
-            true,
+            file_options,
             progress_callback,
             detailed_progress_callback,
-            need_embeddings,
-            file_options,
-            model_override,
+            update,
         )
         .await?;
         if stats.files_indexed > 0 || stats.orphaned_files_removed > 0 {
@@ -1500,18 +2690,16 @@ async fn ensure_index_updated_with_progress(
     if path.is_file() {
         // Index just this one file
         use ck_index::index_file;
-        index_file(path, need_embeddings).await?;
+        index_file(path, update.compute_embeddings).await?;
         Ok(None)
     } else {
         // For directories, use the standard smart update
-        let stats = ck_index::smart_update_index_with_detailed_progress(
+        let stats = ck_index::smart_update_index_with_resume(
             index_root,
-            false,
+            file_options,
             progress_callback,
             detailed_progress_callback,
-            need_embeddings,
-            file_options,
-            model_override,
+            update,
         )
         .await?;
         if stats.files_indexed > 0 || stats.orphaned_files_removed > 0 {
@@ -1525,16 +2713,42 @@ async fn ensure_index_updated_with_progress(
     }
 }
 
-fn get_context_preview(lines: &[String], line_idx: usize, options: &SearchOptions) -> String {
-    let before = options.before_context_lines.max(options.context_lines);
-    let after = options.after_context_lines.max(options.context_lines);
+/// Lines of context `-B`/`-A`/`-C` ask for before and after each match.
+fn context_line_counts(options: &SearchOptions) -> (usize, usize) {
+    (
+        options.before_context_lines.max(options.context_lines),
+        options.after_context_lines.max(options.context_lines),
+    )
+}
 
-    if before > 0 || after > 0 {
-        let start_idx = line_idx.saturating_sub(before);
-        let end_idx = (line_idx + after + 1).min(lines.len());
-        lines[start_idx..end_idx].join("\n")
-    } else {
-        lines[line_idx].to_string()
+/// The context lines before and after `line_idx`, clipped to the file;
+/// `None` when no context was requested.
+fn context_around<'a>(
+    lines: &'a [String],
+    line_idx: usize,
+    options: &SearchOptions,
+) -> Option<(&'a [String], &'a [String])> {
+    let (before, after) = context_line_counts(options);
+    if before == 0 && after == 0 {
+        return None;
+    }
+    let end_idx = (line_idx + after + 1).min(lines.len());
+    Some((
+        &lines[line_idx.saturating_sub(before)..line_idx],
+        &lines[line_idx + 1..end_idx],
+    ))
+}
+
+fn get_context_preview(lines: &[String], line_idx: usize, options: &SearchOptions) -> String {
+    match context_around(lines, line_idx, options) {
+        Some((before, after)) => before
+            .iter()
+            .chain([&lines[line_idx]])
+            .chain(after)
+            .map(String::as_str)
+            .collect::<Vec<_>>()
+            .join("\n"),
+        None => lines[line_idx].to_string(),
     }
 }
 
@@ -1583,13 +2797,29 @@ fn extract_code_sections(file_path: &Path, content: &str) -> Option<Vec<(usize,
 fn find_containing_section(
     sections: &[(usize, usize, String)],
     line_idx: usize,
-) -> Option<&String> {
-    for (start, end, text) in sections {
-        if line_idx >= *start && line_idx <= *end {
-            return Some(text);
-        }
+) -> Option<&(usize, usize, String)> {
+    sections
+        .iter()
+        .find(|(start, end, _)| line_idx >= *start && line_idx <= *end)
+}
+
+/// Preview for a regex match on `line_idx`: under `--full-section`, the code
+/// section containing it along with the section's first line (1-based);
+/// otherwise, or outside any section, the usual context lines.
+fn regex_match_preview(
+    lines: &[String],
+    line_idx: usize,
+    code_sections: &Option<Vec<(usize, usize, String)>>,
+    options: &SearchOptions,
+) -> (String, Option<usize>) {
+    let section = code_sections
+        .as_deref()
+        .filter(|_| options.full_section)
+        .and_then(|sections| find_containing_section(sections, line_idx));
+    match section {
+        Some((start, _, text)) => (text.clone(), Some(start + 1)),
+        None => (get_context_preview(lines, line_idx, options), None),
     }
-    None
 }
 
 #[cfg(test)]
@@ -1613,6 +2843,17 @@ mod tests {
             symbol: None,
             chunk_hash: None,
             index_epoch: None,
+            original_score: None,
+            stale: false,
+            root: None,
+            preview_line: None,
+            duplicates: 0,
+            grouped: 0,
+            column: None,
+            match_line: None,
+            explanation: None,
+            context_before: None,
+            context_after: None,
         }
     }
 
@@ -1629,6 +2870,19 @@ mod tests {
         assert!(hybrid_query_terms("how does the it").is_empty());
     }
 
+    #[test]
+    fn test_highlight_terms_include_identifier_words() {
+        assert_eq!(
+            highlight_terms("where is loadUserProfile, user lookup"),
+            vec!["loaduserprofile", "load", "user", "profile", "lookup"]
+        );
+        assert_eq!(
+            highlight_terms("MAX_RETRY_COUNT"),
+            vec!["max_retry_count", "max", "retry", "count"]
+        );
+        assert!(highlight_terms("how does the it").is_empty());
+    }
+
     #[test]
     fn test_rrf_fuse_merges_keyword_hit_into_containing_semantic_chunk() {
         // Semantic chunk spans lines 10-50; keyword hit lands on line 20
@@ -1638,13 +2892,13 @@ mod tests {
         ];
         let keyword = vec![make_result("src/a.rs", 20, 20, "let rrf_score = ranks")];
 
-        let fused = rrf_fuse(&keyword, &semantic, 1.0);
+        let fused = rrf_fuse(&keyword, &semantic, 1.0, false);
 
         // The keyword hit fused into the chunk: 3 inputs, 2 outputs
         assert_eq!(fused.len(), 2);
         let chunk = fused
             .iter()
-            .find(|r| r.file == PathBuf::from("src/a.rs"))
+            .find(|r| r.file == Path::new("src/a.rs"))
             .unwrap();
         // Chunk-level span retained, score = both lists at rank 1
         assert_eq!((chunk.span.line_start, chunk.span.line_end), (10, 50));
@@ -1654,7 +2908,7 @@ mod tests {
         // The fused result must outrank the semantic-only one
         let other = fused
             .iter()
-            .find(|r| r.file == PathBuf::from("src/b.rs"))
+            .find(|r| r.file == Path::new("src/b.rs"))
             .unwrap();
         assert!(chunk.score > other.score);
     }
@@ -1664,11 +2918,11 @@ mod tests {
         let semantic = vec![make_result("src/a.rs", 10, 50, "chunk")];
         let keyword = vec![make_result("src/z.rs", 7, 7, "standalone line")];
 
-        let fused = rrf_fuse(&keyword, &semantic, 1.0);
+        let fused = rrf_fuse(&keyword, &semantic, 1.0, false);
         assert_eq!(fused.len(), 2);
         let standalone = fused
             .iter()
-            .find(|r| r.file == PathBuf::from("src/z.rs"))
+            .find(|r| r.file == Path::new("src/z.rs"))
             .unwrap();
         assert!((standalone.score - 1.0 / 61.0).abs() < 1e-6);
     }
@@ -1683,12 +2937,49 @@ mod tests {
             make_result("src/a.rs", 40, 40, "second hit"),
         ];
 
-        let fused = rrf_fuse(&keyword, &semantic, 1.0);
+        let fused = rrf_fuse(&keyword, &semantic, 1.0, false);
         assert_eq!(fused.len(), 1);
         let expected = 1.0 / 61.0 + 1.0 / 61.0; // sem rank 1 + best keyword rank 1
         assert!((fused[0].score - expected).abs() < 1e-6);
     }
 
+    #[test]
+    fn test_rrf_fuse_explains_ranks_and_similarity() {
+        let mut chunk = make_result("src/a.rs", 10, 50, "chunk");
+        chunk.explanation = Some(ck_core::ScoreBreakdown {
+            similarity: Some(0.72),
+            ..Default::default()
+        });
+        let semantic = vec![make_result("src/b.rs", 1, 5, "other"), chunk];
+        let keyword = vec![
+            make_result("src/z.rs", 7, 7, "standalone line"),
+            make_result("src/a.rs", 20, 20, "hit"),
+        ];
+
+        let fused = rrf_fuse(&keyword, &semantic, 0.3, true);
+        let explanation = |file: &str| {
+            fused
+                .iter()
+                .find(|r| r.file == Path::new(file))
+                .and_then(|r| r.explanation.clone())
+                .unwrap()
+        };
+        assert_eq!(
+            explanation("src/a.rs"),
+            ck_core::ScoreBreakdown {
+                similarity: Some(0.72),
+                keyword_rank: Some(2),
+                semantic_rank: Some(2),
+                keyword_weight: Some(0.3),
+                rrf_k: Some(60.0),
+                ..Default::default()
+            }
+        );
+        let standalone = explanation("src/z.rs");
+        assert_eq!(standalone.similarity, None);
+        assert_eq!(standalone.semantic_rank, None);
+    }
+
     #[test]
     fn test_lexical_corpus_fingerprint_tracks_changes() {
         let temp_dir = TempDir::new().unwrap();
@@ -1782,7 +3073,9 @@ mod tests {
             line_end: 7,
         };
 
-        let result = extract_content_from_span(&test_file, &span).await.unwrap();
+        let result = extract_content_from_span(&SearchOptions::default(), &test_file, &span)
+            .await
+            .unwrap();
         assert_eq!(result, "fn second() {\n    println!(\"Second\");\n}");
 
         // Test extracting a single line
@@ -1793,7 +3086,9 @@ mod tests {
             line_end: 2,
         };
 
-        let result = extract_content_from_span(&test_file, &span).await.unwrap();
+        let result = extract_content_from_span(&SearchOptions::default(), &test_file, &span)
+            .await
+            .unwrap();
         assert_eq!(result, "    println!(\"First\");");
     }
 
@@ -1921,6 +3216,159 @@ mod tests {
         assert!(results.len() <= 5);
     }
 
+    #[test]
+    fn test_regex_search_max_matches_per_file() {
+        let temp_dir = TempDir::new().unwrap();
+        fs::write(temp_dir.path().join("many.txt"), "test test\ntest\ntest\n").unwrap();
+        fs::write(temp_dir.path().join("one.txt"), "test\n").unwrap();
+
+        let options = SearchOptions {
+            mode: SearchMode::Regex,
+            query: "test".to_string(),
+            path: temp_dir.path().to_path_buf(),
+            max_matches_per_file: Some(3),
+            ..Default::default()
+        };
+        let results = regex_search(&options).unwrap();
+        let lines: Vec<_> = results
+            .iter()
+            .map(|r| {
+                (
+                    r.file.file_name().unwrap().to_str().unwrap(),
+                    r.span.line_start,
+                )
+            })
+            .collect();
+        // The cap counts matches, and a line can hold several
+        assert_eq!(
+            lines,
+            [
+                ("many.txt", 1),
+                ("many.txt", 1),
+                ("many.txt", 2),
+                ("one.txt", 1)
+            ]
+        );
+
+        // With context the file is searched in memory; the cap is the same
+        let options = SearchOptions {
+            context_lines: 1,
+            max_matches_per_file: Some(1),
+            ..options
+        };
+        assert_eq!(regex_search(&options).unwrap().len(), 2);
+    }
+
+    #[test]
+    fn test_regex_search_invert_match() {
+        let temp_dir = TempDir::new().unwrap();
+        fs::write(
+            temp_dir.path().join("file.txt"),
+            "error: disk\r\ninfo: ok\r\nerror: net\r\ndebug\r\n",
+        )
+        .unwrap();
+
+        let options = SearchOptions {
+            mode: SearchMode::Regex,
+            query: "^error".to_string(),
+            path: temp_dir.path().join("file.txt"),
+            invert_match: true,
+            ..Default::default()
+        };
+        let streamed = regex_search(&options).unwrap();
+        let lines: Vec<_> = streamed
+            .iter()
+            .map(|r| (r.span.line_start, r.preview.as_str(), r.column))
+            .collect();
+        // One whole-line result per line the pattern doesn't match
+        assert_eq!(lines, [(2, "info: ok", Some(1)), (4, "debug", Some(1))]);
+        assert_eq!(streamed[0].span.byte_start, 13);
+        assert_eq!(streamed[0].span.byte_end, 21);
+
+        // The in-memory scan (used for context) inverts the same lines
+        let in_memory = regex_search(&SearchOptions {
+            context_lines: 1,
+            ..options.clone()
+        })
+        .unwrap();
+        let byte_ranges = |results: &[SearchResult]| {
+            results
+                .iter()
+                .map(|r| (r.span.byte_start, r.span.byte_end))
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(byte_ranges(&in_memory), byte_ranges(&streamed));
+
+        // An empty pattern matches every line, so nothing is left
+        let empty = SearchOptions {
+            query: String::new(),
+            ..options
+        };
+        assert!(regex_search(&empty).unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_regex_search_replace_expands_captures_per_match() {
+        let temp_dir = TempDir::new().unwrap();
+        fs::write(
+            temp_dir.path().join("deps.toml"),
+            "serde = \"1.0\"\ntokio = \"1.38\"\n",
+        )
+        .unwrap();
+
+        let options = SearchOptions {
+            mode: SearchMode::Regex,
+            query: r#"^(?P<name>\w+) = "([\d.]+)""#.to_string(),
+            path: temp_dir.path().join("deps.toml"),
+            replace: Some("${name}@$2".to_string()),
+            ..Default::default()
+        };
+        let previews: Vec<_> = regex_search(&options)
+            .unwrap()
+            .into_iter()
+            .map(|r| (r.span.line_start, r.preview))
+            .collect();
+        assert_eq!(
+            previews,
+            [(1, "serde@1.0".to_string()), (2, "tokio@1.38".to_string())]
+        );
+
+        // Each match on a line is expanded on its own
+        let options = SearchOptions {
+            query: r"(\d+)\.(\d+)".to_string(),
+            replace: Some("$2.$1".to_string()),
+            ..options
+        };
+        let previews: Vec<_> = regex_search(&options)
+            .unwrap()
+            .into_iter()
+            .map(|r| r.preview)
+            .collect();
+        assert_eq!(previews, ["0.1", "38.1"]);
+    }
+
+    #[test]
+    fn test_max_count_lowers_top_k_before_paging() {
+        let options = SearchOptions {
+            top_k: Some(10),
+            max_count: Some(4),
+            offset: 2,
+            ..Default::default()
+        };
+        assert_eq!(paged_options(&options).top_k, Some(6));
+        let options = SearchOptions {
+            top_k: None,
+            offset: 0,
+            ..options
+        };
+        assert_eq!(paged_options(&options).top_k, Some(4));
+        let options = SearchOptions {
+            top_k: Some(3),
+            ..options
+        };
+        assert_eq!(paged_options(&options).top_k, Some(3));
+    }
+
     #[test]
     fn test_regex_search_span_offsets() {
         // Test that span offsets are correctly calculated for multiple matches on a line
@@ -1970,7 +3418,7 @@ mod tests {
         )
         .unwrap();
 
-        let regex = regex::Regex::new("rust").unwrap();
+        let regex = SearchRegex::from(regex::Regex::new("rust").unwrap());
         let options = SearchOptions::default();
 
         let results = search_file(&regex, &file_path, &options).unwrap();
@@ -1985,7 +3433,7 @@ mod tests {
         let file_path = temp_dir.path().join("test.txt");
         fs::write(&file_path, "line 1\nline 2\ntarget line\nline 4\nline 5").unwrap();
 
-        let regex = regex::Regex::new("target").unwrap();
+        let regex = SearchRegex::from(regex::Regex::new("target").unwrap());
         let options = SearchOptions {
             context_lines: 1,
             ..Default::default()
@@ -2003,6 +3451,34 @@ mod tests {
         assert!(results[0].preview.contains("line 4"));
     }
 
+    #[test]
+    fn test_context_lines_are_returned_separately() {
+        let temp_dir = TempDir::new().unwrap();
+        let file_path = temp_dir.path().join("test.txt");
+        fs::write(&file_path, "target first\nline 2\nline 3\ntarget last").unwrap();
+
+        let regex = SearchRegex::from(regex::Regex::new("target").unwrap());
+        let options = SearchOptions {
+            before_context_lines: 2,
+            after_context_lines: 1,
+            ..Default::default()
+        };
+        let results = search_file(&regex, &file_path, &options).unwrap();
+        assert_eq!(results.len(), 2);
+        assert_eq!(results[0].context_before, Some(Vec::new()));
+        assert_eq!(results[0].context_after, Some(vec!["line 2".to_string()]));
+        assert_eq!(results[0].preview, "target first\nline 2");
+        assert_eq!(
+            results[1].context_before,
+            Some(vec!["line 2".to_string(), "line 3".to_string()])
+        );
+        assert_eq!(results[1].context_after, Some(Vec::new()));
+
+        let results = search_file(&regex, &file_path, &SearchOptions::default()).unwrap();
+        assert_eq!(results[0].context_before, None);
+        assert_eq!(results[0].context_after, None);
+    }
+
     #[tokio::test]
     async fn test_search_main_function() {
         let temp_dir = TempDir::new().unwrap();
@@ -2021,6 +3497,211 @@ mod tests {
         assert!(!results.is_empty());
     }
 
+    #[tokio::test]
+    async fn test_regex_search_past_deadline_returns_truncated() {
+        let temp_dir = TempDir::new().unwrap();
+        create_test_files(temp_dir.path());
+
+        let mut options = SearchOptions {
+            mode: SearchMode::Regex,
+            query: "hello".to_string(),
+            path: temp_dir.path().to_path_buf(),
+            case_insensitive: true,
+            deadline: Some(std::time::Instant::now() + std::time::Duration::from_secs(60)),
+            ..Default::default()
+        };
+        let outcome = search_enhanced_with_outcome(&options, None, None, None)
+            .await
+            .unwrap();
+        assert!(!outcome.results.truncated);
+        assert!(!outcome.results.matches.is_empty());
+
+        options.deadline = Some(std::time::Instant::now());
+        let outcome = search_enhanced_with_outcome(&options, None, None, None)
+            .await
+            .unwrap();
+        assert!(outcome.results.truncated);
+        assert!(outcome.results.matches.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_index_backed_searches_past_deadline_return_truncated() {
+        let temp_dir = TempDir::new().unwrap();
+        create_test_files(temp_dir.path());
+
+        for mode in [
+            SearchMode::Semantic,
+            SearchMode::Lexical,
+            SearchMode::Hybrid,
+        ] {
+            let mut options = SearchOptions {
+                mode: mode.clone(),
+                query: "hello".to_string(),
+                path: temp_dir.path().to_path_buf(),
+                case_insensitive: true,
+                deadline: Some(std::time::Instant::now() + std::time::Duration::from_secs(60)),
+                ..Default::default()
+            };
+            let outcome = search_enhanced_with_outcome(&options, None, None, None)
+                .await
+                .unwrap();
+            assert!(!outcome.results.truncated, "{mode:?}");
+            assert!(!outcome.results.matches.is_empty(), "{mode:?}");
+
+            // The index is up to date, so only reading it runs into the deadline
+            options.deadline = Some(std::time::Instant::now());
+            options.no_auto_index = true;
+            let outcome = search_enhanced_with_outcome(&options, None, None, None)
+                .await
+                .unwrap();
+            assert!(outcome.results.truncated, "{mode:?}");
+            assert!(outcome.results.matches.is_empty(), "{mode:?}");
+        }
+    }
+
+    #[tokio::test]
+    async fn test_auto_index_stops_at_deadline() {
+        let temp_dir = TempDir::new().unwrap();
+        create_test_files(temp_dir.path());
+
+        let options = SearchOptions {
+            mode: SearchMode::Semantic,
+            query: "hello".to_string(),
+            path: temp_dir.path().to_path_buf(),
+            deadline: Some(std::time::Instant::now()),
+            ..Default::default()
+        };
+        let outcome = search_enhanced_with_outcome(&options, None, None, None)
+            .await
+            .unwrap();
+        assert!(outcome.results.truncated);
+        let manifest = ck_core::index_dir(temp_dir.path()).join("manifest.json");
+        assert!(!manifest.exists(), "the update stopped before any file");
+    }
+
+    #[tokio::test]
+    async fn test_ephemeral_search_leaves_no_index() {
+        let temp_dir = TempDir::new().unwrap();
+        fs::write(
+            temp_dir.path().join("retry.rs"),
+            "fn retry_with_backoff() {\n    sleep(delay * 2);\n}\n",
+        )
+        .unwrap();
+        fs::write(temp_dir.path().join("notes.txt"), "Release checklist\n").unwrap();
+
+        let mut options = SearchOptions {
+            mode: SearchMode::Semantic,
+            query: "retry with exponential backoff".to_string(),
+            path: temp_dir.path().to_path_buf(),
+            top_k: Some(10),
+            ephemeral: true,
+            ..Default::default()
+        };
+        let outcome = search_enhanced_with_outcome(&options, None, None, None)
+            .await
+            .unwrap();
+        assert!(outcome.index_update.is_none());
+        let mut files: Vec<_> = outcome
+            .results
+            .matches
+            .iter()
+            .map(|r| r.file.file_name().unwrap().to_string_lossy().into_owned())
+            .collect();
+        files.sort();
+        files.dedup();
+        assert_eq!(files, ["notes.txt", "retry.rs"]);
+        assert!(!ck_core::index_dir(temp_dir.path()).exists());
+
+        options.mode = SearchMode::Lexical;
+        let err = search(&options).await.unwrap_err();
+        assert!(err.to_string().contains("--ephemeral"), "{err}");
+    }
+
+    #[tokio::test]
+    async fn test_max_depth_applies_to_regex_and_lexical_search() {
+        let temp_dir = TempDir::new().unwrap();
+        fs::create_dir_all(temp_dir.path().join("src/nested")).unwrap();
+        fs::write(temp_dir.path().join("top.txt"), "zebra at the top\n").unwrap();
+        fs::write(temp_dir.path().join("src/mid.txt"), "zebra in src\n").unwrap();
+        fs::write(
+            temp_dir.path().join("src/nested/deep.txt"),
+            "zebra deep down\n",
+        )
+        .unwrap();
+
+        for mode in [SearchMode::Regex, SearchMode::Lexical] {
+            let options = SearchOptions {
+                mode: mode.clone(),
+                query: "zebra".to_string(),
+                path: temp_dir.path().to_path_buf(),
+                max_depth: Some(2),
+                ..Default::default()
+            };
+            let mut files: Vec<_> = search(&options)
+                .await
+                .unwrap()
+                .iter()
+                .map(|r| r.file.file_name().unwrap().to_string_lossy().into_owned())
+                .collect();
+            files.sort();
+            assert_eq!(files, ["mid.txt", "top.txt"], "{mode:?}");
+        }
+    }
+
+    #[tokio::test]
+    async fn test_markdown_results_carry_heading_path() {
+        let temp_dir = TempDir::new().unwrap();
+        fs::write(
+            temp_dir.path().join("guide.md"),
+            "# Install\n\n## macOS\n\n```bash\nbrew install ck\n```\n\n# Usage\n\nRun ck.\n",
+        )
+        .unwrap();
+
+        let options = SearchOptions {
+            mode: SearchMode::Regex,
+            query: "brew|Run".to_string(),
+            path: temp_dir.path().to_path_buf(),
+            recursive: true,
+            ..Default::default()
+        };
+        let results = search(&options).await.unwrap();
+        let symbols: Vec<Option<&str>> = results.iter().map(|r| r.symbol.as_deref()).collect();
+        assert_eq!(symbols, [Some("Install > macOS"), Some("Usage")]);
+    }
+
+    #[tokio::test]
+    async fn test_cancelled_search_fails_with_cancelled() {
+        let temp_dir = TempDir::new().unwrap();
+        create_test_files(temp_dir.path());
+
+        let token = ck_core::CancellationToken::new();
+        let options = SearchOptions {
+            mode: SearchMode::Regex,
+            query: "hello".to_string(),
+            path: temp_dir.path().to_path_buf(),
+            cancel: Some(token.clone()),
+            ..Default::default()
+        };
+        assert!(!search(&options).await.unwrap().is_empty());
+
+        token.cancel();
+        let err = search(&options).await.unwrap_err();
+        assert!(ck_core::is_cancelled(&err), "{err:#}");
+        let err = search(&SearchOptions {
+            mode: SearchMode::Lexical,
+            ..options.clone()
+        })
+        .await
+        .unwrap_err();
+        assert!(ck_core::is_cancelled(&err), "{err:#}");
+        // Nothing was indexed for the cancelled lexical search
+        assert!(
+            !ck_core::index_dir(temp_dir.path())
+                .join("manifest.json")
+                .exists()
+        );
+    }
+
     #[tokio::test]
     async fn test_regex_search_mixed_line_endings() {
         // Regression test for byte offset issues with different line endings
@@ -2438,9 +4119,10 @@ mod tests {
             ..Default::default()
         };
 
-        let results = lexical_search(&options)
+        let results = lexical_search(&options, &mut Default::default())
             .await
-            .expect("lenient parse must not error on an unbalanced quote");
+            .expect("lenient parse must not error on an unbalanced quote")
+            .0;
         assert!(
             results
                 .iter()
@@ -2448,6 +4130,112 @@ mod tests {
         );
     }
 
+    #[tokio::test]
+    async fn test_bm25_b_trades_off_long_and_short_files() {
+        // The long file mentions the term twice among much else, the short
+        // one once: length normalization favors the short file, without it
+        // the extra occurrence wins.
+        let temp_dir = TempDir::new().unwrap();
+        let filler = "unrelated words fill this line\n".repeat(40);
+        fs::write(
+            temp_dir.path().join("long.txt"),
+            format!("backoff first\n{filler}backoff again\n"),
+        )
+        .unwrap();
+        fs::write(temp_dir.path().join("short.txt"), "backoff\n").unwrap();
+        fs::write(temp_dir.path().join("other.txt"), "nothing to see\n").unwrap();
+        fs::create_dir_all(temp_dir.path().join(".ck")).unwrap();
+
+        let mut options = SearchOptions {
+            mode: SearchMode::Lexical,
+            query: "backoff".to_string(),
+            path: temp_dir.path().to_path_buf(),
+            recursive: true,
+            ..Default::default()
+        };
+        let ranking = |results: Vec<SearchResult>| -> Vec<String> {
+            results
+                .iter()
+                .map(|r| r.file.file_name().unwrap().to_string_lossy().into_owned())
+                .collect()
+        };
+
+        let results = lexical_search(&options, &mut Default::default())
+            .await
+            .unwrap()
+            .0;
+        assert_eq!(ranking(results), ["short.txt", "long.txt"]);
+
+        options.bm25.b = 0.0;
+        let results = lexical_search(&options, &mut Default::default())
+            .await
+            .unwrap()
+            .0;
+        assert_eq!(ranking(results), ["long.txt", "short.txt"]);
+
+        options.bm25.b = 1.5;
+        assert!(search(&options).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_proximity_ranks_adjacent_terms_above_distant_ones() {
+        // Same words in both files, so BM25 alone ties them (and the path
+        // breaks the tie in favor of the scattered one)
+        let temp_dir = TempDir::new().unwrap();
+        let filler = "unrelated words fill this line\n".repeat(20);
+        fs::write(
+            temp_dir.path().join("a_scattered.txt"),
+            format!("retry\n{filler}backoff\n"),
+        )
+        .unwrap();
+        fs::write(
+            temp_dir.path().join("b_adjacent.txt"),
+            format!("{filler}retry backoff\n"),
+        )
+        .unwrap();
+        fs::create_dir_all(temp_dir.path().join(".ck")).unwrap();
+
+        let mut options = SearchOptions {
+            mode: SearchMode::Lexical,
+            query: "retry backoff".to_string(),
+            path: temp_dir.path().to_path_buf(),
+            recursive: true,
+            ..Default::default()
+        };
+        let ranking = |results: Vec<SearchResult>| -> Vec<(String, f32)> {
+            results
+                .iter()
+                .map(|r| {
+                    let name = r.file.file_name().unwrap().to_string_lossy().into_owned();
+                    (name, r.score)
+                })
+                .collect()
+        };
+
+        let results = lexical_search(&options, &mut Default::default())
+            .await
+            .unwrap()
+            .0;
+        let ranked = ranking(results);
+        assert_eq!(ranked[0].0, "a_scattered.txt");
+        assert_eq!(ranked[0].1, ranked[1].1);
+
+        options.proximity = 1.0;
+        for b in [0.75, 0.0] {
+            options.bm25.b = b;
+            let results = lexical_search(&options, &mut Default::default())
+                .await
+                .unwrap()
+                .0;
+            let ranked = ranking(results);
+            assert_eq!(ranked[0].0, "b_adjacent.txt", "b = {b}");
+            assert!(ranked[0].1 > ranked[1].1, "b = {b}: {ranked:?}");
+        }
+
+        options.proximity = -1.0;
+        assert!(search(&options).await.is_err());
+    }
+
     #[tokio::test]
     async fn test_lexical_search_recovers_term_from_field_colon() {
         // A clause referencing an unknown field is dropped; the bare term still
@@ -2468,9 +4256,10 @@ mod tests {
             ..Default::default()
         };
 
-        let results = lexical_search(&options)
+        let results = lexical_search(&options, &mut Default::default())
             .await
-            .expect("lenient parse must not error on a stray field colon");
+            .expect("lenient parse must not error on a stray field colon")
+            .0;
         assert!(
             results
                 .iter()
@@ -2478,6 +4267,383 @@ mod tests {
         );
     }
 
+    #[tokio::test]
+    async fn test_lexical_search_whole_word() {
+        // -w matches `test` the way regex `\btest\b` does: not inside
+        // `testing`, and not inside `test_case`, which tantivy's tokenizer
+        // would otherwise split into a `test` hit.
+        let temp_dir = TempDir::new().unwrap();
+        fs::write(temp_dir.path().join("exact.txt"), "run the test now\n").unwrap();
+        fs::write(temp_dir.path().join("longer.txt"), "still testing it\n").unwrap();
+        fs::write(temp_dir.path().join("snake.txt"), "call test_case here\n").unwrap();
+        fs::create_dir_all(temp_dir.path().join(".ck")).unwrap();
+
+        let mut options = SearchOptions {
+            mode: SearchMode::Lexical,
+            query: "test".to_string(),
+            path: temp_dir.path().to_path_buf(),
+            recursive: true,
+            whole_word: true,
+            ..Default::default()
+        };
+        let names = |results: Vec<SearchResult>| {
+            let mut names: Vec<String> = results
+                .iter()
+                .map(|r| r.file.file_name().unwrap().to_string_lossy().to_string())
+                .collect();
+            names.sort();
+            names
+        };
+
+        let results = lexical_search(&options, &mut Default::default())
+            .await
+            .unwrap()
+            .0;
+        assert_eq!(names(results), ["exact.txt"]);
+
+        // Without -w the tokenizer's split still finds test_case
+        options.whole_word = false;
+        let results = lexical_search(&options, &mut Default::default())
+            .await
+            .unwrap()
+            .0;
+        assert_eq!(names(results), ["exact.txt", "snake.txt"]);
+
+        // Inverted, the files lacking the whole word are the results
+        options.whole_word = true;
+        options.invert_match = true;
+        let results = lexical_search(&options, &mut Default::default())
+            .await
+            .unwrap()
+            .0;
+        assert_eq!(names(results), ["longer.txt", "snake.txt"]);
+    }
+
+    #[tokio::test]
+    async fn test_lexical_whole_word_looks_past_outranking_partial_hits() {
+        // The test_case files outrank exact.txt for `test`, so -w must keep
+        // reading the ranking past the first top_k hits to find it
+        let temp_dir = TempDir::new().unwrap();
+        fs::write(
+            temp_dir.path().join("exact.txt"),
+            "we run the test here before anything else ships today\n",
+        )
+        .unwrap();
+        for name in ["x1.txt", "x2.txt", "x3.txt"] {
+            fs::write(temp_dir.path().join(name), "test_case test_case\n").unwrap();
+        }
+        fs::create_dir_all(temp_dir.path().join(".ck")).unwrap();
+
+        let mut options = SearchOptions {
+            mode: SearchMode::Lexical,
+            query: "test".to_string(),
+            path: temp_dir.path().to_path_buf(),
+            whole_word: true,
+            top_k: Some(2),
+            ..Default::default()
+        };
+        let names = |results: Vec<SearchResult>| {
+            let mut names: Vec<String> = results
+                .iter()
+                .map(|r| r.file.file_name().unwrap().to_string_lossy().to_string())
+                .collect();
+            names.sort();
+            names
+        };
+
+        let results = lexical_search(&options, &mut Default::default())
+            .await
+            .unwrap()
+            .0;
+        assert_eq!(names(results), ["exact.txt"]);
+
+        // Inverted, every file is a candidate and exact.txt mustn't use up one
+        // of the top_k places
+        options.invert_match = true;
+        options.top_k = Some(3);
+        let results = lexical_search(&options, &mut Default::default())
+            .await
+            .unwrap()
+            .0;
+        assert_eq!(names(results), ["x1.txt", "x2.txt", "x3.txt"]);
+    }
+
+    #[tokio::test]
+    async fn test_lexical_include_globs_apply_before_top_k() {
+        // Top-level files outrank sub/keep.rs; --include must not let them
+        // fill the top_k places and then drop them
+        let temp_dir = TempDir::new().unwrap();
+        fs::create_dir_all(temp_dir.path().join("sub")).unwrap();
+        fs::write(
+            temp_dir.path().join("sub").join("keep.rs"),
+            "// one test among many other words in this longer comment\n",
+        )
+        .unwrap();
+        for name in ["a.rs", "b.rs", "c.rs"] {
+            fs::write(temp_dir.path().join(name), "test test\n").unwrap();
+        }
+        fs::create_dir_all(temp_dir.path().join(".ck")).unwrap();
+
+        let options = SearchOptions {
+            mode: SearchMode::Lexical,
+            query: "test".to_string(),
+            path: temp_dir.path().to_path_buf(),
+            include_globs: vec!["sub/**".to_string()],
+            top_k: Some(2),
+            ..Default::default()
+        };
+        let results = lexical_search(&options, &mut Default::default())
+            .await
+            .unwrap()
+            .0;
+        assert_eq!(results.len(), 1, "{results:?}");
+        assert!(results[0].file.ends_with("sub/keep.rs"));
+    }
+
+    #[tokio::test]
+    async fn test_lexical_modified_since_applies_before_top_k() {
+        // Old files outrank the recent one; --modified-since must not let
+        // them fill the top_k places and then drop them
+        let temp_dir = TempDir::new().unwrap();
+        fs::write(
+            temp_dir.path().join("recent.rs"),
+            "// one test among many other words in this longer comment\n",
+        )
+        .unwrap();
+        let old = std::time::SystemTime::now() - std::time::Duration::from_secs(30 * 86400);
+        for name in ["a.rs", "b.rs", "c.rs"] {
+            let path = temp_dir.path().join(name);
+            fs::write(&path, "test test\n").unwrap();
+            fs::File::options()
+                .write(true)
+                .open(&path)
+                .unwrap()
+                .set_modified(old)
+                .unwrap();
+        }
+        fs::create_dir_all(temp_dir.path().join(".ck")).unwrap();
+
+        let options = SearchOptions {
+            mode: SearchMode::Lexical,
+            query: "test".to_string(),
+            path: temp_dir.path().to_path_buf(),
+            modified_since: Some(
+                std::time::SystemTime::now() - std::time::Duration::from_secs(7 * 86400),
+            ),
+            top_k: Some(2),
+            ..Default::default()
+        };
+        let results = lexical_search(&options, &mut Default::default())
+            .await
+            .unwrap()
+            .0;
+        assert_eq!(results.len(), 1, "{results:?}");
+        assert!(results[0].file.ends_with("recent.rs"));
+    }
+
+    #[tokio::test]
+    async fn test_lexical_no_generated_applies_before_top_k() {
+        // Generated files outrank the handwritten one; --no-generated must
+        // not let them fill the top_k places and then drop them
+        let temp_dir = TempDir::new().unwrap();
+        fs::write(
+            temp_dir.path().join("handwritten.rs"),
+            "// one test among many other words in this longer comment\n",
+        )
+        .unwrap();
+        for name in ["a_generated.rs", "b_generated.rs", "c_generated.rs"] {
+            fs::write(temp_dir.path().join(name), "test test\n").unwrap();
+        }
+        fs::create_dir_all(temp_dir.path().join(".ck")).unwrap();
+
+        let options = SearchOptions {
+            mode: SearchMode::Lexical,
+            query: "test".to_string(),
+            path: temp_dir.path().to_path_buf(),
+            no_generated: true,
+            top_k: Some(2),
+            ..Default::default()
+        };
+        let results = lexical_search(&options, &mut Default::default())
+            .await
+            .unwrap()
+            .0;
+        assert_eq!(results.len(), 1, "{results:?}");
+        assert!(results[0].file.ends_with("handwritten.rs"));
+    }
+
+    #[test]
+    fn test_whole_word_terms_follow_regex_word_boundaries() {
+        assert_eq!(whole_word_terms("Test_Case, parse"), ["test_case", "parse"]);
+        assert_eq!(whole_word_terms("parse AND NOT lex"), ["parse", "lex"]);
+
+        let terms = whole_word_terms("test");
+        assert!(contains_whole_word("a (test) here", &terms));
+        assert!(!contains_whole_word("testing test_case", &terms));
+    }
+
+    #[tokio::test]
+    async fn test_lexical_search_split_identifiers_matches_either_form() {
+        let temp_dir = TempDir::new().unwrap();
+        fs::write(
+            temp_dir.path().join("camel.ts"),
+            "function fetchUserById(id) {\n  return db.get(id);\n}\n",
+        )
+        .unwrap();
+        fs::write(
+            temp_dir.path().join("snake.py"),
+            "def load_user_record(uid):\n    pass\n",
+        )
+        .unwrap();
+        fs::create_dir_all(temp_dir.path().join(".ck")).unwrap();
+
+        let mut options = SearchOptions {
+            mode: SearchMode::Lexical,
+            query: "getUserById".to_string(),
+            path: temp_dir.path().to_path_buf(),
+            recursive: true,
+            ..Default::default()
+        };
+        assert!(
+            lexical_search(&options, &mut Default::default())
+                .await
+                .unwrap()
+                .0
+                .is_empty()
+        );
+
+        options.split_identifiers = true;
+        let results = lexical_search(&options, &mut Default::default())
+            .await
+            .unwrap()
+            .0;
+        let mut names: Vec<_> = results
+            .iter()
+            .map(|r| r.file.file_name().unwrap().to_string_lossy().to_string())
+            .collect();
+        names.sort();
+        assert_eq!(names, vec!["camel.ts", "snake.py"]);
+        assert_eq!(options.query, "getUserById");
+    }
+
+    #[tokio::test]
+    async fn test_lexical_search_boolean_query() {
+        let temp_dir = TempDir::new().unwrap();
+        for (name, content) in [
+            ("a.rs", "fn retry() { exponential backoff }\n"),
+            ("b.rs", "fn retry() { backoff }\n#[test]\nfn t() {}\n"),
+            ("c.rs", "fn backoff_then_retry() { backoff exponential }\n"),
+            ("d.rs", "fn retry_once() {}\n"),
+        ] {
+            fs::write(temp_dir.path().join(name), content).unwrap();
+        }
+        fs::create_dir_all(temp_dir.path().join(".ck")).unwrap();
+
+        let search = |query: &str| {
+            let options = SearchOptions {
+                mode: SearchMode::Lexical,
+                query: query.to_string(),
+                path: temp_dir.path().to_path_buf(),
+                recursive: true,
+                ..Default::default()
+            };
+            async move {
+                lexical_search(&options, &mut Default::default())
+                    .await
+                    .map(|(results, _)| {
+                        let mut names: Vec<_> = results
+                            .iter()
+                            .map(|r| r.file.file_name().unwrap().to_string_lossy().to_string())
+                            .collect();
+                        names.sort();
+                        names
+                    })
+            }
+        };
+
+        assert_eq!(
+            search("retry AND backoff NOT test").await.unwrap(),
+            ["a.rs", "c.rs"]
+        );
+        // The phrase needs its words adjacent and in order
+        assert_eq!(
+            search("\"exponential backoff\" OR retry_once")
+                .await
+                .unwrap(),
+            ["a.rs", "d.rs"]
+        );
+        assert_eq!(search("NOT backoff").await.unwrap(), ["d.rs"]);
+        assert_eq!(
+            search("(exponential OR test) AND NOT retry_once")
+                .await
+                .unwrap(),
+            ["a.rs", "b.rs", "c.rs"]
+        );
+
+        let err = search("retry AND").await.unwrap_err();
+        assert!(err.to_string().contains("Malformed lexical query"), "{err}");
+        // Lower-case operators are ordinary words, as before
+        assert_eq!(
+            search("retry and backoff").await.unwrap(),
+            ["a.rs", "b.rs", "c.rs", "d.rs"]
+        );
+    }
+
+    #[test]
+    fn test_bounded_levenshtein() {
+        assert_eq!(bounded_levenshtein("parser", "parser", 2), Some(0));
+        assert_eq!(bounded_levenshtein("parser", "parsre", 2), Some(2));
+        assert_eq!(bounded_levenshtein("tokenize", "tokenise", 2), Some(1));
+        assert_eq!(bounded_levenshtein("index", "indexer", 1), None);
+        assert_eq!(bounded_levenshtein("abc", "xyz", 2), None);
+        assert_eq!(fuzzy_distance_for("id"), 0);
+        assert_eq!(fuzzy_distance_for("token"), 1);
+        assert_eq!(fuzzy_distance_for("tokenizer"), FUZZY_MAX_DISTANCE);
+    }
+
+    #[tokio::test]
+    async fn test_lexical_search_fuzzy_matches_misspelled_term() {
+        let temp_dir = TempDir::new().unwrap();
+        fs::write(
+            temp_dir.path().join("walker.rs"),
+            "fn collect_files() {\n    let traversal = WalkBuilder::new();\n}\n",
+        )
+        .unwrap();
+        fs::write(temp_dir.path().join("exact.rs"), "fn travesal() {}\n").unwrap();
+        fs::create_dir_all(temp_dir.path().join(".ck")).unwrap();
+
+        let mut options = SearchOptions {
+            mode: SearchMode::Lexical,
+            query: "travesal".to_string(),
+            path: temp_dir.path().to_path_buf(),
+            recursive: true,
+            ..Default::default()
+        };
+
+        let strict = lexical_search(&options, &mut Default::default())
+            .await
+            .unwrap()
+            .0;
+        assert!(
+            strict
+                .iter()
+                .all(|r| r.file.file_name().unwrap() != "walker.rs")
+        );
+
+        options.fuzzy = true;
+        let fuzzy = lexical_search(&options, &mut Default::default())
+            .await
+            .unwrap()
+            .0;
+        let names: Vec<_> = fuzzy
+            .iter()
+            .map(|r| r.file.file_name().unwrap().to_string_lossy().to_string())
+            .collect();
+        assert_eq!(names, vec!["exact.rs", "walker.rs"]);
+        assert!(fuzzy[1].score < fuzzy[0].score);
+        assert!(fuzzy[1].preview.contains("traversal"));
+    }
+
     #[tokio::test]
     async fn test_lexical_search_all_fragments_error_degrades_gracefully() {
         // Every fragment references an unknown field, so nothing is
@@ -2500,7 +4666,9 @@ mod tests {
         };
 
         assert!(
-            lexical_search(&options).await.is_ok(),
+            lexical_search(&options, &mut Default::default())
+                .await
+                .is_ok(),
             "an all-unknown-field query must degrade gracefully, not error"
         );
     }
@@ -2528,6 +4696,7 @@ mod tests {
             content,
             &["zebra".to_string()],
             false,
+            false,
         );
 
         assert!(span.line_start > 1, "span should not start at line 1");
@@ -2552,6 +4721,7 @@ mod tests {
             content,
             &["nonexistentterm".to_string()],
             false,
+            false,
         );
 
         assert_eq!(span.line_start, 1);
@@ -2569,7 +4739,7 @@ mod tests {
         // No query terms (e.g. a query that parsed to nothing on the content
         // field) yields the whole-file span rather than a spurious chunk.
         let content = "one\ntwo\nthree\n";
-        let (span, _) = locate_lexical_span(Path::new("f.txt"), content, &[], false);
+        let (span, _) = locate_lexical_span(Path::new("f.txt"), content, &[], false, false);
         assert_eq!(span.line_start, 1);
         assert_eq!(span.line_end, content.lines().count());
     }
@@ -2584,6 +4754,7 @@ mod tests {
             content,
             &["zebra".to_string()],
             true,
+            false,
         );
 
         assert!(span.line_start > 1);
@@ -2600,8 +4771,13 @@ mod tests {
             "fn printing() {\n    let index = string;\n}\n\nfn other() {\n    let x = \"in\";\n}\n";
         let target_line = content.lines().position(|l| l.contains("\"in\"")).unwrap() + 1;
 
-        let (span, preview) =
-            locate_lexical_span(Path::new("sample.rs"), content, &["in".to_string()], false);
+        let (span, preview) = locate_lexical_span(
+            Path::new("sample.rs"),
+            content,
+            &["in".to_string()],
+            false,
+            false,
+        );
 
         assert!(
             span.line_start > 3,
@@ -2618,8 +4794,13 @@ mod tests {
         // When the winning chunk is the last one in the file, its span reaches
         // the final line rather than stopping short.
         let content = "fn early() {\n    let a = 1;\n}\n\nfn late() {\n    let zebra = 2;\n}\n";
-        let (span, _) =
-            locate_lexical_span(Path::new("s.rs"), content, &["zebra".to_string()], false);
+        let (span, _) = locate_lexical_span(
+            Path::new("s.rs"),
+            content,
+            &["zebra".to_string()],
+            false,
+            false,
+        );
         assert!(span.line_start > 1);
         assert_eq!(
             span.line_end,
@@ -2657,7 +4838,10 @@ mod tests {
                 recursive: true,
                 ..Default::default()
             };
-            let results = lexical_search(&options).await.unwrap();
+            let results = lexical_search(&options, &mut Default::default())
+                .await
+                .unwrap()
+                .0;
             let hit = results
                 .iter()
                 .find(|r| r.file.file_name().unwrap() == "f.rs")
@@ -2698,7 +4882,10 @@ mod tests {
             ..Default::default()
         };
 
-        let results = lexical_search(&options).await.unwrap();
+        let results = lexical_search(&options, &mut Default::default())
+            .await
+            .unwrap()
+            .0;
         let hit = results
             .iter()
             .find(|r| r.file.file_name().unwrap() == "late.rs")
@@ -2730,7 +4917,10 @@ mod tests {
             ..Default::default()
         };
 
-        let results = lexical_search(&options).await.unwrap();
+        let results = lexical_search(&options, &mut Default::default())
+            .await
+            .unwrap()
+            .0;
         let files: Vec<String> = results
             .iter()
             .map(|r| r.file.file_name().unwrap().to_string_lossy().into_owned())
@@ -2747,4 +4937,47 @@ mod tests {
         // Top score is normalized to 1.0, exactly as before this patch.
         assert!((results[0].score - 1.0).abs() < 1e-6);
     }
+
+    #[test]
+    fn test_score_cutoff_uses_percentile_of_candidates() {
+        let scores = (1..=10).map(|i| i as f32 / 10.0);
+        let mut options = SearchOptions {
+            threshold: Some(0.25),
+            ..Default::default()
+        };
+        assert_eq!(score_cutoff(&options, scores.clone()), Some(0.25));
+
+        options.threshold = None;
+        options.threshold_percentile = Some(80.0);
+        assert_eq!(score_cutoff(&options, scores.clone()), Some(0.8));
+        options.threshold_percentile = Some(0.0);
+        assert_eq!(score_cutoff(&options, scores.clone()), Some(0.1));
+        options.threshold_percentile = Some(100.0);
+        assert_eq!(score_cutoff(&options, scores), Some(1.0));
+        assert_eq!(score_cutoff(&options, []), None);
+    }
+
+    #[tokio::test]
+    async fn test_threshold_percentile_is_validated() {
+        let temp_dir = TempDir::new().unwrap();
+        let mut options = SearchOptions {
+            mode: SearchMode::Lexical,
+            query: "anything".to_string(),
+            path: temp_dir.path().to_path_buf(),
+            threshold: Some(0.5),
+            threshold_percentile: Some(90.0),
+            ..Default::default()
+        };
+        let err = search_enhanced_with_outcome(&options, None, None, None)
+            .await
+            .unwrap_err();
+        assert!(err.to_string().contains("cannot be combined"));
+
+        options.threshold = None;
+        options.threshold_percentile = Some(150.0);
+        let err = search_enhanced_with_outcome(&options, None, None, None)
+            .await
+            .unwrap_err();
+        assert!(err.to_string().contains("between 0 and 100"));
+    }
 }
@@ -0,0 +1,155 @@
+//! Proximity boost for lexical search (`--proximity`).
+//!
+//! BM25 scores each query term on its own, so a file with `retry` and
+//! `backoff` side by side scores the same as one with them pages apart.
+//! With a weight `w`, a file holding at least two of the query's terms has
+//! its score multiplied by
+//!
+//! ```text
+//! 1 + w / (1 + gap)
+//! ```
+//!
+//! where `gap` counts the other tokens in the smallest stretch of the file
+//! holding every query term it contains: 0 when they are adjacent. Term
+//! positions come from the lexical index, which records them for every
+//! token. Files with a single query term keep their score.
+
+use std::collections::HashMap;
+use tantivy::collector::{ScoreSegmentTweaker, ScoreTweaker};
+use tantivy::postings::{Postings, SegmentPostings};
+use tantivy::schema::IndexRecordOption;
+use tantivy::{DocId, DocSet, Score, SegmentReader, Term};
+
+use crate::bm25::{Bm25Rescorer, SegmentBm25};
+
+/// Boosts documents whose query terms sit close together; plugs into
+/// `TopDocs::tweak_score`. The score boosted is tantivy's, or the one
+/// `bm25` gives when custom BM25 parameters are in effect.
+#[derive(Clone)]
+pub(crate) struct ProximityBoost {
+    terms: Vec<Term>,
+    weight: Score,
+    bm25: Option<Bm25Rescorer>,
+}
+
+impl ProximityBoost {
+    pub(crate) fn new(terms: Vec<Term>, weight: Score, bm25: Option<Bm25Rescorer>) -> Self {
+        Self {
+            terms,
+            weight,
+            bm25,
+        }
+    }
+}
+
+impl ScoreTweaker<Score> for ProximityBoost {
+    type Child = SegmentProximity;
+
+    fn segment_tweaker(&self, segment_reader: &SegmentReader) -> tantivy::Result<SegmentProximity> {
+        let mut postings = Vec::with_capacity(self.terms.len());
+        for term in &self.terms {
+            if let Some(term_postings) = segment_reader
+                .inverted_index(term.field())?
+                .read_postings(term, IndexRecordOption::WithFreqsAndPositions)?
+            {
+                postings.push(term_postings);
+            }
+        }
+        Ok(SegmentProximity {
+            postings,
+            weight: self.weight,
+            bm25: self
+                .bm25
+                .as_ref()
+                .map(|bm25| bm25.segment_tweaker(segment_reader))
+                .transpose()?,
+        })
+    }
+}
+
+/// [`ProximityBoost`] for one segment, whose documents come in increasing
+/// order.
+pub(crate) struct SegmentProximity {
+    postings: Vec<SegmentPostings>,
+    weight: Score,
+    bm25: Option<SegmentBm25>,
+}
+
+impl ScoreSegmentTweaker<Score> for SegmentProximity {
+    fn score(&mut self, doc: DocId, tantivy_score: Score) -> Score {
+        let score = match &mut self.bm25 {
+            Some(bm25) => bm25.score(doc, tantivy_score),
+            None => tantivy_score,
+        };
+        let mut positions: Vec<Vec<u32>> = Vec::new();
+        for postings in &mut self.postings {
+            if postings.doc() < doc {
+                postings.seek(doc);
+            }
+            if postings.doc() == doc {
+                let mut term_positions = Vec::new();
+                postings.positions(&mut term_positions);
+                positions.push(term_positions);
+            }
+        }
+        match smallest_gap(&positions) {
+            Some(gap) => score * (1.0 + self.weight / (1.0 + gap as Score)),
+            None => score,
+        }
+    }
+}
+
+/// Tokens that aren't one of the terms in the smallest window holding a
+/// position from each of `positions` (one sorted list per term), or `None`
+/// with fewer than two terms.
+fn smallest_gap(positions: &[Vec<u32>]) -> Option<u32> {
+    let terms = positions.iter().filter(|p| !p.is_empty()).count();
+    if terms < 2 {
+        return None;
+    }
+    let mut tokens: Vec<(u32, usize)> = positions
+        .iter()
+        .enumerate()
+        .flat_map(|(term, term_positions)| term_positions.iter().map(move |&pos| (pos, term)))
+        .collect();
+    tokens.sort_unstable();
+
+    // Slide a window over the positions in order, shrinking it from the left
+    // while it still holds every term
+    let mut in_window: HashMap<usize, usize> = HashMap::new();
+    let mut smallest = u32::MAX;
+    let mut start = 0;
+    for &(end_pos, term) in &tokens {
+        *in_window.entry(term).or_default() += 1;
+        while in_window.len() == terms {
+            let (start_pos, start_term) = tokens[start];
+            smallest = smallest.min(end_pos - start_pos);
+            let count = in_window
+                .get_mut(&start_term)
+                .expect("term is in the window");
+            *count -= 1;
+            if *count == 0 {
+                in_window.remove(&start_term);
+            }
+            start += 1;
+        }
+    }
+    Some(smallest + 1 - terms as u32)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_smallest_gap() {
+        // Adjacent somewhere, whatever else is far apart
+        assert_eq!(smallest_gap(&[vec![0, 40], vec![3, 41]]), Some(0));
+        assert_eq!(smallest_gap(&[vec![10], vec![2]]), Some(7));
+        assert_eq!(smallest_gap(&[vec![1, 20], vec![5, 22], vec![21]]), Some(0));
+        assert_eq!(smallest_gap(&[vec![0], vec![4], vec![9]]), Some(7));
+        // Terms missing from the document don't count
+        assert_eq!(smallest_gap(&[vec![3], vec![], vec![5]]), Some(1));
+        assert_eq!(smallest_gap(&[vec![3, 8], vec![]]), None);
+    }
+}
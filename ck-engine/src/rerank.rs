@@ -0,0 +1,210 @@
+use anyhow::Result;
+use ck_core::{SearchOptions, SearchResult};
+use ck_embed::Reranker;
+use std::collections::HashMap;
+
+use super::{SearchProgressCallback, extract_content_from_span};
+
+/// Number of leading candidates rescored when `SearchOptions::rerank_top_k` is unset.
+/// Cross-encoders score every (query, chunk) pair, so this bounds the cost per query.
+pub const DEFAULT_RERANK_TOP_K: usize = 20;
+
+/// How many semantic candidates to collect before reranking, so that results
+/// just outside `top_k` can still be promoted by the cross-encoder.
+pub(crate) fn candidate_limit(options: &SearchOptions) -> Option<usize> {
+    let top_k = options.top_k?;
+    if options.rerank {
+        Some(top_k.max(rerank_k(options)))
+    } else {
+        Some(top_k)
+    }
+}
+
+fn rerank_k(options: &SearchOptions) -> usize {
+    options.rerank_top_k.unwrap_or(DEFAULT_RERANK_TOP_K)
+}
+
+/// Rescore the leading candidates with the configured cross-encoder and
+/// reorder them by the new score. Anything past `rerank_top_k` keeps its
+/// similarity score and original position after the reranked head.
+///
+/// If the rerank model cannot be loaded (not cached and no network, missing
+/// feature, ...) or scoring fails, the results are left untouched. Callers
+/// still need to apply `top_k`, since candidates were widened by `candidate_limit`.
+pub(crate) async fn rerank_results(
+    options: &SearchOptions,
+    results: &mut [SearchResult],
+    progress_callback: Option<&SearchProgressCallback>,
+) -> Result<()> {
+    if !options.rerank || results.is_empty() {
+        return Ok(());
+    }
+
+    let k = rerank_k(options).min(results.len());
+    if k == 0 {
+        return Ok(());
+    }
+
+    if let Some(callback) = progress_callback {
+        callback("Reranking results for improved relevance...");
+    }
+
+    let rerank_registry = ck_models::RerankModelRegistry::default();
+    let (rerank_alias, rerank_config) = rerank_registry
+        .resolve(options.rerank_model.as_deref())
+        .map_err(|e| anyhow::anyhow!(e.to_string()))?;
+
+    let mut reranker = match ck_embed::create_reranker_for_config(&rerank_config, None) {
+        Ok(reranker) => reranker,
+        Err(e) => {
+            tracing::warn!("Failed to create reranker, using original scores: {}", e);
+            if let Some(callback) = progress_callback {
+                callback(&format!(
+                    "Rerank model {rerank_alias} unavailable, keeping similarity order"
+                ));
+            }
+            return Ok(());
+        }
+    };
+
+    if let Some(callback) = progress_callback {
+        callback(&format!(
+            "Reranking top {k} results with model {rerank_alias}"
+        ));
+    }
+
+    // Score against the full chunk text, not the truncated preview.
+    let mut documents = Vec::with_capacity(k);
+    for result in results.iter().take(k) {
+        match extract_content_from_span(options, &result.file, &result.span).await {
+            Ok(content) => documents.push(content),
+            Err(_) => documents.push(result.preview.clone()),
+        }
+    }
+
+    if let Err(e) = apply_rerank(reranker.as_mut(), &options.query, results, &documents) {
+        tracing::warn!("Reranking failed, using original scores: {}", e);
+    }
+
+    Ok(())
+}
+
+/// Apply cross-encoder scores for `documents` (one per leading result) and
+/// re-sort that leading slice. The previous score is kept in `original_score`.
+fn apply_rerank(
+    reranker: &mut dyn Reranker,
+    query: &str,
+    results: &mut [SearchResult],
+    documents: &[String],
+) -> Result<()> {
+    let head = documents.len().min(results.len());
+    let scored = reranker.rerank(query, &documents[..head])?;
+
+    // Rerankers return results in their own order, so match back by document
+    // text. Indices are pushed in reverse so duplicates resolve in order.
+    let mut doc_to_indices: HashMap<&str, Vec<usize>> = HashMap::new();
+    for (i, doc) in documents[..head].iter().enumerate().rev() {
+        doc_to_indices.entry(doc.as_str()).or_default().push(i);
+    }
+
+    for rerank_result in &scored {
+        if let Some(indices) = doc_to_indices.get_mut(rerank_result.document.as_str())
+            && let Some(idx) = indices.pop()
+        {
+            let result = &mut results[idx];
+            result.original_score = Some(result.score);
+            result.score = rerank_result.score;
+        }
+    }
+
+    results[..head].sort_by(SearchResult::rank_cmp);
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ck_core::Span;
+    use std::path::PathBuf;
+
+    fn candidate(name: &str, score: f32) -> SearchResult {
+        SearchResult {
+            file: PathBuf::from(name),
+            span: Span {
+                byte_start: 0,
+                byte_end: 1,
+                line_start: 1,
+                line_end: 1,
+            },
+            score,
+            preview: name.to_string(),
+            lang: None,
+            symbol: None,
+            chunk_hash: None,
+            index_epoch: None,
+            original_score: None,
+            stale: false,
+            root: None,
+            preview_line: None,
+            duplicates: 0,
+            grouped: 0,
+            column: None,
+            match_line: None,
+            explanation: None,
+            context_before: None,
+            context_after: None,
+        }
+    }
+
+    #[test]
+    fn reranks_only_the_leading_candidates() {
+        let mut results = vec![
+            candidate("a", 0.9),
+            candidate("b", 0.8),
+            candidate("c", 0.7),
+            candidate("d", 0.6),
+        ];
+        let documents = vec!["a".to_string(), "b".to_string(), "c".to_string()];
+        let mut reranker = ck_embed::reranker::DummyReranker::new();
+
+        apply_rerank(&mut reranker, "query", &mut results, &documents).unwrap();
+
+        // DummyReranker scores later documents higher, so the head is reversed.
+        let order: Vec<_> = results.iter().map(|r| r.preview.as_str()).collect();
+        assert_eq!(order, vec!["c", "b", "a", "d"]);
+        assert_eq!(results[0].original_score, Some(0.7));
+        assert_eq!(results[2].original_score, Some(0.9));
+        assert_eq!(results[3].original_score, None);
+        assert_eq!(results[3].score, 0.6);
+    }
+
+    #[test]
+    fn duplicate_documents_each_get_a_score() {
+        let mut results = vec![candidate("x", 0.9), candidate("y", 0.8)];
+        let documents = vec!["same".to_string(), "same".to_string()];
+        let mut reranker = ck_embed::reranker::DummyReranker::new();
+
+        apply_rerank(&mut reranker, "query", &mut results, &documents).unwrap();
+
+        assert!(results.iter().all(|r| r.original_score.is_some()));
+    }
+
+    #[test]
+    fn candidate_limit_widens_for_rerank() {
+        let mut options = SearchOptions {
+            top_k: Some(5),
+            ..Default::default()
+        };
+        assert_eq!(candidate_limit(&options), Some(5));
+
+        options.rerank = true;
+        assert_eq!(candidate_limit(&options), Some(DEFAULT_RERANK_TOP_K));
+
+        options.rerank_top_k = Some(3);
+        assert_eq!(candidate_limit(&options), Some(5));
+
+        options.top_k = None;
+        assert_eq!(candidate_limit(&options), None);
+    }
+}
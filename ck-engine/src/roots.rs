@@ -0,0 +1,172 @@
+//! Semantic search across several independently indexed roots.
+//!
+//! Each root is searched against its own index, exactly as if it were the
+//! only path given, and the matches are then ranked as one list. Every root
+//! returns at most `top_k` matches, so the best `top_k` overall are always
+//! among them; the threshold is per match and needs no extra handling.
+
+use crate::{
+    DetailedIndexingProgressCallback, IndexUpdate, IndexingProgressCallback, SearchOutcome,
+    SearchProgressCallback, search_single_root,
+};
+use anyhow::Result;
+use ck_core::{SearchOptions, SearchResult, SearchResults, SearchTimings};
+use std::sync::Arc;
+
+type SharedMessageCallback = Arc<dyn Fn(&str) + Send + Sync>;
+type SharedEmbeddingCallback = Arc<dyn Fn(ck_index::EmbeddingProgress) + Send + Sync>;
+
+pub(crate) async fn search_roots(
+    options: &SearchOptions,
+    progress_callback: Option<SearchProgressCallback>,
+    indexing_progress_callback: Option<IndexingProgressCallback>,
+    detailed_indexing_progress_callback: Option<DetailedIndexingProgressCallback>,
+) -> Result<SearchOutcome> {
+    // The callbacks are shared by every root's search
+    let progress: Option<SharedMessageCallback> = progress_callback.map(Arc::from);
+    let indexing: Option<SharedMessageCallback> = indexing_progress_callback.map(Arc::from);
+    let detailed: Option<SharedEmbeddingCallback> =
+        detailed_indexing_progress_callback.map(Arc::from);
+
+    let mut matches = Vec::new();
+    let mut closest_below_threshold: Option<SearchResult> = None;
+    let mut index_update: Option<IndexUpdate> = None;
+    let mut unreadable = Vec::new();
+    let mut timings = SearchTimings::default();
+    let mut truncated = false;
+    let mut lower_thresholds: Vec<ck_core::ThresholdStep> = Vec::new();
+
+    for root in &options.roots {
+        let mut root_options = options.clone();
+        root_options.path = root.clone();
+        root_options.roots = Vec::new();
+        root_options.include_patterns = Vec::new();
+
+        let outcome = search_single_root(
+            &root_options,
+            progress
+                .clone()
+                .map(|cb| Box::new(move |msg: &str| cb(msg)) as SearchProgressCallback),
+            indexing
+                .clone()
+                .map(|cb| Box::new(move |msg: &str| cb(msg)) as IndexingProgressCallback),
+            detailed.clone().map(|cb| {
+                Box::new(move |p: ck_index::EmbeddingProgress| cb(p))
+                    as DetailedIndexingProgressCallback
+            }),
+        )
+        .await?;
+
+        timings = timings.combined(outcome.results.timings);
+        truncated |= outcome.results.truncated;
+        for step in outcome.results.lower_thresholds {
+            match lower_thresholds
+                .iter_mut()
+                .find(|total| total.threshold == step.threshold)
+            {
+                Some(total) => total.results += step.results,
+                None => lower_thresholds.push(step),
+            }
+        }
+        for mut result in outcome.results.matches {
+            result.root = Some(root.clone());
+            matches.push(result);
+        }
+        if let Some(mut closest) = outcome.results.closest_below_threshold
+            && closest_below_threshold
+                .as_ref()
+                .is_none_or(|best| closest.score > best.score)
+        {
+            closest.root = Some(root.clone());
+            closest_below_threshold = Some(closest);
+        }
+        if let Some(update) = outcome.index_update {
+            let total = index_update.get_or_insert_with(IndexUpdate::default);
+            total.files_indexed += update.files_indexed;
+            total.orphaned_files_removed += update.orphaned_files_removed;
+            total.duration_ms += update.duration_ms;
+        }
+        unreadable.extend(outcome.unreadable);
+    }
+
+    rank_across_roots(&mut matches, options.top_k);
+    if !matches.is_empty() {
+        closest_below_threshold = None;
+        lower_thresholds.clear();
+    }
+    if let Some(top_k) = options.top_k {
+        for step in &mut lower_thresholds {
+            step.results = step.results.min(top_k);
+        }
+    }
+
+    Ok(SearchOutcome {
+        results: SearchResults {
+            matches,
+            closest_below_threshold,
+            timings,
+            truncated,
+            lower_thresholds,
+        },
+        index_update,
+        unreadable,
+    })
+}
+
+/// Order matches from all roots by score (ties by path, then line) and keep
+/// the global `top_k`.
+fn rank_across_roots(matches: &mut Vec<SearchResult>, top_k: Option<usize>) {
+    matches.sort_by(SearchResult::rank_cmp);
+    if let Some(top_k) = top_k {
+        matches.truncate(top_k);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ck_core::Span;
+    use std::path::PathBuf;
+
+    fn result(file: &str, score: f32) -> SearchResult {
+        SearchResult {
+            file: PathBuf::from(file),
+            span: Span {
+                byte_start: 0,
+                byte_end: 1,
+                line_start: 1,
+                line_end: 1,
+            },
+            score,
+            preview: String::new(),
+            lang: None,
+            symbol: None,
+            chunk_hash: None,
+            index_epoch: None,
+            original_score: None,
+            stale: false,
+            root: None,
+            preview_line: None,
+            duplicates: 0,
+            grouped: 0,
+            column: None,
+            match_line: None,
+            explanation: None,
+            context_before: None,
+            context_after: None,
+        }
+    }
+
+    #[test]
+    fn test_rank_across_roots_applies_top_k_globally() {
+        let mut matches = vec![
+            result("a/one.rs", 0.5),
+            result("a/two.rs", 0.4),
+            result("b/one.rs", 0.9),
+            result("b/two.rs", 0.5),
+        ];
+        rank_across_roots(&mut matches, Some(3));
+        let files: Vec<_> = matches.iter().map(|r| r.file.to_str().unwrap()).collect();
+        assert_eq!(files, ["b/one.rs", "a/one.rs", "b/two.rs"]);
+    }
+}
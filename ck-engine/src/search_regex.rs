@@ -0,0 +1,178 @@
+//! The compiled pattern of a regex-mode search.
+//!
+//! By default patterns are Unicode-aware, as in the `regex` crate. With
+//! `--no-unicode` ([`SearchOptions::ascii_only`]) the pattern is compiled
+//! the way ripgrep's fast path does: `.`, `\w`, `\s` and `-i` case folding
+//! cover ASCII only, and any other byte is opaque, matching only itself.
+//! That mode runs over the line's bytes, so a match can begin or end inside
+//! a multi-byte character; match ranges are widened to the enclosing
+//! character boundaries before anything slices the line with them.
+
+use ck_core::SearchOptions;
+use regex::{Regex, RegexBuilder, bytes};
+use std::ops::Range;
+
+#[derive(Debug, Clone)]
+pub enum SearchRegex {
+    Unicode(Regex),
+    Ascii(bytes::Regex),
+}
+
+impl SearchRegex {
+    /// The regex a regex-mode search for `pattern` runs, honoring `-F`, `-w`,
+    /// `-i` and `--no-unicode` from `options`.
+    pub fn new(pattern: &str, options: &SearchOptions) -> Result<Self, regex::Error> {
+        let pattern = if options.fixed_string {
+            regex::escape(pattern)
+        } else if options.whole_word {
+            format!(r"\b{}\b", regex::escape(pattern))
+        } else {
+            pattern.to_string()
+        };
+
+        if options.ascii_only {
+            bytes::RegexBuilder::new(&pattern)
+                .unicode(false)
+                .case_insensitive(options.case_insensitive)
+                .build()
+                .map(Self::Ascii)
+        } else {
+            RegexBuilder::new(&pattern)
+                .case_insensitive(options.case_insensitive)
+                .build()
+                .map(Self::Unicode)
+        }
+    }
+
+    /// The pattern as compiled.
+    pub fn as_str(&self) -> &str {
+        match self {
+            Self::Unicode(regex) => regex.as_str(),
+            Self::Ascii(regex) => regex.as_str(),
+        }
+    }
+
+    pub fn is_match(&self, line: &str) -> bool {
+        match self {
+            Self::Unicode(regex) => regex.is_match(line),
+            Self::Ascii(regex) => regex.is_match(line.as_bytes()),
+        }
+    }
+
+    /// Byte range of the first match in `line`.
+    pub fn find(&self, line: &str) -> Option<Range<usize>> {
+        self.find_iter(line).next()
+    }
+
+    /// Byte ranges of the successive non-overlapping matches in `line`, each
+    /// on character boundaries.
+    pub fn find_iter<'a>(&'a self, line: &'a str) -> Box<dyn Iterator<Item = Range<usize>> + 'a> {
+        match self {
+            Self::Unicode(regex) => Box::new(regex.find_iter(line).map(|m| m.range())),
+            Self::Ascii(regex) => Box::new(
+                regex
+                    .find_iter(line.as_bytes())
+                    .map(|m| char_boundaries(line, m.range())),
+            ),
+        }
+    }
+
+    /// The match starting at `start` in `line`, as `template` with its
+    /// capture references (`$1`, `${name}`) expanded.
+    pub fn expand_at(&self, line: &str, start: usize, template: &str) -> String {
+        match self {
+            Self::Unicode(regex) => {
+                let mut replaced = String::new();
+                if let Some(captures) = regex.captures_at(line, start) {
+                    captures.expand(template, &mut replaced);
+                }
+                replaced
+            }
+            Self::Ascii(regex) => {
+                let mut replaced = Vec::new();
+                if let Some(captures) = regex.captures_at(line.as_bytes(), start) {
+                    captures.expand(template.as_bytes(), &mut replaced);
+                }
+                String::from_utf8_lossy(&replaced).into_owned()
+            }
+        }
+    }
+}
+
+impl From<Regex> for SearchRegex {
+    fn from(regex: Regex) -> Self {
+        Self::Unicode(regex)
+    }
+}
+
+/// `range` widened to start and end on character boundaries of `line`.
+fn char_boundaries(line: &str, range: Range<usize>) -> Range<usize> {
+    let mut start = range.start;
+    while !line.is_char_boundary(start) {
+        start -= 1;
+    }
+    let mut end = range.end;
+    while !line.is_char_boundary(end) {
+        end += 1;
+    }
+    start..end
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn options(case_insensitive: bool) -> SearchOptions {
+        SearchOptions {
+            case_insensitive,
+            ascii_only: true,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn ascii_mode_folds_ascii_case_only() {
+        let regex = SearchRegex::new("error", &options(true)).unwrap();
+        assert!(regex.is_match("ERROR: disk full"));
+
+        // Unicode case folding would match the Kelvin sign to `k`
+        let unicode = SearchRegex::new(
+            "k",
+            &SearchOptions {
+                case_insensitive: true,
+                ..Default::default()
+            },
+        )
+        .unwrap();
+        assert!(unicode.is_match("\u{212A}"));
+        assert!(
+            !SearchRegex::new("k", &options(true))
+                .unwrap()
+                .is_match("\u{212A}")
+        );
+    }
+
+    #[test]
+    fn ascii_mode_treats_other_bytes_opaquely() {
+        // `.` matches one byte, so a multi-byte character takes two
+        let regex = SearchRegex::new("caf..", &options(false)).unwrap();
+        let line = "café au lait";
+        let range = regex.find(line).unwrap();
+        assert_eq!(&line[range], "café");
+        assert_eq!(regex.find_iter(line).count(), 1);
+
+        // A match ending mid-character is widened to the whole character
+        let regex = SearchRegex::new("caf.", &options(false)).unwrap();
+        assert_eq!(regex.find(line), Some(0..5));
+
+        // `\w` is ASCII-only
+        let regex = SearchRegex::new(r"\w+", &options(false)).unwrap();
+        assert_eq!(regex.find("été"), Some(2..3));
+    }
+
+    #[test]
+    fn ascii_mode_expands_replacements() {
+        let regex = SearchRegex::new(r"(\w+)=(\w+)", &options(false)).unwrap();
+        assert_eq!(regex.expand_at("key=value", 0, "$2=$1"), "value=key");
+    }
+}
@@ -1,8 +1,13 @@
 use anyhow::Result;
-use ck_core::{CkError, SearchOptions, SearchResult};
-use std::path::Path;
-use walkdir::WalkDir;
+use ck_core::{CkError, SearchOptions, SearchResult, SearchTimings};
+use ck_embed::query_cache;
+use std::path::{Path, PathBuf};
+use std::time::Instant;
 
+use super::dedup::{self, Candidate, collapse_near_duplicates};
+use super::identifiers::split_query_identifiers;
+use super::rerank::{candidate_limit, rerank_results};
+use super::snippet::{match_line_offset, snippet_window};
 use super::{
     SearchProgressCallback, extract_content_from_span, find_nearest_index_root,
     resolve_model_from_root,
@@ -37,6 +42,20 @@ pub async fn semantic_search_v3_with_progress(
     // via a CK_INDEX_DIR basename-hash collision. No-op in-tree.
     ck_core::check_index_root_marker(&index_root)?;
 
+    if let Some(mut results) =
+        super::ann::ann_search(options, &index_root, progress_callback.as_ref()).await?
+    {
+        let started = Instant::now();
+        rerank_results(options, &mut results.matches, progress_callback.as_ref()).await?;
+        if options.rerank {
+            results.timings.rerank = Some(started.elapsed());
+        }
+        if let Some(limit) = options.top_k {
+            results.matches.truncate(limit);
+        }
+        return Ok(results);
+    }
+
     if let Some(ref callback) = progress_callback {
         callback("Loading embeddings from sidecar files...");
     }
@@ -48,36 +67,35 @@ pub async fn semantic_search_v3_with_progress(
     // Filtering at collection time fixes that and skips embedding loads
     // for chunks we'd discard anyway.
     let scope = PathScope::new(&options.path);
+    let include_globs = super::search_include_globs(options)?;
 
     // Collect all sidecar files and their embeddings
-    let mut file_chunks: Vec<(std::path::PathBuf, ck_index::ChunkEntry)> = Vec::new();
+    let mut timings = SearchTimings::default();
+    let started = Instant::now();
+    let (file_chunks, file_models, truncated) = load_sidecar_chunks(
+        &index_dir,
+        &index_root,
+        options.no_generated,
+        options.deadline,
+        |file| {
+            super::path_matches_include(file, &options.include_patterns)
+                && include_globs.matches(file)
+                && super::modified_since_matches(file, options)
+                && scope.contains(file)
+        },
+    )?;
+    timings.candidate_load = Some(started.elapsed());
+    options.check_cancelled()?;
 
-    for entry in WalkDir::new(&index_dir) {
-        let entry = entry?;
-        if entry.file_type().is_file() {
-            let path = entry.path();
-            if path.extension().and_then(|s| s.to_str()) == Some("ck") {
-                // Load the sidecar file
-                if let Ok(index_entry) = ck_index::load_index_entry(path) {
-                    let original_file = reconstruct_original_path(path, &index_dir, &index_root);
-                    if let Some(original_file) = original_file {
-                        if !super::path_matches_include(&original_file, &options.include_patterns) {
-                            continue;
-                        }
-                        if !scope.contains(&original_file) {
-                            continue;
-                        }
-                        for chunk in index_entry.chunks {
-                            if chunk.embedding.is_some() {
-                                file_chunks.push((original_file.clone(), chunk));
-                            }
-                        }
-                    }
-                }
-            }
-        }
+    if file_chunks.is_empty() && truncated {
+        return Ok(ck_core::SearchResults {
+            matches: Vec::new(),
+            closest_below_threshold: None,
+            timings,
+            truncated,
+            lower_thresholds: Vec::new(),
+        });
     }
-
     if file_chunks.is_empty() {
         return Err(CkError::Index(
             "No embeddings found. Run 'ck --index' first with embeddings.".to_string(),
@@ -115,76 +133,734 @@ pub async fn semantic_search_v3_with_progress(
         }
     }
 
-    let mut embedder = ck_embed::create_embedder_for_config(&resolved_model.config, None)?;
-    let query_embeddings = embedder.embed(std::slice::from_ref(&options.query))?;
+    // Only the embedded text is rewritten; options.query stays as typed for display.
+    let query_text = if options.split_identifiers {
+        split_query_identifiers(&options.query)
+    } else {
+        options.query.clone()
+    };
+    let mut queries = Vec::new();
+    for config in query_models(&index_root, &resolved_model.config, &file_models)? {
+        if let Some(ref callback) = progress_callback
+            && !queries.is_empty()
+        {
+            callback(&format!(
+                "Also embedding the query with per-language model {}",
+                config.name
+            ));
+        }
+        let mut model_timings = SearchTimings::default();
+        let Some(query_embedding) =
+            embed_query(&config, &config.name, &query_text, &mut model_timings)?
+        else {
+            return Ok(ck_core::SearchResults {
+                matches: Vec::new(),
+                closest_below_threshold: None,
+                timings: timings.combined(model_timings),
+                truncated,
+                lower_thresholds: Vec::new(),
+            });
+        };
+        timings = timings.combined(model_timings);
+        queries.push((config.name, query_embedding));
+    }
+    project_queries(
+        ck_index::index_projection(&index_root).as_ref(),
+        &mut queries,
+    );
+    options.check_cancelled()?;
+
+    let started = Instant::now();
+    let mut results = rank_chunks(
+        options,
+        file_chunks,
+        &file_models,
+        &queries,
+        progress_callback.as_ref(),
+    )
+    .await?;
+    timings.scoring = Some(started.elapsed());
+    options.check_cancelled()?;
 
-    if query_embeddings.is_empty() {
-        return Ok(ck_core::SearchResults {
-            matches: Vec::new(),
-            closest_below_threshold: None,
-        });
+    // Rescore the leading candidates with a cross-encoder if requested
+    let started = Instant::now();
+    rerank_results(options, &mut results.matches, progress_callback.as_ref()).await?;
+    if options.rerank {
+        timings.rerank = Some(started.elapsed());
     }
+    if let Some(limit) = options.top_k {
+        results.matches.truncate(limit);
+    }
+    results.timings = timings;
+    results.truncated = truncated;
 
-    let query_embedding = &query_embeddings[0];
+    Ok(results)
+}
 
-    if let Some(ref callback) = progress_callback {
+/// `query_text` embedded by `config`'s model, recording the time taken to
+/// load the model and to embed in `timings`. A repeated query comes from the
+/// query cache and skips loading the model altogether, as does any query
+/// while the embedder pool holds the model.
+pub(crate) fn embed_query(
+    config: &ck_models::ModelConfig,
+    model_name: &str,
+    query_text: &str,
+    timings: &mut SearchTimings,
+) -> Result<Option<Vec<f32>>> {
+    let started = Instant::now();
+    let mut model_load = None;
+    let embedding = query_cache::get_or_embed(model_name, query_text, || {
+        ck_embed::embedder_pool::with_embedder(config, |embedder| {
+            model_load = Some(started.elapsed());
+            embedder.embed(&[query_text.to_string()])
+        })
+    })?;
+    timings.model_load = model_load;
+    timings.query_embed = Some(started.elapsed() - model_load.unwrap_or_default());
+    Ok(embedding)
+}
+
+/// Semantic search with a precomputed query vector instead of `options.query`,
+/// e.g. the embedding of another piece of code. The vector must come from the
+/// model the index was built with; files embedded by a per-language model
+/// are left out.
+pub async fn semantic_search_with_embedding(
+    options: &SearchOptions,
+    query_embedding: &[f32],
+) -> Result<ck_core::SearchResults> {
+    let index_root = find_nearest_index_root(&options.path).unwrap_or_else(|| {
+        if options.path.is_file() {
+            options.path.parent().unwrap_or(&options.path).to_path_buf()
+        } else {
+            options.path.clone()
+        }
+    });
+    let index_dir = ck_core::index_dir(&index_root);
+    if !index_dir.exists() {
+        return Err(CkError::Index("No index found. Run 'ck --index' first.".to_string()).into());
+    }
+    ck_core::check_index_root_marker(&index_root)?;
+
+    let scope = PathScope::new(&options.path);
+    let include_globs = super::search_include_globs(options)?;
+    let (mut file_chunks, mut file_models, truncated) = load_sidecar_chunks(
+        &index_dir,
+        &index_root,
+        options.no_generated,
+        options.deadline,
+        |file| {
+            super::path_matches_include(file, &options.include_patterns)
+                && include_globs.matches(file)
+                && super::modified_since_matches(file, options)
+                && scope.contains(file)
+        },
+    )?;
+
+    let resolved_model = resolve_model_from_root(&index_root, options.embedding_model.as_deref())?;
+    let model = resolved_model.canonical_name();
+    skip_other_index_models(
+        &index_root,
+        model,
+        model,
+        &mut file_chunks,
+        &mut file_models,
+    );
+    let mut queries = [(model.to_string(), query_embedding.to_vec())];
+    project_queries(
+        ck_index::index_projection(&index_root).as_ref(),
+        &mut queries,
+    );
+    let mut results = rank_chunks(options, file_chunks, &file_models, &queries, None).await?;
+    if let Some(limit) = options.top_k {
+        results.matches.truncate(limit);
+    }
+    results.truncated = truncated;
+    Ok(results)
+}
+
+/// Every file with a sidecar under `index_dir`, paired with that sidecar.
+pub(crate) fn indexed_files(
+    index_dir: &Path,
+    index_root: &Path,
+) -> Result<Vec<(PathBuf, PathBuf)>> {
+    let mut files = Vec::new();
+    for entry in ck_index::walk_index_dir(index_dir) {
+        let entry = entry?;
+        if !entry.file_type().is_file() {
+            continue;
+        }
+        let path = entry.path();
+        if path.extension().and_then(|s| s.to_str()) != Some("ck") {
+            continue;
+        }
+        if let Some(original_file) = reconstruct_original_path(path, index_dir, index_root) {
+            files.push((original_file, path.to_path_buf()));
+        }
+    }
+    Ok(files)
+}
+
+/// Embedded chunks keyed by original file, each file's embedding model, and
+/// whether the deadline cut the load short.
+pub(crate) type SidecarChunks = (
+    Vec<(PathBuf, ck_index::ChunkEntry)>,
+    Vec<SidecarModel>,
+    bool,
+);
+
+/// Load every sidecar under `index_dir` whose original file passes `keep`
+/// (and isn't generated, with `skip_generated`), returning the chunks that
+/// carry embeddings and each file's embedding model. Sidecars not reached by
+/// `deadline` are left unread.
+pub(crate) fn load_sidecar_chunks(
+    index_dir: &Path,
+    index_root: &Path,
+    skip_generated: bool,
+    deadline: Option<Instant>,
+    keep: impl Fn(&Path) -> bool,
+) -> Result<SidecarChunks> {
+    let mut file_chunks: Vec<(PathBuf, ck_index::ChunkEntry)> = Vec::new();
+    let mut file_models: Vec<SidecarModel> = Vec::new();
+
+    // Filter on the source path before reading the sidecar itself
+    for (original_file, sidecar) in indexed_files(index_dir, index_root)? {
+        if deadline.is_some_and(|deadline| Instant::now() >= deadline) {
+            return Ok((file_chunks, file_models, true));
+        }
+        if !keep(&original_file) {
+            continue;
+        }
+        let index_entry = match ck_index::load_index_entry(&sidecar) {
+            Ok(index_entry) => index_entry,
+            Err(err) => {
+                tracing::warn!("Skipping {}: {err}", original_file.display());
+                continue;
+            }
+        };
+        // Sidecars from before the flag was recorded fall back to the file
+        if skip_generated
+            && index_entry
+                .generated
+                .unwrap_or_else(|| ck_index::is_generated_file(&original_file))
+        {
+            continue;
+        }
+        if let Some(dimensions) = index_entry
+            .chunks
+            .iter()
+            .find_map(|chunk| chunk.embedding.as_ref().map(Vec::len))
+        {
+            file_models.push(SidecarModel {
+                file: original_file.clone(),
+                model: index_entry.embedding_model.clone(),
+                dimensions,
+            });
+        }
+        for chunk in index_entry.chunks {
+            if chunk.embedding.is_some() {
+                file_chunks.push((original_file.clone(), chunk));
+            }
+        }
+    }
+
+    Ok((file_chunks, file_models, false))
+}
+
+/// Project each query vector the index's `--reduce-dim` `projection` applies
+/// to into the index's reduced space, as its chunks were.
+pub(crate) fn project_queries(
+    projection: Option<&ck_index::Projection>,
+    queries: &mut [(String, Vec<f32>)],
+) {
+    let Some(projection) = projection else {
+        return;
+    };
+    for (model, query) in queries {
+        if projection.applies_to(model, query) {
+            *query = projection.project(query);
+        }
+    }
+}
+
+/// Models the query is embedded with: the index's own, then each of the
+/// index's per-language models that embedded one of the files searched.
+fn query_models(
+    index_root: &Path,
+    index_model: &ck_models::ModelConfig,
+    file_models: &[SidecarModel],
+) -> Result<Vec<ck_models::ModelConfig>> {
+    let registry = ck_models::ModelRegistry::default();
+    let mut configs = vec![index_model.clone()];
+    for model in ck_index::index_language_models(index_root).into_values() {
+        if configs.iter().any(|config| config.name == model)
+            || !file_models
+                .iter()
+                .any(|file| file.model.as_deref() == Some(model.as_str()))
+        {
+            continue;
+        }
+        let (_, config) = registry
+            .resolve(Some(&model))
+            .map_err(|e| CkError::Embedding(e.to_string()))?;
+        configs.push(config);
+    }
+    Ok(configs)
+}
+
+/// Drop files embedded by another of the index's models (its own or a
+/// per-language one) than `model`, for searches that can only compare
+/// against `model`'s vectors. Those files sit in another vector space by
+/// design, so unlike sidecars left by an old model they are skipped without
+/// complaint.
+pub(crate) fn skip_other_index_models(
+    index_root: &Path,
+    index_model: &str,
+    model: &str,
+    file_chunks: &mut Vec<(PathBuf, ck_index::ChunkEntry)>,
+    file_models: &mut Vec<SidecarModel>,
+) {
+    let language_models = ck_index::index_language_models(index_root);
+    if language_models.is_empty() {
+        return;
+    }
+    let skipped: std::collections::HashSet<PathBuf> = file_models
+        .iter()
+        .filter(|file| {
+            file.model.as_deref().is_some_and(|m| {
+                m != model && (m == index_model || language_models.values().any(|lm| lm == m))
+            })
+        })
+        .map(|file| file.file.clone())
+        .collect();
+    file_models.retain(|file| !skipped.contains(&file.file));
+    file_chunks.retain(|(file, _)| !skipped.contains(file));
+}
+
+/// Drop chunks from files whose vectors can't be compared with a query from
+/// any of `expected`'s models / dimensions, or fail unless
+/// `--ignore-model-mismatch` is set. The first entry is the index's model,
+/// which the error names.
+pub(crate) fn retain_comparable(
+    options: &SearchOptions,
+    file_chunks: &mut Vec<(PathBuf, ck_index::ChunkEntry)>,
+    file_models: &[SidecarModel],
+    expected: &[(&str, usize)],
+    progress_callback: Option<&SearchProgressCallback>,
+) -> Result<()> {
+    // Vectors from a different model (or dimension) aren't comparable with the
+    // query; a partially re-indexed tree would otherwise return garbage.
+    let mismatches = find_model_mismatches(file_models, expected);
+    if mismatches.is_empty() {
+        return Ok(());
+    }
+    if !options.ignore_model_mismatch {
+        let (model, dimensions) = expected[0];
+        return Err(
+            CkError::Search(format_model_mismatch_error(&mismatches, model, dimensions)).into(),
+        );
+    }
+    if let Some(callback) = progress_callback {
+        callback(&format!(
+            "Skipping {} files indexed with a different embedding model",
+            mismatches.len()
+        ));
+    }
+    let skipped: std::collections::HashSet<&PathBuf> = mismatches.iter().map(|m| &m.file).collect();
+    file_chunks.retain(|(file, _)| !skipped.contains(file));
+    Ok(())
+}
+
+/// Score every chunk against the query from its file's model (`queries` holds
+/// each model's query vector, the index's model first) and keep the best
+/// candidates.
+async fn rank_chunks(
+    options: &SearchOptions,
+    mut file_chunks: Vec<(PathBuf, ck_index::ChunkEntry)>,
+    file_models: &[SidecarModel],
+    queries: &[(String, Vec<f32>)],
+    progress_callback: Option<&SearchProgressCallback>,
+) -> Result<ck_core::SearchResults> {
+    retain_comparable(
+        options,
+        &mut file_chunks,
+        file_models,
+        &expected_models(queries),
+        progress_callback,
+    )?;
+
+    if let Some(callback) = progress_callback {
         callback("Computing similarity scores...");
     }
 
-    // Compute similarities
-    let mut similarities: Vec<(f32, &std::path::PathBuf, &ck_index::ChunkEntry)> = Vec::new();
+    let candidates = score_chunks(&file_chunks, file_models, queries);
+    let limit = candidate_limit(options).unwrap_or(candidates.len());
+    let (similarities, collapsed) = collapse_near_duplicates(options, candidates, limit);
+    let mut results = collect_ranked_results(options, similarities, limit).await?;
+    dedup::annotate(&mut results.matches, &collapsed);
+    Ok(results)
+}
+
+/// The model and dimensions of each query vector in `queries`.
+fn expected_models(queries: &[(String, Vec<f32>)]) -> Vec<(&str, usize)> {
+    queries
+        .iter()
+        .map(|(model, embedding)| (model.as_str(), embedding.len()))
+        .collect()
+}
 
-    for (file_path, chunk) in &file_chunks {
-        if let Some(ref embedding) = chunk.embedding {
-            let similarity = cosine_similarity(query_embedding, embedding);
-            similarities.push((similarity, file_path, chunk));
+/// Cosine similarity of every embedded chunk with the query vector from its
+/// file's model, kept alongside the chunk's embedding for `--dedup`. Chunks
+/// from a quantized index are scored against their stored form (see
+/// [`ck_index::QuantizedQuery`]).
+fn score_chunks<'a>(
+    file_chunks: &'a [(PathBuf, ck_index::ChunkEntry)],
+    file_models: &[SidecarModel],
+    queries: &[(String, Vec<f32>)],
+) -> Vec<Candidate<'a>> {
+    // Only an index with per-language models has more than one query vector
+    let expected = expected_models(queries);
+    let query_of: std::collections::HashMap<&Path, usize> = if queries.len() > 1 {
+        file_models
+            .iter()
+            .filter_map(|file| Some((file.file.as_path(), comparable_query(&expected, file)?)))
+            .collect()
+    } else {
+        Default::default()
+    };
+    let quantized = file_chunks
+        .iter()
+        .any(|(_, chunk)| chunk.quantized.is_some());
+    let quantized_queries: Vec<_> = queries
+        .iter()
+        .map(|(_, query)| quantized.then(|| ck_index::QuantizedQuery::new(query)))
+        .collect();
+    file_chunks
+        .iter()
+        .filter_map(|(file_path, chunk)| {
+            let embedding = chunk.embedding.as_deref()?;
+            let query = query_of.get(file_path.as_path()).copied().unwrap_or(0);
+            let similarity = match (&chunk.quantized, &quantized_queries[query]) {
+                (Some(quantized), Some(query)) => query.similarity(quantized),
+                _ => cosine_similarity(&queries[query].1, embedding),
+            };
+            Some((similarity, file_path, &chunk.span, embedding))
+        })
+        .collect()
+}
+
+/// Above this many files, an ephemeral search warns that it's slow.
+const EPHEMERAL_LARGE_TARGET_FILES: usize = 2_000;
+
+/// An index loaded once for many queries: its comparable embedded chunks and
+/// the embedding models, so repeated searches (e.g. `--bench`) pay only for
+/// embedding the query and ranking. It can also be built in memory from the
+/// files themselves ([`LoadedIndex::in_memory`]).
+pub struct LoadedIndex {
+    file_chunks: Vec<(PathBuf, ck_index::ChunkEntry)>,
+    file_models: Vec<SidecarModel>,
+    /// The index's model first, then any per-language models in use
+    embedders: Vec<Box<dyn ck_embed::Embedder>>,
+    /// The index's `--reduce-dim` projection
+    projection: Option<ck_index::Projection>,
+}
+
+impl LoadedIndex {
+    /// Load the index covering `options.path`, scoped like a semantic search
+    /// with the same options. The index must already exist.
+    pub fn load(options: &SearchOptions) -> Result<Self> {
+        let index_root = find_nearest_index_root(&options.path).unwrap_or_else(|| {
+            if options.path.is_file() {
+                options.path.parent().unwrap_or(&options.path).to_path_buf()
+            } else {
+                options.path.clone()
+            }
+        });
+        let index_dir = ck_core::index_dir(&index_root);
+        if !index_dir.exists() {
+            return Err(
+                CkError::Index("No index found. Run 'ck --index' first.".to_string()).into(),
+            );
         }
+        ck_core::check_index_root_marker(&index_root)?;
+
+        let scope = PathScope::new(&options.path);
+        let include_globs = super::search_include_globs(options)?;
+        // Loaded once for many queries, so no single search's deadline applies
+        let (mut file_chunks, file_models, _) = load_sidecar_chunks(
+            &index_dir,
+            &index_root,
+            options.no_generated,
+            None,
+            |file| {
+                super::path_matches_include(file, &options.include_patterns)
+                    && include_globs.matches(file)
+                    && super::modified_since_matches(file, options)
+                    && scope.contains(file)
+            },
+        )?;
+
+        let resolved_model =
+            resolve_model_from_root(&index_root, options.embedding_model.as_deref())?;
+        let embedders = query_models(&index_root, &resolved_model.config, &file_models)?
+            .iter()
+            .map(|config| ck_embed::create_embedder_for_config(config, None))
+            .collect::<Result<Vec<_>>>()?;
+        let projection = ck_index::index_projection(&index_root);
+        let expected: Vec<_> = embedders
+            .iter()
+            .map(|embedder| {
+                let dimensions = match &projection {
+                    Some(projection) if projection.model == embedder.model_name() => {
+                        projection.dimensions()
+                    }
+                    _ => embedder.dim(),
+                };
+                (embedder.model_name(), dimensions)
+            })
+            .collect();
+        retain_comparable(options, &mut file_chunks, &file_models, &expected, None)?;
+
+        Ok(Self {
+            file_chunks,
+            file_models,
+            embedders,
+            projection,
+        })
     }
 
-    // Sort by similarity (highest first)
-    similarities.sort_by(|a, b| b.0.partial_cmp(&a.0).unwrap_or(std::cmp::Ordering::Equal));
+    /// Number of embedded chunks each query is scored against.
+    pub fn candidates(&self) -> usize {
+        self.file_chunks
+            .iter()
+            .filter(|(_, chunk)| chunk.embedding.is_some())
+            .count()
+    }
+
+    /// Build an index of `files` in memory for a one-shot search
+    /// (`--ephemeral`): each file is chunked and embedded with the model a
+    /// search of `options.path` would use, and nothing is written to disk.
+    /// Files that can't be embedded this way (binary files, PDFs, notebooks)
+    /// are skipped. With `options.document` set, that is embedded instead.
+    pub fn in_memory(
+        options: &SearchOptions,
+        files: &[PathBuf],
+        progress_callback: Option<&SearchProgressCallback>,
+    ) -> Result<Self> {
+        if files.len() > EPHEMERAL_LARGE_TARGET_FILES {
+            tracing::warn!(
+                "Embedding {} files in memory for an ephemeral search; nothing is cached between runs, so index large trees with 'ck --index' instead",
+                files.len()
+            );
+        }
+        let resolved_model =
+            super::resolve_model_for_path(&options.path, options.embedding_model.as_deref())?;
+        if let Some(callback) = progress_callback {
+            callback("Loading embedding model...");
+        }
+        let mut embedder = ck_embed::create_embedder_for_config(&resolved_model.config, None)?;
+        let model = embedder.model_name().to_string();
+        let dimensions = embedder.dim();
+
+        let mut file_chunks = Vec::new();
+        let mut file_models = Vec::new();
+        if let Some(document) = &options.document {
+            let chunks = ck_index::embed_text_in_memory(
+                &document.name,
+                &document.content,
+                document.language(),
+                &mut embedder,
+                options.embed_batch_size,
+            )?;
+            file_models.push(SidecarModel {
+                file: document.name.clone(),
+                model: Some(model.clone()),
+                dimensions,
+            });
+            file_chunks.extend(
+                chunks
+                    .into_iter()
+                    .map(|chunk| (document.name.clone(), chunk)),
+            );
+        }
+        for (position, file) in files.iter().enumerate() {
+            options.check_cancelled()?;
+            if let Some(callback) = progress_callback {
+                callback(&format!(
+                    "Embedding files in memory ({}/{})",
+                    position + 1,
+                    files.len()
+                ));
+            }
+            let chunks =
+                match ck_index::embed_file_in_memory(file, &mut embedder, options.embed_batch_size)
+                {
+                    Ok(chunks) => chunks,
+                    Err(err) => {
+                        tracing::debug!("Skipping {}: {err}", file.display());
+                        continue;
+                    }
+                };
+            file_models.push(SidecarModel {
+                file: file.clone(),
+                model: Some(model.clone()),
+                dimensions,
+            });
+            file_chunks.extend(chunks.into_iter().map(|chunk| (file.clone(), chunk)));
+        }
+
+        Ok(Self {
+            file_chunks,
+            file_models,
+            embedders: vec![embedder],
+            projection: None,
+        })
+    }
+
+    /// Run `options.query` against the loaded index; `options.path` and the
+    /// scoping options are those given to [`LoadedIndex::load`].
+    pub async fn search(&mut self, options: &SearchOptions) -> Result<ck_core::SearchResults> {
+        let options = &super::paged_options(options);
+        let mut results = self.search_page(options).await?;
+        super::skip_offset(&mut results.matches, options.offset);
+        Ok(results)
+    }
+
+    /// [`LoadedIndex::search`] with `options` already paged, leaving the
+    /// `offset` results in front of the page to the caller.
+    pub(crate) async fn search_page(
+        &mut self,
+        options: &SearchOptions,
+    ) -> Result<ck_core::SearchResults> {
+        let query_text = if options.split_identifiers {
+            split_query_identifiers(&options.query)
+        } else {
+            options.query.clone()
+        };
+        let mut timings = SearchTimings::default();
+        let started = Instant::now();
+        let mut queries = Vec::new();
+        for embedder in &mut self.embedders {
+            let model_name = embedder.model_name().to_string();
+            let Some(query_embedding) =
+                query_cache::get_or_embed(&model_name, &query_text, || {
+                    embedder.embed(std::slice::from_ref(&query_text))
+                })?
+            else {
+                return Ok(ck_core::SearchResults {
+                    matches: Vec::new(),
+                    closest_below_threshold: None,
+                    timings,
+                    truncated: false,
+                    lower_thresholds: Vec::new(),
+                });
+            };
+            queries.push((model_name, query_embedding));
+        }
+        project_queries(self.projection.as_ref(), &mut queries);
+        timings.query_embed = Some(started.elapsed());
+
+        let started = Instant::now();
+        let candidates = score_chunks(&self.file_chunks, &self.file_models, &queries);
+        let limit = candidate_limit(options).unwrap_or(candidates.len());
+        let (similarities, collapsed) = collapse_near_duplicates(options, candidates, limit);
+        let mut results = collect_ranked_results(options, similarities, limit).await?;
+        dedup::annotate(&mut results.matches, &collapsed);
+        timings.scoring = Some(started.elapsed());
+        let started = Instant::now();
+        rerank_results(options, &mut results.matches, None).await?;
+        if options.rerank {
+            timings.rerank = Some(started.elapsed());
+        }
+        if let Some(limit) = options.top_k {
+            results.matches.truncate(limit);
+        }
+        results.timings = timings;
+        Ok(results)
+    }
+}
+
+/// Sort scored spans (highest first), then turn the leading `limit` into
+/// results, holding back the best one under the threshold as a near miss.
+/// Spans past a file's `max_matches_per_file` are skipped before counting,
+/// so the cap never leaves the page short.
+pub(crate) async fn collect_ranked_results(
+    options: &SearchOptions,
+    mut similarities: Vec<(f32, &PathBuf, &ck_core::Span)>,
+    limit: usize,
+) -> Result<ck_core::SearchResults> {
+    // Sort by similarity (highest first), ties by path and position so the
+    // order doesn't depend on sidecar enumeration and pages are stable
+    similarities.sort_by(|a, b| ck_core::rank_order((a.0, a.1, a.2), (b.0, b.1, b.2)));
 
     // Apply threshold and top_k filtering
+    let cutoff = super::score_cutoff(options, similarities.iter().map(|(score, _, _)| *score));
+    let lower_thresholds = match options.threshold {
+        Some(threshold) if options.threshold_percentile.is_none() => {
+            lower_threshold_steps(options, &similarities, threshold, limit)
+        }
+        _ => Vec::new(),
+    };
     let mut results = Vec::new();
     let mut closest_below_threshold: Option<SearchResult> = None;
-    let limit = options.top_k.unwrap_or(similarities.len());
 
-    for (similarity, file_path, chunk) in similarities.into_iter().take(limit) {
-        let is_below_threshold = options
-            .threshold
-            .is_some_and(|threshold| similarity < threshold);
+    let mut within_cap = super::per_file_cap(options);
+    for (similarity, file_path, span) in similarities
+        .into_iter()
+        .filter(|(_, file_path, span)| {
+            options.line_ranges.admits(file_path, span) && within_cap(file_path)
+        })
+        .take(limit)
+    {
+        let is_below_threshold = cutoff.is_some_and(|threshold| similarity < threshold);
 
         // Extract content from the file using the span, skip if file doesn't exist
+        let Ok(full_content) = extract_content_from_span(options, file_path, span).await else {
+            // Skip files that no longer exist (stale index entries)
+            continue;
+        };
+        let match_line = (span.line_end > span.line_start)
+            .then(|| match_line_offset(&full_content, &options.query))
+            .flatten()
+            .map(|offset| span.line_start + offset);
+        let mut preview_line = None;
         let content = if options.full_section {
-            match extract_content_from_span(file_path, &chunk.span).await {
-                Ok(content) => content,
-                Err(_) => {
-                    // Skip files that no longer exist (stale index entries)
-                    continue;
-                }
-            }
+            full_content
         } else {
-            match extract_content_from_span(file_path, &chunk.span).await {
-                Ok(full_content) => {
-                    // Take first 3 lines for preview
-                    full_content.lines().take(3).collect::<Vec<_>>().join("\n")
-                }
-                Err(_) => {
-                    // Skip files that no longer exist (stale index entries)
-                    continue;
+            match options.snippet_lines {
+                Some(lines) => {
+                    let (offset, window) = snippet_window(&full_content, &options.query, lines);
+                    preview_line = Some(span.line_start + offset);
+                    window
                 }
+                // Take first 3 lines for preview
+                None => full_content.lines().take(3).collect::<Vec<_>>().join("\n"),
             }
         };
 
         let search_result = SearchResult {
             file: file_path.clone(),
-            span: chunk.span.clone(),
+            span: span.clone(),
             score: similarity,
             preview: content,
             lang: ck_core::Language::from_path(file_path),
             symbol: None,
             chunk_hash: None,
             index_epoch: None,
+            original_score: None,
+            stale: false,
+            root: None,
+            preview_line,
+            duplicates: 0,
+            grouped: 0,
+            column: None,
+            match_line,
+            explanation: options.explain.then(|| ck_core::ScoreBreakdown {
+                similarity: Some(similarity),
+                threshold: cutoff,
+                ..Default::default()
+            }),
+            context_before: None,
+            context_after: None,
         };
 
         if is_below_threshold {
@@ -198,88 +874,130 @@ pub async fn semantic_search_v3_with_progress(
         }
     }
 
-    // Apply reranking if enabled
-    if options.rerank && !results.is_empty() {
-        if let Some(ref callback) = progress_callback {
-            callback("Reranking results for improved relevance...");
-        }
-
-        let rerank_registry = ck_models::RerankModelRegistry::default();
-        let (rerank_alias, rerank_config) = rerank_registry
-            .resolve(options.rerank_model.as_deref())
-            .map_err(|e| anyhow::anyhow!(e.to_string()))?;
+    Ok(ck_core::SearchResults {
+        matches: results,
+        closest_below_threshold,
+        timings: Default::default(),
+        truncated: false,
+        lower_thresholds,
+    })
+}
 
-        match ck_embed::create_reranker_for_config(&rerank_config, None) {
-            Ok(mut reranker) => {
-                if let Some(ref callback) = progress_callback {
-                    callback(&format!("Reranking results with model {rerank_alias}"));
-                }
+/// How far below the threshold [`lower_threshold_steps`] looks.
+const THRESHOLD_STEPS: [f32; 4] = [0.05, 0.10, 0.15, 0.20];
 
-                let documents: Vec<String> = results.iter().map(|r| r.preview.clone()).collect();
-
-                match reranker.rerank(&options.query, &documents) {
-                    Ok(rerank_results) => {
-                        // Create a map from document text to indices for handling duplicates
-                        let mut doc_to_indices: std::collections::HashMap<String, Vec<usize>> =
-                            std::collections::HashMap::new();
-                        for (i, result) in results.iter().enumerate() {
-                            doc_to_indices
-                                .entry(result.preview.clone())
-                                .or_default()
-                                .push(i);
-                        }
-
-                        // Update results with reranked scores
-                        // The reranker returns results in reranked order, so we match by document text
-                        for rerank_result in rerank_results.iter() {
-                            if let Some(indices) = doc_to_indices.get_mut(&rerank_result.document)
-                                && let Some(idx) = indices.pop()
-                            {
-                                results[idx].score = rerank_result.score;
-                            }
-                        }
-
-                        // Re-sort by reranked scores
-                        results.sort_by(|a, b| {
-                            b.score
-                                .partial_cmp(&a.score)
-                                .unwrap_or(std::cmp::Ordering::Equal)
-                        });
-
-                        // Apply top_k limit again after reranking
-                        if let Some(limit) = options.top_k {
-                            results.truncate(limit);
-                        }
-                    }
-                    Err(e) => {
-                        tracing::warn!("Reranking failed, using original scores: {}", e);
-                    }
-                }
-            }
-            Err(e) => {
-                tracing::warn!("Failed to create reranker, using original scores: {}", e);
-            }
-        }
+/// When none of the sorted `similarities` reaches `threshold`, the number of
+/// results (at most `limit`) each of a few lower thresholds would return, so
+/// the user can be pointed at one that finds something. Empty otherwise.
+fn lower_threshold_steps(
+    options: &SearchOptions,
+    similarities: &[(f32, &PathBuf, &ck_core::Span)],
+    threshold: f32,
+    limit: usize,
+) -> Vec<ck_core::ThresholdStep> {
+    let mut within_cap = super::per_file_cap(options);
+    let scores: Vec<f32> = similarities
+        .iter()
+        .filter(|(_, file_path, span)| {
+            options.line_ranges.admits(file_path, span) && within_cap(file_path)
+        })
+        .map(|(score, _, _)| *score)
+        .take_while(|score| *score >= threshold - THRESHOLD_STEPS[THRESHOLD_STEPS.len() - 1])
+        .collect();
+    if scores.first().is_some_and(|score| *score >= threshold) {
+        return Vec::new();
     }
+    THRESHOLD_STEPS
+        .iter()
+        // Steps land on round values: 0.6 - 0.05 is 0.55, not 0.54999995
+        .map(|step| ((threshold - step) * 100.0).round() / 100.0)
+        .filter(|lower| *lower > -1.0)
+        .map(|lower| ck_core::ThresholdStep {
+            threshold: lower,
+            results: scores
+                .iter()
+                .take_while(|score| **score >= lower)
+                .count()
+                .min(limit),
+        })
+        .collect()
+}
 
-    Ok(ck_core::SearchResults {
-        matches: results,
-        closest_below_threshold,
+/// Embedding metadata for one sidecar, checked against the query embedder.
+pub(crate) struct SidecarModel {
+    pub(crate) file: std::path::PathBuf,
+    /// Recorded model name; None for sidecars written before it was stored
+    pub(crate) model: Option<String>,
+    dimensions: usize,
+}
+
+/// The position in `expected` of the query `file`'s vectors can be compared
+/// with: the one from its model, or for a legacy sidecar without a recorded
+/// model, the first with its dimensions.
+fn comparable_query(expected: &[(&str, usize)], file: &SidecarModel) -> Option<usize> {
+    expected.iter().position(|(model, dimensions)| {
+        *dimensions == file.dimensions && file.model.as_deref().is_none_or(|m| m == *model)
     })
 }
 
+/// Sidecars whose vectors can't be compared with a query from any of the
+/// `expected` models. Legacy sidecars without a recorded model are judged on
+/// dimension alone.
+fn find_model_mismatches<'a>(
+    files: &'a [SidecarModel],
+    expected: &[(&str, usize)],
+) -> Vec<&'a SidecarModel> {
+    files
+        .iter()
+        .filter(|f| comparable_query(expected, f).is_none())
+        .collect()
+}
+
+fn format_model_mismatch_error(
+    mismatches: &[&SidecarModel],
+    expected_model: &str,
+    expected_dimensions: usize,
+) -> String {
+    const MAX_LISTED: usize = 10;
+
+    let mut message = format!(
+        "{} indexed files were embedded with a different model than the query model '{}' ({} dims):",
+        mismatches.len(),
+        expected_model,
+        expected_dimensions
+    );
+    for mismatch in mismatches.iter().take(MAX_LISTED) {
+        message.push_str(&format!(
+            "\n  {}: model '{}' ({} dims)",
+            mismatch.file.display(),
+            mismatch.model.as_deref().unwrap_or("unknown"),
+            mismatch.dimensions
+        ));
+    }
+    if mismatches.len() > MAX_LISTED {
+        message.push_str(&format!(
+            "\n  ... and {} more",
+            mismatches.len() - MAX_LISTED
+        ));
+    }
+    message.push_str(
+        "\nRebuild the index with `ck --switch-model <model> --force`, or pass --ignore-model-mismatch to skip these files.",
+    );
+    message
+}
+
 /// Scope a semantic query to a file, a directory, or the whole index.
 ///
 /// Cached canonical form of `options.path` so per-chunk membership
 /// checks don't re-canonicalize on every iteration.
-enum PathScope {
+pub(crate) enum PathScope {
     All,
     File(std::path::PathBuf),
     Dir(std::path::PathBuf),
 }
 
 impl PathScope {
-    fn new(path: &Path) -> Self {
+    pub(crate) fn new(path: &Path) -> Self {
         if path == Path::new(".") {
             return Self::All;
         }
@@ -291,7 +1009,7 @@ impl PathScope {
         }
     }
 
-    fn contains(&self, file: &Path) -> bool {
+    pub(crate) fn contains(&self, file: &Path) -> bool {
         match self {
             Self::All => true,
             Self::File(target) => {
@@ -306,7 +1024,7 @@ impl PathScope {
     }
 }
 
-fn reconstruct_original_path(
+pub(crate) fn reconstruct_original_path(
     sidecar_path: &Path,
     index_dir: &Path,
     repo_root: &Path,
@@ -328,7 +1046,7 @@ fn reconstruct_original_path(
     Some(repo_root.join(original_path))
 }
 
-fn cosine_similarity(a: &[f32], b: &[f32]) -> f32 {
+pub(crate) fn cosine_similarity(a: &[f32], b: &[f32]) -> f32 {
     if a.len() != b.len() {
         return 0.0;
     }
@@ -388,3 +1106,122 @@ mod path_scope_tests {
         assert!(!scope.contains(&other));
     }
 }
+
+#[cfg(test)]
+mod model_mismatch_tests {
+    use super::{
+        SidecarModel, comparable_query, find_model_mismatches, format_model_mismatch_error,
+    };
+    use std::path::PathBuf;
+
+    fn sidecar(file: &str, model: Option<&str>, dimensions: usize) -> SidecarModel {
+        SidecarModel {
+            file: PathBuf::from(file),
+            model: model.map(str::to_string),
+            dimensions,
+        }
+    }
+
+    #[test]
+    fn flags_other_models_and_dimensions() {
+        let files = vec![
+            sidecar("same.rs", Some("bge"), 384),
+            sidecar("other_model.rs", Some("minilm"), 384),
+            sidecar("other_dims.rs", Some("bge"), 768),
+            sidecar("legacy_ok.rs", None, 384),
+            sidecar("legacy_bad.rs", None, 1024),
+        ];
+
+        let mismatched: Vec<_> = find_model_mismatches(&files, &[("bge", 384)])
+            .into_iter()
+            .map(|m| m.file.to_string_lossy().to_string())
+            .collect();
+        assert_eq!(
+            mismatched,
+            vec!["other_model.rs", "other_dims.rs", "legacy_bad.rs"]
+        );
+    }
+
+    #[test]
+    fn accepts_files_from_any_query_model() {
+        let files = vec![
+            sidecar("lib.rs", Some("jina"), 768),
+            sidecar("README.md", Some("bge"), 384),
+            sidecar("legacy.md", None, 384),
+            sidecar("stale.rs", Some("minilm"), 384),
+        ];
+        let expected = [("bge", 384), ("jina", 768)];
+
+        let mismatched: Vec<_> = find_model_mismatches(&files, &expected)
+            .into_iter()
+            .map(|m| m.file.to_string_lossy().to_string())
+            .collect();
+        assert_eq!(mismatched, vec!["stale.rs"]);
+        assert_eq!(comparable_query(&expected, &files[0]), Some(1));
+        assert_eq!(comparable_query(&expected, &files[2]), Some(0));
+    }
+
+    #[test]
+    fn error_lists_files_and_recorded_models() {
+        let files = vec![
+            sidecar("a.rs", Some("nomic"), 768),
+            sidecar("b.rs", None, 1024),
+        ];
+        let mismatches = find_model_mismatches(&files, &[("bge", 384)]);
+        let message = format_model_mismatch_error(&mismatches, "bge", 384);
+
+        assert!(message.contains("a.rs: model 'nomic' (768 dims)"));
+        assert!(message.contains("b.rs: model 'unknown' (1024 dims)"));
+        assert!(message.contains("--ignore-model-mismatch"));
+    }
+}
+
+#[cfg(test)]
+mod threshold_step_tests {
+    use super::lower_threshold_steps;
+    use ck_core::{SearchOptions, Span, ThresholdStep};
+    use std::path::PathBuf;
+
+    #[test]
+    fn counts_results_at_lower_thresholds_only_when_none_pass() {
+        let file = PathBuf::from("a.rs");
+        let span = Span {
+            byte_start: 0,
+            byte_end: 1,
+            line_start: 1,
+            line_end: 1,
+        };
+        let options = SearchOptions::default();
+        let scored = |scores: &[f32]| -> Vec<(f32, &PathBuf, &Span)> {
+            scores.iter().map(|&score| (score, &file, &span)).collect()
+        };
+
+        let steps = lower_threshold_steps(&options, &scored(&[0.57, 0.56, 0.52, 0.3]), 0.6, 10);
+        assert_eq!(
+            steps,
+            [
+                ThresholdStep {
+                    threshold: 0.55,
+                    results: 2
+                },
+                ThresholdStep {
+                    threshold: 0.5,
+                    results: 3
+                },
+                ThresholdStep {
+                    threshold: 0.45,
+                    results: 3
+                },
+                ThresholdStep {
+                    threshold: 0.4,
+                    results: 3
+                },
+            ]
+        );
+        // Capped at the result limit
+        let steps = lower_threshold_steps(&options, &scored(&[0.57, 0.56, 0.52]), 0.6, 1);
+        assert_eq!(steps[1].results, 1);
+        // Nothing to suggest once a result passes
+        assert!(lower_threshold_steps(&options, &scored(&[0.61, 0.5]), 0.6, 10).is_empty());
+    }
+}
@@ -0,0 +1,229 @@
+//! `--similar`: rank indexed files by how close their embeddings are to those
+//! of a target file, for spotting duplicated logic or related modules.
+//!
+//! Both granularities reuse the target's stored chunk vectors, so no model is
+//! loaded. [`SimilarBy::File`] compares mean vectors, one per file;
+//! [`SimilarBy::Chunk`] scores each file by its single closest chunk pair and
+//! points the result at that chunk.
+
+use anyhow::Result;
+use ck_core::{CkError, SearchOptions, SimilarBy, Span};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+
+use super::semantic_v3::{
+    PathScope, collect_ranked_results, cosine_similarity, load_sidecar_chunks, retain_comparable,
+    skip_other_index_models,
+};
+use super::{SearchProgressCallback, find_nearest_index_root, resolve_model_from_root};
+
+/// Files under `options.path` ranked by similarity to `target`, which is
+/// itself left out. Threshold and top-k apply as for semantic search.
+pub async fn similar_search(
+    options: &SearchOptions,
+    target: &Path,
+    progress_callback: Option<SearchProgressCallback>,
+) -> Result<ck_core::SearchResults> {
+    let index_root = find_nearest_index_root(&options.path).unwrap_or_else(|| {
+        if options.path.is_file() {
+            options.path.parent().unwrap_or(&options.path).to_path_buf()
+        } else {
+            options.path.clone()
+        }
+    });
+    let index_dir = ck_core::index_dir(&index_root);
+    if !index_dir.exists() {
+        return Err(CkError::Index("No index found. Run 'ck --index' first.".to_string()).into());
+    }
+    ck_core::check_index_root_marker(&index_root)?;
+
+    if let Some(ref callback) = progress_callback {
+        callback("Loading embeddings from sidecar files...");
+    }
+
+    let target_canonical = canonical(target);
+    let is_target = |file: &Path| canonical(file) == target_canonical;
+    let scope = PathScope::new(&options.path);
+    let include_globs = super::search_include_globs(options)?;
+    // The target is compared against even when it's generated itself
+    let (file_chunks, mut file_models, truncated) =
+        load_sidecar_chunks(&index_dir, &index_root, false, options.deadline, |file| {
+            is_target(file)
+                || (super::path_matches_include(file, &options.include_patterns)
+                    && include_globs.matches(file)
+                    && super::modified_since_matches(file, options)
+                    && scope.contains(file)
+                    && !(options.no_generated && ck_index::is_generated_file(file)))
+        })?;
+
+    let (target_chunks, mut candidates): (Vec<_>, Vec<_>) = file_chunks
+        .into_iter()
+        .partition(|(file, _)| is_target(file));
+    let target_vectors: Vec<Vec<f32>> = target_chunks
+        .into_iter()
+        .filter_map(|(_, chunk)| chunk.embedding)
+        .collect();
+    let Some(dimensions) = target_vectors.first().map(Vec::len) else {
+        // The deadline passed before the target's sidecar was read
+        if truncated {
+            return Ok(ck_core::SearchResults {
+                matches: Vec::new(),
+                closest_below_threshold: None,
+                timings: Default::default(),
+                truncated,
+                lower_thresholds: Vec::new(),
+            });
+        }
+        return Err(CkError::Index(format!(
+            "No embeddings found for {}. It must be inside the indexed tree; run 'ck --index' first.",
+            target.display()
+        ))
+        .into());
+    };
+
+    // Compare against whatever model embedded the target
+    let index_model = resolve_model_from_root(&index_root, options.embedding_model.as_deref())?
+        .canonical_name()
+        .to_string();
+    let model = file_models
+        .iter()
+        .find(|m| is_target(&m.file))
+        .and_then(|m| m.model.clone())
+        .unwrap_or_else(|| index_model.clone());
+    skip_other_index_models(
+        &index_root,
+        &index_model,
+        &model,
+        &mut candidates,
+        &mut file_models,
+    );
+    retain_comparable(
+        options,
+        &mut candidates,
+        &file_models,
+        &[(&model, dimensions)],
+        progress_callback.as_ref(),
+    )?;
+
+    if let Some(ref callback) = progress_callback {
+        callback("Computing similarity scores...");
+    }
+
+    let mut by_file: HashMap<PathBuf, Vec<(Span, Vec<f32>)>> = HashMap::new();
+    for (file, chunk) in candidates {
+        if let Some(embedding) = chunk.embedding {
+            by_file
+                .entry(file)
+                .or_default()
+                .push((chunk.span, embedding));
+        }
+    }
+
+    let scored: Vec<(f32, PathBuf, Span)> = match options.similar_by {
+        SimilarBy::File => {
+            let target_mean = mean_vector(target_vectors.iter());
+            by_file
+                .into_iter()
+                .map(|(file, chunks)| {
+                    let mean = mean_vector(chunks.iter().map(|(_, v)| v));
+                    let span = covering_span(chunks.iter().map(|(s, _)| s));
+                    (cosine_similarity(&target_mean, &mean), file, span)
+                })
+                .collect()
+        }
+        SimilarBy::Chunk => by_file
+            .into_iter()
+            .filter_map(|(file, chunks)| {
+                chunks
+                    .into_iter()
+                    .map(|(span, vector)| {
+                        let best = target_vectors
+                            .iter()
+                            .map(|t| cosine_similarity(t, &vector))
+                            .fold(f32::MIN, f32::max);
+                        (best, span)
+                    })
+                    .max_by(|a, b| a.0.partial_cmp(&b.0).unwrap_or(std::cmp::Ordering::Equal))
+                    .map(|(score, span)| (score, file, span))
+            })
+            .collect(),
+    };
+
+    let ranked = scored
+        .iter()
+        .map(|(score, file, span)| (*score, file, span))
+        .collect::<Vec<_>>();
+    let limit = options.top_k.unwrap_or(ranked.len());
+    let mut results = collect_ranked_results(options, ranked, limit).await?;
+    results.truncated = truncated;
+    Ok(results)
+}
+
+fn canonical(path: &Path) -> PathBuf {
+    path.canonicalize().unwrap_or_else(|_| path.to_path_buf())
+}
+
+/// Component-wise mean of `vectors`; empty input gives an empty vector.
+fn mean_vector<'a>(vectors: impl Iterator<Item = &'a Vec<f32>>) -> Vec<f32> {
+    let mut sum: Vec<f32> = Vec::new();
+    let mut count = 0usize;
+    for vector in vectors {
+        if sum.is_empty() {
+            sum = vec![0.0; vector.len()];
+        }
+        for (acc, x) in sum.iter_mut().zip(vector) {
+            *acc += x;
+        }
+        count += 1;
+    }
+    if count > 0 {
+        for acc in &mut sum {
+            *acc /= count as f32;
+        }
+    }
+    sum
+}
+
+/// Smallest span containing all of `spans`.
+fn covering_span<'a>(spans: impl Iterator<Item = &'a Span>) -> Span {
+    spans
+        .cloned()
+        .reduce(|a, b| Span {
+            byte_start: a.byte_start.min(b.byte_start),
+            byte_end: a.byte_end.max(b.byte_end),
+            line_start: a.line_start.min(b.line_start),
+            line_end: a.line_end.max(b.line_end),
+        })
+        .unwrap_or(Span {
+            byte_start: 0,
+            byte_end: 0,
+            line_start: 1,
+            line_end: 1,
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn span(lines: (usize, usize), bytes: (usize, usize)) -> Span {
+        Span {
+            byte_start: bytes.0,
+            byte_end: bytes.1,
+            line_start: lines.0,
+            line_end: lines.1,
+        }
+    }
+
+    #[test]
+    fn test_mean_vector_and_covering_span() {
+        let vectors = [vec![1.0, 0.0], vec![0.0, 1.0], vec![2.0, 2.0]];
+        assert_eq!(mean_vector(vectors.iter()), vec![1.0, 1.0]);
+        assert!(mean_vector(std::iter::empty()).is_empty());
+
+        let spans = [span((5, 9), (40, 90)), span((1, 3), (0, 30))];
+        let covering = covering_span(spans.iter());
+        assert_eq!((covering.line_start, covering.line_end), (1, 9));
+        assert_eq!((covering.byte_start, covering.byte_end), (0, 90));
+    }
+}
@@ -0,0 +1,112 @@
+//! Preview windows for `--snippet-lines`.
+//!
+//! A semantic match covers a whole chunk, but its preview is only a few
+//! lines. Instead of always showing the chunk's first lines, the window is
+//! centered on the line sharing the most words with the query, falling back
+//! to the top of the chunk (usually the signature) when no line does. The
+//! same line anchors [`SearchResult::match_line`](ck_core::SearchResult).
+
+use crate::identifiers::identifier_words;
+
+/// Longest line kept in a snippet, in characters; the rest becomes `…`.
+const MAX_SNIPPET_LINE_CHARS: usize = 200;
+
+/// Up to `lines` lines of `text` around its most query-relevant line.
+/// Returns the window's offset from the first line of `text` along with it.
+pub(crate) fn snippet_window(text: &str, query: &str, lines: usize) -> (usize, String) {
+    let all: Vec<&str> = text.lines().collect();
+    let lines = lines.max(1);
+    let best = best_line(&all, query).unwrap_or(0);
+    let start = best
+        .saturating_sub((lines - 1) / 2)
+        .min(all.len().saturating_sub(lines));
+    let window = all
+        .iter()
+        .skip(start)
+        .take(lines)
+        .map(|line| cap_line(line.trim_end()))
+        .collect::<Vec<_>>()
+        .join("\n");
+    (start, window)
+}
+
+/// Offset from the first line of `text` of its most query-relevant line, or
+/// `None` when no line shares a word with the query.
+pub(crate) fn match_line_offset(text: &str, query: &str) -> Option<usize> {
+    best_line(&text.lines().collect::<Vec<_>>(), query)
+}
+
+/// Index of the line matching the most query words, the earliest on a tie;
+/// `None` when no line matches any.
+fn best_line(lines: &[&str], query: &str) -> Option<usize> {
+    let mut terms: Vec<String> = query
+        .split(|c: char| !c.is_alphanumeric() && c != '_')
+        .filter(|word| word.chars().count() > 1)
+        .map(str::to_lowercase)
+        .collect();
+    terms.extend(identifier_words(query));
+    terms.sort();
+    terms.dedup();
+
+    let mut best = None;
+    let mut best_hits = 0;
+    for (index, line) in lines.iter().enumerate() {
+        let lower = line.to_lowercase();
+        let hits = terms.iter().filter(|term| lower.contains(*term)).count();
+        if hits > best_hits {
+            (best, best_hits) = (Some(index), hits);
+        }
+    }
+    best
+}
+
+fn cap_line(line: &str) -> String {
+    match line.char_indices().nth(MAX_SNIPPET_LINE_CHARS) {
+        Some((cut, _)) => format!("{}…", &line[..cut]),
+        None => line.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const CHUNK: &str = "fn load_config(path: &Path) -> Config {\n    let text = read(path);\n    let parsed = toml::from_str(&text);\n    validate_timeout(&parsed);   \n    parsed\n}";
+
+    #[test]
+    fn centers_window_on_best_matching_line() {
+        let (offset, window) = snippet_window(CHUNK, "validate timeout", 3);
+        assert_eq!(offset, 2);
+        assert_eq!(
+            window,
+            "    let parsed = toml::from_str(&text);\n    validate_timeout(&parsed);\n    parsed"
+        );
+    }
+
+    #[test]
+    fn falls_back_to_top_and_clamps_to_chunk() {
+        let (offset, window) = snippet_window(CHUNK, "unrelated words", 2);
+        assert_eq!(offset, 0);
+        assert!(window.starts_with("fn load_config"));
+
+        let (offset, _) = snippet_window(CHUNK, "}", 4);
+        assert_eq!(offset, 0);
+        let (offset, window) = snippet_window(CHUNK, "parsed", 10);
+        assert_eq!(offset, 0);
+        assert_eq!(window.lines().count(), 6);
+    }
+
+    #[test]
+    fn match_line_offset_needs_a_shared_word() {
+        assert_eq!(match_line_offset(CHUNK, "validate timeout"), Some(3));
+        assert_eq!(match_line_offset(CHUNK, "unrelated words"), None);
+    }
+
+    #[test]
+    fn caps_long_lines() {
+        let long = "x".repeat(500);
+        let (_, window) = snippet_window(&long, "x", 1);
+        assert_eq!(window.chars().count(), MAX_SNIPPET_LINE_CHARS + 1);
+        assert!(window.ends_with('…'));
+    }
+}
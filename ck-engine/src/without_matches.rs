@@ -0,0 +1,175 @@
+//! `-L` / `--files-without-matches` across search modes.
+//!
+//! A file is without matches when it is searched and contributes no result:
+//! no matching line for regex, no hit for lexical, and for semantic no chunk
+//! scoring at or above `threshold` (every chunk has *some* similarity, so
+//! without a threshold only unindexed files qualify). Semantic search can
+//! only score files that are in the index; those that aren't are reported
+//! separately rather than passed off as dissimilar.
+//!
+//! With `-w`, a semantic or hybrid hit only counts when its chunk contains
+//! one of the query's words whole; regex and lexical search already apply
+//! `-w` themselves.
+
+use anyhow::Result;
+use ck_core::{SearchMode, SearchOptions};
+use std::collections::HashSet;
+use std::path::PathBuf;
+
+use super::{
+    canonicalize_for_matching, contains_whole_word, extract_lines_from_file,
+    find_nearest_index_root, search_candidate_files, search_enhanced_with_outcome,
+    whole_word_terms,
+};
+
+#[derive(Debug, Clone, Default)]
+pub struct FilesWithoutMatches {
+    /// Searched files that produced no result
+    pub files: Vec<PathBuf>,
+    /// Semantic mode: files that would be searched but have no entry in the
+    /// index, so there was nothing to score
+    pub not_indexed: Vec<PathBuf>,
+}
+
+/// Files under `options.path` with no match for `options.query`, in walk order.
+pub async fn files_without_matches(options: &SearchOptions) -> Result<FilesWithoutMatches> {
+    let (candidates, _) = search_candidate_files(options)?;
+
+    // Every result counts, not just the ones that would be printed
+    let mut search_options = options.clone();
+    search_options.top_k = match options.mode {
+        // One lexical hit per file, and an unset limit means 100
+        SearchMode::Lexical => Some(candidates.len().max(1)),
+        _ => None,
+    };
+    search_options.rerank = false;
+    search_options.roots = Vec::new();
+    let outcome = search_enhanced_with_outcome(&search_options, None, None, None).await?;
+    let word_terms = (options.whole_word
+        && matches!(options.mode, SearchMode::Semantic | SearchMode::Hybrid))
+    .then(|| whole_word_terms(&options.query));
+    let matched: HashSet<PathBuf> = outcome
+        .results
+        .matches
+        .iter()
+        .filter(|result| {
+            word_terms.as_ref().is_none_or(|terms| {
+                extract_lines_from_file(&result.file, result.span.line_start, result.span.line_end)
+                    .is_ok_and(|text| contains_whole_word(&text, terms))
+            })
+        })
+        .map(|result| canonicalize_for_matching(&result.file))
+        .collect();
+
+    let indexed: Option<HashSet<PathBuf>> = (options.mode == SearchMode::Semantic).then(|| {
+        find_nearest_index_root(&options.path)
+            .map(|root| {
+                ck_index::indexed_files(&root)
+                    .iter()
+                    .map(|path| canonicalize_for_matching(path))
+                    .collect()
+            })
+            .unwrap_or_default()
+    });
+
+    let mut report = FilesWithoutMatches::default();
+    for file in candidates {
+        let canonical = canonicalize_for_matching(&file);
+        if matched.contains(&canonical) {
+            continue;
+        }
+        if indexed
+            .as_ref()
+            .is_some_and(|indexed| !indexed.contains(&canonical))
+        {
+            report.not_indexed.push(file);
+        } else {
+            report.files.push(file);
+        }
+    }
+    Ok(report)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+    use tempfile::TempDir;
+
+    fn corpus() -> TempDir {
+        let temp_dir = TempDir::new().unwrap();
+        fs::write(temp_dir.path().join("auth.txt"), "login with a password\n").unwrap();
+        fs::write(temp_dir.path().join("math.txt"), "add two numbers\n").unwrap();
+        temp_dir
+    }
+
+    fn names(files: &[PathBuf]) -> Vec<String> {
+        let mut names: Vec<String> = files
+            .iter()
+            .map(|f| f.file_name().unwrap().to_string_lossy().to_string())
+            .collect();
+        names.sort();
+        names
+    }
+
+    #[tokio::test]
+    async fn regex_lists_files_without_a_matching_line() {
+        let temp_dir = corpus();
+        let options = SearchOptions {
+            mode: SearchMode::Regex,
+            query: "pass.ord".to_string(),
+            path: temp_dir.path().to_path_buf(),
+            ..Default::default()
+        };
+        let report = files_without_matches(&options).await.unwrap();
+        assert_eq!(names(&report.files), ["math.txt"]);
+        assert!(report.not_indexed.is_empty());
+    }
+
+    #[tokio::test]
+    async fn lexical_lists_files_without_a_hit() {
+        let temp_dir = corpus();
+        let options = SearchOptions {
+            mode: SearchMode::Lexical,
+            query: "numbers".to_string(),
+            path: temp_dir.path().to_path_buf(),
+            top_k: Some(1),
+            ..Default::default()
+        };
+        let report = files_without_matches(&options).await.unwrap();
+        assert_eq!(names(&report.files), ["auth.txt"]);
+    }
+
+    #[tokio::test]
+    async fn semantic_uses_threshold_and_separates_unindexed_files() {
+        let temp_dir = corpus();
+        let mut options = SearchOptions {
+            mode: SearchMode::Semantic,
+            query: "credentials".to_string(),
+            path: temp_dir.path().to_path_buf(),
+            top_k: Some(1),
+            ..Default::default()
+        };
+        // Without a threshold every indexed chunk counts as a match
+        let report = files_without_matches(&options).await.unwrap();
+        assert!(report.files.is_empty());
+        assert!(report.not_indexed.is_empty());
+
+        // Nothing reaches a threshold above the best possible score
+        options.threshold = Some(1.5);
+        let report = files_without_matches(&options).await.unwrap();
+        assert_eq!(names(&report.files), ["auth.txt", "math.txt"]);
+
+        // An empty file is indexed with no chunks to score
+        fs::write(temp_dir.path().join("empty.txt"), "").unwrap();
+        options.threshold = None;
+        let report = files_without_matches(&options).await.unwrap();
+        assert_eq!(names(&report.files), ["empty.txt"]);
+
+        // With -w a chunk must also contain a query word whole
+        options.query = "password".to_string();
+        options.whole_word = true;
+        let report = files_without_matches(&options).await.unwrap();
+        assert_eq!(names(&report.files), ["empty.txt", "math.txt"]);
+    }
+}
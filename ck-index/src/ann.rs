@@ -0,0 +1,470 @@
+//! `--ann`: an approximate nearest-neighbour index over the sidecar vectors,
+//! so semantic search doesn't have to score every chunk.
+//!
+//! The index is a hierarchical navigable small world graph (HNSW) stored in
+//! `ann.bin` next to the sidecars, holding its own normalized copy of every
+//! vector. It records a fingerprint of the manifest it was built from; every
+//! indexing pass that changes the manifest rebuilds it, and search ignores
+//! one whose fingerprint no longer matches, falling back to scoring every
+//! chunk. The graph is rebuilt rather than patched: removing nodes from an
+//! HNSW graph degrades it.
+//!
+//! Search explores the `ef_search` most promising nodes: a larger value finds
+//! more of the true nearest neighbours at the cost of more comparisons.
+
+use anyhow::Result;
+use ck_core::Span;
+use serde::{Deserialize, Serialize};
+use std::cmp::{Ordering, Reverse};
+use std::collections::{BinaryHeap, HashSet};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use super::{IndexManifest, atomic_write, load_index_entry, load_or_create_manifest, path_utils};
+
+pub const ANN_FILE: &str = "ann.bin";
+/// `--ef-search` default
+pub const DEFAULT_EF_SEARCH: usize = 64;
+const ANN_FORMAT_VERSION: u32 = 1;
+/// Links per node on the upper layers, and twice that on the bottom one
+const M: usize = 16;
+const EF_CONSTRUCTION: usize = 100;
+
+/// One indexed chunk: its file, relative to the index root, and span.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AnnNode {
+    pub file: PathBuf,
+    pub span: Span,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AnnIndex {
+    version: u32,
+    fingerprint: String,
+    /// Model whose vectors these are, from the manifest
+    pub model: Option<String>,
+    pub dimensions: usize,
+    pub nodes: Vec<AnnNode>,
+    /// Unit-length vectors, `dimensions` per node
+    vectors: Vec<f32>,
+    /// `links[node][layer]`: neighbours of `node` on each layer it is on
+    links: Vec<Vec<Vec<u32>>>,
+    entry_point: Option<u32>,
+}
+
+/// A search hit: a node and its cosine similarity with the query.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct AnnHit {
+    pub node: usize,
+    pub similarity: f32,
+}
+
+/// Distance-ordered node, for the search heaps
+#[derive(Clone, Copy, PartialEq)]
+struct Scored(f32, u32);
+
+impl Eq for Scored {}
+
+impl Ord for Scored {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.0.total_cmp(&other.0).then(self.1.cmp(&other.1))
+    }
+}
+
+impl PartialOrd for Scored {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl AnnIndex {
+    /// Build the graph over `vectors` (any length; they are normalized),
+    /// one per node. Node levels come from a hash of the node number, so the
+    /// same input always gives the same graph.
+    fn build(
+        fingerprint: String,
+        model: Option<String>,
+        dimensions: usize,
+        nodes: Vec<AnnNode>,
+        vectors: Vec<Vec<f32>>,
+    ) -> Self {
+        let mut index = Self {
+            version: ANN_FORMAT_VERSION,
+            fingerprint,
+            model,
+            dimensions,
+            nodes,
+            vectors: vectors.into_iter().flat_map(normalized).collect(),
+            links: Vec::new(),
+            entry_point: None,
+        };
+        for node in 0..index.nodes.len() {
+            index.insert(node as u32);
+        }
+        index
+    }
+
+    pub fn len(&self) -> usize {
+        self.nodes.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.nodes.is_empty()
+    }
+
+    pub fn vector(&self, node: usize) -> &[f32] {
+        &self.vectors[node * self.dimensions..(node + 1) * self.dimensions]
+    }
+
+    /// The `k` nodes most similar to `query`, best first, exploring
+    /// `ef_search` candidates (at least `k`) on the bottom layer.
+    pub fn search(&self, query: &[f32], k: usize, ef_search: usize) -> Vec<AnnHit> {
+        let Some(mut entry) = self.entry_point else {
+            return Vec::new();
+        };
+        if query.len() != self.dimensions {
+            return Vec::new();
+        }
+        let query = normalized(query.to_vec());
+        for layer in (1..self.links[entry as usize].len()).rev() {
+            entry = self.closest_on_layer(&query, entry, layer);
+        }
+        self.search_layer(&query, entry, ef_search.max(k), 0)
+            .into_iter()
+            .take(k)
+            .map(|Scored(distance, node)| AnnHit {
+                node: node as usize,
+                similarity: 1.0 - distance,
+            })
+            .collect()
+    }
+
+    fn distance(&self, query: &[f32], node: u32) -> f32 {
+        let vector = self.vector(node as usize);
+        1.0 - query.iter().zip(vector).map(|(a, b)| a * b).sum::<f32>()
+    }
+
+    fn insert(&mut self, node: u32) {
+        let level = node_level(node);
+        self.links.push(vec![Vec::new(); level + 1]);
+        let Some(mut entry) = self.entry_point else {
+            self.entry_point = Some(node);
+            return;
+        };
+        let query = self.vector(node as usize).to_vec();
+        let top = self.links[entry as usize].len() - 1;
+        for layer in (level + 1..=top).rev() {
+            entry = self.closest_on_layer(&query, entry, layer);
+        }
+        for layer in (0..=level.min(top)).rev() {
+            let candidates = self.search_layer(&query, entry, EF_CONSTRUCTION, layer);
+            entry = candidates[0].1;
+            let neighbours = self.select_neighbours(candidates, max_links(layer));
+            for &neighbour in &neighbours {
+                self.links[neighbour as usize][layer].push(node);
+                self.prune(neighbour, layer);
+            }
+            self.links[node as usize][layer] = neighbours;
+        }
+        if level > top {
+            self.entry_point = Some(node);
+        }
+    }
+
+    /// Keep only the closest links of `node` on `layer` once it has too many.
+    fn prune(&mut self, node: u32, layer: usize) {
+        if self.links[node as usize][layer].len() <= max_links(layer) {
+            return;
+        }
+        let vector = self.vector(node as usize).to_vec();
+        let mut scored: Vec<Scored> = self.links[node as usize][layer]
+            .iter()
+            .map(|&other| Scored(self.distance(&vector, other), other))
+            .collect();
+        // Dropping the farthest link is much cheaper than re-running the
+        // selection heuristic, and costs little recall
+        scored.sort();
+        scored.truncate(max_links(layer));
+        self.links[node as usize][layer] = scored.into_iter().map(|s| s.1).collect();
+    }
+
+    /// Up to `max` of `candidates` (closest first) to link to, preferring
+    /// ones not already closer to a chosen neighbour than to the node itself,
+    /// so links reach out of tight clusters instead of all pointing into one.
+    fn select_neighbours(&self, candidates: Vec<Scored>, max: usize) -> Vec<u32> {
+        let mut chosen: Vec<u32> = Vec::with_capacity(max);
+        let mut skipped = Vec::new();
+        for Scored(distance, candidate) in candidates {
+            if chosen.len() == max {
+                break;
+            }
+            let vector = self.vector(candidate as usize);
+            if chosen
+                .iter()
+                .all(|&other| self.distance(vector, other) > distance)
+            {
+                chosen.push(candidate);
+            } else {
+                skipped.push(candidate);
+            }
+        }
+        let missing = max - chosen.len();
+        chosen.extend(skipped.into_iter().take(missing));
+        chosen
+    }
+
+    /// Greedy walk to the node closest to `query` on an upper layer.
+    fn closest_on_layer(&self, query: &[f32], mut entry: u32, layer: usize) -> u32 {
+        let mut best = self.distance(query, entry);
+        loop {
+            let mut improved = false;
+            for &neighbour in &self.links[entry as usize][layer] {
+                let distance = self.distance(query, neighbour);
+                if distance < best {
+                    best = distance;
+                    entry = neighbour;
+                    improved = true;
+                }
+            }
+            if !improved {
+                return entry;
+            }
+        }
+    }
+
+    /// Best-first search from `entry` keeping the `ef` closest nodes found,
+    /// returned closest first.
+    fn search_layer(&self, query: &[f32], entry: u32, ef: usize, layer: usize) -> Vec<Scored> {
+        let start = Scored(self.distance(query, entry), entry);
+        let mut visited = HashSet::from([entry]);
+        let mut candidates = BinaryHeap::from([Reverse(start)]);
+        let mut found = BinaryHeap::from([start]);
+        while let Some(Reverse(current)) = candidates.pop() {
+            if found.len() >= ef && found.peek().is_some_and(|worst| current.0 > worst.0) {
+                break;
+            }
+            for &neighbour in &self.links[current.1 as usize][layer] {
+                if !visited.insert(neighbour) {
+                    continue;
+                }
+                let scored = Scored(self.distance(query, neighbour), neighbour);
+                if found.len() < ef || found.peek().is_some_and(|worst| scored.0 < worst.0) {
+                    candidates.push(Reverse(scored));
+                    found.push(scored);
+                    if found.len() > ef {
+                        found.pop();
+                    }
+                }
+            }
+        }
+        found.into_sorted_vec()
+    }
+}
+
+fn max_links(layer: usize) -> usize {
+    if layer == 0 { 2 * M } else { M }
+}
+
+/// Layer count minus one for `node`: geometric with ratio 1/M, from a
+/// splitmix64 hash of the node number.
+fn node_level(node: u32) -> usize {
+    let mut x = u64::from(node).wrapping_add(0x9E37_79B9_7F4A_7C15);
+    x = (x ^ (x >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+    x = (x ^ (x >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+    x ^= x >> 31;
+    let uniform = ((x >> 11) as f64 + 1.0) / (1u64 << 53) as f64;
+    (-uniform.ln() / (M as f64).ln()) as usize
+}
+
+fn normalized(mut vector: Vec<f32>) -> Vec<f32> {
+    let norm = vector.iter().map(|x| x * x).sum::<f32>().sqrt();
+    if norm > 0.0 {
+        vector.iter_mut().for_each(|x| *x /= norm);
+    }
+    vector
+}
+
+/// Identifies the indexed content an ANN index was built from: any indexing
+/// pass that changes a file, the model or how files are chunked changes it.
+fn fingerprint(manifest: &IndexManifest) -> String {
+    let mut hasher = blake3::Hasher::new();
+    let settings = (
+        manifest.created,
+        &manifest.embedding_model,
+        manifest.embedding_dimensions,
+        manifest.chunk_overlap_lines,
+        manifest.chunk_strategy,
+        manifest.quantization,
+        manifest.reduced_dimensions,
+    );
+    hasher.update(&serde_json::to_vec(&settings).unwrap_or_default());
+    let mut files: Vec<_> = manifest.files.iter().collect();
+    files.sort_by(|a, b| a.0.cmp(b.0));
+    for (path, metadata) in files {
+        hasher.update(path.to_string_lossy().as_bytes());
+        hasher.update(metadata.hash.as_bytes());
+        hasher.update(&metadata.last_modified.to_le_bytes());
+    }
+    hasher.finalize().to_hex().to_string()
+}
+
+/// Rebuild the ANN index under `path` if the manifest enables it and the one
+/// on disk (if any) was built from different content. Returns the number of
+/// vectors indexed when it was rebuilt. The caller holds the write lock.
+pub(crate) fn refresh_ann_index(path: &Path) -> Result<Option<usize>> {
+    let index_dir = ck_core::index_dir(path);
+    let manifest_path = index_dir.join("manifest.json");
+    if !manifest_path.exists() {
+        return Ok(None);
+    }
+    let manifest = load_or_create_manifest(&manifest_path)?;
+    let ann_path = index_dir.join(ANN_FILE);
+    if !manifest.ann_index {
+        if ann_path.exists() {
+            fs::remove_file(&ann_path)?;
+        }
+        return Ok(None);
+    }
+    let fingerprint = fingerprint(&manifest);
+    if read_ann_index(&ann_path).is_some_and(|ann| ann.fingerprint == fingerprint) {
+        return Ok(None);
+    }
+
+    let Some(dimensions) = manifest.vector_dimensions() else {
+        // Nothing embedded yet
+        return Ok(None);
+    };
+    let mut keys: Vec<&PathBuf> = manifest.files.keys().collect();
+    keys.sort();
+    let mut nodes = Vec::new();
+    let mut vectors = Vec::new();
+    for key in keys {
+        let standard_path = path_utils::from_manifest_path(key);
+        let sidecar_path =
+            path_utils::get_sidecar_path_for_standard_path(&index_dir, &standard_path);
+        let Ok(entry) = load_index_entry(&sidecar_path) else {
+            continue;
+        };
+        // Vectors from another model can't share a graph with the query's; a
+        // mixed index is left to the exhaustive search and its mismatch checks
+        if entry
+            .embedding_model
+            .as_ref()
+            .is_some_and(|model| Some(model) != manifest.embedding_model.as_ref())
+        {
+            if ann_path.exists() {
+                fs::remove_file(&ann_path)?;
+            }
+            return Ok(None);
+        }
+        for chunk in entry.chunks {
+            if let Some(embedding) = chunk.embedding
+                && embedding.len() == dimensions
+            {
+                nodes.push(AnnNode {
+                    file: standard_path.clone(),
+                    span: chunk.span,
+                });
+                vectors.push(embedding);
+            }
+        }
+    }
+
+    let ann = AnnIndex::build(
+        fingerprint,
+        manifest.embedding_model.clone(),
+        dimensions,
+        nodes,
+        vectors,
+    );
+    atomic_write(&ann_path, &bincode::serialize(&ann)?)?;
+    Ok(Some(ann.len()))
+}
+
+/// The ANN index under `index_root`, if there is one built from the index's
+/// current content.
+pub fn load_ann_index(index_root: &Path) -> Option<AnnIndex> {
+    let index_dir = ck_core::index_dir(index_root);
+    let manifest: IndexManifest =
+        serde_json::from_slice(&fs::read(index_dir.join("manifest.json")).ok()?).ok()?;
+    if !manifest.ann_index {
+        return None;
+    }
+    read_ann_index(&index_dir.join(ANN_FILE))
+        .filter(|ann| ann.fingerprint == fingerprint(&manifest))
+}
+
+fn read_ann_index(path: &Path) -> Option<AnnIndex> {
+    let ann: AnnIndex = bincode::deserialize(&fs::read(path).ok()?).ok()?;
+    (ann.version == ANN_FORMAT_VERSION).then_some(ann)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Clustered vectors, like embeddings of related code: 25 topics of 40
+    fn corpus() -> Vec<Vec<f32>> {
+        let mut state = 7u64;
+        let mut next = move || {
+            state = state
+                .wrapping_mul(6364136223846793005)
+                .wrapping_add(1442695040888963407);
+            (state >> 40) as f32 / (1u64 << 23) as f32 - 1.0
+        };
+        let topics: Vec<Vec<f32>> = (0..25).map(|_| (0..64).map(|_| next()).collect()).collect();
+        topics
+            .iter()
+            .flat_map(|topic| {
+                (0..40)
+                    .map(|_| topic.iter().map(|t| t + next()).collect())
+                    .collect::<Vec<_>>()
+            })
+            .collect()
+    }
+
+    fn exact_top(vectors: &[Vec<f32>], query: &[f32], k: usize) -> HashSet<usize> {
+        let query = normalized(query.to_vec());
+        let mut scored: Vec<(f32, usize)> = vectors
+            .iter()
+            .enumerate()
+            .map(|(i, v)| {
+                let v = normalized(v.clone());
+                (query.iter().zip(&v).map(|(a, b)| a * b).sum(), i)
+            })
+            .collect();
+        scored.sort_by(|a, b| b.0.total_cmp(&a.0));
+        scored.into_iter().take(k).map(|(_, i)| i).collect()
+    }
+
+    #[test]
+    fn test_recall_grows_with_ef_search() {
+        let vectors = corpus();
+        let nodes = (0..vectors.len())
+            .map(|i| AnnNode {
+                file: PathBuf::from(format!("f{i}.rs")),
+                span: Span::new(0, 1, 1, 1).unwrap(),
+            })
+            .collect();
+        let ann = AnnIndex::build(String::new(), None, 64, nodes, vectors.clone());
+
+        let recall = |ef: usize| {
+            let mut found = 0;
+            // Queries are corpus vectors, each its own exact best match
+            for query in vectors.iter().step_by(20) {
+                let exact = exact_top(&vectors, query, 10);
+                let hits = ann.search(query, 10, ef);
+                assert!(hits.windows(2).all(|w| w[0].similarity >= w[1].similarity));
+                found += hits.iter().filter(|hit| exact.contains(&hit.node)).count();
+            }
+            found as f32 / (vectors.len() / 20 * 10) as f32
+        };
+        // Measured at 0.90 and 0.98
+        let low = recall(10);
+        let high = recall(200);
+        assert!(high >= 0.95, "recall@10 with ef_search 200: {high}");
+        assert!(high > low, "{high} <= {low}");
+
+        assert!(ann.search(&[1.0; 3], 10, 64).is_empty());
+    }
+}
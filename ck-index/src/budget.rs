@@ -0,0 +1,244 @@
+//! Per-file time budget for indexing (`--file-timeout`).
+//!
+//! A minified one-line bundle or a huge generated file can keep the chunker
+//! or the embedder busy for minutes. Each file gets a deadline when its work
+//! starts; the indexing code is synchronous, so the deadline is checked
+//! between stages and between chunks rather than enforced by an async
+//! timeout. Chunking is a single uninterruptible call, so it runs on a helper
+//! thread that is waited on only until the deadline; one that overruns is
+//! left to finish on its own while indexing moves on. Helpers are kept and
+//! reused between files, and only a few are left behind at once: past that, chunking runs on the calling thread and the
+//! deadline is only checked once it returns. A file that runs out of time is
+//! skipped and reported in [`UpdateStats::timed_out_files`].
+//!
+//! The same checkpoints honour a caller's cancellation token, so a cancelled
+//! run stops mid-file without writing that file's sidecar.
+//!
+//! [`UpdateStats::timed_out_files`]: crate::UpdateStats::timed_out_files
+
+use anyhow::Result;
+use ck_core::{CancellationToken, CkError};
+use std::fmt;
+use std::sync::atomic::{AtomicU8, AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex, PoisonError, mpsc};
+use std::time::{Duration, Instant};
+
+/// Budget used when the caller doesn't set one.
+pub const DEFAULT_FILE_TIMEOUT: Duration = Duration::from_secs(120);
+
+/// Helpers still busy with a file that ran out of time before new work stops
+/// being handed to helper threads.
+const MAX_OVERRUNNING_HELPERS: usize = 4;
+
+/// Idle helpers kept around for the next file; more are let go.
+const MAX_IDLE_HELPERS: usize = 16;
+
+type Job = Box<dyn FnOnce() + Send>;
+
+/// Helper threads waiting for work.
+static IDLE_HELPERS: Mutex<Vec<mpsc::Sender<Job>>> = Mutex::new(Vec::new());
+
+/// Helpers still running work whose deadline passed.
+static OVERRUNNING_HELPERS: AtomicUsize = AtomicUsize::new(0);
+
+/// The state of one piece of work handed to a helper.
+const RUNNING: u8 = 0;
+const FINISHED: u8 = 1;
+const ABANDONED: u8 = 2;
+
+/// A helper thread from the idle pool, or a new one when none is free. A
+/// helper goes back to the pool after each job and exits when the pool is
+/// full.
+fn helper() -> mpsc::Sender<Job> {
+    if let Some(helper) = IDLE_HELPERS
+        .lock()
+        .unwrap_or_else(PoisonError::into_inner)
+        .pop()
+    {
+        return helper;
+    }
+    let (tx, rx) = mpsc::channel::<Job>();
+    let this = tx.clone();
+    std::thread::spawn(move || {
+        while let Ok(job) = rx.recv() {
+            job();
+            let mut idle = IDLE_HELPERS.lock().unwrap_or_else(PoisonError::into_inner);
+            if idle.len() >= MAX_IDLE_HELPERS {
+                break;
+            }
+            idle.push(this.clone());
+        }
+    });
+    tx
+}
+
+/// Number of helper threads still running work for a file that already
+/// timed out.
+fn overrunning_helpers() -> usize {
+    OVERRUNNING_HELPERS.load(Ordering::SeqCst)
+}
+
+/// The error a file that ran out of time fails with.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FileTimedOut {
+    pub timeout: Duration,
+}
+
+impl fmt::Display for FileTimedOut {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "took longer than {}s to index, skipping",
+            self.timeout.as_secs_f64()
+        )
+    }
+}
+
+impl std::error::Error for FileTimedOut {}
+
+/// True when `err` is a [`FileTimedOut`].
+pub fn is_timeout(err: &anyhow::Error) -> bool {
+    err.downcast_ref::<FileTimedOut>().is_some()
+}
+
+/// The deadline for one file, and the token that cancels the run it's part
+/// of; `None` means no limit.
+#[derive(Debug, Clone)]
+pub(crate) struct Deadline {
+    at: Option<(Instant, Duration)>,
+    cancel: Option<CancellationToken>,
+}
+
+impl Deadline {
+    pub(crate) fn start(timeout: Option<Duration>, cancel: Option<&CancellationToken>) -> Self {
+        Self {
+            at: timeout.map(|timeout| (Instant::now() + timeout, timeout)),
+            cancel: cancel.cloned(),
+        }
+    }
+
+    pub(crate) fn check(&self) -> Result<()> {
+        if self
+            .cancel
+            .as_ref()
+            .is_some_and(CancellationToken::is_cancelled)
+        {
+            return Err(CkError::Cancelled.into());
+        }
+        match self.at {
+            Some((at, timeout)) if Instant::now() >= at => Err(FileTimedOut { timeout }.into()),
+            _ => Ok(()),
+        }
+    }
+
+    /// Run `work` on a helper thread and wait for it until the deadline.
+    /// Without a deadline, or when too many helpers are still overrunning,
+    /// it runs on the calling thread.
+    pub(crate) fn run<T: Send + 'static>(
+        &self,
+        work: impl FnOnce() -> Result<T> + Send + 'static,
+    ) -> Result<T> {
+        let Some((at, timeout)) = self.at else {
+            return work();
+        };
+        if overrunning_helpers() >= MAX_OVERRUNNING_HELPERS {
+            let result = work();
+            self.check()?;
+            return result;
+        }
+
+        let (tx, rx) = mpsc::sync_channel(1);
+        let state = Arc::new(AtomicU8::new(RUNNING));
+        let job_state = Arc::clone(&state);
+        let job: Job = Box::new(move || {
+            let result = std::panic::catch_unwind(std::panic::AssertUnwindSafe(work))
+                .unwrap_or_else(|_| Err(anyhow::anyhow!("Chunking thread panicked")));
+            if job_state
+                .compare_exchange(RUNNING, FINISHED, Ordering::SeqCst, Ordering::SeqCst)
+                .is_ok()
+            {
+                let _ = tx.send(result);
+            } else {
+                OVERRUNNING_HELPERS.fetch_sub(1, Ordering::SeqCst);
+            }
+        });
+        // A pooled helper is always waiting on its channel
+        let _ = helper().send(job);
+
+        match rx.recv_timeout(at.saturating_duration_since(Instant::now())) {
+            Ok(result) => result,
+            Err(mpsc::RecvTimeoutError::Timeout) => {
+                OVERRUNNING_HELPERS.fetch_add(1, Ordering::SeqCst);
+                if state
+                    .compare_exchange(RUNNING, ABANDONED, Ordering::SeqCst, Ordering::SeqCst)
+                    .is_ok()
+                {
+                    return Err(FileTimedOut { timeout }.into());
+                }
+                // The work finished as the deadline passed; its result is on
+                // the way
+                OVERRUNNING_HELPERS.fetch_sub(1, Ordering::SeqCst);
+                rx.recv()
+                    .unwrap_or_else(|_| Err(anyhow::anyhow!("Chunking thread panicked")))
+            }
+            Err(mpsc::RecvTimeoutError::Disconnected) => {
+                Err(anyhow::anyhow!("Chunking thread panicked"))
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_deadline_check_and_run() {
+        let unlimited = Deadline::start(None, None);
+        assert!(unlimited.check().is_ok());
+        assert_eq!(unlimited.run(|| Ok(7)).unwrap(), 7);
+
+        let short = Deadline::start(Some(Duration::from_millis(50)), None);
+        assert_eq!(short.run(|| Ok("quick")).unwrap(), "quick");
+        let err = short
+            .run(|| {
+                std::thread::sleep(Duration::from_millis(500));
+                Ok(())
+            })
+            .unwrap_err();
+        assert!(is_timeout(&err));
+        assert!(is_timeout(&short.check().unwrap_err()));
+
+        let token = CancellationToken::new();
+        let cancellable = Deadline::start(None, Some(&token));
+        assert!(cancellable.check().is_ok());
+        token.cancel();
+        assert!(ck_core::is_cancelled(&cancellable.check().unwrap_err()));
+    }
+
+    #[test]
+    fn test_overrunning_helpers_are_released() {
+        let short = Deadline::start(Some(Duration::from_millis(20)), None);
+        let err = short
+            .run(|| {
+                std::thread::sleep(Duration::from_millis(200));
+                Ok(())
+            })
+            .unwrap_err();
+        assert!(is_timeout(&err));
+        assert!(overrunning_helpers() >= 1);
+
+        let waited = Instant::now();
+        while overrunning_helpers() > 0 {
+            assert!(waited.elapsed() < Duration::from_secs(5));
+            std::thread::sleep(Duration::from_millis(10));
+        }
+
+        let roomy = Deadline::start(Some(Duration::from_secs(5)), None);
+        let err = roomy
+            .run(|| -> Result<()> { panic!("chunker bug") })
+            .unwrap_err();
+        assert!(err.to_string().contains("panicked"));
+        assert_eq!(roomy.run(|| Ok(3)).unwrap(), 3);
+    }
+}
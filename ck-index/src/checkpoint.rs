@@ -0,0 +1,117 @@
+//! Checkpoint for resuming an interrupted indexing pass.
+//!
+//! While `smart_update_index` runs, every file whose sidecar has been written
+//! is appended to `.ck/index_checkpoint.jsonl` as a (manifest path, content
+//! hash) pair. A clean finish deletes the file, so its presence means the last
+//! pass was cut short. `--resume` reads it back and skips files whose content
+//! still hashes the same; a file edited in between is indexed again.
+
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fs::{self, File, OpenOptions};
+use std::io::{BufRead, BufReader, Write};
+use std::path::{Path, PathBuf};
+
+/// Checkpoint file name inside the index directory.
+pub const CHECKPOINT_FILE: &str = "index_checkpoint.jsonl";
+
+#[derive(Debug, Serialize, Deserialize)]
+struct CheckpointRecord {
+    path: PathBuf,
+    hash: String,
+}
+
+/// Whether an interrupted indexing pass left a checkpoint for `path`.
+pub fn has_checkpoint(path: &Path) -> bool {
+    ck_core::index_dir(path).join(CHECKPOINT_FILE).exists()
+}
+
+/// Files completed by the interrupted pass, keyed by manifest path. A line
+/// cut off mid-write by the interruption is ignored.
+pub(crate) fn load_checkpoint(index_dir: &Path) -> HashMap<PathBuf, String> {
+    let Ok(file) = File::open(index_dir.join(CHECKPOINT_FILE)) else {
+        return HashMap::new();
+    };
+    BufReader::new(file)
+        .lines()
+        .map_while(std::result::Result::ok)
+        .filter_map(|line| serde_json::from_str::<CheckpointRecord>(&line).ok())
+        .map(|record| (record.path, record.hash))
+        .collect()
+}
+
+pub(crate) fn remove_checkpoint(index_dir: &Path) -> Result<()> {
+    match fs::remove_file(index_dir.join(CHECKPOINT_FILE)) {
+        Ok(()) => Ok(()),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(()),
+        Err(e) => Err(e.into()),
+    }
+}
+
+/// Appends completed files to the checkpoint as the pass progresses.
+pub(crate) struct CheckpointWriter {
+    file: File,
+}
+
+impl CheckpointWriter {
+    /// Open the checkpoint, keeping earlier records when resuming and
+    /// starting a new one otherwise.
+    pub(crate) fn open(index_dir: &Path, resume: bool) -> Result<Self> {
+        let file = OpenOptions::new()
+            .create(true)
+            .append(resume)
+            .write(true)
+            .truncate(!resume)
+            .open(index_dir.join(CHECKPOINT_FILE))?;
+        Ok(Self { file })
+    }
+
+    pub(crate) fn record(&mut self, path: &Path, hash: &str) -> Result<()> {
+        let record = CheckpointRecord {
+            path: path.to_path_buf(),
+            hash: hash.to_string(),
+        };
+        writeln!(self.file, "{}", serde_json::to_string(&record)?)?;
+        self.file.flush()?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_checkpoint_round_trip_and_resume() {
+        let temp_dir = TempDir::new().unwrap();
+        let index_dir = temp_dir.path();
+
+        let mut writer = CheckpointWriter::open(index_dir, false).unwrap();
+        writer.record(Path::new("./a.rs"), "hash-a").unwrap();
+        drop(writer);
+
+        // Resuming appends; a torn trailing line is skipped
+        let mut writer = CheckpointWriter::open(index_dir, true).unwrap();
+        writer.record(Path::new("./b.rs"), "hash-b").unwrap();
+        drop(writer);
+        let mut file = OpenOptions::new()
+            .append(true)
+            .open(index_dir.join(CHECKPOINT_FILE))
+            .unwrap();
+        write!(file, "{{\"path\":\"./c.r").unwrap();
+
+        let completed = load_checkpoint(index_dir);
+        assert_eq!(completed.len(), 2);
+        assert_eq!(completed[Path::new("./b.rs")], "hash-b");
+
+        // A fresh pass starts over
+        drop(CheckpointWriter::open(index_dir, false).unwrap());
+        assert!(load_checkpoint(index_dir).is_empty());
+
+        remove_checkpoint(index_dir).unwrap();
+        remove_checkpoint(index_dir).unwrap();
+        assert!(!index_dir.join(CHECKPOINT_FILE).exists());
+    }
+}
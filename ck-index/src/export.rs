@@ -0,0 +1,642 @@
+//! Portable JSONL dump of an index, and the matching import.
+//!
+//! Every indexed chunk becomes one self-contained line carrying its file's
+//! metadata, so a dump can be filtered or concatenated with ordinary line
+//! tools and still import cleanly.
+
+use super::{
+    ChunkEntry, IndexEntry, acquire_index_write_lock, language_key, load_index_entry,
+    load_or_create_manifest, path_utils, refresh_ann_index, save_index_entry, save_manifest,
+};
+use anyhow::Result;
+use ck_core::{FileMetadata, Span};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fs;
+use std::io::{BufRead, Write};
+use std::path::{Component, Path, PathBuf};
+use std::time::SystemTime;
+
+/// One indexed chunk in an export dump.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ExportRecord {
+    /// Path relative to the index root
+    pub path: PathBuf,
+    pub file_hash: String,
+    pub file_size: u64,
+    pub last_modified: u64,
+    pub span: Span,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub chunk_type: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub breadcrumb: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub ancestry: Option<Vec<String>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub byte_length: Option<usize>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub estimated_tokens: Option<usize>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub leading_trivia: Option<Vec<String>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub trailing_trivia: Option<Vec<String>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub chunk_hash: Option<String>,
+    /// Embedding model recorded for this file, if any
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub model: Option<String>,
+    /// The `[language_models]` language whose model embedded this file;
+    /// absent when the index's own model did
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub language: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub dimensions: Option<usize>,
+    /// Only present when exported with vectors
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub embedding: Option<Vec<f32>>,
+}
+
+#[derive(Debug, Default, Clone)]
+pub struct ExportStats {
+    pub files_exported: usize,
+    pub chunks_exported: usize,
+    /// Manifest entries whose sidecar was missing or unreadable
+    pub files_skipped: usize,
+}
+
+#[derive(Debug, Default, Clone)]
+pub struct ImportStats {
+    pub files_imported: usize,
+    pub chunks_imported: usize,
+    /// Imported files that don't exist under the target root
+    pub files_missing_locally: usize,
+}
+
+/// Stream every chunk of the index at `path` to `out` as JSONL, reading the
+/// existing sidecars without re-chunking or re-embedding anything.
+///
+/// Vectors reduced with `--reduce-dim` are meaningless without the index's
+/// projection, which a dump doesn't carry, so `with_vectors` is refused for
+/// such an index.
+pub fn export_index<W: Write>(path: &Path, with_vectors: bool, out: &mut W) -> Result<ExportStats> {
+    let index_dir = ck_core::index_dir(path);
+    let manifest_path = index_dir.join("manifest.json");
+    if !manifest_path.exists() {
+        return Err(anyhow::anyhow!(
+            "No index found at {}. Run 'ck --index' first.",
+            path.display()
+        ));
+    }
+    let manifest = load_or_create_manifest(&manifest_path)?;
+    if with_vectors && let Some(reduced) = manifest.reduced_dimensions {
+        return Err(anyhow::anyhow!(
+            "The index at {} stores vectors reduced to {} dims with --reduce-dim, which can't be exported. Export without --with-vectors, or rebuild the index without --reduce-dim first.",
+            path.display(),
+            reduced
+        ));
+    }
+
+    let mut files: Vec<&PathBuf> = manifest.files.keys().collect();
+    files.sort();
+
+    let mut stats = ExportStats::default();
+    for manifest_key in files {
+        let standard_path = path_utils::from_manifest_path(manifest_key);
+        let sidecar_path =
+            path_utils::get_sidecar_path_for_standard_path(&index_dir, &standard_path);
+        let entry = match load_index_entry(&sidecar_path) {
+            Ok(entry) => entry,
+            Err(e) => {
+                tracing::warn!("Skipping {:?} in export: {}", standard_path, e);
+                stats.files_skipped += 1;
+                continue;
+            }
+        };
+
+        let model = entry
+            .embedding_model
+            .clone()
+            .or_else(|| manifest.embedding_model.clone());
+        let language = language_key(&standard_path)
+            .filter(|language| manifest.language_models.contains_key(language));
+        for chunk in entry.chunks {
+            let dimensions = chunk
+                .embedding
+                .as_ref()
+                .map(Vec::len)
+                .or(entry.embedding_dimensions);
+            let record = ExportRecord {
+                path: standard_path.clone(),
+                file_hash: entry.metadata.hash.clone(),
+                file_size: entry.metadata.size,
+                last_modified: entry.metadata.last_modified,
+                span: chunk.span,
+                chunk_type: chunk.chunk_type,
+                breadcrumb: chunk.breadcrumb,
+                ancestry: chunk.ancestry,
+                byte_length: chunk.byte_length,
+                estimated_tokens: chunk.estimated_tokens,
+                leading_trivia: chunk.leading_trivia,
+                trailing_trivia: chunk.trailing_trivia,
+                chunk_hash: chunk.chunk_hash,
+                model: dimensions.and(model.clone()),
+                language: dimensions.and(language.clone()),
+                dimensions,
+                embedding: if with_vectors { chunk.embedding } else { None },
+            };
+            serde_json::to_writer(&mut *out, &record)?;
+            out.write_all(b"\n")?;
+            stats.chunks_exported += 1;
+        }
+        stats.files_exported += 1;
+    }
+
+    out.flush()?;
+    Ok(stats)
+}
+
+/// Rebuild sidecars and manifest entries under `path` from an export dump.
+///
+/// The whole dump is validated before anything is written: every path must
+/// be relative and stay inside the index root, every vector must match its
+/// recorded dimension, vectors outside `[language_models]` must come from
+/// one model and those of each mapped language from one model, and those
+/// models must agree with any index already present at `path`. Vectors
+/// can't be imported into an index reduced with `--reduce-dim`.
+pub fn import_index<R: BufRead>(path: &Path, input: R) -> Result<ImportStats> {
+    let mut files: BTreeMap<PathBuf, Vec<ExportRecord>> = BTreeMap::new();
+    // The model and dimensions behind the vectors: the index's own model,
+    // and the model of each `[language_models]` language
+    let mut dump_model: Option<(Option<String>, usize)> = None;
+    let mut language_models: BTreeMap<String, Option<(Option<String>, usize)>> = BTreeMap::new();
+
+    for (line_no, line) in input.lines().enumerate() {
+        let line = line?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        let record: ExportRecord = serde_json::from_str(&line)
+            .map_err(|e| anyhow::anyhow!("Invalid export record on line {}: {}", line_no + 1, e))?;
+        // Sidecars are written at the record's path under the index
+        // directory, so it must not climb out of it
+        if !record
+            .path
+            .components()
+            .all(|component| matches!(component, Component::Normal(_)))
+        {
+            return Err(anyhow::anyhow!(
+                "Line {}: {} is not a path relative to the index root",
+                line_no + 1,
+                record.path.display()
+            ));
+        }
+        if let Some(language) = &record.language
+            && language_key(&record.path).as_ref() != Some(language)
+        {
+            return Err(anyhow::anyhow!(
+                "Line {}: {} is not a {} file",
+                line_no + 1,
+                record.path.display(),
+                language
+            ));
+        }
+
+        if let Some(embedding) = &record.embedding {
+            if record
+                .dimensions
+                .is_some_and(|dims| dims != embedding.len())
+            {
+                return Err(anyhow::anyhow!(
+                    "Line {}: embedding for {} has {} values but the record says {} dims",
+                    line_no + 1,
+                    record.path.display(),
+                    embedding.len(),
+                    record.dimensions.unwrap_or_default()
+                ));
+            }
+            let this_model = (record.model.clone(), embedding.len());
+            let (expected, earlier) = match &record.language {
+                Some(language) => (
+                    language_models.entry(language.clone()).or_default(),
+                    format!("earlier {language} records use"),
+                ),
+                None => (
+                    &mut dump_model,
+                    "earlier records outside [language_models] use".to_string(),
+                ),
+            };
+            match expected {
+                None => *expected = Some(this_model),
+                Some(existing) if *existing != this_model => {
+                    return Err(anyhow::anyhow!(
+                        "Line {}: {} was embedded with '{}' ({} dims) but {} '{}' ({} dims). Each model in a dump must embed every file it covers.",
+                        line_no + 1,
+                        record.path.display(),
+                        this_model.0.as_deref().unwrap_or("unknown"),
+                        this_model.1,
+                        earlier,
+                        existing.0.as_deref().unwrap_or("unknown"),
+                        existing.1
+                    ));
+                }
+                Some(_) => {}
+            }
+        }
+
+        if let Some(first) = files.get(&record.path).and_then(|records| records.first()) {
+            if first.file_hash != record.file_hash {
+                return Err(anyhow::anyhow!(
+                    "Line {}: conflicting file hashes for {}",
+                    line_no + 1,
+                    record.path.display()
+                ));
+            }
+            if first.language != record.language {
+                return Err(anyhow::anyhow!(
+                    "Line {}: conflicting [language_models] languages for {}",
+                    line_no + 1,
+                    record.path.display()
+                ));
+            }
+        }
+        files.entry(record.path.clone()).or_default().push(record);
+    }
+
+    let index_dir = ck_core::index_dir(path);
+    let _lock = acquire_index_write_lock(&index_dir)?;
+    ck_core::check_index_root_marker(path)?;
+    fs::create_dir_all(&index_dir)?;
+    ck_core::write_index_root_marker(path)?;
+
+    let manifest_path = index_dir.join("manifest.json");
+    let mut manifest = load_or_create_manifest(&manifest_path)?;
+
+    if let Some(reduced) = manifest.reduced_dimensions
+        && (dump_model.is_some() || !language_models.is_empty())
+    {
+        return Err(anyhow::anyhow!(
+            "The index at {} stores vectors reduced to {} dims with --reduce-dim, so a dump with vectors can't be imported into it. Run 'ck --clean' first to replace it.",
+            path.display(),
+            reduced
+        ));
+    }
+    for (language, model) in &language_models {
+        if let (Some(existing), Some((Some(model), _))) =
+            (manifest.language_models.get(language), model)
+            && existing != model
+        {
+            return Err(anyhow::anyhow!(
+                "Dump embedded {} files with '{}' but the index at {} uses '{}'. Run 'ck --clean' first to replace it.",
+                language,
+                model,
+                path.display(),
+                existing
+            ));
+        }
+    }
+    if let Some((model, dims)) = &dump_model {
+        if let (Some(existing), Some(model)) = (&manifest.embedding_model, model)
+            && existing != model
+        {
+            return Err(anyhow::anyhow!(
+                "Dump was embedded with '{}' but the index at {} uses '{}'. Run 'ck --clean' first to replace it.",
+                model,
+                path.display(),
+                existing
+            ));
+        }
+        if let Some(existing_dims) = manifest.embedding_dimensions
+            && existing_dims != *dims
+        {
+            return Err(anyhow::anyhow!(
+                "Dump has {}-dimensional vectors but the index at {} uses {} dims. Run 'ck --clean' first to replace it.",
+                dims,
+                path.display(),
+                existing_dims
+            ));
+        }
+        if model.is_some() {
+            manifest.embedding_model = model.clone();
+        }
+        manifest.embedding_dimensions = Some(*dims);
+    }
+    for (language, model) in &language_models {
+        if let Some((Some(model), _)) = model {
+            manifest
+                .language_models
+                .insert(language.clone(), model.clone());
+        }
+    }
+
+    let mut stats = ImportStats::default();
+    for (standard_path, records) in files {
+        let first = &records[0];
+        let metadata = FileMetadata {
+            path: path_utils::to_manifest_path(&standard_path),
+            hash: first.file_hash.clone(),
+            last_modified: first.last_modified,
+            size: first.file_size,
+        };
+        let has_vectors = records.iter().any(|r| r.embedding.is_some());
+        let file_model = match &first.language {
+            Some(language) => language_models.get(language).cloned().flatten(),
+            None => dump_model.clone(),
+        };
+        let (embedding_model, embedding_dimensions) = match (file_model, has_vectors) {
+            (Some((model, dims)), true) => (model, Some(dims)),
+            _ => (None, None),
+        };
+
+        stats.chunks_imported += records.len();
+        let chunks = records
+            .into_iter()
+            .map(|record| ChunkEntry {
+                span: record.span,
+                embedding: record.embedding,
+                chunk_type: record.chunk_type,
+                breadcrumb: record.breadcrumb,
+                ancestry: record.ancestry,
+                byte_length: record.byte_length,
+                estimated_tokens: record.estimated_tokens,
+                leading_trivia: record.leading_trivia,
+                trailing_trivia: record.trailing_trivia,
+                chunk_hash: record.chunk_hash,
+                quantized: None,
+            })
+            .collect();
+
+        if !path.join(&standard_path).exists() {
+            stats.files_missing_locally += 1;
+        }
+
+        let sidecar_path =
+            path_utils::get_sidecar_path_for_standard_path(&index_dir, &standard_path);
+        let entry = IndexEntry {
+            metadata: metadata.clone(),
+            chunks,
+            embedding_model,
+            embedding_dimensions,
+            generated: None,
+        };
+        save_index_entry(&sidecar_path, &entry, manifest.sidecar_encoding())?;
+        manifest.files.insert(metadata.path.clone(), metadata);
+        stats.files_imported += 1;
+    }
+
+    manifest.updated = SystemTime::now()
+        .duration_since(SystemTime::UNIX_EPOCH)
+        .unwrap()
+        .as_secs();
+    save_manifest(&manifest_path, &manifest)?;
+    refresh_ann_index(path)?;
+
+    Ok(stats)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serial_test::serial;
+    use tempfile::TempDir;
+
+    async fn build_index(root: &Path) {
+        fs::write(root.join("a.rs"), "fn alpha() {}\n\nfn beta() {}\n").unwrap();
+        fs::write(root.join("notes.txt"), "some notes").unwrap();
+        let options = ck_core::FileCollectionOptions::default();
+        super::super::smart_update_index_with_detailed_progress(
+            root, false, None, None, true, &options, None,
+        )
+        .await
+        .unwrap();
+    }
+
+    #[tokio::test]
+    #[serial]
+    async fn test_export_import_round_trip() {
+        unsafe { std::env::remove_var(ck_core::INDEX_DIR_ENV) };
+        let source = TempDir::new().unwrap();
+        build_index(source.path()).await;
+
+        let mut dump = Vec::new();
+        let exported = export_index(source.path(), true, &mut dump).unwrap();
+        assert_eq!(exported.files_exported, 2);
+        assert!(exported.chunks_exported >= 2);
+
+        let first: ExportRecord =
+            serde_json::from_str(std::str::from_utf8(&dump).unwrap().lines().next().unwrap())
+                .unwrap();
+        assert!(first.embedding.is_some());
+        assert_eq!(first.dimensions, Some(384));
+
+        let target = TempDir::new().unwrap();
+        let imported = import_index(target.path(), dump.as_slice()).unwrap();
+        assert_eq!(imported.files_imported, 2);
+        assert_eq!(imported.chunks_imported, exported.chunks_exported);
+        assert_eq!(imported.files_missing_locally, 2);
+
+        let original = load_index_entry(&ck_core::get_sidecar_path(
+            source.path(),
+            &source.path().join("a.rs"),
+        ))
+        .unwrap();
+        let restored = load_index_entry(&ck_core::get_sidecar_path(
+            target.path(),
+            &target.path().join("a.rs"),
+        ))
+        .unwrap();
+        assert_eq!(restored.metadata.hash, original.metadata.hash);
+        assert_eq!(restored.chunks.len(), original.chunks.len());
+        assert_eq!(restored.chunks[0].embedding, original.chunks[0].embedding);
+        assert_eq!(restored.embedding_model, original.embedding_model);
+    }
+
+    #[tokio::test]
+    #[serial]
+    async fn test_export_without_vectors_omits_embeddings() {
+        unsafe { std::env::remove_var(ck_core::INDEX_DIR_ENV) };
+        let source = TempDir::new().unwrap();
+        build_index(source.path()).await;
+
+        let mut dump = Vec::new();
+        export_index(source.path(), false, &mut dump).unwrap();
+        let text = String::from_utf8(dump).unwrap();
+        assert!(!text.contains("\"embedding\""));
+        assert!(text.contains("\"estimated_tokens\""));
+    }
+
+    #[tokio::test]
+    #[serial]
+    async fn test_round_trip_keeps_language_models() {
+        unsafe { std::env::remove_var(ck_core::INDEX_DIR_ENV) };
+        let source = TempDir::new().unwrap();
+        let root = source.path();
+        fs::write(root.join("lib.rs"), "fn parse() {}\n").unwrap();
+        fs::write(root.join("README.md"), "# Parsing\n\nHow parsing works.\n").unwrap();
+        let models = BTreeMap::from([(
+            "markdown".to_string(),
+            "sentence-transformers/all-MiniLM-L6-v2".to_string(),
+        )]);
+        super::super::set_language_models(root, &models).unwrap();
+        build_index(root).await;
+
+        let mut dump = Vec::new();
+        export_index(root, true, &mut dump).unwrap();
+        let target = TempDir::new().unwrap();
+        let imported = import_index(target.path(), dump.as_slice()).unwrap();
+        assert_eq!(imported.files_imported, 4);
+
+        assert_eq!(super::super::index_language_models(target.path()), models);
+        let manifest_of = |root: &Path| {
+            load_or_create_manifest(&ck_core::index_dir(root).join("manifest.json")).unwrap()
+        };
+        assert_eq!(
+            manifest_of(target.path()).embedding_model,
+            manifest_of(root).embedding_model
+        );
+        for file in ["lib.rs", "README.md"] {
+            let model = |root: &Path| {
+                load_index_entry(&ck_core::get_sidecar_path(root, &root.join(file)))
+                    .unwrap()
+                    .embedding_model
+            };
+            assert_eq!(model(target.path()), model(root), "{file}");
+        }
+        assert_ne!(
+            manifest_of(target.path()).embedding_model.as_deref(),
+            Some(models["markdown"].as_str())
+        );
+    }
+
+    #[tokio::test]
+    #[serial]
+    async fn test_reduced_index_exports_and_imports_without_vectors() {
+        unsafe { std::env::remove_var(ck_core::INDEX_DIR_ENV) };
+        let source = TempDir::new().unwrap();
+        build_index(source.path()).await;
+        let mut full_dump = Vec::new();
+        export_index(source.path(), true, &mut full_dump).unwrap();
+        super::super::set_dimension_reduction(source.path(), 2).unwrap();
+
+        let err = export_index(source.path(), true, &mut Vec::new()).unwrap_err();
+        assert!(err.to_string().contains("--reduce-dim"));
+
+        let mut dump = Vec::new();
+        let exported = export_index(source.path(), false, &mut dump).unwrap();
+        let target = TempDir::new().unwrap();
+        let imported = import_index(target.path(), dump.as_slice()).unwrap();
+        assert_eq!(imported.chunks_imported, exported.chunks_exported);
+        let restored = load_index_entry(&ck_core::get_sidecar_path(
+            target.path(),
+            &target.path().join("a.rs"),
+        ))
+        .unwrap();
+        assert!(restored.chunks.iter().all(|c| c.embedding.is_none()));
+
+        // Full-size vectors can't be mixed into the reduced index either
+        let err = import_index(source.path(), full_dump.as_slice()).unwrap_err();
+        assert!(err.to_string().contains("--reduce-dim"));
+    }
+
+    #[test]
+    #[serial]
+    fn test_import_rejects_paths_outside_the_index_root() {
+        unsafe { std::env::remove_var(ck_core::INDEX_DIR_ENV) };
+        let target = TempDir::new().unwrap();
+        let root = target.path().join("root");
+        fs::create_dir(&root).unwrap();
+        let escape = target.path().join("escaped.rs");
+        let record = ExportRecord {
+            path: PathBuf::from("ok.rs"),
+            file_hash: "hash".to_string(),
+            file_size: 1,
+            last_modified: 0,
+            span: Span {
+                byte_start: 0,
+                byte_end: 1,
+                line_start: 1,
+                line_end: 1,
+            },
+            chunk_type: None,
+            breadcrumb: None,
+            ancestry: None,
+            byte_length: None,
+            estimated_tokens: None,
+            leading_trivia: None,
+            trailing_trivia: None,
+            chunk_hash: None,
+            model: None,
+            language: None,
+            dimensions: None,
+            embedding: None,
+        };
+
+        for bad in [PathBuf::from("../../escaped.rs"), escape.clone()] {
+            let dump = [
+                record.clone(),
+                ExportRecord {
+                    path: bad.clone(),
+                    ..record.clone()
+                },
+            ]
+            .iter()
+            .map(|r| serde_json::to_string(r).unwrap())
+            .collect::<Vec<_>>()
+            .join("\n");
+            let err = import_index(&root, dump.as_bytes())
+                .unwrap_err()
+                .to_string();
+            assert!(err.starts_with("Line 2:"), "unexpected error: {err}");
+            assert!(!ck_core::index_dir(&root).exists(), "{}", bad.display());
+        }
+        assert!(!target.path().join("escaped.rs.ck").exists());
+    }
+
+    #[test]
+    #[serial]
+    fn test_import_rejects_mixed_models() {
+        unsafe { std::env::remove_var(ck_core::INDEX_DIR_ENV) };
+        let record = |path: &str, model: &str, dims: usize| ExportRecord {
+            path: PathBuf::from(path),
+            file_hash: format!("hash-{path}"),
+            file_size: 1,
+            last_modified: 0,
+            span: Span {
+                byte_start: 0,
+                byte_end: 1,
+                line_start: 1,
+                line_end: 1,
+            },
+            chunk_type: None,
+            breadcrumb: None,
+            ancestry: None,
+            byte_length: None,
+            estimated_tokens: None,
+            leading_trivia: None,
+            trailing_trivia: None,
+            chunk_hash: None,
+            model: Some(model.to_string()),
+            language: None,
+            dimensions: Some(dims),
+            embedding: Some(vec![0.0; dims]),
+        };
+        let dump = [record("a.rs", "bge", 4), record("b.rs", "nomic", 8)]
+            .iter()
+            .map(|r| serde_json::to_string(r).unwrap())
+            .collect::<Vec<_>>()
+            .join("\n");
+
+        let target = TempDir::new().unwrap();
+        let err = import_index(target.path(), dump.as_bytes())
+            .unwrap_err()
+            .to_string();
+        assert!(
+            err.contains("outside [language_models]"),
+            "unexpected error: {err}"
+        );
+        assert!(
+            !ck_core::index_dir(target.path())
+                .join("manifest.json")
+                .exists()
+        );
+    }
+}
@@ -0,0 +1,187 @@
+//! `--gc`: compact an index that has drifted over many updates.
+//!
+//! Orphaned manifest entries and sidecars are dropped as with
+//! `--clean-orphans`, then every remaining sidecar is re-encoded with the
+//! index's current settings, which upgrades sidecars written in older formats
+//! or with since-changed `--compress`/`--quantize` settings. Each rewrite goes
+//! through a temporary file and a rename, so an interrupted run leaves every
+//! sidecar either old or new; temporary files left by an earlier interrupted
+//! write are removed. Sidecars that can no longer be read are dropped along
+//! with their manifest entry, so the next update indexes those files afresh.
+
+use anyhow::Result;
+use serde::Serialize;
+use std::fs;
+use std::path::Path;
+use std::time::SystemTime;
+
+use super::sidecar::encode_index_entry;
+use super::{
+    acquire_index_write_lock, atomic_write, cleanup_validation, load_index_entry,
+    load_or_create_manifest, normalize_manifest_paths, path_utils, refresh_ann_index,
+    remove_empty_dirs, save_manifest, walk_index_dir,
+};
+
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct CompactStats {
+    /// Sidecars whose encoding changed
+    pub sidecars_rewritten: usize,
+    pub orphaned_entries_removed: usize,
+    pub orphaned_sidecars_removed: usize,
+    /// Sidecars that failed to load; their files are re-indexed next update
+    pub unreadable_sidecars_removed: usize,
+    /// Temporary files left behind by interrupted writes
+    pub temp_files_removed: usize,
+    /// Size of the index directory before and after
+    pub bytes_before: u64,
+    pub bytes_after: u64,
+}
+
+impl CompactStats {
+    pub fn bytes_reclaimed(&self) -> u64 {
+        self.bytes_before.saturating_sub(self.bytes_after)
+    }
+}
+
+/// Compact the index at `path`: drop orphans, rewrite every sidecar in the
+/// index's current format and remove leftovers of interrupted writes.
+pub fn compact_index(
+    path: &Path,
+    options: &ck_core::FileCollectionOptions,
+) -> Result<CompactStats> {
+    let index_dir = ck_core::index_dir(path);
+    let manifest_path = index_dir.join("manifest.json");
+    if !manifest_path.exists() {
+        anyhow::bail!(
+            "No index found at {}. Run 'ck --index' to create one.",
+            path.display()
+        );
+    }
+    let _lock = acquire_index_write_lock(&index_dir)?;
+    ck_core::check_index_root_marker(path)?;
+
+    let mut stats = CompactStats {
+        bytes_before: dir_size(&index_dir),
+        ..Default::default()
+    };
+    stats.temp_files_removed = remove_temp_files(&index_dir)?;
+
+    let mut manifest = load_or_create_manifest(&manifest_path)?;
+    normalize_manifest_paths(&mut manifest, path);
+    let cleanup =
+        cleanup_validation::validate_and_cleanup_index(path, &index_dir, &mut manifest, options)?;
+    stats.orphaned_entries_removed = cleanup.orphaned_entries_removed;
+    stats.orphaned_sidecars_removed = cleanup.orphaned_sidecars_removed;
+
+    let encoding = manifest.sidecar_encoding();
+    let manifest_keys: Vec<_> = manifest.files.keys().cloned().collect();
+    for manifest_key in manifest_keys {
+        let standard_path = path_utils::from_manifest_path(&manifest_key);
+        let sidecar_path =
+            path_utils::get_sidecar_path_for_standard_path(&index_dir, &standard_path);
+        let Ok(entry) = load_index_entry(&sidecar_path) else {
+            tracing::warn!("Dropping unreadable sidecar {}", sidecar_path.display());
+            fs::remove_file(&sidecar_path)?;
+            manifest.files.remove(&manifest_key);
+            stats.unreadable_sidecars_removed += 1;
+            continue;
+        };
+        let encoded = encode_index_entry(&entry, encoding)?;
+        if fs::read(&sidecar_path)? != encoded {
+            atomic_write(&sidecar_path, &encoded)?;
+            stats.sidecars_rewritten += 1;
+        }
+    }
+
+    remove_empty_dirs(&index_dir)?;
+    if stats.orphaned_entries_removed > 0 || stats.unreadable_sidecars_removed > 0 {
+        manifest.updated = SystemTime::now()
+            .duration_since(SystemTime::UNIX_EPOCH)
+            .unwrap()
+            .as_secs();
+        save_manifest(&manifest_path, &manifest)?;
+    }
+    // Rebuilt from the compacted sidecars, which also drops dead nodes
+    refresh_ann_index(path)?;
+
+    stats.bytes_after = dir_size(&index_dir);
+    Ok(stats)
+}
+
+/// Remove the temporary files [`atomic_write`] leaves when it is interrupted
+/// before its rename. The caller holds the write lock, so none is in use.
+fn remove_temp_files(index_dir: &Path) -> Result<usize> {
+    let mut removed = 0;
+    for entry in walk_index_dir(index_dir).filter_map(Result::ok) {
+        if entry.file_type().is_file() && entry.file_name().to_string_lossy().starts_with(".tmp") {
+            fs::remove_file(entry.path())?;
+            removed += 1;
+        }
+    }
+    Ok(removed)
+}
+
+fn dir_size(index_dir: &Path) -> u64 {
+    walk_index_dir(index_dir)
+        .filter_map(Result::ok)
+        .filter(|entry| entry.file_type().is_file())
+        .filter_map(|entry| entry.metadata().ok())
+        .map(|metadata| metadata.len())
+        .sum()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sidecar::SidecarEncoding;
+    use serial_test::serial;
+    use tempfile::TempDir;
+
+    #[tokio::test]
+    #[serial]
+    async fn test_compact_index_rewrites_drifted_sidecars_and_drops_orphans() {
+        unsafe { std::env::remove_var(ck_core::INDEX_DIR_ENV) };
+        let temp_dir = TempDir::new().unwrap();
+        let root = temp_dir.path();
+        fs::write(root.join("kept.rs"), "fn kept() {}\n".repeat(50)).unwrap();
+        fs::write(root.join("gone.rs"), "fn gone() {}\n").unwrap();
+        let options = ck_core::FileCollectionOptions::default();
+        crate::smart_update_index_with_detailed_progress(
+            root, false, None, None, false, &options, None,
+        )
+        .await
+        .unwrap();
+        crate::set_sidecar_compression(root, true).unwrap();
+
+        // A sidecar left uncompressed by an older ck, a deleted file, and the
+        // remains of an interrupted write
+        let index_dir = ck_core::index_dir(root);
+        let kept = path_utils::get_sidecar_path_for_standard_path(&index_dir, Path::new("kept.rs"));
+        let entry = load_index_entry(&kept).unwrap();
+        atomic_write(
+            &kept,
+            &encode_index_entry(&entry, SidecarEncoding::default()).unwrap(),
+        )
+        .unwrap();
+        fs::remove_file(root.join("gone.rs")).unwrap();
+        fs::write(index_dir.join(".tmpAbC123"), vec![0u8; 4096]).unwrap();
+
+        let stats = compact_index(root, &options).unwrap();
+        assert_eq!(stats.sidecars_rewritten, 1);
+        assert_eq!(stats.orphaned_entries_removed, 1);
+        assert_eq!(stats.temp_files_removed, 1);
+        assert!(stats.bytes_reclaimed() > 4096, "{stats:?}");
+        assert!(!index_dir.join(".tmpAbC123").exists());
+        let compacted = load_index_entry(&kept).unwrap();
+        assert_eq!(compacted.chunks.len(), entry.chunks.len());
+        assert_eq!(compacted.metadata.hash, entry.metadata.hash);
+
+        // Nothing left to do the second time
+        let stats = compact_index(root, &options).unwrap();
+        assert_eq!(
+            (stats.sidecars_rewritten, stats.orphaned_entries_removed),
+            (0, 0)
+        );
+        assert_eq!(stats.bytes_reclaimed(), 0);
+    }
+}
@@ -0,0 +1,166 @@
+//! Heuristics for files written by a tool rather than a person: protobuf
+//! output, `*_generated.rs`, anything whose header says `DO NOT EDIT`.
+//!
+//! A file is generated when its name matches one of the name globs, or when
+//! one of the markers appears (ignoring case) in its first
+//! [`HEADER_LINES`] lines. Indexing records the verdict in the file's
+//! sidecar, so `--no-generated` can drop index-backed results without
+//! reading the files again; `.ck/config.toml` adds globs and markers to the
+//! built-in ones through [`set_generated_rules`].
+
+use anyhow::Result;
+use globset::{Glob, GlobSet, GlobSetBuilder};
+use std::fs;
+use std::io::{BufRead, BufReader};
+use std::path::Path;
+use std::sync::{Arc, RwLock};
+
+/// File names of generated code, whatever the header says
+const DEFAULT_PATTERNS: &[&str] = &[
+    "*.pb.go",
+    "*.pb.cc",
+    "*.pb.h",
+    "*_pb2.py",
+    "*_pb2.pyi",
+    "*_pb2_grpc.py",
+    "*_generated.rs",
+    "*_generated.go",
+    "*.generated.*",
+    "*.g.dart",
+    "*.freezed.dart",
+    "*.designer.cs",
+    "*.g.cs",
+];
+
+/// Header text that code generators leave behind
+const DEFAULT_MARKERS: &[&str] = &[
+    "DO NOT EDIT",
+    "@generated",
+    "Code generated by",
+    "autogenerated",
+    "auto-generated",
+    "automatically generated",
+    "This file was generated",
+    "This file is generated",
+];
+
+/// How many lines of a file are searched for markers.
+pub const HEADER_LINES: usize = 10;
+
+struct GeneratedRules {
+    patterns: GlobSet,
+    /// Lowercased, for case-insensitive matching
+    markers: Vec<String>,
+}
+
+impl GeneratedRules {
+    fn new(extra_patterns: &[String], extra_markers: &[String]) -> Result<Self> {
+        let mut patterns = GlobSetBuilder::new();
+        for pattern in DEFAULT_PATTERNS
+            .iter()
+            .copied()
+            .chain(extra_patterns.iter().map(String::as_str))
+        {
+            patterns.add(Glob::new(pattern).map_err(|err| {
+                anyhow::anyhow!("Invalid generated file pattern `{pattern}`: {err}")
+            })?);
+        }
+        let markers = DEFAULT_MARKERS
+            .iter()
+            .copied()
+            .chain(extra_markers.iter().map(String::as_str))
+            .filter(|marker| !marker.is_empty())
+            .map(str::to_lowercase)
+            .collect();
+        Ok(Self {
+            patterns: patterns.build()?,
+            markers,
+        })
+    }
+
+    fn name_matches(&self, path: &Path) -> bool {
+        path.file_name()
+            .is_some_and(|name| self.patterns.is_match(Path::new(name)))
+    }
+
+    fn matches(&self, path: &Path, text: &str) -> bool {
+        self.name_matches(path) || text.lines().take(HEADER_LINES).any(|line| self.marks(line))
+    }
+
+    fn marks(&self, line: &str) -> bool {
+        let line = line.to_lowercase();
+        self.markers.iter().any(|marker| line.contains(marker))
+    }
+}
+
+/// The rules in effect: the built-in ones until [`set_generated_rules`]
+/// adds the config's
+static RULES: RwLock<Option<Arc<GeneratedRules>>> = RwLock::new(None);
+
+fn rules() -> Arc<GeneratedRules> {
+    if let Some(rules) = RULES.read().unwrap_or_else(|err| err.into_inner()).as_ref() {
+        return Arc::clone(rules);
+    }
+    let mut rules = RULES.write().unwrap_or_else(|err| err.into_inner());
+    Arc::clone(rules.get_or_insert_with(|| {
+        Arc::new(GeneratedRules::new(&[], &[]).expect("built-in patterns are valid"))
+    }))
+}
+
+/// Recognize files named by `patterns` (globs matched against the file
+/// name) or headed by any of `markers`, besides the built-in ones.
+pub fn set_generated_rules(patterns: &[String], markers: &[String]) -> Result<()> {
+    let rules = GeneratedRules::new(patterns, markers)?;
+    *RULES.write().unwrap_or_else(|err| err.into_inner()) = Some(Arc::new(rules));
+    Ok(())
+}
+
+/// Whether `path`, whose content is `text`, is generated.
+pub fn is_generated(path: &Path, text: &str) -> bool {
+    rules().matches(path, text)
+}
+
+/// Whether the file at `path` is generated, reading no more than its
+/// header. Files that can't be read aren't.
+pub fn is_generated_file(path: &Path) -> bool {
+    let rules = rules();
+    if rules.name_matches(path) {
+        return true;
+    }
+    let Ok(file) = fs::File::open(path) else {
+        return false;
+    };
+    BufReader::new(file)
+        .split(b'\n')
+        .take(HEADER_LINES)
+        .map_while(|line| line.ok())
+        .any(|line| rules.marks(&String::from_utf8_lossy(&line)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_generated_by_name_or_header() {
+        let rules =
+            GeneratedRules::new(&["*.gen.ts".to_string()], &["Generated by foo".to_string()])
+                .unwrap();
+        let generated = |path: &str, text: &str| rules.matches(Path::new(path), text);
+
+        assert!(generated("api/service.pb.go", "package api\n"));
+        assert!(generated("src/schema_generated.rs", ""));
+        assert!(generated("web/client.gen.ts", ""));
+        assert!(generated(
+            "api/client.go",
+            "// Code generated by protoc-gen-go. DO NOT EDIT.\npackage api\n"
+        ));
+        assert!(generated("lib.rs", "// @generated\n"));
+        assert!(generated("lib.py", "# GENERATED BY FOO 2.1\n"));
+
+        assert!(!generated("src/main.rs", "fn main() {}\n"));
+        // A marker below the header is just text
+        let late = format!("{}// DO NOT EDIT\n", "\n".repeat(HEADER_LINES));
+        assert!(!generated("src/main.rs", &late));
+    }
+}
@@ -0,0 +1,133 @@
+//! `--log-file`: one JSON line per file an indexing pass decided about, for
+//! auditing why a file is or isn't in the index (and diffing that in CI).
+//!
+//! Files the walk passes over are logged from the same classification as
+//! `--index --dry-run` ([`plan_index`](crate::plan_index)), an excluded
+//! directory once rather than once per file below it. Walked files are
+//! logged as the pass reaches them. Lines are buffered and flushed when the
+//! pass ends, including when it is interrupted.
+
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+/// What an indexing pass did with a file.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum IndexDecision {
+    /// Chunked (and embedded) and written to its sidecar
+    Indexed,
+    /// Walked but not (re)indexed: unchanged since the last pass, or unreadable
+    Skipped,
+    /// Passed over by the walk
+    Excluded,
+    /// Took longer than `--file-timeout`
+    Timeout,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct IndexLogRecord {
+    /// Relative to the indexed root; directories end in `/`
+    pub path: String,
+    pub decision: IndexDecision,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub reason: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub chunks: Option<usize>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub duration_ms: Option<u64>,
+}
+
+/// A JSONL file the indexing pass appends its decisions to. Clones share
+/// the writer, so worker threads can log as well.
+#[derive(Clone)]
+pub struct IndexLog {
+    root: Arc<Path>,
+    writer: Arc<Mutex<BufWriter<File>>>,
+}
+
+impl IndexLog {
+    /// Create (or truncate) `log_path` for a pass over `root`.
+    pub fn create(log_path: &Path, root: &Path) -> Result<Self> {
+        let file = File::create(log_path).map_err(|e| {
+            anyhow::anyhow!("Failed to create log file {}: {e}", log_path.display())
+        })?;
+        Ok(Self {
+            root: root.into(),
+            writer: Arc::new(Mutex::new(BufWriter::new(file))),
+        })
+    }
+
+    pub(crate) fn indexed(&self, file: &Path, chunks: usize, duration: Duration) {
+        self.write(IndexLogRecord {
+            path: self.relative(file, false),
+            decision: IndexDecision::Indexed,
+            reason: None,
+            chunks: Some(chunks),
+            duration_ms: Some(duration.as_millis() as u64),
+        });
+    }
+
+    pub(crate) fn skipped(&self, file: &Path, reason: impl Into<String>) {
+        self.write(IndexLogRecord {
+            path: self.relative(file, false),
+            decision: IndexDecision::Skipped,
+            reason: Some(reason.into()),
+            chunks: None,
+            duration_ms: None,
+        });
+    }
+
+    pub(crate) fn timed_out(&self, file: &Path, error: &anyhow::Error, duration: Duration) {
+        self.write(IndexLogRecord {
+            path: self.relative(file, false),
+            decision: IndexDecision::Timeout,
+            reason: Some(error.to_string()),
+            chunks: None,
+            duration_ms: Some(duration.as_millis() as u64),
+        });
+    }
+
+    /// Every path the walk of `options` passes over, with the rule responsible.
+    pub(crate) fn excluded(&self, options: &ck_core::FileCollectionOptions) -> Result<()> {
+        for excluded in crate::plan_index(&self.root, options, false)?.excluded {
+            self.write(IndexLogRecord {
+                path: self.relative(&excluded.path, excluded.is_dir),
+                decision: IndexDecision::Excluded,
+                reason: Some(excluded.reason.to_string()),
+                chunks: None,
+                duration_ms: None,
+            });
+        }
+        Ok(())
+    }
+
+    pub fn flush(&self) -> Result<()> {
+        self.writer.lock().unwrap().flush()?;
+        Ok(())
+    }
+
+    fn relative(&self, path: &Path, is_dir: bool) -> String {
+        let relative = path.strip_prefix(&*self.root).unwrap_or(path).display();
+        if is_dir {
+            format!("{relative}/")
+        } else {
+            relative.to_string()
+        }
+    }
+
+    /// Logging is best-effort: a full disk shouldn't fail the pass itself
+    fn write(&self, record: IndexLogRecord) {
+        let mut writer = self.writer.lock().unwrap();
+        let written = serde_json::to_writer(&mut *writer, &record)
+            .map_err(std::io::Error::from)
+            .and_then(|_| writer.write_all(b"\n"));
+        if let Err(e) = written {
+            tracing::warn!("Failed to write index log record: {e}");
+        }
+    }
+}
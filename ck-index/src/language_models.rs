@@ -0,0 +1,207 @@
+//! Per-language embedding models (`[language_models]` in `.ck/config.toml`).
+//!
+//! The manifest records the model each mapped language is embedded with;
+//! files in any other language use the index's own model. Every sidecar
+//! records the model that embedded it, so a search embeds the query once per
+//! model in use and scores each file against the vector from its own model.
+
+use anyhow::Result;
+use std::collections::{BTreeMap, HashMap};
+use std::fs;
+use std::path::Path;
+use std::sync::Arc;
+use std::time::SystemTime;
+
+use super::projection::ProjectedEmbedder;
+use super::{
+    IndexManifest, Projection, acquire_index_write_lock, load_or_create_manifest,
+    normalize_manifest_paths, path_utils, save_manifest,
+};
+
+/// The `[language_models]` key for `file`, if its language is recognized.
+pub fn language_key(file: &Path) -> Option<String> {
+    ck_core::Language::from_path(file).map(|language| language.to_string())
+}
+
+/// The per-language models recorded in the index at `path`, keyed by
+/// language; empty when none are set or there is no index.
+pub fn index_language_models(path: &Path) -> BTreeMap<String, String> {
+    let manifest_path = ck_core::index_dir(path).join("manifest.json");
+    fs::read(&manifest_path)
+        .ok()
+        .and_then(|data| serde_json::from_slice::<IndexManifest>(&data).ok())
+        .map(|manifest| manifest.language_models)
+        .unwrap_or_default()
+}
+
+/// The model that embeds `file` in an index with `manifest`'s settings.
+fn model_for_file<'a>(manifest: &'a IndexManifest, file: &Path) -> Option<&'a str> {
+    language_key(file)
+        .and_then(|language| manifest.language_models.get(&language))
+        .or(manifest.embedding_model.as_ref())
+        .map(String::as_str)
+}
+
+/// Record `models` (language → canonical model name) as the index's
+/// per-language models. Files whose model changes lose their manifest entry
+/// and sidecar, so the next update embeds them afresh with the new model.
+/// Returns the number of files dropped.
+pub fn set_language_models(path: &Path, models: &BTreeMap<String, String>) -> Result<usize> {
+    let index_dir = ck_core::index_dir(path);
+    let manifest_path = index_dir.join("manifest.json");
+    if models.is_empty() && !manifest_path.exists() {
+        return Ok(0);
+    }
+    let _lock = acquire_index_write_lock(&index_dir)?;
+    ck_core::check_index_root_marker(path)?;
+    ck_core::write_index_root_marker(path)?;
+
+    let mut manifest = load_or_create_manifest(&manifest_path)?;
+    if &manifest.language_models == models && manifest_path.exists() {
+        return Ok(0);
+    }
+    normalize_manifest_paths(&mut manifest, path);
+    let previous = manifest.clone();
+    manifest.language_models = models.clone();
+
+    let changed: Vec<_> = manifest
+        .files
+        .keys()
+        .filter(|key| {
+            let file = path_utils::from_manifest_path(key);
+            model_for_file(&previous, &file) != model_for_file(&manifest, &file)
+        })
+        .cloned()
+        .collect();
+    for manifest_key in &changed {
+        let standard_path = path_utils::from_manifest_path(manifest_key);
+        let sidecar_path =
+            path_utils::get_sidecar_path_for_standard_path(&index_dir, &standard_path);
+        if sidecar_path.exists() {
+            fs::remove_file(&sidecar_path)?;
+        }
+        manifest.files.remove(manifest_key);
+    }
+
+    manifest.updated = SystemTime::now()
+        .duration_since(SystemTime::UNIX_EPOCH)
+        .unwrap()
+        .as_secs();
+    save_manifest(&manifest_path, &manifest)?;
+    Ok(changed.len())
+}
+
+/// The embedders an indexing pass needs: the index's model, plus one for
+/// each per-language model, each created the first time a file needs it.
+pub(crate) struct FileEmbedders {
+    default: ck_models::ModelConfig,
+    /// Language → model config, for languages mapped to another model
+    by_language: HashMap<String, ck_models::ModelConfig>,
+    /// Keyed by model name
+    loaded: HashMap<String, Box<dyn ck_embed::Embedder>>,
+    /// The index's `--reduce-dim` projection, applied to its model's vectors
+    projection: Option<Arc<Projection>>,
+}
+
+impl FileEmbedders {
+    pub(crate) fn new(default: &ck_models::ModelConfig, manifest: &IndexManifest) -> Result<Self> {
+        let registry = ck_models::ModelRegistry::default();
+        let by_language = manifest
+            .language_models
+            .iter()
+            .filter(|(_, model)| **model != default.name)
+            .map(|(language, model)| {
+                let (_, config) = registry
+                    .resolve(Some(model))
+                    .map_err(|e| anyhow::anyhow!("{e} (language_models.{language})"))?;
+                Ok((language.clone(), config))
+            })
+            .collect::<Result<_>>()?;
+        Ok(Self {
+            default: default.clone(),
+            by_language,
+            loaded: HashMap::new(),
+            projection: manifest.projection.clone(),
+        })
+    }
+
+    /// The embedder for `file`'s language.
+    pub(crate) fn for_file(&mut self, file: &Path) -> Result<&mut Box<dyn ck_embed::Embedder>> {
+        let config = language_key(file)
+            .and_then(|language| self.by_language.get(&language))
+            .unwrap_or(&self.default);
+        if !self.loaded.contains_key(&config.name) {
+            if config.name != self.default.name {
+                tracing::debug!("Creating embedder for {}", config.name);
+            }
+            let mut embedder = ck_embed::create_embedder_for_config(config, None)?;
+            if let Some(projection) = &self.projection
+                && projection.model == config.name
+            {
+                embedder = Box::new(ProjectedEmbedder {
+                    inner: embedder,
+                    projection: projection.clone(),
+                });
+            }
+            self.loaded.insert(config.name.clone(), embedder);
+        }
+        Ok(self
+            .loaded
+            .get_mut(&config.name)
+            .expect("embedder was just created"))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::load_index_entry;
+    use serial_test::serial;
+    use tempfile::TempDir;
+
+    #[tokio::test]
+    #[serial]
+    async fn test_language_models_embed_each_language_with_its_model() {
+        unsafe { std::env::remove_var(ck_core::INDEX_DIR_ENV) };
+        let temp_dir = TempDir::new().unwrap();
+        let root = temp_dir.path();
+        fs::write(root.join("lib.rs"), "fn parse() {}\n").unwrap();
+        fs::write(root.join("README.md"), "# Parsing\n\nHow parsing works.\n").unwrap();
+        let options = ck_core::FileCollectionOptions::default();
+        crate::smart_update_index_with_detailed_progress(
+            root, false, None, None, true, &options, None,
+        )
+        .await
+        .unwrap();
+
+        let index_dir = ck_core::index_dir(root);
+        let sidecar_model = |file: &str| {
+            let sidecar =
+                path_utils::get_sidecar_path_for_standard_path(&index_dir, Path::new(file));
+            load_index_entry(&sidecar).unwrap().embedding_model.unwrap()
+        };
+        let default_model = sidecar_model("README.md");
+
+        let models = BTreeMap::from([(
+            "markdown".to_string(),
+            "sentence-transformers/all-MiniLM-L6-v2".to_string(),
+        )]);
+        assert_eq!(set_language_models(root, &models).unwrap(), 1);
+        assert_eq!(set_language_models(root, &models).unwrap(), 0);
+        assert_eq!(index_language_models(root), models);
+
+        crate::smart_update_index_with_detailed_progress(
+            root, false, None, None, true, &options, None,
+        )
+        .await
+        .unwrap();
+        assert_eq!(
+            sidecar_model("README.md"),
+            "sentence-transformers/all-MiniLM-L6-v2"
+        );
+        assert_eq!(sidecar_model("lib.rs"), default_model);
+
+        // Unmapping the language sends its files back to the index's model
+        assert_eq!(set_language_models(root, &BTreeMap::new()).unwrap(), 1);
+    }
+}
@@ -1,20 +1,62 @@
 use anyhow::Result;
 use ck_core::{
-    FileMetadata, Language, Span, compute_chunk_hash, compute_file_hash, get_sidecar_path,
+    CancellationToken, CkError, FileMetadata, Language, Span, compute_chunk_hash,
+    compute_file_hash, get_sidecar_path,
+};
+use ignore::{
+    WalkBuilder,
+    gitignore::{Gitignore, GitignoreBuilder},
+    overrides::OverrideBuilder,
 };
-use ignore::{WalkBuilder, overrides::OverrideBuilder};
 use rayon::prelude::*;
 use serde::{Deserialize, Serialize};
-use std::collections::{HashMap, HashSet};
+use std::collections::{BTreeMap, HashMap, HashSet};
 use std::fs;
 use std::io::{Read, Write};
 use std::path::{Path, PathBuf};
-use std::sync::Once;
 use std::sync::atomic::{AtomicBool, Ordering};
-use std::time::SystemTime;
+use std::sync::{Arc, Mutex, Once};
+use std::time::{Duration, SystemTime};
 use tempfile::NamedTempFile;
 use walkdir::WalkDir;
 
+mod ann;
+mod budget;
+mod checkpoint;
+mod export;
+mod gc;
+mod generated;
+mod index_log;
+mod language_models;
+mod patterns;
+mod plan;
+mod projection;
+mod quantize;
+mod sidecar;
+mod submodules;
+mod tokens;
+use ann::refresh_ann_index;
+pub use ann::{ANN_FILE, AnnHit, AnnIndex, AnnNode, DEFAULT_EF_SEARCH, load_ann_index};
+use budget::Deadline;
+pub use budget::{DEFAULT_FILE_TIMEOUT, FileTimedOut, is_timeout};
+pub use checkpoint::{CHECKPOINT_FILE, has_checkpoint};
+pub use export::{ExportRecord, ExportStats, ImportStats, export_index, import_index};
+pub use gc::{CompactStats, compact_index};
+pub use generated::{is_generated, is_generated_file, set_generated_rules};
+pub use index_log::{IndexDecision, IndexLog, IndexLogRecord};
+use language_models::FileEmbedders;
+pub use language_models::{index_language_models, language_key, set_language_models};
+pub use patterns::{PatternSource, UnmatchedPattern, unmatched_patterns};
+pub use plan::{
+    ExcludeReason, ExcludedPath, IndexDiff, IndexPlan, PlannedAction, PlannedFile, diff_index,
+    plan_index,
+};
+pub use projection::{PROJECTION_FILE, Projection};
+pub use quantize::{Quantization, QuantizedQuery, QuantizedVector};
+pub use sidecar::load_index_entry;
+use sidecar::{SidecarEncoding, save_index_entry};
+pub use tokens::{LanguageTokens, TokenCount, count_tokens};
+
 fn legacy_model_config(name: &str, dimensions: Option<usize>) -> ck_models::ModelConfig {
     ck_models::ModelConfig {
         name: name.to_string(),
@@ -22,6 +64,8 @@ fn legacy_model_config(name: &str, dimensions: Option<usize>) -> ck_models::Mode
         dimensions: dimensions.unwrap_or(384),
         max_tokens: 8192,
         description: "Legacy ck embedding model (inferred from manifest)".to_string(),
+        batch_size: ck_models::DEFAULT_BATCH_SIZE,
+        max_batch_size: ck_models::DEFAULT_MAX_BATCH_SIZE,
     }
 }
 
@@ -89,28 +133,192 @@ pub fn request_interrupt() {
     INTERRUPTED.store(true, Ordering::SeqCst);
 }
 
-/// Build override patterns for excluding files during directory traversal
-fn build_overrides(
-    base_path: &Path,
-    exclude_patterns: &[String],
-) -> Result<ignore::overrides::Override> {
-    let mut builder = OverrideBuilder::new(base_path);
+/// The threads indexing and the search file scan run their parallel work on
+/// (`--jobs`): a pool of its own with that many threads, or rayon's global
+/// pool (one thread per CPU) when no count is given. Only work started inside
+/// [`WorkerPool::install`] uses it.
+pub struct WorkerPool(Option<rayon::ThreadPool>);
+
+impl WorkerPool {
+    pub fn new(jobs: Option<usize>) -> Result<Self> {
+        let pool = jobs
+            .map(|jobs| {
+                rayon::ThreadPoolBuilder::new()
+                    .num_threads(jobs)
+                    .build()
+                    .map_err(|err| anyhow::anyhow!("Cannot start {jobs} worker threads: {err}"))
+            })
+            .transpose()?;
+        Ok(Self(pool))
+    }
+
+    /// Run `work`, and every rayon iterator it drives, on this pool.
+    pub fn install<R: Send>(&self, work: impl FnOnce() -> R + Send) -> R {
+        match &self.0 {
+            Some(pool) => pool.install(work),
+            None => work(),
+        }
+    }
+}
+
+/// Chunks embedded per call for `model` (a name or alias): `requested`
+/// (`--embed-batch-size`) clamped to the model's maximum, or the model's
+/// default. The per-file time budget is checked between calls.
+pub fn embed_batch_size(model: &str, requested: Option<usize>) -> usize {
+    match ck_models::ModelRegistry::default().resolve(Some(model)) {
+        Ok((_, config)) => config.effective_batch_size(requested),
+        Err(_) => requested
+            .unwrap_or(ck_models::DEFAULT_BATCH_SIZE)
+            .min(ck_models::DEFAULT_MAX_BATCH_SIZE),
+    }
+}
 
+/// Build the matcher for exclude patterns (`--exclude` and the defaults).
+///
+/// Patterns use gitignore syntax and are evaluated in order, the last match
+/// winning, so `!pattern` re-includes what an earlier pattern excluded:
+/// `*.json`, `!config/*.json`, `config/secret.json` keeps every JSON file
+/// under `config/` except the secret. As in `.gitignore`, a file inside an
+/// excluded directory can't be re-included, since the directory is never
+/// entered.
+fn build_excludes(base_path: &Path, exclude_patterns: &[String]) -> Result<Gitignore> {
+    let mut builder = GitignoreBuilder::new(base_path);
     for pattern in exclude_patterns {
-        if pattern.starts_with('!') {
-            builder.add(pattern)?;
+        builder.add_line(None, pattern)?;
+    }
+    Ok(builder.build()?)
+}
+
+/// Positive `--include` globs, matched relative to a root with the same
+/// override engine that backs exclude patterns. An empty set matches every
+/// path. Excludes are applied by the walker, so an excluded file never reaches
+/// this check and exclude always wins over include.
+///
+/// A depth limit ([`IncludeGlobs::with_max_depth`]) additionally rejects
+/// files more than that many directory levels below the root, counted the
+/// way the walker counts them.
+#[derive(Debug, Clone)]
+pub struct IncludeGlobs {
+    root: PathBuf,
+    canonical_root: Option<PathBuf>,
+    overrides: Option<ignore::overrides::Override>,
+    max_depth: Option<usize>,
+}
+
+impl IncludeGlobs {
+    /// Build a matcher for `globs` relative to `root` (a file's parent
+    /// directory when `root` is a file).
+    pub fn new(root: &Path, globs: &[String]) -> Result<Self> {
+        let root = if root.is_file() {
+            root.parent().unwrap_or(root).to_path_buf()
+        } else {
+            root.to_path_buf()
+        };
+        let overrides = if globs.is_empty() {
+            None
         } else {
-            builder.add(&format!("!{pattern}"))?;
+            let mut builder = OverrideBuilder::new(&root);
+            for glob in globs {
+                builder.add(glob)?;
+            }
+            Some(builder.build()?)
+        };
+        Ok(Self {
+            canonical_root: root.canonicalize().ok(),
+            root,
+            overrides,
+            max_depth: None,
+        })
+    }
+
+    /// Also reject files more than `max_depth` levels below the root (1 =
+    /// only files directly in it).
+    pub fn with_max_depth(mut self, max_depth: Option<usize>) -> Self {
+        self.max_depth = max_depth;
+        self
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.overrides.is_none() && self.max_depth.is_none()
+    }
+
+    pub fn matches(&self, path: &Path) -> bool {
+        if self.is_empty() {
+            return true;
+        }
+        let relative = self.relative(path);
+        if let (Some(max_depth), Some(relative)) = (self.max_depth, &relative)
+            && relative.components().count() > max_depth
+        {
+            return false;
         }
+        let Some(overrides) = &self.overrides else {
+            return true;
+        };
+        let relative = relative.unwrap_or_else(|| path.to_path_buf());
+        overrides.matched(&relative, false).is_whitelist()
     }
 
-    Ok(builder.build()?)
+    /// `path` relative to the root, if it's under it.
+    fn relative(&self, path: &Path) -> Option<PathBuf> {
+        match path.strip_prefix(&self.root) {
+            Ok(relative) => Some(relative.to_path_buf()),
+            Err(_) => path
+                .canonicalize()
+                .ok()
+                .zip(self.canonical_root.as_ref())
+                .and_then(|(canonical, root)| {
+                    canonical.strip_prefix(root).ok().map(Path::to_path_buf)
+                }),
+        }
+    }
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct IndexEntry {
     pub metadata: FileMetadata,
     pub chunks: Vec<ChunkEntry>,
+    /// Embedding model that produced this file's vectors (None for span-only
+    /// entries and sidecars written before this was recorded)
+    #[serde(default)]
+    pub embedding_model: Option<String>,
+    /// Dimension of this file's vectors, recorded alongside `embedding_model`
+    #[serde(default)]
+    pub embedding_dimensions: Option<usize>,
+    /// Whether the file looked generated when it was indexed (see
+    /// [`is_generated`]); None for sidecars written before this was recorded
+    #[serde(default)]
+    pub generated: Option<bool>,
+}
+
+/// Sidecar layout before per-file model metadata was recorded. Bincode encodes
+/// structs positionally, so `serde(default)` cannot fill the trailing fields;
+/// older sidecars are decoded through this shape instead.
+#[derive(Deserialize)]
+struct LegacyIndexEntry {
+    metadata: FileMetadata,
+    chunks: Vec<ChunkEntry>,
+}
+
+/// Sidecar layout of format 2, before generated files were flagged.
+#[derive(Deserialize)]
+struct IndexEntryV2 {
+    metadata: FileMetadata,
+    chunks: Vec<ChunkEntry>,
+    embedding_model: Option<String>,
+    embedding_dimensions: Option<usize>,
+}
+
+impl From<IndexEntryV2> for IndexEntry {
+    fn from(entry: IndexEntryV2) -> Self {
+        Self {
+            metadata: entry.metadata,
+            chunks: entry.chunks,
+            embedding_model: entry.embedding_model,
+            embedding_dimensions: entry.embedding_dimensions,
+            generated: None,
+        }
+    }
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -133,11 +341,32 @@ pub struct ChunkEntry {
     /// Blake3 hash of the chunk text for incremental indexing
     #[serde(default)]
     pub chunk_hash: Option<String>,
+    /// The vector as a quantized sidecar stores it (`embedding` then holds
+    /// its dequantized form). Not part of the bincode encoding.
+    #[serde(skip)]
+    pub quantized: Option<QuantizedVector>,
+}
+
+/// Layout of the manifest and sidecars this ck writes, recorded in both so
+/// an index built by another release is recognised instead of failing to
+/// decode:
+/// - 1 = sidecars without a header (before the format was recorded)
+/// - 2 = sidecars begin with a `CKSC` header carrying the format
+/// - 3 = sidecars record whether their file is generated
+///
+/// Older indexes stay readable; `--migrate` rewrites their sidecars.
+pub const INDEX_FORMAT_VERSION: u32 = 3;
+
+fn legacy_format_version() -> u32 {
+    1
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct IndexManifest {
     pub version: String,
+    /// [`INDEX_FORMAT_VERSION`] of the oldest sidecars the index may hold
+    #[serde(default = "legacy_format_version")]
+    pub format_version: u32,
     pub created: u64,
     pub updated: u64,
     pub files: HashMap<PathBuf, FileMetadata>,
@@ -150,6 +379,50 @@ pub struct IndexManifest {
     /// - v2 = blake3 of chunk text + leading_trivia + trailing_trivia
     #[serde(default)]
     pub chunk_hash_version: Option<u32>,
+    /// Lines of preceding context carried into each chunk (`--chunk-overlap`);
+    /// `None` means no overlap
+    #[serde(default)]
+    pub chunk_overlap_lines: Option<usize>,
+    /// How files were split into chunks (`--chunk-strategy`)
+    #[serde(default)]
+    pub chunk_strategy: ck_chunk::ChunkStrategy,
+    /// Sidecars are written zstd-compressed (`--compress`)
+    #[serde(default)]
+    pub compress_sidecars: bool,
+    /// Precision sidecars store embedding vectors at (`--quantize`)
+    #[serde(default)]
+    pub quantization: Quantization,
+    /// An approximate nearest-neighbour index is kept next to the sidecars
+    /// (`--ann`)
+    #[serde(default)]
+    pub ann_index: bool,
+    /// Embedding model per language (`[language_models]` in
+    /// `.ck/config.toml`); other languages use `embedding_model`
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub language_models: BTreeMap<String, String>,
+    /// Dimensions the index model's vectors are projected to
+    /// (`--reduce-dim`); the projection is in [`PROJECTION_FILE`]
+    #[serde(default)]
+    pub reduced_dimensions: Option<usize>,
+    /// The projection for `reduced_dimensions`, loaded with the manifest.
+    /// Not part of the JSON encoding.
+    #[serde(skip)]
+    pub projection: Option<Arc<Projection>>,
+}
+
+impl IndexManifest {
+    /// Length of the index model's stored vectors: the reduced dimensions
+    /// when there are any, else the model's own.
+    pub fn vector_dimensions(&self) -> Option<usize> {
+        self.reduced_dimensions.or(self.embedding_dimensions)
+    }
+
+    fn sidecar_encoding(&self) -> SidecarEncoding {
+        SidecarEncoding {
+            compress: self.compress_sidecars,
+            quantization: self.quantization,
+        }
+    }
 }
 
 impl Default for IndexManifest {
@@ -161,84 +434,188 @@ impl Default for IndexManifest {
 
         Self {
             version: "0.1.0".to_string(),
+            format_version: INDEX_FORMAT_VERSION,
             created: now,
             updated: now,
             files: HashMap::new(),
             embedding_model: None, // Default to None for backward compatibility
             embedding_dimensions: None,
             chunk_hash_version: Some(2), // v2 = blake3 of chunk text + trivia
+            chunk_overlap_lines: None,
+            chunk_strategy: ck_chunk::ChunkStrategy::SyntaxAware,
+            compress_sidecars: false,
+            quantization: Quantization::None,
+            ann_index: false,
+            language_models: BTreeMap::new(),
+            reduced_dimensions: None,
+            projection: None,
         }
     }
 }
 
-/// Common filtering logic for directory traversal entries
-fn should_include_file(entry: &ignore::DirEntry, index_dir: &Path) -> bool {
-    let path = entry.path();
-    entry.file_type().is_some_and(|ft| ft.is_file())
-        && is_text_file(path)
-        && !path.starts_with(index_dir)
+/// Walk `walker`, keeping the text files outside `index_dir` that `include`
+/// matches. Entries the walk or the binary check can't read (permission
+/// denied, a symlink loop) are skipped and added to `unreadable` with the
+/// reason, so one unreadable file never aborts a walk.
+fn filter_and_collect_files(
+    walker: ignore::Walk,
+    index_dir: &Path,
+    include: &IncludeGlobs,
+    unreadable: &mut Vec<(PathBuf, String)>,
+) -> Vec<PathBuf> {
+    let mut files = Vec::new();
+    for entry in walker {
+        let entry = match entry {
+            Ok(entry) => entry,
+            Err(err) => {
+                match unreadable_walk_entry(&err) {
+                    Some(skipped) => unreadable.push(skipped),
+                    None => tracing::debug!("Walk error: {}", err),
+                }
+                continue;
+            }
+        };
+        let path = entry.path();
+        if !entry.file_type().is_some_and(|ft| ft.is_file())
+            || path.starts_with(index_dir)
+            || !include.matches(path)
+        {
+            continue;
+        }
+        // PDFs are indexable even though they're binary, if this build can
+        // extract their text
+        if ck_core::pdf::is_pdf_file(path) {
+            if cfg!(feature = "pdf") {
+                files.push(path.to_path_buf());
+            }
+            continue;
+        }
+        match sniff_binary(path) {
+            Ok(false) => files.push(path.to_path_buf()),
+            Ok(true) => {}
+            Err(err) => unreadable.push((path.to_path_buf(), err.to_string())),
+        }
+    }
+    files
 }
 
-/// Apply common filtering to a WalkBuilder iterator
-fn filter_and_collect_files(walker: ignore::Walk, index_dir: &Path) -> Vec<PathBuf> {
-    walker
-        .filter_map(std::result::Result::ok)
-        .filter(|entry| should_include_file(entry, index_dir))
-        .map(|entry| entry.path().to_path_buf())
-        .collect()
+/// The entry a walk error is about and why, for IO errors on an entry
+/// (an unreadable directory, a symlink loop). Errors in ignore files
+/// aren't about an entry and give `None`.
+fn unreadable_walk_entry(err: &ignore::Error) -> Option<(PathBuf, String)> {
+    match err {
+        ignore::Error::WithPath { path, err } => err
+            .io_error()
+            .map(|io_err| (path.clone(), io_err.to_string())),
+        ignore::Error::WithDepth { err, .. } => unreadable_walk_entry(err),
+        ignore::Error::Loop { child, .. } => Some((child.clone(), err.to_string())),
+        _ => None,
+    }
 }
 
 pub fn collect_files(
     path: &Path,
     options: &ck_core::FileCollectionOptions,
 ) -> Result<Vec<PathBuf>> {
-    let index_dir = ck_core::index_dir(path);
-
-    if options.respect_gitignore {
-        let overrides = build_overrides(path, &options.exclude_patterns)?;
-        let mut walker_builder = WalkBuilder::new(path);
-        walker_builder
-            .git_ignore(true)
-            .git_global(true)
-            .git_exclude(true)
-            .hidden(!options.show_hidden);
-
-        // Add .ckignore support (hierarchical, like .gitignore)
-        if options.use_ckignore {
-            walker_builder.add_custom_ignore_filename(".ckignore");
-        }
+    Ok(collect_files_with_unreadable(path, options)?.0)
+}
 
-        walker_builder.overrides(overrides);
-        let walker = walker_builder.build();
+/// [`collect_files`], plus the files and directories it skipped because they
+/// couldn't be read, each with the reason.
+#[allow(clippy::type_complexity)]
+pub fn collect_files_with_unreadable(
+    path: &Path,
+    options: &ck_core::FileCollectionOptions,
+) -> Result<(Vec<PathBuf>, Vec<(PathBuf, String)>)> {
+    let index_dir = ck_core::index_dir(path);
+    let include = IncludeGlobs::new(path, &options.include_globs)?;
+    let walker = build_walker(path, options)?;
+    let mut unreadable = Vec::new();
+    let mut files = filter_and_collect_files(walker, &index_dir, &include, &mut unreadable);
+    if options.follow_links {
+        // Links into the tree reach the same file by several paths; keep
+        // the first path the walk gave each one
+        let mut seen = HashSet::new();
+        files.retain(|file| seen.insert(fs::canonicalize(file).unwrap_or_else(|_| file.clone())));
+    }
+    if options.skip_generated {
+        files.retain(|file| !is_generated_file(file));
+    }
+    Ok((files, unreadable))
+}
 
-        Ok(filter_and_collect_files(walker, &index_dir))
+/// Exclude patterns the walker applies: the user's, plus the defaults when
+/// `.gitignore` handling (which normally covers them) is off.
+///
+/// `.git` is skipped along with every other hidden entry by default. When
+/// hidden entries are included it stays skipped, even without the default
+/// excludes, unless an include glob reaches into it (`--include '.git/**'`).
+fn effective_exclude_patterns(options: &ck_core::FileCollectionOptions) -> Vec<String> {
+    let mut patterns = if options.respect_gitignore {
+        options.exclude_patterns.clone()
     } else {
-        // Use WalkBuilder without gitignore support, but still apply overrides
-        use ck_core::get_default_exclude_patterns;
-        let default_patterns = get_default_exclude_patterns();
-
-        // Combine default patterns with user exclude patterns
-        let mut all_patterns = default_patterns;
-        all_patterns.extend(options.exclude_patterns.iter().cloned());
-        let combined_overrides = build_overrides(path, &all_patterns)?;
+        let mut patterns = ck_core::get_default_exclude_patterns();
+        patterns.extend(options.exclude_patterns.iter().cloned());
+        patterns
+    };
+    let includes_git = ck_core::include_globs_reach_git(&options.include_globs);
+    if options.show_hidden && !includes_git && !patterns.iter().any(|p| p == ".git") {
+        patterns.push(".git".to_string());
+    }
+    patterns
+}
 
-        let mut walker_builder = WalkBuilder::new(path);
-        walker_builder
-            .git_ignore(false)
-            .git_global(false)
-            .git_exclude(false)
-            .hidden(!options.show_hidden);
+/// The directory walker behind [`collect_files`], with ignore files, hidden
+/// entries and exclude patterns configured from `options`.
+///
+/// The layers apply in increasing precedence, each one's `!pattern` able to
+/// re-include what a lower layer excluded:
+///
+/// 1. `.gitignore` (plus the global gitignore and `.git/info/exclude`)
+/// 2. `.ckignore`, deeper files taking precedence over their parents
+/// 3. exclude patterns, in order
+///
+/// The walker skips an entry as soon as any layer excludes it, so the last
+/// layer's negations only undo its own earlier patterns; use `--no-ckignore`
+/// or `--no-ignore` to get past the ignore files.
+///
+/// Symbolic links are only followed with `follow_links`; the walker then
+/// reports a link back to one of its own ancestors as an error instead of
+/// descending into it forever.
+///
+/// Git submodules below `path` are skipped unless `include_submodules`, and
+/// the walk stops `max_depth` levels below `path` when that's set (files
+/// directly in `path` are at depth 1).
+fn build_walker(path: &Path, options: &ck_core::FileCollectionOptions) -> Result<ignore::Walk> {
+    let excludes = build_excludes(path, &effective_exclude_patterns(options))?;
+    let submodules = (!options.include_submodules).then(|| submodules::Submodules::for_walk(path));
+    let mut walker_builder = WalkBuilder::new(path);
+    walker_builder
+        .git_ignore(options.respect_gitignore)
+        .git_global(options.respect_gitignore)
+        .git_exclude(options.respect_gitignore)
+        .hidden(!options.show_hidden)
+        .follow_links(options.follow_links)
+        .max_depth(options.max_depth);
+
+    // .ckignore is hierarchical like .gitignore, and honored either way
+    if options.use_ckignore {
+        walker_builder.add_custom_ignore_filename(".ckignore");
+    }
 
-        // Add .ckignore support even without gitignore
-        if options.use_ckignore {
-            walker_builder.add_custom_ignore_filename(".ckignore");
+    walker_builder.filter_entry(move |entry| {
+        let is_dir = entry.file_type().is_some_and(|ft| ft.is_dir());
+        if is_dir
+            && entry.depth() > 0
+            && submodules
+                .as_ref()
+                .is_some_and(|submodules| submodules.contains(entry.path()))
+        {
+            return false;
         }
-
-        walker_builder.overrides(combined_overrides);
-        let walker = walker_builder.build();
-
-        Ok(filter_and_collect_files(walker, &index_dir))
-    }
+        !excludes.matched(entry.path(), is_dir).is_ignore()
+    });
+    Ok(walker_builder.build())
 }
 
 fn collect_files_as_hashset(
@@ -248,11 +625,79 @@ fn collect_files_as_hashset(
     Ok(collect_files(path, options)?.into_iter().collect())
 }
 
+/// How a file on disk compares with its manifest entry.
+#[derive(Debug)]
+pub(crate) enum FileChange {
+    /// Same mtime and size as recorded
+    Unchanged,
+    /// Timestamp or size changed but the content hash did not; carries the
+    /// refreshed manifest entry
+    Touched(FileMetadata),
+    Modified,
+    /// Not in the manifest yet
+    Added,
+}
+
+/// Decide whether `file_path` needs (re)indexing. The content is only hashed
+/// when the cheap mtime/size check says it may have changed, or always with
+/// `force_hash` (`--force-hash`).
+pub(crate) fn classify_file_change(
+    file_path: &Path,
+    manifest_key: &Path,
+    existing: Option<&FileMetadata>,
+    force_hash: bool,
+) -> Result<FileChange> {
+    let Some(metadata) = existing else {
+        return Ok(FileChange::Added);
+    };
+
+    let fs_meta = fs::metadata(file_path)?;
+    let fs_last_modified = fs_meta
+        .modified()?
+        .duration_since(SystemTime::UNIX_EPOCH)
+        .map_err(|_| std::io::Error::other("Time error"))?
+        .as_secs();
+    let fs_size = fs_meta.len();
+
+    let same_stat = fs_last_modified == metadata.last_modified && fs_size == metadata.size;
+    if same_stat && !force_hash {
+        return Ok(FileChange::Unchanged);
+    }
+
+    let hash = compute_file_hash(file_path)?;
+    if hash != metadata.hash {
+        return Ok(FileChange::Modified);
+    }
+    if same_stat {
+        return Ok(FileChange::Unchanged);
+    }
+    Ok(FileChange::Touched(FileMetadata {
+        path: manifest_key.to_path_buf(),
+        hash,
+        last_modified: fs_last_modified,
+        size: fs_size,
+    }))
+}
+
 /// Name of the advisory lock file inside `.ck`, guarding against concurrent
 /// writers (two `ck` processes indexing the same directory would otherwise
 /// interleave manifest writes and silently lose each other's entries).
 const INDEX_LOCK_FILE: &str = ".lock";
 
+/// Directory inside `.ck` where `--at` keeps a snapshot of each commit's
+/// tree, with the snapshot's own index. Its files are not this index's, so
+/// [`walk_index_dir`] leaves it out.
+pub const REFS_DIR: &str = "refs";
+
+/// Walk `index_dir`, skipping the `--at` snapshots under [`REFS_DIR`].
+pub fn walk_index_dir(
+    index_dir: &Path,
+) -> impl Iterator<Item = walkdir::Result<walkdir::DirEntry>> {
+    WalkDir::new(index_dir)
+        .into_iter()
+        .filter_entry(|entry| !(entry.depth() == 1 && entry.file_name() == REFS_DIR))
+}
+
 /// Held for the duration of any index mutation. The OS advisory lock is
 /// released when this is dropped (the file handle closes).
 ///
@@ -296,16 +741,46 @@ pub async fn index_directory(
     model: Option<&str>,
 ) -> Result<()> {
     let _lock = acquire_index_write_lock(&ck_core::index_dir(path))?;
-    index_directory_inner(path, compute_embeddings, options, model).await
+    index_directory_inner(
+        path,
+        options,
+        None,
+        &IndexUpdateOptions {
+            compute_embeddings,
+            model,
+            ..Default::default()
+        },
+    )
+    .await?;
+    refresh_ann_index(path)?;
+    Ok(())
 }
 
 /// Body of [`index_directory`]; callers must hold the index write lock.
+/// Returns the files it skipped, in [`UpdateStats::timed_out_files`] and
+/// [`UpdateStats::unreadable`]. Cancelling `update.cancel` stops it between
+/// files with [`CkError::Cancelled`], leaving a checkpoint for `resume`;
+/// `force_rebuild`, `resume` and `force_hash` don't apply to a fresh index
+/// and are ignored.
 async fn index_directory_inner(
     path: &Path,
-    compute_embeddings: bool,
     options: &ck_core::FileCollectionOptions,
-    model: Option<&str>,
-) -> Result<()> {
+    progress_callback: Option<&ProgressCallback>,
+    update: &IndexUpdateOptions<'_>,
+) -> Result<UpdateStats> {
+    let &IndexUpdateOptions {
+        compute_embeddings,
+        model,
+        chunk_overlap,
+        chunk_strategy,
+        file_timeout,
+        embed_batch_size,
+        jobs,
+        index_log,
+        cancel,
+        ..
+    } = update;
+    let cancelled = || cancel.is_some_and(CancellationToken::is_cancelled);
     tracing::info!(
         "index_directory called with compute_embeddings={}",
         compute_embeddings
@@ -321,6 +796,11 @@ async fn index_directory_inner(
     let manifest_path = index_dir.join("manifest.json");
     let mut manifest = load_or_create_manifest(&manifest_path)?;
     normalize_manifest_paths(&mut manifest, path);
+    let chunking = ChunkSettings {
+        overlap_lines: apply_chunk_overlap_setting(&mut manifest, chunk_overlap)?,
+        strategy: apply_chunk_strategy_setting(&mut manifest, chunk_strategy)?,
+        embed_batch_size,
+    };
 
     // Handle model configuration for embeddings
     let resolved_model = if compute_embeddings {
@@ -349,7 +829,21 @@ async fn index_directory_inner(
         None
     };
 
-    let files = collect_files(path, options)?;
+    let (files, unreadable) = collect_files_with_unreadable(path, options)?;
+    if let Some(log) = index_log {
+        log.excluded(options)?;
+        log_unreadable(log, &unreadable);
+    }
+    let timed_out = Arc::new(Mutex::new(Vec::new()));
+    let mut unwritable = Vec::new();
+    let report_progress = |file_path: &Path| {
+        if let Some(callback) = progress_callback
+            && let Some(file_name) = file_path.file_name()
+        {
+            callback(&file_name.to_string_lossy());
+        }
+    };
+    let mut checkpoint = checkpoint::CheckpointWriter::open(&index_dir, false)?;
 
     if compute_embeddings {
         // Sequential processing with small-batch embeddings for streaming performance
@@ -357,17 +851,43 @@ async fn index_directory_inner(
         let (_, config) = resolved_model
             .as_ref()
             .expect("resolved model must be present when computing embeddings");
-        let mut embedder = ck_embed::create_embedder_for_config(config, None)?;
+        let mut embedders = FileEmbedders::new(config, &manifest)?;
 
         for file_path in files.iter() {
-            match index_single_file(file_path, path, Some(&mut embedder)) {
+            if cancelled() || INTERRUPTED.load(Ordering::SeqCst) {
+                break;
+            }
+            report_progress(file_path);
+            let started = std::time::Instant::now();
+            let embedder = embedders.for_file(file_path)?;
+            let result = index_single_file_with_progress(
+                file_path,
+                path,
+                Some(embedder),
+                chunking,
+                file_timeout,
+                cancel,
+                None,
+                0,
+                1,
+            );
+            match result.map(|(entry, _, _)| entry) {
                 Ok(entry) => {
                     // Write sidecar immediately
                     let sidecar_path = get_sidecar_path(path, file_path);
-                    save_index_entry(&sidecar_path, &entry)?;
+                    if let Err(e) =
+                        save_index_entry(&sidecar_path, &entry, manifest.sidecar_encoding())
+                    {
+                        sidecar_write_failed(&mut unwritable, index_log, file_path, &e);
+                        continue;
+                    }
+                    if let Some(log) = index_log {
+                        log.indexed(file_path, entry.chunks.len(), started.elapsed());
+                    }
 
                     // Update and save manifest immediately
                     let manifest_key = entry.metadata.path.clone();
+                    checkpoint.record(&manifest_key, &entry.metadata.hash)?;
                     manifest.files.insert(manifest_key, entry.metadata);
                     manifest.updated = SystemTime::now()
                         .duration_since(SystemTime::UNIX_EPOCH)
@@ -375,7 +895,18 @@ async fn index_directory_inner(
                         .as_secs();
                     save_manifest(&manifest_path, &manifest)?;
                 }
+                Err(e) if ck_core::is_cancelled(&e) => break,
+                Err(e) if is_timeout(&e) => {
+                    tracing::warn!("Skipped {:?}: {}", file_path, e);
+                    if let Some(log) = index_log {
+                        log.timed_out(file_path, &e, started.elapsed());
+                    }
+                    timed_out.lock().unwrap().push(file_path.clone());
+                }
                 Err(e) => {
+                    if let Some(log) = index_log {
+                        log.skipped(file_path, e.to_string());
+                    }
                     // Suppress warnings for binary files and UTF-8 errors in .git directories
                     let error_msg = e.to_string();
                     let is_binary_skip = error_msg.contains("Binary file, skipping");
@@ -396,40 +927,82 @@ async fn index_directory_inner(
         let (tx, rx) = mpsc::channel();
         let files_clone = files.clone();
         let path_clone = path.to_path_buf();
+        let worker_timed_out = Arc::clone(&timed_out);
+        let worker_log = index_log.cloned();
+        let worker_cancel = cancel.cloned();
+        let pool = WorkerPool::new(jobs)?;
 
         // Spawn worker thread for parallel processing
         let worker_handle = thread::spawn(move || {
-            files_clone.par_iter().for_each(|file_path| {
-                match index_single_file(file_path, &path_clone, None) {
-                    Ok(entry) => {
-                        if tx.send((file_path.clone(), entry)).is_err() {
-                            // Receiver dropped, stop processing
-                        }
+            pool.install(|| {
+                files_clone.par_iter().try_for_each(|file_path| {
+                    if INTERRUPTED.load(Ordering::SeqCst)
+                        || worker_cancel
+                            .as_ref()
+                            .is_some_and(CancellationToken::is_cancelled)
+                    {
+                        return Err(());
                     }
-                    Err(e) => {
-                        // Suppress warnings for binary files and UTF-8 errors in .git directories
-                        let error_msg = e.to_string();
-                        let is_binary_skip = error_msg.contains("Binary file, skipping");
-                        let is_utf8_error =
-                            error_msg.contains("stream did not contain valid UTF-8");
-                        let is_git_file = file_path.components().any(|c| c.as_os_str() == ".git");
-
-                        if !(is_binary_skip || is_utf8_error && is_git_file) {
-                            tracing::warn!("Failed to index {:?}: {}", file_path, e);
+                    let started = std::time::Instant::now();
+                    match index_single_file(file_path, &path_clone, None, chunking, file_timeout) {
+                        Ok(entry) => {
+                            if tx
+                                .send((file_path.clone(), entry, started.elapsed()))
+                                .is_err()
+                            {
+                                // Receiver dropped, stop processing
+                                return Err(());
+                            }
+                        }
+                        Err(e) if is_timeout(&e) => {
+                            tracing::warn!("Skipped {:?}: {}", file_path, e);
+                            if let Some(log) = &worker_log {
+                                log.timed_out(file_path, &e, started.elapsed());
+                            }
+                            worker_timed_out.lock().unwrap().push(file_path.clone());
+                        }
+                        Err(e) => {
+                            if let Some(log) = &worker_log {
+                                log.skipped(file_path, e.to_string());
+                            }
+                            // Suppress warnings for binary files and UTF-8 errors in .git directories
+                            let error_msg = e.to_string();
+                            let is_binary_skip = error_msg.contains("Binary file, skipping");
+                            let is_utf8_error =
+                                error_msg.contains("stream did not contain valid UTF-8");
+                            let is_git_file =
+                                file_path.components().any(|c| c.as_os_str() == ".git");
+
+                            if !(is_binary_skip || is_utf8_error && is_git_file) {
+                                tracing::warn!("Failed to index {:?}: {}", file_path, e);
+                            }
                         }
                     }
-                }
-            });
+                    Ok(())
+                })
+            })
         });
 
         // Main thread: stream results as they arrive
-        while let Ok((file_path, entry)) = rx.recv() {
+        while let Ok((file_path, entry, duration)) = rx.recv() {
+            if cancelled() || INTERRUPTED.load(Ordering::SeqCst) {
+                drop(rx); // Drop receiver to signal worker to stop
+                break;
+            }
+            report_progress(&file_path);
             // Write sidecar immediately
             let sidecar_path = get_sidecar_path(path, &file_path);
-            save_index_entry(&sidecar_path, &entry)?;
+            if let Err(e) = save_index_entry(&sidecar_path, &entry, manifest.sidecar_encoding()) {
+                sidecar_write_failed(&mut unwritable, index_log, &file_path, &e);
+                continue;
+            }
+            if let Some(log) = index_log {
+                log.indexed(&file_path, entry.chunks.len(), duration);
+            }
 
             // Update and save manifest immediately
             let manifest_key = entry.metadata.path.clone();
+            checkpoint.record(&manifest_key, &entry.metadata.hash)?;
             manifest.files.insert(manifest_key, entry.metadata);
             manifest.updated = SystemTime::now()
                 .duration_since(SystemTime::UNIX_EPOCH)
@@ -438,8 +1011,8 @@ async fn index_directory_inner(
             save_manifest(&manifest_path, &manifest)?;
         }
 
-        // Wait for worker to complete
-        worker_handle
+        // Wait for worker to complete; an early stop is reported by `cancelled`
+        let _ = worker_handle
             .join()
             .map_err(|_| anyhow::anyhow!("Worker thread panicked"))?;
     }
@@ -454,7 +1027,51 @@ async fn index_directory_inner(
         save_manifest(&manifest_path, &manifest)?;
     }
 
-    Ok(())
+    if let Some(log) = index_log {
+        log.flush()?;
+    }
+    if cancelled() {
+        return Err(CkError::Cancelled.into());
+    }
+    // A pass that ran to the end leaves nothing to resume
+    drop(checkpoint);
+    if !INTERRUPTED.load(Ordering::SeqCst) {
+        checkpoint::remove_checkpoint(&index_dir)?;
+    }
+    let timed_out_files = std::mem::take(&mut *timed_out.lock().unwrap());
+    Ok(UpdateStats {
+        files_errored: unwritable.len(),
+        timed_out_files,
+        unreadable,
+        unwritable,
+        ..Default::default()
+    })
+}
+
+/// Record a file whose sidecar couldn't be written even after retrying. It
+/// stays out of the manifest, so the next update tries it again, and
+/// indexing goes on with the other files.
+fn sidecar_write_failed(
+    unwritable: &mut Vec<(PathBuf, String)>,
+    index_log: Option<&IndexLog>,
+    file_path: &Path,
+    err: &anyhow::Error,
+) {
+    tracing::warn!(
+        "Failed to write the index entry of {:?}: {:#}",
+        file_path,
+        err
+    );
+    if let Some(log) = index_log {
+        log.skipped(file_path, format!("index entry not written: {err:#}"));
+    }
+    unwritable.push((file_path.to_path_buf(), format!("{err:#}")));
+}
+
+fn log_unreadable(log: &IndexLog, unreadable: &[(PathBuf, String)]) {
+    for (path, reason) in unreadable {
+        log.skipped(path, format!("unreadable: {reason}"));
+    }
 }
 
 pub async fn index_file(file_path: &Path, compute_embeddings: bool) -> Result<()> {
@@ -464,35 +1081,29 @@ pub async fn index_file(file_path: &Path, compute_embeddings: bool) -> Result<()
 
     let manifest_path = index_dir.join("manifest.json");
     let mut manifest = load_or_create_manifest(&manifest_path)?;
+    let chunking = ChunkSettings::of(&manifest);
 
     let entry = if compute_embeddings {
-        let model_registry = ck_models::ModelRegistry::default();
-        let (alias, config) = if let Some(existing) = manifest.embedding_model.as_deref() {
-            match model_registry.resolve(Some(existing)) {
-                Ok(resolved) => resolved,
-                Err(_) => (
-                    existing.to_string(),
-                    legacy_model_config(existing, manifest.embedding_dimensions),
-                ),
-            }
-        } else {
-            model_registry
-                .resolve(None)
-                .map_err(|e| anyhow::anyhow!(e.to_string()))?
-        };
-
-        manifest.embedding_model = Some(config.name.clone());
-        manifest.embedding_dimensions = Some(config.dimensions);
-        tracing::debug!("Using embedding model '{}' ({})", config.name, alias);
-
-        let mut embedder = ck_embed::create_embedder_for_config(&config, None)?;
-        index_single_file(file_path, &repo_root, Some(&mut embedder))?
+        let mut embedders = manifest_embedders(&mut manifest)?;
+        index_single_file(
+            file_path,
+            &repo_root,
+            Some(embedders.for_file(file_path)?),
+            chunking,
+            Some(DEFAULT_FILE_TIMEOUT),
+        )?
     } else {
-        index_single_file(file_path, &repo_root, None)?
+        index_single_file(
+            file_path,
+            &repo_root,
+            None,
+            chunking,
+            Some(DEFAULT_FILE_TIMEOUT),
+        )?
     };
     let sidecar_path = get_sidecar_path(&repo_root, file_path);
 
-    save_index_entry(&sidecar_path, &entry)?;
+    save_index_entry(&sidecar_path, &entry, manifest.sidecar_encoding())?;
     let manifest_key = entry.metadata.path.clone();
     manifest.files.insert(manifest_key, entry.metadata);
     manifest.updated = SystemTime::now()
@@ -505,34 +1116,185 @@ pub async fn index_file(file_path: &Path, compute_embeddings: bool) -> Result<()
     Ok(())
 }
 
-pub async fn update_index(
-    path: &Path,
+/// The embedders for the model the index was built with (the default model
+/// for a new index), recording it in the manifest, and its per-language models.
+fn manifest_embedders(manifest: &mut IndexManifest) -> Result<FileEmbedders> {
+    let model_registry = ck_models::ModelRegistry::default();
+    let (alias, config) = if let Some(existing) = manifest.embedding_model.as_deref() {
+        match model_registry.resolve(Some(existing)) {
+            Ok(resolved) => resolved,
+            Err(_) => (
+                existing.to_string(),
+                legacy_model_config(existing, manifest.embedding_dimensions),
+            ),
+        }
+    } else {
+        model_registry
+            .resolve(None)
+            .map_err(|e| anyhow::anyhow!(e.to_string()))?
+    };
+
+    manifest.embedding_model = Some(config.name.clone());
+    manifest.embedding_dimensions = Some(config.dimensions);
+    tracing::debug!("Using embedding model '{}' ({})", config.name, alias);
+
+    FileEmbedders::new(&config, manifest)
+}
+
+/// What [`add_paths`] did with the files it was given.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct AddStats {
+    pub files_added: usize,
+    pub files_updated: usize,
+    pub files_up_to_date: usize,
+}
+
+/// Index the given files and directories without rescanning the rest of the
+/// tree (`ck --add`). Directories are walked with the usual exclude,
+/// `.gitignore` and `.ckignore` rules; files named directly are indexed as
+/// given. A file is only re-chunked and re-embedded when it is new or its
+/// content changed since it was last indexed. `embed_batch_size` and
+/// `force_hash` are as in [`IndexUpdateOptions`].
+pub async fn add_paths(
+    paths: &[PathBuf],
     compute_embeddings: bool,
     options: &ck_core::FileCollectionOptions,
-) -> Result<()> {
-    let index_dir = ck_core::index_dir(path);
-    let index_existed = index_dir.exists();
-    let _lock = acquire_index_write_lock(&index_dir)?;
-    if !index_existed {
-        return index_directory_inner(
-            path,
-            compute_embeddings,
-            options,
-            None, // model - use existing from manifest for update
-        )
-        .await;
+    embed_batch_size: Option<usize>,
+    force_hash: bool,
+) -> Result<AddStats> {
+    // Paths may belong to different indexes; each is updated under its own lock
+    let mut by_root: std::collections::BTreeMap<PathBuf, std::collections::BTreeSet<PathBuf>> =
+        std::collections::BTreeMap::new();
+    for path in paths {
+        if !path.exists() {
+            anyhow::bail!("{} does not exist", path.display());
+        }
+        let files = if path.is_dir() {
+            collect_files(path, options)?
+        } else {
+            vec![path.clone()]
+        };
+        by_root
+            .entry(find_repo_root(path)?)
+            .or_default()
+            .extend(files);
     }
 
-    let manifest_path = index_dir.join("manifest.json");
-    let mut manifest = load_or_create_manifest(&manifest_path)?;
-
-    let files = collect_files(path, options)?;
+    let mut stats = AddStats::default();
+    for (repo_root, files) in by_root {
+        let index_dir = ck_core::index_dir(&repo_root);
+        let _lock = acquire_index_write_lock(&index_dir)?;
 
-    let updates: Vec<(PathBuf, IndexEntry)> = if compute_embeddings {
-        // Sequential processing when computing embeddings (for memory efficiency)
-        let model_registry = ck_models::ModelRegistry::default();
-        let (alias, config) = if let Some(existing) = manifest.embedding_model.as_deref() {
-            match model_registry.resolve(Some(existing)) {
+        let manifest_path = index_dir.join("manifest.json");
+        let mut manifest = load_or_create_manifest(&manifest_path)?;
+        let chunking = ChunkSettings {
+            embed_batch_size,
+            ..ChunkSettings::of(&manifest)
+        };
+        let mut embedders = None;
+
+        for file_path in files {
+            let manifest_key =
+                path_utils::to_manifest_path(&path_utils::to_standard_path(&file_path, &repo_root));
+            let is_new = match classify_file_change(
+                &file_path,
+                &manifest_key,
+                manifest.files.get(&manifest_key),
+                force_hash,
+            )? {
+                FileChange::Unchanged => {
+                    stats.files_up_to_date += 1;
+                    continue;
+                }
+                FileChange::Touched(metadata) => {
+                    stats.files_up_to_date += 1;
+                    manifest.files.insert(manifest_key, metadata);
+                    continue;
+                }
+                FileChange::Modified => false,
+                FileChange::Added => true,
+            };
+
+            if compute_embeddings && embedders.is_none() {
+                embedders = Some(manifest_embedders(&mut manifest)?);
+            }
+            let embedder = match embedders.as_mut() {
+                Some(embedders) => Some(embedders.for_file(&file_path)?),
+                None => None,
+            };
+            let entry = index_single_file(
+                &file_path,
+                &repo_root,
+                embedder,
+                chunking,
+                Some(DEFAULT_FILE_TIMEOUT),
+            )?;
+            save_index_entry(
+                &get_sidecar_path(&repo_root, &file_path),
+                &entry,
+                manifest.sidecar_encoding(),
+            )?;
+            manifest.files.insert(manifest_key, entry.metadata);
+            if is_new {
+                stats.files_added += 1;
+            } else {
+                stats.files_updated += 1;
+            }
+        }
+
+        manifest.updated = SystemTime::now()
+            .duration_since(SystemTime::UNIX_EPOCH)
+            .unwrap()
+            .as_secs();
+        save_manifest(&manifest_path, &manifest)?;
+        refresh_ann_index(&repo_root)?;
+    }
+
+    Ok(stats)
+}
+
+pub async fn update_index(
+    path: &Path,
+    compute_embeddings: bool,
+    options: &ck_core::FileCollectionOptions,
+    embed_batch_size: Option<usize>,
+    jobs: Option<usize>,
+) -> Result<()> {
+    let index_dir = ck_core::index_dir(path);
+    let index_existed = index_dir.exists();
+    let _lock = acquire_index_write_lock(&index_dir)?;
+    if !index_existed {
+        // No model: use the one from the manifest for an update
+        index_directory_inner(
+            path,
+            options,
+            None,
+            &IndexUpdateOptions {
+                compute_embeddings,
+                embed_batch_size,
+                jobs,
+                ..Default::default()
+            },
+        )
+        .await?;
+        refresh_ann_index(path)?;
+        return Ok(());
+    }
+
+    let manifest_path = index_dir.join("manifest.json");
+    let mut manifest = load_or_create_manifest(&manifest_path)?;
+    let chunking = ChunkSettings {
+        embed_batch_size,
+        ..ChunkSettings::of(&manifest)
+    };
+
+    let files = collect_files(path, options)?;
+
+    let updates: Vec<(PathBuf, IndexEntry)> = if compute_embeddings {
+        // Sequential processing when computing embeddings (for memory efficiency)
+        let model_registry = ck_models::ModelRegistry::default();
+        let (alias, config) = if let Some(existing) = manifest.embedding_model.as_deref() {
+            match model_registry.resolve(Some(existing)) {
                 Ok(resolved) => resolved,
                 Err(_) => (
                     existing.to_string(),
@@ -553,7 +1315,7 @@ pub async fn update_index(
             alias
         );
 
-        let mut embedder = ck_embed::create_embedder_for_config(&config, None)?;
+        let mut embedders = FileEmbedders::new(&config, &manifest)?;
         files
             .iter()
             .filter_map(|file_path| {
@@ -568,7 +1330,15 @@ pub async fn update_index(
                     None => true,
                 };
                 if needs_update {
-                    match index_single_file(file_path, path, Some(&mut embedder)) {
+                    match embedders.for_file(file_path).and_then(|embedder| {
+                        index_single_file(
+                            file_path,
+                            path,
+                            Some(embedder),
+                            chunking,
+                            Some(DEFAULT_FILE_TIMEOUT),
+                        )
+                    }) {
                         Ok(entry) => Some((file_path.clone(), entry)),
                         Err(e) => {
                             // Suppress warnings for binary files and UTF-8 errors in .git directories
@@ -592,48 +1362,57 @@ pub async fn update_index(
             .collect()
     } else {
         // Parallel processing when not computing embeddings
-        files
-            .par_iter()
-            .filter_map(|file_path| {
-                let manifest_key =
-                    path_utils::to_manifest_path(&path_utils::to_standard_path(file_path, path));
-
-                let needs_update = match manifest.files.get(&manifest_key) {
-                    Some(metadata) => match compute_file_hash(file_path) {
-                        Ok(hash) => hash != metadata.hash,
-                        Err(_) => false,
-                    },
-                    None => true,
-                };
+        WorkerPool::new(jobs)?.install(|| {
+            files
+                .par_iter()
+                .filter_map(|file_path| {
+                    let manifest_key = path_utils::to_manifest_path(&path_utils::to_standard_path(
+                        file_path, path,
+                    ));
 
-                if needs_update {
-                    match index_single_file(file_path, path, None) {
-                        Ok(entry) => Some((file_path.clone(), entry)),
-                        Err(e) => {
-                            // Suppress warnings for binary files and UTF-8 errors in .git directories
-                            let error_msg = e.to_string();
-                            let is_binary_skip = error_msg.contains("Binary file, skipping");
-                            let is_utf8_error =
-                                error_msg.contains("stream did not contain valid UTF-8");
-                            let is_git_file =
-                                file_path.components().any(|c| c.as_os_str() == ".git");
+                    let needs_update = match manifest.files.get(&manifest_key) {
+                        Some(metadata) => match compute_file_hash(file_path) {
+                            Ok(hash) => hash != metadata.hash,
+                            Err(_) => false,
+                        },
+                        None => true,
+                    };
 
-                            if !(is_binary_skip || is_utf8_error && is_git_file) {
-                                tracing::warn!("Failed to index {:?}: {}", file_path, e);
+                    if needs_update {
+                        match index_single_file(
+                            file_path,
+                            path,
+                            None,
+                            chunking,
+                            Some(DEFAULT_FILE_TIMEOUT),
+                        ) {
+                            Ok(entry) => Some((file_path.clone(), entry)),
+                            Err(e) => {
+                                // Suppress warnings for binary files and UTF-8 errors in .git directories
+                                let error_msg = e.to_string();
+                                let is_binary_skip = error_msg.contains("Binary file, skipping");
+                                let is_utf8_error =
+                                    error_msg.contains("stream did not contain valid UTF-8");
+                                let is_git_file =
+                                    file_path.components().any(|c| c.as_os_str() == ".git");
+
+                                if !(is_binary_skip || is_utf8_error && is_git_file) {
+                                    tracing::warn!("Failed to index {:?}: {}", file_path, e);
+                                }
+                                None
                             }
-                            None
                         }
+                    } else {
+                        None
                     }
-                } else {
-                    None
-                }
-            })
-            .collect()
+                })
+                .collect()
+        })
     };
 
     for (file_path, entry) in updates {
         let sidecar_path = get_sidecar_path(path, &file_path);
-        save_index_entry(&sidecar_path, &entry)?;
+        save_index_entry(&sidecar_path, &entry, manifest.sidecar_encoding())?;
         let manifest_key = entry.metadata.path.clone();
         manifest.files.insert(manifest_key, entry.metadata);
     }
@@ -645,6 +1424,7 @@ pub async fn update_index(
             .as_secs();
         save_manifest(&manifest_path, &manifest)?;
     }
+    refresh_ann_index(path)?;
 
     Ok(())
 }
@@ -666,11 +1446,15 @@ pub fn clean_index(path: &Path) -> Result<()> {
 }
 
 /// Remove all index contents except the lock file (which the caller holds
-/// open — deleting an open locked file would fail on Windows).
+/// open — deleting an open locked file would fail on Windows) and the
+/// user-authored `config.toml`, which is settings rather than index data.
 fn clean_index_inner(index_dir: &Path) -> Result<()> {
     for entry in fs::read_dir(index_dir)? {
         let entry = entry?;
-        if entry.file_name().to_str() == Some(INDEX_LOCK_FILE) {
+        let name = entry.file_name();
+        if name.to_str() == Some(INDEX_LOCK_FILE)
+            || name.to_str() == Some(ck_core::config::CONFIG_FILE_NAME)
+        {
             continue;
         }
         let entry_path = entry.path();
@@ -713,6 +1497,7 @@ pub fn cleanup_index(
             .unwrap()
             .as_secs();
         save_manifest(&manifest_path, &manifest)?;
+        refresh_ann_index(path)?;
     }
 
     Ok(stats)
@@ -732,6 +1517,9 @@ pub fn get_index_stats(path: &Path) -> Result<IndexStats> {
         total_files: manifest.files.len(),
         index_created: manifest.created,
         index_updated: manifest.updated,
+        compressed_sidecars: manifest.compress_sidecars,
+        quantization: manifest.quantization,
+        reduced_dimensions: manifest.reduced_dimensions,
         ..Default::default()
     };
 
@@ -740,6 +1528,9 @@ pub fn get_index_stats(path: &Path) -> Result<IndexStats> {
         let standard_path = path_utils::from_manifest_path(file_path);
         let sidecar_path =
             path_utils::get_sidecar_path_for_standard_path(&index_dir, &standard_path);
+        if !sidecar_path.exists() || !path.join(&standard_path).exists() {
+            stats.orphaned_files.push(standard_path.clone());
+        }
         if sidecar_path.exists()
             && let Ok(entry) = load_index_entry(&sidecar_path)
         {
@@ -753,14 +1544,32 @@ pub fn get_index_stats(path: &Path) -> Result<IndexStats> {
                 .filter(|c| c.embedding.is_some())
                 .count();
             stats.embedded_chunks += embedded;
+
+            let language = ck_core::Language::from_path(&standard_path)
+                .map(|lang| lang.to_string())
+                .unwrap_or_else(|| "other".to_string());
+            let counts = stats.by_language.entry(language).or_default();
+            counts.files += 1;
+            counts.chunks += entry.chunks.len();
+
+            // Sidecars written before models were recorded per file fall back
+            // to the manifest's model.
+            if embedded > 0
+                && let Some(model) = entry
+                    .embedding_model
+                    .as_ref()
+                    .or(manifest.embedding_model.as_ref())
+                && !stats.models_used.contains(model)
+            {
+                stats.models_used.push(model.clone());
+            }
         }
     }
+    stats.models_used.sort();
+    stats.orphaned_files.sort();
 
     // Calculate index size on disk
-    if let Ok(entries) = WalkDir::new(&index_dir)
-        .into_iter()
-        .collect::<Result<Vec<_>, _>>()
-    {
+    if let Ok(entries) = walk_index_dir(&index_dir).collect::<Result<Vec<_>, _>>() {
         for entry in entries {
             if entry.file_type().is_file()
                 && let Ok(metadata) = entry.metadata()
@@ -819,6 +1628,108 @@ pub async fn smart_update_index_with_detailed_progress(
     options: &ck_core::FileCollectionOptions,
     model: Option<&str>,
 ) -> Result<UpdateStats> {
+    smart_update_index_with_resume(
+        path,
+        options,
+        progress_callback,
+        detailed_progress_callback,
+        IndexUpdateOptions {
+            force_rebuild,
+            compute_embeddings,
+            model,
+            ..Default::default()
+        },
+    )
+    .await
+}
+
+/// How [`smart_update_index_with_resume`] updates an index. The default is an
+/// incremental update without embeddings, in the index's model and chunking
+/// settings, with [`DEFAULT_FILE_TIMEOUT`] per file.
+#[derive(Clone, Copy)]
+pub struct IndexUpdateOptions<'a> {
+    /// Wipe the index and index every file again
+    pub force_rebuild: bool,
+    pub compute_embeddings: bool,
+    /// Embedding model name or alias; `None` keeps the index's
+    pub model: Option<&'a str>,
+    /// Continue from the checkpoint an interrupted pass left behind: files it
+    /// completed are skipped unless their content changed since. With
+    /// `force_rebuild`, an existing checkpoint means the rebuild already
+    /// started, so the index is not wiped again. Without a checkpoint this
+    /// has no effect.
+    pub resume: bool,
+    /// Lines of context shared between consecutive chunks, stored in the
+    /// manifest; `None` keeps the index's setting
+    pub chunk_overlap: Option<usize>,
+    /// How files are split, stored in the manifest; `None` keeps the index's
+    pub chunk_strategy: Option<ck_chunk::ChunkStrategy>,
+    /// A file that takes longer than this to chunk and embed is skipped and
+    /// listed in [`UpdateStats::timed_out_files`]; `None` means no limit
+    pub file_timeout: Option<Duration>,
+    /// Chunks embedded per model call (`--embed-batch-size`), clamped to the
+    /// model's maximum; `None` for the model's default
+    pub embed_batch_size: Option<usize>,
+    /// Hash every file when checking for changes (`--force-hash`) rather than
+    /// trusting one whose mtime and size match the manifest. Checkouts and
+    /// some build tools leave edited files with their old timestamp.
+    pub force_hash: bool,
+    /// Files indexed at once (`--jobs`); `None` for one per CPU
+    pub jobs: Option<usize>,
+    /// Where to record why each file was indexed or skipped
+    pub index_log: Option<&'a IndexLog>,
+    /// Stops the pass between files, embedding batches and chunks
+    pub cancel: Option<&'a CancellationToken>,
+}
+
+impl Default for IndexUpdateOptions<'_> {
+    fn default() -> Self {
+        Self {
+            force_rebuild: false,
+            compute_embeddings: false,
+            model: None,
+            resume: false,
+            chunk_overlap: None,
+            chunk_strategy: None,
+            file_timeout: Some(DEFAULT_FILE_TIMEOUT),
+            embed_batch_size: None,
+            force_hash: false,
+            jobs: None,
+            index_log: None,
+            cancel: None,
+        }
+    }
+}
+
+/// Index the files under `path` as `update` describes, reporting progress
+/// through the callbacks.
+///
+/// Cancelling `update.cancel` makes the pass fail with
+/// [`CkError::Cancelled`]. Files already finished keep their sidecars and
+/// manifest entries; the one in progress is left as it was, and the
+/// checkpoint stays so `resume` can pick up from there.
+pub async fn smart_update_index_with_resume(
+    path: &Path,
+    options: &ck_core::FileCollectionOptions,
+    progress_callback: Option<ProgressCallback>,
+    detailed_progress_callback: Option<DetailedProgressCallback>,
+    update: IndexUpdateOptions<'_>,
+) -> Result<UpdateStats> {
+    let IndexUpdateOptions {
+        force_rebuild,
+        compute_embeddings,
+        model,
+        resume,
+        chunk_overlap,
+        chunk_strategy,
+        file_timeout,
+        embed_batch_size,
+        force_hash,
+        jobs,
+        index_log,
+        cancel,
+    } = update;
+    let cancelled = || cancel.is_some_and(CancellationToken::is_cancelled);
     let index_dir = ck_core::index_dir(path);
     let _lock = acquire_index_write_lock(&index_dir)?;
     // Guard against a CK_INDEX_DIR basename-hash collision before any
@@ -838,13 +1749,28 @@ pub async fn smart_update_index_with_detailed_progress(
     // Reset interrupt flag for this indexing operation
     INTERRUPTED.store(false, Ordering::SeqCst);
 
-    if force_rebuild {
+    let resume = resume && has_checkpoint(path);
+    if force_rebuild && !resume {
         // Use the unlocked variants: we already hold the index write lock,
         // and a second acquisition on a fresh handle would self-deadlock.
+        let compress = index_sidecar_compression(path);
+        let quantization = index_quantization(path);
+        let ann_index = index_has_ann(path);
         clean_index_inner(&index_dir)?;
-        index_directory_inner(path, compute_embeddings, options, model).await?;
+        if compress || quantization != Quantization::None || ann_index {
+            // The rebuilt index keeps its sidecar encoding and ANN index
+            let manifest = IndexManifest {
+                compress_sidecars: compress,
+                quantization,
+                ann_index,
+                ..Default::default()
+            };
+            save_manifest(&index_dir.join("manifest.json"), &manifest)?;
+        }
+        stats = index_directory_inner(path, options, progress_callback.as_ref(), &update).await?;
         let index_stats = get_index_stats(path)?;
         stats.files_indexed = index_stats.total_files;
+        stats.ann_vectors = refresh_ann_index(path)?;
         return Ok(stats);
     }
 
@@ -861,6 +1787,12 @@ pub async fn smart_update_index_with_detailed_progress(
     let manifest_path = index_dir.join("manifest.json");
     let mut manifest = load_or_create_manifest(&manifest_path)?;
     normalize_manifest_paths(&mut manifest, &repo_root);
+    let previous_chunking = (manifest.chunk_overlap_lines, manifest.chunk_strategy);
+    let chunking = ChunkSettings {
+        overlap_lines: apply_chunk_overlap_setting(&mut manifest, chunk_overlap)?,
+        strategy: apply_chunk_strategy_setting(&mut manifest, chunk_strategy)?,
+        embed_batch_size,
+    };
 
     // Handle model configuration for embeddings
     let resolved_model = if compute_embeddings {
@@ -906,15 +1838,32 @@ pub async fn smart_update_index_with_detailed_progress(
 
     // For incremental updates, only process files in the search scope
     // The cleanup phase already handled removing orphaned files from the entire repo
-    let current_files = collect_files(path, options)?;
+    let (current_files, unreadable) = collect_files_with_unreadable(path, options)?;
+    if let Some(log) = index_log {
+        log.excluded(options)?;
+        log_unreadable(log, &unreadable);
+    }
+    stats.unreadable = unreadable;
 
     // First pass: determine which files need updating and collect stats
     let mut files_to_update = Vec::new();
-    let mut manifest_changed = false;
+    let mut manifest_changed =
+        (manifest.chunk_overlap_lines, manifest.chunk_strategy) != previous_chunking;
+    let completed = if resume {
+        checkpoint::load_checkpoint(&index_dir)
+    } else {
+        HashMap::new()
+    };
 
     for file_path in current_files {
         // Check for interrupt
-        if INTERRUPTED.load(Ordering::SeqCst) {
+        if INTERRUPTED.load(Ordering::SeqCst) || cancelled() {
+            if let Some(log) = index_log {
+                log.flush()?;
+            }
+            if cancelled() {
+                return Err(CkError::Cancelled.into());
+            }
             eprintln!("Indexing interrupted during file scanning.");
             return Ok(stats);
         }
@@ -922,73 +1871,82 @@ pub async fn smart_update_index_with_detailed_progress(
         let manifest_key =
             path_utils::to_manifest_path(&path_utils::to_standard_path(&file_path, &repo_root));
 
-        if let Some(metadata) = manifest.files.get(&manifest_key) {
-            let fs_meta = match fs::metadata(&file_path) {
-                Ok(m) => m,
-                Err(_) => {
-                    stats.files_errored += 1;
-                    continue;
-                }
-            };
-
-            let fs_last_modified = match fs_meta.modified().and_then(|m| {
-                m.duration_since(SystemTime::UNIX_EPOCH)
-                    .map_err(|_| std::io::Error::other("Time error"))
-            }) {
-                Ok(dur) => dur.as_secs(),
-                Err(_) => {
-                    stats.files_errored += 1;
-                    continue;
+        // Completed by the interrupted pass and unchanged since: keep its
+        // sidecar, restoring the manifest entry if that write was lost.
+        if let Some(checkpoint_hash) = completed.get(&manifest_key)
+            && compute_file_hash(&file_path).is_ok_and(|hash| &hash == checkpoint_hash)
+        {
+            if manifest.files.contains_key(&manifest_key) {
+                stats.files_up_to_date += 1;
+                if let Some(log) = index_log {
+                    log.skipped(&file_path, "indexed by the interrupted pass");
                 }
-            };
-            let fs_size = fs_meta.len();
-
-            if fs_last_modified == metadata.last_modified && fs_size == metadata.size {
+                continue;
+            }
+            if let Ok(entry) = load_index_entry(&get_sidecar_path(path, &file_path)) {
+                manifest.files.insert(manifest_key, entry.metadata);
+                manifest_changed = true;
                 stats.files_up_to_date += 1;
+                if let Some(log) = index_log {
+                    log.skipped(&file_path, "indexed by the interrupted pass");
+                }
                 continue;
             }
+        }
 
-            let hash = match compute_file_hash(&file_path) {
-                Ok(h) => h,
-                Err(_) => {
-                    stats.files_errored += 1;
-                    continue;
+        match classify_file_change(
+            &file_path,
+            &manifest_key,
+            manifest.files.get(&manifest_key),
+            force_hash,
+        ) {
+            Ok(FileChange::Unchanged) => {
+                stats.files_up_to_date += 1;
+                if let Some(log) = index_log {
+                    log.skipped(&file_path, "unchanged");
                 }
-            };
-
-            if hash != metadata.hash {
-                stats.files_modified += 1;
-                files_to_update.push(file_path);
-            } else {
+            }
+            Ok(FileChange::Touched(metadata)) => {
+                // Content is the same; refresh the recorded mtime and size
                 stats.files_up_to_date += 1;
-                // Convert to standardized path for manifest storage
-                let standard_path = path_utils::to_standard_path(&file_path, &repo_root);
-                let manifest_path = path_utils::to_manifest_path(&standard_path);
-                let new_metadata = FileMetadata {
-                    path: manifest_path.clone(),
-                    hash,
-                    last_modified: fs_last_modified,
-                    size: fs_size,
-                };
-                manifest.files.insert(manifest_path, new_metadata);
+                manifest.files.insert(manifest_key, metadata);
                 manifest_changed = true;
+                if let Some(log) = index_log {
+                    log.skipped(&file_path, "unchanged (only its timestamp changed)");
+                }
+            }
+            Ok(FileChange::Modified) => {
+                stats.files_modified += 1;
+                files_to_update.push(file_path);
+            }
+            Ok(FileChange::Added) => {
+                stats.files_added += 1;
+                files_to_update.push(file_path);
+            }
+            Err(e) => {
+                stats.files_errored += 1;
+                if let Some(log) = index_log {
+                    log.skipped(&file_path, e.to_string());
+                }
             }
-        } else {
-            stats.files_added += 1;
-            files_to_update.push(file_path);
         }
     }
 
-    // Second pass: index the files that need updating
+    // Second pass: index the files that need updating, recording each
+    // finished file so an interrupted pass can be resumed
+    let mut checkpoint = checkpoint::CheckpointWriter::open(&index_dir, resume)?;
     if compute_embeddings {
         // Sequential processing with streaming - write each file immediately
         let (_, config) = resolved_model
             .as_ref()
             .expect("resolved model must exist for embedding updates");
-        let mut embedder = ck_embed::create_embedder_for_config(config, None)?;
+        let mut embedders = FileEmbedders::new(config, &manifest)?;
         let mut _processed_count = 0;
 
         for file_path in files_to_update.iter() {
+            if cancelled() {
+                break;
+            }
             // Check for interrupt
             if INTERRUPTED.load(Ordering::SeqCst) {
                 eprintln!("Indexing interrupted. {_processed_count} files processed.");
@@ -1002,17 +1960,32 @@ pub async fn smart_update_index_with_detailed_progress(
             }
 
             // Call detailed progress version if callback is provided, otherwise use regular version
+            let started = std::time::Instant::now();
+            let embedder = embedders.for_file(file_path)?;
             let result = if let Some(ref detailed_callback) = detailed_progress_callback {
                 index_single_file_with_progress(
                     file_path,
                     path,
-                    Some(&mut embedder),
+                    Some(embedder),
+                    chunking,
+                    file_timeout,
+                    cancel,
                     Some(detailed_callback),
                     _processed_count,
                     files_to_update.len(),
                 )
             } else {
-                index_single_file_with_progress(file_path, path, Some(&mut embedder), None, 0, 1)
+                index_single_file_with_progress(
+                    file_path,
+                    path,
+                    Some(embedder),
+                    chunking,
+                    file_timeout,
+                    cancel,
+                    None,
+                    0,
+                    1,
+                )
             };
 
             match result {
@@ -1023,10 +1996,20 @@ pub async fn smart_update_index_with_detailed_progress(
 
                     // Write sidecar immediately
                     let sidecar_path = get_sidecar_path(path, file_path);
-                    save_index_entry(&sidecar_path, &entry)?;
+                    if let Err(e) =
+                        save_index_entry(&sidecar_path, &entry, manifest.sidecar_encoding())
+                    {
+                        sidecar_write_failed(&mut stats.unwritable, index_log, file_path, &e);
+                        stats.files_errored += 1;
+                        continue;
+                    }
+                    if let Some(log) = index_log {
+                        log.indexed(file_path, entry.chunks.len(), started.elapsed());
+                    }
 
                     // Update and save manifest immediately
                     let manifest_key = entry.metadata.path.clone();
+                    checkpoint.record(&manifest_key, &entry.metadata.hash)?;
                     manifest.files.insert(manifest_key, entry.metadata);
                     manifest.updated = SystemTime::now()
                         .duration_since(SystemTime::UNIX_EPOCH)
@@ -1035,7 +2018,18 @@ pub async fn smart_update_index_with_detailed_progress(
                     save_manifest(&manifest_path, &manifest)?;
                     _processed_count += 1;
                 }
+                Err(e) if ck_core::is_cancelled(&e) => break,
+                Err(e) if is_timeout(&e) => {
+                    tracing::warn!("Skipped {:?}: {}", file_path, e);
+                    if let Some(log) = index_log {
+                        log.timed_out(file_path, &e, started.elapsed());
+                    }
+                    stats.timed_out_files.push(file_path.clone());
+                }
                 Err(e) => {
+                    if let Some(log) = index_log {
+                        log.skipped(file_path, e.to_string());
+                    }
                     // Suppress warnings for binary files and UTF-8 errors in .git directories
                     let error_msg = e.to_string();
                     let is_binary_skip = error_msg.contains("Binary file, skipping");
@@ -1059,39 +2053,67 @@ pub async fn smart_update_index_with_detailed_progress(
         let (tx, rx) = mpsc::channel();
         let files_clone = files_to_update.clone();
         let path_clone = path.to_path_buf();
+        let timed_out = Arc::new(Mutex::new(Vec::new()));
+        let worker_timed_out = Arc::clone(&timed_out);
+        let worker_log = index_log.cloned();
+        let worker_cancel = cancel.cloned();
+        let pool = WorkerPool::new(jobs)?;
 
         // Spawn worker thread for parallel processing
         let worker_handle = thread::spawn(move || {
             use rayon::prelude::*;
 
             // Use par_iter with try_for_each to allow early exit on interrupt
-            let result = files_clone.par_iter().try_for_each(|file_path| {
-                // Check for interrupt
-                if INTERRUPTED.load(Ordering::SeqCst) {
-                    return Err("interrupted");
-                }
+            let result = pool.install(|| {
+                files_clone.par_iter().try_for_each(|file_path| {
+                    // Check for interrupt
+                    if INTERRUPTED.load(Ordering::SeqCst) {
+                        return Err("interrupted");
+                    }
+                    if worker_cancel
+                        .as_ref()
+                        .is_some_and(CancellationToken::is_cancelled)
+                    {
+                        return Err("cancelled");
+                    }
 
-                match index_single_file(file_path, &path_clone, None) {
-                    Ok(entry) => {
-                        if tx.send((file_path.clone(), entry)).is_err() {
-                            // Receiver dropped, stop processing
-                            return Err("receiver_dropped");
+                    let started = std::time::Instant::now();
+                    match index_single_file(file_path, &path_clone, None, chunking, file_timeout) {
+                        Ok(entry) => {
+                            if tx
+                                .send((file_path.clone(), entry, started.elapsed()))
+                                .is_err()
+                            {
+                                // Receiver dropped, stop processing
+                                return Err("receiver_dropped");
+                            }
                         }
-                    }
-                    Err(e) => {
-                        // Suppress warnings for binary files and UTF-8 errors in .git directories
-                        let error_msg = e.to_string();
-                        let is_binary_skip = error_msg.contains("Binary file, skipping");
-                        let is_utf8_error =
-                            error_msg.contains("stream did not contain valid UTF-8");
-                        let is_git_file = file_path.components().any(|c| c.as_os_str() == ".git");
-
-                        if !(is_binary_skip || is_utf8_error && is_git_file) {
-                            tracing::warn!("Failed to index {:?}: {}", file_path, e);
+                        Err(e) if is_timeout(&e) => {
+                            tracing::warn!("Skipped {:?}: {}", file_path, e);
+                            if let Some(log) = &worker_log {
+                                log.timed_out(file_path, &e, started.elapsed());
+                            }
+                            worker_timed_out.lock().unwrap().push(file_path.clone());
+                        }
+                        Err(e) => {
+                            if let Some(log) = &worker_log {
+                                log.skipped(file_path, e.to_string());
+                            }
+                            // Suppress warnings for binary files and UTF-8 errors in .git directories
+                            let error_msg = e.to_string();
+                            let is_binary_skip = error_msg.contains("Binary file, skipping");
+                            let is_utf8_error =
+                                error_msg.contains("stream did not contain valid UTF-8");
+                            let is_git_file =
+                                file_path.components().any(|c| c.as_os_str() == ".git");
+
+                            if !(is_binary_skip || is_utf8_error && is_git_file) {
+                                tracing::warn!("Failed to index {:?}: {}", file_path, e);
+                            }
                         }
                     }
-                }
-                Ok(())
+                    Ok(())
+                })
             });
 
             // Log the result for debugging
@@ -1102,7 +2124,11 @@ pub async fn smart_update_index_with_detailed_progress(
 
         // Main thread: stream results as they arrive
         let mut _processed_count = 0;
-        while let Ok((file_path, entry)) = rx.recv() {
+        while let Ok((file_path, entry, duration)) = rx.recv() {
+            if cancelled() {
+                drop(rx);
+                break;
+            }
             // Check for interrupt
             if INTERRUPTED.load(Ordering::SeqCst) {
                 eprintln!("Indexing interrupted. {_processed_count} files processed.");
@@ -1118,10 +2144,18 @@ pub async fn smart_update_index_with_detailed_progress(
 
             // Write sidecar immediately
             let sidecar_path = get_sidecar_path(path, &file_path);
-            save_index_entry(&sidecar_path, &entry)?;
+            if let Err(e) = save_index_entry(&sidecar_path, &entry, manifest.sidecar_encoding()) {
+                sidecar_write_failed(&mut stats.unwritable, index_log, &file_path, &e);
+                stats.files_errored += 1;
+                continue;
+            }
+            if let Some(log) = index_log {
+                log.indexed(&file_path, entry.chunks.len(), duration);
+            }
 
             // Update and save manifest immediately
             let manifest_key = entry.metadata.path.clone();
+            checkpoint.record(&manifest_key, &entry.metadata.hash)?;
             manifest.files.insert(manifest_key, entry.metadata);
             manifest.updated = SystemTime::now()
                 .duration_since(SystemTime::UNIX_EPOCH)
@@ -1137,6 +2171,7 @@ pub async fn smart_update_index_with_detailed_progress(
         worker_handle
             .join()
             .map_err(|_| anyhow::anyhow!("Worker thread panicked"))?;
+        stats.timed_out_files = std::mem::take(&mut *timed_out.lock().unwrap());
     }
 
     // For sequential processing (embeddings), manifest is already saved after each file
@@ -1149,62 +2184,585 @@ pub async fn smart_update_index_with_detailed_progress(
             .unwrap()
             .as_secs();
         save_manifest(&manifest_path, &manifest)?;
+    } else if compute_embeddings && manifest_changed {
+        save_manifest(&manifest_path, &manifest)?;
+    }
+
+    if let Some(log) = index_log {
+        log.flush()?;
+    }
+    if cancelled() {
+        return Err(CkError::Cancelled.into());
+    }
+    // A pass that ran to the end leaves nothing to resume
+    drop(checkpoint);
+    if !INTERRUPTED.load(Ordering::SeqCst) {
+        checkpoint::remove_checkpoint(&index_dir)?;
+        stats.ann_vectors = refresh_ann_index(path)?;
     }
 
     Ok(stats)
 }
 
-fn index_single_file(
-    file_path: &Path,
-    repo_root: &Path,
-    embedder: Option<&mut Box<dyn ck_embed::Embedder>>,
-) -> Result<IndexEntry> {
-    let (entry, _chunks_reused, _chunks_embedded) =
-        index_single_file_with_progress(file_path, repo_root, embedder, None, 0, 1)?;
-    Ok(entry)
+/// Chunking options an index was built with, applied to every file in it,
+/// and the embedding batch size of the current pass.
+#[derive(Debug, Clone, Copy, Default)]
+struct ChunkSettings {
+    overlap_lines: usize,
+    strategy: ck_chunk::ChunkStrategy,
+    /// `--embed-batch-size`; `None` for the model's default
+    embed_batch_size: Option<usize>,
 }
 
-fn index_single_file_with_progress(
-    file_path: &Path,
-    repo_root: &Path,
-    embedder: Option<&mut Box<dyn ck_embed::Embedder>>,
-    detailed_progress: Option<&DetailedProgressCallback>,
-    file_index: usize,
-    total_files: usize,
-) -> Result<(IndexEntry, usize, usize)> {
-    // Skip binary files to avoid UTF-8 warnings
-    if !is_text_file(file_path) {
-        return Err(anyhow::anyhow!("Binary file, skipping"));
+impl ChunkSettings {
+    fn of(manifest: &IndexManifest) -> Self {
+        Self {
+            overlap_lines: manifest.chunk_overlap_lines.unwrap_or(0),
+            strategy: manifest.chunk_strategy,
+            embed_batch_size: None,
+        }
     }
+}
 
-    // Build chunk cache from old sidecar if it exists (for chunk reuse)
-    let chunk_cache: HashMap<String, Vec<f32>> = if embedder.is_some() {
-        let sidecar_path = get_sidecar_path(repo_root, file_path);
-        if sidecar_path.exists() {
-            match load_index_entry(&sidecar_path) {
-                Ok(old_entry) => old_entry
-                    .chunks
-                    .into_iter()
-                    .filter_map(|chunk| {
-                        if let (Some(hash), Some(embedding)) = (chunk.chunk_hash, chunk.embedding) {
-                            Some((hash, embedding))
-                        } else {
-                            None
-                        }
-                    })
-                    .collect(),
-                Err(_) => HashMap::new(),
-            }
-        } else {
-            HashMap::new()
-        }
-    } else {
+/// Record a requested chunk strategy in the manifest, or fall back to the one
+/// the index was built with. As with the overlap, mixing strategies within
+/// one index is refused.
+fn apply_chunk_strategy_setting(
+    manifest: &mut IndexManifest,
+    requested: Option<ck_chunk::ChunkStrategy>,
+) -> Result<ck_chunk::ChunkStrategy> {
+    let existing = manifest.chunk_strategy;
+    let Some(requested) = requested else {
+        return Ok(existing);
+    };
+
+    if requested != existing && !manifest.files.is_empty() {
+        return Err(anyhow::anyhow!(
+            "Chunk strategy mismatch: Index was built with '{}' chunks, but '{}' were requested. \
+                Run 'ck --index --chunk-strategy {}' to rebuild it.",
+            existing,
+            requested,
+            requested
+        ));
+    }
+
+    manifest.chunk_strategy = requested;
+    Ok(requested)
+}
+
+/// Record a requested chunk overlap in the manifest, or fall back to the one
+/// the index was built with. Sidecars chunked with a different overlap would
+/// be inconsistent, so changing it on a populated index is refused.
+fn apply_chunk_overlap_setting(
+    manifest: &mut IndexManifest,
+    requested: Option<usize>,
+) -> Result<usize> {
+    let existing = manifest.chunk_overlap_lines.unwrap_or(0);
+    let Some(requested) = requested else {
+        return Ok(existing);
+    };
+
+    if requested != existing && !manifest.files.is_empty() {
+        return Err(anyhow::anyhow!(
+            "Chunk overlap mismatch: Index was built with {} overlap lines, but {} were requested. \
+                Run 'ck --index --chunk-overlap {}' to rebuild it.",
+            existing,
+            requested,
+            requested
+        ));
+    }
+
+    manifest.chunk_overlap_lines = (requested > 0).then_some(requested);
+    Ok(requested)
+}
+
+/// The embedding model recorded in the manifest of the nearest index at or
+/// above `path`, if any. Runs that don't name a model use it, so they match
+/// the vectors already on disk.
+pub fn pinned_model(path: &Path) -> Option<String> {
+    let start = fs::canonicalize(path).unwrap_or_else(|_| path.to_path_buf());
+    let manifest_path = start
+        .ancestors()
+        .map(|dir| ck_core::index_dir(dir).join("manifest.json"))
+        .find(|manifest_path| manifest_path.exists())?;
+    read_manifest(&manifest_path).ok()?.embedding_model
+}
+
+/// Chunk overlap (in lines) the index at `path` was built with; 0 when there
+/// is no index or no overlap.
+pub fn index_chunk_overlap(path: &Path) -> usize {
+    let manifest_path = ck_core::index_dir(path).join("manifest.json");
+    fs::read(&manifest_path)
+        .ok()
+        .and_then(|data| serde_json::from_slice::<IndexManifest>(&data).ok())
+        .and_then(|manifest| manifest.chunk_overlap_lines)
+        .unwrap_or(0)
+}
+
+/// Chunk strategy the index at `path` was built with; the default when there
+/// is no index.
+pub fn index_chunk_strategy(path: &Path) -> ck_chunk::ChunkStrategy {
+    let manifest_path = ck_core::index_dir(path).join("manifest.json");
+    fs::read(&manifest_path)
+        .ok()
+        .and_then(|data| serde_json::from_slice::<IndexManifest>(&data).ok())
+        .map(|manifest| manifest.chunk_strategy)
+        .unwrap_or_default()
+}
+
+/// Whether the index at `path` writes zstd-compressed sidecars; false when
+/// there is no index.
+pub fn index_sidecar_compression(path: &Path) -> bool {
+    let manifest_path = ck_core::index_dir(path).join("manifest.json");
+    fs::read(&manifest_path)
+        .ok()
+        .and_then(|data| serde_json::from_slice::<IndexManifest>(&data).ok())
+        .is_some_and(|manifest| manifest.compress_sidecars)
+}
+
+/// Record in the manifest whether sidecars under `path` are compressed, and
+/// re-encode the existing ones to match so the change takes effect without
+/// re-embedding anything. Returns the number of sidecars rewritten.
+pub fn set_sidecar_compression(path: &Path, compress: bool) -> Result<usize> {
+    reencode_sidecars(path, |manifest| manifest.compress_sidecars = compress)
+}
+
+/// Precision the index at `path` stores embedding vectors at; full precision
+/// when there is no index.
+pub fn index_quantization(path: &Path) -> Quantization {
+    let manifest_path = ck_core::index_dir(path).join("manifest.json");
+    fs::read(&manifest_path)
+        .ok()
+        .and_then(|data| serde_json::from_slice::<IndexManifest>(&data).ok())
+        .map(|manifest| manifest.quantization)
+        .unwrap_or_default()
+}
+
+/// Record in the manifest the precision sidecars under `path` store vectors
+/// at, and re-encode the existing ones to match. Vectors already stored more
+/// coarsely keep only the precision they have; rebuild the index to get the
+/// rest back. Returns the number of sidecars rewritten.
+pub fn set_sidecar_quantization(path: &Path, quantization: Quantization) -> Result<usize> {
+    reencode_sidecars(path, |manifest| manifest.quantization = quantization)
+}
+
+/// Dimensions the index at `path` reduces its vectors to; `None` when it
+/// keeps the model's own, or there is no index.
+pub fn index_reduced_dimensions(path: &Path) -> Option<usize> {
+    let manifest_path = ck_core::index_dir(path).join("manifest.json");
+    fs::read(&manifest_path)
+        .ok()
+        .and_then(|data| serde_json::from_slice::<IndexManifest>(&data).ok())
+        .and_then(|manifest| manifest.reduced_dimensions)
+}
+
+/// The projection query embeddings need before they can be compared with
+/// the vectors of the index at `path`, if it reduces them.
+pub fn index_projection(path: &Path) -> Option<Projection> {
+    let index_dir = ck_core::index_dir(path);
+    let manifest: IndexManifest =
+        serde_json::from_slice(&fs::read(index_dir.join("manifest.json")).ok()?).ok()?;
+    manifest.reduced_dimensions?;
+    projection::read_projection(&index_dir)
+}
+
+/// Reduce the index model's vectors under `path` to `dimensions`
+/// (`--reduce-dim`): fit a projection from the vectors the index holds, save
+/// it with the manifest, and rewrite the sidecars with their vectors
+/// projected. Later indexing passes project new vectors the same way.
+/// Projected vectors can't be restored or projected again, so changing the
+/// dimensions of a reduced index needs a rebuild first. Returns the number
+/// of sidecars rewritten.
+pub fn set_dimension_reduction(path: &Path, dimensions: usize) -> Result<usize> {
+    let index_dir = ck_core::index_dir(path);
+    let manifest_path = index_dir.join("manifest.json");
+    if !manifest_path.exists() {
+        return Ok(0);
+    }
+    let _lock = acquire_index_write_lock(&index_dir)?;
+    ck_core::check_index_root_marker(path)?;
+
+    let mut manifest = load_or_create_manifest(&manifest_path)?;
+    let Some(model) = manifest.embedding_model.clone() else {
+        return Ok(0);
+    };
+    let sidecars: Vec<PathBuf> = manifest
+        .files
+        .keys()
+        .map(|key| {
+            path_utils::get_sidecar_path_for_standard_path(
+                &index_dir,
+                &path_utils::from_manifest_path(key),
+            )
+        })
+        .collect();
+
+    let projection = match (&manifest.projection, manifest.reduced_dimensions) {
+        // Reapplying the same reduction only finishes an interrupted rewrite
+        (Some(projection), Some(reduced)) if reduced == dimensions => projection.clone(),
+        (_, Some(reduced)) => {
+            return Err(anyhow::anyhow!(
+                "The index's vectors are already reduced to {reduced} dimensions; rebuild it with `ck --reindex --reduce-dim {dimensions}` to change that"
+            ));
+        }
+        (_, None) => {
+            let source = manifest.embedding_dimensions;
+            let mut vectors = Vec::new();
+            for sidecar_path in &sidecars {
+                let Ok(entry) = load_index_entry(sidecar_path) else {
+                    continue;
+                };
+                if entry.embedding_model.as_ref().is_some_and(|m| *m != model) {
+                    continue;
+                }
+                vectors.extend(
+                    entry
+                        .chunks
+                        .into_iter()
+                        .filter_map(|chunk| chunk.embedding)
+                        .filter(|embedding| source.is_none_or(|d| embedding.len() == d)),
+                );
+            }
+            if vectors.is_empty() {
+                return Ok(0);
+            }
+            let projection = Projection::fit(&model, &vectors, dimensions)?;
+            atomic_write(
+                &index_dir.join(PROJECTION_FILE),
+                &bincode::serialize(&projection)?,
+            )?;
+            let projection = Arc::new(projection);
+            manifest.reduced_dimensions = Some(dimensions);
+            manifest.projection = Some(projection.clone());
+            save_manifest(&manifest_path, &manifest)?;
+            projection
+        }
+    };
+
+    let mut rewritten = 0;
+    for sidecar_path in &sidecars {
+        let Ok(mut entry) = load_index_entry(sidecar_path) else {
+            continue;
+        };
+        let mut projected = false;
+        for chunk in &mut entry.chunks {
+            if let Some(embedding) = &mut chunk.embedding
+                && projection.applies_to(
+                    entry.embedding_model.as_deref().unwrap_or(&model),
+                    embedding,
+                )
+            {
+                *embedding = projection.project(embedding);
+                chunk.quantized = None;
+                projected = true;
+            }
+        }
+        if projected {
+            entry.embedding_dimensions = Some(dimensions);
+            save_index_entry(sidecar_path, &entry, manifest.sidecar_encoding())?;
+            rewritten += 1;
+        }
+    }
+    refresh_ann_index(path)?;
+    Ok(rewritten)
+}
+
+/// Whether the index at `path` keeps an approximate nearest-neighbour index;
+/// false when there is no index.
+pub fn index_has_ann(path: &Path) -> bool {
+    let manifest_path = ck_core::index_dir(path).join("manifest.json");
+    fs::read(&manifest_path)
+        .ok()
+        .and_then(|data| serde_json::from_slice::<IndexManifest>(&data).ok())
+        .is_some_and(|manifest| manifest.ann_index)
+}
+
+/// Record in the manifest whether the index at `path` keeps an approximate
+/// nearest-neighbour index (`--ann`). The next indexing pass builds or
+/// removes it to match.
+pub fn set_ann_index(path: &Path, enabled: bool) -> Result<()> {
+    let index_dir = ck_core::index_dir(path);
+    let _lock = acquire_index_write_lock(&index_dir)?;
+    ck_core::check_index_root_marker(path)?;
+    ck_core::write_index_root_marker(path)?;
+
+    let manifest_path = index_dir.join("manifest.json");
+    let mut manifest = load_or_create_manifest(&manifest_path)?;
+    if manifest.ann_index != enabled || !manifest_path.exists() {
+        manifest.ann_index = enabled;
+        save_manifest(&manifest_path, &manifest)?;
+    }
+    Ok(())
+}
+
+/// Apply `change` to the sidecar settings in the manifest under `path` and
+/// rewrite every sidecar with the new settings, unless they didn't change.
+fn reencode_sidecars(path: &Path, change: impl FnOnce(&mut IndexManifest)) -> Result<usize> {
+    let index_dir = ck_core::index_dir(path);
+    let _lock = acquire_index_write_lock(&index_dir)?;
+    ck_core::check_index_root_marker(path)?;
+    ck_core::write_index_root_marker(path)?;
+
+    let manifest_path = index_dir.join("manifest.json");
+    let mut manifest = load_or_create_manifest(&manifest_path)?;
+    let previous = manifest.sidecar_encoding();
+    change(&mut manifest);
+    let encoding = manifest.sidecar_encoding();
+    if encoding == previous && manifest_path.exists() {
+        return Ok(0);
+    }
+
+    let mut rewritten = 0;
+    for manifest_key in manifest.files.keys() {
+        let standard_path = path_utils::from_manifest_path(manifest_key);
+        let sidecar_path =
+            path_utils::get_sidecar_path_for_standard_path(&index_dir, &standard_path);
+        if let Ok(entry) = load_index_entry(&sidecar_path) {
+            save_index_entry(&sidecar_path, &entry, encoding)?;
+            rewritten += 1;
+        }
+    }
+    save_manifest(&manifest_path, &manifest)?;
+    Ok(rewritten)
+}
+
+/// Outcome of [`migrate_index`].
+#[derive(Debug, Clone, Default)]
+pub struct MigrationStats {
+    /// Sidecars rewritten in the current format
+    pub migrated: usize,
+    /// Sidecars already in the current format
+    pub current: usize,
+    /// Sidecars in a layout no migration path covers; only a rebuild
+    /// (`--reindex`) replaces them
+    pub failed: Vec<PathBuf>,
+}
+
+/// Rewrite the sidecars under `path` that an older ck wrote in the current
+/// [`INDEX_FORMAT_VERSION`], keeping their chunks and embeddings. The
+/// manifest records the new format once every sidecar is converted.
+pub fn migrate_index(path: &Path) -> Result<MigrationStats> {
+    let index_dir = ck_core::index_dir(path);
+    let manifest_path = index_dir.join("manifest.json");
+    if !manifest_path.exists() {
+        return Err(anyhow::anyhow!("No index found at {}", path.display()));
+    }
+    let _lock = acquire_index_write_lock(&index_dir)?;
+    ck_core::check_index_root_marker(path)?;
+
+    let mut manifest = load_or_create_manifest(&manifest_path)?;
+    let encoding = manifest.sidecar_encoding();
+    let mut stats = MigrationStats::default();
+    for manifest_key in manifest.files.keys() {
+        let standard_path = path_utils::from_manifest_path(manifest_key);
+        let sidecar_path =
+            path_utils::get_sidecar_path_for_standard_path(&index_dir, &standard_path);
+        match sidecar::sidecar_format_version(&sidecar_path) {
+            Ok(INDEX_FORMAT_VERSION) => stats.current += 1,
+            Ok(_) => match load_index_entry(&sidecar_path) {
+                Ok(entry) => {
+                    save_index_entry(&sidecar_path, &entry, encoding)?;
+                    stats.migrated += 1;
+                }
+                Err(_) => stats.failed.push(standard_path),
+            },
+            // Sidecars missing from disk are rebuilt by the next update
+            Err(_) => {}
+        }
+    }
+    if stats.failed.is_empty() && manifest.format_version != INDEX_FORMAT_VERSION {
+        manifest.format_version = INDEX_FORMAT_VERSION;
+        save_manifest(&manifest_path, &manifest)?;
+    }
+    Ok(stats)
+}
+
+/// Files among `files` that changed since they were indexed under
+/// `index_root`, so spans recorded for them may no longer line up. As in
+/// `smart_update_index`, a matching size and mtime means unchanged (unless
+/// `force_hash`); otherwise the content hash decides. Files the index
+/// doesn't know about are never stale.
+pub fn find_stale_files<'a>(
+    index_root: &Path,
+    files: impl IntoIterator<Item = &'a Path>,
+    force_hash: bool,
+) -> HashSet<PathBuf> {
+    let manifest_path = ck_core::index_dir(index_root).join("manifest.json");
+    let Some(manifest) = fs::read(&manifest_path)
+        .ok()
+        .and_then(|data| serde_json::from_slice::<IndexManifest>(&data).ok())
+    else {
+        return HashSet::new();
+    };
+    let canonical_root = index_root.canonicalize().ok();
+
+    let mut stale = HashSet::new();
+    for file in files {
+        let relative = match file.strip_prefix(index_root) {
+            Ok(relative) => relative.to_path_buf(),
+            Err(_) => match (&canonical_root, file.canonicalize()) {
+                (Some(root), Ok(canonical)) => path_utils::to_standard_path(&canonical, root),
+                _ => continue,
+            },
+        };
+        let Some(metadata) = manifest.files.get(&path_utils::to_manifest_path(&relative)) else {
+            continue;
+        };
+        let changed = match fs::metadata(file) {
+            Ok(fs_meta) => {
+                let last_modified = fs_meta
+                    .modified()
+                    .ok()
+                    .and_then(|m| m.duration_since(SystemTime::UNIX_EPOCH).ok())
+                    .map(|d| d.as_secs());
+                (force_hash
+                    || last_modified != Some(metadata.last_modified)
+                    || fs_meta.len() != metadata.size)
+                    && compute_file_hash(file).map_or(true, |hash| hash != metadata.hash)
+            }
+            Err(_) => true,
+        };
+        if changed {
+            stale.insert(file.to_path_buf());
+        }
+    }
+    stale
+}
+
+/// How far an index lags behind the files it covers, as seen by
+/// [`check_index_freshness`].
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct IndexFreshness {
+    /// When the index was last updated, in seconds since the Unix epoch
+    pub updated: u64,
+    /// Indexed files modified after that
+    pub changed_files: usize,
+    /// Files the index doesn't know about yet
+    pub new_files: usize,
+}
+
+impl IndexFreshness {
+    pub fn is_fresh(&self) -> bool {
+        self.changed_files == 0 && self.new_files == 0
+    }
+}
+
+/// Compare the files `options` would index under `index_root` with the
+/// index's last update time. This only looks at mtimes, so it is cheap
+/// enough to run before every search; `None` when there is no index.
+pub fn check_index_freshness(
+    index_root: &Path,
+    options: &ck_core::FileCollectionOptions,
+) -> Result<Option<IndexFreshness>> {
+    let manifest_path = ck_core::index_dir(index_root).join("manifest.json");
+    let Ok(data) = fs::read(&manifest_path) else {
+        return Ok(None);
+    };
+    let manifest: IndexManifest = serde_json::from_slice(&data)?;
+
+    let mut freshness = IndexFreshness {
+        updated: manifest.updated,
+        ..Default::default()
+    };
+    for file in collect_files(index_root, options)? {
+        let relative = path_utils::to_standard_path(&file, index_root);
+        if !manifest
+            .files
+            .contains_key(&path_utils::to_manifest_path(&relative))
+        {
+            freshness.new_files += 1;
+            continue;
+        }
+        let modified = fs::metadata(&file)
+            .and_then(|meta| meta.modified())
+            .ok()
+            .and_then(|m| m.duration_since(SystemTime::UNIX_EPOCH).ok())
+            .map(|d| d.as_secs());
+        if modified.is_some_and(|modified| modified > manifest.updated) {
+            freshness.changed_files += 1;
+        }
+    }
+    Ok(Some(freshness))
+}
+
+/// Every file recorded in the index at `index_root`, as paths under
+/// `index_root`. Empty when there is no index.
+pub fn indexed_files(index_root: &Path) -> HashSet<PathBuf> {
+    let manifest_path = ck_core::index_dir(index_root).join("manifest.json");
+    let Some(mut manifest) = fs::read(&manifest_path)
+        .ok()
+        .and_then(|data| serde_json::from_slice::<IndexManifest>(&data).ok())
+    else {
+        return HashSet::new();
+    };
+    normalize_manifest_paths(&mut manifest, index_root);
+    manifest
+        .files
+        .keys()
+        .map(|key| index_root.join(path_utils::from_manifest_path(key)))
+        .collect()
+}
+
+fn index_single_file(
+    file_path: &Path,
+    repo_root: &Path,
+    embedder: Option<&mut Box<dyn ck_embed::Embedder>>,
+    chunking: ChunkSettings,
+    timeout: Option<Duration>,
+) -> Result<IndexEntry> {
+    let (entry, _chunks_reused, _chunks_embedded) = index_single_file_with_progress(
+        file_path, repo_root, embedder, chunking, timeout, None, None, 0, 1,
+    )?;
+    Ok(entry)
+}
+
+#[allow(clippy::too_many_arguments)]
+fn index_single_file_with_progress(
+    file_path: &Path,
+    repo_root: &Path,
+    embedder: Option<&mut Box<dyn ck_embed::Embedder>>,
+    chunking: ChunkSettings,
+    timeout: Option<Duration>,
+    cancel: Option<&CancellationToken>,
+    detailed_progress: Option<&DetailedProgressCallback>,
+    file_index: usize,
+    total_files: usize,
+) -> Result<(IndexEntry, usize, usize)> {
+    let deadline = Deadline::start(timeout, cancel);
+
+    // Skip binary files to avoid UTF-8 warnings
+    if !is_text_file(file_path) {
+        return Err(anyhow::anyhow!("Binary file, skipping"));
+    }
+
+    // Build chunk cache from old sidecar if it exists (for chunk reuse)
+    let chunk_cache: HashMap<String, Vec<f32>> = if embedder.is_some() {
+        let sidecar_path = get_sidecar_path(repo_root, file_path);
+        if sidecar_path.exists() {
+            match load_index_entry(&sidecar_path) {
+                Ok(old_entry) => old_entry
+                    .chunks
+                    .into_iter()
+                    .filter_map(|chunk| {
+                        if let (Some(hash), Some(embedding)) = (chunk.chunk_hash, chunk.embedding) {
+                            Some((hash, embedding))
+                        } else {
+                            None
+                        }
+                    })
+                    .collect(),
+                Err(_) => HashMap::new(),
+            }
+        } else {
+            HashMap::new()
+        }
+    } else {
         HashMap::new()
     };
 
     // Preprocess file (extracts PDFs to cache, returns path to readable content)
     let content_path = preprocess_file(file_path, repo_root)?;
-    let content = fs::read_to_string(&content_path)?;
+    let content = read_text(&content_path)?;
 
     // Always use the ORIGINAL file for hash and metadata
     let hash = compute_file_hash(file_path)?;
@@ -1223,6 +2781,8 @@ fn index_single_file_with_progress(
         size: metadata.len(),
     };
 
+    let generated = is_generated(file_path, &content);
+
     // Detect language for tree-sitter parsing
     let lang = if ck_core::pdf::is_pdf_file(file_path) {
         Some(Language::Pdf)
@@ -1230,8 +2790,22 @@ fn index_single_file_with_progress(
         ck_core::Language::from_path(file_path)
     };
 
+    deadline.check()?;
     let model_name = embedder.as_ref().map(|e| e.model_name());
-    let chunks = ck_chunk::chunk_text_with_model(&content, lang, model_name)?;
+    let chunk_model = model_name.map(str::to_string);
+    let chunk_path = file_path.to_path_buf();
+    let chunks = deadline.run(move || {
+        ck_chunk::chunk_file_content(
+            &chunk_path,
+            &content,
+            lang,
+            chunk_model.as_deref(),
+            chunking.overlap_lines,
+            chunking.strategy,
+        )
+    })?;
+    let embedding_model = model_name.map(str::to_string);
+    let embedding_dimensions = embedder.as_ref().map(|e| e.dim());
 
     // Track chunk reuse statistics
     let mut chunks_reused = 0;
@@ -1258,6 +2832,7 @@ fn index_single_file_with_progress(
                 if INTERRUPTED.load(Ordering::SeqCst) {
                     return Err(anyhow::anyhow!(INDEX_INTERRUPTED_MSG));
                 }
+                deadline.check()?;
                 // Report progress before processing chunk
                 callback(EmbeddingProgress {
                     file_name: file_name.clone(),
@@ -1346,6 +2921,7 @@ fn index_single_file_with_progress(
                     leading_trivia,
                     trailing_trivia,
                     chunk_hash: Some(chunk_hash),
+                    quantized: None,
                 });
             }
             chunk_entries
@@ -1399,7 +2975,13 @@ fn index_single_file_with_progress(
                     file_path,
                     chunks_reused
                 );
-                let embeddings = embedder.embed(&texts)?;
+                // In batches, so an overrunning file stops between them
+                let mut embeddings = Vec::with_capacity(texts.len());
+                let batch_size = embed_batch_size(embedder.model_name(), chunking.embed_batch_size);
+                for batch in texts.chunks(batch_size) {
+                    deadline.check()?;
+                    embeddings.extend(embedder.embed(batch)?);
+                }
 
                 if embeddings.len() != chunks_to_embed.len() {
                     return Err(anyhow::anyhow!(
@@ -1422,41 +3004,7 @@ fn index_single_file_with_progress(
                 .into_iter()
                 .map(|(chunk, chunk_hash, embedding)| {
                     let embedding = embedding.expect("All chunks should have embeddings by now");
-                    let chunk_type_str = match chunk.chunk_type {
-                        ck_chunk::ChunkType::Function => Some("function".to_string()),
-                        ck_chunk::ChunkType::Class => Some("class".to_string()),
-                        ck_chunk::ChunkType::Method => Some("method".to_string()),
-                        ck_chunk::ChunkType::Module => Some("module".to_string()),
-                        ck_chunk::ChunkType::Text => None,
-                    };
-                    let breadcrumb = chunk.metadata.breadcrumb.clone();
-                    let ancestry = if chunk.metadata.ancestry.is_empty() {
-                        None
-                    } else {
-                        Some(chunk.metadata.ancestry.clone())
-                    };
-                    let leading_trivia = if chunk.metadata.leading_trivia.is_empty() {
-                        None
-                    } else {
-                        Some(chunk.metadata.leading_trivia.clone())
-                    };
-                    let trailing_trivia = if chunk.metadata.trailing_trivia.is_empty() {
-                        None
-                    } else {
-                        Some(chunk.metadata.trailing_trivia.clone())
-                    };
-                    ChunkEntry {
-                        span: chunk.span,
-                        embedding: Some(embedding),
-                        chunk_type: chunk_type_str,
-                        breadcrumb,
-                        ancestry,
-                        byte_length: Some(chunk.metadata.byte_length),
-                        estimated_tokens: Some(chunk.metadata.estimated_tokens),
-                        leading_trivia,
-                        trailing_trivia,
-                        chunk_hash: Some(chunk_hash),
-                    }
+                    chunk_entry(chunk, chunk_hash, Some(embedding))
                 })
                 .collect()
         }
@@ -1465,45 +3013,12 @@ fn index_single_file_with_progress(
         chunks
             .into_iter()
             .map(|chunk| {
-                let chunk_type_str = match chunk.chunk_type {
-                    ck_chunk::ChunkType::Function => Some("function".to_string()),
-                    ck_chunk::ChunkType::Class => Some("class".to_string()),
-                    ck_chunk::ChunkType::Method => Some("method".to_string()),
-                    ck_chunk::ChunkType::Module => Some("module".to_string()),
-                    ck_chunk::ChunkType::Text => None,
-                };
-                let breadcrumb = chunk.metadata.breadcrumb.clone();
-                let ancestry = if chunk.metadata.ancestry.is_empty() {
-                    None
-                } else {
-                    Some(chunk.metadata.ancestry.clone())
-                };
-                let leading_trivia = if chunk.metadata.leading_trivia.is_empty() {
-                    None
-                } else {
-                    Some(chunk.metadata.leading_trivia.clone())
-                };
-                let trailing_trivia = if chunk.metadata.trailing_trivia.is_empty() {
-                    None
-                } else {
-                    Some(chunk.metadata.trailing_trivia.clone())
-                };
-                ChunkEntry {
-                    span: chunk.span,
-                    embedding: None,
-                    chunk_type: chunk_type_str,
-                    breadcrumb,
-                    ancestry,
-                    byte_length: Some(chunk.metadata.byte_length),
-                    estimated_tokens: Some(chunk.metadata.estimated_tokens),
-                    leading_trivia: leading_trivia.clone(),
-                    trailing_trivia: trailing_trivia.clone(),
-                    chunk_hash: Some(compute_chunk_hash(
-                        &chunk.text,
-                        &chunk.metadata.leading_trivia,
-                        &chunk.metadata.trailing_trivia,
-                    )),
-                }
+                let chunk_hash = compute_chunk_hash(
+                    &chunk.text,
+                    &chunk.metadata.leading_trivia,
+                    &chunk.metadata.trailing_trivia,
+                );
+                chunk_entry(chunk, chunk_hash, None)
             })
             .collect()
     };
@@ -1512,34 +3027,170 @@ fn index_single_file_with_progress(
         IndexEntry {
             metadata: file_metadata,
             chunks: chunk_entries,
+            embedding_model,
+            embedding_dimensions,
+            generated: Some(generated),
         },
         chunks_reused,
         chunks_embedded,
     ))
 }
 
-fn load_or_create_manifest(path: &Path) -> Result<IndexManifest> {
-    let mut manifest = if path.exists() {
-        let data = fs::read(path)?;
-        serde_json::from_slice(&data)?
-    } else {
-        IndexManifest::default()
+/// The index entry for `chunk`, with its hash and embedding (if any).
+fn chunk_entry(
+    chunk: ck_chunk::Chunk,
+    chunk_hash: String,
+    embedding: Option<Vec<f32>>,
+) -> ChunkEntry {
+    let chunk_type = match chunk.chunk_type {
+        ck_chunk::ChunkType::Function => Some("function".to_string()),
+        ck_chunk::ChunkType::Class => Some("class".to_string()),
+        ck_chunk::ChunkType::Method => Some("method".to_string()),
+        ck_chunk::ChunkType::Module => Some("module".to_string()),
+        ck_chunk::ChunkType::Text => None,
     };
-
-    // Ensure chunk_hash_version is set to v2 if not already set
-    // This handles manifests created before the field existed
-    if manifest.chunk_hash_version.is_none() {
-        manifest.chunk_hash_version = Some(2);
+    let metadata = chunk.metadata;
+    ChunkEntry {
+        span: chunk.span,
+        embedding,
+        chunk_type,
+        breadcrumb: metadata.breadcrumb,
+        ancestry: (!metadata.ancestry.is_empty()).then_some(metadata.ancestry),
+        byte_length: Some(metadata.byte_length),
+        estimated_tokens: Some(metadata.estimated_tokens),
+        leading_trivia: (!metadata.leading_trivia.is_empty()).then_some(metadata.leading_trivia),
+        trailing_trivia: (!metadata.trailing_trivia.is_empty()).then_some(metadata.trailing_trivia),
+        chunk_hash: Some(chunk_hash),
+        quantized: None,
     }
-
-    Ok(manifest)
 }
 
-fn normalize_manifest_paths(manifest: &mut IndexManifest, repo_root: &Path) {
-    let original_entries = std::mem::take(&mut manifest.files);
-    let mut normalized = HashMap::with_capacity(original_entries.len());
-
-    for (key, mut metadata) in original_entries {
+/// Chunk `file_path` and embed every chunk with `embedder`, keeping the
+/// result in memory: nothing under the index directory is read or written,
+/// so this works on read-only trees (`--ephemeral` search). PDFs and
+/// notebooks are refused, since their text lives in the index's content
+/// cache.
+pub fn embed_file_in_memory(
+    file_path: &Path,
+    embedder: &mut Box<dyn ck_embed::Embedder>,
+    embed_batch_size: Option<usize>,
+) -> Result<Vec<ChunkEntry>> {
+    if ck_core::pdf::is_pdf_file(file_path) || ck_core::notebook::is_notebook_file(file_path) {
+        return Err(anyhow::anyhow!(
+            "{} needs its text extracted into an index first",
+            file_path.display()
+        ));
+    }
+    if !is_text_file(file_path) {
+        return Err(anyhow::anyhow!("Binary file, skipping"));
+    }
+    let content = read_text(file_path)?;
+    embed_text_in_memory(
+        file_path,
+        &content,
+        ck_core::Language::from_path(file_path),
+        embedder,
+        embed_batch_size,
+    )
+}
+
+/// [`embed_file_in_memory`] for text already in memory, such as standard
+/// input; `file_path` only names it in errors and chunking decisions.
+pub fn embed_text_in_memory(
+    file_path: &Path,
+    content: &str,
+    language: Option<ck_core::Language>,
+    embedder: &mut Box<dyn ck_embed::Embedder>,
+    batch_size: Option<usize>,
+) -> Result<Vec<ChunkEntry>> {
+    let chunks = ck_chunk::chunk_file_content(
+        file_path,
+        content,
+        language,
+        Some(embedder.model_name()),
+        0,
+        ck_chunk::ChunkStrategy::default(),
+    )?;
+    let texts: Vec<String> = chunks.iter().map(|chunk| chunk.text.clone()).collect();
+    let mut embeddings = Vec::with_capacity(texts.len());
+    for batch in texts.chunks(embed_batch_size(embedder.model_name(), batch_size)) {
+        embeddings.extend(embedder.embed(batch)?);
+    }
+    if embeddings.len() != chunks.len() {
+        return Err(anyhow::anyhow!(
+            "Embedder returned {} embeddings for {} chunks in file {:?}. Expected equal counts.",
+            embeddings.len(),
+            chunks.len(),
+            file_path
+        ));
+    }
+    Ok(chunks
+        .into_iter()
+        .zip(embeddings)
+        .map(|(chunk, embedding)| {
+            let chunk_hash = compute_chunk_hash(
+                &chunk.text,
+                &chunk.metadata.leading_trivia,
+                &chunk.metadata.trailing_trivia,
+            );
+            chunk_entry(chunk, chunk_hash, Some(embedding))
+        })
+        .collect())
+}
+
+/// Read the manifest at `path`, failing with a message that says what to do
+/// when it was written by a ck whose index format this one can't read.
+pub fn read_manifest(path: &Path) -> Result<IndexManifest> {
+    let data = fs::read(path)?;
+    let manifest: IndexManifest = serde_json::from_slice(&data).map_err(|err| {
+        anyhow::anyhow!(
+            "Index manifest {} can't be read ({err}); the index was probably built by a different version of ck. Run `ck --reindex` to rebuild it",
+            path.display()
+        )
+    })?;
+    if manifest.format_version > INDEX_FORMAT_VERSION {
+        return Err(anyhow::anyhow!(
+            "The index at {} was built by a newer ck (index format {}, this ck reads up to {}); upgrade ck or run `ck --reindex` to rebuild it",
+            path.parent().unwrap_or(path).display(),
+            manifest.format_version,
+            INDEX_FORMAT_VERSION
+        ));
+    }
+    Ok(manifest)
+}
+
+fn load_or_create_manifest(path: &Path) -> Result<IndexManifest> {
+    let mut manifest = if path.exists() {
+        read_manifest(path)?
+    } else {
+        IndexManifest::default()
+    };
+
+    // Ensure chunk_hash_version is set to v2 if not already set
+    // This handles manifests created before the field existed
+    if manifest.chunk_hash_version.is_none() {
+        manifest.chunk_hash_version = Some(2);
+    }
+
+    if manifest.reduced_dimensions.is_some() {
+        let index_dir = path.parent().unwrap_or_else(|| Path::new("."));
+        let projection = projection::read_projection(index_dir).ok_or_else(|| {
+            anyhow::anyhow!(
+                "The index at {} reduces its vectors but its {PROJECTION_FILE} is missing or unreadable; run `ck --reindex` to rebuild it",
+                index_dir.display()
+            )
+        })?;
+        manifest.projection = Some(Arc::new(projection));
+    }
+
+    Ok(manifest)
+}
+
+fn normalize_manifest_paths(manifest: &mut IndexManifest, repo_root: &Path) {
+    let original_entries = std::mem::take(&mut manifest.files);
+    let mut normalized = HashMap::with_capacity(original_entries.len());
+
+    for (key, mut metadata) in original_entries {
         let standard_key = if key.is_absolute() {
             path_utils::to_standard_path(&key, repo_root)
         } else {
@@ -1565,12 +3216,7 @@ fn save_manifest(path: &Path, manifest: &IndexManifest) -> Result<()> {
     atomic_write(path, &data)
 }
 
-fn save_index_entry(path: &Path, entry: &IndexEntry) -> Result<()> {
-    let data = bincode::serialize(entry)?;
-    atomic_write(path, &data)
-}
-
-fn atomic_write(path: &Path, data: &[u8]) -> Result<()> {
+pub(crate) fn atomic_write(path: &Path, data: &[u8]) -> Result<()> {
     let parent = path.parent().unwrap_or_else(|| Path::new("."));
     fs::create_dir_all(parent)?;
 
@@ -1586,11 +3232,6 @@ fn atomic_write(path: &Path, data: &[u8]) -> Result<()> {
     Ok(())
 }
 
-pub fn load_index_entry(path: &Path) -> Result<IndexEntry> {
-    let data = fs::read(path)?;
-    Ok(bincode::deserialize(&data)?)
-}
-
 fn find_repo_root(path: &Path) -> Result<PathBuf> {
     let mut current = if path.is_file() {
         path.parent().unwrap_or(path)
@@ -1618,31 +3259,56 @@ fn should_reextract(source_path: &Path, cache_path: &Path) -> Result<bool> {
 
     let source_modified = fs::metadata(source_path)?.modified()?;
     let cache_modified = fs::metadata(cache_path)?.modified()?;
+    if source_modified > cache_modified {
+        return Ok(true);
+    }
 
-    Ok(source_modified > cache_modified)
+    // PDF text cached before it was split into pages has no page headers
+    Ok(ck_core::pdf::is_pdf_file(source_path)
+        && read_text(cache_path)?
+            .lines()
+            .next()
+            .is_none_or(|line| ck_core::pdf::page_header(line).is_none()))
 }
 
-/// Extract text content from a PDF file
+/// Extract the text of a PDF file, page by page (see [`ck_core::pdf`])
+#[cfg(feature = "pdf")]
 fn extract_pdf_text(path: &Path) -> Result<String> {
-    pdf_extract::extract_text(path)
+    pdf_extract::extract_text_by_pages(path)
+        .map(|pages| ck_core::pdf::join_pages(&pages))
         .map_err(|e| anyhow::anyhow!("Failed to extract text from PDF {}: {}", path.display(), e))
 }
 
+#[cfg(not(feature = "pdf"))]
+fn extract_pdf_text(path: &Path) -> Result<String> {
+    Err(anyhow::anyhow!(
+        "Can't extract text from PDF {}: ck was built without the `pdf` feature",
+        path.display()
+    ))
+}
+
 /// Preprocess a file if needed, returning path to readable content
 /// For regular files: returns the original path (no preprocessing)
-/// For PDFs: extracts text to cache, returns cache path
+/// For PDFs and notebooks: extracts text (the cells) to cache, returns cache path
 fn preprocess_file(file_path: &Path, repo_root: &Path) -> Result<PathBuf> {
-    if ck_core::pdf::is_pdf_file(file_path) {
+    let is_pdf = ck_core::pdf::is_pdf_file(file_path);
+    if is_pdf || ck_core::notebook::is_notebook_file(file_path) {
         let cache_path = ck_core::pdf::get_content_cache_path(repo_root, file_path);
 
         // Check if re-extraction needed
         if should_reextract(file_path, &cache_path)? {
             tracing::debug!(
-                "Extracting PDF content from {:?} to {:?}",
+                "Extracting content from {:?} to {:?}",
                 file_path,
                 cache_path
             );
-            let extracted_text = extract_pdf_text(file_path)?;
+            let extracted_text = if is_pdf {
+                extract_pdf_text(file_path)?
+            } else {
+                ck_core::notebook::extract_cells(&read_text(file_path)?).map_err(|e| {
+                    anyhow::anyhow!("Failed to read notebook {}: {e}", file_path.display())
+                })?
+            };
 
             // Ensure cache directory exists
             if let Some(parent) = cache_path.parent() {
@@ -1660,31 +3326,43 @@ fn preprocess_file(file_path: &Path, repo_root: &Path) -> Result<PathBuf> {
 }
 
 fn is_text_file(path: &Path) -> bool {
-    // PDFs are considered indexable even though they're binary
+    // PDFs are indexable even though they're binary, given the `pdf` feature
     if ck_core::pdf::is_pdf_file(path) {
-        return true;
+        return cfg!(feature = "pdf");
     }
+    !is_binary_file(path)
+}
 
-    // Use NUL byte heuristic like ripgrep - read first 8KB and check for NUL bytes
+/// True when `path` looks binary: like ripgrep, a NUL byte in the first 8KB.
+/// Files that can't be opened or read count as binary; empty files don't.
+pub fn is_binary_file(path: &Path) -> bool {
+    sniff_binary(path).unwrap_or(true)
+}
+
+/// Whether `path` looks binary, or the error opening or reading it.
+fn sniff_binary(path: &Path) -> std::io::Result<bool> {
     const BUFFER_SIZE: usize = 8192;
 
-    match std::fs::File::open(path) {
-        Ok(mut file) => {
-            let mut buffer = vec![0; BUFFER_SIZE];
-            match file.read(&mut buffer) {
-                Ok(bytes_read) => {
-                    // If file is empty, consider it text
-                    if bytes_read == 0 {
-                        return true;
-                    }
+    let file = std::fs::File::open(path)?;
+    let mut buffer = Vec::with_capacity(BUFFER_SIZE);
+    file.take(BUFFER_SIZE as u64).read_to_end(&mut buffer)?;
+    Ok(buffer.contains(&0))
+}
 
-                    // Check for NUL bytes in the read portion
-                    !buffer[..bytes_read].contains(&0)
-                }
-                Err(_) => false, // If we can't read, assume binary
-            }
+/// Read a text file, replacing invalid UTF-8 (a Latin-1 comment, a stray
+/// byte) instead of failing, so one odd file can't break indexing. The
+/// replacement is logged as a warning.
+pub fn read_text(path: &Path) -> Result<String> {
+    let bytes = fs::read(path)?;
+    match String::from_utf8(bytes) {
+        Ok(text) => Ok(text),
+        Err(err) => {
+            tracing::warn!(
+                "{} is not valid UTF-8; invalid bytes were replaced",
+                path.display()
+            );
+            Ok(String::from_utf8_lossy(err.as_bytes()).into_owned())
         }
-        Err(_) => false, // If we can't open, assume binary
     }
 }
 
@@ -1745,6 +3423,29 @@ pub struct IndexStats {
     pub index_size_bytes: u64,
     pub index_created: u64,
     pub index_updated: u64,
+    /// Files and chunks per detected language (`"other"` for unrecognized files)
+    pub by_language: HashMap<String, LanguageStats>,
+    /// Distinct embedding models recorded in the sidecars, sorted
+    pub models_used: Vec<String>,
+    /// Indexed files (relative to the index root) whose source file or
+    /// sidecar is gone; `--clean-orphans` removes them. Sorted.
+    pub orphaned_files: Vec<PathBuf>,
+    /// Sidecars are zstd-compressed, so `index_size_bytes` (always measured
+    /// on disk) is below the encoded size of the entries
+    #[serde(default)]
+    pub compressed_sidecars: bool,
+    /// Precision the sidecars store embedding vectors at
+    #[serde(default)]
+    pub quantization: Quantization,
+    /// Dimensions the vectors are reduced to (`--reduce-dim`)
+    #[serde(default)]
+    pub reduced_dimensions: Option<usize>,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct LanguageStats {
+    pub files: usize,
+    pub chunks: usize,
 }
 
 #[derive(Debug, Clone, Default, Serialize, Deserialize)]
@@ -1757,6 +3458,21 @@ pub struct UpdateStats {
     pub orphaned_files_removed: usize,
     pub chunks_reused: usize,
     pub chunks_embedded: usize,
+    /// Files skipped because they took longer than the per-file time budget
+    #[serde(default)]
+    pub timed_out_files: Vec<PathBuf>,
+    /// Vectors in the approximate nearest-neighbour index, when this pass
+    /// rebuilt it
+    #[serde(default)]
+    pub ann_vectors: Option<usize>,
+    /// Files and directories the walk skipped because they couldn't be read
+    /// (permission denied, a symlink loop), with the reason
+    #[serde(default)]
+    pub unreadable: Vec<(PathBuf, String)>,
+    /// Files left out of the index because their sidecar couldn't be
+    /// written, even after retrying, with the error
+    #[serde(default)]
+    pub unwritable: Vec<(PathBuf, String)>,
 }
 
 #[cfg(test)]
@@ -1766,6 +3482,51 @@ mod tests {
     use std::fs;
     use tempfile::TempDir;
 
+    #[test]
+    fn test_worker_pools_of_different_sizes_coexist() {
+        let default_threads = rayon::current_num_threads();
+        let two = WorkerPool::new(Some(2)).unwrap();
+        let three = WorkerPool::new(Some(3)).unwrap();
+        assert_eq!(two.install(rayon::current_num_threads), 2);
+        assert_eq!(three.install(rayon::current_num_threads), 3);
+        assert_eq!(
+            WorkerPool::new(None)
+                .unwrap()
+                .install(rayon::current_num_threads),
+            default_threads
+        );
+    }
+
+    #[test]
+    fn test_embed_batch_size_is_clamped_to_the_model() {
+        let registry = ck_models::ModelRegistry::default();
+        let (_, small) = registry.resolve(Some("bge-small")).unwrap();
+        let (_, long_context) = registry.resolve(Some("nomic-v1.5")).unwrap();
+
+        assert_eq!(small.effective_batch_size(None), small.batch_size);
+        assert_eq!(small.effective_batch_size(Some(100)), 100);
+        assert_eq!(
+            long_context.effective_batch_size(Some(1000)),
+            long_context.max_batch_size
+        );
+        assert!(long_context.batch_size < small.batch_size);
+
+        // Models recorded before batch sizes existed get the old behaviour
+        let legacy = legacy_model_config("custom-model", None);
+        assert_eq!(
+            legacy.effective_batch_size(None),
+            ck_models::DEFAULT_BATCH_SIZE
+        );
+        let parsed: ck_models::ModelConfig = serde_json::from_str(
+            r#"{"name":"m","provider":"fastembed","dimensions":384,"max_tokens":512,"description":""}"#,
+        )
+        .unwrap();
+        assert_eq!(
+            parsed.effective_batch_size(Some(4096)),
+            ck_models::DEFAULT_MAX_BATCH_SIZE
+        );
+    }
+
     /// Test embedder that can return empty results to test error handling
     struct EmptyResultsEmbedder;
 
@@ -1827,7 +3588,13 @@ mod tests {
         let mut empty_embedder: Box<dyn ck_embed::Embedder> = Box::new(EmptyResultsEmbedder);
 
         // This should return an error, not panic
-        let result = index_single_file(&test_file, test_path, Some(&mut empty_embedder));
+        let result = index_single_file(
+            &test_file,
+            test_path,
+            Some(&mut empty_embedder),
+            ChunkSettings::default(),
+            None,
+        );
 
         assert!(result.is_err());
         let error_msg = result.unwrap_err().to_string();
@@ -1855,6 +3622,9 @@ mod tests {
             &test_file,
             test_path,
             Some(&mut empty_embedder),
+            ChunkSettings::default(),
+            None,
+            None,
             Some(&dummy_callback),
             0,
             1,
@@ -1886,7 +3656,13 @@ mod tests {
             Box::new(MismatchedCountEmbedder);
 
         // This should return an error, not silently mismatch
-        let result = index_single_file(&test_file, test_path, Some(&mut mismatched_embedder));
+        let result = index_single_file(
+            &test_file,
+            test_path,
+            Some(&mut mismatched_embedder),
+            ChunkSettings::default(),
+            None,
+        );
 
         assert!(result.is_err());
         let error_msg = result.unwrap_err().to_string();
@@ -1896,6 +3672,33 @@ mod tests {
         assert!(error_msg.contains("Expected equal counts"));
     }
 
+    #[test]
+    fn test_load_index_entry_reads_legacy_sidecars() {
+        #[derive(Serialize)]
+        struct LegacyEntry {
+            metadata: FileMetadata,
+            chunks: Vec<ChunkEntry>,
+        }
+
+        let temp_dir = TempDir::new().unwrap();
+        let sidecar = temp_dir.path().join("legacy.rs.ck");
+        let legacy = LegacyEntry {
+            metadata: FileMetadata {
+                path: PathBuf::from("./legacy.rs"),
+                hash: "hash".to_string(),
+                last_modified: 0,
+                size: 0,
+            },
+            chunks: Vec::new(),
+        };
+        fs::write(&sidecar, bincode::serialize(&legacy).unwrap()).unwrap();
+
+        let entry = load_index_entry(&sidecar).unwrap();
+        assert_eq!(entry.metadata.hash, "hash");
+        assert!(entry.embedding_model.is_none());
+        assert!(entry.embedding_dimensions.is_none());
+    }
+
     #[test]
     fn test_index_single_file_with_valid_embedder_still_works() {
         let temp_dir = TempDir::new().unwrap();
@@ -1910,7 +3713,13 @@ mod tests {
         let mut boxed_embedder: Box<dyn ck_embed::Embedder> = Box::new(dummy_embedder);
 
         // This should work fine
-        let result = index_single_file(&test_file, test_path, Some(&mut boxed_embedder));
+        let result = index_single_file(
+            &test_file,
+            test_path,
+            Some(&mut boxed_embedder),
+            ChunkSettings::default(),
+            None,
+        );
 
         assert!(result.is_ok());
         let entry = result.unwrap();
@@ -1920,6 +3729,8 @@ mod tests {
             assert!(chunk.embedding.is_some());
             assert_eq!(chunk.embedding.as_ref().unwrap().len(), 384); // DummyEmbedder dimension
         }
+        assert_eq!(entry.embedding_model.as_deref(), Some("dummy"));
+        assert_eq!(entry.embedding_dimensions, Some(384));
     }
 
     #[tokio::test]
@@ -1934,7 +3745,12 @@ mod tests {
             respect_gitignore: true,
             use_ckignore: true,
             exclude_patterns: vec![],
+            include_globs: vec![],
             show_hidden: false,
+            follow_links: false,
+            include_submodules: false,
+            max_depth: None,
+            skip_generated: false,
         };
 
         // First index
@@ -1969,122 +3785,855 @@ mod tests {
         assert_eq!(stats4.files_indexed, 1);
     }
 
-    #[test]
+    #[test]
+    #[serial]
+    fn test_cleanup_index() {
+        unsafe { std::env::remove_var(ck_core::INDEX_DIR_ENV) };
+        let temp_dir = TempDir::new().unwrap();
+        let test_path = temp_dir.path();
+
+        // Create index directory and manifest
+        let index_dir = test_path.join(".ck");
+        fs::create_dir_all(&index_dir).unwrap();
+
+        let mut manifest = IndexManifest::default();
+        manifest.files.insert(
+            test_path.join("deleted_file.txt"),
+            FileMetadata {
+                path: test_path.join("deleted_file.txt"),
+                hash: "fake_hash".to_string(),
+                last_modified: 0,
+                size: 0,
+            },
+        );
+
+        let manifest_path = index_dir.join("manifest.json");
+        save_manifest(&manifest_path, &manifest).unwrap();
+
+        // Cleanup should remove orphaned entry
+        let file_options = ck_core::FileCollectionOptions {
+            respect_gitignore: true,
+            use_ckignore: true,
+            exclude_patterns: vec![],
+            include_globs: vec![],
+            show_hidden: false,
+            follow_links: false,
+            include_submodules: false,
+            max_depth: None,
+            skip_generated: false,
+        };
+        let stats = cleanup_index(test_path, &file_options).unwrap();
+        assert_eq!(stats.orphaned_entries_removed, 1);
+
+        // Check that manifest was updated
+        let updated_manifest = load_or_create_manifest(&manifest_path).unwrap();
+        assert_eq!(updated_manifest.files.len(), 0);
+    }
+
+    #[test]
+    fn test_index_write_lock_blocks_concurrent_writer() {
+        use std::sync::Arc;
+        use std::sync::atomic::{AtomicBool, Ordering};
+
+        let temp_dir = TempDir::new().unwrap();
+        let index_dir = temp_dir.path().join(".ck");
+
+        let lock = acquire_index_write_lock(&index_dir).unwrap();
+        let released = Arc::new(AtomicBool::new(false));
+
+        let dir = index_dir.clone();
+        let released_flag = released.clone();
+        let waiter = std::thread::spawn(move || {
+            let _lock = acquire_index_write_lock(&dir).unwrap();
+            assert!(
+                released_flag.load(Ordering::SeqCst),
+                "second writer acquired the index lock while the first still held it"
+            );
+        });
+
+        std::thread::sleep(std::time::Duration::from_millis(200));
+        released.store(true, Ordering::SeqCst);
+        drop(lock);
+        waiter.join().unwrap();
+    }
+
+    #[test]
+    #[serial]
+    fn test_clean_index_removes_index_dir() {
+        unsafe { std::env::remove_var(ck_core::INDEX_DIR_ENV) };
+        let temp_dir = TempDir::new().unwrap();
+        let test_path = temp_dir.path();
+        let index_dir = test_path.join(".ck");
+        fs::create_dir_all(index_dir.join("sub")).unwrap();
+        fs::write(index_dir.join("manifest.json"), "{}").unwrap();
+        fs::write(index_dir.join("sub").join("file.rs.ck"), "x").unwrap();
+
+        clean_index(test_path).unwrap();
+        assert!(
+            !index_dir.exists(),
+            "clean_index should remove the .ck directory entirely"
+        );
+    }
+
+    #[test]
+    #[serial]
+    fn test_clean_index_keeps_config_file() {
+        unsafe { std::env::remove_var(ck_core::INDEX_DIR_ENV) };
+        let temp_dir = TempDir::new().unwrap();
+        let index_dir = temp_dir.path().join(".ck");
+        fs::create_dir_all(&index_dir).unwrap();
+        fs::write(index_dir.join("manifest.json"), "{}").unwrap();
+        fs::write(index_dir.join("config.toml"), "topk = 5\n").unwrap();
+
+        clean_index(temp_dir.path()).unwrap();
+        assert!(!index_dir.join("manifest.json").exists());
+        assert!(index_dir.join("config.toml").exists());
+    }
+
+    #[test]
+    #[serial]
+    fn test_pinned_model_is_found_from_subdirectories() {
+        unsafe { std::env::remove_var(ck_core::INDEX_DIR_ENV) };
+        let temp_dir = TempDir::new().unwrap();
+        let nested = temp_dir.path().join("src");
+        fs::create_dir_all(&nested).unwrap();
+        assert_eq!(pinned_model(&nested), None);
+
+        let index_dir = ck_core::index_dir(temp_dir.path());
+        fs::create_dir_all(&index_dir).unwrap();
+        let manifest = IndexManifest {
+            embedding_model: Some("BAAI/bge-small-en-v1.5".to_string()),
+            ..IndexManifest::default()
+        };
+        fs::write(
+            index_dir.join("manifest.json"),
+            serde_json::to_vec(&manifest).unwrap(),
+        )
+        .unwrap();
+        assert_eq!(
+            pinned_model(&nested).as_deref(),
+            Some("BAAI/bge-small-en-v1.5")
+        );
+
+        clean_index(temp_dir.path()).unwrap();
+        assert_eq!(pinned_model(&nested), None);
+    }
+
+    #[test]
+    #[serial]
+    fn test_get_index_stats() {
+        unsafe { std::env::remove_var(ck_core::INDEX_DIR_ENV) };
+        let temp_dir = TempDir::new().unwrap();
+        let test_path = temp_dir.path();
+
+        // No index exists
+        let stats = get_index_stats(test_path).unwrap();
+        assert_eq!(stats.total_files, 0);
+
+        // Create index
+        let index_dir = test_path.join(".ck");
+        fs::create_dir_all(&index_dir).unwrap();
+
+        let mut manifest = IndexManifest::default();
+        manifest.files.insert(
+            test_path.join("test.txt"),
+            FileMetadata {
+                path: test_path.join("test.txt"),
+                hash: "test_hash".to_string(),
+                last_modified: 1234567890,
+                size: 100,
+            },
+        );
+
+        let manifest_path = index_dir.join("manifest.json");
+        save_manifest(&manifest_path, &manifest).unwrap();
+
+        let stats = get_index_stats(test_path).unwrap();
+        assert_eq!(stats.total_files, 1);
+    }
+
+    #[tokio::test]
+    #[serial]
+    async fn test_add_paths_indexes_only_the_given_files_and_directories() {
+        unsafe { std::env::remove_var(ck_core::INDEX_DIR_ENV) };
+        let temp_dir = TempDir::new().unwrap();
+        let root = temp_dir.path();
+        fs::write(root.join("a.rs"), "fn alpha() {}\n").unwrap();
+        let options = ck_core::FileCollectionOptions::default();
+        smart_update_index(root, false, &options).await.unwrap();
+
+        fs::write(root.join("b.rs"), "fn beta() {}\n").unwrap();
+        fs::write(root.join("untouched.rs"), "fn gamma() {}\n").unwrap();
+        fs::create_dir(root.join("src")).unwrap();
+        fs::write(root.join("src/c.rs"), "fn delta() {}\n").unwrap();
+        fs::write(root.join("src/skip.log"), "noise\n").unwrap();
+        fs::write(root.join(".ckignore"), "*.log\n").unwrap();
+
+        let stats = add_paths(
+            &[root.join("b.rs"), root.join("src")],
+            false,
+            &options,
+            None,
+            false,
+        )
+        .await
+        .unwrap();
+        assert_eq!(stats.files_added, 2);
+        let indexed = indexed_files(root);
+        assert!(indexed.contains(&root.join("src/c.rs")));
+        assert!(!indexed.contains(&root.join("src/skip.log")));
+        assert!(!indexed.contains(&root.join("untouched.rs")));
+
+        fs::write(root.join("a.rs"), "fn alpha() { changed() }\n").unwrap();
+        let stats = add_paths(
+            &[root.join("a.rs"), root.join("b.rs")],
+            false,
+            &options,
+            None,
+            false,
+        )
+        .await
+        .unwrap();
+        assert_eq!(
+            stats,
+            AddStats {
+                files_added: 0,
+                files_updated: 1,
+                files_up_to_date: 1,
+            }
+        );
+
+        assert!(
+            add_paths(&[root.join("missing.rs")], false, &options, None, false)
+                .await
+                .is_err()
+        );
+    }
+
+    #[tokio::test]
+    #[serial]
+    async fn test_resume_skips_checkpointed_files() {
+        unsafe { std::env::remove_var(ck_core::INDEX_DIR_ENV) };
+        let temp_dir = TempDir::new().unwrap();
+        let root = temp_dir.path();
+        for name in ["a.rs", "b.rs", "c.rs", "d.rs"] {
+            fs::write(root.join(name), format!("fn {}() {{}}\n", &name[..1])).unwrap();
+        }
+        let options = ck_core::FileCollectionOptions::default();
+
+        smart_update_index_with_detailed_progress(root, false, None, None, true, &options, None)
+            .await
+            .unwrap();
+        assert!(
+            !has_checkpoint(root),
+            "clean completion removes the checkpoint"
+        );
+
+        // Interrupt a rebuild as it starts on its second file
+        let index_dir = ck_core::index_dir(root);
+        let token = CancellationToken::new();
+        let canceller = token.clone();
+        let started = std::sync::atomic::AtomicUsize::new(0);
+        let progress: ProgressCallback = Box::new(move |_| {
+            if started.fetch_add(1, Ordering::SeqCst) == 1 {
+                canceller.cancel();
+            }
+        });
+        let err = smart_update_index_with_resume(
+            root,
+            &options,
+            Some(progress),
+            None,
+            IndexUpdateOptions {
+                force_rebuild: true,
+                compute_embeddings: true,
+                cancel: Some(&token),
+                ..Default::default()
+            },
+        )
+        .await
+        .unwrap_err();
+        assert!(ck_core::is_cancelled(&err));
+        assert!(has_checkpoint(root), "a cancelled rebuild can be resumed");
+        let completed = checkpoint::load_checkpoint(&index_dir);
+        assert_eq!(completed.len(), 1);
+
+        let stats = smart_update_index_with_resume(
+            root,
+            &options,
+            None,
+            None,
+            IndexUpdateOptions {
+                force_rebuild: true,
+                compute_embeddings: true,
+                resume: true,
+                ..Default::default()
+            },
+        )
+        .await
+        .unwrap();
+        assert_eq!(stats.files_up_to_date, 1);
+        assert_eq!(stats.files_added, 3);
+        assert!(!has_checkpoint(root));
+
+        let manifest = load_or_create_manifest(&index_dir.join("manifest.json")).unwrap();
+        assert_eq!(manifest.files.len(), 4);
+        assert!(completed.keys().all(|key| manifest.files.contains_key(key)));
+    }
+
+    #[tokio::test]
+    #[serial]
+    async fn test_cancelled_update_stops_between_files_and_resumes() {
+        unsafe { std::env::remove_var(ck_core::INDEX_DIR_ENV) };
+        let temp_dir = TempDir::new().unwrap();
+        let root = temp_dir.path();
+        for name in ["a.rs", "b.rs", "c.rs", "d.rs"] {
+            fs::write(root.join(name), format!("fn {}() {{}}\n", &name[..1])).unwrap();
+        }
+        let options = ck_core::FileCollectionOptions::default();
+
+        // Cancelled up front: nothing is written
+        let token = CancellationToken::new();
+        token.cancel();
+        let err = smart_update_index_with_resume(
+            root,
+            &options,
+            None,
+            None,
+            IndexUpdateOptions {
+                compute_embeddings: true,
+                cancel: Some(&token),
+                ..Default::default()
+            },
+        )
+        .await
+        .unwrap_err();
+        assert!(ck_core::is_cancelled(&err));
+        let index_dir = ck_core::index_dir(root);
+        assert!(!index_dir.join("manifest.json").exists());
+
+        // Cancelled while embedding the second file
+        let token = CancellationToken::new();
+        let canceller = token.clone();
+        let progress: DetailedProgressCallback = Box::new(move |progress| {
+            if progress.file_index == 1 {
+                canceller.cancel();
+            }
+        });
+        let err = smart_update_index_with_resume(
+            root,
+            &options,
+            None,
+            Some(progress),
+            IndexUpdateOptions {
+                compute_embeddings: true,
+                cancel: Some(&token),
+                ..Default::default()
+            },
+        )
+        .await
+        .unwrap_err();
+        assert!(ck_core::is_cancelled(&err));
+        let manifest = load_or_create_manifest(&index_dir.join("manifest.json")).unwrap();
+        assert!(!manifest.files.is_empty() && manifest.files.len() < 4);
+        for key in manifest.files.keys() {
+            let file = root.join(path_utils::from_manifest_path(key));
+            assert!(load_index_entry(&get_sidecar_path(root, &file)).is_ok());
+        }
+        assert!(has_checkpoint(root), "a cancelled pass can be resumed");
+
+        let stats = smart_update_index_with_resume(
+            root,
+            &options,
+            None,
+            None,
+            IndexUpdateOptions {
+                compute_embeddings: true,
+                resume: true,
+                ..Default::default()
+            },
+        )
+        .await
+        .unwrap();
+        assert_eq!(stats.files_up_to_date + stats.files_added, 4);
+        assert!(!has_checkpoint(root));
+    }
+
+    #[tokio::test]
+    #[serial]
+    async fn test_cancelled_rebuild_stops_before_indexing() {
+        unsafe { std::env::remove_var(ck_core::INDEX_DIR_ENV) };
+        let temp_dir = TempDir::new().unwrap();
+        let root = temp_dir.path();
+        fs::write(root.join("a.rs"), "fn alpha() {}\n").unwrap();
+        fs::write(root.join("b.rs"), "fn beta() {}\n").unwrap();
+        let options = ck_core::FileCollectionOptions::default();
+        smart_update_index(root, false, &options).await.unwrap();
+
+        let token = CancellationToken::new();
+        token.cancel();
+        // Both the sequential (embedding) and parallel rebuilds honour it
+        for compute_embeddings in [true, false] {
+            let err = smart_update_index_with_resume(
+                root,
+                &options,
+                None,
+                None,
+                IndexUpdateOptions {
+                    force_rebuild: true,
+                    compute_embeddings,
+                    cancel: Some(&token),
+                    ..Default::default()
+                },
+            )
+            .await
+            .unwrap_err();
+            assert!(ck_core::is_cancelled(&err));
+            let manifest_path = ck_core::index_dir(root).join("manifest.json");
+            let manifest = load_or_create_manifest(&manifest_path).unwrap();
+            assert!(manifest.files.is_empty());
+        }
+    }
+
+    #[tokio::test]
+    #[serial]
+    async fn test_index_log_records_every_decision() {
+        unsafe { std::env::remove_var(ck_core::INDEX_DIR_ENV) };
+        let temp_dir = TempDir::new().unwrap();
+        let root = temp_dir.path();
+        fs::create_dir(root.join("vendor")).unwrap();
+        fs::write(root.join("a.rs"), "fn alpha() {}\n").unwrap();
+        fs::write(root.join("b.rs"), "fn beta() {}\n").unwrap();
+        fs::write(root.join("vendor/dep.rs"), "fn dep() {}\n").unwrap();
+        let options = ck_core::FileCollectionOptions {
+            exclude_patterns: vec!["vendor".to_string()],
+            ..Default::default()
+        };
+        let log_dir = TempDir::new().unwrap();
+        let log_path = log_dir.path().join("index.log");
+        let run = || async {
+            let log = IndexLog::create(&log_path, root).unwrap();
+            smart_update_index_with_resume(
+                root,
+                &options,
+                None,
+                None,
+                IndexUpdateOptions {
+                    index_log: Some(&log),
+                    ..Default::default()
+                },
+            )
+            .await
+            .unwrap();
+            let mut records: Vec<IndexLogRecord> = fs::read_to_string(&log_path)
+                .unwrap()
+                .lines()
+                .map(|line| serde_json::from_str(line).unwrap())
+                .collect();
+            records.sort_by(|a, b| a.path.cmp(&b.path));
+            records
+        };
+
+        let records = run().await;
+        let decisions: Vec<_> = records
+            .iter()
+            .map(|r| (r.path.as_str(), r.decision, r.reason.as_deref()))
+            .collect();
+        assert_eq!(
+            decisions,
+            [
+                ("a.rs", IndexDecision::Indexed, None),
+                ("b.rs", IndexDecision::Indexed, None),
+                (
+                    "vendor/",
+                    IndexDecision::Excluded,
+                    Some("exclude pattern 'vendor'")
+                ),
+            ]
+        );
+        assert_eq!(records[0].chunks, Some(1));
+        assert!(records[0].duration_ms.is_some());
+
+        // The next pass overwrites the log and skips what hasn't changed
+        fs::write(root.join("b.rs"), "fn beta() { gamma() }\n").unwrap();
+        let records = run().await;
+        assert_eq!(records[0].decision, IndexDecision::Skipped);
+        assert_eq!(records[0].reason.as_deref(), Some("unchanged"));
+        assert_eq!(records[1].decision, IndexDecision::Indexed);
+        assert_eq!(records.len(), 3);
+    }
+
+    #[tokio::test]
+    #[serial]
+    async fn test_sidecar_compression_converts_and_persists() {
+        unsafe { std::env::remove_var(ck_core::INDEX_DIR_ENV) };
+        let temp_dir = TempDir::new().unwrap();
+        let root = temp_dir.path();
+        fs::write(root.join("a.rs"), "fn alpha() {}\n").unwrap();
+        let options = ck_core::FileCollectionOptions::default();
+        smart_update_index(root, false, &options).await.unwrap();
+        let sidecar = get_sidecar_path(root, &root.join("a.rs"));
+        // The zstd header follows the 5-byte `CKSC` format header
+        assert!(!fs::read(&sidecar).unwrap()[5..].starts_with(b"CKZS"));
+
+        assert_eq!(set_sidecar_compression(root, true).unwrap(), 1);
+        assert!(fs::read(&sidecar).unwrap()[5..].starts_with(b"CKZS"));
+        assert!(index_sidecar_compression(root));
+        assert!(get_index_stats(root).unwrap().compressed_sidecars);
+
+        // New files and a full rebuild keep the setting
+        fs::write(root.join("b.rs"), "fn beta() {}\n").unwrap();
+        smart_update_index(root, false, &options).await.unwrap();
+        let new_sidecar = get_sidecar_path(root, &root.join("b.rs"));
+        assert!(fs::read(&new_sidecar).unwrap()[5..].starts_with(b"CKZS"));
+        smart_update_index_with_resume(
+            root,
+            &options,
+            None,
+            None,
+            IndexUpdateOptions {
+                force_rebuild: true,
+                ..Default::default()
+            },
+        )
+        .await
+        .unwrap();
+        assert!(index_sidecar_compression(root));
+        assert_eq!(load_index_entry(&sidecar).unwrap().chunks.len(), 1);
+
+        assert_eq!(set_sidecar_compression(root, false).unwrap(), 2);
+        assert!(!fs::read(&new_sidecar).unwrap()[5..].starts_with(b"CKZS"));
+    }
+
+    #[tokio::test]
+    #[serial]
+    async fn test_dimension_reduction_projects_stored_and_new_vectors() {
+        unsafe { std::env::remove_var(ck_core::INDEX_DIR_ENV) };
+        let temp_dir = TempDir::new().unwrap();
+        let root = temp_dir.path();
+        fs::write(root.join("a.rs"), "fn alpha() {}\n\nfn beta() {}\n").unwrap();
+        fs::write(root.join("b.rs"), "fn gamma() {}\n\nfn delta() {}\n").unwrap();
+        let options = ck_core::FileCollectionOptions::default();
+        smart_update_index_with_detailed_progress(root, false, None, None, true, &options, None)
+            .await
+            .unwrap();
+        let vector_lengths = |file: &str| -> Vec<usize> {
+            load_index_entry(&get_sidecar_path(root, &root.join(file)))
+                .unwrap()
+                .chunks
+                .iter()
+                .filter_map(|chunk| chunk.embedding.as_ref().map(Vec::len))
+                .collect()
+        };
+        let full = vector_lengths("a.rs")[0];
+
+        assert_eq!(set_dimension_reduction(root, 8).unwrap(), 2);
+        assert_eq!(index_reduced_dimensions(root), Some(8));
+        assert!(vector_lengths("a.rs").iter().all(|&len| len == 8));
+        let projection = index_projection(root).unwrap();
+        assert_eq!(projection.source_dimensions(), full);
+        assert_eq!(get_index_stats(root).unwrap().reduced_dimensions, Some(8));
+
+        // Reapplying is a no-op; other dimensions need the full vectors back
+        assert_eq!(set_dimension_reduction(root, 8).unwrap(), 0);
+        assert!(set_dimension_reduction(root, 4).is_err());
+
+        // New files are embedded straight into the reduced space
+        fs::write(root.join("c.rs"), "fn epsilon() {}\n").unwrap();
+        smart_update_index_with_detailed_progress(root, false, None, None, true, &options, None)
+            .await
+            .unwrap();
+        assert!(vector_lengths("c.rs").iter().all(|&len| len == 8));
+        let entry = load_index_entry(&get_sidecar_path(root, &root.join("c.rs"))).unwrap();
+        assert_eq!(entry.embedding_dimensions, Some(8));
+    }
+
+    #[tokio::test]
+    #[serial]
+    async fn test_migrate_index_rewrites_headerless_sidecars() {
+        unsafe { std::env::remove_var(ck_core::INDEX_DIR_ENV) };
+        let temp_dir = TempDir::new().unwrap();
+        let root = temp_dir.path();
+        fs::write(root.join("a.rs"), "fn alpha() {}\n").unwrap();
+        fs::write(root.join("b.rs"), "fn beta() {}\n").unwrap();
+        let options = ck_core::FileCollectionOptions::default();
+        smart_update_index(root, false, &options).await.unwrap();
+
+        // Make the index look like one an older ck built
+        let manifest_path = ck_core::index_dir(root).join("manifest.json");
+        let mut manifest = read_manifest(&manifest_path).unwrap();
+        manifest.format_version = 1;
+        save_manifest(&manifest_path, &manifest).unwrap();
+        let sidecar = get_sidecar_path(root, &root.join("a.rs"));
+        let entry = load_index_entry(&sidecar).unwrap();
+        fs::write(&sidecar, bincode::serialize(&entry).unwrap()).unwrap();
+
+        let stats = migrate_index(root).unwrap();
+        assert_eq!((stats.migrated, stats.current), (1, 1));
+        assert!(stats.failed.is_empty());
+        assert_eq!(
+            sidecar::sidecar_format_version(&sidecar).unwrap(),
+            INDEX_FORMAT_VERSION
+        );
+        assert_eq!(
+            read_manifest(&manifest_path).unwrap().format_version,
+            INDEX_FORMAT_VERSION
+        );
+
+        // An index from a newer ck is refused with a clear message
+        manifest.format_version = INDEX_FORMAT_VERSION + 1;
+        save_manifest(&manifest_path, &manifest).unwrap();
+        let err = migrate_index(root).unwrap_err();
+        assert!(err.to_string().contains("newer ck"), "{err}");
+    }
+
+    #[tokio::test]
+    #[serial]
+    async fn test_chunk_overlap_is_recorded_and_enforced() {
+        unsafe { std::env::remove_var(ck_core::INDEX_DIR_ENV) };
+        let temp_dir = TempDir::new().unwrap();
+        let root = temp_dir.path();
+        fs::write(
+            root.join("lib.rs"),
+            "fn alpha() {\n    1\n}\n\nfn beta() {\n    2\n}\n",
+        )
+        .unwrap();
+        let options = ck_core::FileCollectionOptions::default();
+
+        smart_update_index_with_resume(
+            root,
+            &options,
+            None,
+            None,
+            IndexUpdateOptions {
+                chunk_overlap: Some(2),
+                ..Default::default()
+            },
+        )
+        .await
+        .unwrap();
+        assert_eq!(index_chunk_overlap(root), 2);
+
+        // Stored spans cover each chunk's own lines only
+        let entry = load_index_entry(&get_sidecar_path(root, &root.join("lib.rs"))).unwrap();
+        for pair in entry.chunks.windows(2) {
+            assert!(pair[0].span.line_end < pair[1].span.line_start);
+        }
+
+        // Later updates keep the setting; a different one needs a rebuild
+        smart_update_index_with_detailed_progress(root, false, None, None, false, &options, None)
+            .await
+            .unwrap();
+        assert_eq!(index_chunk_overlap(root), 2);
+        let err = smart_update_index_with_resume(
+            root,
+            &options,
+            None,
+            None,
+            IndexUpdateOptions {
+                chunk_overlap: Some(4),
+                ..Default::default()
+            },
+        )
+        .await
+        .unwrap_err();
+        assert!(err.to_string().contains("Chunk overlap mismatch"));
+
+        smart_update_index_with_resume(
+            root,
+            &options,
+            None,
+            None,
+            IndexUpdateOptions {
+                force_rebuild: true,
+                chunk_overlap: Some(0),
+                ..Default::default()
+            },
+        )
+        .await
+        .unwrap();
+        assert_eq!(index_chunk_overlap(root), 0);
+    }
+
+    #[tokio::test]
+    #[serial]
+    async fn test_find_stale_files() {
+        unsafe { std::env::remove_var(ck_core::INDEX_DIR_ENV) };
+        let temp_dir = TempDir::new().unwrap();
+        let root = temp_dir.path();
+        fs::write(root.join("kept.txt"), "unchanged\n").unwrap();
+        fs::write(root.join("edited.txt"), "before\n").unwrap();
+        let options = ck_core::FileCollectionOptions::default();
+        smart_update_index_with_detailed_progress(root, false, None, None, false, &options, None)
+            .await
+            .unwrap();
+
+        fs::write(root.join("edited.txt"), "after the edit\n").unwrap();
+        fs::write(root.join("new.txt"), "not indexed\n").unwrap();
+        let kept = root.join("kept.txt");
+        let edited = root.join("edited.txt");
+        let new = root.join("new.txt");
+        let stale = find_stale_files(
+            root,
+            [kept.as_path(), edited.as_path(), new.as_path()],
+            false,
+        );
+        assert_eq!(stale, HashSet::from([edited]));
+    }
+
+    #[tokio::test]
     #[serial]
-    fn test_cleanup_index() {
+    async fn test_check_index_freshness() {
         unsafe { std::env::remove_var(ck_core::INDEX_DIR_ENV) };
         let temp_dir = TempDir::new().unwrap();
-        let test_path = temp_dir.path();
-
-        // Create index directory and manifest
-        let index_dir = test_path.join(".ck");
-        fs::create_dir_all(&index_dir).unwrap();
+        let root = temp_dir.path();
+        fs::write(root.join("kept.txt"), "unchanged\n").unwrap();
+        fs::write(root.join("edited.txt"), "before\n").unwrap();
+        let options = ck_core::FileCollectionOptions::default();
+        assert_eq!(check_index_freshness(root, &options).unwrap(), None);
 
-        let mut manifest = IndexManifest::default();
-        manifest.files.insert(
-            test_path.join("deleted_file.txt"),
-            FileMetadata {
-                path: test_path.join("deleted_file.txt"),
-                hash: "fake_hash".to_string(),
-                last_modified: 0,
-                size: 0,
-            },
+        smart_update_index_with_detailed_progress(root, false, None, None, false, &options, None)
+            .await
+            .unwrap();
+        assert!(
+            check_index_freshness(root, &options)
+                .unwrap()
+                .unwrap()
+                .is_fresh()
         );
 
-        let manifest_path = index_dir.join("manifest.json");
-        save_manifest(&manifest_path, &manifest).unwrap();
-
-        // Cleanup should remove orphaned entry
-        let file_options = ck_core::FileCollectionOptions {
-            respect_gitignore: true,
-            use_ckignore: true,
-            exclude_patterns: vec![],
-            show_hidden: false,
-        };
-        let stats = cleanup_index(test_path, &file_options).unwrap();
-        assert_eq!(stats.orphaned_entries_removed, 1);
-
-        // Check that manifest was updated
-        let updated_manifest = load_or_create_manifest(&manifest_path).unwrap();
-        assert_eq!(updated_manifest.files.len(), 0);
+        // mtimes have one-second resolution here, so date the edit ahead
+        fs::write(root.join("edited.txt"), "after the edit\n").unwrap();
+        fs::File::options()
+            .write(true)
+            .open(root.join("edited.txt"))
+            .unwrap()
+            .set_modified(SystemTime::now() + std::time::Duration::from_secs(5))
+            .unwrap();
+        fs::write(root.join("new.txt"), "not indexed\n").unwrap();
+        let freshness = check_index_freshness(root, &options).unwrap().unwrap();
+        assert_eq!((freshness.changed_files, freshness.new_files), (1, 1));
     }
 
-    #[test]
-    fn test_index_write_lock_blocks_concurrent_writer() {
-        use std::sync::Arc;
-        use std::sync::atomic::{AtomicBool, Ordering};
-
+    #[tokio::test]
+    #[serial]
+    async fn test_force_hash_catches_edits_that_kept_the_mtime() {
+        unsafe { std::env::remove_var(ck_core::INDEX_DIR_ENV) };
         let temp_dir = TempDir::new().unwrap();
-        let index_dir = temp_dir.path().join(".ck");
+        let root = temp_dir.path();
+        let file = root.join("lib.rs");
+        fs::write(&file, "fn before() {}\n").unwrap();
+        let options = ck_core::FileCollectionOptions::default();
+        smart_update_index_with_detailed_progress(root, false, None, None, false, &options, None)
+            .await
+            .unwrap();
 
-        let lock = acquire_index_write_lock(&index_dir).unwrap();
-        let released = Arc::new(AtomicBool::new(false));
+        // Same size, and the timestamp put back as a checkout would leave it
+        let mtime = fs::metadata(&file).unwrap().modified().unwrap();
+        fs::write(&file, "fn after_() {}\n").unwrap();
+        fs::File::options()
+            .write(true)
+            .open(&file)
+            .unwrap()
+            .set_modified(mtime)
+            .unwrap();
 
-        let dir = index_dir.clone();
-        let released_flag = released.clone();
-        let waiter = std::thread::spawn(move || {
-            let _lock = acquire_index_write_lock(&dir).unwrap();
-            assert!(
-                released_flag.load(Ordering::SeqCst),
-                "second writer acquired the index lock while the first still held it"
-            );
-        });
+        let stats = smart_update_index_with_detailed_progress(
+            root, false, None, None, false, &options, None,
+        )
+        .await
+        .unwrap();
+        assert_eq!(stats.files_modified, 0);
 
-        std::thread::sleep(std::time::Duration::from_millis(200));
-        released.store(true, Ordering::SeqCst);
-        drop(lock);
-        waiter.join().unwrap();
+        let force_hash = IndexUpdateOptions {
+            force_hash: true,
+            ..Default::default()
+        };
+        let stats = smart_update_index_with_resume(root, &options, None, None, force_hash)
+            .await
+            .unwrap();
+        assert_eq!((stats.files_modified, stats.files_up_to_date), (1, 0));
+
+        // Hashing an unchanged file still leaves it alone
+        let stats = smart_update_index_with_resume(root, &options, None, None, force_hash)
+            .await
+            .unwrap();
+        assert_eq!(stats.files_up_to_date, 1);
     }
 
-    #[test]
+    #[tokio::test]
     #[serial]
-    fn test_clean_index_removes_index_dir() {
+    async fn test_files_over_the_time_budget_are_skipped_and_reported() {
         unsafe { std::env::remove_var(ck_core::INDEX_DIR_ENV) };
         let temp_dir = TempDir::new().unwrap();
-        let test_path = temp_dir.path();
-        let index_dir = test_path.join(".ck");
-        fs::create_dir_all(index_dir.join("sub")).unwrap();
-        fs::write(index_dir.join("manifest.json"), "{}").unwrap();
-        fs::write(index_dir.join("sub").join("file.rs.ck"), "x").unwrap();
-
-        clean_index(test_path).unwrap();
-        assert!(
-            !index_dir.exists(),
-            "clean_index should remove the .ck directory entirely"
-        );
+        let root = temp_dir.path();
+        fs::write(root.join("bundle.min.js"), "var a=1;".repeat(1000)).unwrap();
+        let options = ck_core::FileCollectionOptions::default();
+
+        // A zero budget runs out before any file is chunked
+        let stats = smart_update_index_with_resume(
+            root,
+            &options,
+            None,
+            None,
+            IndexUpdateOptions {
+                file_timeout: Some(Duration::ZERO),
+                ..Default::default()
+            },
+        )
+        .await
+        .unwrap();
+        assert_eq!(stats.files_indexed, 0);
+        assert_eq!(stats.timed_out_files, [root.join("bundle.min.js")]);
+        assert!(!get_sidecar_path(root, &root.join("bundle.min.js")).exists());
+
+        // Skipped files are picked up again by the next update
+        let stats = smart_update_index_with_resume(
+            root,
+            &options,
+            None,
+            None,
+            IndexUpdateOptions {
+                ..Default::default()
+            },
+        )
+        .await
+        .unwrap();
+        assert_eq!(stats.files_indexed, 1);
+        assert!(stats.timed_out_files.is_empty());
     }
 
-    #[test]
+    #[tokio::test]
     #[serial]
-    fn test_get_index_stats() {
+    async fn test_get_index_stats_breakdown() {
         unsafe { std::env::remove_var(ck_core::INDEX_DIR_ENV) };
         let temp_dir = TempDir::new().unwrap();
         let test_path = temp_dir.path();
+        fs::write(test_path.join("a.rs"), "fn alpha() {}\n\nfn beta() {}\n").unwrap();
+        fs::write(test_path.join("b.rs"), "fn gamma() {}\n").unwrap();
+        fs::write(test_path.join("notes.txt"), "some notes").unwrap();
 
-        // No index exists
-        let stats = get_index_stats(test_path).unwrap();
-        assert_eq!(stats.total_files, 0);
-
-        // Create index
-        let index_dir = test_path.join(".ck");
-        fs::create_dir_all(&index_dir).unwrap();
-
-        let mut manifest = IndexManifest::default();
-        manifest.files.insert(
-            test_path.join("test.txt"),
-            FileMetadata {
-                path: test_path.join("test.txt"),
-                hash: "test_hash".to_string(),
-                last_modified: 1234567890,
-                size: 100,
-            },
-        );
-
-        let manifest_path = index_dir.join("manifest.json");
-        save_manifest(&manifest_path, &manifest).unwrap();
+        let options = ck_core::FileCollectionOptions::default();
+        smart_update_index_with_detailed_progress(
+            test_path, false, None, None, true, &options, None,
+        )
+        .await
+        .unwrap();
 
         let stats = get_index_stats(test_path).unwrap();
-        assert_eq!(stats.total_files, 1);
+        let rust = stats.by_language["rust"];
+        assert_eq!(rust.files, 2);
+        assert!(rust.chunks >= 2);
+        assert_eq!(stats.by_language["other"].files, 1);
+        let chunk_sum: usize = stats.by_language.values().map(|lang| lang.chunks).sum();
+        assert_eq!(chunk_sum, stats.total_chunks);
+        assert_eq!(stats.models_used.len(), 1, "{:?}", stats.models_used);
+        assert!(stats.orphaned_files.is_empty());
+
+        // A deleted source file is reported until the index is cleaned
+        fs::remove_file(test_path.join("b.rs")).unwrap();
+        let stats = get_index_stats(test_path).unwrap();
+        assert_eq!(stats.orphaned_files, vec![PathBuf::from("b.rs")]);
     }
 
     #[test]
@@ -2152,6 +4701,38 @@ mod tests {
         assert!(!is_text_file(&nonexistent));
     }
 
+    #[tokio::test]
+    #[serial]
+    async fn test_index_skips_binary_and_decodes_invalid_utf8_lossily() {
+        unsafe { std::env::remove_var(ck_core::INDEX_DIR_ENV) };
+        let temp_dir = TempDir::new().unwrap();
+        let root = temp_dir.path();
+        // A PNG header: binary, with NUL bytes in the first few bytes
+        fs::write(
+            root.join("logo.png"),
+            b"\x89PNG\r\n\x1a\n\x00\x00\x00\rIHDR\x00\x00\x00\x10",
+        )
+        .unwrap();
+        // Latin-1 text: no NULs, but "café" isn't valid UTF-8
+        fs::write(root.join("notes.txt"), b"menu: caf\xe9 au lait\n").unwrap();
+        fs::write(root.join("main.rs"), "fn main() {}\n").unwrap();
+
+        assert!(is_binary_file(&root.join("logo.png")));
+        assert!(!is_binary_file(&root.join("notes.txt")));
+        assert_eq!(
+            read_text(&root.join("notes.txt")).unwrap(),
+            "menu: caf\u{fffd} au lait\n"
+        );
+
+        let options = ck_core::FileCollectionOptions::default();
+        let stats = smart_update_index(root, false, &options).await.unwrap();
+        assert_eq!(stats.files_errored, 0);
+        assert_eq!(stats.files_indexed, 2);
+        let indexed = indexed_files(root);
+        assert!(indexed.contains(&root.join("notes.txt")));
+        assert!(!indexed.contains(&root.join("logo.png")));
+    }
+
     #[test]
     fn test_remove_empty_dirs() {
         let temp_dir = TempDir::new().unwrap();
@@ -2195,7 +4776,12 @@ mod tests {
             respect_gitignore: true,
             use_ckignore: false,
             exclude_patterns: vec![],
+            include_globs: vec![],
             show_hidden: false,
+            follow_links: false,
+            include_submodules: false,
+            max_depth: None,
+            skip_generated: false,
         };
         let files = collect_files(test_path, &options_respect).unwrap();
         assert_eq!(
@@ -2209,7 +4795,12 @@ mod tests {
             respect_gitignore: false,
             use_ckignore: false,
             exclude_patterns: vec![],
+            include_globs: vec![],
             show_hidden: false,
+            follow_links: false,
+            include_submodules: false,
+            max_depth: None,
+            skip_generated: false,
         };
         let files = collect_files(test_path, &options_no_ignore).unwrap();
         assert_eq!(
@@ -2219,6 +4810,277 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_collect_files_include_globs() {
+        let temp_dir = TempDir::new().unwrap();
+        let test_path = temp_dir.path();
+        fs::create_dir_all(test_path.join("src/nested")).unwrap();
+        fs::create_dir_all(test_path.join("docs")).unwrap();
+        fs::write(test_path.join("src/lib.rs"), "fn a() {}").unwrap();
+        fs::write(test_path.join("src/nested/mod.rs"), "fn b() {}").unwrap();
+        fs::write(test_path.join("src/notes.md"), "notes").unwrap();
+        fs::write(test_path.join("docs/guide.rs"), "fn c() {}").unwrap();
+
+        let options = ck_core::FileCollectionOptions {
+            include_globs: vec!["src/**/*.rs".to_string()],
+            exclude_patterns: vec!["nested".to_string()],
+            ..Default::default()
+        };
+        let files = collect_files(test_path, &options).unwrap();
+        let names: Vec<_> = files
+            .iter()
+            .map(|f| f.strip_prefix(test_path).unwrap().to_path_buf())
+            .collect();
+        // Exclude wins over a matching include
+        assert_eq!(names, vec![PathBuf::from("src/lib.rs")]);
+
+        let include = IncludeGlobs::new(test_path, &["*.md".to_string()]).unwrap();
+        assert!(include.matches(&test_path.join("src/notes.md")));
+        assert!(include.matches(Path::new("src/notes.md")));
+        assert!(!include.matches(&test_path.join("src/lib.rs")));
+        assert!(
+            IncludeGlobs::new(test_path, &[])
+                .unwrap()
+                .matches(Path::new("any"))
+        );
+    }
+
+    #[test]
+    fn test_hidden_entries_and_git_dir() {
+        let temp_dir = TempDir::new().unwrap();
+        let test_path = temp_dir.path();
+        fs::create_dir_all(test_path.join(".git")).unwrap();
+        fs::create_dir_all(test_path.join(".github")).unwrap();
+        fs::write(test_path.join(".git/config"), "[core]").unwrap();
+        fs::write(test_path.join(".github/ci.yml"), "on: push").unwrap();
+        fs::write(test_path.join(".env.example"), "KEY=value").unwrap();
+        fs::write(test_path.join("main.rs"), "fn main() {}").unwrap();
+
+        let names = |options: &ck_core::FileCollectionOptions| {
+            let mut names: Vec<String> = collect_files(test_path, options)
+                .unwrap()
+                .iter()
+                .map(|f| {
+                    f.strip_prefix(test_path)
+                        .unwrap()
+                        .to_string_lossy()
+                        .replace('\\', "/")
+                })
+                .collect();
+            names.sort();
+            names
+        };
+
+        let mut options = ck_core::FileCollectionOptions::default();
+        assert_eq!(names(&options), ["main.rs"]);
+
+        // Without any exclude patterns, `.git` is still left out
+        options.show_hidden = true;
+        assert_eq!(
+            names(&options),
+            [".env.example", ".github/ci.yml", "main.rs"]
+        );
+
+        // ... unless an include glob asks for it
+        options.include_globs = vec![".git/**".to_string()];
+        assert_eq!(names(&options), [".git/config"]);
+    }
+
+    #[cfg(feature = "pdf")]
+    #[test]
+    fn test_pdf_fixture_is_extracted_per_page() {
+        let fixture = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures/design.pdf");
+        let text = extract_pdf_text(&fixture).unwrap();
+        let page_of = |needle: &str| {
+            let line = text.lines().position(|line| line.contains(needle)).unwrap() + 1;
+            ck_core::pdf::page_at_line(&text, line)
+        };
+        assert_eq!(page_of("Retry policy"), Some(1));
+        assert_eq!(page_of("Cache layout"), Some(2));
+    }
+
+    #[test]
+    fn test_pdfs_are_collected_only_with_the_pdf_feature() {
+        let temp_dir = TempDir::new().unwrap();
+        fs::copy(
+            Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures/design.pdf"),
+            temp_dir.path().join("design.pdf"),
+        )
+        .unwrap();
+        let files =
+            collect_files(temp_dir.path(), &ck_core::FileCollectionOptions::default()).unwrap();
+        assert_eq!(files.len(), usize::from(cfg!(feature = "pdf")));
+    }
+
+    #[test]
+    fn test_submodules_are_skipped_unless_included() {
+        let temp_dir = TempDir::new().unwrap();
+        let root = temp_dir.path();
+        fs::create_dir_all(root.join(".git/modules/linked")).unwrap();
+        fs::write(
+            root.join(".gitmodules"),
+            "[submodule \"declared\"]\n\tpath = vendor/declared\n\turl = ../declared.git\n",
+        )
+        .unwrap();
+        fs::write(root.join("main.rs"), "fn main() {}").unwrap();
+        // Declared in .gitmodules
+        fs::create_dir_all(root.join("vendor/declared/.git")).unwrap();
+        fs::write(root.join("vendor/declared/lib.rs"), "pub fn a() {}").unwrap();
+        // A checked-out submodule's gitlink
+        fs::create_dir_all(root.join("vendor/linked")).unwrap();
+        fs::write(
+            root.join("vendor/linked/.git"),
+            "gitdir: ../../.git/modules/linked\n",
+        )
+        .unwrap();
+        fs::write(root.join("vendor/linked/lib.rs"), "pub fn b() {}").unwrap();
+        // A plain nested clone is part of the tree
+        fs::create_dir_all(root.join("nested/.git")).unwrap();
+        fs::write(root.join("nested/lib.rs"), "pub fn c() {}").unwrap();
+
+        let names = |walk_root: &Path, options: &ck_core::FileCollectionOptions| {
+            let mut names: Vec<String> = collect_files(walk_root, options)
+                .unwrap()
+                .iter()
+                .map(|f| {
+                    f.strip_prefix(root)
+                        .unwrap()
+                        .to_string_lossy()
+                        .replace('\\', "/")
+                })
+                .collect();
+            names.sort();
+            names
+        };
+
+        let mut options = ck_core::FileCollectionOptions::default();
+        assert_eq!(names(root, &options), ["main.rs", "nested/lib.rs"]);
+        // .gitmodules above the walk root still applies
+        assert!(names(&root.join("vendor"), &options).is_empty());
+        // Walking a submodule itself indexes it
+        assert_eq!(
+            names(&root.join("vendor/linked"), &options),
+            ["vendor/linked/lib.rs"]
+        );
+
+        options.include_submodules = true;
+        assert_eq!(
+            names(root, &options),
+            [
+                "main.rs",
+                "nested/lib.rs",
+                "vendor/declared/lib.rs",
+                "vendor/linked/lib.rs"
+            ]
+        );
+    }
+
+    #[test]
+    fn test_max_depth_limits_the_walk() {
+        let temp_dir = TempDir::new().unwrap();
+        let root = temp_dir.path();
+        fs::create_dir_all(root.join("src/nested")).unwrap();
+        fs::create_dir_all(root.join("build/out")).unwrap();
+        fs::write(root.join("main.rs"), "fn main() {}").unwrap();
+        fs::write(root.join("src/lib.rs"), "pub fn a() {}").unwrap();
+        fs::write(root.join("src/nested/deep.rs"), "pub fn b() {}").unwrap();
+        fs::write(root.join("build/out/gen.rs"), "pub fn c() {}").unwrap();
+
+        let names = |options: &ck_core::FileCollectionOptions| {
+            let mut names: Vec<String> = collect_files(root, options)
+                .unwrap()
+                .iter()
+                .map(|f| {
+                    f.strip_prefix(root)
+                        .unwrap()
+                        .to_string_lossy()
+                        .replace('\\', "/")
+                })
+                .collect();
+            names.sort();
+            names
+        };
+
+        let mut options = ck_core::FileCollectionOptions {
+            exclude_patterns: vec!["build".to_string()],
+            max_depth: Some(1),
+            ..Default::default()
+        };
+        assert_eq!(names(&options), ["main.rs"]);
+        options.max_depth = Some(2);
+        assert_eq!(names(&options), ["main.rs", "src/lib.rs"]);
+        options.max_depth = None;
+        assert_eq!(
+            names(&options),
+            ["main.rs", "src/lib.rs", "src/nested/deep.rs"]
+        );
+
+        // The same limit filters paths on the search side
+        let include = IncludeGlobs::new(root, &[])
+            .unwrap()
+            .with_max_depth(Some(2));
+        assert!(!include.is_empty());
+        assert!(include.matches(&root.join("src/lib.rs")));
+        assert!(!include.matches(&root.join("src/nested/deep.rs")));
+    }
+
+    #[tokio::test]
+    #[serial]
+    async fn test_generated_files_are_flagged_and_can_be_skipped() {
+        unsafe { std::env::remove_var(ck_core::INDEX_DIR_ENV) };
+        let temp_dir = TempDir::new().unwrap();
+        let root = temp_dir.path();
+        fs::write(root.join("main.go"), "package main\n").unwrap();
+        fs::write(root.join("api.pb.go"), "package api\n").unwrap();
+        fs::write(
+            root.join("client.go"),
+            "// Code generated by mockgen. DO NOT EDIT.\npackage api\n",
+        )
+        .unwrap();
+
+        let mut options = ck_core::FileCollectionOptions::default();
+        smart_update_index(root, false, &options).await.unwrap();
+        let generated = |file: &str| {
+            load_index_entry(&get_sidecar_path(root, &root.join(file)))
+                .unwrap()
+                .generated
+        };
+        assert_eq!(generated("main.go"), Some(false));
+        assert_eq!(generated("api.pb.go"), Some(true));
+        assert_eq!(generated("client.go"), Some(true));
+
+        options.skip_generated = true;
+        let files = collect_files(root, &options).unwrap();
+        assert_eq!(files, [root.join("main.go")]);
+    }
+
+    #[tokio::test]
+    #[serial]
+    async fn test_unwritable_sidecar_skips_only_its_file() {
+        unsafe { std::env::remove_var(ck_core::INDEX_DIR_ENV) };
+        let temp_dir = TempDir::new().unwrap();
+        let root = temp_dir.path();
+        fs::write(root.join("a.rs"), "fn a() {}\n").unwrap();
+        fs::write(root.join("b.rs"), "fn b() {}\n").unwrap();
+        // A directory where b.rs's sidecar goes can't be replaced by a file
+        let blocked = get_sidecar_path(root, &root.join("b.rs"));
+        fs::create_dir_all(&blocked).unwrap();
+
+        let options = ck_core::FileCollectionOptions::default();
+        let stats = smart_update_index(root, false, &options).await.unwrap();
+        let unwritable: Vec<_> = stats.unwritable.iter().map(|(file, _)| file).collect();
+        assert_eq!(unwritable, [&root.join("b.rs")]);
+        assert_eq!(stats.files_errored, 1);
+        assert!(load_index_entry(&get_sidecar_path(root, &root.join("a.rs"))).is_ok());
+
+        // Left out of the manifest, so the next update tries it again
+        fs::remove_dir(&blocked).unwrap();
+        let stats = smart_update_index(root, false, &options).await.unwrap();
+        assert_eq!((stats.files_added, stats.files_up_to_date), (1, 1));
+        assert!(stats.unwritable.is_empty());
+        assert!(load_index_entry(&blocked).is_ok());
+    }
+
     #[test]
     fn test_ckignore_works_without_gitignore() {
         // Test that .ckignore is respected even when respect_gitignore is false
@@ -2239,7 +5101,12 @@ mod tests {
             respect_gitignore: false,
             use_ckignore: true,
             exclude_patterns: vec![],
+            include_globs: vec![],
             show_hidden: false,
+            follow_links: false,
+            include_submodules: false,
+            max_depth: None,
+            skip_generated: false,
         };
 
         let files = collect_files(test_path, &options).unwrap();
@@ -2272,7 +5139,12 @@ mod tests {
             respect_gitignore: false,
             use_ckignore: false,
             exclude_patterns: vec![],
+            include_globs: vec![],
             show_hidden: false,
+            follow_links: false,
+            include_submodules: false,
+            max_depth: None,
+            skip_generated: false,
         };
 
         let files_all = collect_files(test_path, &options_both_disabled).unwrap();
@@ -2292,6 +5164,71 @@ mod tests {
             "Should find .ck file when use_ckignore=false"
         );
     }
+
+    #[test]
+    fn test_exclude_negation_follows_order_and_layer_precedence() {
+        let temp_dir = TempDir::new().unwrap();
+        let root = temp_dir.path();
+        fs::create_dir(root.join(".git")).unwrap();
+        fs::create_dir(root.join("config")).unwrap();
+        for file in [
+            "package.json",
+            "config/schema.json",
+            "config/secret.json",
+            "generated.rs",
+            "main.rs",
+        ] {
+            fs::write(root.join(file), "{}\n").unwrap();
+        }
+        let names = |options: &ck_core::FileCollectionOptions| {
+            let mut names: Vec<String> = collect_files(root, options)
+                .unwrap()
+                .iter()
+                .map(|p| {
+                    p.strip_prefix(root)
+                        .unwrap()
+                        .to_string_lossy()
+                        .replace('\\', "/")
+                })
+                .collect();
+            names.sort();
+            names
+        };
+        let options = ck_core::FileCollectionOptions::default();
+
+        // .ckignore: exclude, re-include, re-exclude, last match winning;
+        // its negation also re-includes a file .gitignore excluded
+        fs::write(root.join(".gitignore"), "generated.rs\n").unwrap();
+        fs::write(
+            root.join(".ckignore"),
+            "*.json\n!config/*.json\nconfig/secret.json\n!generated.rs\n",
+        )
+        .unwrap();
+        assert_eq!(
+            names(&options),
+            ["config/schema.json", "generated.rs", "main.rs"]
+        );
+
+        // The same sequence as exclude patterns
+        fs::remove_file(root.join(".ckignore")).unwrap();
+        let options = ck_core::FileCollectionOptions {
+            exclude_patterns: vec![
+                "*.json".to_string(),
+                "!config/*.json".to_string(),
+                "config/secret.json".to_string(),
+            ],
+            ..options
+        };
+        assert_eq!(names(&options), ["config/schema.json", "main.rs"]);
+
+        // Patterns only undo their own layer's excludes: the ignore files
+        // already dropped generated.rs
+        let options = ck_core::FileCollectionOptions {
+            exclude_patterns: vec!["!generated.rs".to_string()],
+            ..options
+        };
+        assert!(!names(&options).contains(&"generated.rs".to_string()));
+    }
 }
 
 // ============================================================================
@@ -2365,8 +5302,10 @@ mod cleanup_validation {
             stats.orphaned_sidecars_removed += 1;
         }
 
-        // Remove content cache for PDFs
-        if ck_core::pdf::is_pdf_file(&standard_path) {
+        // Remove content cache for PDFs and notebooks
+        if ck_core::pdf::is_pdf_file(&standard_path)
+            || ck_core::notebook::is_notebook_file(&standard_path)
+        {
             let absolute_path = repo_root.join(&standard_path);
             let cache_path = ck_core::pdf::get_content_cache_path(repo_root, &absolute_path);
             if cache_path.exists() {
@@ -2391,7 +5330,7 @@ mod cleanup_validation {
             return Ok(());
         }
 
-        for entry in WalkDir::new(index_dir) {
+        for entry in walk_index_dir(index_dir) {
             let entry = entry?;
             if entry.file_type().is_file() {
                 let sidecar_path = entry.path();
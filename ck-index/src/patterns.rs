@@ -0,0 +1,227 @@
+//! `--verbose` pattern diagnostics: `--exclude`, `--include` and `.ckignore`
+//! patterns that match nothing under the tree, which usually means a typo.
+//!
+//! Every pattern is checked on its own against an unfiltered walk of the
+//! tree, so a pattern counts as matching even when another rule already
+//! excludes what it matches. `.ckignore` files are read from the walked tree
+//! only; one in a parent directory may well have patterns for elsewhere.
+
+use anyhow::Result;
+use ignore::gitignore::{Gitignore, GitignoreBuilder};
+use std::fmt;
+use std::path::{Path, PathBuf};
+use walkdir::WalkDir;
+
+use super::{IncludeGlobs, build_excludes};
+
+/// Where a pattern came from.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PatternSource {
+    Exclude,
+    Include,
+    /// A line of this `.ckignore` file (1-based)
+    Ckignore {
+        file: PathBuf,
+        line: usize,
+    },
+}
+
+impl fmt::Display for PatternSource {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PatternSource::Exclude => f.write_str("--exclude"),
+            PatternSource::Include => f.write_str("--include"),
+            PatternSource::Ckignore { file, line } => write!(f, "{}:{line}", file.display()),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct UnmatchedPattern {
+    pub source: PatternSource,
+    pub pattern: String,
+}
+
+enum Matcher {
+    /// A gitignore-style line, matched under its root
+    Ignore {
+        root: PathBuf,
+        matcher: Gitignore,
+    },
+    Include(IncludeGlobs),
+}
+
+impl Matcher {
+    fn matches(&self, path: &Path, is_dir: bool) -> bool {
+        match self {
+            Matcher::Ignore { root, matcher } => {
+                path.starts_with(root) && !matcher.matched(path, is_dir).is_none()
+            }
+            // Include globs select files
+            Matcher::Include(globs) => !is_dir && globs.matches(path),
+        }
+    }
+}
+
+struct Tracked {
+    pattern: UnmatchedPattern,
+    matcher: Matcher,
+    matched: bool,
+}
+
+/// The user's `excludes` (not the defaults), the `--include` globs in
+/// `options` and, when `.ckignore` is honored, the lines of every `.ckignore`
+/// under `path` that match no file or directory there.
+pub fn unmatched_patterns(
+    path: &Path,
+    excludes: &[String],
+    options: &ck_core::FileCollectionOptions,
+) -> Result<Vec<UnmatchedPattern>> {
+    let mut tracked = Vec::new();
+    for pattern in excludes {
+        tracked.push(Tracked {
+            pattern: UnmatchedPattern {
+                source: PatternSource::Exclude,
+                pattern: pattern.clone(),
+            },
+            matcher: Matcher::Ignore {
+                root: path.to_path_buf(),
+                matcher: build_excludes(path, std::slice::from_ref(pattern))?,
+            },
+            matched: false,
+        });
+    }
+    for glob in &options.include_globs {
+        tracked.push(Tracked {
+            pattern: UnmatchedPattern {
+                source: PatternSource::Include,
+                pattern: glob.clone(),
+            },
+            matcher: Matcher::Include(IncludeGlobs::new(path, std::slice::from_ref(glob))?),
+            matched: false,
+        });
+    }
+
+    let index_dir = ck_core::index_dir(path);
+    let mut walk = WalkDir::new(path).sort_by_file_name().into_iter();
+    while let Some(entry) = walk.next() {
+        let Ok(entry) = entry else { continue };
+        let entry_path = entry.path();
+        let is_dir = entry.file_type().is_dir();
+        if entry_path.starts_with(&index_dir) || entry.file_name() == ".git" {
+            if is_dir {
+                walk.skip_current_dir();
+            }
+            continue;
+        }
+        // A directory's .ckignore applies to its children, visited next
+        if is_dir && options.use_ckignore {
+            tracked.extend(ckignore_patterns(entry_path));
+        }
+        if entry.depth() == 0 {
+            continue;
+        }
+        for pattern in tracked.iter_mut().filter(|t| !t.matched) {
+            pattern.matched = pattern.matcher.matches(entry_path, is_dir);
+        }
+    }
+
+    Ok(tracked
+        .into_iter()
+        .filter(|t| !t.matched)
+        .map(|t| t.pattern)
+        .collect())
+}
+
+/// One tracked pattern per rule line of `dir/.ckignore`, if there is one.
+fn ckignore_patterns(dir: &Path) -> Vec<Tracked> {
+    let file = dir.join(".ckignore");
+    let Ok(content) = std::fs::read_to_string(&file) else {
+        return Vec::new();
+    };
+    content
+        .lines()
+        .enumerate()
+        .filter(|(_, line)| {
+            let line = line.trim();
+            !line.is_empty() && !line.starts_with('#')
+        })
+        .filter_map(|(index, line)| {
+            let mut builder = GitignoreBuilder::new(dir);
+            builder.add_line(Some(file.clone()), line).ok()?;
+            Some(Tracked {
+                pattern: UnmatchedPattern {
+                    source: PatternSource::Ckignore {
+                        file: file.clone(),
+                        line: index + 1,
+                    },
+                    pattern: line.trim().to_string(),
+                },
+                matcher: Matcher::Ignore {
+                    root: dir.to_path_buf(),
+                    matcher: builder.build().ok()?,
+                },
+                matched: false,
+            })
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_reports_patterns_that_match_nothing() {
+        let temp_dir = TempDir::new().unwrap();
+        let root = temp_dir.path();
+        fs::create_dir_all(root.join("src/gen")).unwrap();
+        fs::write(root.join("src/main.rs"), "fn main() {}\n").unwrap();
+        fs::write(root.join("src/gen/out.rs"), "// generated\n").unwrap();
+        fs::write(root.join("notes.tmp"), "scratch\n").unwrap();
+        fs::write(
+            root.join(".ckignore"),
+            "# comment\n*.tmp\n*.pnng\n\n!src/gen/keep.rs\n",
+        )
+        .unwrap();
+        fs::write(root.join("src/.ckignore"), "gen/\nvendor/\n").unwrap();
+
+        let options = ck_core::FileCollectionOptions {
+            include_globs: vec!["src/**/*.rs".to_string(), "lib/**".to_string()],
+            ..Default::default()
+        };
+        let excludes = ["*.tmp".to_string(), "*.tpm".to_string(), "gen".to_string()];
+        let unmatched = unmatched_patterns(root, &excludes, &options).unwrap();
+        let described: Vec<String> = unmatched
+            .iter()
+            .map(|u| {
+                let source = u
+                    .source
+                    .to_string()
+                    .replace(&root.display().to_string(), "");
+                format!("{source} {}", u.pattern)
+            })
+            .collect();
+
+        assert_eq!(
+            described,
+            [
+                "--exclude *.tpm",
+                "--include lib/**",
+                "/.ckignore:3 *.pnng",
+                "/.ckignore:5 !src/gen/keep.rs",
+                "/src/.ckignore:2 vendor/",
+            ]
+        );
+
+        // Without .ckignore handling its lines aren't checked
+        let options = ck_core::FileCollectionOptions {
+            use_ckignore: false,
+            ..options
+        };
+        let unmatched = unmatched_patterns(root, &excludes, &options).unwrap();
+        assert_eq!(unmatched.len(), 2);
+    }
+}
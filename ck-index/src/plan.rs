@@ -0,0 +1,372 @@
+//! `--index --dry-run`: what an indexing pass would do, without doing it.
+//!
+//! Files the walker collects are compared with the manifest using the same
+//! mtime/size-then-hash check as `smart_update_index`. Everything the walker
+//! passes over is reported with the rule responsible, found by walking the
+//! tree again with no filters and asking each rule in turn. Nothing under
+//! `.ck` is created or modified.
+//!
+//! `--diff-index` reports the same comparison as drift: files new, modified
+//! or deleted since the index was last updated.
+
+use anyhow::{Result, bail};
+use serde::Serialize;
+use std::collections::HashSet;
+use std::fmt;
+use std::fs;
+use std::path::{Path, PathBuf};
+use walkdir::WalkDir;
+
+use super::{
+    FileChange, IncludeGlobs, IndexManifest, build_excludes, build_walker, classify_file_change,
+    effective_exclude_patterns, find_repo_root, is_text_file, normalize_manifest_paths, path_utils,
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PlannedAction {
+    /// Not in the index yet
+    Add,
+    /// Content changed since it was indexed
+    Update,
+    UpToDate,
+    /// Could not be read to compare with the index
+    Unreadable,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ExcludeReason {
+    /// Matched this `--exclude` (or default) pattern
+    Pattern(String),
+    /// Skipped by `.gitignore` or `.ckignore`
+    Ignored,
+    /// Hidden entry, see `--hidden`
+    Hidden,
+    /// Binary content
+    Binary,
+    /// Outside every `--include` glob
+    NotIncluded,
+}
+
+impl fmt::Display for ExcludeReason {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ExcludeReason::Pattern(pattern) => write!(f, "exclude pattern '{pattern}'"),
+            ExcludeReason::Ignored => f.write_str(".gitignore/.ckignore"),
+            ExcludeReason::Hidden => f.write_str("hidden"),
+            ExcludeReason::Binary => f.write_str("binary"),
+            ExcludeReason::NotIncluded => f.write_str("not matched by --include"),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct PlannedFile {
+    pub path: PathBuf,
+    pub action: PlannedAction,
+}
+
+/// A file or directory the walk skips. An excluded directory is reported once
+/// rather than once per file below it.
+#[derive(Debug, Clone)]
+pub struct ExcludedPath {
+    pub path: PathBuf,
+    pub is_dir: bool,
+    pub reason: ExcludeReason,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct IndexPlan {
+    pub files: Vec<PlannedFile>,
+    pub excluded: Vec<ExcludedPath>,
+}
+
+impl IndexPlan {
+    pub fn count(&self, action: PlannedAction) -> usize {
+        self.files.iter().filter(|f| f.action == action).count()
+    }
+}
+
+/// How the tree under a path differs from its index. Paths are relative to
+/// that path and sorted.
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct IndexDiff {
+    /// Files indexing would add
+    pub new: Vec<PathBuf>,
+    /// Indexed files whose content hash changed
+    pub modified: Vec<PathBuf>,
+    /// Indexed files no longer on disk
+    pub deleted: Vec<PathBuf>,
+}
+
+impl IndexDiff {
+    pub fn is_empty(&self) -> bool {
+        self.new.is_empty() && self.modified.is_empty() && self.deleted.is_empty()
+    }
+}
+
+/// Compare the files under `path` with its index, read-only: the report
+/// `smart_update_index` would act on. Files only touched (mtime changed,
+/// same hash) count as unchanged. `force_hash` hashes files whose mtime and
+/// size match too (`--force-hash`).
+pub fn diff_index(
+    path: &Path,
+    options: &ck_core::FileCollectionOptions,
+    force_hash: bool,
+) -> Result<IndexDiff> {
+    if !ck_core::index_dir(path).join("manifest.json").exists() {
+        bail!(
+            "No index found at {}. Run 'ck --index' to create one.",
+            path.display()
+        );
+    }
+    let (manifest, repo_root) = load_manifest(path)?;
+    let plan = plan_index(path, options, force_hash)?;
+
+    let relative = |file: &Path| file.strip_prefix(path).unwrap_or(file).to_path_buf();
+    let mut diff = IndexDiff::default();
+    for file in &plan.files {
+        match file.action {
+            PlannedAction::Add => diff.new.push(relative(&file.path)),
+            PlannedAction::Update => diff.modified.push(relative(&file.path)),
+            PlannedAction::UpToDate | PlannedAction::Unreadable => {}
+        }
+    }
+    for key in manifest.files.keys() {
+        let file = repo_root.join(path_utils::from_manifest_path(key));
+        if file.starts_with(path) && !file.exists() {
+            diff.deleted.push(relative(&file));
+        }
+    }
+    diff.new.sort();
+    diff.modified.sort();
+    diff.deleted.sort();
+    Ok(diff)
+}
+
+/// The manifest for the index at `path` (empty if there is none yet), keyed
+/// relative to the repository root, which is returned alongside.
+fn load_manifest(path: &Path) -> Result<(IndexManifest, PathBuf)> {
+    let repo_root = find_repo_root(path)?;
+    let manifest_path = ck_core::index_dir(path).join("manifest.json");
+    let mut manifest = if manifest_path.exists() {
+        serde_json::from_slice(&fs::read(&manifest_path)?)?
+    } else {
+        IndexManifest::default()
+    };
+    normalize_manifest_paths(&mut manifest, &repo_root);
+    Ok((manifest, repo_root))
+}
+
+/// Classify every file under `path` the way indexing it with `options` (and
+/// `--force-hash` when `force_hash`) would.
+pub fn plan_index(
+    path: &Path,
+    options: &ck_core::FileCollectionOptions,
+    force_hash: bool,
+) -> Result<IndexPlan> {
+    let index_dir = ck_core::index_dir(path);
+    let include = IncludeGlobs::new(path, &options.include_globs)?;
+    let (manifest, repo_root) = load_manifest(path)?;
+
+    // Everything the real walk visits, directories included, so the second
+    // walk can tell where it stopped descending
+    let visited: HashSet<PathBuf> = build_walker(path, options)?
+        .filter_map(std::result::Result::ok)
+        .map(ignore::DirEntry::into_path)
+        .collect();
+
+    let patterns = effective_exclude_patterns(options);
+    let pattern_matchers = patterns
+        .iter()
+        .map(|pattern| {
+            Ok((
+                pattern,
+                build_excludes(path, std::slice::from_ref(pattern))?,
+            ))
+        })
+        .collect::<Result<Vec<_>>>()?;
+
+    let mut plan = IndexPlan::default();
+    let mut walk = WalkDir::new(path).sort_by_file_name().into_iter();
+    while let Some(entry) = walk.next() {
+        let Ok(entry) = entry else { continue };
+        let entry_path = entry.path();
+        let is_dir = entry.file_type().is_dir();
+        if entry_path.starts_with(&index_dir) || entry.file_name() == ".git" {
+            if is_dir {
+                walk.skip_current_dir();
+            }
+            continue;
+        }
+        if entry.depth() == 0 && is_dir {
+            continue;
+        }
+
+        if !visited.contains(entry_path) {
+            let reason = pattern_matchers
+                .iter()
+                .rfind(|(_, matcher)| matcher.matched(entry_path, is_dir).is_ignore())
+                .map(|(pattern, _)| ExcludeReason::Pattern((*pattern).clone()))
+                .unwrap_or_else(|| {
+                    let hidden = entry.file_name().to_string_lossy().starts_with('.');
+                    if hidden && !options.show_hidden {
+                        ExcludeReason::Hidden
+                    } else {
+                        ExcludeReason::Ignored
+                    }
+                });
+            plan.excluded.push(ExcludedPath {
+                path: entry_path.to_path_buf(),
+                is_dir,
+                reason,
+            });
+            if is_dir {
+                walk.skip_current_dir();
+            }
+            continue;
+        }
+        if !entry.file_type().is_file() {
+            continue;
+        }
+
+        let reason = if !is_text_file(entry_path) {
+            Some(ExcludeReason::Binary)
+        } else if !include.matches(entry_path) {
+            Some(ExcludeReason::NotIncluded)
+        } else {
+            None
+        };
+        if let Some(reason) = reason {
+            plan.excluded.push(ExcludedPath {
+                path: entry_path.to_path_buf(),
+                is_dir: false,
+                reason,
+            });
+            continue;
+        }
+
+        let manifest_key =
+            path_utils::to_manifest_path(&path_utils::to_standard_path(entry_path, &repo_root));
+        let action = match classify_file_change(
+            entry_path,
+            &manifest_key,
+            manifest.files.get(&manifest_key),
+            force_hash,
+        ) {
+            Ok(FileChange::Added) => PlannedAction::Add,
+            Ok(FileChange::Modified) => PlannedAction::Update,
+            Ok(FileChange::Unchanged | FileChange::Touched(_)) => PlannedAction::UpToDate,
+            Err(_) => PlannedAction::Unreadable,
+        };
+        plan.files.push(PlannedFile {
+            path: entry_path.to_path_buf(),
+            action,
+        });
+    }
+    Ok(plan)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[tokio::test]
+    async fn test_plan_index_classifies_without_writing() {
+        let temp_dir = TempDir::new().unwrap();
+        let root = temp_dir.path();
+        fs::write(root.join("kept.rs"), "fn kept() {}").unwrap();
+        fs::write(root.join("changed.rs"), "fn before() {}").unwrap();
+        fs::write(root.join("blob.bin"), [0u8, 1, 2, 3]).unwrap();
+        fs::create_dir(root.join("vendor")).unwrap();
+        fs::write(root.join("vendor/lib.rs"), "fn vendored() {}").unwrap();
+        fs::write(root.join(".secret"), "hidden").unwrap();
+
+        let options = ck_core::FileCollectionOptions {
+            respect_gitignore: true,
+            use_ckignore: true,
+            exclude_patterns: vec!["vendor".to_string()],
+            include_globs: Vec::new(),
+            show_hidden: false,
+            follow_links: false,
+            include_submodules: false,
+            max_depth: None,
+            skip_generated: false,
+        };
+        crate::smart_update_index(root, false, &options)
+            .await
+            .unwrap();
+
+        fs::write(root.join("changed.rs"), "fn after_the_edit() {}").unwrap();
+        fs::write(root.join("new.rs"), "fn new() {}").unwrap();
+        let manifest_before = fs::read(ck_core::index_dir(root).join("manifest.json")).unwrap();
+
+        let plan = plan_index(root, &options, false).unwrap();
+        let action = |name: &str| {
+            plan.files
+                .iter()
+                .find(|f| f.path.ends_with(name))
+                .map(|f| f.action)
+        };
+        assert_eq!(action("kept.rs"), Some(PlannedAction::UpToDate));
+        assert_eq!(action("changed.rs"), Some(PlannedAction::Update));
+        assert_eq!(action("new.rs"), Some(PlannedAction::Add));
+        assert_eq!(plan.count(PlannedAction::Add), 1);
+
+        let reason = |name: &str| {
+            plan.excluded
+                .iter()
+                .find(|e| e.path.ends_with(name))
+                .map(|e| e.reason.clone())
+        };
+        assert_eq!(
+            reason("vendor"),
+            Some(ExcludeReason::Pattern("vendor".to_string()))
+        );
+        assert_eq!(reason("vendor/lib.rs"), None);
+        assert_eq!(reason("blob.bin"), Some(ExcludeReason::Binary));
+        assert_eq!(reason(".secret"), Some(ExcludeReason::Hidden));
+
+        let manifest_after = fs::read(ck_core::index_dir(root).join("manifest.json")).unwrap();
+        assert_eq!(manifest_before, manifest_after);
+    }
+
+    #[tokio::test]
+    async fn test_diff_index_lists_new_modified_and_deleted() {
+        let temp_dir = TempDir::new().unwrap();
+        let root = temp_dir.path();
+        let options = ck_core::FileCollectionOptions {
+            respect_gitignore: true,
+            use_ckignore: true,
+            exclude_patterns: Vec::new(),
+            include_globs: Vec::new(),
+            show_hidden: false,
+            follow_links: false,
+            include_submodules: false,
+            max_depth: None,
+            skip_generated: false,
+        };
+        assert!(diff_index(root, &options, false).is_err());
+
+        fs::write(root.join("kept.rs"), "fn kept() {}").unwrap();
+        fs::write(root.join("touched.rs"), "fn touched() {}").unwrap();
+        fs::write(root.join("changed.rs"), "fn before() {}").unwrap();
+        fs::write(root.join("gone.rs"), "fn gone() {}").unwrap();
+        crate::smart_update_index(root, false, &options)
+            .await
+            .unwrap();
+        assert!(diff_index(root, &options, false).unwrap().is_empty());
+
+        fs::write(root.join("changed.rs"), "fn after_the_edit() {}").unwrap();
+        // Rewritten with the same content: a newer mtime but the same hash
+        std::thread::sleep(std::time::Duration::from_millis(20));
+        fs::write(root.join("touched.rs"), "fn touched() {}").unwrap();
+        fs::remove_file(root.join("gone.rs")).unwrap();
+        fs::write(root.join("new.rs"), "fn new() {}").unwrap();
+
+        let diff = diff_index(root, &options, false).unwrap();
+        assert_eq!(diff.new, vec![PathBuf::from("new.rs")]);
+        assert_eq!(diff.modified, vec![PathBuf::from("changed.rs")]);
+        assert_eq!(diff.deleted, vec![PathBuf::from("gone.rs")]);
+    }
+}
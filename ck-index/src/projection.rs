@@ -0,0 +1,341 @@
+//! `--reduce-dim`: embedding vectors projected onto fewer dimensions.
+//!
+//! Scoring every chunk costs time proportional to the vector length, so a
+//! 1024-dimension index is four times slower to search than one reduced to
+//! 256. The projection is PCA: the mean of the index's vectors and the
+//! directions along which they vary most, fitted once from the vectors the
+//! index already holds. It is saved next to the manifest ([`PROJECTION_FILE`])
+//! so a query embedding is projected exactly as the chunks were; sidecars
+//! store only the projected vectors.
+
+use anyhow::Result;
+use rayon::prelude::*;
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::Path;
+
+/// File under the index directory holding the [`Projection`].
+pub const PROJECTION_FILE: &str = "projection.bin";
+
+/// At most this many vectors are sampled to fit a projection; the principal
+/// directions of a large index are settled long before its last chunk.
+const MAX_FIT_VECTORS: usize = 4096;
+
+/// Rounds of subspace iteration. Only the spanned subspace matters for
+/// scoring, not the individual components, and it converges quickly.
+const FIT_ITERATIONS: usize = 12;
+
+/// A linear map from one model's vectors to `dimensions()` components.
+#[derive(Clone, Serialize, Deserialize)]
+pub struct Projection {
+    /// Embedding model whose vectors this projects
+    pub model: String,
+    /// Mean of the fitted vectors, subtracted before projecting
+    mean: Vec<f32>,
+    /// Orthonormal components, one row of `mean.len()` values each
+    components: Vec<Vec<f32>>,
+}
+
+impl std::fmt::Debug for Projection {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Projection")
+            .field("model", &self.model)
+            .field("source_dimensions", &self.source_dimensions())
+            .field("dimensions", &self.dimensions())
+            .finish()
+    }
+}
+
+impl Projection {
+    /// Fit a projection of `model`'s `vectors` onto their top `dimensions`
+    /// principal components. Every vector must have the same length, greater
+    /// than `dimensions`.
+    pub fn fit(model: &str, vectors: &[Vec<f32>], dimensions: usize) -> Result<Self> {
+        let Some(source) = vectors.first().map(Vec::len) else {
+            return Err(anyhow::anyhow!(
+                "No embedded chunks to fit a dimension reduction from"
+            ));
+        };
+        if dimensions == 0 || dimensions >= source {
+            return Err(anyhow::anyhow!(
+                "Can't reduce {source}-dimension vectors to {dimensions} dimensions; choose between 1 and {}",
+                source - 1
+            ));
+        }
+        if vectors.iter().any(|vector| vector.len() != source) {
+            return Err(anyhow::anyhow!(
+                "Vectors of different lengths can't share one projection"
+            ));
+        }
+
+        // An evenly spread sample keeps fitting cheap on large indexes
+        let step = vectors.len().div_ceil(MAX_FIT_VECTORS);
+        let sample: Vec<&Vec<f32>> = vectors.iter().step_by(step).collect();
+
+        let count = sample.len() as f64;
+        let mut mean = vec![0f64; source];
+        for vector in &sample {
+            for (sum, &value) in mean.iter_mut().zip(vector.iter()) {
+                *sum += f64::from(value);
+            }
+        }
+        mean.iter_mut().for_each(|sum| *sum /= count);
+
+        // Covariance, one row per rayon task
+        let centered: Vec<Vec<f64>> = sample
+            .iter()
+            .map(|vector| {
+                vector
+                    .iter()
+                    .zip(&mean)
+                    .map(|(&value, mean)| f64::from(value) - mean)
+                    .collect()
+            })
+            .collect();
+        let covariance: Vec<Vec<f64>> = (0..source)
+            .into_par_iter()
+            .map(|i| {
+                let mut row = vec![0f64; source];
+                for vector in &centered {
+                    let scale = vector[i];
+                    for (cell, value) in row.iter_mut().zip(vector) {
+                        *cell += scale * value;
+                    }
+                }
+                row.iter_mut().for_each(|cell| *cell /= count);
+                row
+            })
+            .collect();
+
+        // Subspace iteration from a fixed start, so refitting the same
+        // vectors gives the same projection
+        let mut seed = 0x9E37_79B9_7F4A_7C15u64;
+        let mut basis: Vec<Vec<f64>> = (0..dimensions)
+            .map(|_| {
+                (0..source)
+                    .map(|_| {
+                        seed ^= seed << 13;
+                        seed ^= seed >> 7;
+                        seed ^= seed << 17;
+                        (seed >> 11) as f64 / (1u64 << 53) as f64 - 0.5
+                    })
+                    .collect()
+            })
+            .collect();
+        orthonormalize(&mut basis);
+        for _ in 0..FIT_ITERATIONS {
+            basis = basis
+                .par_iter()
+                .map(|component| {
+                    covariance
+                        .iter()
+                        .map(|row| row.iter().zip(component).map(|(a, b)| a * b).sum())
+                        .collect()
+                })
+                .collect();
+            orthonormalize(&mut basis);
+        }
+
+        Ok(Self {
+            model: model.to_string(),
+            mean: mean.into_iter().map(|value| value as f32).collect(),
+            components: basis
+                .into_iter()
+                .map(|component| component.into_iter().map(|value| value as f32).collect())
+                .collect(),
+        })
+    }
+
+    /// Length of the vectors this projects.
+    pub fn source_dimensions(&self) -> usize {
+        self.mean.len()
+    }
+
+    /// Length of the projected vectors.
+    pub fn dimensions(&self) -> usize {
+        self.components.len()
+    }
+
+    /// Whether `vector`, embedded by `model`, is one this projects rather than
+    /// one already projected or from another model.
+    pub fn applies_to(&self, model: &str, vector: &[f32]) -> bool {
+        model == self.model && vector.len() == self.source_dimensions()
+    }
+
+    /// `vector` centered and projected onto the components, scaled to unit
+    /// length.
+    pub fn project(&self, vector: &[f32]) -> Vec<f32> {
+        let mut projected: Vec<f32> = self
+            .components
+            .iter()
+            .map(|component| {
+                component
+                    .iter()
+                    .zip(vector.iter().zip(&self.mean))
+                    .map(|(c, (v, m))| c * (v - m))
+                    .sum()
+            })
+            .collect();
+        let norm = projected.iter().map(|x| x * x).sum::<f32>().sqrt();
+        if norm > 0.0 {
+            projected.iter_mut().for_each(|x| *x /= norm);
+        }
+        projected
+    }
+}
+
+/// Gram-Schmidt, in place. A component that collapses (the vectors span
+/// fewer dimensions than asked for) is left at zero rather than dividing by
+/// nothing.
+fn orthonormalize(basis: &mut [Vec<f64>]) {
+    for i in 0..basis.len() {
+        let (done, rest) = basis.split_at_mut(i);
+        let component = &mut rest[0];
+        for previous in done.iter() {
+            let dot: f64 = component.iter().zip(previous).map(|(a, b)| a * b).sum();
+            for (value, p) in component.iter_mut().zip(previous) {
+                *value -= dot * p;
+            }
+        }
+        let norm = component.iter().map(|x| x * x).sum::<f64>().sqrt();
+        if norm > 1e-12 {
+            component.iter_mut().for_each(|x| *x /= norm);
+        } else {
+            component.iter_mut().for_each(|x| *x = 0.0);
+        }
+    }
+}
+
+/// The projection saved in `index_dir`, if there is one.
+pub(crate) fn read_projection(index_dir: &Path) -> Option<Projection> {
+    bincode::deserialize(&fs::read(index_dir.join(PROJECTION_FILE)).ok()?).ok()
+}
+
+/// An embedder whose vectors come out projected, so an indexing pass writes
+/// sidecars in the index's reduced space.
+pub(crate) struct ProjectedEmbedder {
+    pub inner: Box<dyn ck_embed::Embedder>,
+    pub projection: std::sync::Arc<Projection>,
+}
+
+impl ck_embed::Embedder for ProjectedEmbedder {
+    fn id(&self) -> &'static str {
+        self.inner.id()
+    }
+
+    fn dim(&self) -> usize {
+        self.projection.dimensions()
+    }
+
+    fn model_name(&self) -> &str {
+        self.inner.model_name()
+    }
+
+    fn embed(&mut self, texts: &[String]) -> Result<Vec<Vec<f32>>> {
+        Ok(self
+            .inner
+            .embed(texts)?
+            .iter()
+            .map(|vector| self.projection.project(vector))
+            .collect())
+    }
+
+    fn preload(&mut self) -> Result<()> {
+        self.inner.preload()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Deterministic vectors in 64 dimensions that mostly vary along 8 hidden
+    /// directions, like embeddings of a corpus about a few topics.
+    fn fixture(count: usize) -> Vec<Vec<f32>> {
+        let mut seed = 42u64;
+        let mut next = move || {
+            seed = seed
+                .wrapping_mul(6364136223846793005)
+                .wrapping_add(1442695040888963407);
+            (seed >> 33) as f32 / (1u64 << 31) as f32 - 0.5
+        };
+        let directions: Vec<Vec<f32>> = (0..8).map(|_| (0..64).map(|_| next()).collect()).collect();
+        (0..count)
+            .map(|_| {
+                let weights: Vec<f32> = (0..8).map(|_| next()).collect();
+                (0..64)
+                    .map(|i| {
+                        let signal: f32 =
+                            directions.iter().zip(&weights).map(|(d, w)| d[i] * w).sum();
+                        signal + 0.05 * next()
+                    })
+                    .collect()
+            })
+            .collect()
+    }
+
+    fn cosine(a: &[f32], b: &[f32]) -> f32 {
+        let dot: f32 = a.iter().zip(b).map(|(x, y)| x * y).sum();
+        let norm = |v: &[f32]| v.iter().map(|x| x * x).sum::<f32>().sqrt();
+        dot / (norm(a) * norm(b))
+    }
+
+    /// Rank of each position when sorted by descending score.
+    fn ranks(scores: &[f32]) -> Vec<f64> {
+        let mut order: Vec<usize> = (0..scores.len()).collect();
+        order.sort_by(|&a, &b| scores[b].total_cmp(&scores[a]));
+        let mut ranks = vec![0.0; scores.len()];
+        for (rank, index) in order.into_iter().enumerate() {
+            ranks[index] = rank as f64;
+        }
+        ranks
+    }
+
+    fn spearman(a: &[f32], b: &[f32]) -> f64 {
+        let (ra, rb) = (ranks(a), ranks(b));
+        let n = ra.len() as f64;
+        let squared: f64 = ra.iter().zip(&rb).map(|(x, y)| (x - y).powi(2)).sum();
+        1.0 - 6.0 * squared / (n * (n * n - 1.0))
+    }
+
+    #[test]
+    fn projected_rankings_follow_full_dimension_rankings() {
+        let vectors = fixture(300);
+        let projection = Projection::fit("test-model", &vectors, 16).unwrap();
+        assert_eq!(projection.source_dimensions(), 64);
+        assert_eq!(projection.dimensions(), 16);
+
+        let projected: Vec<Vec<f32>> = vectors.iter().map(|v| projection.project(v)).collect();
+        for query in fixture(310).iter().skip(300) {
+            let full: Vec<f32> = vectors.iter().map(|v| cosine(query, v)).collect();
+            let query = projection.project(query);
+            let reduced: Vec<f32> = projected.iter().map(|v| cosine(&query, v)).collect();
+            let correlation = spearman(&full, &reduced);
+            assert!(
+                correlation > 0.9,
+                "projected ranking correlates {correlation:.3} with the full one"
+            );
+        }
+    }
+
+    #[test]
+    fn fit_is_deterministic_and_rejects_bad_sizes() {
+        let vectors = fixture(50);
+        let a = Projection::fit("m", &vectors, 4).unwrap();
+        let b = Projection::fit("m", &vectors, 4).unwrap();
+        assert_eq!(a.project(&vectors[0]), b.project(&vectors[0]));
+
+        assert!(Projection::fit("m", &vectors, 64).is_err());
+        assert!(Projection::fit("m", &vectors, 0).is_err());
+        assert!(Projection::fit("m", &[], 4).is_err());
+    }
+
+    #[test]
+    fn applies_only_to_its_models_full_vectors() {
+        let vectors = fixture(50);
+        let projection = Projection::fit("m", &vectors, 4).unwrap();
+        assert!(projection.applies_to("m", &vectors[0]));
+        assert!(!projection.applies_to("other", &vectors[0]));
+        assert!(!projection.applies_to("m", &projection.project(&vectors[0])));
+    }
+}
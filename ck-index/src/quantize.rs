@@ -0,0 +1,368 @@
+//! `--quantize`: embedding vectors stored at reduced precision.
+//!
+//! Float32 vectors are most of a sidecar. `int8` stores each component as a
+//! signed byte plus one scale per vector (about a quarter of the size);
+//! `binary` stores only the sign of each component (about a thirty-second).
+//! Quantized sidecars are dequantized when loaded, so everything that reads
+//! `ChunkEntry::embedding` keeps working, and keep the quantized form in
+//! `ChunkEntry::quantized` for search to score against directly: int8 vectors
+//! by an integer dot product, binary ones by Hamming distance.
+
+use serde::{Deserialize, Serialize};
+
+/// Precision of the stored embedding vectors.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Quantization {
+    /// Full float32 vectors
+    #[default]
+    None,
+    /// One signed byte per component, scaled per vector
+    Int8,
+    /// One bit per component: its sign
+    Binary,
+}
+
+impl Quantization {
+    /// Whether vectors stored at `other` precision lack detail this keeps,
+    /// so switching to this one needs the vectors re-embedded.
+    pub fn is_finer_than(self, other: Quantization) -> bool {
+        self.rank() < other.rank()
+    }
+
+    fn rank(self) -> u8 {
+        match self {
+            Quantization::None => 0,
+            Quantization::Int8 => 1,
+            Quantization::Binary => 2,
+        }
+    }
+
+    /// Reduce `vector` to this precision; `None` for full precision.
+    pub fn quantize(self, vector: &[f32]) -> Option<QuantizedVector> {
+        match self {
+            Quantization::None => None,
+            Quantization::Int8 => {
+                let (scale, values) = quantize_int8(vector);
+                Some(QuantizedVector::Int8 { scale, values })
+            }
+            Quantization::Binary => Some(QuantizedVector::Binary {
+                dimensions: vector.len(),
+                bits: sign_bits(vector),
+            }),
+        }
+    }
+}
+
+impl std::fmt::Display for Quantization {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            Quantization::None => "none",
+            Quantization::Int8 => "int8",
+            Quantization::Binary => "binary",
+        })
+    }
+}
+
+/// An embedding vector as a quantized sidecar stores it.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum QuantizedVector {
+    /// Component `i` is `values[i] as f32 * scale`
+    Int8 { scale: f32, values: Vec<i8> },
+    /// Bit `i % 64` of `bits[i / 64]` is set when component `i` is >= 0
+    Binary { dimensions: usize, bits: Vec<u64> },
+}
+
+impl QuantizedVector {
+    pub fn quantization(&self) -> Quantization {
+        match self {
+            QuantizedVector::Int8 { .. } => Quantization::Int8,
+            QuantizedVector::Binary { .. } => Quantization::Binary,
+        }
+    }
+
+    /// The float vector this approximates. Binary vectors become unit-length
+    /// vectors of equal-magnitude components with the stored signs.
+    pub fn dequantize(&self) -> Vec<f32> {
+        match self {
+            QuantizedVector::Int8 { scale, values } => {
+                values.iter().map(|&v| f32::from(v) * scale).collect()
+            }
+            QuantizedVector::Binary { dimensions, bits } => {
+                let magnitude = 1.0 / (*dimensions.max(&1) as f32).sqrt();
+                (0..*dimensions)
+                    .map(|i| {
+                        if bits[i / 64] >> (i % 64) & 1 == 1 {
+                            magnitude
+                        } else {
+                            -magnitude
+                        }
+                    })
+                    .collect()
+            }
+        }
+    }
+}
+
+/// A query vector prepared once for scoring against quantized vectors of
+/// any kind.
+#[derive(Debug, Clone)]
+pub struct QuantizedQuery {
+    int8: Vec<i8>,
+    int8_norm: f32,
+    bits: Vec<u64>,
+    dimensions: usize,
+}
+
+impl QuantizedQuery {
+    pub fn new(query: &[f32]) -> Self {
+        let (_, int8) = quantize_int8(query);
+        Self {
+            int8_norm: int8_norm(&int8),
+            int8,
+            bits: sign_bits(query),
+            dimensions: query.len(),
+        }
+    }
+
+    /// Similarity on the cosine scale, -1 to 1. For int8 vectors it is the
+    /// cosine of the quantized vectors, computed in integers (the scales
+    /// cancel); for binary ones `1 - 2 * hamming / dimensions`, the cosine of
+    /// the two sign vectors. Vectors of another dimension score 0.
+    pub fn similarity(&self, vector: &QuantizedVector) -> f32 {
+        match vector {
+            QuantizedVector::Int8 { values, .. } => {
+                if values.len() != self.int8.len() {
+                    return 0.0;
+                }
+                let dot: i32 = self
+                    .int8
+                    .iter()
+                    .zip(values)
+                    .map(|(&a, &b)| i32::from(a) * i32::from(b))
+                    .sum();
+                let norm = self.int8_norm * int8_norm(values);
+                if norm == 0.0 { 0.0 } else { dot as f32 / norm }
+            }
+            QuantizedVector::Binary { dimensions, bits } => {
+                if *dimensions != self.dimensions || *dimensions == 0 {
+                    return 0.0;
+                }
+                let hamming: u32 = self
+                    .bits
+                    .iter()
+                    .zip(bits)
+                    .map(|(a, b)| (a ^ b).count_ones())
+                    .sum();
+                1.0 - 2.0 * hamming as f32 / *dimensions as f32
+            }
+        }
+    }
+}
+
+/// Symmetric int8 quantization: the largest magnitude maps to 127.
+fn quantize_int8(vector: &[f32]) -> (f32, Vec<i8>) {
+    let max = vector.iter().fold(0.0f32, |max, v| max.max(v.abs()));
+    if max == 0.0 || !max.is_finite() {
+        return (0.0, vec![0; vector.len()]);
+    }
+    let scale = max / 127.0;
+    let values = vector
+        .iter()
+        .map(|v| (v / scale).round().clamp(-127.0, 127.0) as i8)
+        .collect();
+    (scale, values)
+}
+
+fn int8_norm(values: &[i8]) -> f32 {
+    (values.iter().map(|&v| i32::from(v).pow(2)).sum::<i32>() as f32).sqrt()
+}
+
+fn sign_bits(vector: &[f32]) -> Vec<u64> {
+    let mut bits = vec![0u64; vector.len().div_ceil(64)];
+    for (i, v) in vector.iter().enumerate() {
+        if *v >= 0.0 {
+            bits[i / 64] |= 1 << (i % 64);
+        }
+    }
+    bits
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sidecar::{SidecarEncoding, load_index_entry, save_index_entry};
+    use crate::{ChunkEntry, IndexEntry};
+    use ck_core::{FileMetadata, Span};
+    use std::collections::HashSet;
+    use std::path::PathBuf;
+    use tempfile::TempDir;
+
+    const DIMENSIONS: usize = 384;
+
+    /// Deterministic values in -1..1 (a 64-bit LCG), so the fixture needs
+    /// no random-number crate
+    struct Lcg(u64);
+
+    impl Lcg {
+        fn next(&mut self) -> f32 {
+            self.0 = self
+                .0
+                .wrapping_mul(6364136223846793005)
+                .wrapping_add(1442695040888963407);
+            (self.0 >> 40) as f32 / (1u64 << 23) as f32 - 1.0
+        }
+
+        fn vector(&mut self) -> Vec<f32> {
+            (0..DIMENSIONS).map(|_| self.next()).collect()
+        }
+    }
+
+    /// 20 topics of 20 chunks each: every chunk is its topic's direction plus
+    /// noise, as embeddings of related code cluster together. Ten nearest
+    /// out of twenty close ones is a fine-grained ranking to get right.
+    fn corpus(rng: &mut Lcg) -> (Vec<Vec<f32>>, Vec<Vec<f32>>) {
+        let topics: Vec<Vec<f32>> = (0..20).map(|_| rng.vector()).collect();
+        let chunks = topics
+            .iter()
+            .flat_map(|topic| (0..20).map(|_| topic.clone()).collect::<Vec<_>>())
+            .map(|topic| {
+                let noise = rng.vector();
+                topic.iter().zip(noise).map(|(t, n)| t + 0.8 * n).collect()
+            })
+            .collect();
+        (topics, chunks)
+    }
+
+    fn cosine(a: &[f32], b: &[f32]) -> f32 {
+        let dot: f32 = a.iter().zip(b).map(|(x, y)| x * y).sum();
+        let norm = |v: &[f32]| v.iter().map(|x| x * x).sum::<f32>().sqrt();
+        dot / (norm(a) * norm(b))
+    }
+
+    fn top_k(scores: impl Iterator<Item = f32>, k: usize) -> HashSet<usize> {
+        let mut ranked: Vec<(usize, f32)> = scores.enumerate().collect();
+        ranked.sort_by(|a, b| b.1.total_cmp(&a.1).then(a.0.cmp(&b.0)));
+        ranked.into_iter().take(k).map(|(i, _)| i).collect()
+    }
+
+    fn entry(chunks: &[Vec<f32>]) -> IndexEntry {
+        IndexEntry {
+            metadata: FileMetadata {
+                path: PathBuf::from("./src/topics.rs"),
+                hash: "abc123".to_string(),
+                last_modified: 1_700_000_000,
+                size: 160_000,
+            },
+            chunks: chunks
+                .iter()
+                .enumerate()
+                .map(|(i, embedding)| ChunkEntry {
+                    span: Span {
+                        byte_start: i * 400,
+                        byte_end: (i + 1) * 400,
+                        line_start: i * 12 + 1,
+                        line_end: (i + 1) * 12,
+                    },
+                    embedding: Some(embedding.clone()),
+                    chunk_type: Some("function".to_string()),
+                    breadcrumb: Some(format!("topic_{}", i / 20)),
+                    ancestry: None,
+                    byte_length: Some(400),
+                    estimated_tokens: Some(100),
+                    leading_trivia: None,
+                    trailing_trivia: None,
+                    chunk_hash: None,
+                    quantized: None,
+                })
+                .collect(),
+            embedding_model: Some("BAAI/bge-small-en-v1.5".to_string()),
+            embedding_dimensions: Some(DIMENSIONS),
+            generated: None,
+        }
+    }
+
+    #[test]
+    fn test_quantized_sidecars_shrink_and_keep_recall() {
+        let mut rng = Lcg(42);
+        let (topics, chunks) = corpus(&mut rng);
+        let temp_dir = TempDir::new().unwrap();
+        let save = |quantization: Quantization| {
+            let path = temp_dir.path().join(format!("{quantization}.rs.ck"));
+            let encoding = SidecarEncoding {
+                compress: false,
+                quantization,
+            };
+            save_index_entry(&path, &entry(&chunks), encoding).unwrap();
+            let size = std::fs::metadata(&path).unwrap().len();
+            (size, load_index_entry(&path).unwrap())
+        };
+
+        let (full_size, _) = save(Quantization::None);
+        let (int8_size, int8) = save(Quantization::Int8);
+        let (binary_size, binary) = save(Quantization::Binary);
+        // Chunk metadata doesn't shrink, so the files do a little less than
+        // the vectors
+        assert!(
+            int8_size * 100 < full_size * 32,
+            "int8 {int8_size} vs {full_size} bytes"
+        );
+        assert!(
+            binary_size * 100 < full_size * 12,
+            "binary {binary_size} vs {full_size} bytes"
+        );
+
+        // Recall@10 against the full-precision ranking, for queries that are
+        // perturbed topic directions
+        let mut recall = [0usize; 2];
+        let mut total = 0;
+        for topic in &topics {
+            let noise = rng.vector();
+            let query: Vec<f32> = topic.iter().zip(noise).map(|(t, n)| t + 0.5 * n).collect();
+            let prepared = QuantizedQuery::new(&query);
+            let exact = top_k(chunks.iter().map(|c| cosine(&query, c)), 10);
+            for (hits, entry) in recall.iter_mut().zip([&int8, &binary]) {
+                let found = top_k(
+                    entry
+                        .chunks
+                        .iter()
+                        .map(|c| prepared.similarity(c.quantized.as_ref().unwrap())),
+                    10,
+                );
+                *hits += exact.intersection(&found).count();
+            }
+            total += exact.len();
+        }
+        let [int8_recall, binary_recall] = recall.map(|hits| hits as f32 / total as f32);
+        // Measured at 0.99 and 0.65: int8 barely changes the ranking, binary
+        // keeps the right neighborhood but not the order within it
+        assert!(int8_recall >= 0.95, "int8 recall@10 {int8_recall}");
+        assert!(binary_recall >= 0.5, "binary recall@10 {binary_recall}");
+
+        // Loaded vectors are dequantized for everything else that reads them
+        let original = &chunks[7];
+        let dequantized = int8.chunks[7].embedding.as_ref().unwrap();
+        assert!(cosine(original, dequantized) > 0.999);
+        let signs = binary.chunks[7].embedding.as_ref().unwrap();
+        assert!(
+            original
+                .iter()
+                .zip(signs)
+                .all(|(o, s)| (*o >= 0.0) == (*s > 0.0))
+        );
+    }
+
+    #[test]
+    fn test_requantizing_dequantized_vectors_is_lossless() {
+        let vector = Lcg(7).vector();
+        for quantization in [Quantization::Int8, Quantization::Binary] {
+            let quantized = quantization.quantize(&vector).unwrap();
+            assert_eq!(
+                quantization.quantize(&quantized.dequantize()),
+                Some(quantized)
+            );
+        }
+        assert!(Quantization::None.is_finer_than(Quantization::Int8));
+        assert!(!Quantization::Binary.is_finer_than(Quantization::Int8));
+    }
+}
@@ -0,0 +1,500 @@
+//! Reading and writing `.ck` sidecar files.
+//!
+//! A sidecar begins with the bytes `CKSC` and the index format it was written
+//! in ([`INDEX_FORMAT_VERSION`]), followed by a bincode-encoded
+//! [`IndexEntry`]. Sidecars from before the format was recorded have no
+//! header and are decoded by trying the layouts older releases wrote.
+//!
+//! With `--compress` the
+//! encoding is wrapped in a zstd stream behind a short header: the bytes
+//! `CKZS` and a format version. The header cannot begin a plain sidecar,
+//! whose first 8 bytes are the length of the file path, so readers tell the
+//! two apart by looking, and an index can hold both while it is converted.
+//!
+//! With `--quantize` the (possibly compressed) payload is instead `CKQV`, a
+//! format version, and the bincode encoding of the entry with its vectors
+//! taken out, followed by the quantized vectors, one per chunk.
+//!
+//! Writes that fail with an error that tends to clear up by itself (a file
+//! held open by a virus scanner, a timeout on a network share) are retried a
+//! few times with a growing delay before the sidecar is given up on.
+
+use anyhow::Result;
+use std::fs;
+use std::io::{self, BufRead, BufReader, Read, Write};
+use std::path::Path;
+use std::time::Duration;
+
+use super::{
+    INDEX_FORMAT_VERSION, IndexEntry, IndexEntryV2, LegacyIndexEntry, Quantization,
+    QuantizedVector, atomic_write,
+};
+
+const SIDECAR_MAGIC: &[u8; 4] = b"CKSC";
+const ZSTD_MAGIC: &[u8; 4] = b"CKZS";
+const ZSTD_FORMAT_VERSION: u8 = 1;
+/// zstd's default level; higher levels gain little on embedding vectors
+const ZSTD_LEVEL: i32 = 3;
+const QUANTIZED_MAGIC: &[u8; 4] = b"CKQV";
+const QUANTIZED_FORMAT_VERSION: u8 = 1;
+
+/// How sidecars of an index are written, from its manifest.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub(crate) struct SidecarEncoding {
+    pub compress: bool,
+    pub quantization: Quantization,
+}
+
+/// Attempts at writing a sidecar before giving up on it
+const WRITE_ATTEMPTS: u32 = 4;
+/// Wait before the first retry of a failed write; doubled for each one after
+const WRITE_RETRY_DELAY: Duration = Duration::from_millis(50);
+
+/// Puts a sidecar's bytes at its path. Sidecars are written through
+/// [`AtomicWriter`]; tests substitute writers that fail.
+pub(crate) trait SidecarWriter {
+    fn write(&self, path: &Path, data: &[u8]) -> Result<()>;
+}
+
+/// Writes with [`atomic_write`], so a reader never sees half a sidecar.
+pub(crate) struct AtomicWriter;
+
+impl SidecarWriter for AtomicWriter {
+    fn write(&self, path: &Path, data: &[u8]) -> Result<()> {
+        atomic_write(path, data)
+    }
+}
+
+pub(crate) fn save_index_entry(
+    path: &Path,
+    entry: &IndexEntry,
+    encoding: SidecarEncoding,
+) -> Result<()> {
+    write_with_retry(&AtomicWriter, path, &encode_index_entry(entry, encoding)?)
+}
+
+/// Write `data` to `path` with `writer`, retrying transient failures (see
+/// [`is_transient`]) up to [`WRITE_ATTEMPTS`] times in all.
+pub(crate) fn write_with_retry(
+    writer: &impl SidecarWriter,
+    path: &Path,
+    data: &[u8],
+) -> Result<()> {
+    let mut delay = WRITE_RETRY_DELAY;
+    let mut attempt = 1;
+    loop {
+        match writer.write(path, data) {
+            Err(err) if attempt < WRITE_ATTEMPTS && is_transient(&err) => {
+                tracing::warn!(
+                    "Writing {} failed ({err:#}); retrying in {delay:?} (attempt {attempt} of {WRITE_ATTEMPTS})",
+                    path.display()
+                );
+                std::thread::sleep(delay);
+                delay *= 2;
+                attempt += 1;
+            }
+            result => return result,
+        }
+    }
+}
+
+/// Whether a failed write is worth trying again: interrupted or timed out,
+/// a busy or locked file, a stale network file handle. Windows reports a
+/// file another process holds open as a sharing or lock violation, or as
+/// access denied while a scanner has it.
+fn is_transient(err: &anyhow::Error) -> bool {
+    err.chain()
+        .filter_map(|cause| {
+            cause.downcast_ref::<io::Error>().or_else(|| {
+                cause
+                    .downcast_ref::<tempfile::PersistError>()
+                    .map(|err| &err.error)
+            })
+        })
+        .any(|err| {
+            matches!(
+                err.kind(),
+                io::ErrorKind::Interrupted
+                    | io::ErrorKind::WouldBlock
+                    | io::ErrorKind::TimedOut
+                    | io::ErrorKind::ResourceBusy
+                    | io::ErrorKind::StaleNetworkFileHandle
+            ) || (cfg!(windows)
+                && (err.kind() == io::ErrorKind::PermissionDenied
+                    // ERROR_SHARING_VIOLATION, ERROR_LOCK_VIOLATION
+                    || matches!(err.raw_os_error(), Some(32 | 33))))
+        })
+}
+
+/// The bytes [`save_index_entry`] would write for `entry`.
+pub(crate) fn encode_index_entry(entry: &IndexEntry, encoding: SidecarEncoding) -> Result<Vec<u8>> {
+    let mut data = SIDECAR_MAGIC.to_vec();
+    data.push(INDEX_FORMAT_VERSION as u8);
+    if encoding.compress {
+        let mut header = data;
+        header.extend_from_slice(ZSTD_MAGIC);
+        header.push(ZSTD_FORMAT_VERSION);
+        let mut encoder = zstd::stream::write::Encoder::new(header, ZSTD_LEVEL)?;
+        write_payload(&mut encoder, entry, encoding.quantization)?;
+        Ok(encoder.finish()?)
+    } else {
+        write_payload(&mut data, entry, encoding.quantization)?;
+        Ok(data)
+    }
+}
+
+/// The [`INDEX_FORMAT_VERSION`] the sidecar at `path` was written in; 1 for
+/// sidecars without a header.
+pub(crate) fn sidecar_format_version(path: &Path) -> Result<u32> {
+    let mut header = Vec::with_capacity(SIDECAR_MAGIC.len() + 1);
+    fs::File::open(path)?
+        .take(SIDECAR_MAGIC.len() as u64 + 1)
+        .read_to_end(&mut header)?;
+    Ok(match header.strip_prefix(SIDECAR_MAGIC) {
+        Some([version]) => u32::from(*version),
+        _ => 1,
+    })
+}
+
+fn write_payload(
+    writer: &mut impl Write,
+    entry: &IndexEntry,
+    quantization: Quantization,
+) -> Result<()> {
+    if quantization == Quantization::None {
+        return Ok(bincode::serialize_into(writer, entry)?);
+    }
+    let mut stripped = entry.clone();
+    let vectors: Vec<Option<QuantizedVector>> = stripped
+        .chunks
+        .iter_mut()
+        .map(|chunk| {
+            let embedding = chunk.embedding.take()?;
+            // Chunks kept from a coarser encoding are already at this precision
+            match chunk.quantized.take() {
+                Some(quantized) if quantized.quantization() == quantization => Some(quantized),
+                _ => quantization.quantize(&embedding),
+            }
+        })
+        .collect();
+    writer.write_all(QUANTIZED_MAGIC)?;
+    writer.write_all(&[QUANTIZED_FORMAT_VERSION])?;
+    bincode::serialize_into(&mut *writer, &stripped)?;
+    bincode::serialize_into(writer, &vectors)?;
+    Ok(())
+}
+
+/// Decode an entry laid out as sidecar format `version` wrote it.
+fn deserialize_entry(reader: impl Read, version: u32) -> bincode::Result<IndexEntry> {
+    if version >= 3 {
+        bincode::deserialize_from(reader)
+    } else {
+        bincode::deserialize_from::<_, IndexEntryV2>(reader).map(IndexEntry::from)
+    }
+}
+
+/// Decode a quantized payload (after its header), dequantizing each vector
+/// into `embedding` and keeping the quantized form alongside.
+fn read_quantized(mut reader: impl Read, path: &Path, format: u32) -> Result<IndexEntry> {
+    let mut version = [0u8];
+    reader.read_exact(&mut version)?;
+    if version[0] != QUANTIZED_FORMAT_VERSION {
+        return Err(anyhow::anyhow!(
+            "Unsupported quantized sidecar format {} in {}; it was written by a newer ck",
+            version[0],
+            path.display()
+        ));
+    }
+    let mut entry = deserialize_entry(&mut reader, format)?;
+    let vectors: Vec<Option<QuantizedVector>> = bincode::deserialize_from(reader)?;
+    for (chunk, quantized) in entry.chunks.iter_mut().zip(vectors) {
+        chunk.embedding = quantized.as_ref().map(QuantizedVector::dequantize);
+        chunk.quantized = quantized;
+    }
+    Ok(entry)
+}
+
+/// Load a sidecar in either encoding. Compressed sidecars are decoded as
+/// they are read, so only the decoded entry is held in memory.
+pub fn load_index_entry(path: &Path) -> Result<IndexEntry> {
+    let mut reader = BufReader::new(fs::File::open(path)?);
+    let header = reader.fill_buf()?;
+    let versioned = header.starts_with(SIDECAR_MAGIC);
+    // Headerless sidecars hold the format 2 layout or the legacy one before it
+    let mut format = 2;
+    if versioned {
+        let version = header.get(SIDECAR_MAGIC.len()).copied();
+        match version {
+            Some(version) if u32::from(version) <= INDEX_FORMAT_VERSION => {
+                format = u32::from(version);
+            }
+            _ => {
+                return Err(anyhow::anyhow!(
+                    "Unsupported sidecar format {:?} in {}; it was written by a newer ck. Upgrade ck or run `ck --reindex`",
+                    version,
+                    path.display()
+                ));
+            }
+        }
+        reader.consume(SIDECAR_MAGIC.len() + 1);
+    }
+    let header = reader.fill_buf()?;
+    if header.starts_with(ZSTD_MAGIC) {
+        let version = header.get(ZSTD_MAGIC.len()).copied();
+        if version != Some(ZSTD_FORMAT_VERSION) {
+            return Err(anyhow::anyhow!(
+                "Unsupported compressed sidecar format {:?} in {}; it was written by a newer ck",
+                version,
+                path.display()
+            ));
+        }
+        reader.consume(ZSTD_MAGIC.len() + 1);
+        let mut decoder = BufReader::new(zstd::stream::read::Decoder::with_buffer(reader)?);
+        if decoder.fill_buf()?.starts_with(QUANTIZED_MAGIC) {
+            decoder.consume(QUANTIZED_MAGIC.len());
+            return read_quantized(decoder, path, format);
+        }
+        return Ok(deserialize_entry(decoder, format)?);
+    }
+
+    let mut data = Vec::new();
+    reader.read_to_end(&mut data)?;
+    if let Some(quantized) = data.strip_prefix(QUANTIZED_MAGIC) {
+        return read_quantized(quantized, path, format);
+    }
+    match deserialize_entry(data.as_slice(), format) {
+        Ok(entry) => Ok(entry),
+        Err(err) if versioned => Err(err.into()),
+        Err(_) => {
+            let legacy: LegacyIndexEntry = bincode::deserialize(&data).map_err(|err| {
+                anyhow::anyhow!(
+                    "Sidecar {} was written by an older ck in a format this one can't read ({err}); run `ck --reindex` to rebuild the index",
+                    path.display()
+                )
+            })?;
+            Ok(IndexEntry {
+                metadata: legacy.metadata,
+                chunks: legacy.chunks,
+                embedding_model: None,
+                embedding_dimensions: None,
+                generated: None,
+            })
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ChunkEntry;
+    use ck_core::{FileMetadata, Span};
+    use std::path::PathBuf;
+    use tempfile::TempDir;
+
+    /// A 40-chunk file embedded with a 384-dimension model, the shape of a
+    /// typical source file's sidecar
+    fn fixture() -> IndexEntry {
+        let chunks = (0..40)
+            .map(|i| ChunkEntry {
+                span: Span {
+                    byte_start: i * 400,
+                    byte_end: (i + 1) * 400,
+                    line_start: i * 12 + 1,
+                    line_end: (i + 1) * 12,
+                },
+                embedding: Some(
+                    (0..384)
+                        .map(|d| ((i * 384 + d) as f32 * 0.37).sin() * 0.08)
+                        .collect(),
+                ),
+                chunk_type: Some("function".to_string()),
+                breadcrumb: Some(format!("Parser::parse_item_{i}")),
+                ancestry: Some(vec!["Parser".to_string()]),
+                byte_length: Some(400),
+                estimated_tokens: Some(100),
+                leading_trivia: Some(vec![format!("/// Parses item {i}")]),
+                trailing_trivia: None,
+                chunk_hash: Some(blake3::hash(&i.to_le_bytes()).to_hex().to_string()),
+                quantized: None,
+            })
+            .collect();
+        IndexEntry {
+            metadata: FileMetadata {
+                path: PathBuf::from("./src/parser.rs"),
+                hash: "abc123".to_string(),
+                last_modified: 1_700_000_000,
+                size: 16_000,
+            },
+            chunks,
+            embedding_model: Some("BAAI/bge-small-en-v1.5".to_string()),
+            embedding_dimensions: Some(384),
+            generated: Some(false),
+        }
+    }
+
+    #[test]
+    fn test_compressed_round_trip_and_size() {
+        let temp_dir = TempDir::new().unwrap();
+        let plain = temp_dir.path().join("plain.rs.ck");
+        let compressed = temp_dir.path().join("compressed.rs.ck");
+        let entry = fixture();
+        save_index_entry(&plain, &entry, SidecarEncoding::default()).unwrap();
+        let encoding = SidecarEncoding {
+            compress: true,
+            ..Default::default()
+        };
+        save_index_entry(&compressed, &entry, encoding).unwrap();
+
+        for path in [&plain, &compressed] {
+            let loaded = load_index_entry(path).unwrap();
+            assert_eq!(loaded.metadata.path, entry.metadata.path);
+            assert_eq!(loaded.chunks.len(), 40);
+            assert_eq!(loaded.chunks[7].embedding, entry.chunks[7].embedding);
+            assert_eq!(loaded.chunks[7].breadcrumb, entry.chunks[7].breadcrumb);
+            assert_eq!(loaded.embedding_dimensions, Some(384));
+        }
+
+        let plain_size = fs::metadata(&plain).unwrap().len();
+        let compressed_size = fs::metadata(&compressed).unwrap().len();
+        assert!(
+            compressed_size * 10 < plain_size * 9,
+            "{compressed_size} compressed vs {plain_size} plain bytes"
+        );
+    }
+
+    #[test]
+    fn test_quantized_sidecars_round_trip_compressed_or_not() {
+        let temp_dir = TempDir::new().unwrap();
+        let entry = fixture();
+        for compress in [false, true] {
+            let sidecar = temp_dir.path().join(format!("{compress}.rs.ck"));
+            let encoding = SidecarEncoding {
+                compress,
+                quantization: Quantization::Int8,
+            };
+            save_index_entry(&sidecar, &entry, encoding).unwrap();
+            let loaded = load_index_entry(&sidecar).unwrap();
+            assert_eq!(loaded.chunks.len(), 40);
+            assert_eq!(loaded.chunks[7].breadcrumb, entry.chunks[7].breadcrumb);
+            let quantized = loaded.chunks[7].quantized.as_ref().unwrap();
+            assert_eq!(quantized.quantization(), Quantization::Int8);
+            assert_eq!(
+                loaded.chunks[7].embedding.as_ref().unwrap().len(),
+                entry.chunks[7].embedding.as_ref().unwrap().len()
+            );
+        }
+    }
+
+    #[test]
+    fn test_unknown_format_version_is_rejected() {
+        let temp_dir = TempDir::new().unwrap();
+        let sidecar = temp_dir.path().join("future.rs.ck");
+        fs::write(&sidecar, b"CKZS\x09rest").unwrap();
+        let err = load_index_entry(&sidecar).unwrap_err();
+        assert!(err.to_string().contains("newer ck"));
+
+        fs::write(&sidecar, b"CKSC\x09rest").unwrap();
+        let err = load_index_entry(&sidecar).unwrap_err();
+        assert!(err.to_string().contains("newer ck"));
+    }
+
+    #[test]
+    fn test_generated_flag_round_trips_and_format_2_sidecars_leave_it_unknown() {
+        let temp_dir = TempDir::new().unwrap();
+        let sidecar = temp_dir.path().join("api.pb.go.ck");
+        let mut entry = fixture();
+        entry.generated = Some(true);
+        for compress in [false, true] {
+            let encoding = SidecarEncoding {
+                compress,
+                quantization: Quantization::Int8,
+            };
+            save_index_entry(&sidecar, &entry, encoding).unwrap();
+            assert_eq!(load_index_entry(&sidecar).unwrap().generated, Some(true));
+        }
+
+        // Format 2 wrote the same fields, without the flag
+        let mut data = b"CKSC\x02".to_vec();
+        bincode::serialize_into(
+            &mut data,
+            &(
+                &entry.metadata,
+                &entry.chunks,
+                &entry.embedding_model,
+                entry.embedding_dimensions,
+            ),
+        )
+        .unwrap();
+        fs::write(&sidecar, data).unwrap();
+        let loaded = load_index_entry(&sidecar).unwrap();
+        assert_eq!(loaded.generated, None);
+        assert_eq!(loaded.chunks.len(), 40);
+        assert_eq!(loaded.embedding_dimensions, Some(384));
+    }
+
+    #[test]
+    fn test_headerless_sidecars_still_load_and_garbage_asks_for_reindex() {
+        let temp_dir = TempDir::new().unwrap();
+        let sidecar = temp_dir.path().join("old.rs.ck");
+        let entry = fixture();
+        fs::write(&sidecar, bincode::serialize(&entry).unwrap()).unwrap();
+        assert_eq!(sidecar_format_version(&sidecar).unwrap(), 1);
+        let loaded = load_index_entry(&sidecar).unwrap();
+        assert_eq!(loaded.chunks.len(), 40);
+
+        save_index_entry(&sidecar, &loaded, SidecarEncoding::default()).unwrap();
+        assert_eq!(
+            sidecar_format_version(&sidecar).unwrap(),
+            INDEX_FORMAT_VERSION
+        );
+
+        fs::write(&sidecar, b"\x03\x00\x00\x00\x00\x00\x00\x00abc").unwrap();
+        let err = load_index_entry(&sidecar).unwrap_err();
+        assert!(err.to_string().contains("ck --reindex"), "{err}");
+    }
+
+    /// Fails its first `failures` writes with `kind`, then writes for real.
+    struct FlakyWriter {
+        failures: u32,
+        kind: io::ErrorKind,
+        attempts: std::cell::Cell<u32>,
+    }
+
+    impl SidecarWriter for FlakyWriter {
+        fn write(&self, path: &Path, data: &[u8]) -> Result<()> {
+            self.attempts.set(self.attempts.get() + 1);
+            if self.attempts.get() <= self.failures {
+                return Err(io::Error::from(self.kind).into());
+            }
+            AtomicWriter.write(path, data)
+        }
+    }
+
+    #[test]
+    fn test_transient_write_failures_are_retried() {
+        let temp_dir = TempDir::new().unwrap();
+        let sidecar = temp_dir.path().join("lib.rs.ck");
+        let flaky = |failures, kind| FlakyWriter {
+            failures,
+            kind,
+            attempts: std::cell::Cell::new(0),
+        };
+
+        // Clears up before the attempts run out
+        let writer = flaky(2, io::ErrorKind::TimedOut);
+        write_with_retry(&writer, &sidecar, b"entry").unwrap();
+        assert_eq!(writer.attempts.get(), 3);
+        assert_eq!(fs::read(&sidecar).unwrap(), b"entry");
+
+        // Never clears up
+        let writer = flaky(u32::MAX, io::ErrorKind::ResourceBusy);
+        assert!(write_with_retry(&writer, &sidecar, b"other").is_err());
+        assert_eq!(writer.attempts.get(), WRITE_ATTEMPTS);
+
+        // Not worth retrying
+        let writer = flaky(1, io::ErrorKind::StorageFull);
+        assert!(write_with_retry(&writer, &sidecar, b"other").is_err());
+        assert_eq!(writer.attempts.get(), 1);
+        assert_eq!(fs::read(&sidecar).unwrap(), b"entry");
+    }
+}
@@ -0,0 +1,85 @@
+//! Git submodules met by the directory walk.
+//!
+//! A submodule has its own history, so by default the walk treats it as
+//! another project and doesn't descend into it (`--index-submodules` does).
+//! A directory is a submodule when the superproject's `.gitmodules` declares
+//! its path, or when its `.git` is a gitlink file (`gitdir: ...`) rather
+//! than a repository directory; an ordinary nested clone is walked as
+//! before. Files of an included submodule keep paths under the top
+//! repository, like any other file of the tree.
+
+use std::collections::HashSet;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// The submodules a walk rooted at some directory can run into.
+#[derive(Debug, Default)]
+pub(crate) struct Submodules {
+    /// Declared submodule paths, as the walk spells them (under its root)
+    declared: HashSet<PathBuf>,
+}
+
+impl Submodules {
+    /// The submodules `.gitmodules` declares in the repository holding
+    /// `root`, for a walk started at `root`.
+    pub(crate) fn for_walk(root: &Path) -> Self {
+        let Ok(canonical_root) = fs::canonicalize(root) else {
+            return Self::default();
+        };
+        let Some(top) = canonical_root
+            .ancestors()
+            .find(|dir| dir.join(".git").exists())
+        else {
+            return Self::default();
+        };
+        let Ok(gitmodules) = fs::read_to_string(top.join(".gitmodules")) else {
+            return Self::default();
+        };
+        let declared = declared_paths(&gitmodules)
+            .filter_map(|path| {
+                top.join(path)
+                    .strip_prefix(&canonical_root)
+                    .ok()
+                    .filter(|relative| !relative.as_os_str().is_empty())
+                    .map(|relative| root.join(relative))
+            })
+            .collect();
+        Self { declared }
+    }
+
+    /// Whether the directory `dir` is the working tree of a submodule.
+    pub(crate) fn contains(&self, dir: &Path) -> bool {
+        self.declared.contains(dir) || is_gitlink(&dir.join(".git"))
+    }
+}
+
+/// The `path = ...` values of a `.gitmodules` file.
+fn declared_paths(gitmodules: &str) -> impl Iterator<Item = &str> {
+    gitmodules.lines().filter_map(|line| {
+        let (key, value) = line.split_once('=')?;
+        (key.trim() == "path")
+            .then(|| value.trim().trim_matches('"'))
+            .filter(|path| !path.is_empty())
+    })
+}
+
+/// Whether `git` is a gitlink: a `.git` file pointing at a repository kept
+/// elsewhere, as checked-out submodules have.
+fn is_gitlink(git: &Path) -> bool {
+    git.is_file()
+        && fs::read_to_string(git).is_ok_and(|content| content.trim_start().starts_with("gitdir:"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn declared_paths_reads_path_entries() {
+        let gitmodules = "[submodule \"lib\"]\n\tpath = vendor/lib\n\turl = https://example.com/lib.git\n[submodule \"docs\"]\n\tpath = \"docs/theme\"\n";
+        assert_eq!(
+            declared_paths(gitmodules).collect::<Vec<_>>(),
+            ["vendor/lib", "docs/theme"]
+        );
+    }
+}
@@ -0,0 +1,172 @@
+//! `--count-tokens`: the indexing walk and chunking without the embedding,
+//! summed into token counts per language, to estimate what indexing a tree
+//! with a remote embedder would cost before doing it.
+//!
+//! Files are collected and chunked exactly as indexing would (same rules,
+//! same chunk overlap and strategy as the existing index, if any). Nothing
+//! under `.ck` is created or modified, so PDFs, whose text indexing extracts
+//! into a cache there, are listed as skipped rather than counted.
+
+use anyhow::Result;
+use rayon::prelude::*;
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+
+use ck_embed::TokenEstimator;
+
+use super::{collect_files, index_chunk_overlap, index_chunk_strategy, read_text};
+
+/// Totals for one language (`"text"` for files without one).
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+pub struct LanguageTokens {
+    pub language: String,
+    pub files: usize,
+    pub chunks: usize,
+    pub tokens: usize,
+}
+
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct TokenCount {
+    /// Model whose tokenizer (or chunk sizes) the counts are for
+    pub model: String,
+    /// Counted with the model's own tokenizer; otherwise estimated
+    pub exact: bool,
+    /// Per-language totals, most tokens first
+    pub languages: Vec<LanguageTokens>,
+    pub files: usize,
+    pub chunks: usize,
+    pub tokens: usize,
+    /// Files that would be indexed but couldn't be counted here
+    pub skipped: Vec<PathBuf>,
+}
+
+/// Walk `path` with indexing's rules, chunk every file for `model` and sum
+/// the chunks' tokens. Chunk overlap lines count, since they are embedded too.
+pub fn count_tokens(
+    path: &Path,
+    options: &ck_core::FileCollectionOptions,
+    model: &str,
+) -> Result<TokenCount> {
+    let overlap_lines = index_chunk_overlap(path);
+    let strategy = index_chunk_strategy(path);
+    let files = collect_files(path, options)?;
+
+    // Each file becomes a one-file total, or its path when it can't be counted
+    let counted: Vec<Result<LanguageTokens, PathBuf>> = files
+        .into_par_iter()
+        .map(|file| {
+            if ck_core::pdf::is_pdf_file(&file) {
+                return Err(file);
+            }
+            let lang = ck_core::Language::from_path(&file);
+            let chunks = read_text(&file).and_then(|content| {
+                ck_chunk::chunk_file_content(
+                    &file,
+                    &content,
+                    lang,
+                    Some(model),
+                    overlap_lines,
+                    strategy,
+                )
+            });
+            let Ok(chunks) = chunks else {
+                return Err(file);
+            };
+            Ok(LanguageTokens {
+                language: lang.map_or_else(|| "text".to_string(), |l| l.to_string()),
+                files: 1,
+                chunks: chunks.len(),
+                tokens: chunks
+                    .iter()
+                    .map(|chunk| TokenEstimator::count_tokens(&chunk.text, Some(model)))
+                    .sum(),
+            })
+        })
+        .collect();
+
+    let mut by_language: BTreeMap<String, LanguageTokens> = BTreeMap::new();
+    let mut count = TokenCount {
+        model: model.to_string(),
+        exact: TokenEstimator::exact_tokens("fn", model).is_some(),
+        ..Default::default()
+    };
+    for file in counted {
+        let file = match file {
+            Ok(file) => file,
+            Err(path) => {
+                count.skipped.push(path);
+                continue;
+            }
+        };
+        count.files += 1;
+        count.chunks += file.chunks;
+        count.tokens += file.tokens;
+        let entry = by_language
+            .entry(file.language.clone())
+            .or_insert_with(|| LanguageTokens {
+                language: file.language.clone(),
+                ..Default::default()
+            });
+        entry.files += 1;
+        entry.chunks += file.chunks;
+        entry.tokens += file.tokens;
+    }
+
+    count.languages = by_language.into_values().collect();
+    count
+        .languages
+        .sort_by(|a, b| b.tokens.cmp(&a.tokens).then(a.language.cmp(&b.language)));
+    count.skipped.sort();
+    Ok(count)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_count_tokens_sums_per_language() {
+        let temp_dir = TempDir::new().unwrap();
+        let root = temp_dir.path();
+        fs::write(root.join("a.rs"), "fn alpha() {\n    beta();\n}\n").unwrap();
+        fs::write(root.join("b.rs"), "fn beta() {}\n").unwrap();
+        fs::write(root.join("notes.txt"), "plain notes about the project\n").unwrap();
+        fs::write(root.join("manual.pdf"), "%PDF-1.4\n").unwrap();
+        fs::write(root.join("logo.png"), b"\x89PNG\x00\x00").unwrap();
+
+        let count = count_tokens(
+            root,
+            &ck_core::FileCollectionOptions::default(),
+            "nomic-embed-text-v1.5",
+        )
+        .unwrap();
+
+        assert_eq!(count.files, 3);
+        // PDFs are only collected by builds that can extract them
+        let skipped = if cfg!(feature = "pdf") {
+            vec![root.join("manual.pdf")]
+        } else {
+            Vec::new()
+        };
+        assert_eq!(count.skipped, skipped);
+        let rust = count
+            .languages
+            .iter()
+            .find(|l| l.language == "rust")
+            .unwrap();
+        assert_eq!(rust.files, 2);
+        assert!(rust.tokens > 0);
+        assert!(count.languages.iter().any(|l| l.language == "text"));
+        assert_eq!(
+            count.tokens,
+            count.languages.iter().map(|l| l.tokens).sum::<usize>()
+        );
+        assert!(
+            !root.join(".ck").exists(),
+            "counting must not create an index"
+        );
+    }
+}
@@ -1,6 +1,6 @@
 use anyhow::{Result, anyhow};
 use serde::{Deserialize, Serialize};
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap};
 use std::path::Path;
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -10,6 +10,35 @@ pub struct ModelConfig {
     pub dimensions: usize,
     pub max_tokens: usize,
     pub description: String,
+    /// Chunks sent to the model per embedding call unless
+    /// `--embed-batch-size` says otherwise
+    #[serde(default = "default_batch_size")]
+    pub batch_size: usize,
+    /// Largest batch the model is given; long-context models pad every input
+    /// in a batch to its longest, so memory grows quickly with the batch
+    #[serde(default = "default_max_batch_size")]
+    pub max_batch_size: usize,
+}
+
+pub const DEFAULT_BATCH_SIZE: usize = 32;
+pub const DEFAULT_MAX_BATCH_SIZE: usize = 256;
+
+fn default_batch_size() -> usize {
+    DEFAULT_BATCH_SIZE
+}
+
+fn default_max_batch_size() -> usize {
+    DEFAULT_MAX_BATCH_SIZE
+}
+
+impl ModelConfig {
+    /// The batch size indexing uses: `requested`, or the model's default,
+    /// clamped to what the model takes.
+    pub fn effective_batch_size(&self, requested: Option<usize>) -> usize {
+        requested
+            .unwrap_or(self.batch_size)
+            .clamp(1, self.max_batch_size.max(1))
+    }
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -30,6 +59,8 @@ impl Default for ModelRegistry {
                 dimensions: 384,
                 max_tokens: 512,
                 description: "Small, fast English embedding model".to_string(),
+                batch_size: 32,
+                max_batch_size: 256,
             },
         );
 
@@ -41,6 +72,8 @@ impl Default for ModelRegistry {
                 dimensions: 384,
                 max_tokens: 256,
                 description: "Lightweight English embedding model".to_string(),
+                batch_size: 64,
+                max_batch_size: 256,
             },
         );
 
@@ -54,6 +87,8 @@ impl Default for ModelRegistry {
                 max_tokens: 8192,
                 description: "High-quality English embedding model with large context window"
                     .to_string(),
+                batch_size: 8,
+                max_batch_size: 64,
             },
         );
 
@@ -66,6 +101,8 @@ impl Default for ModelRegistry {
                 max_tokens: 8192,
                 description: "Code-specific embedding model optimized for programming tasks"
                     .to_string(),
+                batch_size: 8,
+                max_batch_size: 64,
             },
         );
 
@@ -77,6 +114,8 @@ impl Default for ModelRegistry {
                 dimensions: 384,
                 max_tokens: 4096,
                 description: "Mixedbread xsmall embedding model (4k context, 384 dims) optimized for local semantic search".to_string(),
+                batch_size: 16,
+                max_batch_size: 128,
             },
         );
 
@@ -126,6 +165,26 @@ impl ModelRegistry {
         }
     }
 
+    /// Resolve a language → model mapping (`[language_models]` in
+    /// `.ck/config.toml`), keyed by the language's canonical name.
+    pub fn resolve_language_models(
+        &self,
+        mapping: &BTreeMap<String, String>,
+    ) -> Result<BTreeMap<String, (String, ModelConfig)>> {
+        mapping
+            .iter()
+            .map(|(language, model)| {
+                let language = language
+                    .parse::<ck_core::Language>()
+                    .map_err(|e| anyhow!("{e} in language_models"))?;
+                let resolved = self
+                    .resolve(Some(model))
+                    .map_err(|e| anyhow!("{e} (language_models.{language})"))?;
+                Ok((language.to_string(), resolved))
+            })
+            .collect()
+    }
+
     pub fn aliases(&self) -> Vec<String> {
         let mut keys = self.models.keys().cloned().collect::<Vec<_>>();
         keys.sort();
@@ -578,9 +578,50 @@ impl TuiApp {
             use_ckignore: true,
             full_section: false,
             hidden: false,
+            follow_links: false,
+            include_submodules: false,
+            max_depth: None,
+            no_generated: false,
             rerank: false,
             rerank_model: None,
+            rerank_top_k: None,
             embedding_model: None,
+            embed_batch_size: None,
+            force_hash: false,
+            jobs: None,
+            ignore_model_mismatch: false,
+            fuzzy: false,
+            bm25: ck_core::Bm25Params::default(),
+            proximity: 0.0,
+            match_on: ck_core::MatchOn::default(),
+            document: None,
+            split_identifiers: false,
+            include_globs: Vec::new(),
+            similar_to: None,
+            similar_by: ck_core::SimilarBy::File,
+            roots: Vec::new(),
+            no_stale: false,
+            snippet_lines: None,
+            threshold_percentile: None,
+            at_ref: None,
+            dedup: None,
+            no_auto_index: false,
+            ephemeral: false,
+            sort: ck_core::ResultSort::Score,
+            explain: false,
+            modified_since: None,
+            offset: 0,
+            exact: false,
+            ef_search: None,
+            max_matches_per_file: None,
+            max_count: None,
+            invert_match: false,
+            explicit_files: Vec::new(),
+            line_ranges: Default::default(),
+            cancel: None,
+            replace: None,
+            ascii_only: false,
+            deadline: None,
         };
 
         let progress_tx = self.progress_tx.clone();
@@ -413,8 +413,15 @@ pub fn chunk_file_live(file_path: &Path) -> Result<(Vec<String>, Vec<IndexedChun
 
     // Use model-aware chunking (same approach as --dump-chunks)
     let default_model = "nomic-embed-text-v1.5";
-    let chunks = ck_chunk::chunk_text_with_model(&content, detected_lang, Some(default_model))
-        .map_err(|err| format!("Failed to chunk file: {err}"))?;
+    let chunks = ck_chunk::chunk_file_content(
+        file_path,
+        &content,
+        detected_lang,
+        Some(default_model),
+        0,
+        ck_chunk::ChunkStrategy::SyntaxAware,
+    )
+    .map_err(|err| format!("Failed to chunk file: {err}"))?;
 
     // Convert chunks to IndexedChunkMeta format
     let chunk_metas = convert_chunks_to_meta(chunks);